@@ -0,0 +1,104 @@
+//! Benchmarks of the Prometheus exposition writers
+//!
+//! Renders the converted (cAdvisor) and raw metrics of 1, 50 and 250 mock
+//! sandboxes, as the aggregated `/metrics` render does:
+//!
+//! ```sh
+//! cargo bench --bench exposition
+//! ```
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+
+use kata_pulse::monitor::mock_sandboxes::MockSandboxes;
+use kata_pulse::utils::metrics_converter::cadvisor::{CadvisorMetrics, PrometheusFormat};
+use kata_pulse::utils::metrics_converter::config::{ConversionConfig, EnrichedLabels};
+use kata_pulse::utils::metrics_converter::create_converter;
+use kata_pulse::utils::prometheus_parser::{NameFilter, PrometheusMetrics};
+
+const SANDBOX_COUNTS: [usize; 3] = [1, 50, 250];
+
+/// Pod labels of the size seen on real nodes
+fn pod_labels(sandbox_id: &str) -> EnrichedLabels {
+    EnrichedLabels::new(
+        "0f6c9a6e-3b1d-4c59-9d7e-2a8f1e4b7c30",
+        format!("checkout-service-7d9f8b6c5d-{}", sandbox_id),
+        "production-payments",
+    )
+}
+
+/// Scraped and converted metrics of `count` mock sandboxes
+fn sandboxes(count: usize) -> Vec<(String, PrometheusMetrics, CadvisorMetrics)> {
+    let mock = MockSandboxes::new(count);
+    (0..count)
+        .map(|index| {
+            let sandbox_id = format!("mock-{:06}", index);
+            let (raw, _) = mock.scrape(&sandbox_id, &NameFilter::default()).unwrap();
+            let converted = create_converter(ConversionConfig::default(), pod_labels(&sandbox_id))
+                .convert_all(&raw)
+                .unwrap();
+            (sandbox_id, raw, converted)
+        })
+        .collect()
+}
+
+fn bench_cadvisor(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cadvisor");
+    for count in SANDBOX_COUNTS {
+        let sandboxes = sandboxes(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::new("to_prometheus_format", count),
+            &sandboxes,
+            |b, sandboxes| {
+                b.iter(|| {
+                    let mut output = String::new();
+                    for (sandbox_id, _, converted) in sandboxes {
+                        output.push_str(&converted.to_prometheus_format(Some(sandbox_id)));
+                    }
+                    black_box(output)
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("write_prometheus", count),
+            &sandboxes,
+            |b, sandboxes| {
+                let mut output = String::new();
+                b.iter(|| {
+                    output.clear();
+                    for (sandbox_id, _, converted) in sandboxes {
+                        converted.write_prometheus(&mut output, Some(sandbox_id));
+                    }
+                    black_box(output.len())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_raw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("raw");
+    for count in SANDBOX_COUNTS {
+        let sandboxes = sandboxes(count);
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::new("to_prometheus_format", count),
+            &sandboxes,
+            |b, sandboxes| {
+                b.iter(|| {
+                    let mut output = String::new();
+                    for (_, raw, _) in sandboxes {
+                        output.push_str(&raw.to_prometheus_format(None));
+                    }
+                    black_box(output)
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_cadvisor, bench_raw);
+criterion_main!(benches);
@@ -0,0 +1,158 @@
+//! Benchmarks of the scrape pipeline hot paths on shim payloads
+//!
+//! Parses, converts (each converter family and the whole conversion) and
+//! renders the shim payloads of the golden tests in `tests/fixtures/shim/`
+//! and the larger payloads in `benches/fixtures/`:
+//!
+//! ```sh
+//! cargo bench --bench pipeline
+//! cargo bench --bench pipeline -- convert/cpu
+//! ```
+//!
+//! The larger fixtures follow the payload of the 2 vCPU Cloud Hypervisor
+//! sandbox in `tests/fixtures/shim/clh.prom` at 8 and 64 vCPUs, with more
+//! interfaces and disks. To bench a payload captured on a node, save it next
+//! to them:
+//!
+//! ```sh
+//! kata-pulse scrape <sandbox-id> --raw > benches/fixtures/<name>.prom
+//! ```
+
+use anyhow::Result;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+use std::path::Path;
+
+use kata_pulse::utils::metrics_converter::cadvisor::PrometheusFormat;
+use kata_pulse::utils::metrics_converter::config::{ConversionConfig, EnrichedLabels};
+use kata_pulse::utils::metrics_converter::{create_converter, MetricsConverter};
+use kata_pulse::utils::prometheus_parser::PrometheusMetrics;
+
+const SANDBOX_ID: &str = "0b5d8e3c7f1a4b6e9d2c5f8a1e4b7d0c3f6a9e2d5b8c1f4a7e0d3b6c9f2a5e8d";
+
+/// A fixture payload, parsed
+struct Fixture {
+    name: String,
+    payload: String,
+    metrics: PrometheusMetrics,
+}
+
+/// Directories of the benchmarked payloads
+const FIXTURE_DIRS: &[&str] = &["tests/fixtures/shim", "benches/fixtures"];
+
+/// All payloads in [`FIXTURE_DIRS`], smallest first
+fn fixtures() -> Vec<Fixture> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut fixtures: Vec<Fixture> = FIXTURE_DIRS
+        .iter()
+        .flat_map(|dir| std::fs::read_dir(root.join(dir)).expect(dir))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "prom"))
+        .map(|path| {
+            let payload = std::fs::read_to_string(&path).unwrap();
+            let (metrics, errors) = PrometheusMetrics::parse_with_diagnostics(&payload);
+            assert!(errors.is_empty(), "{}: {:?}", path.display(), errors);
+            Fixture {
+                name: path.file_stem().unwrap().to_string_lossy().into_owned(),
+                payload,
+                metrics,
+            }
+        })
+        .collect();
+    fixtures.sort_by_key(|fixture| fixture.payload.len());
+    fixtures
+}
+
+fn converter() -> Box<dyn MetricsConverter> {
+    create_converter(
+        ConversionConfig::default(),
+        EnrichedLabels::new(
+            "0f6c9a6e-3b1d-4c59-9d7e-2a8f1e4b7c30",
+            "checkout-service-7d9f8b6c5d-x2x9k",
+            "production-payments",
+        ),
+    )
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for fixture in fixtures() {
+        group.throughput(Throughput::Bytes(fixture.payload.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(&fixture.name),
+            &fixture.payload,
+            |b, payload| b.iter(|| PrometheusMetrics::parse_with_diagnostics(black_box(payload))),
+        );
+    }
+    group.finish();
+}
+
+/// A converter family, as a function of the converter and the parsed payload
+type Family = (
+    &'static str,
+    fn(&dyn MetricsConverter, &PrometheusMetrics) -> Result<()>,
+);
+
+const FAMILIES: [Family; 10] = [
+    ("cpu", |c, m| c.convert_cpu(m).map(drop)),
+    ("memory", |c, m| c.convert_memory(m).map(drop)),
+    ("network", |c, m| c.convert_network(m).map(drop)),
+    ("disk", |c, m| c.convert_disk(m).map(drop)),
+    ("process", |c, m| c.convert_process(m).map(drop)),
+    ("shim", |c, m| c.convert_shim(m).map(drop)),
+    ("virtiofsd", |c, m| c.convert_virtiofsd(m).map(drop)),
+    ("overhead", |c, m| c.convert_overhead(m).map(drop)),
+    ("vmm", |c, m| c.convert_vmm(m).map(drop)),
+    ("all", |c, m| c.convert_all(m).map(drop)),
+];
+
+fn bench_convert(c: &mut Criterion) {
+    let fixtures = fixtures();
+    let converter = converter();
+    for (family, convert) in FAMILIES {
+        let mut group = c.benchmark_group(format!("convert/{}", family));
+        for fixture in &fixtures {
+            group.bench_with_input(
+                BenchmarkId::from_parameter(&fixture.name),
+                &fixture.metrics,
+                |b, metrics| b.iter(|| convert(converter.as_ref(), black_box(metrics)).unwrap()),
+            );
+        }
+        group.finish();
+    }
+}
+
+fn bench_render(c: &mut Criterion) {
+    let converter = converter();
+    let mut group = c.benchmark_group("render");
+    for fixture in fixtures() {
+        let converted = converter.convert_all(&fixture.metrics).unwrap();
+        let mut output = String::new();
+        group.bench_with_input(
+            BenchmarkId::new("cadvisor", &fixture.name),
+            &converted,
+            |b, converted| {
+                b.iter(|| {
+                    output.clear();
+                    converted.write_prometheus(&mut output, Some(SANDBOX_ID));
+                    black_box(output.len())
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("raw", &fixture.name),
+            &fixture.metrics,
+            |b, metrics| {
+                b.iter(|| {
+                    output.clear();
+                    metrics.write_prometheus(&mut output, None);
+                    black_box(output.len())
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse, bench_convert, bench_render);
+criterion_main!(benches);
@@ -0,0 +1,37 @@
+//! Mock shim - serve canned Kata metrics on a shim monitor socket
+//!
+//! Runs the mock shim of the integration tests standalone, to try kata-pulse
+//! without a Kata runtime:
+//!
+//! ```text
+//! cargo run --example mock_shim -- /tmp/sbs/sb-1/shim-monitor.sock [metrics.prom]
+//! ```
+//!
+//! Point the `sandboxes` storage path of the configuration file at `/tmp/sbs`.
+
+#[path = "../tests/support/mock_shim.rs"]
+#[allow(dead_code)]
+mod mock_shim;
+
+use mock_shim::{MockShim, Reply};
+use std::path::PathBuf;
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args_os().skip(1);
+    let socket = PathBuf::from(
+        args.next()
+            .ok_or_else(|| anyhow::anyhow!("usage: mock_shim <socket> [metrics file]"))?,
+    );
+    let reply = match args.next() {
+        Some(path) => Reply::Metrics(std::fs::read_to_string(path)?),
+        None => Reply::kata_metrics(),
+    };
+
+    let _ = std::fs::remove_file(&socket);
+    let _shim = MockShim::spawn(&socket, reply);
+    println!("Serving shim metrics on {}", socket.display());
+    tokio::signal::ctrl_c().await?;
+    let _ = std::fs::remove_file(&socket);
+    Ok(())
+}
@@ -0,0 +1,25 @@
+//! Fuzz target of `PrometheusMetrics::parse` on whole payloads
+//!
+//! Malformed lines must be reported, not panic. What the parser accepts must
+//! render and parse again, and the interned labels of dropped samples must
+//! be released like at the end of a collection cycle.
+
+#![no_main]
+
+use kata_pulse::utils::interner;
+use kata_pulse::utils::metrics_converter::cadvisor::PrometheusFormat;
+use kata_pulse::utils::prometheus_parser::PrometheusMetrics;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(content) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let (metrics, _errors) = PrometheusMetrics::parse_with_diagnostics(content);
+    let rendered = metrics.to_prometheus_format(None);
+    let _ = PrometheusMetrics::parse_with_diagnostics(&rendered);
+
+    drop(metrics);
+    interner::labels().purge_unused();
+});
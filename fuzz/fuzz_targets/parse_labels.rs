@@ -0,0 +1,18 @@
+//! Fuzz target of the label set parser of sample lines
+//!
+//! Accepted label sets must have non-empty label names.
+
+#![no_main]
+
+use kata_pulse::utils::prometheus_parser::fuzz_parse_labels;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    if let Ok(labels) = fuzz_parse_labels(input) {
+        assert!(labels.iter().all(|(key, _)| !key.is_empty()));
+    }
+});
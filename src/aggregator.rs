@@ -0,0 +1,536 @@
+//! Aggregator - one cluster-wide scrape target over the per-node instances
+//!
+//! Responsibilities:
+//! - Scrape `/metrics` and `/sandboxes` of a configured list of kata-pulse
+//!   instances every collection interval, all targets in parallel, with the
+//!   bearer token of a file re-read every round
+//! - Add a `node` label to every series (a `node` label of the target's own
+//!   output is kept as `exported_node`) and a `node` field to every sandbox
+//! - Merge the outputs into one `/metrics` and one `/sandboxes`, with
+//!   `kata_pulse_aggregate_target_up` and the scrape duration per target
+//!
+//! In this mode no local sandboxes are read: there is no CRI sync, shim
+//! scraping or push export. A target that fails a round contributes no series
+//! to it, like a failed scrape in Prometheus.
+
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use futures::future::join_all;
+use http_body_util::{BodyExt, Empty, Limited};
+use hyper::body::Bytes;
+use hyper::header::{AUTHORIZATION, USER_AGENT};
+use hyper::{Request, Uri};
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use serde_json::{Map, Value};
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::exporter::http::{connector, Connector};
+use crate::utils::interner;
+use crate::utils::metrics_converter::cadvisor::{write_escaped, PrometheusFormat};
+use crate::utils::prometheus_parser::{format_value, PrometheusMetrics};
+
+/// Default timeout of the scrape of one target
+pub const DEFAULT_AGGREGATE_TIMEOUT: &str = "5s";
+
+/// Largest response accepted from a target
+const MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+/// Label identifying the target of a series
+const NODE_LABEL: &str = "node";
+
+/// Name the `node` label of a target's own series is kept under
+const EXPORTED_NODE_LABEL: &str = "exported_node";
+
+/// A kata-pulse instance scraped by the aggregator
+#[derive(Clone, Debug, PartialEq)]
+pub struct AggregateTarget {
+    /// Value of the `node` label of its series
+    pub node: String,
+    /// Base URL of the instance, e.g. `http://10.0.0.1:8090`
+    pub url: String,
+}
+
+impl FromStr for AggregateTarget {
+    type Err = anyhow::Error;
+
+    /// Parse `node=url`, or `url` with the host of the URL as the node
+    fn from_str(s: &str) -> Result<Self> {
+        let (node, url) = match s.split_once('=') {
+            Some((node, url)) if !node.contains('/') => (Some(node.trim()), url.trim()),
+            _ => (None, s.trim()),
+        };
+        let uri: Uri = url
+            .parse()
+            .with_context(|| format!("invalid aggregate target URL {}", url))?;
+        let Some(host) = uri
+            .host()
+            .filter(|_| matches!(uri.scheme_str(), Some("http" | "https")))
+        else {
+            anyhow::bail!(
+                "aggregate target must be [node=]http(s)://host:port, got {}",
+                s
+            );
+        };
+        let node = node.unwrap_or(host);
+        if node.is_empty() {
+            anyhow::bail!("aggregate target {} has an empty node name", s);
+        }
+        Ok(AggregateTarget {
+            node: node.to_string(),
+            url: url.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+/// Settings of the aggregator mode
+#[derive(Clone, Debug)]
+pub struct AggregatorConfig {
+    /// Instances scraped
+    pub targets: Vec<AggregateTarget>,
+    /// Time between two scrape rounds
+    pub interval: Duration,
+    /// Timeout of the scrape of one target
+    pub timeout: Duration,
+    /// File holding the bearer token sent to the targets, if any
+    pub bearer_token_file: Option<PathBuf>,
+}
+
+impl AggregatorConfig {
+    /// Scrape `targets` every 60 seconds
+    pub fn new(targets: Vec<AggregateTarget>) -> Self {
+        AggregatorConfig {
+            targets,
+            interval: Duration::from_secs(60),
+            timeout: Duration::from_secs(5),
+            bearer_token_file: None,
+        }
+    }
+
+    /// Set the time between two scrape rounds
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the timeout of the scrape of one target
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send the bearer token held by this file to the targets
+    pub fn with_bearer_token_file(mut self, bearer_token_file: Option<PathBuf>) -> Self {
+        self.bearer_token_file = bearer_token_file;
+        self
+    }
+}
+
+/// What a target served in one round
+struct TargetScrape {
+    metrics: String,
+    sandboxes: String,
+}
+
+/// Merged outputs of the last round
+#[derive(Default)]
+pub struct AggregatedOutput {
+    /// Cluster-wide metrics in the Prometheus text format
+    pub metrics: String,
+    /// Cluster-wide sandbox listing as JSON
+    pub sandboxes: String,
+}
+
+/// Scrapes the per-node instances and serves their merged outputs
+pub struct Aggregator {
+    config: AggregatorConfig,
+    client: Client<Connector, Empty<Bytes>>,
+    output: ArcSwap<AggregatedOutput>,
+    /// Whether a round has completed
+    ready: AtomicBool,
+}
+
+impl Aggregator {
+    /// Create an aggregator of the targets of `config`
+    pub fn new(config: AggregatorConfig) -> Result<Self> {
+        if config.targets.is_empty() {
+            anyhow::bail!("the aggregator mode needs at least one target");
+        }
+        // All targets share a client; connector() refuses https without the `tls` feature
+        let mut connectors = Vec::new();
+        for target in &config.targets {
+            let uri: Uri = target.url.parse()?;
+            connectors.push(
+                connector(&uri)
+                    .with_context(|| format!("unsupported aggregate target {}", target.url))?,
+            );
+        }
+        let connector = connectors.swap_remove(0);
+        Ok(Aggregator {
+            config,
+            client: Client::builder(TokioExecutor::new()).build(connector),
+            output: ArcSwap::from_pointee(AggregatedOutput {
+                metrics: String::new(),
+                sandboxes: "[]".to_string(),
+            }),
+            ready: AtomicBool::new(false),
+        })
+    }
+
+    /// The settings of the aggregator
+    pub fn config(&self) -> &AggregatorConfig {
+        &self.config
+    }
+
+    /// Merged outputs of the last round
+    pub fn output(&self) -> Arc<AggregatedOutput> {
+        self.output.load_full()
+    }
+
+    /// Whether a round has completed, so the outputs cover the targets
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// Scrape the targets every interval until `cancel` fires
+    pub async fn run(&self, cancel: CancellationToken) {
+        info!(
+            targets = self.config.targets.len(),
+            interval = ?self.config.interval,
+            "Aggregating the metrics of kata-pulse instances"
+        );
+        let mut ticker = tokio::time::interval(self.config.interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = ticker.tick() => self.collect().await,
+            }
+        }
+    }
+
+    /// Scrape all targets once and publish the merged outputs
+    pub async fn collect(&self) {
+        let token = self.bearer_token();
+        let scrapes = join_all(self.config.targets.iter().map(|target| async {
+            let start = Instant::now();
+            let scrape = match &token {
+                Ok(token) => self.scrape(target, token.as_deref()).await,
+                Err(e) => Err(anyhow::anyhow!("{:#}", e)),
+            };
+            if let Err(e) = &scrape {
+                warn!(node = %target.node, url = %target.url, error = %format!("{:#}", e), "Failed to scrape aggregate target");
+            }
+            (scrape, start.elapsed())
+        }))
+        .await;
+        let output = merge(&self.config.targets, scrapes);
+        self.output.store(Arc::new(output));
+        self.ready.store(true, Ordering::Release);
+    }
+
+    /// The bearer token sent to the targets, if configured
+    ///
+    /// The file is re-read every round, so a rotated token applies right away.
+    fn bearer_token(&self) -> Result<Option<String>> {
+        let Some(path) = &self.config.bearer_token_file else {
+            return Ok(None);
+        };
+        let token = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read the bearer token file {}", path.display()))?;
+        Ok(Some(token.trim().to_string()).filter(|token| !token.is_empty()))
+    }
+
+    /// Fetch the metrics and sandboxes of a target
+    async fn scrape(&self, target: &AggregateTarget, token: Option<&str>) -> Result<TargetScrape> {
+        let (metrics, sandboxes) = tokio::try_join!(
+            self.get(&target.url, "/metrics", token),
+            self.get(&target.url, "/sandboxes", token),
+        )?;
+        Ok(TargetScrape { metrics, sandboxes })
+    }
+
+    /// GET `path` of a target URL, returning the body
+    async fn get(&self, base_url: &str, path: &str, token: Option<&str>) -> Result<String> {
+        let url = format!("{}{}", base_url, path);
+        let mut request = Request::get(&url).header(
+            USER_AGENT,
+            concat!("kata-pulse/", env!("CARGO_PKG_VERSION")),
+        );
+        if let Some(token) = token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let request = request.body(Empty::new())?;
+        let response = tokio::time::timeout(self.config.timeout, async {
+            let response = self.client.request(request).await?;
+            let status = response.status();
+            let body = Limited::new(response.into_body(), MAX_RESPONSE_BYTES)
+                .collect()
+                .await
+                .map_err(|e| anyhow::anyhow!("failed to read the response: {}", e))?
+                .to_bytes();
+            anyhow::Ok((status, body))
+        })
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out after {:?}", self.config.timeout))?;
+        let (status, body) = response.with_context(|| format!("failed to fetch {}", url))?;
+        if !status.is_success() {
+            anyhow::bail!("HTTP status {} from {}", status, url);
+        }
+        String::from_utf8(body.to_vec()).with_context(|| format!("invalid UTF-8 from {}", url))
+    }
+}
+
+/// Merge the scrapes of a round, given in the order of `targets`
+fn merge(
+    targets: &[AggregateTarget],
+    scrapes: Vec<(Result<TargetScrape>, Duration)>,
+) -> AggregatedOutput {
+    let mut metrics = PrometheusMetrics::new();
+    let mut sandboxes: Vec<Value> = Vec::new();
+    let mut rounds = Vec::with_capacity(targets.len());
+    for (target, (scrape, duration)) in targets.iter().zip(scrapes) {
+        let up = match scrape.and_then(|scrape| parse_target(target, scrape)) {
+            Ok((target_metrics, target_sandboxes)) => {
+                for conflict in metrics.merge(target_metrics) {
+                    debug!(node = %target.node, conflict = %conflict, "Conflicting series between aggregate targets");
+                }
+                sandboxes.extend(target_sandboxes);
+                1.0
+            }
+            Err(e) => {
+                debug!(node = %target.node, error = %format!("{:#}", e), "Aggregate target left out of the round");
+                0.0
+            }
+        };
+        rounds.push((target, up, duration.as_secs_f64()));
+    }
+
+    let mut output = metrics.to_prometheus_format(None);
+    write_target_gauge(
+        &mut output,
+        "kata_pulse_aggregate_target_up",
+        "Whether the last scrape of the kata-pulse instance succeeded",
+        rounds.iter().map(|(target, up, _)| (*target, *up)),
+    );
+    write_target_gauge(
+        &mut output,
+        "kata_pulse_aggregate_target_scrape_duration_seconds",
+        "Duration of the last scrape of the kata-pulse instance",
+        rounds
+            .iter()
+            .map(|(target, _, duration)| (*target, *duration)),
+    );
+    AggregatedOutput {
+        metrics: output,
+        sandboxes: Value::Array(sandboxes).to_string(),
+    }
+}
+
+/// Append a gauge with one series per target to `out`
+fn write_target_gauge<'a>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    values: impl Iterator<Item = (&'a AggregateTarget, f64)>,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    for (target, value) in values {
+        out.push_str(name);
+        out.push_str("{node=\"");
+        write_escaped(out, &target.node);
+        out.push_str("\",url=\"");
+        write_escaped(out, &target.url);
+        let _ = writeln!(out, "\"}} {}", format_value(value));
+    }
+}
+
+/// Parse the outputs of a target, adding its node to every series and sandbox
+fn parse_target(
+    target: &AggregateTarget,
+    scrape: TargetScrape,
+) -> Result<(PrometheusMetrics, Vec<Value>)> {
+    let (mut metrics, errors) = PrometheusMetrics::parse_with_diagnostics(&scrape.metrics);
+    if !errors.is_empty() {
+        debug!(node = %target.node, errors = errors.len(), "Skipped invalid lines of aggregate target metrics");
+    }
+    let mut labels = interner::labels().batch();
+    let node_label = labels.intern(NODE_LABEL);
+    let exported_node_label = labels.intern(EXPORTED_NODE_LABEL);
+    let node = labels.intern(&target.node);
+    for sample in metrics
+        .metrics
+        .values_mut()
+        .flat_map(|metric| metric.samples.iter_mut())
+    {
+        if let Some(exported) = sample.labels.insert(node_label.clone(), node.clone()) {
+            sample.labels.insert(exported_node_label.clone(), exported);
+        }
+    }
+
+    let listed: Vec<(String, Map<String, Value>)> = serde_json::from_str(&scrape.sandboxes)
+        .with_context(|| format!("invalid sandbox listing from {}", target.url))?;
+    let sandboxes = listed
+        .into_iter()
+        .map(|(id, mut entry)| {
+            entry.insert(NODE_LABEL.to_string(), Value::String(target.node.clone()));
+            Value::Array(vec![Value::String(id), Value::Object(entry)])
+        })
+        .collect();
+    Ok((metrics, sandboxes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(node: &str) -> AggregateTarget {
+        AggregateTarget {
+            node: node.to_string(),
+            url: format!("http://{}:8090", node),
+        }
+    }
+
+    #[test]
+    fn test_parse_target() {
+        let parsed: AggregateTarget = "worker-1=http://10.0.0.1:8090/".parse().unwrap();
+        assert_eq!(parsed.node, "worker-1");
+        assert_eq!(parsed.url, "http://10.0.0.1:8090");
+
+        let parsed: AggregateTarget = "http://10.0.0.2:8090".parse().unwrap();
+        assert_eq!(parsed.node, "10.0.0.2");
+
+        assert!("10.0.0.3:8090".parse::<AggregateTarget>().is_err());
+        assert!("=http://10.0.0.3:8090".parse::<AggregateTarget>().is_err());
+        assert!(Aggregator::new(AggregatorConfig::new(Vec::new())).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token_reread() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token");
+        let config = AggregatorConfig::new(vec![target("worker-1")])
+            .with_bearer_token_file(Some(path.clone()));
+        let aggregator = Aggregator::new(config).unwrap();
+        assert!(aggregator.bearer_token().is_err());
+
+        std::fs::write(&path, "token-a\n").unwrap();
+        assert_eq!(
+            aggregator.bearer_token().unwrap().as_deref(),
+            Some("token-a")
+        );
+        std::fs::write(&path, "token-b").unwrap();
+        assert_eq!(
+            aggregator.bearer_token().unwrap().as_deref(),
+            Some("token-b")
+        );
+
+        let open = Aggregator::new(AggregatorConfig::new(vec![target("worker-1")])).unwrap();
+        assert_eq!(open.bearer_token().unwrap(), None);
+    }
+
+    #[test]
+    fn test_merge_adds_node() {
+        let scrape = |name: &str| TargetScrape {
+            metrics: format!(
+                "# HELP container_memory_usage_bytes Memory usage\n\
+                 # TYPE container_memory_usage_bytes gauge\n\
+                 container_memory_usage_bytes{{pod=\"{0}\"}} 100\n\
+                 # HELP container_cpu_usage_seconds_total CPU time\n\
+                 # TYPE container_cpu_usage_seconds_total counter\n\
+                 container_cpu_usage_seconds_total{{pod=\"{0}\"}} 5\n\
+                 # TYPE kata_pulse_node_info gauge\n\
+                 kata_pulse_node_info{{node=\"{0}\"}} 1\n",
+                name
+            ),
+            sandboxes: format!(
+                r#"[["sb-{0}",{{"name":"{0}","namespace":"default"}}]]"#,
+                name
+            ),
+        };
+        let targets = [target("worker-1"), target("worker-2"), target("worker-3")];
+        let output = merge(
+            &targets,
+            vec![
+                (Ok(scrape("worker-1")), Duration::from_millis(10)),
+                (Ok(scrape("worker-2")), Duration::from_millis(20)),
+                (Err(anyhow::anyhow!("refused")), Duration::from_millis(5)),
+            ],
+        );
+
+        let metrics = PrometheusMetrics::parse(&output.metrics).unwrap();
+        let memory = &metrics.metrics["container_memory_usage_bytes"];
+        assert_eq!(memory.samples.len(), 2);
+        assert_eq!(memory.metric_type.as_deref(), Some("gauge"));
+        assert_eq!(
+            output
+                .metrics
+                .matches("# TYPE container_memory_usage_bytes")
+                .count(),
+            1
+        );
+        for sample in &memory.samples {
+            assert_eq!(sample.labels["node"], sample.labels["pod"]);
+        }
+        // Families keep the names of their metadata lines through the merge
+        assert_eq!(
+            output
+                .metrics
+                .matches("# TYPE container_cpu_usage_seconds_total counter\n")
+                .count(),
+            1
+        );
+        assert!(output
+            .metrics
+            .contains("# HELP container_cpu_usage_seconds_total CPU time\n"));
+        assert!(output
+            .metrics
+            .contains("# TYPE kata_pulse_node_info gauge\n"));
+        assert!(!output
+            .metrics
+            .contains("# TYPE container_cpu_usage_seconds counter"));
+        let info = &metrics.metrics["kata_pulse_node"].samples[0];
+        assert_eq!(info.labels["node"], info.labels["exported_node"]);
+
+        let up: Vec<_> = metrics.metrics["kata_pulse_aggregate_target_up"]
+            .samples
+            .iter()
+            .map(|sample| (sample.labels["node"].to_string(), sample.value))
+            .collect();
+        assert_eq!(up.len(), 3);
+        assert!(up.contains(&("worker-3".to_string(), 0.0)));
+        assert!(up.contains(&("worker-1".to_string(), 1.0)));
+
+        let sandboxes: Vec<(String, Map<String, Value>)> =
+            serde_json::from_str(&output.sandboxes).unwrap();
+        assert_eq!(sandboxes.len(), 2);
+        assert_eq!(sandboxes[1].0, "sb-worker-2");
+        assert_eq!(sandboxes[1].1["node"], "worker-2");
+    }
+
+    #[test]
+    fn test_invalid_sandbox_listing_marks_target_down() {
+        let output = merge(
+            &[target("worker-1")],
+            vec![(
+                Ok(TargetScrape {
+                    metrics: "up 1\n".to_string(),
+                    sandboxes: "not json".to_string(),
+                }),
+                Duration::ZERO,
+            )],
+        );
+        assert!(output.metrics.contains(
+            "kata_pulse_aggregate_target_up{node=\"worker-1\",url=\"http://worker-1:8090\"} 0"
+        ));
+        assert_eq!(output.sandboxes, "[]");
+    }
+}
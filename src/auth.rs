@@ -0,0 +1,297 @@
+//! Request authentication - Kubernetes TokenReview of bearer tokens
+//!
+//! Responsibilities:
+//! - Validate the bearer token of incoming requests against the Kubernetes
+//!   TokenReview API, like the kubelet does, with the in-cluster service account
+//! - Require the token to be valid for one of the configured audiences, so
+//!   only ServiceAccounts with a projected token for them are accepted; the
+//!   audiences the API server reports are checked, not assumed
+//! - Cache the review results, so scrapes don't hit the API server each time
+//!
+//! Requires the `k8s` feature; without it enabling the reviews fails at startup.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Default time a successful review is cached
+pub const DEFAULT_TOKEN_REVIEW_CACHE_TTL: &str = "2m";
+
+/// Time a failed review is cached, like the kubelet's webhook authenticator
+const DENIED_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Number of cached reviews before the cache is cleared
+const MAX_CACHED_REVIEWS: usize = 4096;
+
+/// Settings of the TokenReview authentication
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenReviewConfig {
+    /// Audiences a token must be valid for (required)
+    pub audiences: Vec<String>,
+    /// Time a successful review is cached
+    pub cache_ttl: Duration,
+}
+
+impl TokenReviewConfig {
+    /// Review tokens, caching results for 2 minutes; audiences must be set
+    pub fn new() -> Self {
+        TokenReviewConfig {
+            audiences: Vec::new(),
+            cache_ttl: Duration::from_secs(120),
+        }
+    }
+
+    /// Require tokens valid for one of these audiences
+    pub fn with_audiences(mut self, audiences: Vec<String>) -> Self {
+        self.audiences = audiences;
+        self
+    }
+
+    /// Set the time a successful review is cached
+    pub fn with_cache_ttl(mut self, cache_ttl: Duration) -> Self {
+        self.cache_ttl = cache_ttl;
+        self
+    }
+}
+
+impl Default for TokenReviewConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Outcome of the review of a token
+#[derive(Clone, Debug, PartialEq)]
+pub enum Review {
+    /// The token is valid for the audiences; the user it belongs to
+    Authenticated(String),
+    /// The token is invalid, expired or not valid for the audiences
+    Denied,
+}
+
+/// Cached review results by token
+#[derive(Default)]
+struct ReviewCache {
+    entries: HashMap<String, (Review, Instant)>,
+}
+
+impl ReviewCache {
+    /// The cached review of `token`, if not expired at `now`
+    fn get(&self, token: &str, now: Instant) -> Option<Review> {
+        self.entries
+            .get(token)
+            .filter(|(_, expires)| *expires > now)
+            .map(|(review, _)| review.clone())
+    }
+
+    /// Cache the review of `token` until `expires`
+    fn insert(&mut self, token: &str, review: Review, expires: Instant) {
+        if self.entries.len() >= MAX_CACHED_REVIEWS {
+            let now = Instant::now();
+            self.entries.retain(|_, (_, expires)| *expires > now);
+            if self.entries.len() >= MAX_CACHED_REVIEWS {
+                self.entries.clear();
+            }
+        }
+        self.entries.insert(token.to_string(), (review, expires));
+    }
+}
+
+/// Reviews bearer tokens through the Kubernetes API
+pub struct TokenReviewer {
+    config: TokenReviewConfig,
+    cache: Mutex<ReviewCache>,
+    #[cfg(feature = "k8s")]
+    client: kube::Client,
+}
+
+impl TokenReviewer {
+    /// Create a reviewer with the in-cluster service account
+    ///
+    /// Must be called within the Tokio runtime.
+    pub fn new(config: TokenReviewConfig) -> Result<Self> {
+        if config.audiences.is_empty() {
+            anyhow::bail!(
+                "the TokenReview authentication requires the audiences tokens must be valid for (--auth-audiences)"
+            );
+        }
+        Self::connect(config)
+    }
+
+    /// Connect to the API server with the in-cluster service account
+    #[cfg(feature = "k8s")]
+    fn connect(config: TokenReviewConfig) -> Result<Self> {
+        use anyhow::Context;
+
+        let kube_config = kube::Config::incluster()
+            .context("failed to load the in-cluster Kubernetes configuration")?;
+        let client = kube::Client::try_from(kube_config)
+            .context("failed to create the Kubernetes client")?;
+        Ok(TokenReviewer {
+            config,
+            cache: Mutex::new(ReviewCache::default()),
+            client,
+        })
+    }
+
+    /// Connect to the API server with the in-cluster service account
+    #[cfg(not(feature = "k8s"))]
+    fn connect(_config: TokenReviewConfig) -> Result<Self> {
+        anyhow::bail!(
+            "the Kubernetes TokenReview authentication requires kata-pulse to be built with the `k8s` feature"
+        )
+    }
+
+    /// The settings of the reviewer
+    pub fn config(&self) -> &TokenReviewConfig {
+        &self.config
+    }
+
+    /// Review a bearer token, from the cache if reviewed recently
+    ///
+    /// Errors are failures to reach the API server, not invalid tokens.
+    pub async fn review(&self, token: &str) -> Result<Review> {
+        let cached = self
+            .cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(token, Instant::now());
+        if let Some(review) = cached {
+            return Ok(review);
+        }
+
+        let review = self.token_review(token).await?;
+        let ttl = match &review {
+            Review::Authenticated(user) => {
+                debug!(user = %user, "Token authenticated");
+                self.config.cache_ttl
+            }
+            Review::Denied => DENIED_CACHE_TTL,
+        };
+        self.cache.lock().unwrap_or_else(|e| e.into_inner()).insert(
+            token,
+            review.clone(),
+            Instant::now() + ttl,
+        );
+        Ok(review)
+    }
+
+    /// Create a TokenReview of `token`
+    #[cfg(feature = "k8s")]
+    async fn token_review(&self, token: &str) -> Result<Review> {
+        use anyhow::Context;
+        use k8s_openapi::api::authentication::v1::{TokenReview, TokenReviewSpec};
+        use kube::api::{Api, PostParams};
+
+        let request = TokenReview {
+            spec: TokenReviewSpec {
+                token: Some(token.to_string()),
+                audiences: Some(self.config.audiences.clone()),
+            },
+            ..Default::default()
+        };
+        let api: Api<TokenReview> = Api::all(self.client.clone());
+        let status = api
+            .create(&PostParams::default(), &request)
+            .await
+            .context("TokenReview request failed")?
+            .status
+            .unwrap_or_default();
+        if status.authenticated != Some(true) {
+            if let Some(error) = status.error {
+                debug!(error = %error, "Token rejected by TokenReview");
+            }
+            return Ok(Review::Denied);
+        }
+        // Authenticators that ignore the requested audiences return none or others
+        if !audiences_match(&self.config.audiences, status.audiences.as_deref()) {
+            debug!(audiences = ?status.audiences, "Token not valid for the audiences");
+            return Ok(Review::Denied);
+        }
+        let user = status
+            .user
+            .and_then(|user| user.username)
+            .unwrap_or_default();
+        Ok(Review::Authenticated(user))
+    }
+
+    /// Create a TokenReview of `token`
+    #[cfg(not(feature = "k8s"))]
+    async fn token_review(&self, _token: &str) -> Result<Review> {
+        anyhow::bail!("TokenReview authentication requires the `k8s` feature")
+    }
+}
+
+/// Whether the audiences the API server authenticated a token for include
+/// one of the `requested` ones
+#[cfg(any(feature = "k8s", test))]
+fn audiences_match(requested: &[String], authenticated: Option<&[String]>) -> bool {
+    authenticated
+        .unwrap_or_default()
+        .iter()
+        .any(|audience| requested.contains(audience))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_review_cache_expiry() {
+        let mut cache = ReviewCache::default();
+        let now = Instant::now();
+        cache.insert(
+            "token-a",
+            Review::Authenticated("system:serviceaccount:monitoring:prometheus".to_string()),
+            now + Duration::from_secs(120),
+        );
+        cache.insert("token-b", Review::Denied, now + Duration::from_secs(30));
+
+        assert!(matches!(
+            cache.get("token-a", now),
+            Some(Review::Authenticated(_))
+        ));
+        assert_eq!(cache.get("token-b", now), Some(Review::Denied));
+        assert_eq!(cache.get("token-b", now + Duration::from_secs(31)), None);
+        assert_eq!(cache.get("token-c", now), None);
+    }
+
+    #[test]
+    fn test_review_cache_bounded() {
+        let mut cache = ReviewCache::default();
+        let expires = Instant::now() + Duration::from_secs(60);
+        for i in 0..=MAX_CACHED_REVIEWS {
+            cache.insert(&format!("token-{}", i), Review::Denied, expires);
+        }
+        assert!(cache.entries.len() <= MAX_CACHED_REVIEWS);
+    }
+
+    #[test]
+    fn test_audiences_match() {
+        let requested = vec!["kata-pulse".to_string()];
+        let other = vec!["https://kubernetes.default.svc".to_string()];
+        let both = vec![other[0].clone(), requested[0].clone()];
+
+        assert!(audiences_match(&requested, Some(&requested)));
+        assert!(audiences_match(&requested, Some(&both)));
+        assert!(!audiences_match(&requested, Some(&other)));
+        assert!(!audiences_match(&requested, Some(&[])));
+        assert!(!audiences_match(&requested, None));
+        assert!(!audiences_match(&[], Some(&other)));
+    }
+
+    #[test]
+    fn test_reviewer_needs_audiences() {
+        let error = TokenReviewer::new(TokenReviewConfig::new()).err().unwrap();
+        assert!(error.to_string().contains("--auth-audiences"));
+    }
+
+    #[cfg(not(feature = "k8s"))]
+    #[test]
+    fn test_reviewer_needs_k8s_feature() {
+        let config = TokenReviewConfig::new().with_audiences(vec!["kata-pulse".to_string()]);
+        assert!(TokenReviewer::new(config).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+//! Build info - version and build provenance of the running binary
+//!
+//! Responsibilities:
+//! - Expose the version, git SHA, build date (stamped by `build.rs`) and the
+//!   enabled cargo features
+//! - Render them as the `kata_pulse_build_info` metric, so fleet tooling can
+//!   audit the deployed agent versions
+
+use serde::Serialize;
+
+use crate::utils::metrics_converter::cadvisor::escape_label_value;
+
+/// Version and build provenance
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BuildInfo {
+    /// Crate version
+    pub version: &'static str,
+    /// Short git SHA of the build (`unknown` outside a checkout)
+    pub git_sha: &'static str,
+    /// UTC build timestamp (RFC 3339)
+    pub build_date: &'static str,
+    /// Enabled optional cargo features
+    pub features: Vec<&'static str>,
+}
+
+impl BuildInfo {
+    /// Build info of the running binary
+    pub fn current() -> Self {
+        let features = [
+            ("tls", cfg!(feature = "tls")),
+            ("kafka", cfg!(feature = "kafka")),
+            ("otlp", cfg!(feature = "otlp")),
+            ("k8s", cfg!(feature = "k8s")),
+            ("jemalloc", cfg!(feature = "jemalloc")),
+        ];
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: env!("KATA_PULSE_GIT_SHA"),
+            build_date: env!("KATA_PULSE_BUILD_DATE"),
+            features: features
+                .into_iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| name)
+                .collect(),
+        }
+    }
+
+    /// Render `kata_pulse_build_info` in Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        let mut output = String::new();
+        output.push_str("# HELP kata_pulse_build_info Version and build of kata-pulse, always 1\n");
+        output.push_str("# TYPE kata_pulse_build_info gauge\n");
+        output.push_str(&format!(
+            "kata_pulse_build_info{{version=\"{}\",git_sha=\"{}\",build_date=\"{}\",features=\"{}\"}} 1\n",
+            escape_label_value(self.version),
+            escape_label_value(self.git_sha),
+            escape_label_value(self.build_date),
+            escape_label_value(&self.features.join(","))
+        ));
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_build_info() {
+        let info = BuildInfo {
+            version: "1.2.3",
+            git_sha: "0123456789ab",
+            build_date: "2026-10-16T12:00:00Z",
+            features: vec!["tls", "otlp"],
+        };
+        assert!(info.render_metrics().contains(
+            r#"kata_pulse_build_info{version="1.2.3",git_sha="0123456789ab",build_date="2026-10-16T12:00:00Z",features="tls,otlp"} 1"#
+        ));
+
+        let current = BuildInfo::current();
+        assert_eq!(current.version, env!("CARGO_PKG_VERSION"));
+        assert!(!current.git_sha.is_empty());
+        assert_eq!(current.build_date.len(), "2026-10-16T12:00:00Z".len());
+    }
+}
@@ -0,0 +1,287 @@
+//! `kata-pulse check`: preflight validation of the node environment
+//!
+//! Responsibilities:
+//! - Check that a CRI runtime endpoint answers `Version`
+//! - Check that the Kata storage directories exist and are readable
+//! - Check that the shim monitor socket of at least one running sandbox answers
+//! - Check that CLK_TCK is configured or detected rather than assumed
+//! - Render the results as a human-readable table or JSON
+//!
+//! A failed check makes the command exit non-zero; warnings do not.
+
+use serde::Serialize;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::config::{self, StoragePaths};
+use crate::monitor::cri::{self, CRIClientConfig};
+use crate::utils::metrics_converter::{detect_clk_tck, ClkTckSource};
+use crate::utils::shim_client::{ShimClient, ShimClientConfig};
+
+/// Outcome of a single check
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl CheckStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        }
+    }
+}
+
+/// Result of a single check
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Results of all checks
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    /// False if any check failed
+    pub ok: bool,
+    pub checks: Vec<CheckResult>,
+}
+
+impl CheckReport {
+    fn new(checks: Vec<CheckResult>) -> Self {
+        CheckReport {
+            ok: checks.iter().all(|check| check.status != CheckStatus::Fail),
+            checks,
+        }
+    }
+
+    /// One line per check followed by the overall result
+    pub fn to_text(&self) -> String {
+        let width = self
+            .checks
+            .iter()
+            .map(|check| check.name.len())
+            .max()
+            .unwrap_or(0);
+        let mut text = String::new();
+        for check in &self.checks {
+            let _ = writeln!(
+                text,
+                "[{}] {:width$}  {}",
+                check.status.as_str(),
+                check.name,
+                check.detail,
+                width = width
+            );
+        }
+        text.push_str(if self.ok {
+            "\nAll checks passed\n"
+        } else {
+            "\nSome checks failed\n"
+        });
+        text
+    }
+}
+
+/// Settings of the agent the checks connect with
+pub struct CheckOptions {
+    /// CRI endpoints, tried in order
+    pub runtime_endpoints: Vec<String>,
+    pub cri_client: CRIClientConfig,
+    pub shim_client: ShimClientConfig,
+    /// Configured CLK_TCK of the guest, detected from the host if None
+    pub cpu_jiffy_conversion_factor: Option<f64>,
+}
+
+/// Run all checks
+pub async fn run(options: &CheckOptions) -> CheckReport {
+    let storage = config::StoragePaths {
+        sandboxes: config::get_sandboxes_storage_path(),
+        sandboxes_rust: config::get_sandboxes_storage_path_rust(),
+        vm: config::get_vm_storage_path(),
+    };
+    let mut checks = vec![check_cri(&options.runtime_endpoints, &options.cri_client).await];
+    checks.extend(check_storage(&storage));
+    checks.push(check_shim_sockets(&storage.sandboxes, &options.shim_client).await);
+    checks.push(check_clk_tck(options.cpu_jiffy_conversion_factor));
+    CheckReport::new(checks)
+}
+
+/// The first CRI endpoint that answers `Version`
+async fn check_cri(endpoints: &[String], config: &CRIClientConfig) -> CheckResult {
+    let mut errors = Vec::new();
+    for endpoint in endpoints {
+        let client = cri::init_cri_client(endpoint.as_str(), config);
+        let result = match client.connect().await {
+            Ok(()) => client.probe().await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(version) => {
+                return CheckResult::new(
+                    "cri runtime",
+                    CheckStatus::Pass,
+                    format!("{} answered: {}", endpoint, version),
+                )
+            }
+            Err(e) => errors.push(format!("{}: {:#}", endpoint, e)),
+        }
+    }
+    if errors.is_empty() {
+        errors.push("no runtime endpoint configured".to_string());
+    }
+    CheckResult::new("cri runtime", CheckStatus::Fail, errors.join("; "))
+}
+
+/// The sandbox directory the agent watches must be readable; the Rust
+/// runtime and VM directories are optional
+fn check_storage(storage: &StoragePaths) -> Vec<CheckResult> {
+    [
+        ("sandbox storage", &storage.sandboxes, CheckStatus::Fail),
+        (
+            "rust runtime storage",
+            &storage.sandboxes_rust,
+            CheckStatus::Warn,
+        ),
+        ("vm storage", &storage.vm, CheckStatus::Warn),
+    ]
+    .into_iter()
+    .map(|(name, path, status)| match fs::read_dir(path) {
+        Ok(entries) => CheckResult::new(
+            name,
+            CheckStatus::Pass,
+            format!("{} ({} entries)", path.display(), entries.count()),
+        ),
+        Err(e) => CheckResult::new(name, status, format!("{}: {}", path.display(), e)),
+    })
+    .collect()
+}
+
+/// At least one shim monitor socket of the sandboxes in `sandboxes_dir` answers
+async fn check_shim_sockets(sandboxes_dir: &Path, config: &ShimClientConfig) -> CheckResult {
+    let mut sandbox_ids: Vec<String> = match fs::read_dir(sandboxes_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().to_str().map(str::to_string))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    sandbox_ids.sort();
+    if sandbox_ids.is_empty() {
+        return CheckResult::new(
+            "shim sockets",
+            CheckStatus::Warn,
+            "no running sandboxes to connect to",
+        );
+    }
+
+    let client = ShimClient::new(config.clone());
+    let probes = sandbox_ids.iter().map(|id| {
+        let client = &client;
+        async move {
+            let (_, socket) = config::resolve_shim_socket(id)?;
+            client.get(id, &socket, "/metrics", config.timeout).await?;
+            anyhow::Ok(())
+        }
+    });
+    let results = futures::future::join_all(probes).await;
+    let answered = results.iter().filter(|result| result.is_ok()).count();
+    let detail = format!(
+        "{} of {} shim monitor sockets answered",
+        answered,
+        sandbox_ids.len()
+    );
+    match results
+        .into_iter()
+        .zip(&sandbox_ids)
+        .find_map(|(result, id)| {
+            result
+                .err()
+                .map(|e| format!("{}; sandbox {}: {:#}", detail, id, e))
+        }) {
+        None => CheckResult::new("shim sockets", CheckStatus::Pass, detail),
+        Some(detail) if answered > 0 => CheckResult::new("shim sockets", CheckStatus::Warn, detail),
+        Some(detail) => CheckResult::new("shim sockets", CheckStatus::Fail, detail),
+    }
+}
+
+/// CLK_TCK comes from the settings or the system, not the 100 Hz fallback
+fn check_clk_tck(configured: Option<f64>) -> CheckResult {
+    let (clk_tck, source) = detect_clk_tck(configured);
+    let status = match source {
+        ClkTckSource::Fallback => CheckStatus::Fail,
+        _ => CheckStatus::Pass,
+    };
+    CheckResult::new(
+        "clk_tck",
+        status,
+        format!("{} Hz from {}", clk_tck, source.as_str()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sb-1")).unwrap();
+        let storage = StoragePaths {
+            sandboxes: dir.path().to_path_buf(),
+            sandboxes_rust: dir.path().join("missing"),
+            vm: dir.path().join("missing"),
+        };
+        let checks = check_storage(&storage);
+        assert_eq!(checks[0].status, CheckStatus::Pass);
+        assert!(checks[0].detail.ends_with("(1 entries)"));
+        assert_eq!(checks[1].status, CheckStatus::Warn);
+
+        let storage = StoragePaths {
+            sandboxes: dir.path().join("missing"),
+            ..storage
+        };
+        assert_eq!(check_storage(&storage)[0].status, CheckStatus::Fail);
+    }
+
+    #[tokio::test]
+    async fn test_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ShimClientConfig::default();
+        let check = check_shim_sockets(dir.path(), &config).await;
+        assert_eq!(check.status, CheckStatus::Warn);
+
+        let report = CheckReport::new(vec![
+            check,
+            CheckResult::new("clk_tck", CheckStatus::Pass, "100 Hz"),
+        ]);
+        assert!(report.ok);
+        assert!(report.to_text().contains("[WARN] shim sockets"));
+
+        let report = CheckReport::new(vec![CheckResult::new(
+            "cri runtime",
+            CheckStatus::Fail,
+            "down",
+        )]);
+        assert!(!report.ok);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["checks"][0]["status"], "fail");
+    }
+}
@@ -0,0 +1,162 @@
+//! `kata-pulse list`: table of the sandboxes on the node
+//!
+//! Responsibilities:
+//! - Read the sandbox IDs from the Go and Rust runtime storage directories
+//! - Query CRI once for the pod of every sandbox
+//! - Print sandbox ID, pod, namespace, runtime flavor and shim socket path
+//!
+//! Used for node debugging over SSH without the HTTP API.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::config;
+use crate::monitor::cri::{self, CRIClientConfig};
+use crate::monitor::sandbox_cache::{SandboxCRIMetadata, SandboxCache};
+
+/// Column headers of the table
+const HEADERS: [&str; 5] = ["SANDBOX ID", "POD", "NAMESPACE", "RUNTIME", "SOCKET"];
+
+/// Agent settings of the CRI query
+pub struct ListOptions {
+    /// CRI endpoints asked for the pods, in order
+    pub runtime_endpoints: Vec<String>,
+    pub cri_client: CRIClientConfig,
+}
+
+/// List the sandboxes of the storage directories as a table
+pub async fn run(options: &ListOptions) -> String {
+    let sandbox_ids = sandbox_ids(&[
+        config::get_sandboxes_storage_path(),
+        config::get_sandboxes_storage_path_rust(),
+    ]);
+
+    // Pods are best effort; sandboxes unknown to CRI are listed without them
+    let cache = Arc::new(SandboxCache::new());
+    for id in &sandbox_ids {
+        cache
+            .put_if_not_exists(id, SandboxCRIMetadata::default())
+            .await;
+    }
+    if !sandbox_ids.is_empty() {
+        if let Err(e) = cri::sync_sandboxes(
+            &options.runtime_endpoints,
+            &options.cri_client,
+            &cache,
+            sandbox_ids.clone(),
+        )
+        .await
+        {
+            warn!(error = %format!("{:#}", e), "Failed to query CRI, pods are not listed");
+        }
+    }
+
+    let mut rows = Vec::with_capacity(sandbox_ids.len());
+    for id in sandbox_ids {
+        let metadata = cache.get_metadata(&id).await.unwrap_or_default();
+        let (runtime, socket) = match config::resolve_shim_socket(&id) {
+            Ok((runtime, socket)) => (
+                format!("{:?}", runtime).to_lowercase(),
+                socket.display().to_string(),
+            ),
+            Err(_) => ("-".to_string(), "-".to_string()),
+        };
+        rows.push([
+            id,
+            or_dash(metadata.name),
+            or_dash(metadata.namespace),
+            runtime,
+            socket,
+        ]);
+    }
+    render_table(&rows)
+}
+
+/// Names of the directories in `dirs`, sorted and deduplicated; missing
+/// directories are skipped
+fn sandbox_ids(dirs: &[PathBuf]) -> Vec<String> {
+    let mut ids = BTreeSet::new();
+    for dir in dirs {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                if let Some(name) = entry.file_name().to_str() {
+                    ids.insert(name.to_string());
+                }
+            }
+        }
+    }
+    ids.into_iter().collect()
+}
+
+fn or_dash(value: String) -> String {
+    if value.is_empty() {
+        "-".to_string()
+    } else {
+        value
+    }
+}
+
+/// Left-aligned columns separated by two spaces
+fn render_table(rows: &[[String; 5]]) -> String {
+    let mut widths = HEADERS.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut table = String::new();
+    let headers = HEADERS.map(str::to_string);
+    for row in std::iter::once(&headers).chain(rows) {
+        let mut line = String::new();
+        for (cell, width) in row.iter().zip(widths) {
+            let _ = write!(line, "{:width$}  ", cell, width = width);
+        }
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_ids_and_table() {
+        let go = tempfile::tempdir().unwrap();
+        let rust = tempfile::tempdir().unwrap();
+        fs::create_dir(go.path().join("sb-b")).unwrap();
+        fs::create_dir(go.path().join("sb-a")).unwrap();
+        fs::create_dir(rust.path().join("sb-a")).unwrap();
+        fs::write(rust.path().join("not-a-sandbox"), "").unwrap();
+
+        let ids = sandbox_ids(&[
+            go.path().to_path_buf(),
+            rust.path().to_path_buf(),
+            go.path().join("missing"),
+        ]);
+        assert_eq!(ids, vec!["sb-a", "sb-b"]);
+
+        let table = render_table(&[[
+            "sb-a".to_string(),
+            "my-pod".to_string(),
+            "default".to_string(),
+            "go".to_string(),
+            "/run/vc/sbs/sb-a/shim-monitor.sock".to_string(),
+        ]]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "SANDBOX ID  POD     NAMESPACE  RUNTIME  SOCKET");
+        assert_eq!(
+            lines[1],
+            "sb-a        my-pod  default    go       /run/vc/sbs/sb-a/shim-monitor.sock"
+        );
+    }
+}
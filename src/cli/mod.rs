@@ -0,0 +1,11 @@
+//! One-shot subcommands, run instead of the agent
+//!
+//! - `check`: validate the node environment before deploying the agent
+//! - `scrape`: print the metrics of one sandbox
+//! - `list`: print the sandboxes of the node and their pods
+//! - `validate-config`: check a configuration file
+
+pub mod check;
+pub mod list;
+pub mod scrape;
+pub mod validate_config;
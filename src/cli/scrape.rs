@@ -0,0 +1,141 @@
+//! `kata-pulse scrape`: fetch and print the metrics of one sandbox
+//!
+//! Responsibilities:
+//! - Read the metrics of a sandbox from its shim monitor socket once
+//! - Look up the pod of the sandbox in CRI for the converted labels
+//! - Print the shim metrics as-is, or converted to cAdvisor text or JSON
+//!
+//! Used to validate a conversion change on a node without deploying the agent.
+
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::config;
+use crate::monitor::cri::{self, CRIClientConfig};
+use crate::monitor::sandbox_cache::{SandboxCRIMetadata, SandboxCache};
+use crate::utils::metrics_converter::cadvisor::PrometheusFormat;
+use crate::utils::metrics_converter::{
+    create_converter, CRILabelEnricher, ConversionConfig, EnrichedLabels, LabelEnricher,
+};
+use crate::utils::prometheus_parser::{NameFilter, PrometheusMetrics};
+use crate::utils::shim_client::{ShimClient, ShimClientConfig};
+
+/// How the metrics are printed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ScrapeFormat {
+    /// The shim response, unparsed
+    Raw,
+    /// Converted, in the Prometheus text format served by `/metrics`
+    #[default]
+    Cadvisor,
+    /// Converted, as a JSON document
+    Json,
+}
+
+/// Sandbox, output and agent settings of a scrape
+pub struct ScrapeOptions {
+    pub sandbox_id: String,
+    pub format: ScrapeFormat,
+    /// CRI endpoints asked for the pod of the sandbox, in order
+    pub runtime_endpoints: Vec<String>,
+    pub cri_client: CRIClientConfig,
+    pub shim_client: ShimClientConfig,
+    pub name_filter: NameFilter,
+    pub conversion: ConversionConfig,
+}
+
+/// Scrape the sandbox and render its metrics in the requested format
+pub async fn run(options: &ScrapeOptions) -> Result<String> {
+    let sandbox_id = &options.sandbox_id;
+    let (_, socket) = config::resolve_shim_socket(sandbox_id)?;
+    let client = ShimClient::new(options.shim_client.clone());
+    let data = client
+        .get(
+            sandbox_id,
+            &socket,
+            config::METRICS_URL,
+            options.shim_client.timeout,
+        )
+        .await
+        .with_context(|| format!("failed to read metrics from {}", socket.display()))?;
+    if options.format == ScrapeFormat::Raw {
+        return Ok(String::from_utf8_lossy(&data).into_owned());
+    }
+
+    // Pod labels are best effort; the metrics are printed without them
+    let cache = Arc::new(SandboxCache::new());
+    cache
+        .put_if_not_exists(sandbox_id, SandboxCRIMetadata::default())
+        .await;
+    match cri::sync_sandboxes(
+        &options.runtime_endpoints,
+        &options.cri_client,
+        &cache,
+        vec![sandbox_id.clone()],
+    )
+    .await
+    {
+        Ok(unknown) if !unknown.is_empty() => {
+            warn!(sandbox_id = %sandbox_id, "Sandbox is not known to CRI, pod labels are empty")
+        }
+        Ok(_) => {}
+        Err(e) => {
+            warn!(error = %format!("{:#}", e), "Failed to query CRI, pod labels are empty")
+        }
+    }
+
+    let labels = CRILabelEnricher::new(cache).enrich(sandbox_id).await;
+    render(options, &data, labels)
+}
+
+/// Parse and convert the shim response `data`
+fn render(options: &ScrapeOptions, data: &[u8], labels: EnrichedLabels) -> Result<String> {
+    let (metrics, errors) =
+        PrometheusMetrics::parse_filtered(&String::from_utf8_lossy(data), &options.name_filter);
+    for error in &errors {
+        warn!(error = %error, "Skipped malformed metrics line");
+    }
+
+    let converter = create_converter(options.conversion.clone(), labels);
+    let converted = converter
+        .convert_all(&metrics)
+        .context("failed to convert metrics")?;
+    Ok(match options.format {
+        ScrapeFormat::Json => serde_json::to_string_pretty(&converted)? + "\n",
+        _ => converted.to_prometheus_format(Some(&options.sandbox_id)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let data = b"# TYPE kata_guest_cpu_time gauge\n\
+            kata_guest_cpu_time{cpu=\"total\",item=\"user\"} 500\n\
+            kata_guest_cpu_time{cpu=\"total\",item=\"system\"} 300\n\
+            garbage line\n";
+        let mut options = ScrapeOptions {
+            sandbox_id: "sb-1".to_string(),
+            format: ScrapeFormat::Cadvisor,
+            runtime_endpoints: Vec::new(),
+            cri_client: CRIClientConfig::default(),
+            shim_client: ShimClientConfig::default(),
+            name_filter: NameFilter::default(),
+            conversion: ConversionConfig {
+                cpu_jiffy_conversion_factor: 100.0,
+                ..Default::default()
+            },
+        };
+
+        let text = render(&options, data, EnrichedLabels::default()).unwrap();
+        assert!(text.contains("container_cpu_usage_seconds_total{"));
+
+        options.format = ScrapeFormat::Json;
+        let json = render(&options, data, EnrichedLabels::default()).unwrap();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(json["cpu"]["usage_seconds_total"], 8.0);
+    }
+}
@@ -0,0 +1,160 @@
+//! `kata-pulse validate-config`: check a configuration file and print the
+//! effective configuration
+//!
+//! Responsibilities:
+//! - Render every setting with the value that wins after command line,
+//!   environment, file and default precedence, and where it comes from
+//! - Render the `[storage]` section
+//!
+//! The output is a valid configuration file: every setting is quoted except
+//! flags, which are booleans. Parsing and validation errors are reported by
+//! the caller, with the line and column of the file for syntax errors.
+
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command};
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::config::file::ConfigFile;
+use crate::config::StoragePaths;
+
+/// Settings whose values are not printed
+const REDACTED: &[&str] = &["password"];
+
+/// Effective configuration of `resolved`, the matches after applying `file`
+/// to the command line and environment of `original`
+pub fn render(
+    command: &Command,
+    original: &ArgMatches,
+    resolved: &ArgMatches,
+    file: &ConfigFile,
+) -> String {
+    let mut out = String::new();
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if arg.get_long().is_none() || id == "config" {
+            continue;
+        }
+        let Some(values) = resolved.get_raw(id) else {
+            continue;
+        };
+        let values: Vec<String> = values
+            .map(|value| value.to_string_lossy().into_owned())
+            .collect();
+
+        let source = match original.value_source(id) {
+            Some(ValueSource::CommandLine) => "command line",
+            Some(ValueSource::EnvVariable) => "environment",
+            _ if file.settings.contains_key(id)
+                || file.settings.contains_key(&id.replace('_', "-")) =>
+            {
+                "file"
+            }
+            _ => "default",
+        };
+        let value = if REDACTED.iter().any(|redacted| id.contains(redacted)) {
+            quote("<redacted>")
+        } else if matches!(arg.get_action(), ArgAction::SetTrue) {
+            values.join("")
+        } else if matches!(arg.get_action(), ArgAction::Append) {
+            let values: Vec<String> = values.iter().map(|value| quote(value)).collect();
+            format!("[{}]", values.join(", "))
+        } else {
+            quote(&values.join(""))
+        };
+        let _ = writeln!(out, "{} = {}  # {}", id, value, source);
+    }
+
+    let source = |set: bool| if set { "file" } else { "default" };
+    let defaults = StoragePaths::default();
+    let _ = writeln!(out, "\n[storage]");
+    for (name, path, default) in [
+        ("sandboxes", &file.storage.sandboxes, &defaults.sandboxes),
+        (
+            "sandboxes_rust",
+            &file.storage.sandboxes_rust,
+            &defaults.sandboxes_rust,
+        ),
+        ("vm", &file.storage.vm, &defaults.vm),
+    ] {
+        let _ = writeln!(
+            out,
+            "{} = {}  # {}",
+            name,
+            quote_path(path),
+            source(path != default)
+        );
+    }
+    out
+}
+
+/// TOML basic string of `value`
+fn quote(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+fn quote_path(path: &Path) -> String {
+    quote(&path.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, Parser};
+    use std::ffi::OsString;
+
+    #[derive(Parser, Debug)]
+    struct TestArgs {
+        #[arg(long, default_value = "127.0.0.1:8090")]
+        listen_address: String,
+        #[arg(long, default_value_t = 60)]
+        metrics_interval_secs: u64,
+        #[arg(long, value_delimiter = ',')]
+        runtime_endpoint: Vec<String>,
+        #[arg(long)]
+        disable_virtiofsd_metrics: bool,
+        #[arg(long)]
+        remote_write_password: Option<String>,
+        #[arg(long)]
+        node_name: Option<String>,
+    }
+
+    #[test]
+    fn test_render_round_trips() {
+        let file: ConfigFile = toml::from_str(
+            r#"
+            metrics-interval-secs = 30
+            disable_virtiofsd_metrics = true
+            runtime_endpoint = ["/run/a.sock", "/run/b.sock"]
+
+            [storage]
+            vm = "/var/run/vc/vm"
+            "#,
+        )
+        .unwrap();
+        let cli = ["kata-pulse", "--remote-write-password", "secret"];
+        let original = TestArgs::command().try_get_matches_from(cli).unwrap();
+        let extra = file.args(&TestArgs::command(), &original).unwrap();
+        let resolved = TestArgs::command()
+            .try_get_matches_from(cli.iter().map(OsString::from).chain(extra))
+            .unwrap();
+        let text = render(&TestArgs::command(), &original, &resolved, &file);
+        assert!(text.contains("listen_address = \"127.0.0.1:8090\"  # default\n"));
+        assert!(text.contains("metrics_interval_secs = \"30\"  # file\n"));
+        assert!(text.contains("disable_virtiofsd_metrics = true  # file\n"));
+        assert!(text.contains("runtime_endpoint = [\"/run/a.sock\", \"/run/b.sock\"]  # file\n"));
+        assert!(text.contains("remote_write_password = \"<redacted>\"  # command line\n"));
+        assert!(!text.contains("node_name"));
+        assert!(text.contains("vm = \"/var/run/vc/vm\"  # file\n"));
+
+        // The effective configuration is a valid configuration file
+        let reparsed: ConfigFile = toml::from_str(&text).unwrap();
+        assert_eq!(reparsed.storage.vm, file.storage.vm);
+        assert!(reparsed
+            .args(
+                &TestArgs::command(),
+                &TestArgs::command().get_matches_from(["kata-pulse"])
+            )
+            .is_ok());
+    }
+}
@@ -0,0 +1,190 @@
+//! Configuration file
+//!
+//! Responsibilities:
+//! - Load a TOML or YAML (`.yaml`/`.yml`) file holding any command line
+//!   setting by name, plus the `storage` section for the settings that have
+//!   no flag
+//! - Turn the file settings into command line arguments for the settings
+//!   left unset on the command line and in the environment, so the
+//!   precedence is CLI > env > file > defaults
+
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Command};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+use super::StoragePaths;
+
+/// Contents of a configuration file
+#[derive(Debug, Default, Deserialize)]
+pub struct ConfigFile {
+    /// Kata runtime storage directories
+    #[serde(default)]
+    pub storage: StoragePaths,
+
+    /// Command line settings by flag name (`listen_address` or `listen-address`)
+    #[serde(flatten)]
+    pub settings: BTreeMap<String, Value>,
+}
+
+impl ConfigFile {
+    /// Load a configuration file; the format follows the extension (TOML unless `.yaml`/`.yml`)
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read configuration file {}", path.display()))?;
+        let yaml = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("yaml" | "yml")
+        );
+        let file = if yaml {
+            serde_yaml::from_str(&text).map_err(anyhow::Error::from)
+        } else {
+            toml::from_str(&text).map_err(anyhow::Error::from)
+        };
+        file.with_context(|| format!("failed to parse configuration file {}", path.display()))
+    }
+
+    /// Command line arguments for the file settings not given on the command
+    /// line or in the environment of `matches`
+    pub fn args(&self, command: &Command, matches: &ArgMatches) -> Result<Vec<OsString>> {
+        let mut args = Vec::new();
+        for (key, value) in &self.settings {
+            let id = key.replace('-', "_");
+            let arg = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id.as_str() && arg.get_long().is_some())
+                .filter(|arg| arg.get_id() != "config")
+                .with_context(|| format!("unknown setting {:?}", key))?;
+            if matches!(
+                matches.value_source(&id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            ) {
+                continue;
+            }
+
+            let long = arg.get_long().unwrap_or_default();
+            let action = arg.get_action();
+            let values = match value {
+                Value::Array(values) if matches!(action, ArgAction::Append) => {
+                    values.iter().collect()
+                }
+                Value::Array(_) => anyhow::bail!("setting {:?} takes a single value", key),
+                value => vec![value],
+            };
+            for value in values {
+                let value = match (action, value) {
+                    (ArgAction::SetTrue, Value::Bool(true)) => {
+                        args.push(format!("--{}", long).into());
+                        continue;
+                    }
+                    (ArgAction::SetTrue, Value::Bool(false)) => continue,
+                    (ArgAction::SetTrue, _) => {
+                        anyhow::bail!("setting {:?} must be true or false", key)
+                    }
+                    (_, Value::String(value)) => value.clone(),
+                    (_, Value::Number(value)) => value.to_string(),
+                    (_, Value::Bool(value)) => value.to_string(),
+                    _ => anyhow::bail!("setting {:?} must be a string, number or boolean", key),
+                };
+                if value.is_empty() {
+                    // `--name=` is rejected as a missing value
+                    args.extend([format!("--{}", long).into(), OsString::new()]);
+                } else {
+                    args.push(format!("--{}={}", long, value).into());
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches, Parser};
+
+    #[derive(Parser, Debug)]
+    struct TestArgs {
+        #[arg(long, default_value = "127.0.0.1:8090")]
+        listen_address: String,
+        #[arg(long, default_value_t = 60)]
+        metrics_interval_secs: u64,
+        #[arg(long, value_delimiter = ',')]
+        runtime_endpoint: Vec<String>,
+        #[arg(long)]
+        disable_virtiofsd_metrics: bool,
+        #[arg(long, default_value = "/sys/fs/cgroup")]
+        host_cgroup_root: String,
+        #[arg(long)]
+        config: Option<String>,
+    }
+
+    fn parse(file: &ConfigFile, cli: &[&str]) -> Result<TestArgs> {
+        let matches = TestArgs::command().try_get_matches_from(cli)?;
+        let extra = file.args(&TestArgs::command(), &matches)?;
+        let cli = cli.iter().map(OsString::from).chain(extra);
+        Ok(TestArgs::from_arg_matches(
+            &TestArgs::command().try_get_matches_from(cli)?,
+        )?)
+    }
+
+    #[test]
+    fn test_cli_wins_over_file() {
+        let file: ConfigFile = toml::from_str(
+            r#"
+            listen-address = "0.0.0.0:8090"
+            metrics_interval_secs = 30
+            runtime_endpoint = ["/run/a.sock", "/run/b.sock"]
+            disable_virtiofsd_metrics = true
+            host_cgroup_root = ""
+
+            [storage]
+            sandboxes_rust = "/var/run/kata"
+            "#,
+        )
+        .unwrap();
+
+        let args = parse(&file, &["kata-pulse", "--metrics-interval-secs", "10"]).unwrap();
+        assert_eq!(args.listen_address, "0.0.0.0:8090");
+        assert_eq!(args.metrics_interval_secs, 10);
+        assert_eq!(args.runtime_endpoint, vec!["/run/a.sock", "/run/b.sock"]);
+        assert!(args.disable_virtiofsd_metrics);
+        assert_eq!(args.host_cgroup_root, "");
+
+        assert_eq!(file.storage.sandboxes_rust, Path::new("/var/run/kata"));
+        assert_eq!(file.storage.sandboxes, StoragePaths::default().sandboxes);
+    }
+
+    #[test]
+    fn test_load_yaml_and_reject_invalid_settings() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(
+            &path,
+            "metrics_interval_secs: 15\nstorage:\n  vm: /var/run/vc/vm\n",
+        )
+        .unwrap();
+        let file = ConfigFile::load(&path).unwrap();
+        assert_eq!(
+            parse(&file, &["kata-pulse"]).unwrap().metrics_interval_secs,
+            15
+        );
+        assert_eq!(file.storage.vm, Path::new("/var/run/vc/vm"));
+
+        for invalid in [
+            "unknown_setting = 1",
+            "config = \"other.toml\"",
+            "listen_address = [\"a\", \"b\"]",
+            "disable_virtiofsd_metrics = \"yes\"",
+        ] {
+            let file: ConfigFile = toml::from_str(invalid).unwrap();
+            assert!(parse(&file, &["kata-pulse"]).is_err(), "{}", invalid);
+        }
+        assert!(toml::from_str::<ConfigFile>("[storage]\nsandboxes_go = \"/run\"").is_err());
+    }
+}
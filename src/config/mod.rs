@@ -1,16 +1,72 @@
+pub mod file;
+pub mod reload;
+pub mod settings;
+
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
 
 // HTTP endpoint paths
 pub const METRICS_URL: &str = "/metrics";
 
+// Pod annotation overriding the metrics collection interval for a sandbox
+pub const SCRAPE_INTERVAL_ANNOTATION: &str = "kata-pulse.io/scrape-interval";
+
+// Pod annotation overriding the guest CLK_TCK (jiffies per second) for a sandbox
+pub const CLK_TCK_ANNOTATION: &str = "kata-pulse.io/clk-tck";
+
+// Prefix of the well-known annotations set by the CRI runtime on pod sandboxes
+pub const CRI_ANNOTATION_PREFIX: &str = "io.kubernetes.cri.";
+
+// Kata runtime storage directories, overridable from the configuration file
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoragePaths {
+    // Sandbox state of the Go runtime
+    pub sandboxes: PathBuf,
+    // Sandbox state of the Rust runtime
+    pub sandboxes_rust: PathBuf,
+    // VM (hypervisor) state of the Go runtime
+    pub vm: PathBuf,
+}
+
+impl Default for StoragePaths {
+    fn default() -> Self {
+        StoragePaths {
+            sandboxes: PathBuf::from("/run/vc/sbs"),
+            sandboxes_rust: PathBuf::from("/run/kata"),
+            vm: PathBuf::from("/run/vc/vm"),
+        }
+    }
+}
+
+static STORAGE_PATHS: OnceLock<StoragePaths> = OnceLock::new();
+
+// Override the storage directories; must run before any of them is used
+pub fn set_storage_paths(paths: StoragePaths) -> anyhow::Result<()> {
+    STORAGE_PATHS
+        .set(paths)
+        .map_err(|_| anyhow::anyhow!("storage paths are already in use"))
+}
+
+fn storage_paths() -> &'static StoragePaths {
+    STORAGE_PATHS.get_or_init(StoragePaths::default)
+}
+
 // Get the storage path where sandboxes info are stored (Go runtime)
 pub fn get_sandboxes_storage_path() -> PathBuf {
-    PathBuf::from("/run/vc/sbs")
+    storage_paths().sandboxes.clone()
 }
 
 // Get the storage path where sandboxes info are stored (Rust runtime)
 pub fn get_sandboxes_storage_path_rust() -> PathBuf {
-    PathBuf::from("/run/kata")
+    storage_paths().sandboxes_rust.clone()
+}
+
+// Get the storage path of the VM (hypervisor) state of sandboxes (Go runtime)
+pub fn get_vm_storage_path() -> PathBuf {
+    storage_paths().vm.clone()
 }
 
 // Get socket path for the given storage path
@@ -28,18 +84,26 @@ pub fn socket_path_rust(id: &str) -> PathBuf {
     socket_path(id, &get_sandboxes_storage_path_rust())
 }
 
-// Get the client socket address
+// Flavor of the Kata runtime (shim) serving a sandbox
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuntimeFlavor {
+    Go,
+    Rust,
+}
+
+// Resolve the shim monitor socket of a sandbox
 // Tries both Go and Rust runtime socket paths
-pub fn client_socket_address(id: &str) -> anyhow::Result<String> {
+pub fn resolve_shim_socket(id: &str) -> anyhow::Result<(RuntimeFlavor, PathBuf)> {
     let go_socket = socket_path_go(id);
 
     if go_socket.exists() {
-        return Ok(format!("unix://{}", go_socket.display()));
+        return Ok((RuntimeFlavor::Go, go_socket));
     }
 
     let rust_socket = socket_path_rust(id);
     if rust_socket.exists() {
-        return Ok(format!("unix://{}", rust_socket.display()));
+        return Ok((RuntimeFlavor::Rust, rust_socket));
     }
 
     Err(anyhow::anyhow!(
@@ -49,3 +113,115 @@ pub fn client_socket_address(id: &str) -> anyhow::Result<String> {
         rust_socket.display()
     ))
 }
+
+// Parse a human-readable duration such as "10s", "5m", "1h" or a bare number of seconds
+pub fn parse_duration(value: &str) -> anyhow::Result<Duration> {
+    let value = value.trim();
+    let (number, multiplier) = if let Some(n) = value.strip_suffix("ms") {
+        (n, 0.001)
+    } else if let Some(n) = value.strip_suffix('s') {
+        (n, 1.0)
+    } else if let Some(n) = value.strip_suffix('m') {
+        (n, 60.0)
+    } else if let Some(n) = value.strip_suffix('h') {
+        (n, 3600.0)
+    } else {
+        (value, 1.0)
+    };
+
+    let number: f64 = number
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("invalid duration: {:?}", value))?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(anyhow::anyhow!("invalid duration: {:?}", value));
+    }
+
+    Ok(Duration::from_secs_f64(number * multiplier))
+}
+
+// Parse a human-readable size such as "100MiB", "512KiB", "1GiB" or a bare number of bytes
+pub fn parse_size(value: &str) -> anyhow::Result<u64> {
+    let value = value.trim();
+    let units: [(&str, u64); 6] = [
+        ("KiB", 1 << 10),
+        ("MiB", 1 << 20),
+        ("GiB", 1 << 30),
+        ("K", 1 << 10),
+        ("M", 1 << 20),
+        ("G", 1 << 30),
+    ];
+    let (number, multiplier) = units
+        .iter()
+        .find_map(|(suffix, multiplier)| value.strip_suffix(suffix).map(|n| (n, *multiplier)))
+        .unwrap_or((value.strip_suffix('B').unwrap_or(value), 1));
+
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| anyhow::anyhow!("invalid size: {:?}", value))
+}
+
+// Parse a probability between 0 and 1, such as "0.05"
+pub fn parse_probability(value: &str) -> anyhow::Result<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|p| (0.0..=1.0).contains(p))
+        .ok_or_else(|| anyhow::anyhow!("invalid probability: {:?} (expected 0 to 1)", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration_units() {
+        assert_eq!(parse_duration("10s").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1h").unwrap(), Duration::from_secs(3600));
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("45").unwrap(), Duration::from_secs(45));
+    }
+
+    #[test]
+    fn test_parse_duration_invalid() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("abc").is_err());
+        assert!(parse_duration("-5s").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100MiB").unwrap(), 100 * 1024 * 1024);
+        assert_eq!(parse_size("512K").unwrap(), 512 * 1024);
+        assert_eq!(parse_size("1GiB").unwrap(), 1 << 30);
+        assert_eq!(parse_size("4096").unwrap(), 4096);
+        assert_eq!(parse_size("10B").unwrap(), 10);
+        assert!(parse_size("1.5M").is_err());
+        assert!(parse_size("lots").is_err());
+    }
+
+    #[test]
+    fn test_parse_probability() {
+        assert_eq!(parse_probability("0").unwrap(), 0.0);
+        assert_eq!(parse_probability("0.05").unwrap(), 0.05);
+        assert_eq!(parse_probability("1").unwrap(), 1.0);
+        assert!(parse_probability("1.5").is_err());
+        assert!(parse_probability("-0.1").is_err());
+        assert!(parse_probability("NaN").is_err());
+        assert!(parse_probability("often").is_err());
+    }
+
+    #[test]
+    fn test_resolve_shim_socket_missing() {
+        let err = resolve_shim_socket("no-such-sandbox")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("/run/vc/sbs/no-such-sandbox/shim-monitor.sock"));
+        assert!(err.contains("/run/kata/no-such-sandbox/shim-monitor.sock"));
+    }
+}
@@ -0,0 +1,211 @@
+//! Configuration hot reload
+//!
+//! Responsibilities:
+//! - Hold the reload-safe settings (collection interval, sandbox and metric
+//!   filters, conversion rules) behind an `Arc` that is swapped as a whole,
+//!   so every reader sees either the old or the new settings
+//! - Watch the configuration file (inotify on its directory, so editors that
+//!   replace the file are seen) and run a reload callback on change
+//!
+//! Settings that need a restart (listen address, runtime endpoints,
+//! exporters, ...) are read once at startup.
+
+use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use crate::monitor::sandbox_filter::SandboxFilter;
+use crate::utils::metrics_converter::ConversionConfig;
+use crate::utils::prometheus_parser::NameFilter;
+
+/// Time to wait for more events after a change, so a burst of writes reloads once
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Settings applied without a restart
+#[derive(Clone, Debug)]
+pub struct ReloadableConfig {
+    /// Global metrics collection interval in seconds
+    pub metrics_interval_secs: u64,
+
+    /// Which sandboxes are collected and served
+    pub sandbox_filter: SandboxFilter,
+
+    /// Metric families dropped while parsing shim metrics
+    pub name_filter: NameFilter,
+
+    /// How shim metrics are converted to the served format
+    pub conversion: ConversionConfig,
+}
+
+impl ReloadableConfig {
+    /// Global metrics collection interval
+    pub fn metrics_interval(&self) -> Duration {
+        Duration::from_secs(self.metrics_interval_secs)
+    }
+
+    /// Check the settings
+    pub fn validate(&self) -> Result<()> {
+        if self.metrics_interval_secs == 0 {
+            anyhow::bail!("metrics_interval_secs must be > 0");
+        }
+        self.conversion.validate()
+    }
+}
+
+/// The current reloadable settings, shared by the collector, exporters and server
+pub struct SharedConfig {
+    current: RwLock<Arc<ReloadableConfig>>,
+}
+
+impl SharedConfig {
+    /// Share `config` as the current settings
+    pub fn new(config: ReloadableConfig) -> Self {
+        SharedConfig {
+            current: RwLock::new(Arc::new(config)),
+        }
+    }
+
+    /// The current settings; hold the returned `Arc` for a consistent view
+    pub fn load(&self) -> Arc<ReloadableConfig> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the current settings; invalid settings are rejected and the old ones kept
+    pub fn store(&self, config: ReloadableConfig) -> Result<()> {
+        config.validate()?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+        Ok(())
+    }
+}
+
+/// Run `reload` whenever the file at `path` changes, until `cancel` fires
+///
+/// A failed reload is logged and the running settings are kept.
+pub async fn watch(
+    path: PathBuf,
+    cancel: CancellationToken,
+    mut reload: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(std::path::Path::new("."))
+        .to_path_buf();
+    let file_name = path
+        .file_name()
+        .context("configuration path has no file name")?
+        .to_os_string();
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut watcher =
+        notify::recommended_watcher(move |event: notify::Result<notify::Event>| match event {
+            // Reading the file (access events) must not trigger another reload
+            Ok(event)
+                if (event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove())
+                    && event
+                        .paths
+                        .iter()
+                        .any(|p| p.file_name() == Some(&file_name)) =>
+            {
+                let _ = tx.send(());
+            }
+            Ok(_) => {}
+            Err(e) => warn!(error = %e, "Configuration file watch error"),
+        })
+        .context("failed to create configuration file watcher")?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .with_context(|| format!("failed to watch {}", dir.display()))?;
+    info!(path = %path.display(), "Watching configuration file for changes");
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            event = rx.recv() => {
+                if event.is_none() {
+                    break;
+                }
+            }
+        }
+
+        // Let the writer finish, then reload once for the whole burst
+        tokio::time::sleep(DEBOUNCE).await;
+        while rx.try_recv().is_ok() {}
+        debug!(path = %path.display(), "Configuration file changed");
+        match reload() {
+            Ok(()) => info!(path = %path.display(), "Configuration reloaded"),
+            Err(e) => {
+                warn!(path = %path.display(), error = %format!("{:#}", e), "Failed to reload configuration, keeping the running settings")
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn config(interval: u64) -> ReloadableConfig {
+        ReloadableConfig {
+            metrics_interval_secs: interval,
+            sandbox_filter: SandboxFilter::default(),
+            name_filter: NameFilter::default(),
+            conversion: ConversionConfig::default(),
+        }
+    }
+
+    #[test]
+    fn test_store_swaps_and_rejects_invalid() {
+        let shared = SharedConfig::new(config(60));
+        let before = shared.load();
+        shared.store(config(30)).unwrap();
+        assert!(shared.store(config(0)).is_err());
+        assert_eq!(before.metrics_interval_secs, 60);
+        assert_eq!(shared.load().metrics_interval(), Duration::from_secs(30));
+    }
+
+    #[tokio::test]
+    async fn test_watch_reloads_on_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "metrics_interval_secs = 60\n").unwrap();
+
+        let reloads = Arc::new(AtomicUsize::new(0));
+        let cancel = CancellationToken::new();
+        let task = tokio::spawn(watch(path.clone(), cancel.clone(), {
+            let reloads = reloads.clone();
+            move || {
+                reloads.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }));
+
+        // Give the watcher time to start, then replace the file like an editor would
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        std::fs::write(dir.path().join("other.toml"), "ignored").unwrap();
+        let tmp = dir.path().join("config.toml.tmp");
+        std::fs::write(&tmp, "metrics_interval_secs = 30\n").unwrap();
+        std::fs::rename(&tmp, &path).unwrap();
+
+        for _ in 0..50 {
+            if reloads.load(Ordering::SeqCst) > 0 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(reloads.load(Ordering::SeqCst), 1);
+
+        cancel.cancel();
+        task.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,1285 @@
+//! Settings of kata-pulse
+//!
+//! Responsibilities:
+//! - Define every setting once, as a typed field of [`Settings`] with its
+//!   command line flag, `KATA_PULSE_*` environment variable and default
+//! - Layer the sources: command line > environment > configuration file >
+//!   defaults, so no module reads the environment itself
+//! - Derive the settings of the components (CRI and shim clients, reloadable
+//!   settings) from the layered values
+
+use anyhow::{Context, Result};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use super::file::ConfigFile;
+use super::reload::ReloadableConfig;
+use crate::aggregator::{AggregateTarget, AggregatorConfig, DEFAULT_AGGREGATE_TIMEOUT};
+use crate::auth::{TokenReviewConfig, DEFAULT_TOKEN_REVIEW_CACHE_TTL};
+use crate::monitor::agent_stats::{AgentStatsConfig, DEFAULT_AGENT_STATS_TIMEOUT};
+use crate::monitor::chaos::ChaosConfig;
+use crate::monitor::cri::CRIClientConfig;
+use crate::monitor::janitor::{JanitorConfig, DEFAULT_JANITOR_GRACE_PERIOD};
+use crate::monitor::k8s_pods::PodWatchConfig;
+use crate::monitor::recording::DEFAULT_RECORD_MAX_FILES;
+use crate::monitor::sandbox_cache_manager::DEFAULT_METADATA_RETRY_BUDGET;
+use crate::monitor::sandbox_filter::SandboxFilter;
+use crate::utils::metrics_converter::ConversionConfig;
+use crate::utils::prometheus_parser::NameFilter;
+use crate::utils::shim_client::ShimClientConfig;
+use crate::utils::systemd::SystemdNotify;
+use crate::{config, context, exporter, monitor, utils};
+
+pub const APP_NAME: &str = "kata-pulse";
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8090";
+const DEFAULT_RUNTIME_ENDPOINT: &str = "/run/containerd/containerd.sock";
+const DEFAULT_CRI_TIMEOUT: &str = "10s";
+const DEFAULT_CRI_MAX_RETRIES: u32 = 3;
+const DEFAULT_CRI_RETRY_BACKOFF: &str = "100ms";
+const DEFAULT_CRI_KEEPALIVE_INTERVAL: &str = "30s";
+const DEFAULT_CRI_KEEPALIVE_TIMEOUT: &str = "20s";
+const DEFAULT_CRI_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+const DEFAULT_LOG_LEVEL: &str = "info";
+const DEFAULT_METRICS_INTERVAL_SECS: u64 = 60;
+const DEFAULT_SCRAPE_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_SCRAPE_MAX_BACKOFF_CYCLES: u32 = 16;
+const DEFAULT_SHIM_MAX_RETRIES: u32 = 2;
+const DEFAULT_SHIM_RETRY_BACKOFF_MS: u64 = 100;
+const DEFAULT_SHIM_MAX_PAYLOAD_BYTES: usize = utils::shim_client::DEFAULT_MAX_PAYLOAD_BYTES;
+const DEFAULT_DROP_METRIC_PREFIXES: &str = "kata_firecracker_,kata_shim_go_,go_";
+const DEFAULT_NETWORK_INTERFACE_PATTERNS: &str = "eth0,veth.*,tap.*,tun.*";
+
+/// Default guest block devices reported in the disk metrics
+const DEFAULT_DISK_DEVICE_PATTERNS: &str = "vd.*,sd.*,xvd.*,nvme.*";
+
+/// Block major of virtio-blk disks in the Kata guest kernel
+const DEFAULT_REMOTE_WRITE_TIMEOUT: &str = "10s";
+const DEFAULT_REMOTE_WRITE_MAX_RETRIES: u32 = 3;
+const DEFAULT_REMOTE_WRITE_RETRY_BACKOFF: &str = "500ms";
+
+/// Environment variables read before every setting took a `KATA_PULSE_`
+/// name, as (setting, deprecated variable, current variable)
+const DEPRECATED_ENV: [(&str, &str, &str); 4] = [
+    (
+        "runtime_endpoint",
+        "RUNTIME_ENDPOINT",
+        "KATA_PULSE_RUNTIME_ENDPOINT",
+    ),
+    ("node_name", "NODE_NAME", "KATA_PULSE_NODE_NAME"),
+    ("log_level", "RUST_LOG", "KATA_PULSE_LOG_LEVEL"),
+    (
+        "cpu_jiffy_conversion_factor",
+        "KATA_PULSE_CLK_TCK",
+        "KATA_PULSE_CPU_JIFFY_CONVERSION_FACTOR",
+    ),
+];
+
+/// A deprecated environment variable that is set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeprecatedEnv {
+    pub deprecated: &'static str,
+    pub current: &'static str,
+    /// Whether the deprecated variable is read, i.e. the current one is unset
+    pub used: bool,
+}
+
+impl fmt::Display for DeprecatedEnv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.used {
+            write!(
+                f,
+                "{} is deprecated, set {} instead",
+                self.deprecated, self.current
+            )
+        } else {
+            write!(
+                f,
+                "{} is deprecated and ignored because {} is set",
+                self.deprecated, self.current
+            )
+        }
+    }
+}
+
+#[derive(Parser, Debug, Clone)]
+#[command(
+    name = APP_NAME,
+    version = VERSION,
+    about = "Real-time metrics for Kata Containers",
+    long_about = "KataPulse: cadvisor-compatible monitoring agent for Kata Containers. Provides metrics collection, sandbox management, and agent URL discovery"
+)]
+pub struct Settings {
+    /// Configuration file
+    #[arg(
+        long,
+        global = true,
+        env = "KATA_PULSE_CONFIG",
+        help = "TOML or YAML (.yaml/.yml) file holding any of these settings by name plus a [storage] section; the command line and environment win over the file"
+    )]
+    pub config: Option<PathBuf>,
+
+    /// The address to listen on for HTTP requests
+    #[arg(
+        long,
+        env = "KATA_PULSE_LISTEN",
+        default_value = DEFAULT_LISTEN_ADDRESS,
+        help = "The address to listen on for HTTP requests"
+    )]
+    pub listen_address: String,
+
+    /// Endpoints of CRI container runtime services
+    #[arg(
+        long,
+        env = "KATA_PULSE_RUNTIME_ENDPOINT",
+        default_value = DEFAULT_RUNTIME_ENDPOINT,
+        value_delimiter = ',',
+        help = "Endpoint of CRI container runtime service (repeat or comma-separate to try several in order)"
+    )]
+    pub runtime_endpoint: Vec<String>,
+
+    /// Timeout of CRI requests
+    #[arg(
+        long,
+        env = "KATA_PULSE_CRI_TIMEOUT",
+        default_value = DEFAULT_CRI_TIMEOUT,
+        value_parser = config::parse_duration,
+        help = "Timeout of CRI connections and requests, e.g. 10s"
+    )]
+    pub cri_timeout: Duration,
+
+    /// Maximum retries of failed CRI requests
+    #[arg(
+        long,
+        env = "KATA_PULSE_CRI_MAX_RETRIES",
+        default_value_t = DEFAULT_CRI_MAX_RETRIES,
+        help = "Maximum retries of failed CRI requests"
+    )]
+    pub cri_max_retries: u32,
+
+    /// Backoff between CRI request retries
+    #[arg(
+        long,
+        env = "KATA_PULSE_CRI_RETRY_BACKOFF",
+        default_value = DEFAULT_CRI_RETRY_BACKOFF,
+        value_parser = config::parse_duration,
+        help = "Backoff between retries of failed CRI requests, e.g. 100ms"
+    )]
+    pub cri_retry_backoff: Duration,
+
+    /// Interval of gRPC keepalive pings on the CRI connection
+    #[arg(
+        long,
+        env = "KATA_PULSE_CRI_KEEPALIVE_INTERVAL",
+        default_value = DEFAULT_CRI_KEEPALIVE_INTERVAL,
+        value_parser = config::parse_duration,
+        help = "Interval of gRPC keepalive pings on the CRI connection, e.g. 30s (0 disables keepalive)"
+    )]
+    pub cri_keepalive_interval: Duration,
+
+    /// Timeout of gRPC keepalive pings on the CRI connection
+    #[arg(
+        long,
+        env = "KATA_PULSE_CRI_KEEPALIVE_TIMEOUT",
+        default_value = DEFAULT_CRI_KEEPALIVE_TIMEOUT,
+        value_parser = config::parse_duration,
+        help = "Time to wait for a keepalive ping acknowledgement before the CRI connection is closed"
+    )]
+    pub cri_keepalive_timeout: Duration,
+
+    /// Maximum size of a CRI response message
+    #[arg(
+        long,
+        env = "KATA_PULSE_CRI_MAX_MESSAGE_SIZE",
+        default_value_t = DEFAULT_CRI_MAX_MESSAGE_SIZE,
+        help = "Maximum size in bytes of a CRI response message (raise for very large pod lists)"
+    )]
+    pub cri_max_message_size: usize,
+
+    /// CRI metadata syncs a sandbox may miss before it is marked metadata_unavailable
+    #[arg(
+        long,
+        env = "KATA_PULSE_METADATA_RETRY_BUDGET",
+        default_value_t = DEFAULT_METADATA_RETRY_BUDGET,
+        help = "CRI metadata syncs (every 5s) a sandbox may miss before it is marked metadata_unavailable (0 = never)"
+    )]
+    pub metadata_retry_budget: u32,
+
+    /// Log level
+    #[arg(
+        long,
+        env = "KATA_PULSE_LOG_LEVEL",
+        default_value = DEFAULT_LOG_LEVEL,
+        help = "Log level (trace/debug/info/warn/error)"
+    )]
+    pub log_level: String,
+
+    /// Log output format
+    #[arg(
+        long,
+        env = "KATA_PULSE_LOG_FORMAT",
+        default_value_t = LogFormat::Text,
+        help = "Log output format: text, or json for one JSON object per line with the event fields (sandbox_id, pod, namespace, duration_ms, ...) at the top level"
+    )]
+    pub log_format: LogFormat,
+
+    /// File holding the bearer token of the admin endpoints
+    #[arg(
+        long,
+        env = "KATA_PULSE_ADMIN_TOKEN_FILE",
+        help = "File holding the bearer token of the admin endpoints (PUT /admin/loglevel), re-read on every request; admin endpoints are disabled without it"
+    )]
+    pub admin_token_file: Option<PathBuf>,
+
+    /// Authenticate the requests of the metrics endpoints with Kubernetes TokenReviews
+    #[arg(
+        long,
+        env = "KATA_PULSE_AUTH_TOKEN_REVIEW",
+        help = "Require a bearer token on /metrics, /sandboxes, /api/history and /debug/state, validated against the Kubernetes TokenReview API (in-cluster service account, needs the k8s feature)"
+    )]
+    pub auth_token_review: bool,
+
+    /// Audiences the bearer tokens must be valid for
+    #[arg(
+        long,
+        env = "KATA_PULSE_AUTH_AUDIENCES",
+        value_delimiter = ',',
+        help = "Audiences a bearer token must be valid for with --auth-token-review, e.g. kata-pulse (comma-separated; required)"
+    )]
+    pub auth_audiences: Vec<String>,
+
+    /// Time a successful TokenReview is cached
+    #[arg(
+        long,
+        env = "KATA_PULSE_AUTH_CACHE_TTL",
+        default_value = DEFAULT_TOKEN_REVIEW_CACHE_TTL,
+        value_parser = config::parse_duration,
+        help = "Time a successful TokenReview is cached, e.g. 2m (failed reviews are cached 30s)"
+    )]
+    pub auth_cache_ttl: Duration,
+
+    /// File mapping tenant bearer tokens to the namespaces they may see
+    #[arg(
+        long,
+        env = "KATA_PULSE_TENANTS_FILE",
+        help = "TOML (or YAML) file of tenants, each with a bearer token or Kubernetes users (with --auth-token-review) and the namespaces whose series and sandboxes it may see (reloaded on change; namespace \"*\" sees all)"
+    )]
+    pub tenants_file: Option<PathBuf>,
+
+    /// OTLP/gRPC endpoint the collection spans are exported to
+    #[arg(
+        long,
+        env = "OTEL_EXPORTER_OTLP_ENDPOINT",
+        help = "OTLP/gRPC endpoint the collection pipeline spans are exported to, e.g. http://tempo:4317 (requires the otlp feature; disabled by default)"
+    )]
+    pub otlp_endpoint: Option<String>,
+
+    /// Metrics collection interval in seconds
+    #[arg(
+        long,
+        env = "KATA_PULSE_METRICS_INTERVAL",
+        default_value_t = DEFAULT_METRICS_INTERVAL_SECS,
+        help = "Metrics collection interval in seconds"
+    )]
+    pub metrics_interval_secs: u64,
+
+    /// Maximum age of cached metrics before they stop being served
+    #[arg(
+        long,
+        env = "KATA_PULSE_METRICS_TTL",
+        help = "Maximum age in seconds of cached metrics that are still served (default: 3x metrics interval)"
+    )]
+    pub metrics_ttl_secs: Option<u64>,
+
+    /// Consecutive scrape failures before a sandbox is quarantined
+    #[arg(
+        long,
+        env = "KATA_PULSE_SCRAPE_FAILURE_THRESHOLD",
+        default_value_t = DEFAULT_SCRAPE_FAILURE_THRESHOLD,
+        help = "Consecutive scrape failures before a sandbox is quarantined"
+    )]
+    pub scrape_failure_threshold: u32,
+
+    /// Maximum number of collection cycles a quarantined sandbox is skipped
+    #[arg(
+        long,
+        env = "KATA_PULSE_SCRAPE_MAX_BACKOFF_CYCLES",
+        default_value_t = DEFAULT_SCRAPE_MAX_BACKOFF_CYCLES,
+        help = "Maximum number of collection cycles skipped between probes of a quarantined sandbox"
+    )]
+    pub scrape_max_backoff_cycles: u32,
+
+    /// Maximum retries when connecting to a shim socket fails
+    #[arg(
+        long,
+        env = "KATA_PULSE_SHIM_MAX_RETRIES",
+        default_value_t = DEFAULT_SHIM_MAX_RETRIES,
+        help = "Maximum retries when connecting to a shim monitor socket fails"
+    )]
+    pub shim_max_retries: u32,
+
+    /// Initial backoff between shim connection retries
+    #[arg(
+        long,
+        env = "KATA_PULSE_SHIM_RETRY_BACKOFF_MS",
+        default_value_t = DEFAULT_SHIM_RETRY_BACKOFF_MS,
+        help = "Initial backoff in milliseconds between shim connection retries (doubled per retry, with jitter)"
+    )]
+    pub shim_retry_backoff_ms: u64,
+
+    /// Largest accepted shim response body
+    #[arg(
+        long,
+        env = "KATA_PULSE_SHIM_MAX_PAYLOAD_BYTES",
+        default_value_t = DEFAULT_SHIM_MAX_PAYLOAD_BYTES,
+        help = "Maximum size in bytes of a shim metrics response (after gzip decoding); larger responses fail the scrape"
+    )]
+    pub shim_max_payload_bytes: usize,
+
+    /// File to persist the metrics cache to for warm restarts
+    #[arg(
+        long,
+        env = "KATA_PULSE_CACHE_SNAPSHOT",
+        help = "File to persist the metrics cache to for warm restarts, e.g. /var/lib/kata-pulse/cache/metrics.json (disabled by default)"
+    )]
+    pub cache_snapshot_path: Option<PathBuf>,
+
+    /// Number of scrapes kept per sandbox for /api/history
+    #[arg(
+        long,
+        env = "KATA_PULSE_HISTORY_LEN",
+        default_value_t = context::DEFAULT_HISTORY_LEN,
+        help = "Number of scrapes kept per sandbox in memory for /api/history (0 disables history)"
+    )]
+    pub history_len: usize,
+
+    /// Blocking workers converting sandbox metrics
+    #[arg(
+        long,
+        env = "KATA_PULSE_CONVERSION_WORKERS",
+        default_value_t = 0,
+        help = "Threads converting sandbox metrics off the async runtime (0 uses one per CPU)"
+    )]
+    pub conversion_workers: usize,
+
+    /// Name of the node (Kubernetes downward API)
+    #[arg(
+        long,
+        env = "KATA_PULSE_NODE_NAME",
+        help = "Name of the node, reported in kata_pulse_node_info (set from the downward API spec.nodeName)"
+    )]
+    pub node_name: Option<String>,
+
+    /// Topology zone of the node
+    #[arg(
+        long,
+        env = "KATA_PULSE_NODE_ZONE",
+        help = "Topology zone of the node, reported in kata_pulse_node_info"
+    )]
+    pub node_zone: Option<String>,
+
+    /// Topology region of the node
+    #[arg(
+        long,
+        env = "KATA_PULSE_NODE_REGION",
+        help = "Topology region of the node, reported in kata_pulse_node_info"
+    )]
+    pub node_region: Option<String>,
+
+    /// Watch the pods of the node through the Kubernetes API
+    #[arg(
+        long,
+        env = "KATA_PULSE_ENABLE_K8S_API",
+        help = "Watch the pods of the node through the Kubernetes API (in-cluster service account, needs --node-name and the k8s feature) and add their --k8s-pod-labels to the series"
+    )]
+    pub enable_k8s_api: bool,
+
+    /// Pod labels added to the series when the pod watch is enabled
+    #[arg(
+        long,
+        env = "KATA_PULSE_K8S_POD_LABELS",
+        value_delimiter = ',',
+        default_value = "app,team",
+        help = "Pod labels added to the series as label_<name> when --enable-k8s-api is set (comma-separated)"
+    )]
+    pub k8s_pod_labels: Vec<String>,
+
+    /// Detect sandbox directories whose shim and pod are gone
+    #[arg(
+        long,
+        env = "KATA_PULSE_JANITOR",
+        help = "Detect stale sandbox directories (shim gone and pod no longer reported by CRI) and report them in /debug/state and kata_pulse_stale_sandboxes"
+    )]
+    pub janitor: bool,
+
+    /// Time a sandbox must be stale before the janitor reports it
+    #[arg(
+        long,
+        env = "KATA_PULSE_JANITOR_GRACE_PERIOD",
+        default_value = DEFAULT_JANITOR_GRACE_PERIOD,
+        value_parser = config::parse_duration,
+        help = "Time a sandbox directory must be stale before --janitor reports (and removes) it, e.g. 10m"
+    )]
+    pub janitor_grace_period: Duration,
+
+    /// Remove the stale sandbox directories instead of only reporting them
+    #[arg(
+        long,
+        env = "KATA_PULSE_JANITOR_REMOVE",
+        help = "Remove the stale sandbox directories found by --janitor (default: dry run, only report them)"
+    )]
+    pub janitor_remove: bool,
+
+    /// Query the kata-agents for per-container stats
+    #[arg(
+        long,
+        env = "KATA_PULSE_AGENT_STATS",
+        help = "Query the kata-agent in each guest (ttrpc over vsock, at the agent URL of the shim) for per-container CPU and memory stats, served as kata_guest_container_* series"
+    )]
+    pub agent_stats: bool,
+
+    /// Timeout of each agent stats query
+    #[arg(
+        long,
+        env = "KATA_PULSE_AGENT_STATS_TIMEOUT",
+        default_value = DEFAULT_AGENT_STATS_TIMEOUT,
+        value_parser = config::parse_duration,
+        help = "Timeout of the agent URL lookup and of each kata-agent call of --agent-stats, e.g. 2s"
+    )]
+    pub agent_stats_timeout: Duration,
+
+    /// Namespaces whose sandboxes are collected (all if empty)
+    #[arg(
+        long,
+        env = "KATA_PULSE_NAMESPACE_ALLOW",
+        value_delimiter = ',',
+        help = "Only collect sandboxes in these namespaces (comma-separated, default: all)"
+    )]
+    pub namespace_allow: Vec<String>,
+
+    /// Namespaces whose sandboxes are never collected
+    #[arg(
+        long,
+        env = "KATA_PULSE_NAMESPACE_DENY",
+        value_delimiter = ',',
+        help = "Never collect sandboxes in these namespaces (comma-separated)"
+    )]
+    pub namespace_deny: Vec<String>,
+
+    /// CRI runtime handlers whose sandboxes are collected (all if empty)
+    #[arg(
+        long,
+        env = "KATA_PULSE_RUNTIME_HANDLERS",
+        value_delimiter = ',',
+        help = "Only collect sandboxes created by these CRI runtime handlers, e.g. kata,kata-qemu (comma-separated, default: all)"
+    )]
+    pub runtime_handlers: Vec<String>,
+
+    /// Skip sandboxes marked metadata_unavailable
+    #[arg(
+        long,
+        env = "KATA_PULSE_EXCLUDE_METADATA_UNAVAILABLE",
+        help = "Do not collect sandboxes marked metadata_unavailable (no CRI pod after the retry budget)"
+    )]
+    pub exclude_metadata_unavailable: bool,
+
+    /// Disable the separate virtiofsd metric families
+    #[arg(
+        long,
+        env = "KATA_PULSE_DISABLE_VIRTIOFSD_METRICS",
+        help = "Do not emit the kata_pod_virtiofsd_* metric families (virtiofsd threads/fds still count towards the process totals)"
+    )]
+    pub disable_virtiofsd_metrics: bool,
+
+    /// Emit per-CPU usage series
+    #[arg(
+        long,
+        env = "KATA_PULSE_INCLUDE_PER_CPU",
+        help = "Emit per-CPU series of container_cpu_usage_seconds_total in addition to the total"
+    )]
+    pub include_per_cpu: bool,
+
+    /// Emit per-interface network series
+    #[arg(
+        long,
+        env = "KATA_PULSE_INCLUDE_PER_INTERFACE",
+        help = "Emit network series per interface matching --network-interface-patterns instead of one summed series"
+    )]
+    pub include_per_interface: bool,
+
+    /// Emit per-device disk series
+    #[arg(
+        long,
+        env = "KATA_PULSE_INCLUDE_PER_DEVICE",
+        help = "Emit disk I/O series per block device instead of one summed series"
+    )]
+    pub include_per_device: bool,
+
+    /// Guest network interfaces the network metrics are reported for
+    #[arg(
+        long,
+        env = "KATA_PULSE_NETWORK_INTERFACE_PATTERNS",
+        default_value = DEFAULT_NETWORK_INTERFACE_PATTERNS,
+        value_delimiter = ',',
+        help = "Guest network interfaces to report: exact names or prefix.* (repeat or comma-separate)"
+    )]
+    pub network_interface_patterns: Vec<String>,
+
+    /// Guest block devices the disk metrics are reported for
+    #[arg(
+        long,
+        env = "KATA_PULSE_DISK_DEVICE_PATTERNS",
+        default_value = DEFAULT_DISK_DEVICE_PATTERNS,
+        value_delimiter = ',',
+        help = "Guest block devices to report: exact names or prefix.* (repeat or comma-separate); loop and pmem devices are excluded by default"
+    )]
+    pub disk_device_patterns: Vec<String>,
+
+    /// Block majors of guest disks whose driver has a dynamic major
+    #[arg(
+        long,
+        env = "KATA_PULSE_GUEST_BLOCK_MAJORS",
+        value_delimiter = ',',
+        help = "Block majors of guest disks named <prefix><letters>, as prefix=major (e.g. vd=254, as in the guest's /proc/devices), used for the major/minor labels of per-device disk series (repeat or comma-separate; empty labels if unset)"
+    )]
+    pub guest_block_majors: Vec<String>,
+
+    /// Guest clock ticks per second used to convert CPU jiffies to seconds
+    #[arg(
+        long,
+        env = "KATA_PULSE_CPU_JIFFY_CONVERSION_FACTOR",
+        help = "Clock ticks per second of the guest CPU times (USER_HZ); detected from the host (sysconf) if unset"
+    )]
+    pub cpu_jiffy_conversion_factor: Option<f64>,
+
+    /// Host cgroup hierarchy to read the sandbox overhead from
+    #[arg(
+        long,
+        env = "KATA_PULSE_HOST_CGROUP_ROOT",
+        default_value = monitor::host_cgroup::DEFAULT_CGROUP_ROOT,
+        help = "Mount point of the host cgroup hierarchy, read for kata_overhead_* metrics (empty disables them)"
+    )]
+    pub host_cgroup_root: PathBuf,
+
+    /// Host procfs to sample the hypervisor processes from
+    #[arg(
+        long,
+        env = "KATA_PULSE_HOST_PROC_ROOT",
+        default_value = monitor::hypervisor_proc::DEFAULT_PROC_ROOT,
+        help = "Mount point of the host procfs, read for kata_vmm_* metrics (requires the host PID namespace; empty disables them)"
+    )]
+    pub host_proc_root: PathBuf,
+
+    /// How malformed lines in shim metrics are treated
+    #[arg(
+        long,
+        env = "KATA_PULSE_PARSE_MODE",
+        default_value = "lenient",
+        help = "How malformed lines in shim metrics are treated: lenient (skip the lines) or strict (fail the scrape)"
+    )]
+    pub parse_mode: utils::prometheus_parser::ParseMode,
+
+    /// Metric name prefixes dropped while parsing shim metrics
+    #[arg(
+        long,
+        env = "KATA_PULSE_DROP_METRIC_PREFIXES",
+        default_value = DEFAULT_DROP_METRIC_PREFIXES,
+        value_delimiter = ',',
+        help = "Drop shim metric families with these name prefixes while parsing, before they are allocated (comma-separated, empty keeps all)"
+    )]
+    pub drop_metric_prefixes: Vec<String>,
+
+    /// Prometheus remote_write endpoint the converted metrics are pushed to
+    #[arg(
+        long,
+        env = "KATA_PULSE_REMOTE_WRITE_URL",
+        help = "Prometheus remote_write endpoint the converted metrics are pushed to after every collection cycle, e.g. http://prometheus:9090/api/v1/write (disabled by default)"
+    )]
+    pub remote_write_url: Option<String>,
+
+    /// Username for basic authentication against the remote_write endpoint
+    #[arg(
+        long,
+        env = "KATA_PULSE_REMOTE_WRITE_USERNAME",
+        help = "Username for basic authentication against the remote_write endpoint"
+    )]
+    pub remote_write_username: Option<String>,
+
+    /// Password for basic authentication against the remote_write endpoint
+    #[arg(
+        long,
+        env = "KATA_PULSE_REMOTE_WRITE_PASSWORD",
+        hide_env_values = true,
+        help = "Password for basic authentication against the remote_write endpoint"
+    )]
+    pub remote_write_password: Option<String>,
+
+    /// File holding a bearer token for the remote_write endpoint
+    #[arg(
+        long,
+        env = "KATA_PULSE_REMOTE_WRITE_BEARER_TOKEN_FILE",
+        help = "File holding a bearer token for the remote_write endpoint, re-read before every push"
+    )]
+    pub remote_write_bearer_token_file: Option<PathBuf>,
+
+    /// Timeout of a single remote_write request
+    #[arg(
+        long,
+        env = "KATA_PULSE_REMOTE_WRITE_TIMEOUT",
+        default_value = DEFAULT_REMOTE_WRITE_TIMEOUT,
+        value_parser = config::parse_duration,
+        help = "Timeout of a single remote_write request, e.g. 10s"
+    )]
+    pub remote_write_timeout: Duration,
+
+    /// Maximum retries of a failed remote_write push
+    #[arg(
+        long,
+        env = "KATA_PULSE_REMOTE_WRITE_MAX_RETRIES",
+        default_value_t = DEFAULT_REMOTE_WRITE_MAX_RETRIES,
+        help = "Maximum retries of a failed remote_write push (transport errors, 5xx and 429 responses)"
+    )]
+    pub remote_write_max_retries: u32,
+
+    /// Backoff before the first retry of a failed remote_write push
+    #[arg(
+        long,
+        env = "KATA_PULSE_REMOTE_WRITE_RETRY_BACKOFF",
+        default_value = DEFAULT_REMOTE_WRITE_RETRY_BACKOFF,
+        value_parser = config::parse_duration,
+        help = "Backoff before the first retry of a failed remote_write push, doubled per retry, e.g. 500ms"
+    )]
+    pub remote_write_retry_backoff: Duration,
+
+    /// StatsD server the key converted metrics are sent to
+    #[arg(
+        long,
+        env = "KATA_PULSE_STATSD_ADDRESS",
+        help = "StatsD server (host:port) the key converted metrics are sent to over UDP after every collection cycle, e.g. 127.0.0.1:8125 (disabled by default)"
+    )]
+    pub statsd_address: Option<String>,
+
+    /// Prefix of the StatsD metric names
+    #[arg(
+        long,
+        env = "KATA_PULSE_STATSD_PREFIX",
+        default_value = exporter::statsd::DEFAULT_PREFIX,
+        help = "Prefix of the StatsD metric names (empty for none)"
+    )]
+    pub statsd_prefix: String,
+
+    /// Line protocol of the StatsD server
+    #[arg(
+        long,
+        env = "KATA_PULSE_STATSD_FLAVOR",
+        default_value = "statsd",
+        help = "Line protocol of the StatsD server: statsd (labels folded into the name) or dogstatsd (labels as tags)"
+    )]
+    pub statsd_flavor: exporter::statsd::StatsdFlavor,
+
+    /// Prometheus Pushgateway the converted metrics are pushed to
+    #[arg(
+        long,
+        env = "KATA_PULSE_PUSHGATEWAY_URL",
+        help = "Prometheus Pushgateway the converted metrics are pushed to after every collection cycle, one group per pod, e.g. http://pushgateway:9091 (disabled by default)"
+    )]
+    pub pushgateway_url: Option<String>,
+
+    /// `job` grouping label of the Pushgateway groups
+    #[arg(
+        long,
+        env = "KATA_PULSE_PUSHGATEWAY_JOB",
+        default_value = exporter::pushgateway::DEFAULT_JOB,
+        help = "job grouping label of the Pushgateway groups"
+    )]
+    pub pushgateway_job: String,
+
+    /// Username for basic authentication against the Pushgateway
+    #[arg(
+        long,
+        env = "KATA_PULSE_PUSHGATEWAY_USERNAME",
+        help = "Username for basic authentication against the Pushgateway"
+    )]
+    pub pushgateway_username: Option<String>,
+
+    /// Password for basic authentication against the Pushgateway
+    #[arg(
+        long,
+        env = "KATA_PULSE_PUSHGATEWAY_PASSWORD",
+        hide_env_values = true,
+        help = "Password for basic authentication against the Pushgateway"
+    )]
+    pub pushgateway_password: Option<String>,
+
+    /// File holding a bearer token for the Pushgateway
+    #[arg(
+        long,
+        env = "KATA_PULSE_PUSHGATEWAY_BEARER_TOKEN_FILE",
+        help = "File holding a bearer token for the Pushgateway, re-read before every push"
+    )]
+    pub pushgateway_bearer_token_file: Option<PathBuf>,
+
+    /// JSON-lines file the converted metrics are appended to
+    #[arg(
+        long,
+        env = "KATA_PULSE_JSONL_PATH",
+        help = "File one JSON document per sandbox is appended to after every collection cycle, e.g. /var/lib/kata-pulse/metrics.jsonl (disabled by default)"
+    )]
+    pub jsonl_path: Option<PathBuf>,
+
+    /// Size after which the JSON-lines file is rotated
+    #[arg(
+        long,
+        env = "KATA_PULSE_JSONL_MAX_SIZE",
+        default_value = "100MiB",
+        value_parser = config::parse_size,
+        help = "Size after which the JSON-lines file is rotated, e.g. 100MiB"
+    )]
+    pub jsonl_max_size: u64,
+
+    /// Age after which the JSON-lines file is rotated
+    #[arg(
+        long,
+        env = "KATA_PULSE_JSONL_MAX_AGE",
+        default_value = "1h",
+        value_parser = config::parse_duration,
+        help = "Age after which the JSON-lines file is rotated, e.g. 1h"
+    )]
+    pub jsonl_max_age: Duration,
+
+    /// Number of rotated JSON-lines files kept
+    #[arg(
+        long,
+        env = "KATA_PULSE_JSONL_MAX_FILES",
+        default_value_t = exporter::jsonl::DEFAULT_MAX_FILES,
+        help = "Number of rotated JSON-lines files kept next to the current one"
+    )]
+    pub jsonl_max_files: usize,
+
+    /// Kafka bootstrap brokers the converted metrics are published to
+    #[arg(
+        long,
+        env = "KATA_PULSE_KAFKA_BROKERS",
+        help = "Kafka bootstrap brokers (comma-separated host:port) each sandbox's converted metrics are published to after every collection cycle (requires the kafka feature; disabled by default)"
+    )]
+    pub kafka_brokers: Option<String>,
+
+    /// Kafka topic the converted metrics are published to
+    #[arg(
+        long,
+        env = "KATA_PULSE_KAFKA_TOPIC",
+        default_value = exporter::kafka::DEFAULT_TOPIC,
+        help = "Kafka topic the records are published to (keyed by sandbox ID)"
+    )]
+    pub kafka_topic: String,
+
+    /// Payload encoding of the Kafka records
+    #[arg(
+        long,
+        env = "KATA_PULSE_KAFKA_FORMAT",
+        default_value = "json",
+        help = "Payload encoding of the Kafka records: json or protobuf (remote_write WriteRequest)"
+    )]
+    pub kafka_format: exporter::kafka::KafkaFormat,
+
+    /// Time a Kafka record may wait for delivery
+    #[arg(
+        long,
+        env = "KATA_PULSE_KAFKA_TIMEOUT",
+        default_value = "10s",
+        value_parser = config::parse_duration,
+        help = "Time a Kafka record may wait for delivery (including retries) before the push fails, e.g. 10s"
+    )]
+    pub kafka_timeout: Duration,
+
+    /// Output the key converted metrics are written to as CloudWatch EMF
+    #[arg(
+        long,
+        env = "KATA_PULSE_EMF_OUTPUT",
+        help = "Write the key converted metrics of every pod as CloudWatch Embedded Metric Format after every collection cycle: stdout or a file path (disabled by default)"
+    )]
+    pub emf_output: Option<exporter::emf::EmfOutput>,
+
+    /// CloudWatch namespace of the EMF metrics
+    #[arg(
+        long,
+        env = "KATA_PULSE_EMF_NAMESPACE",
+        default_value = exporter::emf::DEFAULT_NAMESPACE,
+        help = "CloudWatch namespace of the EMF metrics"
+    )]
+    pub emf_namespace: String,
+
+    /// Number of fake sandboxes to serve instead of the node's
+    #[arg(
+        long,
+        env = "KATA_PULSE_MOCK_SANDBOXES",
+        default_value_t = 0,
+        help = "Serve N fake sandboxes with generated Kata metrics instead of the node's (no containerd or shims needed; for developing dashboards and load testing)"
+    )]
+    pub mock_sandboxes: usize,
+
+    /// Probability that chaos mode fails a scrape
+    #[arg(
+        long,
+        env = "KATA_PULSE_CHAOS_SCRAPE_FAILURE_PROBABILITY",
+        default_value_t = 0.0,
+        value_parser = config::parse_probability,
+        help = "Chaos mode: probability (0 to 1) that a scrape fails without contacting the shim; for validating alerting, never in production"
+    )]
+    pub chaos_scrape_failure_probability: f64,
+
+    /// Probability that chaos mode delays a scrape
+    #[arg(
+        long,
+        env = "KATA_PULSE_CHAOS_SLOW_SCRAPE_PROBABILITY",
+        default_value_t = 0.0,
+        value_parser = config::parse_probability,
+        help = "Chaos mode: probability (0 to 1) that a scrape is delayed by --chaos-slow-scrape-delay"
+    )]
+    pub chaos_slow_scrape_probability: f64,
+
+    /// Delay of the scrapes slowed down by chaos mode
+    #[arg(
+        long,
+        env = "KATA_PULSE_CHAOS_SLOW_SCRAPE_DELAY",
+        default_value = "5s",
+        value_parser = config::parse_duration,
+        help = "Chaos mode: delay of the slowed down scrapes, e.g. 5s"
+    )]
+    pub chaos_slow_scrape_delay: Duration,
+
+    /// Probability that chaos mode times out a CRI metadata sync
+    #[arg(
+        long,
+        env = "KATA_PULSE_CHAOS_CRI_TIMEOUT_PROBABILITY",
+        default_value_t = 0.0,
+        value_parser = config::parse_probability,
+        help = "Chaos mode: probability (0 to 1) that a CRI metadata sync times out after --cri-timeout"
+    )]
+    pub chaos_cri_timeout_probability: f64,
+
+    /// Probability that chaos mode corrupts a scraped payload
+    #[arg(
+        long,
+        env = "KATA_PULSE_CHAOS_MALFORMED_PAYLOAD_PROBABILITY",
+        default_value_t = 0.0,
+        value_parser = config::parse_probability,
+        help = "Chaos mode: probability (0 to 1) that a scraped payload is truncated and gets malformed lines"
+    )]
+    pub chaos_malformed_payload_probability: f64,
+
+    /// Seed of the faults injected by chaos mode
+    #[arg(
+        long,
+        env = "KATA_PULSE_CHAOS_SEED",
+        help = "Chaos mode: seed of the random faults, to reproduce a run (random by default)"
+    )]
+    pub chaos_seed: Option<u64>,
+
+    /// Directory the raw shim payloads are recorded to
+    #[arg(
+        long,
+        env = "KATA_PULSE_RECORD_DIR",
+        help = "Record every raw shim payload under DIR/<sandbox-id>/<unix-ms>.prom, with the pod metadata, for replay with --replay-dir (disabled by default)"
+    )]
+    pub record_dir: Option<PathBuf>,
+
+    /// Number of recorded payloads kept per sandbox
+    #[arg(
+        long,
+        env = "KATA_PULSE_RECORD_MAX_FILES",
+        default_value_t = DEFAULT_RECORD_MAX_FILES,
+        help = "Recorded payloads kept per sandbox, the oldest being deleted (0 = keep all)"
+    )]
+    pub record_max_files: usize,
+
+    /// Directory of a recording to replay instead of scraping shims
+    #[arg(
+        long,
+        env = "KATA_PULSE_REPLAY_DIR",
+        conflicts_with = "mock_sandboxes",
+        help = "Serve the sandboxes recorded with --record-dir in DIR, feeding their payloads through the pipeline in recorded order instead of scraping shims"
+    )]
+    pub replay_dir: Option<PathBuf>,
+
+    /// kata-pulse instances scraped and merged in aggregator mode
+    #[arg(
+        long,
+        env = "KATA_PULSE_AGGREGATE_TARGETS",
+        value_delimiter = ',',
+        conflicts_with_all = ["mock_sandboxes", "replay_dir"],
+        help = "Aggregator mode: instead of reading local sandboxes, scrape these kata-pulse instances ([node=]http://host:port, comma-separated) every metrics interval and serve their merged /metrics and /sandboxes with a node label"
+    )]
+    pub aggregate_targets: Vec<AggregateTarget>,
+
+    /// Timeout of the scrape of one aggregated instance
+    #[arg(
+        long,
+        env = "KATA_PULSE_AGGREGATE_TIMEOUT",
+        default_value = DEFAULT_AGGREGATE_TIMEOUT,
+        value_parser = config::parse_duration,
+        help = "Aggregator mode: timeout of the scrape of one instance, e.g. 5s"
+    )]
+    pub aggregate_timeout: Duration,
+
+    /// File holding the bearer token sent to the aggregated instances
+    #[arg(
+        long,
+        env = "KATA_PULSE_AGGREGATE_BEARER_TOKEN_FILE",
+        help = "Aggregator mode: file holding the bearer token sent to the instances, re-read every round; it must see all namespaces there (a tenant with namespace \"*\" or, with --auth-token-review, a user of such a tenant)"
+    )]
+    pub aggregate_bearer_token_file: Option<PathBuf>,
+
+    /// Socket of the systemd notify protocol, set by systemd for Type=notify services
+    #[arg(long, env = "NOTIFY_SOCKET", hide = true)]
+    pub notify_socket: Option<String>,
+
+    /// Watchdog timeout of the service in microseconds, set by systemd for WatchdogSec=
+    #[arg(long, env = "WATCHDOG_USEC", hide = true)]
+    pub watchdog_usec: Option<u64>,
+
+    /// Process the watchdog applies to, set by systemd alongside WATCHDOG_USEC
+    #[arg(long, env = "WATCHDOG_PID", hide = true)]
+    pub watchdog_pid: Option<u32>,
+
+    /// Subcommand to run instead of the agent
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+}
+
+/// One-shot subcommands; the settings go before the subcommand name
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Commands {
+    /// Validate the node environment: CRI runtime, storage directories, shim sockets and CLK_TCK
+    Check {
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Fetch the metrics of a sandbox from its shim, print them and exit
+    Scrape {
+        /// Sandbox ID (directory name under the sandbox storage path)
+        sandbox_id: String,
+
+        /// Print the shim metrics as-is
+        #[arg(long, group = "format")]
+        raw: bool,
+
+        /// Print the converted metrics in the Prometheus text format (default)
+        #[arg(long, group = "format")]
+        cadvisor: bool,
+
+        /// Print the converted metrics as JSON
+        #[arg(long, group = "format")]
+        json: bool,
+    },
+
+    /// List the sandboxes of the node with their pod, runtime and shim socket
+    List,
+
+    /// Check the configuration file (--config) and print the effective configuration
+    ValidateConfig,
+}
+
+impl Settings {
+    /// The command line definition, reading the deprecated environment
+    /// variable of a setting whose current variable is unset
+    pub fn cli() -> clap::Command {
+        Self::deprecated_env()
+            .into_iter()
+            .filter(|env| env.used)
+            .fold(Self::command(), |command, env| {
+                let (id, ..) = DEPRECATED_ENV
+                    .iter()
+                    .find(|(_, deprecated, _)| *deprecated == env.deprecated)
+                    .expect("deprecated variables come from DEPRECATED_ENV");
+                command.mut_arg(*id, |arg| arg.env(env.deprecated))
+            })
+    }
+
+    /// Deprecated environment variables that are set, to warn about once
+    /// logging is up
+    pub fn deprecated_env() -> Vec<DeprecatedEnv> {
+        Self::deprecated_env_from(|name| std::env::var_os(name).is_some())
+    }
+
+    fn deprecated_env_from(is_set: impl Fn(&str) -> bool) -> Vec<DeprecatedEnv> {
+        DEPRECATED_ENV
+            .iter()
+            .filter(|(_, deprecated, _)| is_set(deprecated))
+            .map(|(_, deprecated, current)| DeprecatedEnv {
+                deprecated,
+                current,
+                used: !is_set(current),
+            })
+            .collect()
+    }
+
+    /// Layer the configuration file of `matches`, if any, under the command
+    /// line and environment; precedence is command line > environment >
+    /// file > defaults
+    pub fn load(matches: ArgMatches) -> Result<(Self, ConfigFile)> {
+        let (matches, file) = Self::resolve(&matches)?;
+        Ok((Self::from_arg_matches(&matches)?, file))
+    }
+
+    /// Matches of the command line with the settings it and the environment
+    /// leave unset filled from the configuration file of `matches`, if any
+    pub fn resolve(matches: &ArgMatches) -> Result<(ArgMatches, ConfigFile)> {
+        let Some(path) = matches.get_one::<PathBuf>("config") else {
+            return Ok((matches.clone(), Default::default()));
+        };
+
+        let file = ConfigFile::load(path)?;
+        let file_args = file
+            .args(&Self::cli(), matches)
+            .with_context(|| format!("invalid configuration file {}", path.display()))?;
+        // The file settings go before a subcommand, like the command line settings
+        let mut argv = std::env::args_os();
+        let matches = Self::cli()
+            .try_get_matches_from(argv.next().into_iter().chain(file_args).chain(argv))
+            .with_context(|| format!("invalid setting in configuration file {}", path.display()))?;
+        Ok((matches, file))
+    }
+
+    /// CRI client settings
+    pub fn cri_client(&self) -> CRIClientConfig {
+        CRIClientConfig::default()
+            .with_timeout(self.cri_timeout)
+            .with_max_retries(self.cri_max_retries)
+            .with_retry_backoff(self.cri_retry_backoff)
+            .with_keepalive(
+                Some(self.cri_keepalive_interval).filter(|interval| !interval.is_zero()),
+                self.cri_keepalive_timeout,
+            )
+            .with_max_message_size(self.cri_max_message_size)
+    }
+
+    /// Faults injected by chaos mode
+    pub fn chaos(&self) -> ChaosConfig {
+        ChaosConfig {
+            scrape_failure: self.chaos_scrape_failure_probability,
+            slow_scrape: self.chaos_slow_scrape_probability,
+            slow_scrape_delay: self.chaos_slow_scrape_delay,
+            cri_timeout: self.chaos_cri_timeout_probability,
+            malformed_payload: self.chaos_malformed_payload_probability,
+            seed: self.chaos_seed,
+        }
+    }
+
+    /// Shim client settings
+    pub fn shim_client(&self) -> ShimClientConfig {
+        ShimClientConfig::default()
+            .with_max_retries(self.shim_max_retries)
+            .with_retry_backoff(Duration::from_millis(self.shim_retry_backoff_ms))
+            .with_max_payload_bytes(self.shim_max_payload_bytes)
+    }
+
+    /// systemd notifications, if kata-pulse runs as a Type=notify service
+    ///
+    /// The watchdog is ignored when it is meant for another process.
+    pub fn systemd(&self) -> Option<SystemdNotify> {
+        let socket = self.notify_socket.clone().filter(|s| !s.is_empty())?;
+        let watchdog_usec = self.watchdog_usec.filter(|_| {
+            self.watchdog_pid
+                .is_none_or(|pid| pid == std::process::id())
+        });
+        Some(SystemdNotify::new(socket, watchdog_usec))
+    }
+
+    /// Settings of the Kubernetes pod watch, if enabled
+    pub fn pod_watch(&self) -> Option<PodWatchConfig> {
+        self.enable_k8s_api.then(|| {
+            PodWatchConfig::new(self.node_name.clone().unwrap_or_default())
+                .with_labels(self.k8s_pod_labels.clone())
+        })
+    }
+
+    /// Settings of the stale sandbox janitor, if enabled
+    pub fn janitor(&self) -> Option<JanitorConfig> {
+        self.janitor.then(|| {
+            JanitorConfig::new()
+                .with_grace_period(self.janitor_grace_period)
+                .with_remove(self.janitor_remove)
+        })
+    }
+
+    /// Settings of the agent stats collector, if enabled
+    pub fn agent_stats(&self) -> Option<AgentStatsConfig> {
+        self.agent_stats
+            .then(|| AgentStatsConfig::new().with_timeout(self.agent_stats_timeout))
+    }
+
+    /// Settings of the TokenReview authentication, if enabled
+    pub fn token_review(&self) -> Option<TokenReviewConfig> {
+        self.auth_token_review.then(|| {
+            TokenReviewConfig::new()
+                .with_audiences(self.auth_audiences.clone())
+                .with_cache_ttl(self.auth_cache_ttl)
+        })
+    }
+
+    /// Settings of the aggregator mode, if targets are configured
+    pub fn aggregator(&self) -> Option<AggregatorConfig> {
+        (!self.aggregate_targets.is_empty()).then(|| {
+            AggregatorConfig::new(self.aggregate_targets.clone())
+                .with_interval(Duration::from_secs(self.metrics_interval_secs))
+                .with_timeout(self.aggregate_timeout)
+                .with_bearer_token_file(self.aggregate_bearer_token_file.clone())
+        })
+    }
+
+    /// Settings that are reloaded without a restart
+    pub fn reloadable(&self) -> ReloadableConfig {
+        let mut conversion = ConversionConfig {
+            include_per_cpu: self.include_per_cpu,
+            include_per_interface: self.include_per_interface,
+            include_per_device: self.include_per_device,
+            network_interface_patterns: self.network_interface_patterns.clone(),
+            disk_device_patterns: self.disk_device_patterns.clone(),
+            guest_block_majors: self.guest_block_majors.clone(),
+            include_virtiofsd: !self.disable_virtiofsd_metrics,
+            ..Default::default()
+        };
+        if let Some(factor) = self.cpu_jiffy_conversion_factor {
+            conversion.cpu_jiffy_conversion_factor = factor;
+        }
+        ReloadableConfig {
+            metrics_interval_secs: self.metrics_interval_secs,
+            sandbox_filter: SandboxFilter::default()
+                .with_namespace_allow(self.namespace_allow.clone())
+                .with_namespace_deny(self.namespace_deny.clone())
+                .with_runtime_handlers(self.runtime_handlers.clone())
+                .with_exclude_metadata_unavailable(self.exclude_metadata_unavailable),
+            name_filter: NameFilter::new(self.drop_metric_prefixes.clone()),
+            conversion,
+        }
+    }
+
+    /// Whether settings that are only read at startup differ from `new`
+    pub fn restart_required(&self, new: &Settings) -> bool {
+        let old = self;
+        let mut new = new.clone();
+        new.metrics_interval_secs = old.metrics_interval_secs;
+        new.namespace_allow = old.namespace_allow.clone();
+        new.namespace_deny = old.namespace_deny.clone();
+        new.runtime_handlers = old.runtime_handlers.clone();
+        new.exclude_metadata_unavailable = old.exclude_metadata_unavailable;
+        new.drop_metric_prefixes = old.drop_metric_prefixes.clone();
+        new.disable_virtiofsd_metrics = old.disable_virtiofsd_metrics;
+        new.include_per_cpu = old.include_per_cpu;
+        new.include_per_interface = old.include_per_interface;
+        new.include_per_device = old.include_per_device;
+        new.network_interface_patterns = old.network_interface_patterns.clone();
+        new.disk_device_patterns = old.disk_device_patterns.clone();
+        new.guest_block_majors = old.guest_block_majors.clone();
+        new.cpu_jiffy_conversion_factor = old.cpu_jiffy_conversion_factor;
+        new.log_level = old.log_level.clone();
+        format!("{:?}", new) != format!("{:?}", old)
+    }
+}
+
+/// Output format of the logs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line, for log pipelines
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => anyhow::bail!("unknown log format {:?} (expected text or json)", s),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_setting_has_an_env_var() {
+        let command = Settings::command();
+        command.clone().debug_assert();
+        for arg in command
+            .get_arguments()
+            .filter(|arg| arg.get_long().is_some())
+        {
+            let id = arg.get_id().as_str();
+            // Variables of other protocols keep their standard names
+            if matches!(
+                id,
+                "help"
+                    | "version"
+                    | "otlp_endpoint"
+                    | "notify_socket"
+                    | "watchdog_usec"
+                    | "watchdog_pid"
+            ) {
+                continue;
+            }
+            let env = arg.get_env().and_then(|env| env.to_str());
+            assert!(
+                env.is_some_and(|env| env.starts_with("KATA_PULSE_")),
+                "{} has env {:?}",
+                id,
+                env
+            );
+        }
+    }
+
+    #[test]
+    fn test_deprecated_env() {
+        let command = Settings::command();
+        for (id, _, current) in DEPRECATED_ENV {
+            let arg = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .unwrap();
+            assert_eq!(arg.get_env().and_then(|env| env.to_str()), Some(current));
+        }
+
+        let set = ["RUNTIME_ENDPOINT", "RUST_LOG", "KATA_PULSE_LOG_LEVEL"];
+        let deprecated = Settings::deprecated_env_from(|name| set.contains(&name));
+        assert_eq!(
+            deprecated,
+            vec![
+                DeprecatedEnv {
+                    deprecated: "RUNTIME_ENDPOINT",
+                    current: "KATA_PULSE_RUNTIME_ENDPOINT",
+                    used: true,
+                },
+                DeprecatedEnv {
+                    deprecated: "RUST_LOG",
+                    current: "KATA_PULSE_LOG_LEVEL",
+                    used: false,
+                },
+            ]
+        );
+        assert_eq!(
+            deprecated[0].to_string(),
+            "RUNTIME_ENDPOINT is deprecated, set KATA_PULSE_RUNTIME_ENDPOINT instead"
+        );
+    }
+}
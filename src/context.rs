@@ -3,14 +3,244 @@
 //! This module provides a centralized container for all application singletons.
 //! All services are created once during startup and accessed through this context.
 
-use anyhow::Result;
-use std::sync::Arc;
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
+use crate::auth::{TokenReviewConfig, TokenReviewer};
+use crate::config::reload::{ReloadableConfig, SharedConfig};
+use crate::exporter::emf::{EmfConfig, EmfExporter};
+use crate::exporter::http::PushConfig;
+use crate::exporter::jsonl::{JsonlConfig, JsonlExporter};
+use crate::exporter::kafka::{self, KafkaConfig};
+use crate::exporter::pushgateway::{PushgatewayExporter, DEFAULT_JOB};
+use crate::exporter::remote_write::RemoteWriteExporter;
+use crate::exporter::statsd::{StatsdConfig, StatsdExporter};
+use crate::exporter::ExportPipeline;
+use crate::log_level::LogLevel;
+use crate::monitor::agent_stats::{AgentStats, AgentStatsConfig};
+use crate::monitor::chaos::{ChaosConfig, FaultInjector};
+use crate::monitor::conversion_pool::ConversionPool;
+use crate::monitor::cri::CRIClientConfig;
+use crate::monitor::host_cgroup::{HostCgroupReader, DEFAULT_CGROUP_ROOT};
+use crate::monitor::hypervisor_proc::{HypervisorProcReader, DEFAULT_PROC_ROOT};
+use crate::monitor::janitor::{Janitor, JanitorConfig};
+use crate::monitor::k8s_pods::{PodWatchConfig, PodWatcher};
 use crate::monitor::metrics_cache::MetricsCache;
 use crate::monitor::metrics_collector::MetricsCollector;
+use crate::monitor::mock_sandboxes::MockSandboxes;
+use crate::monitor::node_info::NodeInfo;
+use crate::monitor::pipeline_health::{Component, PipelineHealth};
+use crate::monitor::recording::{PayloadRecorder, PayloadReplay, DEFAULT_RECORD_MAX_FILES};
+use crate::monitor::rendered_metrics::RenderedMetrics;
+use crate::monitor::sandbox_boot::BootTracker;
 use crate::monitor::sandbox_cache::SandboxCache;
-use crate::monitor::sandbox_cache_manager::SandboxCacheManager;
-use crate::utils::metrics_converter::{CRILabelEnricher, LabelEnricher};
+use crate::monitor::sandbox_cache_manager::{SandboxCacheManager, DEFAULT_METADATA_RETRY_BUDGET};
+use crate::monitor::sandbox_filter::SandboxFilter;
+use crate::monitor::scrape_health::{CircuitBreakerConfig, ScrapeHealth};
+use crate::monitor::supervisor::{spawn_supervised, RestartPolicy};
+use crate::tenants::Tenants;
+use crate::utils::metrics_converter::{CRILabelEnricher, ConversionConfig, LabelEnricher};
+use crate::utils::prometheus_parser::{NameFilter, ParseMode};
+use crate::utils::shim_client::{ShimClient, ShimClientConfig};
+use crate::utils::systemd::SystemdNotify;
+
+/// Number of collection intervals after which cached metrics are considered stale
+/// when no explicit TTL is configured
+const DEFAULT_METRICS_TTL_INTERVALS: u64 = 3;
+
+/// Number of scrapes kept per sandbox in the in-memory history by default
+pub const DEFAULT_HISTORY_LEN: usize = 10;
+
+/// Options used to build the application context
+///
+/// Populated from CLI arguments and environment variables in `main`.
+#[derive(Clone, Debug)]
+pub struct AppOptions {
+    /// Endpoints of CRI container runtime services, tried in order
+    pub runtime_endpoints: Vec<String>,
+
+    /// Timeout and retry policy for CRI requests (the endpoint is ignored)
+    pub cri_client: CRIClientConfig,
+
+    /// CRI metadata syncs a sandbox may miss before it is marked
+    /// metadata_unavailable (0 = never)
+    pub metadata_retry_budget: u32,
+
+    /// Global metrics collection interval in seconds
+    pub metrics_interval_secs: u64,
+
+    /// Maximum age of cached metrics that are still served, in seconds
+    /// (defaults to three collection intervals)
+    pub metrics_ttl_secs: Option<u64>,
+
+    /// Circuit breaker settings for persistently failing sandboxes
+    pub circuit_breaker: CircuitBreakerConfig,
+
+    /// Timeout and retry policy for requests to shim monitor sockets
+    pub shim_client: ShimClientConfig,
+
+    /// File to persist the metrics cache to for warm restarts (disabled if None)
+    pub cache_snapshot_path: Option<PathBuf>,
+
+    /// Number of scrapes kept per sandbox for `/api/history` (0 = disabled)
+    pub history_len: usize,
+
+    /// Blocking workers converting sandbox metrics (0 = number of CPUs)
+    pub conversion_workers: usize,
+
+    /// Name and topology of the node kata-pulse runs on
+    pub node_info: NodeInfo,
+
+    /// Which sandboxes are collected and served
+    pub sandbox_filter: SandboxFilter,
+
+    /// How shim metrics are converted to the served format
+    pub conversion: ConversionConfig,
+
+    /// Host cgroup root to read the sandbox overhead from (disabled if None)
+    pub host_cgroup_root: Option<PathBuf>,
+
+    /// Host procfs to sample the hypervisor processes from (disabled if None)
+    pub host_proc_root: Option<PathBuf>,
+
+    /// Whether malformed lines in shim metrics are skipped or fail the scrape
+    pub parse_mode: ParseMode,
+
+    /// Metric families dropped while parsing shim metrics
+    pub name_filter: NameFilter,
+
+    /// Prometheus remote_write endpoint the converted metrics are pushed to (disabled if None)
+    pub remote_write: Option<PushConfig>,
+
+    /// StatsD server the key converted metrics are sent to (disabled if None)
+    pub statsd: Option<StatsdConfig>,
+
+    /// Prometheus Pushgateway the converted metrics are pushed to (disabled if None)
+    pub pushgateway: Option<PushConfig>,
+
+    /// `job` grouping label of the Pushgateway groups
+    pub pushgateway_job: String,
+
+    /// JSON-lines file the converted metrics are appended to (disabled if None)
+    pub jsonl: Option<JsonlConfig>,
+
+    /// Kafka topic the converted metrics are published to (disabled if None)
+    pub kafka: Option<KafkaConfig>,
+
+    /// Output the key converted metrics are written to as CloudWatch EMF (disabled if None)
+    pub emf: Option<EmfConfig>,
+
+    /// File holding the bearer token of the admin endpoints (disabled if None)
+    pub admin_token_file: Option<PathBuf>,
+
+    /// Log level of the running subscriber, changed via the admin endpoints
+    pub log_level: Option<Arc<LogLevel>>,
+
+    /// Number of fake sandboxes served instead of the node's (0 = disabled)
+    pub mock_sandboxes: usize,
+
+    /// Faults injected into the scrapes and CRI syncs (disabled by default)
+    pub chaos: ChaosConfig,
+
+    /// Directory the raw shim payloads are recorded to (disabled if None)
+    pub record_dir: Option<PathBuf>,
+
+    /// Number of recorded payloads kept per sandbox (0 = all)
+    pub record_max_files: usize,
+
+    /// Directory of a recording served instead of the node's sandboxes (disabled if None)
+    pub replay_dir: Option<PathBuf>,
+
+    /// Kubernetes pod watch adding pod labels to the series (disabled if None)
+    pub pod_watch: Option<PodWatchConfig>,
+
+    /// Janitor of stale sandbox directories (disabled if None)
+    pub janitor: Option<JanitorConfig>,
+
+    /// Per-container stats from the kata-agents (disabled if None)
+    pub agent_stats: Option<AgentStatsConfig>,
+
+    /// TokenReview authentication of the metrics endpoints (disabled if None)
+    pub token_review: Option<TokenReviewConfig>,
+
+    /// Tenants file scoping bearer tokens to namespaces (disabled if None)
+    pub tenants_file: Option<PathBuf>,
+
+    /// systemd readiness and watchdog notifications (disabled if None)
+    pub systemd: Option<Arc<SystemdNotify>>,
+}
+
+impl AppOptions {
+    /// Create options with the required settings and defaults for the rest
+    pub fn new<S: Into<String>>(
+        runtime_endpoints: impl IntoIterator<Item = S>,
+        metrics_interval_secs: u64,
+    ) -> Self {
+        AppOptions {
+            runtime_endpoints: runtime_endpoints.into_iter().map(Into::into).collect(),
+            cri_client: CRIClientConfig::default(),
+            metadata_retry_budget: DEFAULT_METADATA_RETRY_BUDGET,
+            metrics_interval_secs,
+            metrics_ttl_secs: None,
+            circuit_breaker: CircuitBreakerConfig::default(),
+            shim_client: ShimClientConfig::default(),
+            cache_snapshot_path: None,
+            history_len: DEFAULT_HISTORY_LEN,
+            conversion_workers: 0,
+            node_info: NodeInfo::default(),
+            sandbox_filter: SandboxFilter::default(),
+            conversion: ConversionConfig::default(),
+            host_cgroup_root: Some(PathBuf::from(DEFAULT_CGROUP_ROOT)),
+            host_proc_root: Some(PathBuf::from(DEFAULT_PROC_ROOT)),
+            parse_mode: ParseMode::default(),
+            name_filter: NameFilter::default(),
+            remote_write: None,
+            statsd: None,
+            pushgateway: None,
+            pushgateway_job: DEFAULT_JOB.to_string(),
+            jsonl: None,
+            kafka: None,
+            emf: None,
+            admin_token_file: None,
+            log_level: None,
+            mock_sandboxes: 0,
+            chaos: ChaosConfig::default(),
+            record_dir: None,
+            record_max_files: DEFAULT_RECORD_MAX_FILES,
+            replay_dir: None,
+            pod_watch: None,
+            janitor: None,
+            agent_stats: None,
+            token_review: None,
+            tenants_file: None,
+            systemd: None,
+        }
+    }
+
+    /// The settings of these options that can be reloaded without a restart
+    pub fn reloadable(&self) -> ReloadableConfig {
+        ReloadableConfig {
+            metrics_interval_secs: self.metrics_interval_secs,
+            sandbox_filter: self.sandbox_filter.clone(),
+            name_filter: self.name_filter.clone(),
+            conversion: self.conversion.clone(),
+        }
+    }
+
+    /// Effective metrics TTL
+    pub fn metrics_ttl(&self) -> Duration {
+        Duration::from_secs(
+            self.metrics_ttl_secs.unwrap_or(
+                self.metrics_interval_secs
+                    .saturating_mul(DEFAULT_METRICS_TTL_INTERVALS),
+            ),
+        )
+    }
+}
 
 /// Application context holding all singleton instances
 ///
@@ -25,14 +255,83 @@ pub struct AppContext {
     /// Metrics cache - double-buffered cache for metrics from all sandboxes
     metrics_cache: Arc<MetricsCache>,
 
+    /// Scrape health - per-sandbox results of the last metrics scrapes
+    scrape_health: Arc<ScrapeHealth>,
+
+    /// Shim client - HTTP client with keep-alive connections to shim sockets
+    shim_client: Arc<ShimClient>,
+
+    /// Pipeline health - states of the background components
+    pipeline_health: Arc<PipelineHealth>,
+
     /// Sandbox cache manager - handles directory monitoring and CRI metadata sync
     sandbox_cache_manager: Arc<SandboxCacheManager>,
 
     /// Metrics collector - handles periodic metrics collection
     metrics_collector: Arc<MetricsCollector>,
 
+    /// Mock sandboxes - fake sandboxes replacing the sandbox cache manager (if enabled)
+    mock_sandboxes: Option<Arc<MockSandboxes>>,
+
+    /// Fault injector - chaos mode faults in the scrapes and CRI syncs (if enabled)
+    chaos: Option<Arc<FaultInjector>>,
+
+    /// Payload replay - recorded sandboxes replacing the sandbox cache manager (if enabled)
+    replay: Option<Arc<PayloadReplay>>,
+
+    /// Payload recorder - saves the scraped shim payloads (if enabled)
+    recorder: Option<Arc<PayloadRecorder>>,
+
+    /// Pod watcher - pod labels from the Kubernetes API (if enabled)
+    pod_watcher: Option<Arc<PodWatcher>>,
+
+    /// Janitor - finds (and removes) stale sandbox directories (if enabled)
+    janitor: Option<Arc<Janitor>>,
+
+    /// Agent stats - per-container stats from the kata-agents (if enabled)
+    agent_stats: Option<Arc<AgentStats>>,
+
+    /// Boot tracker - times the boot of the sandboxes created while running
+    boot_tracker: Arc<BootTracker>,
+
     /// CRI label enricher - enriches metrics with pod metadata
     cri_enricher: Arc<dyn LabelEnricher>,
+
+    /// Rendered metrics - aggregated `/metrics` output, rendered once per buffer swap
+    rendered_metrics: Arc<RenderedMetrics>,
+
+    /// Conversion pool - converts sandbox metrics on blocking workers
+    conversion_pool: ConversionPool,
+
+    /// Node info - name and topology of the node
+    node_info: Arc<NodeInfo>,
+
+    /// Reloadable config - interval, sandbox/name filters and conversion rules
+    config: Arc<SharedConfig>,
+
+    /// Parse mode - whether malformed shim metrics lines fail the scrape
+    parse_mode: ParseMode,
+
+    /// Export pipeline - pushes the converted metrics to the configured exporters
+    exports: Arc<ExportPipeline>,
+
+    /// Token reviewer - authenticates the requests of the metrics endpoints (if enabled)
+    token_reviewer: Option<Arc<TokenReviewer>>,
+
+    /// Tenants - scope bearer tokens to the namespaces they may see (if enabled)
+    tenants: Option<Arc<Tenants>>,
+
+    /// Admin token file - bearer token of the admin endpoints
+    admin_token_file: Option<Arc<PathBuf>>,
+
+    /// Log level - changes the filter of the running subscriber
+    log_level: Option<Arc<LogLevel>>,
+
+    /// Cancellation token - fired on shutdown to stop the background tasks
+    cancel: CancellationToken,
+
+    /// Handles of the supervised background tasks
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
 }
 
 impl AppContext {
@@ -40,10 +339,13 @@ impl AppContext {
     ///
     /// This should be called once during startup before creating the HTTP server.
     /// All services are created and stored as Arc for shared ownership.
-    pub fn new(runtime_endpoint: String, metrics_interval_secs: u64) -> Result<Self> {
+    pub fn new(options: AppOptions) -> Result<Self> {
         tracing::info!("Initializing application context");
 
-        if runtime_endpoint.is_empty() {
+        let runtime_endpoints = options.runtime_endpoints.clone();
+        let metrics_interval_secs = options.metrics_interval_secs;
+
+        if runtime_endpoints.is_empty() || runtime_endpoints.iter().any(String::is_empty) {
             return Err(anyhow::anyhow!("runtime endpoint missing"));
         }
 
@@ -55,71 +357,440 @@ impl AppContext {
             ));
         }
 
+        if options.metrics_ttl_secs == Some(0) {
+            return Err(anyhow::anyhow!("metrics_ttl_secs must be > 0"));
+        }
+
+        if options.circuit_breaker.failure_threshold == 0 {
+            return Err(anyhow::anyhow!("scrape failure threshold must be > 0"));
+        }
+
+        if options.mock_sandboxes > 0 && options.replay_dir.is_some() {
+            return Err(anyhow::anyhow!(
+                "mock sandboxes and payload replay cannot be combined"
+            ));
+        }
+
+        if let Some(pod_watch) = &options.pod_watch {
+            if !cfg!(feature = "k8s") {
+                return Err(anyhow::anyhow!(
+                    "the Kubernetes pod watch requires kata-pulse to be built with the `k8s` feature"
+                ));
+            }
+            if pod_watch.node_name.trim().is_empty() {
+                return Err(anyhow::anyhow!(
+                    "the Kubernetes pod watch requires the node name (--node-name)"
+                ));
+            }
+        }
+
+        let token_reviewer = match options.token_review.clone() {
+            Some(config) => {
+                let reviewer = TokenReviewer::new(config)
+                    .context("failed to set up the TokenReview authentication")?;
+                tracing::info!(audiences = ?reviewer.config().audiences, "Authenticating metrics requests with Kubernetes TokenReviews");
+                Some(Arc::new(reviewer))
+            }
+            None => None,
+        };
+
+        let tenants = match options.tenants_file.as_ref() {
+            Some(path) => {
+                let tenants = Tenants::load(path).context("failed to load the tenants file")?;
+                tracing::info!(path = %path.display(), tenants = tenants.current().len(), "Scoping metrics requests to the namespaces of the tenants");
+                Some(Arc::new(tenants))
+            }
+            None => None,
+        };
+
+        options.chaos.validate()?;
+        let chaos = options.chaos.is_enabled().then(|| {
+            tracing::warn!(
+                scrape_failure = options.chaos.scrape_failure,
+                slow_scrape = options.chaos.slow_scrape,
+                slow_scrape_delay = ?options.chaos.slow_scrape_delay,
+                cri_timeout = options.chaos.cri_timeout,
+                malformed_payload = options.chaos.malformed_payload,
+                "Chaos mode enabled: injecting faults into scrapes and CRI syncs"
+            );
+            Arc::new(FaultInjector::new(options.chaos.clone()))
+        });
+
         // Create the core caches
         let sandbox_cache = Arc::new(SandboxCache::new());
-        let metrics_cache = Arc::new(MetricsCache::new());
-        tracing::info!("Core caches initialized");
+        let metrics_cache = Arc::new(
+            MetricsCache::with_ttl(options.metrics_ttl()).with_history(options.history_len),
+        );
+        let scrape_health = Arc::new(ScrapeHealth::new(options.circuit_breaker));
+        let shim_client = Arc::new(ShimClient::new(options.shim_client.clone()));
+        let pipeline_health = Arc::new(PipelineHealth::new());
+        let config = Arc::new(SharedConfig::new(options.reloadable()));
+        tracing::info!(
+            metrics_ttl_secs = options.metrics_ttl().as_secs(),
+            "Core caches initialized"
+        );
 
         // Create sandbox cache manager (directory monitoring + CRI sync)
-        let sandbox_cache_manager = Arc::new(SandboxCacheManager::new(
-            sandbox_cache.clone(),
-            metrics_cache.clone(),
-            runtime_endpoint,
-        ));
+        let sandbox_cache_manager = Arc::new(
+            SandboxCacheManager::new(
+                sandbox_cache.clone(),
+                metrics_cache.clone(),
+                pipeline_health.clone(),
+                runtime_endpoints,
+            )
+            .with_cri_client_config(options.cri_client.clone())
+            .with_metadata_retry_budget(options.metadata_retry_budget)
+            .with_chaos(chaos.clone()),
+        );
         tracing::info!("Sandbox cache manager initialized");
 
-        // Create metrics collector (periodic metrics collection)
-        let metrics_collector = Arc::new(MetricsCollector::new(
-            sandbox_cache.clone(),
-            metrics_cache.clone(),
-            metrics_interval_secs,
-        ));
-        tracing::info!("Metrics collector initialized");
-
         // Create the CRI label enricher
         let cri_enricher: Arc<dyn LabelEnricher> =
             Arc::new(CRILabelEnricher::new(sandbox_cache.clone()));
         tracing::info!("CRI label enricher initialized");
 
+        // Create the conversion pool (conversion is CPU-bound, kept off the reactor)
+        let conversion_pool = ConversionPool::new(options.conversion_workers);
+        tracing::info!(
+            workers = conversion_pool.workers(),
+            "Conversion pool initialized"
+        );
+
+        // Create the aggregated /metrics renderer (renders after every buffer swap)
+        let rendered_metrics = Arc::new(
+            RenderedMetrics::new(
+                sandbox_cache.clone(),
+                metrics_cache.clone(),
+                config.clone(),
+                cri_enricher.clone(),
+            )
+            .with_conversion_pool(conversion_pool.clone()),
+        );
+
+        // Create the export pipeline (pushes after every collection cycle)
+        let mut exports = ExportPipeline::new(
+            sandbox_cache.clone(),
+            metrics_cache.clone(),
+            config.clone(),
+            cri_enricher.clone(),
+        )
+        .with_conversion_pool(conversion_pool.clone());
+        if let Some(config) = options.remote_write.clone() {
+            tracing::info!(url = %config.url, "Pushing metrics to remote_write endpoint");
+            exports = exports.with_exporter(Arc::new(RemoteWriteExporter::new(config)?));
+        }
+        if let Some(config) = options.statsd.clone() {
+            tracing::info!(address = %config.address, flavor = %config.flavor, "Sending metrics to StatsD server");
+            exports = exports.with_exporter(Arc::new(StatsdExporter::new(config)));
+        }
+        if let Some(config) = options.pushgateway.clone() {
+            tracing::info!(url = %config.url, job = %options.pushgateway_job, "Pushing metrics to Pushgateway");
+            exports = exports.with_exporter(Arc::new(PushgatewayExporter::new(
+                config,
+                options.pushgateway_job.clone(),
+            )?));
+        }
+        if let Some(config) = options.jsonl.clone() {
+            tracing::info!(path = %config.path.display(), "Appending metrics to JSON-lines file");
+            exports = exports.with_exporter(Arc::new(JsonlExporter::new(config)?));
+        }
+        if let Some(config) = options.kafka.clone() {
+            tracing::info!(brokers = %config.brokers, topic = %config.topic, format = %config.format, "Publishing metrics to Kafka");
+            exports = exports.with_exporter(kafka::exporter(config)?);
+        }
+        if let Some(config) = options.emf.clone() {
+            tracing::info!(output = %config.output, namespace = %config.namespace, "Writing metrics as CloudWatch EMF");
+            exports = exports.with_exporter(Arc::new(EmfExporter::new(config)?));
+        }
+        let exports = Arc::new(exports);
+
+        // Fake sandboxes for development, replacing the node's
+        let mock_sandboxes = (options.mock_sandboxes > 0).then(|| {
+            tracing::warn!(
+                count = options.mock_sandboxes,
+                "Serving mock sandboxes instead of the node's sandboxes"
+            );
+            Arc::new(MockSandboxes::new(options.mock_sandboxes))
+        });
+
+        // Recorded shim payloads, replayed in place of the node's sandboxes
+        let replay = match options.replay_dir.clone() {
+            Some(dir) => {
+                let replay = PayloadReplay::load(dir.clone())
+                    .with_context(|| format!("failed to load recording {}", dir.display()))?;
+                tracing::warn!(dir = %dir.display(), "Replaying recorded shim payloads instead of the node's sandboxes");
+                Some(Arc::new(replay))
+            }
+            None => None,
+        };
+        let pod_watcher = options
+            .pod_watch
+            .clone()
+            .map(|config| Arc::new(PodWatcher::new(config, sandbox_cache.clone())));
+        let janitor = options
+            .janitor
+            .clone()
+            .map(|config| Arc::new(Janitor::new(config, sandbox_cache.clone())));
+        let agent_stats = options.agent_stats.clone().map(|agent_config| {
+            Arc::new(AgentStats::new(
+                agent_config,
+                sandbox_cache.clone(),
+                shim_client.clone(),
+                config.clone(),
+            ))
+        });
+        let boot_tracker = Arc::new(BootTracker::new(sandbox_cache.clone(), shim_client.clone()));
+        let recorder = options.record_dir.clone().map(|dir| {
+            tracing::info!(dir = %dir.display(), max_files = options.record_max_files, "Recording shim payloads");
+            Arc::new(PayloadRecorder::new(dir, options.record_max_files))
+        });
+
+        // Create metrics collector (periodic metrics collection)
+        let metrics_collector = Arc::new(
+            MetricsCollector::new(
+                sandbox_cache.clone(),
+                metrics_cache.clone(),
+                scrape_health.clone(),
+                shim_client.clone(),
+                pipeline_health.clone(),
+                config.clone(),
+            )
+            .with_snapshot_path(options.cache_snapshot_path.clone())
+            .with_host_cgroup(
+                options
+                    .host_cgroup_root
+                    .clone()
+                    .map(|root| Arc::new(HostCgroupReader::new(root))),
+            )
+            .with_hypervisor_proc(
+                options
+                    .host_proc_root
+                    .clone()
+                    .map(HypervisorProcReader::new)
+                    .filter(|reader| {
+                        let visible = reader.host_pids_visible();
+                        if !visible {
+                            tracing::info!("Host processes are not visible (no hostPID), kata_vmm_* metrics are disabled");
+                        }
+                        visible
+                    })
+                    .map(Arc::new),
+            )
+            .with_parse_mode(options.parse_mode)
+            .with_exports(Some(exports.clone()).filter(|exports| !exports.is_empty()))
+            .with_rendered_metrics(Some(rendered_metrics.clone()))
+            .with_mock_sandboxes(mock_sandboxes.clone())
+            .with_systemd(options.systemd.clone())
+            .with_chaos(chaos.clone())
+            .with_replay(replay.clone())
+            .with_recorder(recorder.clone()),
+        );
+        tracing::info!("Metrics collector initialized");
+
         Ok(AppContext {
             sandbox_cache,
             metrics_cache,
+            scrape_health,
+            shim_client,
+            pipeline_health,
             sandbox_cache_manager,
             metrics_collector,
+            mock_sandboxes,
+            chaos,
+            replay,
+            recorder,
+            pod_watcher,
+            janitor,
+            agent_stats,
+            boot_tracker,
             cri_enricher,
+            rendered_metrics,
+            conversion_pool,
+            node_info: Arc::new(options.node_info),
+            config,
+            parse_mode: options.parse_mode,
+            exports,
+            token_reviewer,
+            tenants,
+            admin_token_file: options.admin_token_file.map(Arc::new),
+            log_level: options.log_level,
+            cancel: CancellationToken::new(),
+            tasks: Arc::new(Mutex::new(Vec::new())),
         })
     }
 
     /// Start background tasks for sandbox cache management and metrics collection
     ///
-    /// This spawns two long-running background tasks:
+    /// This spawns two supervised long-running background tasks:
     /// - Sandbox cache manager (directory monitoring + CRI metadata sync)
     /// - Metrics collector (periodic metrics collection)
     ///
+    /// and the sandbox boot tracker, plus the Kubernetes pod watch, the
+    /// janitor and the agent stats collector, if enabled.
+    ///
+    /// A task that panics or returns an error is marked failed and restarted
+    /// with backoff; `shutdown` stops both.
+    ///
     /// Note: We clone the Arc<T> (cheap - just increments reference count),
     /// not the underlying data. All tasks share the same singleton instances.
     pub fn start(&self) -> Result<()> {
-        // Spawn the sandbox cache manager task (directory monitoring + CRI sync)
-        // Clone the Arc to move into the async task (cheap - just ref counting)
+        let policy = RestartPolicy::default();
+
+        // Spawn the sandbox cache manager task (directory monitoring + CRI sync),
+        // or register the fake or recorded sandboxes in its place
         let sandbox_cache_manager = self.sandbox_cache_manager.clone();
-        tokio::spawn(async move {
-            if let Err(e) = sandbox_cache_manager.start().await {
-                tracing::error!(error = %e, "Sandbox cache manager error");
-            }
-        });
+        let mock_sandboxes = self.mock_sandboxes.clone();
+        let replay = self.replay.clone();
+        let sandbox_cache = self.sandbox_cache.clone();
+        let pipeline_health = self.pipeline_health.clone();
+        let manager_handle = spawn_supervised(
+            Component::SandboxCacheManager,
+            self.pipeline_health.clone(),
+            policy,
+            self.cancel.clone(),
+            move |cancel| {
+                let sandbox_cache_manager = sandbox_cache_manager.clone();
+                let mock_sandboxes = mock_sandboxes.clone();
+                let replay = replay.clone();
+                let sandbox_cache = sandbox_cache.clone();
+                let pipeline_health = pipeline_health.clone();
+                async move {
+                    match (mock_sandboxes, replay) {
+                        (Some(mock), _) => mock.start(sandbox_cache, pipeline_health, cancel).await,
+                        (None, Some(replay)) => {
+                            replay.start(sandbox_cache, pipeline_health, cancel).await
+                        }
+                        (None, None) => sandbox_cache_manager.start(cancel).await,
+                    }
+                }
+            },
+        );
 
         // Spawn the metrics collector task (periodic metrics collection)
-        // Clone the Arc to move into the async task (cheap - just ref counting)
         let metrics_collector = self.metrics_collector.clone();
-        tokio::spawn(async move {
-            if let Err(e) = metrics_collector.start().await {
-                tracing::error!(error = %e, "Metrics collector error");
+        let collector_handle = spawn_supervised(
+            Component::MetricsCollector,
+            self.pipeline_health.clone(),
+            policy,
+            self.cancel.clone(),
+            move |cancel| {
+                let metrics_collector = metrics_collector.clone();
+                async move { metrics_collector.start(cancel).await }
+            },
+        );
+
+        let mut tasks = vec![manager_handle, collector_handle];
+
+        // Spawn the Kubernetes pod watch (restarts its watches by itself, and is
+        // restarted after a panic)
+        if let Some(pod_watcher) = self.pod_watcher.clone() {
+            let pipeline_health = self.pipeline_health.clone();
+            tasks.push(spawn_supervised(
+                Component::PodWatch,
+                self.pipeline_health.clone(),
+                policy,
+                self.cancel.clone(),
+                move |cancel| {
+                    let pod_watcher = pod_watcher.clone();
+                    let pipeline_health = pipeline_health.clone();
+                    async move {
+                        pipeline_health.healthy(Component::PodWatch);
+                        pod_watcher.start(cancel).await
+                    }
+                },
+            ));
+        }
+
+        // Spawn the janitor of the node's sandbox directories
+        if let Some(janitor) = self.janitor.clone() {
+            if self.mock_sandboxes.is_none() && self.replay.is_none() {
+                let pipeline_health = self.pipeline_health.clone();
+                tasks.push(spawn_supervised(
+                    Component::Janitor,
+                    self.pipeline_health.clone(),
+                    policy,
+                    self.cancel.clone(),
+                    move |cancel| {
+                        let janitor = janitor.clone();
+                        let pipeline_health = pipeline_health.clone();
+                        async move {
+                            pipeline_health.healthy(Component::Janitor);
+                            janitor.start(cancel).await;
+                            Ok(())
+                        }
+                    },
+                ));
             }
-        });
+        }
+
+        // Spawn the boot tracker of the node's new sandboxes
+        if self.mock_sandboxes.is_none() && self.replay.is_none() {
+            let boot_tracker = self.boot_tracker.clone();
+            let pipeline_health = self.pipeline_health.clone();
+            tasks.push(spawn_supervised(
+                Component::BootTracker,
+                self.pipeline_health.clone(),
+                policy,
+                self.cancel.clone(),
+                move |cancel| {
+                    let boot_tracker = boot_tracker.clone();
+                    let pipeline_health = pipeline_health.clone();
+                    async move {
+                        pipeline_health.healthy(Component::BootTracker);
+                        boot_tracker.start(cancel).await;
+                        Ok(())
+                    }
+                },
+            ));
+        }
+
+        // Spawn the agent stats collector of the node's sandboxes
+        if let Some(agent_stats) = self.agent_stats.clone() {
+            if self.mock_sandboxes.is_none() && self.replay.is_none() {
+                let pipeline_health = self.pipeline_health.clone();
+                tasks.push(spawn_supervised(
+                    Component::AgentStats,
+                    self.pipeline_health.clone(),
+                    policy,
+                    self.cancel.clone(),
+                    move |cancel| {
+                        let agent_stats = agent_stats.clone();
+                        let pipeline_health = pipeline_health.clone();
+                        async move {
+                            pipeline_health.healthy(Component::AgentStats);
+                            agent_stats.start(cancel).await;
+                            Ok(())
+                        }
+                    },
+                ));
+            }
+        }
+
+        self.tasks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(tasks);
 
         Ok(())
     }
 
+    /// Stop the background tasks and wait for them to finish
+    pub async fn shutdown(&self) {
+        self.cancel.cancel();
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap_or_else(|e| e.into_inner()));
+        for task in tasks {
+            if let Err(e) = task.await {
+                tracing::error!(error = %e, "Background task did not stop cleanly");
+            }
+        }
+        tracing::info!("Background tasks stopped");
+    }
+
+    /// Get the shutdown cancellation token
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
     /// Get reference to the sandbox cache
     pub fn sandbox_cache(&self) -> &Arc<SandboxCache> {
         &self.sandbox_cache
@@ -130,10 +801,100 @@ impl AppContext {
         &self.metrics_cache
     }
 
+    /// Get reference to the scrape health registry
+    pub fn scrape_health(&self) -> &Arc<ScrapeHealth> {
+        &self.scrape_health
+    }
+
+    /// Get reference to the pipeline health registry
+    pub fn pipeline_health(&self) -> &Arc<PipelineHealth> {
+        &self.pipeline_health
+    }
+
+    /// Get reference to the shim client
+    pub fn shim_client(&self) -> &Arc<ShimClient> {
+        &self.shim_client
+    }
+
+    /// Get reference to the node info
+    pub fn node_info(&self) -> &Arc<NodeInfo> {
+        &self.node_info
+    }
+
+    /// Get reference to the reloadable config (interval, filters, conversion rules)
+    pub fn config(&self) -> &Arc<SharedConfig> {
+        &self.config
+    }
+
+    /// Get the parse mode of shim metrics
+    pub fn parse_mode(&self) -> ParseMode {
+        self.parse_mode
+    }
+
+    /// Get reference to the export pipeline
+    pub fn exports(&self) -> &Arc<ExportPipeline> {
+        &self.exports
+    }
+
+    /// Get reference to the aggregated metrics renderer
+    pub fn rendered_metrics(&self) -> &Arc<RenderedMetrics> {
+        &self.rendered_metrics
+    }
+
+    /// Get reference to the conversion pool
+    pub fn conversion_pool(&self) -> &ConversionPool {
+        &self.conversion_pool
+    }
+
     /// Get reference to the CRI label enricher
     pub fn cri_enricher(&self) -> &Arc<dyn LabelEnricher> {
         &self.cri_enricher
     }
+
+    /// Get the reviewer of the bearer tokens of the metrics endpoints, if enabled
+    pub fn token_reviewer(&self) -> Option<&Arc<TokenReviewer>> {
+        self.token_reviewer.as_ref()
+    }
+
+    /// Get the tenants scoping bearer tokens to namespaces, if enabled
+    pub fn tenants(&self) -> Option<&Arc<Tenants>> {
+        self.tenants.as_ref()
+    }
+
+    /// Get the file holding the bearer token of the admin endpoints
+    pub fn admin_token_file(&self) -> Option<&Arc<PathBuf>> {
+        self.admin_token_file.as_ref()
+    }
+
+    /// Get reference to the log level of the running subscriber
+    pub fn log_level(&self) -> Option<&Arc<LogLevel>> {
+        self.log_level.as_ref()
+    }
+
+    /// Get the janitor of stale sandbox directories, if enabled
+    pub fn janitor(&self) -> Option<&Arc<Janitor>> {
+        self.janitor.as_ref()
+    }
+
+    /// Get the sandbox boot tracker
+    pub fn boot_tracker(&self) -> &Arc<BootTracker> {
+        &self.boot_tracker
+    }
+
+    /// Get the agent stats collector, if enabled
+    pub fn agent_stats(&self) -> Option<&Arc<AgentStats>> {
+        self.agent_stats.as_ref()
+    }
+
+    /// Get the fault injector of chaos mode, if enabled
+    pub fn chaos(&self) -> Option<&Arc<FaultInjector>> {
+        self.chaos.as_ref()
+    }
+
+    /// Get the recorder of the scraped shim payloads, if enabled
+    pub fn recorder(&self) -> Option<&Arc<PayloadRecorder>> {
+        self.recorder.as_ref()
+    }
 }
 
 #[cfg(test)]
@@ -142,7 +903,7 @@ mod tests {
 
     #[test]
     fn test_app_context_creation() {
-        let context = AppContext::new("/tmp/test.sock".to_string(), 1);
+        let context = AppContext::new(AppOptions::new(["/tmp/test.sock"], 1));
         assert!(context.is_ok());
 
         let ctx = context.unwrap();
@@ -154,7 +915,7 @@ mod tests {
 
     #[test]
     fn test_app_context_clone() {
-        let context = AppContext::new("/tmp/test.sock".to_string(), 1).unwrap();
+        let context = AppContext::new(AppOptions::new(["/tmp/test.sock"], 1)).unwrap();
         let cloned = context.clone();
 
         // Both should reference the same sandbox cache instance (same Arc pointer)
@@ -163,21 +924,86 @@ mod tests {
         assert_eq!(ptr1, ptr2);
     }
 
+    #[tokio::test]
+    async fn test_app_context_shutdown_stops_tasks() {
+        let context = AppContext::new(AppOptions::new(["/tmp/test.sock"], 1)).unwrap();
+        context.start().unwrap();
+        tokio::time::timeout(Duration::from_secs(5), context.shutdown())
+            .await
+            .expect("background tasks should stop on shutdown");
+        assert!(context.cancellation_token().is_cancelled());
+    }
+
     #[test]
     fn test_app_context_empty_endpoint() {
-        let context = AppContext::new(String::new(), 1);
+        let context = AppContext::new(AppOptions::new([String::new()], 1));
+        assert!(context.is_err());
+
+        let context = AppContext::new(AppOptions::new(Vec::<String>::new(), 1));
         assert!(context.is_err());
     }
 
     #[test]
     fn test_app_context_zero_metrics_interval() {
-        let context = AppContext::new("/tmp/test.sock".to_string(), 0);
+        let context = AppContext::new(AppOptions::new(["/tmp/test.sock"], 0));
         assert!(context.is_err(), "Should reject zero metrics_interval_secs");
     }
 
+    #[test]
+    fn test_app_options_default_ttl() {
+        let options = AppOptions::new(["/tmp/test.sock"], 60);
+        assert_eq!(options.metrics_ttl(), Duration::from_secs(180));
+
+        let options = AppOptions {
+            metrics_ttl_secs: Some(30),
+            ..AppOptions::new(["/tmp/test.sock"], 60)
+        };
+        assert_eq!(options.metrics_ttl(), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_app_context_zero_metrics_ttl() {
+        let options = AppOptions {
+            metrics_ttl_secs: Some(0),
+            ..AppOptions::new(["/tmp/test.sock"], 60)
+        };
+        assert!(AppContext::new(options).is_err());
+    }
+
+    #[test]
+    fn test_app_context_pod_watch_needs_node_name() {
+        let options = AppOptions {
+            pod_watch: Some(PodWatchConfig::new("")),
+            ..AppOptions::new(["/tmp/test.sock"], 60)
+        };
+        assert!(AppContext::new(options).is_err());
+    }
+
+    #[test]
+    fn test_app_context_token_review_needs_audiences() {
+        let options = AppOptions {
+            token_review: Some(TokenReviewConfig::new()),
+            ..AppOptions::new(["/tmp/test.sock"], 60)
+        };
+        let error = AppContext::new(options).err().unwrap();
+        assert!(format!("{:#}", error).contains("--auth-audiences"));
+    }
+
+    #[cfg(not(feature = "k8s"))]
+    #[test]
+    fn test_app_context_token_review_needs_k8s_feature() {
+        let options = AppOptions {
+            token_review: Some(
+                TokenReviewConfig::new().with_audiences(vec!["kata-pulse".to_string()]),
+            ),
+            ..AppOptions::new(["/tmp/test.sock"], 60)
+        };
+        assert!(AppContext::new(options).is_err());
+    }
+
     #[test]
     fn test_app_context_valid_metrics_interval() {
-        let context = AppContext::new("/tmp/test.sock".to_string(), 60);
+        let context = AppContext::new(AppOptions::new(["/tmp/test.sock"], 60));
         assert!(
             context.is_ok(),
             "Should accept valid metrics_interval_secs > 0"
@@ -0,0 +1,329 @@
+//! CloudWatch Embedded Metric Format (EMF) exporter
+//!
+//! Responsibilities:
+//! - Write one EMF document per pod per collection cycle holding the key
+//!   converted metrics, with `Namespace` and `PodName` dimensions
+//! - Send counters as increments since the previous cycle (CloudWatch sums
+//!   the values of a period), gauges as their current value
+//! - Write the documents to stdout or append them to a file
+//!
+//! Used on EKS nodes where the CloudWatch agent collects the documents from
+//! the container log or a file, without running Prometheus.
+
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use super::{key_metrics, ExportBatch, Exporter, KeyMetricKind, SandboxExport, KEY_METRICS};
+use crate::utils::prometheus_parser::PrometheusMetrics;
+
+/// Default CloudWatch namespace of the metrics
+pub const DEFAULT_NAMESPACE: &str = "KataPulse";
+
+/// Dimensions every metric is reported under
+const DIMENSIONS: [&str; 2] = ["Namespace", "PodName"];
+
+/// Where the EMF documents are written
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EmfOutput {
+    /// Standard output, collected with the container log
+    Stdout,
+    /// A file the documents are appended to
+    File(PathBuf),
+}
+
+impl FromStr for EmfOutput {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "" => anyhow::bail!("EMF output must be stdout or a file path"),
+            "stdout" | "-" => Ok(EmfOutput::Stdout),
+            path => Ok(EmfOutput::File(PathBuf::from(path))),
+        }
+    }
+}
+
+impl fmt::Display for EmfOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmfOutput::Stdout => f.write_str("stdout"),
+            EmfOutput::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Output and namespace of the EMF exporter
+#[derive(Clone, Debug)]
+pub struct EmfConfig {
+    /// Where the documents are written
+    pub output: EmfOutput,
+
+    /// CloudWatch namespace of the metrics
+    pub namespace: String,
+}
+
+impl EmfConfig {
+    /// Create a config writing to `output` under the `KataPulse` namespace
+    pub fn new(output: EmfOutput) -> Self {
+        EmfConfig {
+            output,
+            namespace: DEFAULT_NAMESPACE.to_string(),
+        }
+    }
+
+    /// Set the CloudWatch namespace of the metrics
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = namespace.into();
+        self
+    }
+}
+
+/// Writes the key converted metrics of every pod as CloudWatch EMF documents
+pub struct EmfExporter {
+    config: EmfConfig,
+    /// Key metrics of every sandbox at the previous push, to turn counters into increments
+    previous: Mutex<HashMap<String, PrometheusMetrics>>,
+}
+
+impl EmfExporter {
+    /// Create an exporter writing to the output of `config`
+    pub fn new(config: EmfConfig) -> Result<Self> {
+        if config.namespace.is_empty() {
+            anyhow::bail!("EMF namespace must not be empty");
+        }
+        if let EmfOutput::File(path) = &config.output {
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                fs::create_dir_all(dir)
+                    .with_context(|| format!("failed to create directory {}", dir.display()))?;
+            }
+        }
+        Ok(EmfExporter {
+            config,
+            previous: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// EMF documents of a batch, one per sandbox with at least one value
+    ///
+    /// Counters of a sandbox are only reported once a previous push exists.
+    fn documents(&self, batch: &ExportBatch) -> Vec<Value> {
+        let mut previous = self.previous.lock().unwrap_or_else(|e| e.into_inner());
+        let mut current = HashMap::with_capacity(batch.sandboxes.len());
+        let mut documents = Vec::new();
+        for sandbox in &batch.sandboxes {
+            let key = key_metrics(&sandbox.metrics);
+            let deltas = previous
+                .get(&sandbox.sandbox_id)
+                .map(|previous| key.diff(previous));
+            if let Some(document) =
+                self.document(sandbox, &key, deltas.as_ref(), batch.timestamp_ms)
+            {
+                documents.push(document);
+            }
+            current.insert(sandbox.sandbox_id.clone(), key);
+        }
+
+        // Sandboxes that are gone are dropped here
+        *previous = current;
+        documents
+    }
+
+    /// EMF document of a sandbox; the samples of a metric are summed
+    fn document(
+        &self,
+        sandbox: &SandboxExport,
+        key: &PrometheusMetrics,
+        deltas: Option<&PrometheusMetrics>,
+        timestamp_ms: i64,
+    ) -> Option<Value> {
+        let mut fields = Map::new();
+        let mut definitions = Vec::new();
+        for (name, kind) in KEY_METRICS {
+            let source = match kind {
+                KeyMetricKind::Gauge => Some(key),
+                KeyMetricKind::Counter => deltas,
+            };
+            let Some(value) = source.and_then(|metrics| sum(metrics, name)) else {
+                continue;
+            };
+            definitions.push(json!({ "Name": name, "Unit": unit(name) }));
+            fields.insert(name.to_string(), json!(value));
+        }
+        if definitions.is_empty() {
+            return None;
+        }
+
+        fields.insert(
+            "_aws".to_string(),
+            json!({
+                "Timestamp": timestamp_ms,
+                "CloudWatchMetrics": [{
+                    "Namespace": self.config.namespace,
+                    "Dimensions": [DIMENSIONS],
+                    "Metrics": definitions,
+                }],
+            }),
+        );
+        fields.insert(DIMENSIONS[0].to_string(), json!(sandbox.metadata.namespace));
+        fields.insert(DIMENSIONS[1].to_string(), json!(sandbox.metadata.name));
+        fields.insert("SandboxId".to_string(), json!(sandbox.sandbox_id));
+        Some(Value::Object(fields))
+    }
+}
+
+impl Exporter for EmfExporter {
+    fn name(&self) -> &'static str {
+        "emf"
+    }
+
+    fn push<'a>(&'a self, batch: &'a ExportBatch) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut data = Vec::new();
+            for document in self.documents(batch) {
+                serde_json::to_writer(&mut data, &document)?;
+                data.push(b'\n');
+            }
+            if data.is_empty() {
+                return Ok(());
+            }
+
+            let output = self.config.output.clone();
+            tokio::task::spawn_blocking(move || write(&output, &data))
+                .await
+                .context("EMF writer task failed")?
+        })
+    }
+}
+
+/// Sum of the finite samples named `name`, None if there are none
+fn sum(metrics: &PrometheusMetrics, name: &str) -> Option<f64> {
+    metrics
+        .metrics
+        .values()
+        .flat_map(|metric| &metric.samples)
+        .filter(|sample| sample.name == name && sample.value.is_finite())
+        .map(|sample| sample.value)
+        .reduce(|a, b| a + b)
+}
+
+/// CloudWatch unit of a key metric
+fn unit(name: &str) -> &'static str {
+    if name.ends_with("_bytes") || name.ends_with("_bytes_total") {
+        "Bytes"
+    } else if name.ends_with("_seconds_total") {
+        "Seconds"
+    } else {
+        "Count"
+    }
+}
+
+/// Write the documents of a push in one piece
+fn write(output: &EmfOutput, data: &[u8]) -> Result<()> {
+    match output {
+        EmfOutput::Stdout => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(data)?;
+            stdout
+                .flush()
+                .context("failed to write EMF documents to stdout")
+        }
+        EmfOutput::File(path) => OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(data))
+            .with_context(|| format!("failed to write {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::sandbox_cache::SandboxCRIMetadata;
+
+    fn batch(cpu: f64) -> ExportBatch {
+        let text = format!(
+            "# TYPE container_cpu_usage_seconds_total counter\n\
+             container_cpu_usage_seconds_total{{pod=\"my-pod\",namespace=\"default\",cpu=\"total\"}} {}\n\
+             # TYPE container_network_receive_bytes_total counter\n\
+             container_network_receive_bytes_total{{pod=\"my-pod\",interface=\"eth0\"}} 100\n\
+             container_network_receive_bytes_total{{pod=\"my-pod\",interface=\"eth1\"}} 50\n\
+             # TYPE container_memory_usage_bytes gauge\n\
+             container_memory_usage_bytes{{pod=\"my-pod\",namespace=\"default\"}} 1024\n\
+             # TYPE container_fs_usage_bytes gauge\n\
+             container_fs_usage_bytes{{pod=\"my-pod\",namespace=\"default\"}} 1\n",
+            cpu
+        );
+        ExportBatch {
+            sandboxes: vec![SandboxExport {
+                sandbox_id: "sb-1".to_string(),
+                metadata: SandboxCRIMetadata {
+                    name: "my-pod".to_string(),
+                    namespace: "default".to_string(),
+                    ..Default::default()
+                },
+                metrics: PrometheusMetrics::parse(&text).unwrap(),
+            }],
+            timestamp_ms: 1_760_620_000_000,
+        }
+    }
+
+    #[test]
+    fn test_documents() {
+        let exporter = EmfExporter::new(EmfConfig::new(EmfOutput::Stdout)).unwrap();
+
+        // Counters need a previous push
+        let documents = exporter.documents(&batch(10.0));
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0]["container_memory_usage_bytes"], 1024.0);
+        assert!(documents[0]
+            .get("container_cpu_usage_seconds_total")
+            .is_none());
+
+        let documents = exporter.documents(&batch(12.5));
+        let document = &documents[0];
+        assert_eq!(document["Namespace"], "default");
+        assert_eq!(document["PodName"], "my-pod");
+        assert_eq!(document["container_cpu_usage_seconds_total"], 2.5);
+        assert_eq!(document["container_network_receive_bytes_total"], 0.0);
+        assert!(document.get("container_fs_usage_bytes").is_none());
+
+        let directive = &document["_aws"];
+        assert_eq!(directive["Timestamp"], 1_760_620_000_000i64);
+        let metrics = &directive["CloudWatchMetrics"][0];
+        assert_eq!(metrics["Namespace"], DEFAULT_NAMESPACE);
+        assert_eq!(metrics["Dimensions"], json!([["Namespace", "PodName"]]));
+        assert!(metrics["Metrics"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({"Name": "container_cpu_usage_seconds_total", "Unit": "Seconds"})));
+    }
+
+    #[tokio::test]
+    async fn test_push_appends_to_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("emf").join("metrics.log");
+        let output: EmfOutput = path.to_str().unwrap().parse().unwrap();
+        let exporter = EmfExporter::new(EmfConfig::new(output).with_namespace("Kata")).unwrap();
+
+        exporter.push(&batch(1.0)).await.unwrap();
+        exporter.push(&batch(2.0)).await.unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        let document: Value = serde_json::from_str(content.lines().last().unwrap()).unwrap();
+        assert_eq!(
+            document["_aws"]["CloudWatchMetrics"][0]["Namespace"],
+            "Kata"
+        );
+        assert_eq!("stdout".parse::<EmfOutput>().unwrap(), EmfOutput::Stdout);
+    }
+}
@@ -0,0 +1,321 @@
+//! HTTP client shared by the push exporters
+//!
+//! Responsibilities:
+//! - Send a payload to a remote endpoint with the configured authentication
+//! - Retry transport errors, 5xx and 429 responses with exponential backoff
+//! - Refuse `https://` endpoints when built without the `tls` feature
+
+use anyhow::{Context, Result};
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
+use hyper::header::{HeaderName, HeaderValue, AUTHORIZATION, USER_AGENT};
+use hyper::{Method, Request, StatusCode, Uri};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use std::path::PathBuf;
+use std::time::Duration;
+use tracing::debug;
+
+/// Maximum number of response body bytes quoted in errors
+const MAX_ERROR_BODY: usize = 256;
+
+/// Authentication of push requests
+#[derive(Clone, Debug, Default)]
+pub enum PushAuth {
+    /// No authentication
+    #[default]
+    None,
+    /// HTTP basic authentication
+    Basic { username: String, password: String },
+    /// Bearer token read from a file before every push (rotated tokens are picked up)
+    BearerTokenFile(PathBuf),
+}
+
+impl PushAuth {
+    /// Pick the authentication from CLI credentials; a bearer token file wins over basic auth
+    pub fn from_credentials(
+        username: Option<String>,
+        password: Option<String>,
+        bearer_token_file: Option<PathBuf>,
+    ) -> Self {
+        match (bearer_token_file, username) {
+            (Some(path), _) => PushAuth::BearerTokenFile(path),
+            (None, Some(username)) => PushAuth::Basic {
+                username,
+                password: password.unwrap_or_default(),
+            },
+            (None, None) => PushAuth::None,
+        }
+    }
+}
+
+/// Endpoint and retry policy of a push exporter
+#[derive(Clone, Debug)]
+pub struct PushConfig {
+    /// URL the payload is POSTed to
+    pub url: String,
+
+    /// Authentication of the requests
+    pub auth: PushAuth,
+
+    /// Timeout of a single request
+    pub timeout: Duration,
+
+    /// Maximum number of retries of a failed push
+    pub max_retries: u32,
+
+    /// Backoff before the first retry, doubled for each further retry
+    pub retry_backoff: Duration,
+}
+
+impl PushConfig {
+    /// Create a config for `url` with default timeout and retries
+    pub fn new(url: impl Into<String>) -> Self {
+        PushConfig {
+            url: url.into(),
+            auth: PushAuth::None,
+            timeout: Duration::from_secs(10),
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(500),
+        }
+    }
+
+    /// Set the authentication of the requests
+    pub fn with_auth(mut self, auth: PushAuth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    /// Set the timeout of a single request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Set the maximum retries
+    pub fn with_max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Set the initial retry backoff
+    pub fn with_retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+}
+
+#[cfg(feature = "tls")]
+pub(crate) type Connector = hyper_rustls::HttpsConnector<HttpConnector>;
+#[cfg(not(feature = "tls"))]
+pub(crate) type Connector = HttpConnector;
+
+/// Connector for the configured endpoint
+#[cfg(feature = "tls")]
+pub(crate) fn connector(_uri: &Uri) -> Result<Connector> {
+    Ok(hyper_rustls::HttpsConnectorBuilder::new()
+        .with_webpki_roots()
+        .https_or_http()
+        .enable_http1()
+        .build())
+}
+
+/// Connector for the configured endpoint
+#[cfg(not(feature = "tls"))]
+pub(crate) fn connector(uri: &Uri) -> Result<Connector> {
+    if uri.scheme_str() == Some("https") {
+        anyhow::bail!("https endpoints require kata-pulse to be built with the `tls` feature");
+    }
+    Ok(HttpConnector::new())
+}
+
+/// Sends payloads to a push endpoint
+pub struct HttpPusher {
+    config: PushConfig,
+    uri: Uri,
+    client: Client<Connector, Full<Bytes>>,
+}
+
+impl HttpPusher {
+    /// Create a pusher for the endpoint of `config`
+    pub fn new(config: PushConfig) -> Result<Self> {
+        let uri: Uri = config
+            .url
+            .parse()
+            .with_context(|| format!("invalid push URL {}", config.url))?;
+        if !matches!(uri.scheme_str(), Some("http" | "https")) || uri.host().is_none() {
+            anyhow::bail!("push URL must be http(s)://host/..., got {}", config.url);
+        }
+        let client = Client::builder(TokioExecutor::new()).build(connector(&uri)?);
+        Ok(HttpPusher {
+            config,
+            uri,
+            client,
+        })
+    }
+
+    /// POST `body` with `headers` to the endpoint, retrying transient failures
+    pub async fn post(&self, body: Bytes, headers: &[(HeaderName, &'static str)]) -> Result<()> {
+        self.request(Method::POST, "", body, headers).await
+    }
+
+    /// Send `body` with `headers` to `path` below the endpoint URL, retrying
+    /// transient failures
+    pub async fn request(
+        &self,
+        method: Method,
+        path: &str,
+        body: Bytes,
+        headers: &[(HeaderName, &'static str)],
+    ) -> Result<()> {
+        let uri = if path.is_empty() {
+            self.uri.clone()
+        } else {
+            format!("{}{}", self.config.url.trim_end_matches('/'), path)
+                .parse()
+                .with_context(|| format!("invalid push path {}", path))?
+        };
+        let mut attempt = 0;
+        loop {
+            match self.send(&method, &uri, body.clone(), headers).await {
+                Ok(()) => return Ok(()),
+                Err(PushError::Permanent(e)) => return Err(e),
+                Err(PushError::Transient(e)) if attempt >= self.config.max_retries => {
+                    return Err(e.context(format!("giving up after {} retries", attempt)));
+                }
+                Err(PushError::Transient(e)) => {
+                    attempt += 1;
+                    let factor = 1u32 << (attempt - 1).min(16);
+                    let backoff = self.config.retry_backoff.saturating_mul(factor);
+                    debug!(url = %uri, attempt = attempt, backoff = ?backoff, error = %e, "Push failed, retrying");
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+    }
+
+    /// Send a single request
+    async fn send(
+        &self,
+        method: &Method,
+        uri: &Uri,
+        body: Bytes,
+        headers: &[(HeaderName, &'static str)],
+    ) -> std::result::Result<(), PushError> {
+        let mut request = Request::builder()
+            .method(method.clone())
+            .uri(uri.clone())
+            .header(
+                USER_AGENT,
+                concat!("kata-pulse/", env!("CARGO_PKG_VERSION")),
+            )
+            .body(Full::new(body))
+            .map_err(|e| PushError::Permanent(e.into()))?;
+        for (name, value) in headers {
+            request
+                .headers_mut()
+                .insert(name.clone(), HeaderValue::from_static(value));
+        }
+        if let Some(authorization) = self.authorization().map_err(PushError::Permanent)? {
+            request.headers_mut().insert(AUTHORIZATION, authorization);
+        }
+
+        let response = tokio::time::timeout(self.config.timeout, self.client.request(request))
+            .await
+            .map_err(|_| {
+                PushError::Transient(anyhow::anyhow!(
+                    "timed out after {:?} pushing to {}",
+                    self.config.timeout,
+                    uri
+                ))
+            })?
+            .map_err(|e| {
+                PushError::Transient(anyhow::anyhow!("failed to push to {}: {}", uri, e))
+            })?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .map(|body| body.to_bytes())
+            .unwrap_or_default();
+        let body = String::from_utf8_lossy(&body[..body.len().min(MAX_ERROR_BODY)]).into_owned();
+        let error = anyhow::anyhow!("HTTP status {} from {}: {}", status, uri, body.trim());
+        if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+            Err(PushError::Transient(error))
+        } else {
+            Err(PushError::Permanent(error))
+        }
+    }
+
+    /// Value of the Authorization header, if any
+    fn authorization(&self) -> Result<Option<HeaderValue>> {
+        let value = match &self.config.auth {
+            PushAuth::None => return Ok(None),
+            PushAuth::Basic { username, password } => {
+                use base64::Engine;
+                let credentials = base64::engine::general_purpose::STANDARD
+                    .encode(format!("{}:{}", username, password));
+                format!("Basic {}", credentials)
+            }
+            PushAuth::BearerTokenFile(path) => {
+                let token = std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read token file {}", path.display()))?;
+                format!("Bearer {}", token.trim())
+            }
+        };
+        let mut value = HeaderValue::from_str(&value).context("invalid credentials")?;
+        value.set_sensitive(true);
+        Ok(Some(value))
+    }
+}
+
+/// Failure of a single push request
+enum PushError {
+    /// Worth retrying (transport errors, 5xx, 429)
+    Transient(anyhow::Error),
+    /// Retrying would fail the same way (4xx, bad credentials)
+    Permanent(anyhow::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rejects_invalid_urls() {
+        assert!(HttpPusher::new(PushConfig::new("not a url")).is_err());
+        assert!(HttpPusher::new(PushConfig::new("ftp://example.com/push")).is_err());
+        assert!(HttpPusher::new(PushConfig::new("http://example.com/api/v1/write")).is_ok());
+    }
+
+    #[test]
+    fn test_authorization_header() {
+        let pusher = HttpPusher::new(PushConfig::new("http://example.com/push").with_auth(
+            PushAuth::Basic {
+                username: "user".to_string(),
+                password: "pass".to_string(),
+            },
+        ))
+        .unwrap();
+        assert_eq!(
+            pusher.authorization().unwrap().unwrap(),
+            "Basic dXNlcjpwYXNz"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let token = dir.path().join("token");
+        std::fs::write(&token, "secret\n").unwrap();
+        let pusher = HttpPusher::new(
+            PushConfig::new("http://example.com/push").with_auth(PushAuth::BearerTokenFile(token)),
+        )
+        .unwrap();
+        assert_eq!(pusher.authorization().unwrap().unwrap(), "Bearer secret");
+    }
+}
@@ -0,0 +1,298 @@
+//! JSON-lines file exporter
+//!
+//! Responsibilities:
+//! - Append one JSON document per sandbox per collection cycle to a file
+//! - Rotate the file once it exceeds a size or age limit, keeping a bounded
+//!   number of rotated files
+//!
+//! Used for offline analysis of raw per-cycle data without a TSDB.
+
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+use super::{ExportBatch, Exporter};
+
+/// Default size after which the file is rotated
+pub const DEFAULT_MAX_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Default age after which the file is rotated
+pub const DEFAULT_MAX_AGE: Duration = Duration::from_secs(3600);
+
+/// Default number of rotated files kept
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+/// File and rotation policy of the JSON-lines exporter
+#[derive(Clone, Debug)]
+pub struct JsonlConfig {
+    /// File the documents are appended to
+    pub path: PathBuf,
+
+    /// Size in bytes after which the file is rotated
+    pub max_size: u64,
+
+    /// Age after which the file is rotated
+    pub max_age: Duration,
+
+    /// Number of rotated files kept (`<path>.<unix millis>`)
+    pub max_files: usize,
+}
+
+impl JsonlConfig {
+    /// Create a config for `path` with the default rotation policy
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        JsonlConfig {
+            path: path.into(),
+            max_size: DEFAULT_MAX_SIZE,
+            max_age: DEFAULT_MAX_AGE,
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+
+    /// Set the size after which the file is rotated
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Set the age after which the file is rotated
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Set the number of rotated files kept
+    pub fn with_max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+}
+
+/// Appends the converted metrics of every sandbox to a rotating JSON-lines file
+pub struct JsonlExporter {
+    file: Arc<Mutex<RotatingFile>>,
+}
+
+impl JsonlExporter {
+    /// Create an exporter writing to the file of `config`
+    pub fn new(config: JsonlConfig) -> Result<Self> {
+        if config.max_size == 0 {
+            anyhow::bail!("JSON-lines max size must be > 0");
+        }
+        if let Some(dir) = config
+            .path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create directory {}", dir.display()))?;
+        }
+        Ok(JsonlExporter {
+            file: Arc::new(Mutex::new(RotatingFile {
+                config,
+                file: None,
+                size: 0,
+                opened_at: Instant::now(),
+            })),
+        })
+    }
+}
+
+impl Exporter for JsonlExporter {
+    fn name(&self) -> &'static str {
+        "jsonl"
+    }
+
+    fn push<'a>(&'a self, batch: &'a ExportBatch) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut data = Vec::new();
+            for sandbox in &batch.sandboxes {
+                data.extend(sandbox.to_json(batch.timestamp_ms)?);
+                data.push(b'\n');
+            }
+            if data.is_empty() {
+                return Ok(());
+            }
+
+            let file = self.file.clone();
+            tokio::task::spawn_blocking(move || {
+                file.lock().unwrap_or_else(|e| e.into_inner()).append(&data)
+            })
+            .await
+            .context("JSON-lines writer task failed")?
+        })
+    }
+}
+
+/// The output file with its size and age
+struct RotatingFile {
+    config: JsonlConfig,
+    file: Option<File>,
+    size: u64,
+    opened_at: Instant,
+}
+
+impl RotatingFile {
+    /// Append `data`, rotating first if the file is full or too old
+    ///
+    /// A cycle's documents are never split across files.
+    fn append(&mut self, data: &[u8]) -> Result<()> {
+        if self.file.is_none() {
+            self.open()?;
+        }
+        let full = self.size + data.len() as u64 > self.config.max_size;
+        let old = self.opened_at.elapsed() >= self.config.max_age;
+        if self.size > 0 && (full || old) {
+            self.rotate()?;
+        }
+
+        let path = &self.config.path;
+        let file = self.file.as_mut().context("JSON-lines file not open")?;
+        file.write_all(data)
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+
+    /// Open (or create) the file for appending
+    fn open(&mut self) -> Result<()> {
+        let path = &self.config.path;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        self.size = file.metadata().map(|m| m.len()).unwrap_or(0);
+        self.file = Some(file);
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+
+    /// Move the file aside as `<path>.<unix millis>` and start a new one
+    fn rotate(&mut self) -> Result<()> {
+        self.file = None;
+        let path = &self.config.path;
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let rotated = PathBuf::from(format!("{}.{}", path.display(), millis));
+        fs::rename(path, &rotated)
+            .with_context(|| format!("failed to rotate {}", path.display()))?;
+        info!(path = %rotated.display(), "Rotated JSON-lines file");
+
+        if let Err(e) = prune(path, self.config.max_files) {
+            warn!(path = %path.display(), error = %e, "Failed to remove old JSON-lines files");
+        }
+        self.open()
+    }
+}
+
+/// Remove the oldest rotated files of `path` beyond `keep`
+fn prune(path: &Path, keep: usize) -> Result<()> {
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let prefix = format!(
+        "{}.",
+        path.file_name()
+            .context("JSON-lines path has no file name")?
+            .to_string_lossy()
+    );
+
+    let mut rotated: Vec<(u128, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let millis = name.strip_prefix(&prefix)?.parse().ok()?;
+            Some((millis, entry.path()))
+        })
+        .collect();
+    rotated.sort();
+
+    let excess = rotated.len().saturating_sub(keep);
+    for (_, old) in rotated.into_iter().take(excess) {
+        debug!(path = %old.display(), "Removing old JSON-lines file");
+        fs::remove_file(&old)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporter::SandboxExport;
+    use crate::monitor::sandbox_cache::SandboxCRIMetadata;
+    use crate::utils::prometheus_parser::PrometheusMetrics;
+
+    fn batch() -> ExportBatch {
+        ExportBatch {
+            sandboxes: vec![SandboxExport {
+                sandbox_id: "sb-1".to_string(),
+                metadata: SandboxCRIMetadata {
+                    uid: "uid-1".to_string(),
+                    name: "my-pod".to_string(),
+                    namespace: "default".to_string(),
+                    ..Default::default()
+                },
+                metrics: PrometheusMetrics::parse(
+                    "# TYPE container_memory_usage_bytes gauge\ncontainer_memory_usage_bytes{pod=\"my-pod\"} 1024\n",
+                )
+                .unwrap(),
+            }],
+            timestamp_ms: 1_760_620_000_000,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_appends_one_document_per_sandbox() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.jsonl");
+        let exporter = JsonlExporter::new(JsonlConfig::new(&path)).unwrap();
+
+        exporter.push(&batch()).await.unwrap();
+        exporter.push(&batch()).await.unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let document: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(document["sandbox_id"], "sb-1");
+        assert_eq!(document["pod"], "my-pod");
+        assert_eq!(document["timestamp_ms"], 1_760_620_000_000i64);
+        let metrics: PrometheusMetrics =
+            serde_json::from_value(document["metrics"].clone()).unwrap();
+        assert_eq!(
+            metrics.metrics["container_memory_usage_bytes"].samples[0].value,
+            1024.0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rotates_by_size_and_prunes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.jsonl");
+        let exporter =
+            JsonlExporter::new(JsonlConfig::new(&path).with_max_size(10).with_max_files(2))
+                .unwrap();
+
+        for _ in 0..5 {
+            exporter.push(&batch()).await.unwrap();
+            // Rotated files are named by millisecond
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+
+        let files: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(files.len(), 3, "current file and 2 rotated: {:?}", files);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 1);
+    }
+}
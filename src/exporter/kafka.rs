@@ -0,0 +1,228 @@
+//! Kafka exporter
+//!
+//! Responsibilities:
+//! - Publish the converted metrics of every sandbox as one record per
+//!   collection cycle, keyed by sandbox ID (records of a sandbox stay ordered
+//!   within a partition)
+//! - Encode the records as JSON documents or remote_write protobuf
+//!
+//! Requires the `kafka` feature (bundled librdkafka); without it configuring
+//! the exporter fails.
+
+use anyhow::Result;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::Exporter;
+
+/// Default topic the records are published to
+pub const DEFAULT_TOPIC: &str = "kata-pulse-metrics";
+
+/// Payload encoding of the records
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KafkaFormat {
+    /// The JSON document of the JSON-lines exporter
+    #[default]
+    Json,
+    /// An uncompressed remote_write `WriteRequest`
+    Protobuf,
+}
+
+impl FromStr for KafkaFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(KafkaFormat::Json),
+            "protobuf" => Ok(KafkaFormat::Protobuf),
+            _ => anyhow::bail!("unknown Kafka format {:?} (expected json or protobuf)", s),
+        }
+    }
+}
+
+impl fmt::Display for KafkaFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            KafkaFormat::Json => "json",
+            KafkaFormat::Protobuf => "protobuf",
+        })
+    }
+}
+
+/// Brokers, topic and payload of the Kafka exporter
+#[derive(Clone, Debug)]
+pub struct KafkaConfig {
+    /// Comma-separated `host:port` list of bootstrap brokers
+    pub brokers: String,
+
+    /// Topic the records are published to
+    pub topic: String,
+
+    /// Payload encoding
+    pub format: KafkaFormat,
+
+    /// Time a record may wait for delivery before the push fails
+    pub timeout: Duration,
+}
+
+impl KafkaConfig {
+    /// Create a config for `brokers` with the default topic and JSON payloads
+    pub fn new(brokers: impl Into<String>) -> Self {
+        KafkaConfig {
+            brokers: brokers.into(),
+            topic: DEFAULT_TOPIC.to_string(),
+            format: KafkaFormat::default(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Set the topic the records are published to
+    pub fn with_topic(mut self, topic: impl Into<String>) -> Self {
+        self.topic = topic.into();
+        self
+    }
+
+    /// Set the payload encoding
+    pub fn with_format(mut self, format: KafkaFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the time a record may wait for delivery
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// Create the Kafka exporter of `config`
+#[cfg(feature = "kafka")]
+pub fn exporter(config: KafkaConfig) -> Result<Arc<dyn Exporter>> {
+    Ok(Arc::new(producer::KafkaExporter::new(config)?))
+}
+
+/// Create the Kafka exporter of `config`
+#[cfg(not(feature = "kafka"))]
+pub fn exporter(config: KafkaConfig) -> Result<Arc<dyn Exporter>> {
+    anyhow::bail!(
+        "Kafka export to {} requires kata-pulse to be built with the `kafka` feature",
+        config.brokers
+    )
+}
+
+#[cfg(feature = "kafka")]
+mod producer {
+    use anyhow::{Context, Result};
+    use futures::future::BoxFuture;
+    use rdkafka::config::ClientConfig;
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+    use rdkafka::util::Timeout;
+
+    use super::{KafkaConfig, KafkaFormat};
+    use crate::exporter::{remote_write, ExportBatch, Exporter, SandboxExport};
+
+    /// Publishes the converted metrics of every sandbox to a Kafka topic
+    pub struct KafkaExporter {
+        config: KafkaConfig,
+        producer: FutureProducer,
+    }
+
+    impl KafkaExporter {
+        /// Create a producer for the brokers of `config`
+        pub fn new(config: KafkaConfig) -> Result<Self> {
+            if config.topic.is_empty() {
+                anyhow::bail!("Kafka topic must not be empty");
+            }
+            let producer = ClientConfig::new()
+                .set("bootstrap.servers", &config.brokers)
+                .set("message.timeout.ms", config.timeout.as_millis().to_string())
+                .create()
+                .with_context(|| {
+                    format!("failed to create Kafka producer for {}", config.brokers)
+                })?;
+            Ok(KafkaExporter { config, producer })
+        }
+    }
+
+    impl Exporter for KafkaExporter {
+        fn name(&self) -> &'static str {
+            "kafka"
+        }
+
+        fn push<'a>(&'a self, batch: &'a ExportBatch) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                let mut deliveries = Vec::with_capacity(batch.sandboxes.len());
+                for sandbox in &batch.sandboxes {
+                    let payload = payload(self.config.format, sandbox, batch.timestamp_ms)?;
+                    deliveries.push(async move {
+                        self.producer
+                            .send(
+                                FutureRecord::to(&self.config.topic)
+                                    .key(&sandbox.sandbox_id)
+                                    .payload(&payload)
+                                    .timestamp(batch.timestamp_ms),
+                                Timeout::Never,
+                            )
+                            .await
+                            .map_err(|(e, _)| {
+                                anyhow::anyhow!(
+                                    "failed to publish metrics of sandbox {}: {}",
+                                    sandbox.sandbox_id,
+                                    e
+                                )
+                            })
+                    });
+                }
+
+                let failures: Vec<_> = futures::future::join_all(deliveries)
+                    .await
+                    .into_iter()
+                    .filter_map(|result| result.err())
+                    .collect();
+                match failures.into_iter().next() {
+                    None => Ok(()),
+                    Some(e) => Err(e),
+                }
+            })
+        }
+    }
+
+    /// Encode the record payload of a sandbox
+    fn payload(format: KafkaFormat, sandbox: &SandboxExport, timestamp_ms: i64) -> Result<Vec<u8>> {
+        Ok(match format {
+            KafkaFormat::Json => sandbox.to_json(timestamp_ms)?,
+            KafkaFormat::Protobuf => remote_write::encode_metrics(&sandbox.metrics, timestamp_ms),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kafka_format() {
+        assert_eq!("json".parse::<KafkaFormat>().unwrap(), KafkaFormat::Json);
+        assert_eq!(
+            "protobuf".parse::<KafkaFormat>().unwrap(),
+            KafkaFormat::Protobuf
+        );
+        assert!("avro".parse::<KafkaFormat>().is_err());
+    }
+
+    #[cfg(feature = "kafka")]
+    #[test]
+    fn test_create_producer() {
+        // librdkafka connects lazily, so no broker is needed
+        assert!(exporter(KafkaConfig::new("127.0.0.1:9092")).is_ok());
+        assert!(exporter(KafkaConfig::new("127.0.0.1:9092").with_topic("")).is_err());
+    }
+
+    #[cfg(not(feature = "kafka"))]
+    #[test]
+    fn test_requires_feature() {
+        assert!(exporter(KafkaConfig::new("127.0.0.1:9092")).is_err());
+    }
+}
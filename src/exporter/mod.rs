@@ -0,0 +1,457 @@
+//! Push exporters - send the converted metrics to remote systems
+//!
+//! Responsibilities:
+//! - Convert the published metrics of all collected sandboxes once per
+//!   collection cycle, exactly as `/metrics` serves them
+//! - Hand the batch to every configured exporter (remote_write, StatsD,
+//!   Pushgateway, JSON-lines file, Kafka, CloudWatch EMF, ...)
+//! - Keep a slow destination from stalling collection: a push still in
+//!   flight when the next cycle finishes makes that cycle's push be skipped
+//! - Count pushes per exporter for `/metrics` and `/debug/state`
+
+pub mod emf;
+pub mod http;
+pub mod jsonl;
+pub mod kafka;
+pub mod pushgateway;
+pub mod remote_write;
+pub mod statsd;
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+use crate::config::reload::SharedConfig;
+use crate::monitor::conversion_pool::{ConversionJob, ConversionPool};
+use crate::monitor::metrics_cache::MetricsCache;
+use crate::monitor::sandbox_cache::{SandboxCRIMetadata, SandboxCache};
+use crate::utils::metrics_converter::LabelEnricher;
+use crate::utils::prometheus_parser::PrometheusMetrics;
+
+/// Key converted metrics sent by the exporters that take a handful of series
+/// (StatsD, CloudWatch EMF) and how they are sent
+const KEY_METRICS: &[(&str, KeyMetricKind)] = &[
+    ("container_cpu_usage_seconds_total", KeyMetricKind::Counter),
+    ("container_memory_usage_bytes", KeyMetricKind::Gauge),
+    ("container_memory_working_set_bytes", KeyMetricKind::Gauge),
+    ("container_memory_cache_bytes", KeyMetricKind::Gauge),
+    (
+        "container_network_receive_bytes_total",
+        KeyMetricKind::Counter,
+    ),
+    (
+        "container_network_transmit_bytes_total",
+        KeyMetricKind::Counter,
+    ),
+    ("container_disk_io_reads_total", KeyMetricKind::Counter),
+    ("container_disk_io_writes_total", KeyMetricKind::Counter),
+    ("container_processes_count", KeyMetricKind::Gauge),
+    ("container_threads_count", KeyMetricKind::Gauge),
+    ("kata_pod_shim_rss_bytes", KeyMetricKind::Gauge),
+];
+
+/// How a key metric is sent
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum KeyMetricKind {
+    /// Current value
+    Gauge,
+    /// Increment since the previous push
+    Counter,
+}
+
+/// The families of `metrics` holding key metrics
+fn key_metrics(metrics: &PrometheusMetrics) -> PrometheusMetrics {
+    let mut key = PrometheusMetrics::new();
+    for (family, metric) in &metrics.metrics {
+        if metric
+            .samples
+            .iter()
+            .any(|sample| KEY_METRICS.iter().any(|(name, _)| *name == sample.name))
+        {
+            key.metrics.insert(family.clone(), metric.clone());
+        }
+    }
+    key
+}
+
+/// Converted metrics of all collected sandboxes at the end of a collection cycle
+pub struct ExportBatch {
+    /// Converted metrics of each sandbox
+    pub sandboxes: Vec<SandboxExport>,
+    /// End of the collection cycle in milliseconds since the Unix epoch
+    pub timestamp_ms: i64,
+}
+
+impl ExportBatch {
+    /// Converted metrics of all sandboxes in one set, labelled per pod
+    pub fn merged(&self) -> PrometheusMetrics {
+        let mut metrics = PrometheusMetrics::new();
+        for sandbox in &self.sandboxes {
+            for conflict in metrics.merge(sandbox.metrics.clone()) {
+                debug!(sandbox_id = %sandbox.sandbox_id, conflict = %conflict, "Conflicting exported metric family");
+            }
+        }
+        metrics
+    }
+}
+
+/// Converted metrics of a sandbox
+pub struct SandboxExport {
+    /// Sandbox ID
+    pub sandbox_id: String,
+    /// CRI metadata of the sandbox (pod name, namespace, UID)
+    pub metadata: SandboxCRIMetadata,
+    /// Converted metrics, labelled with the pod
+    pub metrics: PrometheusMetrics,
+}
+
+impl SandboxExport {
+    /// JSON document of the sandbox's metrics at `timestamp_ms`
+    pub fn to_json(&self, timestamp_ms: i64) -> serde_json::Result<Vec<u8>> {
+        serde_json::to_vec(&SandboxDocument {
+            timestamp_ms,
+            sandbox_id: &self.sandbox_id,
+            pod: &self.metadata.name,
+            namespace: &self.metadata.namespace,
+            pod_uid: &self.metadata.uid,
+            metrics: &self.metrics,
+        })
+    }
+}
+
+/// JSON form of a sandbox's converted metrics, as written by the file and Kafka exporters
+#[derive(Serialize)]
+struct SandboxDocument<'a> {
+    timestamp_ms: i64,
+    sandbox_id: &'a str,
+    pod: &'a str,
+    namespace: &'a str,
+    pod_uid: &'a str,
+    metrics: &'a PrometheusMetrics,
+}
+
+/// A destination the converted metrics are pushed to after every collection cycle
+pub trait Exporter: Send + Sync {
+    /// Name used in logs and metric labels
+    fn name(&self) -> &'static str;
+
+    /// Push a batch, retrying as the destination allows
+    fn push<'a>(&'a self, batch: &'a ExportBatch) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Push results of an exporter
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ExportStats {
+    /// Successful pushes
+    pub successes: u64,
+    /// Failed pushes (after retries)
+    pub failures: u64,
+    /// Cycles skipped because the previous push was still in flight
+    pub skipped: u64,
+    /// Time of the last successful push in seconds since the Unix epoch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_success_unix_secs: Option<u64>,
+    /// Error of the last failed push
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// An exporter with its push state
+struct ExportTarget {
+    exporter: Arc<dyn Exporter>,
+    in_flight: Arc<AtomicBool>,
+    stats: Arc<Mutex<ExportStats>>,
+}
+
+/// Converts the published metrics after every collection cycle and pushes
+/// them to the configured exporters
+pub struct ExportPipeline {
+    sandbox_cache: Arc<SandboxCache>,
+    metrics_cache: Arc<MetricsCache>,
+    /// Sandbox filter and conversion rules; re-read every cycle
+    config: Arc<SharedConfig>,
+    enricher: Arc<dyn LabelEnricher>,
+    pool: ConversionPool,
+    targets: Vec<ExportTarget>,
+}
+
+impl ExportPipeline {
+    /// Create a pipeline without exporters
+    pub fn new(
+        sandbox_cache: Arc<SandboxCache>,
+        metrics_cache: Arc<MetricsCache>,
+        config: Arc<SharedConfig>,
+        enricher: Arc<dyn LabelEnricher>,
+    ) -> Self {
+        ExportPipeline {
+            sandbox_cache,
+            metrics_cache,
+            config,
+            enricher,
+            pool: ConversionPool::default(),
+            targets: Vec::new(),
+        }
+    }
+
+    /// Convert on `pool` instead of a pool with one worker per CPU
+    pub fn with_conversion_pool(mut self, pool: ConversionPool) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Add an exporter
+    pub fn with_exporter(mut self, exporter: Arc<dyn Exporter>) -> Self {
+        self.targets.push(ExportTarget {
+            exporter,
+            in_flight: Arc::new(AtomicBool::new(false)),
+            stats: Arc::new(Mutex::new(ExportStats::default())),
+        });
+        self
+    }
+
+    /// Whether no exporter is configured
+    pub fn is_empty(&self) -> bool {
+        self.targets.is_empty()
+    }
+
+    /// Push the currently published metrics to all exporters
+    ///
+    /// Pushes run in the background; an exporter whose previous push is still
+    /// in flight skips this cycle.
+    pub async fn export_cycle(&self) {
+        if self.targets.is_empty() {
+            return;
+        }
+        let batch = Arc::new(self.build_batch().await);
+        debug!(
+            sandboxes = batch.sandboxes.len(),
+            "Pushing converted metrics to exporters"
+        );
+
+        for target in &self.targets {
+            if target.in_flight.swap(true, Ordering::AcqRel) {
+                warn!(
+                    exporter = target.exporter.name(),
+                    "Previous push still in flight, skipping cycle"
+                );
+                lock(&target.stats).skipped += 1;
+                continue;
+            }
+
+            let exporter = target.exporter.clone();
+            let in_flight = target.in_flight.clone();
+            let stats = target.stats.clone();
+            let batch = batch.clone();
+            tokio::spawn(async move {
+                let result = exporter.push(&batch).await;
+                let mut stats = lock(&stats);
+                match result {
+                    Ok(()) => {
+                        debug!(exporter = exporter.name(), "Pushed metrics");
+                        stats.successes += 1;
+                        stats.last_success_unix_secs = Some(unix_now().as_secs());
+                        stats.last_error = None;
+                    }
+                    Err(e) => {
+                        warn!(exporter = exporter.name(), error = %format!("{:#}", e), "Failed to push metrics");
+                        stats.failures += 1;
+                        stats.last_error = Some(format!("{:#}", e));
+                    }
+                }
+                in_flight.store(false, Ordering::Release);
+            });
+        }
+    }
+
+    /// Convert the published metrics of all collected sandboxes
+    async fn build_batch(&self) -> ExportBatch {
+        let published = self.metrics_cache.published().await;
+        let sandboxes = self.sandbox_cache.get_sandboxes_with_metadata().await;
+        let config = self.config.load();
+
+        let mut jobs = Vec::new();
+        let mut collected = Vec::new();
+        for (sandbox_id, metadata) in sandboxes {
+            if !config.sandbox_filter.allows(&metadata) {
+                continue;
+            }
+            let Some(cached) = published.get(&sandbox_id) else {
+                continue;
+            };
+
+            jobs.push(ConversionJob {
+                sandbox_id: sandbox_id.clone(),
+                metrics: cached.clone(),
+                labels: self.enricher.enrich(&sandbox_id).await,
+            });
+            collected.push((sandbox_id, metadata));
+        }
+
+        let converted = self.pool.convert(&config, jobs).await;
+        let mut exports = Vec::new();
+        for ((sandbox_id, metadata), result) in collected.into_iter().zip(converted) {
+            match result {
+                Ok(text) => {
+                    let (metrics, _) = PrometheusMetrics::parse_with_diagnostics(&text);
+                    exports.push(SandboxExport {
+                        sandbox_id,
+                        metadata,
+                        metrics,
+                    });
+                }
+                Err(e) => {
+                    warn!(sandbox_id = %sandbox_id, error = %e, "Failed to convert metrics for export")
+                }
+            }
+        }
+
+        ExportBatch {
+            sandboxes: exports,
+            timestamp_ms: unix_now().as_millis().try_into().unwrap_or(i64::MAX),
+        }
+    }
+
+    /// Push results per exporter
+    pub fn stats(&self) -> Vec<(&'static str, ExportStats)> {
+        self.targets
+            .iter()
+            .map(|target| (target.exporter.name(), lock(&target.stats).clone()))
+            .collect()
+    }
+
+    /// Render the push results in Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        let mut output = String::new();
+        if self.targets.is_empty() {
+            return output;
+        }
+        let stats = self.stats();
+
+        output.push_str("# HELP kata_pulse_export_pushes_total Pushes of the converted metrics by exporter and result\n");
+        output.push_str("# TYPE kata_pulse_export_pushes_total counter\n");
+        for (name, stats) in &stats {
+            for (result, count) in [
+                ("success", stats.successes),
+                ("failure", stats.failures),
+                ("skipped", stats.skipped),
+            ] {
+                output.push_str(&format!(
+                    "kata_pulse_export_pushes_total{{exporter=\"{}\",result=\"{}\"}} {}\n",
+                    name, result, count
+                ));
+            }
+        }
+
+        output.push_str("# HELP kata_pulse_export_last_success_timestamp_seconds Time of the last successful push by exporter\n");
+        output.push_str("# TYPE kata_pulse_export_last_success_timestamp_seconds gauge\n");
+        for (name, stats) in &stats {
+            output.push_str(&format!(
+                "kata_pulse_export_last_success_timestamp_seconds{{exporter=\"{}\"}} {}\n",
+                name,
+                stats.last_success_unix_secs.unwrap_or(0)
+            ));
+        }
+        output
+    }
+}
+
+fn lock(stats: &Mutex<ExportStats>) -> std::sync::MutexGuard<'_, ExportStats> {
+    stats.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn unix_now() -> std::time::Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::reload::ReloadableConfig;
+    use crate::utils::metrics_converter::{CRILabelEnricher, ConversionConfig};
+    use crate::utils::prometheus_parser::MetricSample;
+    use std::time::Duration;
+
+    /// Records the batches it receives
+    #[derive(Default)]
+    struct RecordingExporter {
+        batches: Mutex<Vec<usize>>,
+    }
+
+    impl Exporter for RecordingExporter {
+        fn name(&self) -> &'static str {
+            "recording"
+        }
+
+        fn push<'a>(&'a self, batch: &'a ExportBatch) -> BoxFuture<'a, Result<()>> {
+            Box::pin(async move {
+                self.batches
+                    .lock()
+                    .unwrap()
+                    .push(batch.merged().metrics.len());
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_cycle_pushes_converted_metrics() {
+        let sandbox_cache = Arc::new(SandboxCache::new());
+        sandbox_cache
+            .put_if_not_exists(
+                "sb-1",
+                SandboxCRIMetadata {
+                    uid: "uid-1".to_string(),
+                    name: "my-pod".to_string(),
+                    namespace: "default".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await;
+        let metrics_cache = Arc::new(MetricsCache::new());
+        let mut metrics = PrometheusMetrics::new();
+        metrics.push_sample(
+            "gauge",
+            "Guest memory",
+            MetricSample::new("kata_guest_meminfo", 1.0),
+        );
+        metrics_cache.start_collection().await;
+        metrics_cache.add_metrics("sb-1".to_string(), metrics).await;
+        metrics_cache.finish_collection().await;
+
+        let exporter = Arc::new(RecordingExporter::default());
+        let pipeline = ExportPipeline::new(
+            sandbox_cache.clone(),
+            metrics_cache,
+            Arc::new(SharedConfig::new(ReloadableConfig {
+                metrics_interval_secs: 60,
+                sandbox_filter: Default::default(),
+                name_filter: Default::default(),
+                conversion: ConversionConfig::default(),
+            })),
+            Arc::new(CRILabelEnricher::new(sandbox_cache)),
+        )
+        .with_exporter(exporter.clone());
+
+        pipeline.export_cycle().await;
+        for _ in 0..100 {
+            if pipeline.stats()[0].1.successes == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let batches = exporter.batches.lock().unwrap().clone();
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0] > 0, "converted families should be exported");
+        let output = pipeline.render_metrics();
+        assert!(output.contains(
+            "kata_pulse_export_pushes_total{exporter=\"recording\",result=\"success\"} 1"
+        ));
+    }
+}
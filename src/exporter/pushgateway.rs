@@ -0,0 +1,172 @@
+//! Prometheus Pushgateway exporter
+//!
+//! Responsibilities:
+//! - PUT the converted metrics of every pod to the Pushgateway, grouped by
+//!   `job`, `namespace` and `pod` so each sandbox replaces only its own group
+//! - DELETE the groups of sandboxes that are gone, so the gateway does not
+//!   serve their last values forever
+//!
+//! Used in air-gapped environments where the only egress is the gateway.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use futures::future::BoxFuture;
+use hyper::body::Bytes;
+use hyper::header::CONTENT_TYPE;
+use hyper::Method;
+use std::collections::BTreeSet;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+use super::http::{HttpPusher, PushConfig};
+use super::{ExportBatch, Exporter};
+use crate::utils::metrics_converter::cadvisor::PrometheusFormat;
+
+/// Default `job` grouping label
+pub const DEFAULT_JOB: &str = "kata-pulse";
+
+/// Labels identifying the sandbox of a sample, in grouping key order
+const GROUPING_LABELS: [&str; 2] = ["namespace", "pod"];
+
+/// Pushes the converted metrics to a Prometheus Pushgateway, one group per pod
+pub struct PushgatewayExporter {
+    pusher: HttpPusher,
+    job: String,
+    /// Grouping key paths pushed by the previous push
+    pushed: Mutex<BTreeSet<String>>,
+}
+
+impl PushgatewayExporter {
+    /// Create an exporter pushing to the gateway at the URL of `config` under `job`
+    pub fn new(config: PushConfig, job: impl Into<String>) -> Result<Self> {
+        let job = job.into();
+        if job.is_empty() {
+            anyhow::bail!("Pushgateway job must not be empty");
+        }
+        Ok(PushgatewayExporter {
+            pusher: HttpPusher::new(config).context("invalid Pushgateway URL")?,
+            job,
+            pushed: Mutex::new(BTreeSet::new()),
+        })
+    }
+
+    /// Grouping key path of a sandbox, e.g. `/metrics/job/kata-pulse/namespace/default/pod/my-pod`
+    fn group_path(&self, values: &[String]) -> String {
+        let mut path = format!("/metrics/{}", encode_label("job", &self.job));
+        for (name, value) in GROUPING_LABELS.iter().zip(values) {
+            path.push('/');
+            path.push_str(&encode_label(name, value));
+        }
+        path
+    }
+}
+
+impl Exporter for PushgatewayExporter {
+    fn name(&self) -> &'static str {
+        "pushgateway"
+    }
+
+    fn push<'a>(&'a self, batch: &'a ExportBatch) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let groups = batch.merged().partition_by(|sample| {
+                GROUPING_LABELS.map(|name| {
+                    sample
+                        .labels
+                        .get(name)
+                        .map(|value| value.to_string())
+                        .unwrap_or_default()
+                })
+            });
+
+            let mut pushed = self.pushed.lock().await;
+            let mut current = BTreeSet::new();
+            let mut errors = Vec::new();
+            for (values, metrics) in groups {
+                let path = self.group_path(&values);
+                let body = Bytes::from(metrics.to_prometheus_format(None));
+                // PUT replaces every metric of the group
+                if let Err(e) = self
+                    .pusher
+                    .request(
+                        Method::PUT,
+                        &path,
+                        body,
+                        &[(CONTENT_TYPE, "text/plain; version=0.0.4")],
+                    )
+                    .await
+                {
+                    errors.push(e);
+                }
+                current.insert(path);
+            }
+
+            // Groups of sandboxes that are gone; kept for the next push if the delete fails
+            let stale: Vec<String> = pushed.difference(&current).cloned().collect();
+            for path in stale {
+                match self
+                    .pusher
+                    .request(Method::DELETE, &path, Bytes::new(), &[])
+                    .await
+                {
+                    Ok(()) => debug!(group = %path, "Deleted Pushgateway group of removed sandbox"),
+                    Err(e) => {
+                        warn!(group = %path, error = %format!("{:#}", e), "Failed to delete Pushgateway group");
+                        current.insert(path);
+                    }
+                }
+            }
+            *pushed = current;
+
+            match errors.len() {
+                0 => Ok(()),
+                failed => Err(errors
+                    .swap_remove(0)
+                    .context(format!("{} Pushgateway group(s) failed", failed))),
+            }
+        })
+    }
+}
+
+/// Encode a grouping label as a path segment pair
+///
+/// Values that are empty or contain `/` use the base64 (URL-safe) form the
+/// Pushgateway supports.
+fn encode_label(name: &str, value: &str) -> String {
+    if value.is_empty() {
+        format!("{}@base64/=", name)
+    } else if value.contains('/') || !value.chars().all(is_path_safe) {
+        format!(
+            "{}@base64/{}",
+            name,
+            base64::engine::general_purpose::URL_SAFE.encode(value)
+        )
+    } else {
+        format!("{}/{}", name, value)
+    }
+}
+
+/// Characters allowed unencoded in a grouping label value
+fn is_path_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_path() {
+        let exporter =
+            PushgatewayExporter::new(PushConfig::new("http://gateway:9091"), DEFAULT_JOB).unwrap();
+        assert_eq!(
+            exporter.group_path(&["default".to_string(), "my-pod".to_string()]),
+            "/metrics/job/kata-pulse/namespace/default/pod/my-pod"
+        );
+        assert_eq!(
+            exporter.group_path(&["default".to_string(), String::new()]),
+            "/metrics/job/kata-pulse/namespace/default/pod@base64/="
+        );
+        assert_eq!(encode_label("pod", "a/b"), "pod@base64/YS9i");
+        assert!(PushgatewayExporter::new(PushConfig::new("http://gateway:9091"), "").is_err());
+    }
+}
@@ -0,0 +1,195 @@
+//! Prometheus remote_write exporter
+//!
+//! Responsibilities:
+//! - Encode the converted metrics as a remote_write 1.0 `WriteRequest`
+//!   (protobuf, snappy block compression)
+//! - Push it to the configured endpoint after every collection cycle
+//!
+//! Used on nodes that cannot be scraped inbound.
+
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use hyper::body::Bytes;
+use hyper::header::{HeaderName, CONTENT_ENCODING, CONTENT_TYPE};
+use prost::Message;
+
+use super::http::{HttpPusher, PushConfig};
+use super::{ExportBatch, Exporter};
+use crate::utils::prometheus_parser::PrometheusMetrics;
+
+/// Label holding the metric name in remote_write series
+const NAME_LABEL: &str = "__name__";
+
+/// `prometheus.WriteRequest` of the remote_write 1.0 protocol
+#[derive(Clone, PartialEq, Message)]
+struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    timeseries: Vec<TimeSeries>,
+}
+
+/// `prometheus.TimeSeries`
+#[derive(Clone, PartialEq, Message)]
+struct TimeSeries {
+    /// Labels sorted by name, `__name__` included
+    #[prost(message, repeated, tag = "1")]
+    labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    samples: Vec<Sample>,
+}
+
+/// `prometheus.Label`
+#[derive(Clone, PartialEq, Message)]
+struct Label {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+/// `prometheus.Sample`
+#[derive(Clone, PartialEq, Message)]
+struct Sample {
+    #[prost(double, tag = "1")]
+    value: f64,
+    /// Milliseconds since the Unix epoch
+    #[prost(int64, tag = "2")]
+    timestamp: i64,
+}
+
+/// Pushes the converted metrics to a Prometheus remote_write endpoint
+pub struct RemoteWriteExporter {
+    pusher: HttpPusher,
+}
+
+impl RemoteWriteExporter {
+    /// Create an exporter pushing to the endpoint of `config`
+    pub fn new(config: PushConfig) -> Result<Self> {
+        Ok(RemoteWriteExporter {
+            pusher: HttpPusher::new(config).context("invalid remote_write endpoint")?,
+        })
+    }
+}
+
+impl Exporter for RemoteWriteExporter {
+    fn name(&self) -> &'static str {
+        "remote_write"
+    }
+
+    fn push<'a>(&'a self, batch: &'a ExportBatch) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let body = encode(batch)?;
+            self.pusher
+                .post(
+                    body,
+                    &[
+                        (CONTENT_TYPE, "application/x-protobuf"),
+                        (CONTENT_ENCODING, "snappy"),
+                        (
+                            HeaderName::from_static("x-prometheus-remote-write-version"),
+                            "0.1.0",
+                        ),
+                    ],
+                )
+                .await
+        })
+    }
+}
+
+/// Encode a batch as a snappy-compressed `WriteRequest`
+fn encode(batch: &ExportBatch) -> Result<Bytes> {
+    let request = write_request(batch);
+    let compressed = snap::raw::Encoder::new()
+        .compress_vec(&request.encode_to_vec())
+        .context("failed to compress remote_write request")?;
+    Ok(Bytes::from(compressed))
+}
+
+/// One series per sample, stamped with the sample's or the batch's timestamp
+fn write_request(batch: &ExportBatch) -> WriteRequest {
+    WriteRequest {
+        timeseries: batch
+            .sandboxes
+            .iter()
+            .flat_map(|sandbox| timeseries(&sandbox.metrics, batch.timestamp_ms))
+            .collect(),
+    }
+}
+
+/// Uncompressed `WriteRequest` of a single set of metrics (Kafka protobuf payloads)
+#[cfg(feature = "kafka")]
+pub(super) fn encode_metrics(metrics: &PrometheusMetrics, timestamp_ms: i64) -> Vec<u8> {
+    WriteRequest {
+        timeseries: timeseries(metrics, timestamp_ms),
+    }
+    .encode_to_vec()
+}
+
+/// One series per sample of `metrics`, stamped with the sample's timestamp or `timestamp_ms`
+fn timeseries(metrics: &PrometheusMetrics, timestamp_ms: i64) -> Vec<TimeSeries> {
+    let mut timeseries = Vec::new();
+    for metric in metrics.metrics.values() {
+        for sample in &metric.samples {
+            let mut labels: Vec<Label> = sample
+                .labels
+                .iter()
+                .map(|(name, value)| Label {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect();
+            labels.push(Label {
+                name: NAME_LABEL.to_string(),
+                value: sample.name.clone(),
+            });
+            labels.sort_by(|a, b| a.name.cmp(&b.name));
+
+            timeseries.push(TimeSeries {
+                labels,
+                samples: vec![Sample {
+                    value: sample.value,
+                    timestamp: sample.timestamp.unwrap_or(timestamp_ms),
+                }],
+            });
+        }
+    }
+    timeseries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporter::SandboxExport;
+
+    #[test]
+    fn test_encode_write_request() {
+        let metrics = PrometheusMetrics::parse(
+            "# TYPE container_memory_usage_bytes gauge\ncontainer_memory_usage_bytes{pod=\"my-pod\",namespace=\"default\"} 1024\n",
+        )
+        .unwrap();
+        let batch = ExportBatch {
+            sandboxes: vec![SandboxExport {
+                sandbox_id: "sb-1".to_string(),
+                metadata: Default::default(),
+                metrics,
+            }],
+            timestamp_ms: 1_760_620_000_000,
+        };
+
+        let body = encode(&batch).unwrap();
+        let decoded = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+        let request = WriteRequest::decode(decoded.as_slice()).unwrap();
+
+        assert_eq!(request.timeseries.len(), 1);
+        let series = &request.timeseries[0];
+        let names: Vec<&str> = series.labels.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, vec!["__name__", "namespace", "pod"]);
+        assert_eq!(series.labels[0].value, "container_memory_usage_bytes");
+        assert_eq!(
+            series.samples,
+            vec![Sample {
+                value: 1024.0,
+                timestamp: 1_760_620_000_000,
+            }]
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! StatsD / DogStatsD exporter
+//!
+//! Responsibilities:
+//! - Emit the key converted gauges and counters of every pod over UDP after
+//!   each collection cycle
+//! - Send counters as increments since the previous push (StatsD counters are
+//!   deltas), gauges as their current value
+//! - Tag series with their labels (DogStatsD) or fold the labels into the
+//!   metric name (plain StatsD)
+//!
+//! Used on nodes whose agents ingest StatsD instead of scraping `/metrics`.
+
+use anyhow::{Context, Result};
+use futures::future::BoxFuture;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+use tokio::net::UdpSocket;
+
+use super::{key_metrics, ExportBatch, Exporter, KeyMetricKind, KEY_METRICS};
+use crate::utils::prometheus_parser::{MetricSample, PrometheusMetrics};
+
+/// Default prefix of the metric names
+pub const DEFAULT_PREFIX: &str = "kata";
+
+/// Maximum size of a datagram, below the usual 1500 byte MTU
+const MAX_DATAGRAM_SIZE: usize = 1432;
+
+/// Labels that identify the series but are not worth a tag
+const SKIPPED_LABELS: &[&str] = &["id", "image", "name"];
+
+/// Line protocol of the StatsD server
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatsdFlavor {
+    /// Plain StatsD: labels are folded into the metric name
+    #[default]
+    Statsd,
+    /// DogStatsD: labels are sent as `|#name:value` tags
+    DogStatsd,
+}
+
+impl FromStr for StatsdFlavor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "statsd" => Ok(StatsdFlavor::Statsd),
+            "dogstatsd" => Ok(StatsdFlavor::DogStatsd),
+            _ => anyhow::bail!(
+                "unknown StatsD flavor {:?} (expected statsd or dogstatsd)",
+                s
+            ),
+        }
+    }
+}
+
+impl fmt::Display for StatsdFlavor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StatsdFlavor::Statsd => "statsd",
+            StatsdFlavor::DogStatsd => "dogstatsd",
+        })
+    }
+}
+
+/// Server and line format of the StatsD exporter
+#[derive(Clone, Debug)]
+pub struct StatsdConfig {
+    /// `host:port` of the server, resolved before every push
+    pub address: String,
+
+    /// Prefix of the metric names
+    pub prefix: String,
+
+    /// Line protocol of the server
+    pub flavor: StatsdFlavor,
+}
+
+impl StatsdConfig {
+    /// Create a config for `address` with the `kata` prefix and plain StatsD lines
+    pub fn new(address: impl Into<String>) -> Self {
+        StatsdConfig {
+            address: address.into(),
+            prefix: DEFAULT_PREFIX.to_string(),
+            flavor: StatsdFlavor::default(),
+        }
+    }
+
+    /// Set the prefix of the metric names (empty for none)
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Set the line protocol of the server
+    pub fn with_flavor(mut self, flavor: StatsdFlavor) -> Self {
+        self.flavor = flavor;
+        self
+    }
+}
+
+/// Pushes the key converted metrics to a StatsD server
+pub struct StatsdExporter {
+    config: StatsdConfig,
+    /// Metrics of the previous push, to turn counters into increments
+    previous: Mutex<Option<PrometheusMetrics>>,
+}
+
+impl StatsdExporter {
+    /// Create an exporter sending to the server of `config`
+    pub fn new(mut config: StatsdConfig) -> Self {
+        config.prefix = config.prefix.trim_end_matches('.').to_string();
+        StatsdExporter {
+            config,
+            previous: Mutex::new(None),
+        }
+    }
+
+    /// StatsD lines of a batch; counters are only sent once a previous push exists
+    fn lines(&self, batch: &ExportBatch) -> Vec<String> {
+        let key_metrics = key_metrics(&batch.merged());
+        let mut previous = self.previous.lock().unwrap_or_else(|e| e.into_inner());
+        let deltas = previous.as_ref().map(|previous| key_metrics.diff(previous));
+
+        let mut lines = Vec::new();
+        for (name, kind) in KEY_METRICS {
+            let source = match kind {
+                KeyMetricKind::Gauge => Some(&key_metrics),
+                KeyMetricKind::Counter => deltas.as_ref(),
+            };
+            for sample in source
+                .into_iter()
+                .flat_map(|metrics| samples(metrics, name))
+            {
+                if sample.value.is_finite() {
+                    lines.push(self.line(sample, *kind));
+                }
+            }
+        }
+
+        *previous = Some(key_metrics);
+        lines
+    }
+
+    /// Format a sample as a StatsD line
+    fn line(&self, sample: &MetricSample, kind: KeyMetricKind) -> String {
+        let mut labels: Vec<(&str, &str)> = sample
+            .labels
+            .iter()
+            .map(|(name, value)| (name.as_ref(), value.as_ref()))
+            .filter(|(name, value)| !value.is_empty() && !SKIPPED_LABELS.contains(name))
+            .collect();
+        labels.sort();
+        let suffix = match kind {
+            KeyMetricKind::Gauge => "g",
+            KeyMetricKind::Counter => "c",
+        };
+
+        let mut name = sanitize(&sample.name);
+        if !self.config.prefix.is_empty() {
+            name = format!("{}.{}", sanitize(&self.config.prefix), name);
+        }
+        match self.config.flavor {
+            StatsdFlavor::Statsd => {
+                for (_, value) in &labels {
+                    name.push('.');
+                    name.push_str(&name_segment(value));
+                }
+                format!("{}:{}|{}", name, sample.value, suffix)
+            }
+            StatsdFlavor::DogStatsd => {
+                let tags: Vec<String> = labels
+                    .iter()
+                    .map(|(name, value)| format!("{}:{}", name, sanitize(value)))
+                    .collect();
+                if tags.is_empty() {
+                    format!("{}:{}|{}", name, sample.value, suffix)
+                } else {
+                    format!("{}:{}|{}|#{}", name, sample.value, suffix, tags.join(","))
+                }
+            }
+        }
+    }
+}
+
+impl Exporter for StatsdExporter {
+    fn name(&self) -> &'static str {
+        "statsd"
+    }
+
+    fn push<'a>(&'a self, batch: &'a ExportBatch) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let lines = self.lines(batch);
+            if lines.is_empty() {
+                return Ok(());
+            }
+
+            let socket = UdpSocket::bind("0.0.0.0:0")
+                .await
+                .context("failed to bind StatsD socket")?;
+            socket
+                .connect(&self.config.address)
+                .await
+                .with_context(|| {
+                    format!("failed to resolve StatsD server {}", self.config.address)
+                })?;
+            for datagram in datagrams(&lines) {
+                socket.send(datagram.as_bytes()).await.with_context(|| {
+                    format!("failed to send to StatsD server {}", self.config.address)
+                })?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Samples named `name`
+fn samples<'a>(
+    metrics: &'a PrometheusMetrics,
+    name: &'a str,
+) -> impl Iterator<Item = &'a MetricSample> {
+    metrics
+        .metrics
+        .values()
+        .flat_map(|metric| &metric.samples)
+        .filter(move |sample| sample.name == name)
+}
+
+/// Replace the characters with a meaning in the StatsD protocol
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            ':' | '|' | '@' | '#' | ',' | '\n' | ' ' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Sanitize a label value folded into a dotted metric name
+fn name_segment(value: &str) -> String {
+    sanitize(value).replace(['.', '/'], "_")
+}
+
+/// Pack lines into newline-separated datagrams of at most `MAX_DATAGRAM_SIZE` bytes
+fn datagrams(lines: &[String]) -> Vec<String> {
+    let mut datagrams = Vec::new();
+    let mut current = String::new();
+    for line in lines {
+        if !current.is_empty() && current.len() + 1 + line.len() > MAX_DATAGRAM_SIZE {
+            datagrams.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        datagrams.push(current);
+    }
+    datagrams
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporter::SandboxExport;
+
+    fn batch(cpu: f64, memory: f64) -> ExportBatch {
+        let text = format!(
+            "# TYPE container_cpu_usage_seconds_total counter\n\
+             container_cpu_usage_seconds_total{{pod=\"my-pod\",namespace=\"default\",cpu=\"total\",id=\"/kubepods\"}} {}\n\
+             # TYPE container_memory_usage_bytes gauge\n\
+             container_memory_usage_bytes{{pod=\"my-pod\",namespace=\"default\",image=\"\"}} {}\n\
+             # TYPE container_fs_usage_bytes gauge\n\
+             container_fs_usage_bytes{{pod=\"my-pod\",namespace=\"default\"}} 1\n",
+            cpu, memory
+        );
+        ExportBatch {
+            sandboxes: vec![SandboxExport {
+                sandbox_id: "sb-1".to_string(),
+                metadata: Default::default(),
+                metrics: PrometheusMetrics::parse(&text).unwrap(),
+            }],
+            timestamp_ms: 0,
+        }
+    }
+
+    #[test]
+    fn test_dogstatsd_lines() {
+        let exporter = StatsdExporter::new(
+            StatsdConfig::new("127.0.0.1:8125")
+                .with_prefix("kata.")
+                .with_flavor(StatsdFlavor::DogStatsd),
+        );
+
+        // Counters need a previous push
+        assert_eq!(
+            exporter.lines(&batch(10.0, 1024.0)),
+            vec!["kata.container_memory_usage_bytes:1024|g|#namespace:default,pod:my-pod"]
+        );
+        assert_eq!(
+            exporter.lines(&batch(12.5, 2048.0)),
+            vec![
+                "kata.container_cpu_usage_seconds_total:2.5|c|#cpu:total,namespace:default,pod:my-pod",
+                "kata.container_memory_usage_bytes:2048|g|#namespace:default,pod:my-pod",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_statsd_lines_fold_labels_into_name() {
+        let exporter = StatsdExporter::new(StatsdConfig::new("127.0.0.1:8125"));
+        assert_eq!(
+            exporter.lines(&batch(10.0, 1024.0)),
+            vec!["kata.container_memory_usage_bytes.default.my-pod:1024|g"]
+        );
+        assert_eq!(
+            "dogstatsd".parse::<StatsdFlavor>().unwrap(),
+            StatsdFlavor::DogStatsd
+        );
+        assert!("graphite".parse::<StatsdFlavor>().is_err());
+    }
+
+    #[test]
+    fn test_datagrams_respect_size_limit() {
+        let lines: Vec<String> = (0..100).map(|i| format!("metric_{}:{}|g", i, i)).collect();
+        let datagrams = datagrams(&lines);
+        assert!(datagrams.len() > 1);
+        assert!(datagrams.iter().all(|d| d.len() <= MAX_DATAGRAM_SIZE));
+        assert_eq!(datagrams.join("\n").lines().count(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_push_sends_datagram() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let exporter = StatsdExporter::new(
+            StatsdConfig::new(server.local_addr().unwrap().to_string())
+                .with_flavor(StatsdFlavor::DogStatsd),
+        );
+        exporter.push(&batch(1.0, 512.0)).await.unwrap();
+
+        let mut buf = [0u8; MAX_DATAGRAM_SIZE];
+        let len = server.recv(&mut buf).await.unwrap();
+        assert_eq!(
+            std::str::from_utf8(&buf[..len]).unwrap(),
+            "kata.container_memory_usage_bytes:512|g|#namespace:default,pod:my-pod"
+        );
+    }
+}
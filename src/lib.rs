@@ -0,0 +1,17 @@
+//! kata-pulse - real-time metrics for Kata Containers
+//!
+//! The agent's modules, shared by the `kata-pulse` binary and the benchmarks.
+
+pub mod aggregator;
+pub mod auth;
+pub mod build_info;
+pub mod cli;
+pub mod config;
+pub mod context;
+pub mod exporter;
+pub mod log_level;
+pub mod monitor;
+pub mod otlp;
+pub mod server;
+pub mod tenants;
+pub mod utils;
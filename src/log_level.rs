@@ -0,0 +1,97 @@
+//! Runtime log level
+//!
+//! Responsibilities:
+//! - Map log level names to the log filter of the subscriber
+//! - Swap the filter of the running subscriber, so debug logging can be
+//!   turned on for a live node without a restart (and without losing the
+//!   caches)
+
+use anyhow::{Context, Result};
+use std::sync::Mutex;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// Log level names accepted at runtime
+pub const LOG_LEVELS: [&str; 5] = ["trace", "debug", "info", "warn", "error"];
+
+/// Handle changing the filter of the running subscriber
+pub type LogLevelHandle = reload::Handle<EnvFilter, Registry>;
+
+/// Log filter of a log level name (unknown names log at info)
+pub fn log_filter(log_level: &str) -> EnvFilter {
+    match log_level {
+        "trace" => EnvFilter::new("trace"),
+        "debug" => EnvFilter::new("debug"),
+        "info" => EnvFilter::new("info"),
+        "warn" => EnvFilter::new("warn"),
+        "error" => EnvFilter::new("error"),
+        _ => EnvFilter::new("info"),
+    }
+}
+
+/// Log level of the running subscriber
+#[derive(Debug)]
+pub struct LogLevel {
+    handle: LogLevelHandle,
+    current: Mutex<String>,
+}
+
+impl LogLevel {
+    /// Wrap the handle of a subscriber started at `log_level`
+    pub fn new(handle: LogLevelHandle, log_level: impl Into<String>) -> Self {
+        LogLevel {
+            handle,
+            current: Mutex::new(log_level.into()),
+        }
+    }
+
+    /// The current log level name
+    pub fn current(&self) -> String {
+        self.current
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Switch to `log_level`, which must be one of [`LOG_LEVELS`]
+    pub fn set(&self, log_level: &str) -> Result<()> {
+        if !LOG_LEVELS.contains(&log_level) {
+            anyhow::bail!(
+                "unknown log level {:?} (expected one of {})",
+                log_level,
+                LOG_LEVELS.join(", ")
+            );
+        }
+        self.reload(log_level)
+    }
+
+    /// Switch to `log_level`, logging at info for unknown names like `--log-level`
+    pub fn reload(&self, log_level: &str) -> Result<()> {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        self.handle
+            .reload(log_filter(log_level))
+            .context("failed to change the log level")?;
+        *current = log_level.to_string();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_log_level() {
+        // The handle only works while the layer is alive
+        let (layer, handle) = reload::Layer::<_, Registry>::new(log_filter("info"));
+        let log_level = LogLevel::new(handle, "info");
+
+        log_level.set("debug").unwrap();
+        assert_eq!(log_level.current(), "debug");
+        assert!(log_level.set("verbose").is_err());
+        assert_eq!(log_level.current(), "debug");
+
+        drop(layer);
+        assert!(log_level.set("warn").is_err());
+        assert_eq!(log_level.current(), "debug");
+    }
+}
@@ -1,20 +1,14 @@
-mod config;
-mod context;
-mod monitor;
-mod server;
-mod utils;
-
 use anyhow::Result;
-use clap::Parser;
+use clap::{ArgMatches, FromArgMatches};
+use std::path::PathBuf;
 use tracing::info;
-use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use tracing_subscriber::{fmt as tracing_fmt, prelude::*, reload};
 
-const APP_NAME: &str = "kata-pulse";
-const VERSION: &str = env!("CARGO_PKG_VERSION");
-const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8090";
-const DEFAULT_RUNTIME_ENDPOINT: &str = "/run/containerd/containerd.sock";
-const DEFAULT_LOG_LEVEL: &str = "info";
-const DEFAULT_METRICS_INTERVAL_SECS: u64 = 60;
+use kata_pulse::config::settings::{Commands, LogFormat, Settings, APP_NAME, VERSION};
+use kata_pulse::log_level::{log_filter, LogLevel, LogLevelHandle};
+use kata_pulse::{
+    aggregator, auth, cli, config, context, exporter, monitor, otlp, server, tenants,
+};
 
 const BANNER: &str = r#"
 ╔═══════════════════════════════════════════════════════════════════╗
@@ -30,63 +24,80 @@ const BANNER: &str = r#"
 ╚═══════════════════════════════════════════════════════════════════╝
 "#;
 
-#[derive(Parser, Debug)]
-#[command(
-    name = APP_NAME,
-    version = VERSION,
-    about = "Real-time metrics for Kata Containers",
-    long_about = "KataPulse: cadvisor-compatible monitoring agent for Kata Containers. Provides metrics collection, sandbox management, and agent URL discovery"
-)]
-struct Args {
-    /// The address to listen on for HTTP requests
-    #[arg(
-        long,
-        env = "KATA_PULSE_LISTEN",
-        default_value = DEFAULT_LISTEN_ADDRESS,
-        help = "The address to listen on for HTTP requests"
-    )]
-    listen_address: String,
-
-    /// Endpoint of CRI container runtime service
-    #[arg(
-        long,
-        env = "RUNTIME_ENDPOINT",
-        default_value = DEFAULT_RUNTIME_ENDPOINT,
-        help = "Endpoint of CRI container runtime service"
-    )]
-    runtime_endpoint: String,
-
-    /// Log level
-    #[arg(
-        long,
-        env = "RUST_LOG",
-        default_value = DEFAULT_LOG_LEVEL,
-        help = "Log level (trace/debug/info/warn/error)"
-    )]
-    log_level: String,
-
-    /// Metrics collection interval in seconds
-    #[arg(
-        long,
-        env = "KATA_PULSE_METRICS_INTERVAL",
-        default_value_t = DEFAULT_METRICS_INTERVAL_SECS,
-        help = "Metrics collection interval in seconds"
-    )]
-    metrics_interval_secs: u64,
-}
+/// jemalloc replaces the system allocator with the `jemalloc` feature, so its
+/// heap statistics can be exported
+#[cfg(feature = "jemalloc")]
+#[global_allocator]
+static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    // Report configuration errors instead of failing to start
+    let matches = Settings::cli().get_matches();
+    if let Some(("validate-config", _)) = matches.subcommand() {
+        std::process::exit(run_validate_config(&matches));
+    }
+
+    let (args, config_file) = match Settings::load(matches) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Failed to load configuration: {:#}", e);
+            return;
+        }
+    };
+
+    // Initialize logging (the guard flushes exported spans on exit)
+    let (_otlp_guard, log_level_handle) = match init_logging(
+        &args.log_level,
+        args.log_format,
+        args.otlp_endpoint.as_deref(),
+    ) {
+        Ok(logging) => logging,
+        Err(e) => {
+            eprintln!("Failed to initialize logging: {}", e);
+            return;
+        }
+    };
+
+    for env in Settings::deprecated_env() {
+        tracing::warn!("{}", env);
+    }
 
-    // Initialize logging
-    if let Err(e) = init_logging(&args.log_level) {
-        eprintln!("Failed to initialize logging: {}", e);
+    let log_level = std::sync::Arc::new(LogLevel::new(log_level_handle, args.log_level.clone()));
+
+    if let Err(e) = config::set_storage_paths(config_file.storage.clone()) {
+        eprintln!("Failed to apply storage paths: {}", e);
         return;
     }
 
-    // Print banner
-    println!("{}", BANNER);
+    // Run a subcommand instead of the agent
+    if let Some(command) = args.command.clone() {
+        let code = match command {
+            Commands::Check { json } => run_check(&args, json).await,
+            Commands::Scrape {
+                sandbox_id,
+                raw,
+                cadvisor: _,
+                json,
+            } => {
+                let format = match (raw, json) {
+                    (true, _) => cli::scrape::ScrapeFormat::Raw,
+                    (_, true) => cli::scrape::ScrapeFormat::Json,
+                    _ => cli::scrape::ScrapeFormat::Cadvisor,
+                };
+                run_scrape(&args, sandbox_id, format).await
+            }
+            Commands::List => run_list(&args).await,
+            Commands::ValidateConfig => unreachable!("handled before logging is initialized"),
+        };
+        drop(_otlp_guard);
+        std::process::exit(code);
+    }
+
+    // Print banner (not in JSON mode, where every line must be JSON)
+    if args.log_format == LogFormat::Text {
+        println!("{}", BANNER);
+    }
 
     // Validate arguments
     if args.metrics_interval_secs == 0 {
@@ -98,22 +109,206 @@ async fn main() {
     info!(
         app = APP_NAME,
         version = VERSION,
+        config = ?args.config,
         listen_address = %args.listen_address,
-        runtime_endpoint = %args.runtime_endpoint,
+        runtime_endpoints = ?args.runtime_endpoint,
+        cri_timeout = ?args.cri_timeout,
+        cri_max_retries = args.cri_max_retries,
+        cri_retry_backoff = ?args.cri_retry_backoff,
+        cri_keepalive_interval = ?args.cri_keepalive_interval,
+        cri_keepalive_timeout = ?args.cri_keepalive_timeout,
+        cri_max_message_size = args.cri_max_message_size,
+        metadata_retry_budget = args.metadata_retry_budget,
         log_level = %args.log_level,
+        log_format = %args.log_format,
+        admin_token_file = ?args.admin_token_file,
+        auth_token_review = args.auth_token_review,
+        auth_audiences = ?args.auth_audiences,
+        auth_cache_ttl = ?args.auth_cache_ttl,
+        tenants_file = ?args.tenants_file,
+        otlp_endpoint = ?args.otlp_endpoint,
         metrics_interval_secs = args.metrics_interval_secs,
+        metrics_ttl_secs = ?args.metrics_ttl_secs,
+        scrape_failure_threshold = args.scrape_failure_threshold,
+        scrape_max_backoff_cycles = args.scrape_max_backoff_cycles,
+        shim_max_retries = args.shim_max_retries,
+        shim_retry_backoff_ms = args.shim_retry_backoff_ms,
+        shim_max_payload_bytes = args.shim_max_payload_bytes,
+        cache_snapshot_path = ?args.cache_snapshot_path,
+        history_len = args.history_len,
+        conversion_workers = args.conversion_workers,
+        node_name = ?args.node_name,
+        node_zone = ?args.node_zone,
+        node_region = ?args.node_region,
+        namespace_allow = ?args.namespace_allow,
+        namespace_deny = ?args.namespace_deny,
+        runtime_handlers = ?args.runtime_handlers,
+        exclude_metadata_unavailable = args.exclude_metadata_unavailable,
+        disable_virtiofsd_metrics = args.disable_virtiofsd_metrics,
+        include_per_cpu = args.include_per_cpu,
+        include_per_interface = args.include_per_interface,
+        include_per_device = args.include_per_device,
+        network_interface_patterns = ?args.network_interface_patterns,
+        disk_device_patterns = ?args.disk_device_patterns,
+        guest_block_majors = ?args.guest_block_majors,
+        cpu_jiffy_conversion_factor = ?args.cpu_jiffy_conversion_factor,
+        host_cgroup_root = %args.host_cgroup_root.display(),
+        host_proc_root = %args.host_proc_root.display(),
+        parse_mode = %args.parse_mode,
+        drop_metric_prefixes = ?args.drop_metric_prefixes,
+        remote_write_url = ?args.remote_write_url,
+        remote_write_username = ?args.remote_write_username,
+        remote_write_bearer_token_file = ?args.remote_write_bearer_token_file,
+        remote_write_timeout = ?args.remote_write_timeout,
+        remote_write_max_retries = args.remote_write_max_retries,
+        remote_write_retry_backoff = ?args.remote_write_retry_backoff,
+        statsd_address = ?args.statsd_address,
+        statsd_prefix = %args.statsd_prefix,
+        statsd_flavor = %args.statsd_flavor,
+        pushgateway_url = ?args.pushgateway_url,
+        pushgateway_job = %args.pushgateway_job,
+        pushgateway_username = ?args.pushgateway_username,
+        pushgateway_bearer_token_file = ?args.pushgateway_bearer_token_file,
+        jsonl_path = ?args.jsonl_path,
+        jsonl_max_size = args.jsonl_max_size,
+        jsonl_max_age = ?args.jsonl_max_age,
+        jsonl_max_files = args.jsonl_max_files,
+        kafka_brokers = ?args.kafka_brokers,
+        kafka_topic = %args.kafka_topic,
+        kafka_format = %args.kafka_format,
+        kafka_timeout = ?args.kafka_timeout,
+        emf_output = ?args.emf_output.as_ref().map(|output| output.to_string()),
+        emf_namespace = %args.emf_namespace,
+        mock_sandboxes = args.mock_sandboxes,
+        chaos_scrape_failure_probability = args.chaos_scrape_failure_probability,
+        chaos_slow_scrape_probability = args.chaos_slow_scrape_probability,
+        chaos_slow_scrape_delay = ?args.chaos_slow_scrape_delay,
+        chaos_cri_timeout_probability = args.chaos_cri_timeout_probability,
+        chaos_malformed_payload_probability = args.chaos_malformed_payload_probability,
+        chaos_seed = ?args.chaos_seed,
+        enable_k8s_api = args.enable_k8s_api,
+        k8s_pod_labels = ?args.k8s_pod_labels,
+        janitor = args.janitor,
+        janitor_grace_period = ?args.janitor_grace_period,
+        janitor_remove = args.janitor_remove,
+        agent_stats = args.agent_stats,
+        agent_stats_timeout = ?args.agent_stats_timeout,
+        record_dir = ?args.record_dir,
+        record_max_files = args.record_max_files,
+        replay_dir = ?args.replay_dir,
+        aggregate_targets = ?args.aggregate_targets,
+        aggregate_timeout = ?args.aggregate_timeout,
+        aggregate_bearer_token_file = ?args.aggregate_bearer_token_file,
+        notify_socket = ?args.notify_socket,
+        watchdog_usec = ?args.watchdog_usec,
         "announcement"
     );
 
+    // Serve the merged outputs of other instances instead of the local sandboxes
+    if let Some(config) = args.aggregator() {
+        run_aggregator(
+            &args.listen_address,
+            config,
+            args.tenants_file.clone(),
+            args.token_review(),
+        )
+        .await;
+        return;
+    }
+
     // Create application context with all singletons
-    let app_context =
-        match context::AppContext::new(args.runtime_endpoint, args.metrics_interval_secs) {
-            Ok(ctx) => ctx,
-            Err(e) => {
-                eprintln!("Failed to initialize application context: {}", e);
-                return;
-            }
-        };
+    let reloadable = args.reloadable();
+    if let Err(e) = reloadable.validate() {
+        eprintln!("Error: {:#}", e);
+        return;
+    }
+    let initial_args = args.clone();
+    let options = context::AppOptions {
+        systemd: args.systemd().map(std::sync::Arc::new),
+        chaos: args.chaos(),
+        pod_watch: args.pod_watch(),
+        janitor: args.janitor(),
+        agent_stats: args.agent_stats(),
+        token_review: args.token_review(),
+        tenants_file: args.tenants_file.clone(),
+        cri_client: args.cri_client(),
+        metadata_retry_budget: args.metadata_retry_budget,
+        metrics_ttl_secs: args.metrics_ttl_secs,
+        circuit_breaker: monitor::scrape_health::CircuitBreakerConfig {
+            failure_threshold: args.scrape_failure_threshold,
+            max_skip_cycles: args.scrape_max_backoff_cycles,
+        },
+        shim_client: args.shim_client(),
+        cache_snapshot_path: args.cache_snapshot_path,
+        history_len: args.history_len,
+        conversion_workers: args.conversion_workers,
+        node_info: monitor::node_info::NodeInfo::new(
+            args.node_name,
+            args.node_zone,
+            args.node_region,
+        ),
+        sandbox_filter: reloadable.sandbox_filter,
+        conversion: reloadable.conversion,
+        host_cgroup_root: Some(args.host_cgroup_root).filter(|root| !root.as_os_str().is_empty()),
+        host_proc_root: Some(args.host_proc_root).filter(|root| !root.as_os_str().is_empty()),
+        parse_mode: args.parse_mode,
+        name_filter: reloadable.name_filter,
+        remote_write: args.remote_write_url.map(|url| {
+            exporter::http::PushConfig::new(url)
+                .with_auth(exporter::http::PushAuth::from_credentials(
+                    args.remote_write_username,
+                    args.remote_write_password,
+                    args.remote_write_bearer_token_file,
+                ))
+                .with_timeout(args.remote_write_timeout)
+                .with_max_retries(args.remote_write_max_retries)
+                .with_retry_backoff(args.remote_write_retry_backoff)
+        }),
+        statsd: args.statsd_address.map(|address| {
+            exporter::statsd::StatsdConfig::new(address)
+                .with_prefix(args.statsd_prefix)
+                .with_flavor(args.statsd_flavor)
+        }),
+        pushgateway: args.pushgateway_url.map(|url| {
+            exporter::http::PushConfig::new(url).with_auth(
+                exporter::http::PushAuth::from_credentials(
+                    args.pushgateway_username,
+                    args.pushgateway_password,
+                    args.pushgateway_bearer_token_file,
+                ),
+            )
+        }),
+        pushgateway_job: args.pushgateway_job,
+        jsonl: args.jsonl_path.map(|path| {
+            exporter::jsonl::JsonlConfig::new(path)
+                .with_max_size(args.jsonl_max_size)
+                .with_max_age(args.jsonl_max_age)
+                .with_max_files(args.jsonl_max_files)
+        }),
+        kafka: args.kafka_brokers.map(|brokers| {
+            exporter::kafka::KafkaConfig::new(brokers)
+                .with_topic(args.kafka_topic)
+                .with_format(args.kafka_format)
+                .with_timeout(args.kafka_timeout)
+        }),
+        emf: args
+            .emf_output
+            .map(|output| exporter::emf::EmfConfig::new(output).with_namespace(args.emf_namespace)),
+        admin_token_file: args.admin_token_file,
+        log_level: Some(log_level.clone()),
+        mock_sandboxes: args.mock_sandboxes,
+        record_dir: args.record_dir,
+        record_max_files: args.record_max_files,
+        replay_dir: args.replay_dir,
+        ..context::AppOptions::new(args.runtime_endpoint, args.metrics_interval_secs)
+    };
+    let app_context = match context::AppContext::new(options) {
+        Ok(ctx) => ctx,
+        Err(e) => {
+            eprintln!("Failed to initialize application context: {}", e);
+            return;
+        }
+    };
 
     match app_context.start() {
         Ok(_) => (),
@@ -123,32 +318,255 @@ async fn main() {
         }
     };
 
+    // Apply changes of the configuration file without a restart
+    if let Some(path) = initial_args.config.clone() {
+        let shared = app_context.config().clone();
+        let cancel = app_context.cancellation_token().clone();
+        // A log level set via the admin endpoint is kept until the file changes it
+        let log_level = log_level.clone();
+        let mut previous_log_level = initial_args.log_level.clone();
+        let reload = move || -> Result<()> {
+            let (args, file) =
+                Settings::load(Settings::cli().try_get_matches_from(std::env::args_os())?)?;
+            if initial_args.restart_required(&args) || file.storage != config_file.storage {
+                tracing::warn!("Changed settings other than the collection interval, filters, conversion rules and log level take effect after a restart");
+            }
+            shared.store(args.reloadable())?;
+            if args.log_level != previous_log_level {
+                log_level.reload(&args.log_level)?;
+                previous_log_level = args.log_level;
+            }
+            Ok(())
+        };
+        tokio::spawn(async move {
+            if let Err(e) = config::reload::watch(path, cancel, reload).await {
+                tracing::warn!(error = %format!("{:#}", e), "Configuration hot reload disabled");
+            }
+        });
+    }
+
+    // Stop the background tasks and the HTTP server on SIGINT/SIGTERM
+    let shutdown = app_context.cancellation_token().clone();
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!("Shutdown signal received");
+        shutdown.cancel();
+    });
+
     // Start HTTP server
     tracing::debug!(listen_address = %args.listen_address, "Starting HTTP server");
-    if let Err(e) = server::start_server(&args.listen_address, app_context).await {
+    if let Err(e) = server::start_server(&args.listen_address, app_context.clone()).await {
         tracing::error!(error = %e, "Server error");
     }
+
+    app_context.shutdown().await;
 }
 
-/// Initialize the logging system
-fn init_logging(log_level: &str) -> Result<()> {
-    let env_filter = match log_level {
-        "trace" => EnvFilter::new("trace"),
-        "debug" => EnvFilter::new("debug"),
-        "info" => EnvFilter::new("info"),
-        "warn" => EnvFilter::new("warn"),
-        "error" => EnvFilter::new("error"),
-        _ => EnvFilter::new("info"),
+/// Run the aggregator mode until SIGINT/SIGTERM
+async fn run_aggregator(
+    listen_address: &str,
+    config: aggregator::AggregatorConfig,
+    tenants_file: Option<PathBuf>,
+    token_review: Option<auth::TokenReviewConfig>,
+) {
+    let aggregator = match aggregator::Aggregator::new(config) {
+        Ok(aggregator) => std::sync::Arc::new(aggregator),
+        Err(e) => {
+            eprintln!("Failed to start the aggregator: {:#}", e);
+            return;
+        }
+    };
+    let tenants = match tenants_file.map(tenants::Tenants::load).transpose() {
+        Ok(tenants) => tenants.map(std::sync::Arc::new),
+        Err(e) => {
+            eprintln!("Failed to load the tenants file: {:#}", e);
+            return;
+        }
+    };
+    let token_reviewer = match token_review.map(auth::TokenReviewer::new).transpose() {
+        Ok(reviewer) => reviewer.map(std::sync::Arc::new),
+        Err(e) => {
+            eprintln!("Failed to set up the TokenReview authentication: {:#}", e);
+            return;
+        }
+    };
+    let authentication = server::Authentication {
+        tenants,
+        token_reviewer,
+    };
+    let cancel = tokio_util::sync::CancellationToken::new();
+    let shutdown = cancel.clone();
+    tokio::spawn(async move {
+        wait_for_signal().await;
+        info!("Shutdown signal received");
+        shutdown.cancel();
+    });
+
+    let collector = tokio::spawn({
+        let aggregator = aggregator.clone();
+        let cancel = cancel.clone();
+        async move { aggregator.run(cancel).await }
+    });
+    if let Err(e) =
+        server::start_aggregator_server(listen_address, aggregator, authentication, cancel.clone())
+            .await
+    {
+        tracing::error!(error = %e, "Server error");
+    }
+    cancel.cancel();
+    let _ = collector.await;
+}
+
+/// Run the preflight checks and print the report, returning the exit code
+async fn run_check(args: &Settings, json: bool) -> i32 {
+    let report = cli::check::run(&cli::check::CheckOptions {
+        runtime_endpoints: args.runtime_endpoint.clone(),
+        cri_client: args.cri_client(),
+        shim_client: args.shim_client(),
+        cpu_jiffy_conversion_factor: args.cpu_jiffy_conversion_factor,
+    })
+    .await;
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Failed to serialize check report: {}", e);
+                return 2;
+            }
+        }
+    } else {
+        print!("{}", report.to_text());
+    }
+    if report.ok {
+        0
+    } else {
+        1
+    }
+}
+
+/// Scrape one sandbox and print its metrics, returning the exit code
+async fn run_scrape(args: &Settings, sandbox_id: String, format: cli::scrape::ScrapeFormat) -> i32 {
+    let reloadable = args.reloadable();
+    let options = cli::scrape::ScrapeOptions {
+        sandbox_id,
+        format,
+        runtime_endpoints: args.runtime_endpoint.clone(),
+        cri_client: args.cri_client(),
+        shim_client: args.shim_client(),
+        name_filter: reloadable.name_filter,
+        conversion: reloadable.conversion,
+    };
+    match cli::scrape::run(&options).await {
+        Ok(output) => {
+            print!("{}", output);
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to scrape sandbox {}: {:#}", options.sandbox_id, e);
+            1
+        }
+    }
+}
+
+/// Print the sandboxes of the node, returning the exit code
+async fn run_list(args: &Settings) -> i32 {
+    let options = cli::list::ListOptions {
+        runtime_endpoints: args.runtime_endpoint.clone(),
+        cri_client: args.cri_client(),
+    };
+    print!("{}", cli::list::run(&options).await);
+    0
+}
+
+/// Check the configuration file of `matches` and print the effective
+/// configuration, returning the exit code
+fn run_validate_config(matches: &ArgMatches) -> i32 {
+    let Some(path) = matches.get_one::<PathBuf>("config") else {
+        eprintln!("validate-config requires --config or KATA_PULSE_CONFIG");
+        return 2;
+    };
+    for env in Settings::deprecated_env() {
+        eprintln!("warning: {}", env);
+    }
+    let validated = Settings::resolve(matches).and_then(|(resolved, file)| {
+        let args = Settings::from_arg_matches(&resolved)?;
+        args.reloadable().validate()?;
+        Ok(cli::validate_config::render(
+            &Settings::cli(),
+            matches,
+            &resolved,
+            &file,
+        ))
+    });
+    match validated {
+        Ok(effective) => {
+            println!("# Effective configuration of {}", path.display());
+            print!("{}", effective);
+            0
+        }
+        Err(e) => {
+            eprintln!("Invalid configuration: {:#}", e);
+            1
+        }
+    }
+}
+
+/// Wait for SIGINT (Ctrl+C) or SIGTERM
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = sigterm.recv() => {}
+            }
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "Failed to install SIGTERM handler");
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+/// Initialize the logging system, exporting spans to `otlp_endpoint` if set
+///
+/// The returned handle changes the log level at runtime.
+fn init_logging(
+    log_level: &str,
+    log_format: LogFormat,
+    otlp_endpoint: Option<&str>,
+) -> Result<(otlp::OtlpGuard, LogLevelHandle)> {
+    let (env_filter, log_level_handle) = reload::Layer::new(log_filter(log_level));
+    let (otlp_layer, otlp_guard) = otlp::layer(otlp_endpoint)?;
+    let (text_layer, json_layer) = match log_format {
+        LogFormat::Text => (
+            Some(
+                tracing_fmt::layer()
+                    .with_writer(std::io::stderr)
+                    .with_thread_ids(true),
+            ),
+            None,
+        ),
+        // Event fields at the top level so every event has the same shape
+        LogFormat::Json => (
+            None,
+            Some(
+                tracing_fmt::layer()
+                    .json()
+                    .flatten_event(true)
+                    .with_writer(std::io::stderr)
+                    .with_thread_ids(true),
+            ),
+        ),
     };
 
     tracing_subscriber::registry()
-        .with(
-            fmt::layer()
-                .with_writer(std::io::stderr)
-                .with_thread_ids(true),
-        )
         .with(env_filter)
+        .with(otlp_layer)
+        .with(text_layer)
+        .with(json_layer)
         .init();
 
-    Ok(())
+    Ok((otlp_guard, log_level_handle))
 }
@@ -0,0 +1,479 @@
+//! Agent stats - per-container stats straight from the kata-agent
+//!
+//! Responsibilities:
+//! - Ask each shim for the URL of its agent (`/agent-url`) and remember it
+//! - List the running containers of each sandbox from the CRI runtime
+//! - Query the agent in the guest (ttrpc over vsock) for the cgroup stats of
+//!   each container, once per collection interval
+//! - Render them as `kata_guest_container_*` series, one per container,
+//!   instead of the sandbox-wide aggregates of the shim metrics
+//!
+//! The shim metrics only describe the sandbox as a whole; the agent sees the
+//! cgroup of every container inside the guest.
+
+use futures::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::cri::{self, SandboxContainer};
+use super::sandbox_cache::{SandboxCRIMetadata, SandboxCache};
+use crate::config::reload::SharedConfig;
+use crate::config::resolve_shim_socket;
+use crate::utils::agent_client::{
+    AgentConnection, AgentUrl, CgroupStats, CpuUsage, MemoryData, ThrottlingData,
+};
+use crate::utils::metrics_converter::cadvisor::escape_label_value;
+use crate::utils::shim_client::ShimClient;
+
+/// Default timeout of the agent URL lookup and of each agent call
+pub const DEFAULT_AGENT_STATS_TIMEOUT: &str = "2s";
+
+/// Sandboxes whose agents are queried at the same time
+const CONCURRENCY: usize = 8;
+
+/// Memory limits above this are "unlimited" (the cgroup max of the guest kernel)
+const UNLIMITED_MEMORY: u64 = 1 << 62;
+
+/// Settings of the agent stats collector
+#[derive(Clone, Debug, PartialEq)]
+pub struct AgentStatsConfig {
+    /// Timeout of the agent URL lookup and of each agent call
+    pub timeout: Duration,
+}
+
+impl AgentStatsConfig {
+    /// Query the agents with a 2 second timeout
+    pub fn new() -> Self {
+        AgentStatsConfig {
+            timeout: Duration::from_secs(2),
+        }
+    }
+
+    /// Set the timeout of the agent URL lookup and of each agent call
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl Default for AgentStatsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stats of a container, as of the last sweep
+#[derive(Clone, Debug, PartialEq)]
+struct ContainerStats {
+    container: SandboxContainer,
+    stats: CgroupStats,
+}
+
+/// Stats of the containers of a sandbox, as of the last sweep
+#[derive(Clone, Debug, PartialEq)]
+struct SandboxStats {
+    metadata: SandboxCRIMetadata,
+    /// Whether the agent answered for every container
+    up: bool,
+    containers: Vec<ContainerStats>,
+}
+
+/// Collects per-container stats from the kata-agents of the sandboxes
+pub struct AgentStats {
+    config: AgentStatsConfig,
+    sandbox_cache: Arc<SandboxCache>,
+    shim_client: Arc<ShimClient>,
+    reloadable: Arc<SharedConfig>,
+    /// Agent URL of each sandbox, as reported by its shim
+    agent_urls: Mutex<HashMap<String, AgentUrl>>,
+    /// Stats of each sandbox, as of the last sweep
+    stats: Mutex<HashMap<String, SandboxStats>>,
+}
+
+impl AgentStats {
+    /// Create a collector of the sandboxes of `sandbox_cache`
+    pub fn new(
+        config: AgentStatsConfig,
+        sandbox_cache: Arc<SandboxCache>,
+        shim_client: Arc<ShimClient>,
+        reloadable: Arc<SharedConfig>,
+    ) -> Self {
+        AgentStats {
+            config,
+            sandbox_cache,
+            shim_client,
+            reloadable,
+            agent_urls: Mutex::new(HashMap::new()),
+            stats: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The settings of the collector
+    pub fn config(&self) -> &AgentStatsConfig {
+        &self.config
+    }
+
+    /// Sweep the sandboxes every collection interval until `cancel` fires
+    pub async fn start(&self, cancel: CancellationToken) {
+        info!(timeout = ?self.config.timeout, "Starting the agent stats collector");
+        loop {
+            self.sweep().await;
+            let interval = self.reloadable.load().metrics_interval();
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = sleep(interval) => {}
+            }
+        }
+    }
+
+    /// Query the agents of the collected sandboxes
+    async fn sweep(&self) {
+        let filter = self.reloadable.load().sandbox_filter.clone();
+        let sandboxes: Vec<_> = self
+            .sandbox_cache
+            .get_sandboxes_with_metadata()
+            .await
+            .into_iter()
+            .filter(|(_, metadata)| filter.allows(metadata))
+            .collect();
+
+        let stats: HashMap<String, SandboxStats> = stream::iter(sandboxes)
+            .map(|(id, metadata)| async move {
+                let stats = self.collect(&id, metadata).await;
+                (id, stats)
+            })
+            .buffer_unordered(CONCURRENCY)
+            .collect()
+            .await;
+
+        self.agent_urls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|id, _| stats.contains_key(id));
+        *self.stats.lock().unwrap_or_else(|e| e.into_inner()) = stats;
+    }
+
+    /// Collect the stats of the containers of a sandbox
+    async fn collect(&self, id: &str, metadata: SandboxCRIMetadata) -> SandboxStats {
+        let mut sandbox = SandboxStats {
+            metadata,
+            up: false,
+            containers: Vec::new(),
+        };
+        let url = match self.agent_url(id).await {
+            Ok(url) => url,
+            Err(e) => {
+                debug!(sandbox_id = %id, error = %format!("{:#}", e), "Failed to get agent URL");
+                return sandbox;
+            }
+        };
+        let containers = match cri::list_sandbox_containers(id).await {
+            Ok(containers) => containers,
+            Err(e) => {
+                debug!(sandbox_id = %id, error = %format!("{:#}", e), "Failed to list sandbox containers");
+                return sandbox;
+            }
+        };
+
+        let timeout = self.config.timeout;
+        let result = tokio::task::spawn_blocking(move || {
+            let mut connection = AgentConnection::connect(&url, timeout)?;
+            containers
+                .into_iter()
+                .map(|container| {
+                    let stats = connection.stats_container(&container.id)?;
+                    Ok(ContainerStats { container, stats })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+        })
+        .await;
+        match result {
+            Ok(Ok(containers)) => {
+                sandbox.up = true;
+                sandbox.containers = containers;
+            }
+            Ok(Err(e)) => {
+                warn!(sandbox_id = %id, error = %format!("{:#}", e), "Failed to get container stats from the agent");
+                // The agent may have moved (sandbox recreated): ask the shim again
+                self.agent_urls
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(id);
+            }
+            Err(e) => warn!(sandbox_id = %id, error = %e, "Agent stats task failed"),
+        }
+        sandbox
+    }
+
+    /// Agent URL of a sandbox, asking its shim the first time
+    async fn agent_url(&self, id: &str) -> anyhow::Result<AgentUrl> {
+        if let Some(url) = self
+            .agent_urls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(id)
+        {
+            return Ok(url.clone());
+        }
+        let (_, socket) = resolve_shim_socket(id)?;
+        let body = self
+            .shim_client
+            .get(id, &socket, "/agent-url", self.config.timeout)
+            .await?;
+        let url = AgentUrl::parse(&String::from_utf8_lossy(&body))?;
+        self.agent_urls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id.to_string(), url.clone());
+        Ok(url)
+    }
+
+    /// Render the container stats in Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        let stats = self.stats.lock().unwrap_or_else(|e| e.into_inner());
+        let mut sandboxes: Vec<_> = stats.iter().collect();
+        sandboxes.sort_by(|a, b| a.0.cmp(b.0));
+        render(&sandboxes)
+    }
+}
+
+/// A per-container family: name, type, help and value of a container (None if not reported)
+type Family = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&CgroupStats) -> Option<f64>,
+);
+
+const FAMILIES: [Family; 11] = [
+    (
+        "kata_guest_container_cpu_usage_seconds_total",
+        "counter",
+        "CPU time consumed by the container in the guest",
+        |s| cpu_usage(s).map(|u| nanos(u.total_usage)),
+    ),
+    (
+        "kata_guest_container_cpu_user_seconds_total",
+        "counter",
+        "User CPU time consumed by the container in the guest",
+        |s| cpu_usage(s).map(|u| nanos(u.usage_in_usermode)),
+    ),
+    (
+        "kata_guest_container_cpu_system_seconds_total",
+        "counter",
+        "System CPU time consumed by the container in the guest",
+        |s| cpu_usage(s).map(|u| nanos(u.usage_in_kernelmode)),
+    ),
+    (
+        "kata_guest_container_cpu_cfs_periods_total",
+        "counter",
+        "Elapsed CFS enforcement periods of the container",
+        |s| throttling(s).map(|t| t.periods as f64),
+    ),
+    (
+        "kata_guest_container_cpu_cfs_throttled_periods_total",
+        "counter",
+        "Throttled CFS periods of the container",
+        |s| throttling(s).map(|t| t.throttled_periods as f64),
+    ),
+    (
+        "kata_guest_container_cpu_cfs_throttled_seconds_total",
+        "counter",
+        "Time the container was throttled",
+        |s| throttling(s).map(|t| nanos(t.throttled_time)),
+    ),
+    (
+        "kata_guest_container_memory_usage_bytes",
+        "gauge",
+        "Memory usage of the container, page cache included",
+        |s| memory_usage(s).map(|m| m.usage as f64),
+    ),
+    (
+        "kata_guest_container_memory_max_usage_bytes",
+        "gauge",
+        "Highest memory usage of the container",
+        |s| memory_usage(s).map(|m| m.max_usage as f64),
+    ),
+    (
+        "kata_guest_container_memory_working_set_bytes",
+        "gauge",
+        "Memory usage of the container minus its inactive page cache",
+        working_set,
+    ),
+    (
+        "kata_guest_container_memory_cache",
+        "gauge",
+        "Page cache of the container",
+        |s| s.memory_stats.as_ref().map(|m| m.cache as f64),
+    ),
+    (
+        "kata_guest_container_spec_memory_limit_bytes",
+        "gauge",
+        "Memory limit of the container, 0 if unlimited",
+        |s| {
+            memory_usage(s).map(|m| {
+                if m.limit >= UNLIMITED_MEMORY {
+                    0.0
+                } else {
+                    m.limit as f64
+                }
+            })
+        },
+    ),
+];
+
+fn cpu_usage(stats: &CgroupStats) -> Option<&CpuUsage> {
+    stats.cpu_stats.as_ref()?.cpu_usage.as_ref()
+}
+
+fn throttling(stats: &CgroupStats) -> Option<&ThrottlingData> {
+    stats.cpu_stats.as_ref()?.throttling_data.as_ref()
+}
+
+fn memory_usage(stats: &CgroupStats) -> Option<&MemoryData> {
+    stats.memory_stats.as_ref()?.usage.as_ref()
+}
+
+/// Usage minus the inactive page cache (cgroup v1 or v2 name), like cAdvisor
+fn working_set(stats: &CgroupStats) -> Option<f64> {
+    let memory = stats.memory_stats.as_ref()?;
+    let usage = memory.usage.as_ref()?.usage;
+    let inactive_file = ["total_inactive_file", "inactive_file"]
+        .iter()
+        .find_map(|key| memory.stats.get(*key))
+        .copied()
+        .unwrap_or_default();
+    Some(usage.saturating_sub(inactive_file) as f64)
+}
+
+fn nanos(value: u64) -> f64 {
+    value as f64 / 1e9
+}
+
+/// Render the stats of `sandboxes`, sorted by sandbox ID
+fn render(sandboxes: &[(&String, &SandboxStats)]) -> String {
+    let mut output = String::new();
+    if sandboxes.is_empty() {
+        return output;
+    }
+
+    output.push_str("# HELP kata_pulse_agent_stats_up Whether the agent of the sandbox answered the last stats query\n");
+    output.push_str("# TYPE kata_pulse_agent_stats_up gauge\n");
+    for (id, sandbox) in sandboxes {
+        let _ = writeln!(
+            output,
+            "kata_pulse_agent_stats_up{{sandbox_id=\"{}\",pod=\"{}\",namespace=\"{}\"}} {}",
+            escape_label_value(id),
+            escape_label_value(&sandbox.metadata.name),
+            escape_label_value(&sandbox.metadata.namespace),
+            u8::from(sandbox.up)
+        );
+    }
+
+    for (name, kind, help, value) in FAMILIES {
+        let mut header = false;
+        for (id, sandbox) in sandboxes {
+            for container in &sandbox.containers {
+                let Some(value) = value(&container.stats) else {
+                    continue;
+                };
+                if !header {
+                    let _ = writeln!(output, "# HELP {} {}", name, help);
+                    let _ = writeln!(output, "# TYPE {} {}", name, kind);
+                    header = true;
+                }
+                let _ = writeln!(
+                    output,
+                    "{}{{container=\"{}\",image=\"{}\",pod=\"{}\",namespace=\"{}\",sandbox_id=\"{}\"}} {}",
+                    name,
+                    escape_label_value(&container.container.name),
+                    escape_label_value(&container.container.image),
+                    escape_label_value(&sandbox.metadata.name),
+                    escape_label_value(&sandbox.metadata.namespace),
+                    escape_label_value(id),
+                    value
+                );
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::agent_client::{CpuStats, MemoryStats};
+
+    #[test]
+    fn test_render() {
+        let stats = CgroupStats {
+            cpu_stats: Some(CpuStats {
+                cpu_usage: Some(CpuUsage {
+                    total_usage: 1_500_000_000,
+                    usage_in_kernelmode: 500_000_000,
+                    usage_in_usermode: 1_000_000_000,
+                }),
+                throttling_data: None,
+            }),
+            memory_stats: Some(MemoryStats {
+                cache: 100,
+                usage: Some(MemoryData {
+                    usage: 1000,
+                    max_usage: 1200,
+                    failcnt: 0,
+                    limit: u64::MAX,
+                }),
+                stats: [("inactive_file".to_string(), 300)].into(),
+            }),
+            pids_stats: None,
+        };
+        let sandbox = SandboxStats {
+            metadata: SandboxCRIMetadata {
+                name: "my-pod".to_string(),
+                namespace: "default".to_string(),
+                ..Default::default()
+            },
+            up: true,
+            containers: vec![ContainerStats {
+                container: SandboxContainer {
+                    id: "c1".to_string(),
+                    name: "app".to_string(),
+                    image: "nginx:1.27".to_string(),
+                },
+                stats,
+            }],
+        };
+        let down = SandboxStats {
+            up: false,
+            containers: Vec::new(),
+            ..sandbox.clone()
+        };
+        let (id, down_id) = ("sb-1".to_string(), "sb-2".to_string());
+        let output = render(&[(&id, &sandbox), (&down_id, &down)]);
+
+        let labels = r#"container="app",image="nginx:1.27",pod="my-pod",namespace="default",sandbox_id="sb-1""#;
+        assert!(output.contains(&format!(
+            "kata_guest_container_cpu_usage_seconds_total{{{}}} 1.5\n",
+            labels
+        )));
+        assert!(output.contains(&format!(
+            "kata_guest_container_memory_working_set_bytes{{{}}} 700\n",
+            labels
+        )));
+        assert!(output.contains(&format!(
+            "kata_guest_container_spec_memory_limit_bytes{{{}}} 0\n",
+            labels
+        )));
+        assert!(output.contains(
+            "kata_pulse_agent_stats_up{sandbox_id=\"sb-2\",pod=\"my-pod\",namespace=\"default\"} 0\n"
+        ));
+        // No throttling data: no CFS families
+        assert!(!output.contains("kata_guest_container_cpu_cfs_periods_total"));
+        assert!(render(&[]).is_empty());
+    }
+}
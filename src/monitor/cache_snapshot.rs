@@ -0,0 +1,198 @@
+//! Metrics cache snapshots - persist the published metrics across restarts
+//!
+//! Responsibilities:
+//! - Write the current `MetricsCache` buffer to disk after collection cycles
+//! - Restore it on startup so a restart does not leave a metrics gap
+//!
+//! Metrics are stored in Prometheus text format together with their wall-clock
+//! collection time, so restored entries keep aging against the cache TTL.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+use super::metrics_cache::{CachedMetrics, MetricsCache};
+use crate::utils::metrics_converter::cadvisor::PrometheusFormat;
+use crate::utils::prometheus_parser::PrometheusMetrics;
+
+/// Snapshot file format version
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// On-disk representation of the metrics cache
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    sandboxes: Vec<SnapshotEntry>,
+}
+
+/// Metrics of a single sandbox in a snapshot
+#[derive(Serialize, Deserialize)]
+struct SnapshotEntry {
+    sandbox_id: String,
+    /// Collection time in milliseconds since the Unix epoch
+    collected_at_unix_ms: u64,
+    /// Metrics in Prometheus text format
+    metrics: String,
+}
+
+/// Write the currently published metrics to `path`
+///
+/// The file is written next to its final location and renamed into place,
+/// so a crash mid-write never leaves a truncated snapshot behind.
+pub async fn save(cache: &MetricsCache, path: &Path) -> Result<usize> {
+    let current = cache.published().await;
+
+    let sandboxes: Vec<SnapshotEntry> = current
+        .iter()
+        .map(|(sandbox_id, cached)| SnapshotEntry {
+            sandbox_id: sandbox_id.clone(),
+            collected_at_unix_ms: cached
+                .collected_at_unix()
+                .as_millis()
+                .try_into()
+                .unwrap_or(u64::MAX),
+            metrics: cached.metrics.to_prometheus_format(None),
+        })
+        .collect();
+    let count = sandboxes.len();
+
+    let data = serde_json::to_vec(&Snapshot {
+        version: SNAPSHOT_VERSION,
+        sandboxes,
+    })?;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    tokio::fs::write(&tmp_path, data)
+        .await
+        .with_context(|| format!("failed to write {}", tmp_path.display()))?;
+    tokio::fs::rename(&tmp_path, path)
+        .await
+        .with_context(|| format!("failed to rename snapshot to {}", path.display()))?;
+
+    debug!(path = %path.display(), sandbox_count = count, "Saved metrics cache snapshot");
+    Ok(count)
+}
+
+/// Restore the metrics cache from the snapshot at `path`
+///
+/// Entries older than the cache TTL are skipped. A missing snapshot is not an
+/// error (first start); returns the number of restored sandboxes.
+pub async fn load(cache: &MetricsCache, path: &Path) -> Result<usize> {
+    let data = match tokio::fs::read(path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+
+    let snapshot: Snapshot = serde_json::from_slice(&data)
+        .with_context(|| format!("failed to parse snapshot {}", path.display()))?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(anyhow::anyhow!(
+            "unsupported snapshot version {} in {}",
+            snapshot.version,
+            path.display()
+        ));
+    }
+
+    let now_unix = unix_now();
+    let now = Instant::now();
+    let mut entries = HashMap::new();
+    for entry in snapshot.sandboxes {
+        let age = now_unix.saturating_sub(Duration::from_millis(entry.collected_at_unix_ms));
+        let Some(collected_at) = now.checked_sub(age) else {
+            continue;
+        };
+
+        match PrometheusMetrics::parse(&entry.metrics) {
+            Ok(metrics) => {
+                entries.insert(
+                    entry.sandbox_id,
+                    CachedMetrics::new(Arc::new(metrics), collected_at),
+                );
+            }
+            Err(e) => {
+                warn!(sandbox_id = %entry.sandbox_id, error = %e, "Skipping unparsable snapshot entry")
+            }
+        }
+    }
+
+    Ok(cache.restore(entries).await)
+}
+
+/// Current wall-clock time as a duration since the Unix epoch
+fn unix_now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METRICS: &str = "# TYPE kata_hypervisor_fds gauge\nkata_hypervisor_fds 42\n";
+
+    async fn populated_cache(ttl: Duration) -> MetricsCache {
+        let cache = MetricsCache::with_ttl(ttl);
+        cache.start_collection().await;
+        cache
+            .add_metrics(
+                "sandbox-1".to_string(),
+                PrometheusMetrics::parse(METRICS).unwrap(),
+            )
+            .await;
+        cache.finish_collection().await;
+        cache
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache").join("metrics.json");
+
+        let cache = populated_cache(Duration::from_secs(60)).await;
+        assert_eq!(save(&cache, &path).await.unwrap(), 1);
+
+        let restored = MetricsCache::with_ttl(Duration::from_secs(60));
+        assert_eq!(load(&restored, &path).await.unwrap(), 1);
+
+        let cached = restored.get_metrics("sandbox-1").await.unwrap();
+        let metric = &cached.metrics.metrics["kata_hypervisor_fds"];
+        assert_eq!(metric.samples[0].value, 42.0);
+    }
+
+    #[tokio::test]
+    async fn test_missing_snapshot_is_not_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = MetricsCache::new();
+        assert_eq!(
+            load(&cache, &dir.path().join("missing.json"))
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expired_entries_are_not_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.json");
+
+        let cache = populated_cache(Duration::from_secs(60)).await;
+        save(&cache, &path).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        let restored = MetricsCache::with_ttl(Duration::from_millis(10));
+        assert_eq!(load(&restored, &path).await.unwrap(), 0);
+        assert!(restored.get_metrics("sandbox-1").await.is_none());
+    }
+}
@@ -0,0 +1,361 @@
+//! Chaos mode - fault injection into the scrape and CRI paths
+//!
+//! Responsibilities:
+//! - Fail and delay shim scrapes at random, with configured probabilities
+//! - Corrupt scraped payloads (truncated mid-line, with malformed lines)
+//! - Make CRI metadata syncs time out
+//! - Count the injected faults per kind for the internal metrics
+//!
+//! Lets operators check their alerting and the degradation of the agent
+//! (quarantine, stale metrics, degraded components) before a real incident.
+//! Disabled unless a `--chaos-*` probability is set; never leave it on in
+//! production.
+
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::debug;
+
+/// Default delay of the injected slow scrapes
+pub const DEFAULT_SLOW_SCRAPE_DELAY: Duration = Duration::from_secs(5);
+
+/// Malformed lines appended to a corrupted payload
+const MALFORMED_LINES: [&str; 3] = [
+    "kata_guest_load{item=\"load1\" 0.5",
+    "kata_guest_tasks{item=\"cur\"} not-a-number",
+    "kata_guest_meminfo{item=mem_free} 1024",
+];
+
+/// A kind of injected fault
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// A scrape fails without contacting the shim
+    ScrapeFailure,
+    /// A scrape is delayed
+    SlowScrape,
+    /// A CRI metadata sync times out
+    CriTimeout,
+    /// A scraped payload is truncated and gets malformed lines
+    MalformedPayload,
+}
+
+impl Fault {
+    /// All faults, in reporting order
+    pub const ALL: [Fault; 4] = [
+        Fault::ScrapeFailure,
+        Fault::SlowScrape,
+        Fault::CriTimeout,
+        Fault::MalformedPayload,
+    ];
+
+    /// Name used in metric labels
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Fault::ScrapeFailure => "scrape_failure",
+            Fault::SlowScrape => "slow_scrape",
+            Fault::CriTimeout => "cri_timeout",
+            Fault::MalformedPayload => "malformed_payload",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Probabilities of the injected faults
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChaosConfig {
+    /// Probability that a scrape fails
+    pub scrape_failure: f64,
+    /// Probability that a scrape is delayed by `slow_scrape_delay`
+    pub slow_scrape: f64,
+    /// Delay of the slow scrapes
+    pub slow_scrape_delay: Duration,
+    /// Probability that a CRI metadata sync times out
+    pub cri_timeout: f64,
+    /// Probability that a scraped payload is corrupted
+    pub malformed_payload: f64,
+    /// Seed of the random faults, for reproducible runs (random if None)
+    pub seed: Option<u64>,
+}
+
+impl Default for ChaosConfig {
+    fn default() -> Self {
+        ChaosConfig {
+            scrape_failure: 0.0,
+            slow_scrape: 0.0,
+            slow_scrape_delay: DEFAULT_SLOW_SCRAPE_DELAY,
+            cri_timeout: 0.0,
+            malformed_payload: 0.0,
+            seed: None,
+        }
+    }
+}
+
+impl ChaosConfig {
+    /// Probability of a fault
+    pub fn probability(&self, fault: Fault) -> f64 {
+        match fault {
+            Fault::ScrapeFailure => self.scrape_failure,
+            Fault::SlowScrape => self.slow_scrape,
+            Fault::CriTimeout => self.cri_timeout,
+            Fault::MalformedPayload => self.malformed_payload,
+        }
+    }
+
+    /// Whether any fault is injected
+    pub fn is_enabled(&self) -> bool {
+        Fault::ALL
+            .iter()
+            .any(|fault| self.probability(*fault) > 0.0)
+    }
+
+    /// Check that the probabilities are between 0 and 1
+    pub fn validate(&self) -> Result<()> {
+        for fault in Fault::ALL {
+            let probability = self.probability(fault);
+            if !(0.0..=1.0).contains(&probability) {
+                anyhow::bail!(
+                    "chaos {} probability must be between 0 and 1, got {}",
+                    fault.as_str(),
+                    probability
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Injects the faults of a `ChaosConfig`
+pub struct FaultInjector {
+    config: ChaosConfig,
+    rng: Mutex<StdRng>,
+    injected: [AtomicU64; 4],
+}
+
+impl FaultInjector {
+    /// Create a fault injector (the probabilities must be valid)
+    pub fn new(config: ChaosConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        FaultInjector {
+            config,
+            rng: Mutex::new(rng),
+            injected: Default::default(),
+        }
+    }
+
+    /// The injected fault probabilities
+    pub fn config(&self) -> &ChaosConfig {
+        &self.config
+    }
+
+    /// Decide whether to inject `fault` now, counting it if so
+    fn roll(&self, fault: Fault) -> bool {
+        let probability = self.config.probability(fault);
+        if probability <= 0.0 {
+            return false;
+        }
+        let hit = self
+            .rng
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .gen_bool(probability);
+        if hit {
+            self.injected[fault.index()].fetch_add(1, Ordering::Relaxed);
+        }
+        hit
+    }
+
+    /// Delay or fail a scrape before it reaches the shim
+    pub async fn before_scrape(&self, sandbox_id: &str) -> Result<()> {
+        if self.roll(Fault::SlowScrape) {
+            debug!(sandbox_id = %sandbox_id, delay = ?self.config.slow_scrape_delay, "Chaos: delaying scrape");
+            tokio::time::sleep(self.config.slow_scrape_delay).await;
+        }
+        if self.roll(Fault::ScrapeFailure) {
+            debug!(sandbox_id = %sandbox_id, "Chaos: failing scrape");
+            anyhow::bail!("chaos: injected scrape failure");
+        }
+        Ok(())
+    }
+
+    /// Corrupt a scraped payload: cut it in the middle of a line and append malformed lines
+    pub fn corrupt_payload<'a>(&self, sandbox_id: &str, payload: Cow<'a, str>) -> Cow<'a, str> {
+        if !self.roll(Fault::MalformedPayload) {
+            return payload;
+        }
+        debug!(sandbox_id = %sandbox_id, "Chaos: corrupting payload");
+        let mut cut = if payload.is_empty() {
+            0
+        } else {
+            self.rng
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .gen_range(payload.len() / 2..payload.len())
+        };
+        while !payload.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let mut corrupted = String::with_capacity(cut + 128);
+        corrupted.push_str(&payload[..cut]);
+        for line in MALFORMED_LINES {
+            corrupted.push('\n');
+            corrupted.push_str(line);
+        }
+        corrupted.push('\n');
+        Cow::Owned(corrupted)
+    }
+
+    /// Make a CRI metadata sync time out after `timeout`
+    pub async fn before_cri_sync(&self, timeout: Duration) -> Result<()> {
+        if self.roll(Fault::CriTimeout) {
+            debug!(timeout = ?timeout, "Chaos: timing out CRI sync");
+            tokio::time::sleep(timeout).await;
+            anyhow::bail!("chaos: injected CRI timeout after {:?}", timeout);
+        }
+        Ok(())
+    }
+
+    /// Number of injected faults of a kind
+    pub fn injected(&self, fault: Fault) -> u64 {
+        self.injected[fault.index()].load(Ordering::Relaxed)
+    }
+
+    /// Render the fault probabilities and injected fault counts as internal metrics
+    pub fn render_metrics(&self) -> String {
+        let mut output = String::new();
+        output.push_str(
+            "# HELP kata_pulse_chaos_fault_probability Probability of the faults injected by chaos mode\n",
+        );
+        output.push_str("# TYPE kata_pulse_chaos_fault_probability gauge\n");
+        for fault in Fault::ALL {
+            output.push_str(&format!(
+                "kata_pulse_chaos_fault_probability{{fault=\"{}\"}} {}\n",
+                fault.as_str(),
+                self.config.probability(fault)
+            ));
+        }
+        output.push_str(
+            "# HELP kata_pulse_chaos_faults_injected_total Faults injected by chaos mode\n",
+        );
+        output.push_str("# TYPE kata_pulse_chaos_faults_injected_total counter\n");
+        for fault in Fault::ALL {
+            output.push_str(&format!(
+                "kata_pulse_chaos_faults_injected_total{{fault=\"{}\"}} {}\n",
+                fault.as_str(),
+                self.injected(fault)
+            ));
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::prometheus_parser::PrometheusMetrics;
+
+    fn injector(config: ChaosConfig) -> FaultInjector {
+        FaultInjector::new(ChaosConfig {
+            seed: Some(42),
+            ..config
+        })
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(!ChaosConfig::default().is_enabled());
+        let config = ChaosConfig {
+            cri_timeout: 0.1,
+            ..Default::default()
+        };
+        assert!(config.is_enabled());
+        assert!(config.validate().is_ok());
+        let config = ChaosConfig {
+            scrape_failure: 1.5,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scrape_faults() {
+        let always = injector(ChaosConfig {
+            scrape_failure: 1.0,
+            slow_scrape: 1.0,
+            slow_scrape_delay: Duration::from_millis(10),
+            ..Default::default()
+        });
+        let start = std::time::Instant::now();
+        let err = always.before_scrape("sb-1").await.unwrap_err();
+        assert!(err.to_string().contains("injected scrape failure"));
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        assert_eq!(always.injected(Fault::ScrapeFailure), 1);
+        assert_eq!(always.injected(Fault::SlowScrape), 1);
+
+        let never = injector(ChaosConfig::default());
+        for _ in 0..100 {
+            never.before_scrape("sb-1").await.unwrap();
+        }
+        assert_eq!(never.injected(Fault::ScrapeFailure), 0);
+    }
+
+    #[test]
+    fn test_probability_is_honored() {
+        let half = injector(ChaosConfig {
+            scrape_failure: 0.5,
+            ..Default::default()
+        });
+        let hits = (0..1000)
+            .filter(|_| half.roll(Fault::ScrapeFailure))
+            .count();
+        assert!((400..600).contains(&hits), "hits: {}", hits);
+        assert_eq!(half.injected(Fault::ScrapeFailure), hits as u64);
+    }
+
+    #[test]
+    fn test_corrupted_payload_has_parse_errors() {
+        let payload = "# TYPE kata_guest_load gauge\nkata_guest_load{item=\"load1\"} 0.5\nkata_guest_load{item=\"load5\"} 0.7\n";
+        let never = injector(ChaosConfig::default());
+        assert!(matches!(
+            never.corrupt_payload("sb-1", Cow::Borrowed(payload)),
+            Cow::Borrowed(_)
+        ));
+
+        let always = injector(ChaosConfig {
+            malformed_payload: 1.0,
+            ..Default::default()
+        });
+        let corrupted = always.corrupt_payload("sb-1", Cow::Borrowed(payload));
+        let (_, errors) = PrometheusMetrics::parse_with_diagnostics(&corrupted);
+        assert!(errors.len() >= MALFORMED_LINES.len());
+        assert_eq!(always.injected(Fault::MalformedPayload), 1);
+        assert!(always
+            .corrupt_payload("sb-1", Cow::Borrowed(""))
+            .contains(MALFORMED_LINES[0]));
+    }
+
+    #[tokio::test]
+    async fn test_cri_timeout() {
+        let always = injector(ChaosConfig {
+            cri_timeout: 1.0,
+            ..Default::default()
+        });
+        let err = always
+            .before_cri_sync(Duration::from_millis(5))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("injected CRI timeout"));
+        assert!(always
+            .render_metrics()
+            .contains("kata_pulse_chaos_faults_injected_total{fault=\"cri_timeout\"} 1\n"));
+    }
+}
@@ -0,0 +1,151 @@
+//! Conversion pool - converts sandbox metrics off the async runtime
+//!
+//! Responsibilities:
+//! - Run the CPU-bound conversion of many sandboxes on tokio's blocking
+//!   thread pool, so the reactor threads keep serving other requests
+//! - Bound the parallelism to a fixed number of workers (the CPU count by
+//!   default), so a render does not claim every blocking thread
+
+use anyhow::{anyhow, Result};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use tracing::Span;
+
+use super::metrics_cache::CachedMetrics;
+use crate::config::reload::ReloadableConfig;
+use crate::utils::metrics_converter::config::EnrichedLabels;
+
+/// Metrics of one sandbox to convert, with its resolved pod labels
+pub struct ConversionJob {
+    pub sandbox_id: String,
+    pub metrics: CachedMetrics,
+    pub labels: EnrichedLabels,
+}
+
+/// Converts sandbox metrics on a bounded number of blocking workers
+#[derive(Clone, Debug)]
+pub struct ConversionPool {
+    workers: usize,
+}
+
+impl ConversionPool {
+    /// Convert on up to `workers` blocking threads (0 = number of CPUs)
+    pub fn new(workers: usize) -> Self {
+        let workers = match workers {
+            0 => std::thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            workers => workers,
+        };
+        ConversionPool { workers }
+    }
+
+    /// Number of blocking workers
+    pub fn workers(&self) -> usize {
+        self.workers
+    }
+
+    /// Convert the metrics of `jobs` (memoized per scrape, see
+    /// [`CachedMetrics::converted`])
+    ///
+    /// The jobs are split into one contiguous chunk per worker. Results are
+    /// returned in job order; a panicking conversion fails its whole chunk.
+    pub async fn convert(
+        &self,
+        config: &Arc<ReloadableConfig>,
+        jobs: Vec<ConversionJob>,
+    ) -> Vec<Result<Arc<str>>> {
+        if jobs.is_empty() {
+            return Vec::new();
+        }
+        let chunk_size = jobs.len().div_ceil(self.workers);
+        let mut jobs = jobs.into_iter();
+        let mut chunks = Vec::new();
+        loop {
+            let chunk: Vec<ConversionJob> = jobs.by_ref().take(chunk_size).collect();
+            if chunk.is_empty() {
+                break;
+            }
+            let (config, span) = (config.clone(), Span::current());
+            let len = chunk.len();
+            let handle = tokio::task::spawn_blocking(move || {
+                let _entered = span.enter();
+                chunk
+                    .into_iter()
+                    .map(|job| job.metrics.converted(&config, job.labels, &job.sandbox_id))
+                    .collect::<Vec<_>>()
+            });
+            chunks.push((len, handle));
+        }
+
+        let mut results = Vec::with_capacity(chunks.iter().map(|(len, _)| len).sum());
+        for (len, handle) in chunks {
+            match handle.await {
+                Ok(converted) => results.extend(converted),
+                Err(e) => {
+                    results.extend((0..len).map(|_| Err(anyhow!("conversion failed: {}", e))))
+                }
+            }
+        }
+        results
+    }
+
+    /// Convert the metrics of a single sandbox
+    pub async fn convert_one(
+        &self,
+        config: &Arc<ReloadableConfig>,
+        job: ConversionJob,
+    ) -> Result<Arc<str>> {
+        self.convert(config, vec![job])
+            .await
+            .pop()
+            .unwrap_or_else(|| Err(anyhow!("conversion produced no output")))
+    }
+}
+
+impl Default for ConversionPool {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::prometheus_parser::PrometheusMetrics;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_results_keep_job_order() {
+        let config = Arc::new(ReloadableConfig {
+            metrics_interval_secs: 60,
+            sandbox_filter: Default::default(),
+            name_filter: Default::default(),
+            conversion: Default::default(),
+        });
+        let jobs: Vec<ConversionJob> = (0..7)
+            .map(|index| ConversionJob {
+                sandbox_id: format!("sb-{}", index),
+                metrics: CachedMetrics::new(
+                    Arc::new(
+                        PrometheusMetrics::parse(&format!(
+                            "kata_guest_cpu_time{{cpu=\"total\",item=\"user\"}} {}\n",
+                            index * 100
+                        ))
+                        .unwrap(),
+                    ),
+                    Instant::now(),
+                ),
+                labels: EnrichedLabels::new("", format!("pod-{}", index), "default"),
+            })
+            .collect();
+
+        let pool = ConversionPool::new(3);
+        assert_eq!(pool.workers(), 3);
+        let results = pool.convert(&config, jobs).await;
+        assert_eq!(results.len(), 7);
+        for (index, result) in results.into_iter().enumerate() {
+            let text = result.unwrap();
+            assert!(text.contains(&format!("pod=\"pod-{}\"", index)), "{}", text);
+        }
+        assert!(ConversionPool::default().workers() >= 1);
+    }
+}
@@ -1,10 +1,11 @@
-use anyhow::Result;
-use std::sync::OnceLock;
-use std::time::Duration;
-use tracing::{debug, error, info, warn};
+use anyhow::{Context, Result};
+use std::sync::Mutex;
+use tracing::{debug, info, warn};
 
-pub use super::cri_client::{CRIClient, CRIClientConfig};
-use crate::monitor::sandbox_cache::{SandboxCRIMetadata, SandboxCache};
+pub use super::cri_client::{CRIClient, CRIClientConfig, SandboxContainer};
+use crate::monitor::sandbox_cache::{
+    CriAnnotations, MetadataStatus, SandboxCRIMetadata, SandboxCache,
+};
 
 // Re-export proto definitions from cri_client
 #[allow(unused_imports)]
@@ -12,79 +13,198 @@ pub mod runtime {
     pub use crate::monitor::cri_client::runtime::*;
 }
 
-/// Global CRI client instance for reuse across monitor operations
-static CRI_CLIENT: OnceLock<CRIClient> = OnceLock::new();
+/// CRI client of the endpoint that answered last, reused across sync cycles
+static CRI_CLIENT: Mutex<Option<CRIClient>> = Mutex::new(None);
 
-/// Initialize the CRI client with the given endpoint
-pub fn init_cri_client(endpoint: impl Into<String>) -> Result<CRIClient> {
+/// Initialize a CRI client for the given endpoint
+///
+/// Timeout and retry settings are taken from `config`; its endpoint is ignored.
+pub fn init_cri_client(endpoint: impl Into<String>, config: &CRIClientConfig) -> CRIClient {
     let config = CRIClientConfig::with_endpoint(endpoint)
-        .with_timeout(Duration::from_secs(10))
-        .with_max_retries(3);
+        .with_timeout(config.timeout)
+        .with_max_retries(config.max_retries)
+        .with_retry_backoff(config.retry_backoff)
+        .with_keepalive(config.keepalive_interval, config.keepalive_timeout)
+        .with_max_message_size(config.max_message_size);
 
-    let client = CRIClient::new(config);
-    Ok(client)
+    CRIClient::new(config)
 }
 
 /// Get the global CRI client instance
-pub fn get_cri_client() -> Option<&'static CRIClient> {
-    CRI_CLIENT.get()
+pub fn get_cri_client() -> Option<CRIClient> {
+    CRI_CLIENT.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Set (or forget, with `None`) the global CRI client instance
+pub fn set_cri_client(client: Option<CRIClient>) {
+    *CRI_CLIENT.lock().unwrap_or_else(|e| e.into_inner()) = client;
+}
+
+/// Endpoint of the CRI runtime that answered the last metadata sync
+pub fn active_endpoint() -> Option<String> {
+    get_cri_client().map(|client| client.endpoint().to_string())
 }
 
-/// Set the global CRI client instance
-pub fn set_cri_client(client: CRIClient) -> Result<()> {
-    CRI_CLIENT
-        .set(client)
-        .map_err(|_| anyhow::anyhow!("CRI client already initialized"))
+/// List the running containers of a sandbox from the CRI runtime of the last metadata sync
+pub async fn list_sandbox_containers(sandbox_id: &str) -> Result<Vec<SandboxContainer>> {
+    let client = get_cri_client().context("no CRI runtime answered yet")?;
+    client.list_containers(sandbox_id).await
+}
+
+/// List pod sandboxes from the first CRI endpoint that answers
+///
+/// The endpoint that answered last is asked first; if it fails, all
+/// `endpoints` are tried in order with a fresh connection and the first one
+/// that answers is remembered for the next cycles.
+async fn list_pod_sandboxes(
+    endpoints: &[String],
+    config: &CRIClientConfig,
+) -> Result<Vec<runtime::PodSandbox>> {
+    if let Some(client) = get_cri_client() {
+        match client.list_pod_sandboxes().await {
+            Ok(pods) => return Ok(pods),
+            Err(e) => {
+                warn!(
+                    endpoint = %client.endpoint(),
+                    error = %e,
+                    "CRI endpoint stopped answering, trying configured endpoints"
+                );
+                set_cri_client(None);
+            }
+        }
+    }
+
+    let mut last_error = None;
+    for endpoint in endpoints {
+        let client = init_cri_client(endpoint.as_str(), config);
+        let result = match client.connect().await {
+            Ok(()) => client
+                .list_pod_sandboxes()
+                .await
+                .context("failed to retrieve pod sandboxes from CRI"),
+            Err(e) => Err(e.context("failed to connect to CRI endpoint")),
+        };
+        match result {
+            Ok(pods) => {
+                match client.probe().await {
+                    Ok(version) => {
+                        info!(endpoint = %endpoint, runtime = %version, "Using CRI endpoint")
+                    }
+                    Err(e) => {
+                        info!(endpoint = %endpoint, error = %e, "Using CRI endpoint (version unknown)")
+                    }
+                }
+                set_cri_client(Some(client));
+                return Ok(pods);
+            }
+            Err(e) => {
+                debug!(endpoint = %endpoint, error = format!("{:#}", e), "CRI endpoint did not answer");
+                last_error = Some(e);
+            }
+        }
+    }
+
+    // We'll try again next cycle
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("no CRI endpoint configured")))
+}
+
+/// Extract the per-sandbox collection interval from pod annotations
+///
+/// Invalid or zero values are ignored (with a warning) so the sandbox falls
+/// back to the global collection interval.
+pub fn scrape_interval_from_annotations(
+    annotations: &std::collections::HashMap<String, String>,
+) -> Option<u64> {
+    let value = annotations.get(crate::config::SCRAPE_INTERVAL_ANNOTATION)?;
+    match crate::config::parse_duration(value) {
+        Ok(interval) if interval.as_secs() > 0 => Some(interval.as_secs()),
+        Ok(_) => {
+            warn!(value = %value, "Ignoring scrape interval annotation below one second");
+            None
+        }
+        Err(e) => {
+            warn!(value = %value, error = %e, "Ignoring invalid scrape interval annotation");
+            None
+        }
+    }
+}
+
+/// Extract the guest CLK_TCK of a sandbox from pod annotations
+///
+/// For guest images whose kernel ticks at another rate than the host; values
+/// that are not a positive number are ignored (with a warning) so the sandbox
+/// falls back to the global `cpu_jiffy_conversion_factor`.
+pub fn clk_tck_from_annotations(
+    annotations: &std::collections::HashMap<String, String>,
+) -> Option<f64> {
+    let value = annotations.get(crate::config::CLK_TCK_ANNOTATION)?;
+    match value.trim().parse::<f64>() {
+        Ok(clk_tck) if clk_tck.is_finite() && clk_tck > 0.0 => Some(clk_tck),
+        _ => {
+            warn!(value = %value, "Ignoring invalid clk-tck annotation");
+            None
+        }
+    }
+}
+
+/// Extract the well-known `io.kubernetes.cri.*` annotations of a pod sandbox
+///
+/// Values that do not parse are ignored (with a debug log); the others are
+/// kept, so one malformed annotation does not hide the rest.
+pub fn cri_annotations_from(
+    annotations: &std::collections::HashMap<String, String>,
+) -> CriAnnotations {
+    fn get<'a>(
+        annotations: &'a std::collections::HashMap<String, String>,
+        name: &str,
+    ) -> Option<&'a str> {
+        annotations
+            .get(&format!("{}{}", crate::config::CRI_ANNOTATION_PREFIX, name))
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+    fn parse<T: std::str::FromStr>(
+        annotations: &std::collections::HashMap<String, String>,
+        name: &str,
+    ) -> Option<T> {
+        let value = get(annotations, name)?;
+        let parsed = value.parse().ok();
+        if parsed.is_none() {
+            debug!(annotation = %name, value = %value, "Ignoring invalid CRI annotation");
+        }
+        parsed
+    }
+
+    CriAnnotations {
+        cpu_period: parse(annotations, "sandbox-cpu-period"),
+        cpu_quota: parse(annotations, "sandbox-cpu-quota"),
+        cpu_shares: parse(annotations, "sandbox-cpu-shares"),
+        // containerd reports no memory limit as 0
+        memory_limit_bytes: parse(annotations, "sandbox-memory").filter(|bytes| *bytes > 0),
+        image_name: get(annotations, "image-name").map(str::to_string),
+        container_type: get(annotations, "container-type").map(str::to_string),
+    }
 }
 
 /// Sync sandboxes with CRI runtime metadata
 ///
-/// Attempts to connect to the CRI endpoint and retrieve pod metadata
-/// for all known sandboxes. This enriches our sandbox cache with
-/// Kubernetes pod information (name, namespace, UID).
+/// Attempts to connect to the CRI endpoints (in order, see
+/// `list_pod_sandboxes`) and retrieve pod metadata for all known sandboxes.
+/// This enriches our sandbox cache with Kubernetes pod information
+/// (name, namespace, UID).
 pub async fn sync_sandboxes(
-    endpoint: &str,
+    endpoints: &[String],
+    config: &CRIClientConfig,
     cache: &SandboxCache,
     mut sandbox_list: Vec<String>,
 ) -> Result<Vec<String>> {
     debug!(
-        endpoint = %endpoint,
+        endpoints = ?endpoints,
         sandbox_count = sandbox_list.len(),
         "Starting CRI sandbox metadata sync"
     );
 
-    // Create or get the CRI client
-    let client = match get_cri_client() {
-        Some(c) => c.clone(),
-        None => {
-            let mut c = init_cri_client(endpoint)?;
-
-            // Try to connect - if it fails, we'll return the sandbox list as-is
-            match c.connect().await {
-                Ok(_) => {
-                    set_cri_client(c.clone())?;
-                    c
-                }
-                Err(e) => {
-                    warn!(
-                        error = %e,
-                        "Failed to connect to CRI endpoint, skipping metadata sync"
-                    );
-                    return Ok(sandbox_list);
-                }
-            }
-        }
-    };
-
-    // Try to retrieve pod list from CRI
-    let pods = match client.list_pod_sandboxes().await {
-        Ok(pods) => pods,
-        Err(e) => {
-            error!(error = %e, "Failed to retrieve pod sandboxes from CRI");
-            // Return original list - we'll try again next cycle
-            return Ok(sandbox_list);
-        }
-    };
+    let pods = list_pod_sandboxes(endpoints, config).await?;
 
     debug!(pod_count = pods.len(), "Retrieved pods from CRI");
 
@@ -92,19 +212,21 @@ pub async fn sync_sandboxes(
     for pod in pods {
         if let Some(pos) = sandbox_list.iter().position(|s| pod.id == *s) {
             let sandbox_id = sandbox_list[pos].clone();
-            let metadata = pod
+            let mut metadata = pod
                 .metadata
                 .as_ref()
                 .map(|m| SandboxCRIMetadata {
                     uid: m.uid.clone(),
                     name: m.name.clone(),
                     namespace: m.namespace.clone(),
+                    ..Default::default()
                 })
-                .unwrap_or_else(|| SandboxCRIMetadata {
-                    uid: String::new(),
-                    name: String::new(),
-                    namespace: String::new(),
-                });
+                .unwrap_or_default();
+            metadata.runtime_handler = pod.runtime_handler.clone();
+            metadata.scrape_interval_secs = scrape_interval_from_annotations(&pod.annotations);
+            metadata.clk_tck = clk_tck_from_annotations(&pod.annotations);
+            metadata.cri_annotations = cri_annotations_from(&pod.annotations);
+            metadata.metadata_status = MetadataStatus::Synced;
 
             cache.set_cri_metadata(&sandbox_id, metadata).await;
 
@@ -113,8 +235,8 @@ pub async fn sync_sandboxes(
 
             info!(
                 sandbox_id = %sandbox_id,
-                pod_name = %pod.metadata.as_ref().map(|m| &m.name).unwrap_or(&"unknown".to_string()),
-                pod_namespace = %pod.metadata.as_ref().map(|m| &m.namespace).unwrap_or(&"unknown".to_string()),
+                pod = %pod.metadata.as_ref().map(|m| &m.name).unwrap_or(&"unknown".to_string()),
+                namespace = %pod.metadata.as_ref().map(|m| &m.namespace).unwrap_or(&"unknown".to_string()),
                 "Synced KATA POD metadata from CRI"
             );
         }
@@ -127,3 +249,113 @@ pub async fn sync_sandboxes(
 
     Ok(sandbox_list)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_sync_fails_when_no_endpoint_answers() {
+        let dir = tempfile::tempdir().unwrap();
+        let endpoints = vec![
+            dir.path().join("containerd.sock").display().to_string(),
+            dir.path().join("crio.sock").display().to_string(),
+        ];
+
+        let cache = SandboxCache::new();
+        let result = sync_sandboxes(
+            &endpoints,
+            &CRIClientConfig::default(),
+            &cache,
+            vec!["sandbox-1".to_string()],
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_scrape_interval_from_annotations() {
+        let mut annotations = HashMap::new();
+        assert_eq!(scrape_interval_from_annotations(&annotations), None);
+
+        annotations.insert(
+            crate::config::SCRAPE_INTERVAL_ANNOTATION.to_string(),
+            "10s".to_string(),
+        );
+        assert_eq!(scrape_interval_from_annotations(&annotations), Some(10));
+
+        annotations.insert(
+            crate::config::SCRAPE_INTERVAL_ANNOTATION.to_string(),
+            "5m".to_string(),
+        );
+        assert_eq!(scrape_interval_from_annotations(&annotations), Some(300));
+
+        annotations.insert(
+            crate::config::SCRAPE_INTERVAL_ANNOTATION.to_string(),
+            "soon".to_string(),
+        );
+        assert_eq!(scrape_interval_from_annotations(&annotations), None);
+    }
+
+    #[test]
+    fn test_clk_tck_from_annotations() {
+        let mut annotations = HashMap::new();
+        assert_eq!(clk_tck_from_annotations(&annotations), None);
+
+        annotations.insert(
+            crate::config::CLK_TCK_ANNOTATION.to_string(),
+            "250".to_string(),
+        );
+        assert_eq!(clk_tck_from_annotations(&annotations), Some(250.0));
+
+        for invalid in ["0", "-100", "fast", "NaN"] {
+            annotations.insert(
+                crate::config::CLK_TCK_ANNOTATION.to_string(),
+                invalid.to_string(),
+            );
+            assert_eq!(clk_tck_from_annotations(&annotations), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_cri_annotations_from() {
+        assert!(cri_annotations_from(&HashMap::new()).is_empty());
+
+        let annotations: HashMap<String, String> = [
+            ("io.kubernetes.cri.sandbox-cpu-period", "100000"),
+            ("io.kubernetes.cri.sandbox-cpu-quota", "250000"),
+            ("io.kubernetes.cri.sandbox-cpu-shares", "not-a-number"),
+            ("io.kubernetes.cri.sandbox-memory", "536870912"),
+            ("io.kubernetes.cri.image-name", "registry.k8s.io/pause:3.10"),
+            ("io.kubernetes.cri.container-type", "sandbox"),
+            ("kata-pulse.io/clk-tck", "250"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let parsed = cri_annotations_from(&annotations);
+        assert_eq!(parsed.cpu_period, Some(100_000));
+        assert_eq!(parsed.cpu_quota, Some(250_000));
+        assert_eq!(parsed.cpu_shares, None);
+        assert_eq!(parsed.cpu_limit_cores(), Some(2.5));
+        assert_eq!(parsed.memory_limit_bytes, Some(536_870_912));
+        assert_eq!(
+            parsed.image_name.as_deref(),
+            Some("registry.k8s.io/pause:3.10")
+        );
+        assert_eq!(parsed.container_type.as_deref(), Some("sandbox"));
+
+        let unlimited: HashMap<String, String> = [
+            ("io.kubernetes.cri.sandbox-cpu-quota", "-1"),
+            ("io.kubernetes.cri.sandbox-cpu-period", "100000"),
+            ("io.kubernetes.cri.sandbox-memory", "0"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let parsed = cri_annotations_from(&unlimited);
+        assert_eq!(parsed.cpu_limit_cores(), None);
+        assert_eq!(parsed.memory_limit_bytes, None);
+    }
+}
@@ -4,17 +4,40 @@
 //! container runtimes (containerd, CRI-O) via the CRI API.
 //! Uses k8s-cri for official Kubernetes CRI proto types.
 
+use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{anyhow, Result};
-use containerd_client::tonic::transport::Channel;
+use containerd_client::tonic::transport::{Channel, Endpoint, Error, Uri};
+use containerd_client::tonic::{self, Code, Status};
+use hyper_util::rt::TokioIo;
+use tokio::net::UnixStream;
 use tokio::sync::Mutex;
 use tracing::{debug, info, warn};
 
 // Re-export k8s-cri proto definitions
 pub use k8s_cri::v1 as runtime;
 use k8s_cri::v1::runtime_service_client::RuntimeServiceClient;
+use k8s_cri::v1alpha2;
+
+/// CRI API version spoken by the runtime
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CriApiVersion {
+    V1,
+    /// Pre-1.26 containerd / CRI-O
+    V1Alpha2,
+}
+
+impl CriApiVersion {
+    /// Name of the API version
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CriApiVersion::V1 => "v1",
+            CriApiVersion::V1Alpha2 => "v1alpha2",
+        }
+    }
+}
 
 /// Configuration for the CRI client
 #[derive(Clone, Debug)]
@@ -30,6 +53,15 @@ pub struct CRIClientConfig {
 
     /// Retry backoff duration
     pub retry_backoff: Duration,
+
+    /// Interval of HTTP/2 keepalive pings (None disables keepalive)
+    pub keepalive_interval: Option<Duration>,
+
+    /// Time to wait for a keepalive ping acknowledgement before closing the connection
+    pub keepalive_timeout: Duration,
+
+    /// Maximum size of a decoded gRPC response message in bytes
+    pub max_message_size: usize,
 }
 
 impl Default for CRIClientConfig {
@@ -39,6 +71,9 @@ impl Default for CRIClientConfig {
             timeout: Duration::from_secs(10),
             max_retries: 3,
             retry_backoff: Duration::from_millis(100),
+            keepalive_interval: Some(Duration::from_secs(30)),
+            keepalive_timeout: Duration::from_secs(20),
+            max_message_size: 16 * 1024 * 1024,
         }
     }
 }
@@ -63,6 +98,25 @@ impl CRIClientConfig {
         self.max_retries = retries;
         self
     }
+
+    /// Set the backoff between retries
+    pub fn with_retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Set the HTTP/2 keepalive ping interval (None disables) and ack timeout
+    pub fn with_keepalive(mut self, interval: Option<Duration>, timeout: Duration) -> Self {
+        self.keepalive_interval = interval;
+        self.keepalive_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum size of a decoded response message
+    pub fn with_max_message_size(mut self, bytes: usize) -> Self {
+        self.max_message_size = bytes;
+        self
+    }
 }
 
 /// CRI Runtime Service Client
@@ -73,6 +127,8 @@ pub struct CRIClient {
     config: CRIClientConfig,
     // Channel is kept in Arc<Mutex<>> for shared access across async tasks
     channel: Arc<Mutex<Option<Channel>>>,
+    // Negotiated API version, shared by clones like the channel
+    api_version: Arc<std::sync::Mutex<Option<CriApiVersion>>>,
 }
 
 impl CRIClient {
@@ -81,11 +137,17 @@ impl CRIClient {
         CRIClient {
             config,
             channel: Arc::new(Mutex::new(None)),
+            api_version: Arc::new(std::sync::Mutex::new(None)),
         }
     }
 
+    /// Endpoint this client talks to
+    pub fn endpoint(&self) -> &str {
+        &self.config.endpoint
+    }
+
     /// Connect to the CRI endpoint
-    pub async fn connect(&mut self) -> Result<()> {
+    pub async fn connect(&self) -> Result<()> {
         debug!(
             endpoint = %self.config.endpoint,
             timeout_ms = self.config.timeout.as_millis(),
@@ -112,20 +174,9 @@ impl CRIClient {
             "Attempting Unix socket connection"
         );
 
-        // Use containerd_client but with JUST the socket path (no unix:// prefix)
-        // containerd_client::connect expects raw filesystem paths for Unix sockets
-        let connect_path = socket_path_str.clone();
-
-        debug!(
-            socket_path = %connect_path,
-            "Using containerd_client::connect with direct path"
-        );
-
-        // Connect using containerd_client with just the path
-        // It internally handles the unix:// URL construction
         match tokio::time::timeout(
             self.config.timeout,
-            containerd_client::connect(&connect_path),
+            connect_channel(&socket_path_str, &self.config),
         )
         .await
         {
@@ -144,7 +195,7 @@ impl CRIClient {
             Ok(Err(e)) => {
                 warn!(
                     endpoint = %self.config.endpoint,
-                    socket_path = %connect_path,
+                    socket_path = %socket_path_str,
                     error = %e,
                     "gRPC channel creation failed"
                 );
@@ -188,12 +239,137 @@ impl CRIClient {
         }
     }
 
-    /// Get the stored channel
+    /// Get the stored channel, re-dialing the endpoint if it was dropped
     async fn get_channel(&self) -> Result<Channel> {
-        let channel = self.channel.lock().await;
-        channel
+        if let Some(channel) = self.channel.lock().await.clone() {
+            return Ok(channel);
+        }
+
+        info!(endpoint = %self.config.endpoint, "Reconnecting to CRI endpoint");
+        self.connect().await?;
+        self.channel
+            .lock()
+            .await
             .clone()
-            .ok_or_else(|| anyhow!("CRI client not connected. Call connect() first."))
+            .ok_or_else(|| anyhow!("CRI client not connected"))
+    }
+
+    /// v1 runtime service client on the stored channel
+    async fn runtime_client(&self) -> Result<RuntimeServiceClient<Channel>> {
+        let channel = self.get_channel().await?;
+        Ok(RuntimeServiceClient::new(channel)
+            .max_decoding_message_size(self.config.max_message_size))
+    }
+
+    /// v1alpha2 runtime service client on the stored channel
+    async fn runtime_client_v1alpha2(
+        &self,
+    ) -> Result<v1alpha2::runtime_service_client::RuntimeServiceClient<Channel>> {
+        let channel = self.get_channel().await?;
+        Ok(
+            v1alpha2::runtime_service_client::RuntimeServiceClient::new(channel)
+                .max_decoding_message_size(self.config.max_message_size),
+        )
+    }
+
+    /// CRI API version negotiated with the runtime (None until the first successful call)
+    pub fn api_version(&self) -> Option<CriApiVersion> {
+        *self.api_version.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn set_api_version(&self, version: Option<CriApiVersion>) {
+        let mut current = self.api_version.lock().unwrap_or_else(|e| e.into_inner());
+        if *current != version {
+            if let Some(version) = version {
+                info!(endpoint = %self.config.endpoint, api_version = version.as_str(), "Negotiated CRI API version");
+            }
+            *current = version;
+        }
+    }
+
+    /// Run an RPC with the configured timeout, dropping the channel on transport errors
+    async fn rpc<T>(
+        &self,
+        call: impl Future<Output = Result<tonic::Response<T>, Status>>,
+    ) -> Result<T, Status> {
+        match tokio::time::timeout(self.config.timeout, call).await {
+            Ok(Ok(response)) => Ok(response.into_inner()),
+            Ok(Err(status)) => {
+                if is_transport_error(&status) {
+                    self.reset_channel().await;
+                }
+                Err(status)
+            }
+            Err(_) => {
+                self.reset_channel().await;
+                Err(Status::deadline_exceeded(format!(
+                    "timed out after {:?}",
+                    self.config.timeout
+                )))
+            }
+        }
+    }
+
+    /// Whether to fall back to v1alpha2 after a failed v1 call
+    ///
+    /// Only while the version is not negotiated yet: a runtime that answered
+    /// v1 once does not lose it.
+    fn should_fall_back(&self, status: &Status) -> bool {
+        status.code() == Code::Unimplemented && self.api_version().is_none()
+    }
+
+    /// Drop the channel after a transport error so the next request re-dials
+    ///
+    /// The gRPC channel of a restarted runtime keeps failing otherwise.
+    async fn reset_channel(&self) {
+        // The runtime may come back with a different API version (upgrade)
+        self.set_api_version(None);
+        let mut channel = self.channel.lock().await;
+        if channel.take().is_some() {
+            warn!(endpoint = %self.config.endpoint, "Dropped CRI channel after transport error");
+        }
+    }
+
+    /// Check that the runtime answers, returning its name, version and CRI API version
+    pub async fn probe(&self) -> Result<String> {
+        let response = if self.api_version() != Some(CriApiVersion::V1Alpha2) {
+            let mut client = self.runtime_client().await?;
+            match self
+                .rpc(client.version(runtime::VersionRequest::default()))
+                .await
+            {
+                Ok(response) => {
+                    self.set_api_version(Some(CriApiVersion::V1));
+                    Some((
+                        response.runtime_name,
+                        response.runtime_version,
+                        response.runtime_api_version,
+                    ))
+                }
+                Err(status) if self.should_fall_back(&status) => None,
+                Err(status) => return Err(anyhow!("Version RPC failed: {}", status)),
+            }
+        } else {
+            None
+        };
+
+        let (name, version, api_version) = match response {
+            Some(response) => response,
+            None => {
+                let mut client = self.runtime_client_v1alpha2().await?;
+                let response = self
+                    .rpc(client.version(v1alpha2::VersionRequest::default()))
+                    .await
+                    .map_err(|status| anyhow!("Version RPC failed: {}", status))?;
+                self.set_api_version(Some(CriApiVersion::V1Alpha2));
+                (
+                    response.runtime_name,
+                    response.runtime_version,
+                    response.runtime_api_version,
+                )
+            }
+        };
+        Ok(format!("{} {} (CRI {})", name, version, api_version))
     }
 
     /// List pod sandboxes with retry logic
@@ -241,30 +417,189 @@ impl CRIClient {
     }
 
     /// Internal implementation of list_pod_sandboxes
+    ///
+    /// Tries CRI v1 first and falls back to v1alpha2 on runtimes that don't
+    /// implement v1 (pre-1.26 containerd/CRI-O); v1alpha2 results are converted
+    /// to v1 types.
     async fn list_pod_sandboxes_internal(
         &self,
         filter: Option<runtime::PodSandboxFilter>,
     ) -> Result<Vec<runtime::PodSandbox>> {
         debug!("Sending ListPodSandbox request to CRI");
 
-        let channel = self.get_channel().await?;
-        let mut client = RuntimeServiceClient::new(channel);
+        if self.api_version() != Some(CriApiVersion::V1Alpha2) {
+            let mut client = self.runtime_client().await?;
+            let request = runtime::ListPodSandboxRequest {
+                filter: filter.clone(),
+            };
+            match self.rpc(client.list_pod_sandbox(request)).await {
+                Ok(response) => {
+                    self.set_api_version(Some(CriApiVersion::V1));
+                    return Ok(response.items);
+                }
+                Err(status) if self.should_fall_back(&status) => {
+                    info!(endpoint = %self.config.endpoint, "CRI v1 not implemented, falling back to v1alpha2");
+                }
+                Err(status) => return Err(anyhow!("ListPodSandbox RPC failed: {}", status)),
+            }
+        }
+
+        let mut client = self.runtime_client_v1alpha2().await?;
+        let request = v1alpha2::ListPodSandboxRequest {
+            filter: filter.map(filter_to_v1alpha2),
+        };
+        let response = self
+            .rpc(client.list_pod_sandbox(request))
+            .await
+            .map_err(|status| anyhow!("ListPodSandbox (v1alpha2) RPC failed: {}", status))?;
+        self.set_api_version(Some(CriApiVersion::V1Alpha2));
+
+        Ok(response
+            .items
+            .into_iter()
+            .map(pod_sandbox_from_v1alpha2)
+            .collect())
+    }
+
+    /// List the running containers of a pod sandbox
+    pub async fn list_containers(&self, pod_sandbox_id: &str) -> Result<Vec<SandboxContainer>> {
+        debug!(pod_sandbox_id, "Sending ListContainers request to CRI");
+
+        if self.api_version() != Some(CriApiVersion::V1Alpha2) {
+            let mut client = self.runtime_client().await?;
+            let request = runtime::ListContainersRequest {
+                filter: Some(runtime::ContainerFilter {
+                    pod_sandbox_id: pod_sandbox_id.to_string(),
+                    state: Some(runtime::ContainerStateValue {
+                        state: runtime::ContainerState::ContainerRunning as i32,
+                    }),
+                    ..Default::default()
+                }),
+            };
+            match self.rpc(client.list_containers(request)).await {
+                Ok(response) => {
+                    self.set_api_version(Some(CriApiVersion::V1));
+                    return Ok(response
+                        .containers
+                        .into_iter()
+                        .map(|container| SandboxContainer {
+                            id: container.id,
+                            name: container.metadata.map(|m| m.name).unwrap_or_default(),
+                            image: container.image.map(|i| i.image).unwrap_or_default(),
+                        })
+                        .collect());
+                }
+                Err(status) if self.should_fall_back(&status) => {
+                    info!(endpoint = %self.config.endpoint, "CRI v1 not implemented, falling back to v1alpha2");
+                }
+                Err(status) => return Err(anyhow!("ListContainers RPC failed: {}", status)),
+            }
+        }
 
-        let request = runtime::ListPodSandboxRequest { filter };
-        let response = client
-            .list_pod_sandbox(request)
+        let mut client = self.runtime_client_v1alpha2().await?;
+        let request = v1alpha2::ListContainersRequest {
+            filter: Some(v1alpha2::ContainerFilter {
+                pod_sandbox_id: pod_sandbox_id.to_string(),
+                state: Some(v1alpha2::ContainerStateValue {
+                    state: v1alpha2::ContainerState::ContainerRunning as i32,
+                }),
+                ..Default::default()
+            }),
+        };
+        let response = self
+            .rpc(client.list_containers(request))
             .await
-            .map_err(|e| anyhow!("ListPodSandbox RPC failed: {}", e))?;
+            .map_err(|status| anyhow!("ListContainers (v1alpha2) RPC failed: {}", status))?;
+        self.set_api_version(Some(CriApiVersion::V1Alpha2));
+
+        Ok(response
+            .containers
+            .into_iter()
+            .map(|container| SandboxContainer {
+                id: container.id,
+                name: container.metadata.map(|m| m.name).unwrap_or_default(),
+                image: container.image.map(|i| i.image).unwrap_or_default(),
+            })
+            .collect())
+    }
+}
 
-        Ok(response.into_inner().items)
+/// A running container of a pod sandbox, as reported by CRI
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SandboxContainer {
+    /// Container ID, also its ID inside the guest
+    pub id: String,
+    /// Container name of the pod spec
+    pub name: String,
+    pub image: String,
+}
+
+/// Convert a v1 pod sandbox filter to v1alpha2 (the messages are field-for-field identical)
+fn filter_to_v1alpha2(filter: runtime::PodSandboxFilter) -> v1alpha2::PodSandboxFilter {
+    v1alpha2::PodSandboxFilter {
+        id: filter.id,
+        state: filter
+            .state
+            .map(|state| v1alpha2::PodSandboxStateValue { state: state.state }),
+        label_selector: filter.label_selector,
     }
 }
 
+/// Convert a v1alpha2 pod sandbox to v1 (the messages are field-for-field identical)
+fn pod_sandbox_from_v1alpha2(pod: v1alpha2::PodSandbox) -> runtime::PodSandbox {
+    runtime::PodSandbox {
+        id: pod.id,
+        metadata: pod.metadata.map(|m| runtime::PodSandboxMetadata {
+            name: m.name,
+            uid: m.uid,
+            namespace: m.namespace,
+            attempt: m.attempt,
+        }),
+        state: pod.state,
+        created_at: pod.created_at,
+        labels: pod.labels,
+        annotations: pod.annotations,
+        runtime_handler: pod.runtime_handler,
+    }
+}
+
+/// Create a gRPC channel over the Unix socket at `socket_path`
+///
+/// Keepalive pings detect connections that died silently (e.g. through a
+/// proxy) while idle between sync cycles.
+async fn connect_channel(socket_path: &str, config: &CRIClientConfig) -> Result<Channel, Error> {
+    // The URI is ignored: the connector dials the Unix socket directly
+    let mut endpoint = Endpoint::from_static("http://[::]").connect_timeout(config.timeout);
+    if let Some(interval) = config.keepalive_interval {
+        endpoint = endpoint
+            .http2_keep_alive_interval(interval)
+            .keep_alive_timeout(config.keepalive_timeout)
+            .keep_alive_while_idle(true);
+    }
+
+    let socket_path = socket_path.to_string();
+    endpoint
+        .connect_with_connector(tower::service_fn(move |_: Uri| {
+            let socket_path = socket_path.clone();
+            async move { Ok::<_, std::io::Error>(TokioIo::new(UnixStream::connect(socket_path).await?)) }
+        }))
+        .await
+}
+
+/// Whether a failed RPC points at a broken connection rather than a runtime error
+fn is_transport_error(status: &Status) -> bool {
+    matches!(
+        status.code(),
+        Code::Unavailable | Code::Unknown | Code::Cancelled
+    )
+}
+
 impl Clone for CRIClient {
     fn clone(&self) -> Self {
         CRIClient {
             config: self.config.clone(),
             channel: Arc::clone(&self.channel),
+            api_version: Arc::clone(&self.api_version),
         }
     }
 }
@@ -291,11 +626,13 @@ mod tests {
     fn test_cri_client_config_builder() {
         let config = CRIClientConfig::with_endpoint("/tmp/test.sock")
             .with_timeout(Duration::from_secs(20))
-            .with_max_retries(5);
+            .with_max_retries(5)
+            .with_retry_backoff(Duration::from_millis(250));
 
         assert_eq!(config.endpoint, "/tmp/test.sock");
         assert_eq!(config.timeout, Duration::from_secs(20));
         assert_eq!(config.max_retries, 5);
+        assert_eq!(config.retry_backoff, Duration::from_millis(250));
     }
 
     #[test]
@@ -314,6 +651,84 @@ mod tests {
         assert_eq!(client1.config.endpoint, client2.config.endpoint);
     }
 
+    #[test]
+    fn test_transport_errors() {
+        assert!(is_transport_error(&Status::unavailable(
+            "connection refused"
+        )));
+        assert!(is_transport_error(&Status::unknown("transport error")));
+        assert!(!is_transport_error(&Status::unimplemented("v1 not served")));
+        assert!(!is_transport_error(&Status::permission_denied("denied")));
+    }
+
+    #[tokio::test]
+    async fn test_request_redials_missing_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let config =
+            CRIClientConfig::with_endpoint(dir.path().join("cri.sock").display().to_string())
+                .with_timeout(Duration::from_secs(1))
+                .with_max_retries(0);
+        let client = CRIClient::new(config);
+
+        // Not connected: the request dials itself and reports the connect error
+        let err = client.list_pod_sandboxes().await.unwrap_err();
+        assert!(err.to_string().contains("cri.sock"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_connect_channel_with_keepalive() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("cri.sock");
+        let listener = tokio::net::UnixListener::bind(&socket_path).unwrap();
+        tokio::spawn(async move {
+            let _conn = listener.accept().await;
+            std::future::pending::<()>().await;
+        });
+
+        let config = CRIClientConfig::default()
+            .with_keepalive(Some(Duration::from_secs(5)), Duration::from_secs(1))
+            .with_max_message_size(1024);
+        assert!(connect_channel(socket_path.to_str().unwrap(), &config)
+            .await
+            .is_ok());
+    }
+
+    #[test]
+    fn test_pod_sandbox_from_v1alpha2() {
+        let pod = v1alpha2::PodSandbox {
+            id: "sandbox-1".to_string(),
+            metadata: Some(v1alpha2::PodSandboxMetadata {
+                name: "my-pod".to_string(),
+                uid: "uid-1".to_string(),
+                namespace: "default".to_string(),
+                attempt: 1,
+            }),
+            runtime_handler: "kata".to_string(),
+            annotations: [("a".to_string(), "b".to_string())].into(),
+            ..Default::default()
+        };
+
+        let converted = pod_sandbox_from_v1alpha2(pod);
+        assert_eq!(converted.id, "sandbox-1");
+        assert_eq!(converted.runtime_handler, "kata");
+        assert_eq!(converted.annotations["a"], "b");
+        let metadata = converted.metadata.unwrap();
+        assert_eq!(metadata.name, "my-pod");
+        assert_eq!(metadata.namespace, "default");
+        assert_eq!(metadata.uid, "uid-1");
+    }
+
+    #[test]
+    fn test_fallback_only_before_negotiation() {
+        let client = CRIClient::new(CRIClientConfig::default());
+        let unimplemented = Status::unimplemented("unknown service runtime.v1.RuntimeService");
+        assert!(client.should_fall_back(&unimplemented));
+        assert!(!client.should_fall_back(&Status::unavailable("down")));
+
+        client.set_api_version(Some(CriApiVersion::V1));
+        assert!(!client.should_fall_back(&unimplemented));
+    }
+
     #[test]
     fn test_cri_client_config_retry_backoff() {
         let config = CRIClientConfig::default();
@@ -0,0 +1,164 @@
+//! Entity info - info-style metrics carrying the metadata of the targets
+//!
+//! Responsibilities:
+//! - Render `target_info`, the OpenMetrics info metric of the exporter itself
+//!   (service, version and node as resource attributes)
+//! - Render `kata_pulse_sandbox_info` with one series per collected sandbox,
+//!   carrying its pod, namespace, UID, runtime handler and hypervisor
+//!
+//! The metadata is attached to other series with a PromQL join on
+//! `sandbox_id` (or `pod`/`namespace`) instead of being repeated on every
+//! series, so rich metadata does not multiply their cardinality:
+//!
+//! ```promql
+//! container_memory_usage_bytes * on (pod, namespace) group_left (hypervisor) kata_pulse_sandbox_info
+//! ```
+
+use std::fmt::Write;
+
+use super::node_info::NodeInfo;
+use super::sandbox_cache::SandboxCRIMetadata;
+use super::sandbox_filter::SandboxFilter;
+use crate::build_info::BuildInfo;
+use crate::utils::metrics_converter::cadvisor::escape_label_value;
+
+/// Hypervisors by runtime handler suffix, following the kata-deploy handler names
+const HYPERVISOR_SUFFIXES: [(&str, &str); 6] = [
+    ("qemu", "qemu"),
+    ("clh", "cloud-hypervisor"),
+    ("cloud-hypervisor", "cloud-hypervisor"),
+    ("fc", "firecracker"),
+    ("dragonball", "dragonball"),
+    ("stratovirt", "stratovirt"),
+];
+
+/// Hypervisor of a sandbox from its CRI runtime handler (empty if unknown)
+///
+/// kata-deploy names the handlers `kata-<hypervisor>[-<variant>]`, e.g.
+/// `kata-clh` or `kata-qemu-tdx`; the plain `kata` handler tells nothing.
+pub fn hypervisor_of(runtime_handler: &str) -> &'static str {
+    let Some(rest) = runtime_handler.strip_prefix("kata-") else {
+        return "";
+    };
+    HYPERVISOR_SUFFIXES
+        .iter()
+        .find(|(suffix, _)| {
+            rest == *suffix
+                || rest
+                    .strip_prefix(suffix)
+                    .is_some_and(|variant| variant.starts_with('-'))
+        })
+        .map_or("", |(_, hypervisor)| hypervisor)
+}
+
+/// Render `target_info` in Prometheus text format
+///
+/// The labels follow the OpenTelemetry resource attributes, so a
+/// Prometheus-to-OTLP bridge turns them back into the resource.
+pub fn render_target_info(node_info: &NodeInfo, build_info: &BuildInfo) -> String {
+    let mut output = String::new();
+    output.push_str("# HELP target_info Target metadata\n");
+    output.push_str("# TYPE target_info gauge\n");
+    let _ = writeln!(
+        output,
+        "target_info{{service_name=\"kata-pulse\",service_version=\"{}\",host_name=\"{}\",cloud_availability_zone=\"{}\",cloud_region=\"{}\"}} 1",
+        escape_label_value(build_info.version),
+        escape_label_value(node_info.node_name.as_deref().unwrap_or_default()),
+        escape_label_value(node_info.zone.as_deref().unwrap_or_default()),
+        escape_label_value(node_info.region.as_deref().unwrap_or_default()),
+    );
+    output
+}
+
+/// Render `kata_pulse_sandbox_info` for the sandboxes allowed by `filter`
+pub fn render_sandbox_info(
+    sandboxes: &[(String, SandboxCRIMetadata)],
+    filter: &SandboxFilter,
+) -> String {
+    let mut output = String::new();
+    let mut collected: Vec<_> = sandboxes
+        .iter()
+        .filter(|(_, metadata)| filter.allows(metadata))
+        .collect();
+    if collected.is_empty() {
+        return output;
+    }
+    collected.sort_by(|a, b| a.0.cmp(&b.0));
+
+    output.push_str(
+        "# HELP kata_pulse_sandbox_info Pod and runtime metadata of the sandbox, always 1\n",
+    );
+    output.push_str("# TYPE kata_pulse_sandbox_info gauge\n");
+    for (id, metadata) in collected {
+        let _ = writeln!(
+            output,
+            "kata_pulse_sandbox_info{{sandbox_id=\"{}\",pod=\"{}\",namespace=\"{}\",uid=\"{}\",runtime=\"{}\",hypervisor=\"{}\"}} 1",
+            escape_label_value(id),
+            escape_label_value(&metadata.name),
+            escape_label_value(&metadata.namespace),
+            escape_label_value(&metadata.uid),
+            escape_label_value(&metadata.runtime_handler),
+            hypervisor_of(&metadata.runtime_handler),
+        );
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandbox(id: &str, namespace: &str, runtime_handler: &str) -> (String, SandboxCRIMetadata) {
+        (
+            id.to_string(),
+            SandboxCRIMetadata {
+                uid: format!("uid-{}", id),
+                name: format!("pod-{}", id),
+                namespace: namespace.to_string(),
+                runtime_handler: runtime_handler.to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_hypervisor_of() {
+        assert_eq!(hypervisor_of("kata-qemu"), "qemu");
+        assert_eq!(hypervisor_of("kata-qemu-tdx"), "qemu");
+        assert_eq!(hypervisor_of("kata-clh"), "cloud-hypervisor");
+        assert_eq!(hypervisor_of("kata-fc"), "firecracker");
+        assert_eq!(hypervisor_of("kata-fcx"), "");
+        assert_eq!(hypervisor_of("kata"), "");
+        assert_eq!(hypervisor_of("runc"), "");
+    }
+
+    #[test]
+    fn test_render_sandbox_info() {
+        let sandboxes = vec![
+            sandbox("sb-2", "default", "kata-clh"),
+            sandbox("sb-1", "default", "kata"),
+            sandbox("sb-3", "kube-system", "kata-qemu"),
+        ];
+        let filter = SandboxFilter::default().with_namespace_deny(vec!["kube-system".to_string()]);
+        let output = render_sandbox_info(&sandboxes, &filter);
+
+        assert!(output.contains("# TYPE kata_pulse_sandbox_info gauge\n"));
+        let lines: Vec<_> = output.lines().filter(|l| !l.starts_with('#')).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"kata_pulse_sandbox_info{sandbox_id="sb-1",pod="pod-sb-1",namespace="default",uid="uid-sb-1",runtime="kata",hypervisor=""} 1"#
+        );
+        assert!(lines[1].contains(r#"runtime="kata-clh",hypervisor="cloud-hypervisor""#));
+        assert!(render_sandbox_info(&[], &filter).is_empty());
+    }
+
+    #[test]
+    fn test_render_target_info() {
+        let node_info = NodeInfo::new(Some("worker-1".to_string()), None, None);
+        let output = render_target_info(&node_info, &BuildInfo::current());
+        assert!(output.contains("# TYPE target_info gauge\n"));
+        assert!(output.contains(r#"target_info{service_name="kata-pulse",service_version=""#));
+        assert!(output.contains(r#"host_name="worker-1",cloud_availability_zone="""#));
+    }
+}
@@ -0,0 +1,183 @@
+//! Fleet gauges - node-level counts of the tracked sandboxes
+//!
+//! Responsibilities:
+//! - Count tracked sandboxes, sandboxes without CRI metadata (and those that
+//!   ran out of metadata retries), excluded ones and those whose scrapes fail, from `SandboxCache` and scrape health
+//! - Render them as `kata_pulse_sandboxes_*` gauges, the first things to alert on
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::sandbox_cache::{MetadataStatus, SandboxCRIMetadata};
+use super::sandbox_filter::SandboxFilter;
+use super::scrape_health::ScrapeStatus;
+
+/// Node-level counts of tracked sandboxes
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct FleetStats {
+    /// Sandboxes found on the node
+    pub total: usize,
+    /// Sandboxes the CRI runtime has not reported metadata for (yet)
+    pub missing_metadata: usize,
+    /// Sandboxes marked metadata_unavailable after the metadata retry budget
+    pub metadata_unavailable: usize,
+    /// Sandboxes skipped by the sandbox filter
+    pub excluded: usize,
+    /// Collected sandboxes whose last scrape failed
+    pub failing_scrape: usize,
+    /// Collected sandboxes quarantined by the circuit breaker
+    pub quarantined: usize,
+}
+
+impl FleetStats {
+    /// Compute the counts from the sandbox cache contents and scrape statuses
+    pub fn compute(
+        sandboxes: &[(String, SandboxCRIMetadata)],
+        statuses: &[(String, ScrapeStatus)],
+        filter: &SandboxFilter,
+    ) -> Self {
+        let statuses: HashMap<&str, &ScrapeStatus> = statuses
+            .iter()
+            .map(|(id, status)| (id.as_str(), status))
+            .collect();
+
+        let mut stats = FleetStats {
+            total: sandboxes.len(),
+            ..Default::default()
+        };
+        for (id, metadata) in sandboxes {
+            if metadata.uid.is_empty() {
+                stats.missing_metadata += 1;
+            }
+            if metadata.metadata_status == MetadataStatus::MetadataUnavailable {
+                stats.metadata_unavailable += 1;
+            }
+            if !filter.allows(metadata) {
+                stats.excluded += 1;
+                continue;
+            }
+            if let Some(status) = statuses.get(id.as_str()) {
+                if !status.up {
+                    stats.failing_scrape += 1;
+                }
+                if status.quarantined {
+                    stats.quarantined += 1;
+                }
+            }
+        }
+        stats
+    }
+
+    /// Render the counts in Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        let gauges = [
+            (
+                "kata_pulse_sandboxes_total",
+                "Number of Kata sandboxes found on the node",
+                self.total,
+            ),
+            (
+                "kata_pulse_sandboxes_missing_metadata",
+                "Number of sandboxes without CRI metadata (pod name, namespace, UID)",
+                self.missing_metadata,
+            ),
+            (
+                "kata_pulse_sandboxes_metadata_unavailable",
+                "Number of sandboxes that got no CRI metadata within the metadata retry budget",
+                self.metadata_unavailable,
+            ),
+            (
+                "kata_pulse_sandboxes_excluded",
+                "Number of sandboxes skipped by the namespace/runtime handler filter",
+                self.excluded,
+            ),
+            (
+                "kata_pulse_sandboxes_failing_scrape",
+                "Number of collected sandboxes whose last metrics scrape failed",
+                self.failing_scrape,
+            ),
+            (
+                "kata_pulse_sandboxes_quarantined",
+                "Number of collected sandboxes quarantined after repeated scrape failures",
+                self.quarantined,
+            ),
+        ];
+
+        let mut output = String::new();
+        for (name, help, value) in gauges {
+            output.push_str(&format!("# HELP {} {}\n", name, help));
+            output.push_str(&format!("# TYPE {} gauge\n", name));
+            output.push_str(&format!("{} {}\n", name, value));
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandbox(id: &str, uid: &str, namespace: &str) -> (String, SandboxCRIMetadata) {
+        (
+            id.to_string(),
+            SandboxCRIMetadata {
+                uid: uid.to_string(),
+                namespace: namespace.to_string(),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn status(id: &str, up: bool, quarantined: bool) -> (String, ScrapeStatus) {
+        (
+            id.to_string(),
+            ScrapeStatus {
+                up,
+                quarantined,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn test_compute_fleet_stats() {
+        let sandboxes = vec![
+            sandbox("sb-1", "uid-1", "default"),
+            sandbox("sb-2", "", ""),
+            sandbox("sb-3", "uid-3", "default"),
+            sandbox("sb-4", "uid-4", "kube-system"),
+            (
+                "sb-5".to_string(),
+                SandboxCRIMetadata {
+                    metadata_status: MetadataStatus::MetadataUnavailable,
+                    ..Default::default()
+                },
+            ),
+        ];
+        let statuses = vec![
+            status("sb-1", true, false),
+            status("sb-3", false, true),
+            status("sb-4", false, false),
+        ];
+        let filter = SandboxFilter::default().with_namespace_deny(vec!["kube-system".to_string()]);
+
+        let stats = FleetStats::compute(&sandboxes, &statuses, &filter);
+        assert_eq!(
+            stats,
+            FleetStats {
+                total: 5,
+                missing_metadata: 2,
+                metadata_unavailable: 1,
+                excluded: 1,
+                failing_scrape: 1,
+                quarantined: 1,
+            }
+        );
+
+        let output = stats.render_metrics();
+        assert!(output.contains("kata_pulse_sandboxes_total 5\n"));
+        assert!(output.contains("kata_pulse_sandboxes_missing_metadata 2\n"));
+        assert!(output.contains("kata_pulse_sandboxes_metadata_unavailable 1\n"));
+        assert!(output.contains("kata_pulse_sandboxes_failing_scrape 1\n"));
+    }
+}
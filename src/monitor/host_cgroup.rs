@@ -0,0 +1,189 @@
+//! Host cgroup - resource usage of the sandbox overhead outside the guest
+//!
+//! Responsibilities:
+//! - Locate the overhead cgroup Kata creates on the host for each sandbox's
+//!   shim/hypervisor processes (`kata_overhead/<sandbox-id>`, used unless
+//!   `sandbox_cgroup_only` is set)
+//! - Read its CPU time and memory usage (cgroup v2, falling back to v1)
+//! - Attach them to the scraped metrics as `kata_overhead_*` families
+//!
+//! Guest metrics cannot show the cost of the VMM itself, which is what
+//! capacity planning for Kata needs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::utils::prometheus_parser::{MetricSample, PrometheusMetrics};
+
+/// Default mount point of the host cgroup hierarchy
+pub const DEFAULT_CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Parent cgroup Kata places the per-sandbox overhead cgroups in
+const OVERHEAD_CGROUP: &str = "kata_overhead";
+
+/// Raw family of the overhead CPU time attached to scraped metrics
+pub const OVERHEAD_CPU_METRIC: &str = "kata_overhead_cpu_seconds_total";
+
+/// Raw family of the overhead memory usage attached to scraped metrics
+pub const OVERHEAD_MEMORY_METRIC: &str = "kata_overhead_memory_bytes";
+
+/// Resource usage of a sandbox's overhead cgroup
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OverheadStats {
+    /// CPU time consumed by the shim/hypervisor processes
+    pub cpu_seconds_total: Option<f64>,
+    /// Memory charged to the shim/hypervisor processes
+    pub memory_bytes: Option<u64>,
+}
+
+/// Reads per-sandbox overhead cgroups below a cgroup root
+#[derive(Clone, Debug)]
+pub struct HostCgroupReader {
+    root: PathBuf,
+}
+
+impl HostCgroupReader {
+    /// Create a reader for the cgroup hierarchy mounted at `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        HostCgroupReader { root: root.into() }
+    }
+
+    /// Read the overhead cgroup of a sandbox
+    ///
+    /// Returns None if the sandbox has no overhead cgroup (e.g. `sandbox_cgroup_only`).
+    pub fn read_overhead(&self, sandbox_id: &str) -> Option<OverheadStats> {
+        let v2 = self.root.join(OVERHEAD_CGROUP).join(sandbox_id);
+        if v2.join("cgroup.procs").exists() && v2.join("cpu.stat").exists() {
+            return Some(OverheadStats {
+                cpu_seconds_total: read_cpu_stat_usage(&v2.join("cpu.stat")),
+                memory_bytes: read_u64(&v2.join("memory.current")),
+            });
+        }
+
+        let cpu_dir = ["cpu,cpuacct", "cpuacct"]
+            .iter()
+            .map(|controller| {
+                self.root
+                    .join(controller)
+                    .join(OVERHEAD_CGROUP)
+                    .join(sandbox_id)
+            })
+            .find(|dir| dir.is_dir());
+        let memory_dir = self
+            .root
+            .join("memory")
+            .join(OVERHEAD_CGROUP)
+            .join(sandbox_id);
+        if cpu_dir.is_none() && !memory_dir.is_dir() {
+            return None;
+        }
+
+        Some(OverheadStats {
+            // cpuacct.usage is in nanoseconds
+            cpu_seconds_total: cpu_dir
+                .and_then(|dir| read_u64(&dir.join("cpuacct.usage")))
+                .map(|ns| ns as f64 / 1e9),
+            memory_bytes: read_u64(&memory_dir.join("memory.usage_in_bytes")),
+        })
+    }
+
+    /// Attach the overhead of a sandbox to its scraped metrics
+    pub fn append_overhead(&self, sandbox_id: &str, metrics: &mut PrometheusMetrics) {
+        let Some(stats) = self.read_overhead(sandbox_id) else {
+            debug!(sandbox_id = %sandbox_id, "No overhead cgroup found for sandbox");
+            return;
+        };
+
+        if let Some(cpu) = stats.cpu_seconds_total {
+            metrics.push_sample(
+                "counter",
+                "CPU time of the sandbox's shim and hypervisor on the host",
+                MetricSample::new(OVERHEAD_CPU_METRIC, cpu),
+            );
+        }
+        if let Some(memory) = stats.memory_bytes {
+            metrics.push_sample(
+                "gauge",
+                "Memory of the sandbox's shim and hypervisor on the host",
+                MetricSample::new(OVERHEAD_MEMORY_METRIC, memory as f64),
+            );
+        }
+    }
+}
+
+/// Read `usage_usec` from a cgroup v2 `cpu.stat` file, in seconds
+fn read_cpu_stat_usage(path: &Path) -> Option<f64> {
+    let content = fs::read_to_string(path).ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .and_then(|usec| usec.trim().parse::<u64>().ok())
+        .map(|usec| usec as f64 / 1e6)
+}
+
+/// Read a file holding a single integer
+fn read_u64(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_read_cgroup_v2() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("kata_overhead/sb1");
+        write(&dir.join("cgroup.procs"), "1234\n");
+        write(
+            &dir.join("cpu.stat"),
+            "usage_usec 2500000\nuser_usec 2000000\nsystem_usec 500000\n",
+        );
+        write(&dir.join("memory.current"), "73400320\n");
+
+        let reader = HostCgroupReader::new(root.path());
+        assert_eq!(
+            reader.read_overhead("sb1"),
+            Some(OverheadStats {
+                cpu_seconds_total: Some(2.5),
+                memory_bytes: Some(73400320),
+            })
+        );
+        assert_eq!(reader.read_overhead("missing"), None);
+
+        let mut metrics = PrometheusMetrics::new();
+        reader.append_overhead("sb1", &mut metrics);
+        let cpu = &metrics.metrics["kata_overhead_cpu_seconds"];
+        assert_eq!(cpu.metric_type.as_deref(), Some("counter"));
+        assert_eq!(cpu.samples[0].value, 2.5);
+    }
+
+    #[test]
+    fn test_read_cgroup_v1() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            &root
+                .path()
+                .join("cpu,cpuacct/kata_overhead/sb1/cpuacct.usage"),
+            "1500000000\n",
+        );
+        write(
+            &root
+                .path()
+                .join("memory/kata_overhead/sb1/memory.usage_in_bytes"),
+            "4096\n",
+        );
+
+        let stats = HostCgroupReader::new(root.path())
+            .read_overhead("sb1")
+            .unwrap();
+        assert_eq!(stats.cpu_seconds_total, Some(1.5));
+        assert_eq!(stats.memory_bytes, Some(4096));
+    }
+}
@@ -0,0 +1,268 @@
+//! Hypervisor process - host resource usage of the VMM from /proc
+//!
+//! Responsibilities:
+//! - Discover the Cloud Hypervisor / QEMU PID of each sandbox from the
+//!   runtime persistence files (`persist.json`, falling back to the VM pid file)
+//! - Sample its RSS, CPU time and open fds from `/proc`
+//! - Attach them to the scraped metrics as `kata_vmm_*` families
+//!
+//! This catches VMM memory leaks that are invisible inside the guest. The
+//! VMM runs in the host PID namespace; a kata-pulse container without
+//! `hostPID` (or a host procfs mount) cannot see it, and the sampler is off.
+
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+use crate::config;
+use crate::utils::prometheus_parser::{MetricSample, PrometheusMetrics};
+
+/// Default mount point of the host procfs
+pub const DEFAULT_PROC_ROOT: &str = "/proc";
+
+/// Raw family of the VMM resident set size attached to scraped metrics
+pub const VMM_RSS_METRIC: &str = "kata_vmm_rss_bytes";
+
+/// Raw family of the VMM CPU time attached to scraped metrics
+pub const VMM_CPU_METRIC: &str = "kata_vmm_cpu_seconds_total";
+
+/// Raw family of the VMM open file descriptors attached to scraped metrics
+pub const VMM_FDS_METRIC: &str = "kata_vmm_fds";
+
+/// Subset of the Go runtime's `persist.json` holding the hypervisor PID
+#[derive(Deserialize)]
+struct PersistedSandbox {
+    #[serde(rename = "HypervisorState")]
+    hypervisor_state: PersistedHypervisor,
+}
+
+#[derive(Deserialize)]
+struct PersistedHypervisor {
+    #[serde(rename = "Pid")]
+    pid: i32,
+}
+
+/// Resource usage of a VMM process
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VmmStats {
+    /// Resident set size (in bytes)
+    pub rss_bytes: Option<u64>,
+    /// User + system CPU time in seconds
+    pub cpu_seconds_total: Option<f64>,
+    /// Number of open file descriptors
+    pub fds: Option<u64>,
+}
+
+/// Reads VMM process statistics of sandboxes from the host procfs
+#[derive(Clone, Debug)]
+pub struct HypervisorProcReader {
+    proc_root: PathBuf,
+    sandboxes_path: PathBuf,
+    vm_path: PathBuf,
+    clk_tck: f64,
+}
+
+impl HypervisorProcReader {
+    /// Create a reader for the procfs mounted at `proc_root`
+    pub fn new(proc_root: impl Into<PathBuf>) -> Self {
+        HypervisorProcReader {
+            proc_root: proc_root.into(),
+            sandboxes_path: config::get_sandboxes_storage_path(),
+            vm_path: config::get_vm_storage_path(),
+            clk_tck: host_clk_tck(),
+        }
+    }
+
+    /// Look up persistence files below `sandboxes_path` and `vm_path` instead
+    /// of the runtime defaults
+    #[cfg(test)]
+    fn with_storage_paths(
+        mut self,
+        sandboxes_path: impl Into<PathBuf>,
+        vm_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.sandboxes_path = sandboxes_path.into();
+        self.vm_path = vm_path.into();
+        self
+    }
+
+    /// Whether the procfs shows the processes of the host PID namespace
+    ///
+    /// Only the initial PID namespace has the kernel thread daemon as PID 2.
+    pub fn host_pids_visible(&self) -> bool {
+        fs::read_to_string(self.proc_root.join("2/status")).is_ok_and(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Name:"))
+                .is_some_and(|name| name.trim() == "kthreadd")
+        })
+    }
+
+    /// Find the PID of the hypervisor of a sandbox
+    pub fn find_pid(&self, sandbox_id: &str) -> Option<i32> {
+        let persisted = fs::read(self.sandboxes_path.join(sandbox_id).join("persist.json"))
+            .ok()
+            .and_then(|data| serde_json::from_slice::<PersistedSandbox>(&data).ok())
+            .map(|sandbox| sandbox.hypervisor_state.pid);
+
+        persisted
+            .or_else(|| {
+                fs::read_to_string(self.vm_path.join(sandbox_id).join("pid"))
+                    .ok()
+                    .and_then(|pid| pid.trim().parse().ok())
+            })
+            .filter(|pid| *pid > 0)
+    }
+
+    /// Sample the VMM process of a sandbox
+    ///
+    /// Returns None if the PID is unknown or the process is gone.
+    pub fn read_vmm(&self, sandbox_id: &str) -> Option<VmmStats> {
+        let pid = self.find_pid(sandbox_id)?;
+        let proc_dir = self.proc_root.join(pid.to_string());
+        let stat = fs::read_to_string(proc_dir.join("stat")).ok()?;
+
+        Some(VmmStats {
+            rss_bytes: read_vm_rss(&proc_dir.join("status")),
+            cpu_seconds_total: parse_cpu_ticks(&stat).map(|ticks| ticks as f64 / self.clk_tck),
+            fds: fs::read_dir(proc_dir.join("fd"))
+                .ok()
+                .map(|entries| entries.count() as u64),
+        })
+    }
+
+    /// Attach the VMM usage of a sandbox to its scraped metrics
+    pub fn append_vmm(&self, sandbox_id: &str, metrics: &mut PrometheusMetrics) {
+        let Some(stats) = self.read_vmm(sandbox_id) else {
+            debug!(sandbox_id = %sandbox_id, "No hypervisor process found for sandbox");
+            return;
+        };
+
+        if let Some(rss) = stats.rss_bytes {
+            metrics.push_sample(
+                "gauge",
+                "Resident set size of the sandbox's hypervisor process",
+                MetricSample::new(VMM_RSS_METRIC, rss as f64),
+            );
+        }
+        if let Some(cpu) = stats.cpu_seconds_total {
+            metrics.push_sample(
+                "counter",
+                "CPU time of the sandbox's hypervisor process",
+                MetricSample::new(VMM_CPU_METRIC, cpu),
+            );
+        }
+        if let Some(fds) = stats.fds {
+            metrics.push_sample(
+                "gauge",
+                "Open file descriptors of the sandbox's hypervisor process",
+                MetricSample::new(VMM_FDS_METRIC, fds as f64),
+            );
+        }
+    }
+}
+
+/// Read `VmRSS` from `/proc/<pid>/status`, in bytes
+fn read_vm_rss(path: &Path) -> Option<u64> {
+    let content = fs::read_to_string(path).ok()?;
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| {
+            value
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse::<u64>()
+                .ok()
+        })
+        .map(|kb| kb * 1024)
+}
+
+/// Sum of utime and stime from `/proc/<pid>/stat`, in clock ticks
+fn parse_cpu_ticks(stat: &str) -> Option<u64> {
+    // The command name may contain spaces, so split after its closing parenthesis
+    let rest = &stat[stat.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // Fields after the command start at `state` (field 3); utime/stime are fields 14/15
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// Clock ticks per second of the host
+fn host_clk_tck() -> f64 {
+    let clk_tck = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if clk_tck > 0 {
+        clk_tck as f64
+    } else {
+        100.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn reader(root: &Path) -> HypervisorProcReader {
+        HypervisorProcReader::new(root.join("proc"))
+            .with_storage_paths(root.join("sbs"), root.join("vm"))
+    }
+
+    #[test]
+    fn test_find_pid() {
+        let root = tempfile::tempdir().unwrap();
+        write(
+            &root.path().join("sbs/sb1/persist.json"),
+            r#"{"HypervisorState":{"Pid":4242,"Type":"clh"},"State":{"State":"running"}}"#,
+        );
+        write(&root.path().join("vm/sb2/pid"), "5151\n");
+
+        let reader = reader(root.path());
+        assert!(!reader.host_pids_visible());
+        write(
+            &root.path().join("proc/2/status"),
+            "Name:\tkthreadd\nState:\tS (sleeping)\n",
+        );
+        assert!(reader.host_pids_visible());
+        assert_eq!(reader.find_pid("sb1"), Some(4242));
+        assert_eq!(reader.find_pid("sb2"), Some(5151));
+        assert_eq!(reader.find_pid("sb3"), None);
+    }
+
+    #[test]
+    fn test_read_vmm() {
+        let root = tempfile::tempdir().unwrap();
+        write(&root.path().join("vm/sb1/pid"), "4242");
+        let proc_dir = root.path().join("proc/4242");
+        write(
+            &proc_dir.join("stat"),
+            "4242 (cloud hypervisor) S 1 4242 4242 0 -1 4194560 1000 0 0 0 250 150 0 0 20 0 8 0 100 0 0",
+        );
+        write(
+            &proc_dir.join("status"),
+            "Name:\tcloud-hypervisor\nVmRSS:\t  204800 kB\nThreads:\t8\n",
+        );
+        fs::create_dir_all(proc_dir.join("fd")).unwrap();
+        for fd in ["0", "1", "2"] {
+            fs::write(proc_dir.join("fd").join(fd), "").unwrap();
+        }
+
+        let reader = reader(root.path());
+        let stats = reader.read_vmm("sb1").unwrap();
+        assert_eq!(stats.rss_bytes, Some(204800 * 1024));
+        assert_eq!(stats.fds, Some(3));
+        assert_eq!(stats.cpu_seconds_total, Some(400.0 / reader.clk_tck));
+
+        let mut metrics = PrometheusMetrics::new();
+        reader.append_vmm("sb1", &mut metrics);
+        assert_eq!(metrics.metrics["kata_vmm_fds"].samples[0].value, 3.0);
+        assert!(metrics.metrics.contains_key("kata_vmm_cpu_seconds"));
+    }
+}
@@ -0,0 +1,341 @@
+//! Janitor - stale sandbox directories left behind by the runtime
+//!
+//! Responsibilities:
+//! - Detect tracked sandboxes whose shim is gone (no monitor socket, or
+//!   nothing listening on it) and whose pod the CRI runtime no longer
+//!   reports, for longer than the grace period
+//! - Report them in `/debug/state` and as internal metrics
+//! - Remove their directories if enabled (a dry run by default), so leaked
+//!   directories stop being collected forever
+//!
+//! A sandbox only counts as gone from CRI after a successful metadata sync
+//! missed it, so an unreachable CRI runtime never makes sandboxes stale.
+
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::UnixStream;
+use tokio::time::{sleep, timeout};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::pipeline_health::unix_now_ms;
+use super::sandbox_cache::SandboxCache;
+use crate::config;
+
+/// Default time a sandbox must be stale before it is reported
+pub const DEFAULT_JANITOR_GRACE_PERIOD: &str = "10m";
+
+/// Time between two sweeps of the tracked sandboxes
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time a shim gets to accept a connection on its monitor socket
+const SHIM_CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Settings of the janitor
+#[derive(Clone, Debug, PartialEq)]
+pub struct JanitorConfig {
+    /// Time a sandbox must be stale before it is reported (and removed)
+    pub grace_period: Duration,
+    /// Remove the directories of stale sandboxes instead of only reporting them
+    pub remove: bool,
+}
+
+impl JanitorConfig {
+    /// Report sandboxes stale for 10 minutes, without removing them
+    pub fn new() -> Self {
+        JanitorConfig {
+            grace_period: Duration::from_secs(600),
+            remove: false,
+        }
+    }
+
+    /// Set the time a sandbox must be stale before it is reported
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// Remove the directories of stale sandboxes
+    pub fn with_remove(mut self, remove: bool) -> Self {
+        self.remove = remove;
+        self
+    }
+}
+
+impl Default for JanitorConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A sandbox stale for longer than the grace period
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StaleSandbox {
+    pub sandbox_id: String,
+    /// Pod of the sandbox, as last reported by CRI (empty if never synced)
+    pub pod: String,
+    pub namespace: String,
+    /// When the sandbox was first seen stale (ms since the Unix epoch)
+    pub stale_since_unix_ms: u64,
+}
+
+/// Finds (and optionally removes) stale sandbox directories
+pub struct Janitor {
+    config: JanitorConfig,
+    sandbox_cache: Arc<SandboxCache>,
+    /// Directories holding the sandbox directories (Go and Rust runtimes)
+    sandbox_dirs: Vec<PathBuf>,
+    /// When each currently suspect sandbox was first seen stale
+    suspects: Mutex<HashMap<String, (Instant, u64)>>,
+    /// Sandboxes stale for longer than the grace period, as of the last sweep
+    stale: Mutex<Vec<StaleSandbox>>,
+    removed: AtomicU64,
+    failed_removals: AtomicU64,
+}
+
+impl Janitor {
+    /// Create a janitor of the sandboxes of `sandbox_cache`
+    pub fn new(config: JanitorConfig, sandbox_cache: Arc<SandboxCache>) -> Self {
+        Janitor {
+            config,
+            sandbox_cache,
+            sandbox_dirs: vec![
+                crate::config::get_sandboxes_storage_path(),
+                crate::config::get_sandboxes_storage_path_rust(),
+            ],
+            suspects: Mutex::new(HashMap::new()),
+            stale: Mutex::new(Vec::new()),
+            removed: AtomicU64::new(0),
+            failed_removals: AtomicU64::new(0),
+        }
+    }
+
+    /// The settings of the janitor
+    pub fn config(&self) -> &JanitorConfig {
+        &self.config
+    }
+
+    /// Sweep the tracked sandboxes every minute until `cancel` fires
+    pub async fn start(&self, cancel: CancellationToken) {
+        info!(
+            grace_period = ?self.config.grace_period,
+            remove = self.config.remove,
+            "Starting the stale sandbox janitor"
+        );
+        loop {
+            self.sweep(Instant::now()).await;
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = sleep(SWEEP_INTERVAL) => {}
+            }
+        }
+    }
+
+    /// Sandboxes stale for longer than the grace period, as of the last sweep
+    pub fn stale(&self) -> Vec<StaleSandbox> {
+        self.stale.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Find the stale sandboxes, removing their directories if enabled
+    async fn sweep(&self, now: Instant) {
+        let mut suspect = Vec::new();
+        for (id, metadata) in self.sandbox_cache.get_sandboxes_with_metadata().await {
+            if self.sandbox_cache.metadata_misses(&id).await == 0 || self.shim_alive(&id).await {
+                continue;
+            }
+            suspect.push((id, metadata));
+        }
+
+        let mut stale = Vec::new();
+        {
+            let mut suspects = self.suspects.lock().unwrap_or_else(|e| e.into_inner());
+            let current: HashSet<&str> = suspect.iter().map(|(id, _)| id.as_str()).collect();
+            suspects.retain(|id, _| current.contains(id.as_str()));
+            for (id, metadata) in suspect {
+                let (since, since_unix_ms) = *suspects
+                    .entry(id.clone())
+                    .or_insert_with(|| (now, unix_now_ms()));
+                if now.saturating_duration_since(since) < self.config.grace_period {
+                    debug!(sandbox_id = %id, "Sandbox shim and pod are gone, within the grace period");
+                    continue;
+                }
+                stale.push(StaleSandbox {
+                    sandbox_id: id,
+                    pod: metadata.name,
+                    namespace: metadata.namespace,
+                    stale_since_unix_ms: since_unix_ms,
+                });
+            }
+        }
+
+        // Dry runs report each stale sandbox once
+        let reported: HashSet<String> = self
+            .stale()
+            .into_iter()
+            .map(|sandbox| sandbox.sandbox_id)
+            .collect();
+        for sandbox in &stale {
+            if self.config.remove {
+                self.remove(sandbox).await;
+            } else if !reported.contains(&sandbox.sandbox_id) {
+                warn!(
+                    sandbox_id = %sandbox.sandbox_id,
+                    pod = %sandbox.pod,
+                    namespace = %sandbox.namespace,
+                    "Stale sandbox directory: shim and pod are gone (dry run, not removed)"
+                );
+            }
+        }
+        stale.sort_by(|a, b| a.sandbox_id.cmp(&b.sandbox_id));
+        *self.stale.lock().unwrap_or_else(|e| e.into_inner()) = stale;
+    }
+
+    /// Whether the shim of a sandbox accepts connections on its monitor socket
+    async fn shim_alive(&self, id: &str) -> bool {
+        for dir in &self.sandbox_dirs {
+            let socket = config::socket_path(id, dir);
+            if !socket.exists() {
+                continue;
+            }
+            if let Ok(Ok(_)) = timeout(SHIM_CONNECT_TIMEOUT, UnixStream::connect(&socket)).await {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Remove the directories of a stale sandbox
+    ///
+    /// The sandbox cache manager then sees the directory gone and drops the sandbox.
+    async fn remove(&self, sandbox: &StaleSandbox) {
+        for dir in &self.sandbox_dirs {
+            let path = dir.join(&sandbox.sandbox_id);
+            if !path.is_dir() {
+                continue;
+            }
+            match tokio::fs::remove_dir_all(&path).await {
+                Ok(()) => {
+                    warn!(
+                        sandbox_id = %sandbox.sandbox_id,
+                        pod = %sandbox.pod,
+                        namespace = %sandbox.namespace,
+                        path = %path.display(),
+                        "Removed stale sandbox directory: shim and pod are gone"
+                    );
+                    self.removed.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    warn!(sandbox_id = %sandbox.sandbox_id, path = %path.display(), error = %e, "Failed to remove stale sandbox directory");
+                    self.failed_removals.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Render the janitor state in Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        let mut output = String::new();
+        output.push_str("# HELP kata_pulse_stale_sandboxes Sandbox directories whose shim and pod are gone for longer than the grace period\n");
+        output.push_str("# TYPE kata_pulse_stale_sandboxes gauge\n");
+        output.push_str(&format!(
+            "kata_pulse_stale_sandboxes {}\n",
+            self.stale.lock().unwrap_or_else(|e| e.into_inner()).len()
+        ));
+        output.push_str("# HELP kata_pulse_janitor_removals_total Removals of stale sandbox directories by result\n");
+        output.push_str("# TYPE kata_pulse_janitor_removals_total counter\n");
+        output.push_str(&format!(
+            "kata_pulse_janitor_removals_total{{result=\"removed\"}} {}\n",
+            self.removed.load(Ordering::Relaxed)
+        ));
+        output.push_str(&format!(
+            "kata_pulse_janitor_removals_total{{result=\"failed\"}} {}\n",
+            self.failed_removals.load(Ordering::Relaxed)
+        ));
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::sandbox_cache::SandboxCRIMetadata;
+
+    /// A janitor over sandbox directories in `root`, tracking `ids`
+    async fn janitor(root: &std::path::Path, config: JanitorConfig, ids: &[&str]) -> Janitor {
+        let sandbox_cache = Arc::new(SandboxCache::new());
+        for id in ids {
+            std::fs::create_dir(root.join(id)).unwrap();
+            sandbox_cache
+                .put_if_not_exists(id, SandboxCRIMetadata::default())
+                .await;
+        }
+        Janitor {
+            sandbox_dirs: vec![root.to_path_buf()],
+            ..Janitor::new(config, sandbox_cache)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stale_after_grace_period() {
+        let root = tempfile::tempdir().unwrap();
+        let janitor = janitor(
+            root.path(),
+            JanitorConfig::new(),
+            &["gone", "running", "in-cri"],
+        )
+        .await;
+        let _listener =
+            std::os::unix::net::UnixListener::bind(config::socket_path("running", root.path()))
+                .unwrap();
+        for id in ["gone", "running"] {
+            janitor.sandbox_cache.record_metadata_miss(id, 0).await;
+        }
+
+        let start = Instant::now();
+        janitor.sweep(start).await;
+        assert!(janitor.stale().is_empty());
+
+        janitor.sweep(start + Duration::from_secs(601)).await;
+        let stale = janitor.stale();
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].sandbox_id, "gone");
+        // A dry run keeps the directory
+        assert!(root.path().join("gone").is_dir());
+        assert!(janitor
+            .render_metrics()
+            .contains("kata_pulse_stale_sandboxes 1\n"));
+
+        // Reported by CRI again: no longer stale
+        janitor
+            .sandbox_cache
+            .set_cri_metadata("gone", SandboxCRIMetadata::default())
+            .await;
+        janitor.sweep(start + Duration::from_secs(602)).await;
+        assert!(janitor.stale().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remove_stale_sandbox() {
+        let root = tempfile::tempdir().unwrap();
+        let janitor_config = JanitorConfig::new()
+            .with_grace_period(Duration::ZERO)
+            .with_remove(true);
+        let janitor = janitor(root.path(), janitor_config, &["gone"]).await;
+        // A leftover socket with no shim listening
+        drop(
+            std::os::unix::net::UnixListener::bind(config::socket_path("gone", root.path()))
+                .unwrap(),
+        );
+        janitor.sandbox_cache.record_metadata_miss("gone", 0).await;
+
+        janitor.sweep(Instant::now()).await;
+        assert!(!root.path().join("gone").exists());
+        assert!(janitor
+            .render_metrics()
+            .contains("kata_pulse_janitor_removals_total{result=\"removed\"} 1\n"));
+    }
+}
@@ -0,0 +1,353 @@
+//! Kubernetes pod watch - pod labels beyond what CRI exposes
+//!
+//! Responsibilities:
+//! - Watch the pods of the local node through the Kubernetes API, with the
+//!   in-cluster service account
+//! - Keep their selected labels (e.g. `app`, `team`) in the sandbox cache by
+//!   pod UID, so they are added to the converted series of their sandboxes
+//! - Resolve the owner chain of each pod (ReplicaSet to Deployment, Job to
+//!   CronJob) to its workload, added as `workload`/`workload_kind`
+//! - Forget the labels of deleted pods, and of pods gone during a relist
+//!
+//! Requires the `k8s` feature; without it enabling the watch fails at startup.
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+
+use super::sandbox_cache::SandboxCache;
+
+/// Pod labels added to the series by default
+pub const DEFAULT_POD_LABELS: [&str; 2] = ["app", "team"];
+
+/// Settings of the pod watch
+#[derive(Clone, Debug, PartialEq)]
+pub struct PodWatchConfig {
+    /// Node whose pods are watched (`spec.nodeName`)
+    pub node_name: String,
+    /// Keys of the pod labels kept
+    pub labels: Vec<String>,
+}
+
+impl PodWatchConfig {
+    /// Watch the pods of `node_name`, keeping the default labels
+    pub fn new(node_name: impl Into<String>) -> Self {
+        PodWatchConfig {
+            node_name: node_name.into(),
+            labels: DEFAULT_POD_LABELS.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Keep the pod labels with these keys
+    pub fn with_labels(mut self, labels: Vec<String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// The labels of a pod kept by this watch
+    pub fn select(&self, labels: &BTreeMap<String, String>) -> BTreeMap<String, String> {
+        self.labels
+            .iter()
+            .filter_map(|key| Some((key.clone(), labels.get(key)?.clone())))
+            .collect()
+    }
+}
+
+/// Watches the pods of the node and caches their selected labels
+pub struct PodWatcher {
+    config: PodWatchConfig,
+    #[cfg_attr(not(feature = "k8s"), allow(dead_code))]
+    sandbox_cache: Arc<SandboxCache>,
+}
+
+impl PodWatcher {
+    /// Create a pod watcher filling `sandbox_cache`
+    pub fn new(config: PodWatchConfig, sandbox_cache: Arc<SandboxCache>) -> Self {
+        PodWatcher {
+            config,
+            sandbox_cache,
+        }
+    }
+
+    /// The settings of the watch
+    pub fn config(&self) -> &PodWatchConfig {
+        &self.config
+    }
+
+    /// Watch the pods until `cancel` fires
+    ///
+    /// Failed watches are restarted with backoff; only a missing in-cluster
+    /// configuration is returned as an error.
+    #[cfg(feature = "k8s")]
+    pub async fn start(&self, cancel: CancellationToken) -> Result<()> {
+        watch::run(self, cancel).await
+    }
+
+    /// Watch the pods until `cancel` fires
+    #[cfg(not(feature = "k8s"))]
+    pub async fn start(&self, _cancel: CancellationToken) -> Result<()> {
+        anyhow::bail!(
+            "the Kubernetes pod watch of node {} requires kata-pulse to be built with the `k8s` feature",
+            self.config.node_name
+        )
+    }
+}
+
+#[cfg(feature = "k8s")]
+mod watch {
+    use anyhow::{Context, Result};
+    use futures::StreamExt;
+    use k8s_openapi::api::apps::v1::ReplicaSet;
+    use k8s_openapi::api::batch::v1::Job;
+    use k8s_openapi::api::core::v1::Pod;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
+    use kube::runtime::watcher::{self, Event};
+    use kube::runtime::WatchStreamExt;
+    use kube::{Api, Client, Config};
+    use std::collections::{HashMap, HashSet};
+    use tokio_util::sync::CancellationToken;
+    use tracing::{debug, info, warn};
+
+    use super::PodWatcher;
+    use crate::monitor::sandbox_cache::{K8sPodInfo, Workload};
+
+    /// Longest owner chain followed from a pod
+    const MAX_OWNER_DEPTH: usize = 4;
+
+    /// Number of resolved owners kept before the cache is cleared
+    const MAX_CACHED_OWNERS: usize = 4096;
+
+    /// Whether pods owned by a controller of `kind` belong to the controller's own owner
+    ///
+    /// ReplicaSets are managed by Deployments and Jobs by CronJobs; a
+    /// ReplicaSet or Job without a controller is the workload itself.
+    fn is_intermediate_owner(kind: &str) -> bool {
+        matches!(kind, "ReplicaSet" | "Job")
+    }
+
+    pub(super) async fn run(pod_watcher: &PodWatcher, cancel: CancellationToken) -> Result<()> {
+        let config = Config::incluster()
+            .context("failed to load the in-cluster Kubernetes configuration")?;
+        let client = Client::try_from(config).context("failed to create the Kubernetes client")?;
+        let node_name = &pod_watcher.config.node_name;
+        let api: Api<Pod> = Api::all(client.clone());
+        let watch_config =
+            watcher::Config::default().fields(&format!("spec.nodeName={}", node_name));
+        let mut events = watcher::watcher(api, watch_config)
+            .default_backoff()
+            .boxed();
+        info!(node = %node_name, labels = ?pod_watcher.config.labels, "Watching the pods of the node");
+
+        let cache = &pod_watcher.sandbox_cache;
+        let mut owners = OwnerResolver::new(client);
+        let mut relisted = HashSet::new();
+        loop {
+            let event = tokio::select! {
+                _ = cancel.cancelled() => return Ok(()),
+                event = events.next() => event,
+            };
+            match event {
+                Some(Ok(Event::Init)) => relisted.clear(),
+                Some(Ok(Event::InitApply(pod))) => {
+                    if let Some(uid) = apply(pod_watcher, &mut owners, pod).await {
+                        relisted.insert(uid);
+                    }
+                }
+                Some(Ok(Event::InitDone)) => {
+                    cache.retain_pod_info(&relisted).await;
+                    info!(pods = relisted.len(), "Listed the pods of the node");
+                }
+                Some(Ok(Event::Apply(pod))) => {
+                    apply(pod_watcher, &mut owners, pod).await;
+                }
+                Some(Ok(Event::Delete(pod))) => {
+                    if let Some(uid) = pod.metadata.uid {
+                        debug!(pod_uid = %uid, "Pod deleted");
+                        cache.remove_pod_info(&uid).await;
+                    }
+                }
+                Some(Err(e)) => warn!(error = %e, "Kubernetes pod watch failed, retrying"),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Cache the selected labels and the workload of a pod, returning its UID
+    async fn apply(
+        pod_watcher: &PodWatcher,
+        owners: &mut OwnerResolver,
+        pod: Pod,
+    ) -> Option<String> {
+        let uid = pod.metadata.uid?;
+        let namespace = pod.metadata.namespace.unwrap_or_default();
+        let workload = match controller_of(pod.metadata.owner_references.as_deref()) {
+            Some(owner) => Some(owners.workload(&namespace, owner).await),
+            None => None,
+        };
+        let labels = pod_watcher
+            .config
+            .select(&pod.metadata.labels.unwrap_or_default());
+        pod_watcher
+            .sandbox_cache
+            .set_pod_info(&uid, K8sPodInfo { labels, workload })
+            .await;
+        Some(uid)
+    }
+
+    /// The controller among owner references
+    fn controller_of(owners: Option<&[OwnerReference]>) -> Option<Workload> {
+        owners
+            .unwrap_or_default()
+            .iter()
+            .find(|owner| owner.controller == Some(true))
+            .map(|owner| Workload {
+                kind: owner.kind.clone(),
+                name: owner.name.clone(),
+            })
+    }
+
+    /// Follows owner chains through the Kubernetes API, caching the answers
+    struct OwnerResolver {
+        client: Client,
+        /// Controller of each looked up (kind, namespace, name); None if it has none
+        parents: HashMap<(String, String, String), Option<Workload>>,
+        /// Whether a failed lookup was logged as a warning already
+        warned: bool,
+    }
+
+    impl OwnerResolver {
+        fn new(client: Client) -> Self {
+            OwnerResolver {
+                client,
+                parents: HashMap::new(),
+                warned: false,
+            }
+        }
+
+        /// The workload of a pod whose controller is `owner`
+        ///
+        /// An owner that cannot be looked up is taken as the workload.
+        async fn workload(&mut self, namespace: &str, mut owner: Workload) -> Workload {
+            for _ in 0..MAX_OWNER_DEPTH {
+                if !is_intermediate_owner(&owner.kind) {
+                    break;
+                }
+                match self.parent(namespace, &owner).await {
+                    Some(parent) => owner = parent,
+                    None => break,
+                }
+            }
+            owner
+        }
+
+        /// The controller of an owner, looked up once
+        async fn parent(&mut self, namespace: &str, owner: &Workload) -> Option<Workload> {
+            let key = (
+                owner.kind.clone(),
+                namespace.to_string(),
+                owner.name.clone(),
+            );
+            if let Some(parent) = self.parents.get(&key) {
+                return parent.clone();
+            }
+            let owners = match owner.kind.as_str() {
+                "ReplicaSet" => Api::<ReplicaSet>::namespaced(self.client.clone(), namespace)
+                    .get_opt(&owner.name)
+                    .await
+                    .map(|rs| rs.and_then(|rs| rs.metadata.owner_references)),
+                "Job" => Api::<Job>::namespaced(self.client.clone(), namespace)
+                    .get_opt(&owner.name)
+                    .await
+                    .map(|job| job.and_then(|job| job.metadata.owner_references)),
+                _ => return None,
+            };
+            let parent = match owners {
+                Ok(owners) => controller_of(owners.as_deref()),
+                Err(e) => {
+                    // Not cached: retried on the next event of a pod it owns
+                    if std::mem::replace(&mut self.warned, true) {
+                        debug!(kind = %owner.kind, name = %owner.name, error = %e, "Failed to look up pod owner");
+                    } else {
+                        warn!(kind = %owner.kind, name = %owner.name, error = %e, "Failed to look up pod owner, using it as the workload (missing RBAC?)");
+                    }
+                    return None;
+                }
+            };
+            if self.parents.len() >= MAX_CACHED_OWNERS {
+                self.parents.clear();
+            }
+            self.parents.insert(key, parent.clone());
+            parent
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn owner(kind: &str, name: &str, controller: bool) -> OwnerReference {
+            OwnerReference {
+                kind: kind.to_string(),
+                name: name.to_string(),
+                controller: Some(controller),
+                ..Default::default()
+            }
+        }
+
+        #[test]
+        fn test_controller_of() {
+            let owners = [
+                owner("Node", "worker-1", false),
+                owner("ReplicaSet", "checkout-7d9f8b6c5d", true),
+            ];
+            assert_eq!(
+                controller_of(Some(&owners)),
+                Some(Workload {
+                    kind: "ReplicaSet".to_string(),
+                    name: "checkout-7d9f8b6c5d".to_string(),
+                })
+            );
+            assert_eq!(controller_of(Some(&owners[..1])), None);
+            assert_eq!(controller_of(None), None);
+            assert!(is_intermediate_owner("ReplicaSet"));
+            assert!(is_intermediate_owner("Job"));
+            assert!(!is_intermediate_owner("StatefulSet"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_labels() {
+        let labels: BTreeMap<String, String> = [
+            ("app", "checkout"),
+            ("team", "payments"),
+            ("pod-template-hash", "7d9f8b6c5d"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+
+        let config = PodWatchConfig::new("worker-1");
+        assert_eq!(config.select(&labels).len(), 2);
+        let config = config.with_labels(vec!["team".to_string(), "tier".to_string()]);
+        assert_eq!(
+            config.select(&labels),
+            [("team".to_string(), "payments".to_string())].into()
+        );
+    }
+
+    #[cfg(not(feature = "k8s"))]
+    #[tokio::test]
+    async fn test_requires_feature() {
+        let watcher = PodWatcher::new(
+            PodWatchConfig::new("worker-1"),
+            Arc::new(SandboxCache::new()),
+        );
+        assert!(watcher.start(CancellationToken::new()).await.is_err());
+    }
+}
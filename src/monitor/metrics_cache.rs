@@ -1,14 +1,118 @@
+use super::sandbox_cache::SandboxCRIMetadata;
+use crate::config::reload::ReloadableConfig;
+use crate::utils::metrics_converter::cadvisor::PrometheusFormat;
+use crate::utils::metrics_converter::config::EnrichedLabels;
+use crate::utils::metrics_converter::create_converter;
 use crate::utils::prometheus_parser::PrometheusMetrics;
-use std::collections::HashMap;
+use anyhow::Result;
+use arc_swap::ArcSwap;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::Mutex;
-use tracing::debug;
+use tracing::{debug, info_span};
 
 /// Cached metrics for a single sandbox
+///
+/// The parsed metrics are shared behind an Arc so carrying them over between
+/// collection cycles and handing them to readers never deep-copies them.
 #[derive(Clone, Debug)]
 pub struct CachedMetrics {
     /// The parsed metrics
-    pub metrics: PrometheusMetrics,
+    pub metrics: Arc<PrometheusMetrics>,
+    /// When the metrics were scraped from the shim
+    pub collected_at: Instant,
+    /// Converted output of the metrics, shared by all clones of the entry
+    converted: Arc<std::sync::Mutex<Option<ConvertedOutput>>>,
+}
+
+/// Memoized cAdvisor output of a scrape, with the inputs it was converted with
+#[derive(Debug)]
+struct ConvertedOutput {
+    /// Settings the output was converted with (compared by identity)
+    config: Arc<ReloadableConfig>,
+    /// Pod labels (and guest CLK_TCK) of the sandbox at conversion time
+    labels: EnrichedLabels,
+    /// The cAdvisor exposition text
+    text: Arc<str>,
+}
+
+impl CachedMetrics {
+    /// Wrap metrics scraped at `collected_at`
+    pub fn new(metrics: Arc<PrometheusMetrics>, collected_at: Instant) -> Self {
+        CachedMetrics {
+            metrics,
+            collected_at,
+            converted: Arc::default(),
+        }
+    }
+
+    /// cAdvisor exposition text of the metrics
+    ///
+    /// The metrics of an entry never change, so the output is converted once
+    /// and reused by every render until the settings are reloaded or the pod
+    /// metadata of the sandbox changes. `labels` are the pod labels of the
+    /// sandbox, resolved by the caller.
+    pub fn converted(
+        &self,
+        config: &Arc<ReloadableConfig>,
+        labels: EnrichedLabels,
+        sandbox_id: &str,
+    ) -> Result<Arc<str>> {
+        let mut converted = self.converted.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(output) = converted
+            .as_ref()
+            .filter(|output| Arc::ptr_eq(&output.config, config) && output.labels == labels)
+        {
+            return Ok(output.text.clone());
+        }
+
+        let converter = create_converter(config.conversion.clone(), labels.clone());
+        let cadvisor_metrics = info_span!("convert", sandbox_id = %sandbox_id)
+            .in_scope(|| converter.convert_all(&self.metrics))?;
+        let text: Arc<str> = cadvisor_metrics
+            .to_prometheus_format(Some(sandbox_id))
+            .into();
+        *converted = Some(ConvertedOutput {
+            config: config.clone(),
+            labels,
+            text: text.clone(),
+        });
+        Ok(text)
+    }
+
+    /// Age of the metrics since they were scraped
+    pub fn age(&self) -> Duration {
+        self.collected_at.elapsed()
+    }
+
+    /// Wall-clock collection time as a duration since the Unix epoch
+    pub fn collected_at_unix(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .saturating_sub(self.age())
+    }
+}
+
+/// Number of collection intervals a removed sandbox is reported as down
+const TOMBSTONE_REPORT_INTERVALS: u32 = 2;
+
+/// Time a tombstone is kept, and the longest it is reported
+const TOMBSTONE_MAX_AGE: Duration = Duration::from_secs(600);
+
+/// Record of a recently removed sandbox
+///
+/// Keeps late scrape results of the sandbox from being published again, and lets
+/// the renders of the next intervals report the sandbox as down instead of
+/// silently dropping it.
+#[derive(Clone, Debug)]
+pub struct Tombstone {
+    /// CRI metadata of the sandbox at removal time (pod/namespace labels)
+    pub metadata: SandboxCRIMetadata,
+    /// When the sandbox was removed
+    pub removed_at: Instant,
 }
 
 /// Double-buffered cache for metrics from all sandboxes
@@ -19,45 +123,136 @@ pub struct CachedMetrics {
 /// - After collection completes, buffers are swapped atomically
 ///
 /// Benefits:
-/// - Readers are NEVER blocked by writers
+/// - Readers are NEVER blocked by writers or by each other (lock-free load)
 /// - Writers don't block readers
 /// - Atomic all-or-nothing consistency
 /// - Better cache locality
 #[derive(Clone)]
 pub struct MetricsCache {
     /// Current buffer - readers read from here (HTTP requests)
-    current_cache: Arc<Mutex<Arc<HashMap<String, CachedMetrics>>>>,
+    current_cache: Arc<ArcSwap<HashMap<String, CachedMetrics>>>,
     /// Staging buffer - writer builds here during collection
     staging_cache: Arc<Mutex<HashMap<String, CachedMetrics>>>,
+    /// Maximum age of metrics that are still served (None = no limit)
+    ttl: Option<Duration>,
+    /// Last scrapes per sandbox, oldest first (bounded by `history_len`)
+    history: Arc<Mutex<HashMap<String, VecDeque<CachedMetrics>>>>,
+    /// Number of scrapes kept per sandbox in the history (0 = disabled)
+    history_len: usize,
+    /// Recently removed sandboxes
+    tombstones: Arc<Mutex<HashMap<String, Tombstone>>>,
+    /// Wall-clock time of the last buffer swap in ms since the epoch (0 = none yet)
+    last_collection_ms: Arc<AtomicU64>,
 }
 
 impl MetricsCache {
     /// Create a new empty double-buffered metrics cache
     pub fn new() -> Self {
         MetricsCache {
-            current_cache: Arc::new(Mutex::new(Arc::new(HashMap::new()))),
+            current_cache: Arc::new(ArcSwap::from_pointee(HashMap::new())),
             staging_cache: Arc::new(Mutex::new(HashMap::new())),
+            ttl: None,
+            history: Arc::new(Mutex::new(HashMap::new())),
+            history_len: 0,
+            tombstones: Arc::new(Mutex::new(HashMap::new())),
+            last_collection_ms: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Create a metrics cache that stops serving entries older than `ttl`
+    ///
+    /// Protects against serving frozen numbers forever when a shim hangs
+    /// or the collector stops making progress.
+    pub fn with_ttl(ttl: Duration) -> Self {
+        MetricsCache {
+            ttl: Some(ttl),
+            ..Self::new()
         }
     }
 
+    /// Keep the last `len` scrapes of every sandbox in an in-memory history
+    pub fn with_history(mut self, len: usize) -> Self {
+        self.history_len = len;
+        self
+    }
+
+    /// Maximum age of metrics that are still served (None = no limit)
+    pub fn ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    /// Check whether cached metrics are past the configured TTL
+    pub fn is_stale(&self, cached: &CachedMetrics) -> bool {
+        self.ttl.is_some_and(|ttl| cached.age() > ttl)
+    }
+
     /// Get cached metrics for a sandbox (reader - NEVER blocked by writers)
     ///
     /// This is fast because:
-    /// 1. Only takes a Mutex lock on current_cache (very brief - just clones Arc)
+    /// 1. Only loads the current_cache pointer (lock-free, no contention between readers)
     /// 2. Never blocked by metrics collection (which writes to staging_cache)
+    ///
+    /// Entries older than the TTL are treated as missing.
     pub async fn get_metrics(&self, sandbox_id: &str) -> Option<CachedMetrics> {
-        let current = self.current_cache.lock().await;
-        current.get(sandbox_id).cloned()
+        let cached = self.current_cache.load().get(sandbox_id).cloned()?;
+
+        if self.is_stale(&cached) {
+            debug!(
+                sandbox_id = %sandbox_id,
+                age_secs = cached.age().as_secs(),
+                "Cached metrics exceeded TTL, not serving them"
+            );
+            return None;
+        }
+        Some(cached)
     }
 
     /// Store a single metric in staging cache (internal use only)
     /// Used by metrics collection to build up new metrics
     async fn set_metrics_staging(&self, sandbox_id: String, metrics: PrometheusMetrics) {
-        let cached = CachedMetrics { metrics };
+        // A scrape that was in flight while the sandbox was removed
+        if self.tombstones.lock().await.contains_key(&sandbox_id) {
+            debug!(sandbox_id = %sandbox_id, "Dropping metrics of removed sandbox");
+            return;
+        }
+        let cached = CachedMetrics::new(Arc::new(metrics), Instant::now());
+        self.record_history(&sandbox_id, &cached).await;
         let mut staging = self.staging_cache.lock().await;
         staging.insert(sandbox_id, cached);
     }
 
+    /// Append a fresh scrape to the history ring buffer of the sandbox
+    async fn record_history(&self, sandbox_id: &str, cached: &CachedMetrics) {
+        if self.history_len == 0 {
+            return;
+        }
+        let mut history = self.history.lock().await;
+        let entries = history.entry(sandbox_id.to_string()).or_default();
+        if entries.len() >= self.history_len {
+            entries.pop_front();
+        }
+        entries.push_back(cached.clone());
+    }
+
+    /// Get the recorded scrapes of a sandbox within `window`, oldest first
+    ///
+    /// Returns None if there is no history for the sandbox.
+    pub async fn get_history(
+        &self,
+        sandbox_id: &str,
+        window: Option<Duration>,
+    ) -> Option<Vec<CachedMetrics>> {
+        let history = self.history.lock().await;
+        let entries = history.get(sandbox_id)?;
+        Some(
+            entries
+                .iter()
+                .filter(|cached| window.is_none_or(|window| cached.age() <= window))
+                .cloned()
+                .collect(),
+        )
+    }
+
     /// Start a new metrics collection cycle
     ///
     /// Call this when starting to collect metrics from all sandboxes
@@ -74,13 +269,37 @@ impl MetricsCache {
         self.set_metrics_staging(sandbox_id, metrics).await;
     }
 
+    /// Carry the currently published metrics of a sandbox over into staging
+    ///
+    /// Used for sandboxes that are not due in this collection cycle (per-sandbox
+    /// intervals), so their last metrics survive the buffer swap.
+    /// The original collection timestamp is kept, and entries past the TTL are
+    /// evicted instead of being carried over.
+    /// Returns true if there was anything to carry over.
+    pub async fn carry_over(&self, sandbox_id: &str) -> bool {
+        let cached = self
+            .current_cache
+            .load()
+            .get(sandbox_id)
+            .cloned()
+            .filter(|cached| !self.is_stale(cached));
+
+        match cached {
+            Some(cached) => {
+                let mut staging = self.staging_cache.lock().await;
+                staging.insert(sandbox_id.to_string(), cached);
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Finish collection and swap buffers atomically
     ///
     /// This is the critical section - it:
     /// 1. Takes staging_cache lock (to finalize collection)
-    /// 2. Takes current_cache lock (to swap - VERY BRIEF)
-    /// 3. Performs atomic swap
-    /// 4. Clears staging for next cycle
+    /// 2. Publishes the new buffer with an atomic pointer store
+    /// 3. Clears staging for next cycle
     ///
     /// The swap is atomic and happens in <1 microsecond
     pub async fn finish_collection(&self) {
@@ -88,38 +307,164 @@ impl MetricsCache {
 
         // Prepare the new data
         let mut staging = self.staging_cache.lock().await;
-        let new_data = std::mem::take(&mut *staging);
+        let mut new_data = std::mem::take(&mut *staging);
+
+        // Drop sandboxes removed while the cycle was running; the tombstones
+        // stay locked until the swap so a concurrent delete is not undone
+        let tombstones = self.tombstones.lock().await;
+        new_data.retain(|sandbox_id, _| !tombstones.contains_key(sandbox_id));
 
         // The actual atomic swap (very fast - just updates Arc pointer)
-        {
-            let mut current = self.current_cache.lock().await;
-            *current = Arc::new(new_data);
-            debug!("Metrics buffers swapped - staging cache cleared");
-        }
+        self.current_cache.store(Arc::new(new_data));
+        drop(tombstones);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.last_collection_ms
+            .store(now.as_millis() as u64, Ordering::Relaxed);
+        debug!("Metrics buffers swapped - staging cache cleared");
 
         // staging is now empty, ready for next collection cycle
     }
 
-    /// Remove metrics for a sandbox (when sandbox is deleted)
+    /// Wall-clock time of the last buffer swap, as a duration since the Unix epoch
     ///
-    /// This updates the current cache immediately since we're removing stale data
-    pub async fn delete_metrics(&self, sandbox_id: &str) -> bool {
-        let mut current = self.current_cache.lock().await;
-        // We need to modify the current cache, so we rebuild it without the deleted entry
-        let new_data: HashMap<String, CachedMetrics> = current
+    /// None until the first collection cycle finished (a warm restart does
+    /// not count, its entries keep their original collection times).
+    pub fn last_collection(&self) -> Option<Duration> {
+        match self.last_collection_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(Duration::from_millis(ms)),
+        }
+    }
+
+    /// Render `kata_pulse_last_collection_timestamp_seconds` in Prometheus text format
+    ///
+    /// Empty until the first collection cycle finished.
+    pub fn render_metrics(&self) -> String {
+        let Some(last_collection) = self.last_collection() else {
+            return String::new();
+        };
+        let mut output = String::new();
+        output.push_str("# HELP kata_pulse_last_collection_timestamp_seconds Unix time at which the last collection cycle was published\n");
+        output.push_str("# TYPE kata_pulse_last_collection_timestamp_seconds gauge\n");
+        output.push_str(&format!(
+            "kata_pulse_last_collection_timestamp_seconds {}\n",
+            last_collection.as_secs_f64()
+        ));
+        output
+    }
+
+    /// Get the currently published buffer (all sandboxes, including stale entries)
+    pub async fn published(&self) -> Arc<HashMap<String, CachedMetrics>> {
+        self.current_cache.load_full()
+    }
+
+    /// Publish previously saved metrics (warm restart)
+    ///
+    /// Entries past the TTL are dropped; entries already in the cache win.
+    /// Returns the number of restored sandboxes.
+    pub async fn restore(&self, entries: HashMap<String, CachedMetrics>) -> usize {
+        let entries: Vec<_> = entries
+            .into_iter()
+            .filter(|(_, cached)| !self.is_stale(cached))
+            .collect();
+        let mut restored = 0;
+        self.current_cache.rcu(|current| {
+            let mut new_data: HashMap<String, CachedMetrics> = (**current).clone();
+            restored = 0;
+            for (sandbox_id, cached) in &entries {
+                if !new_data.contains_key(sandbox_id) {
+                    new_data.insert(sandbox_id.clone(), cached.clone());
+                    restored += 1;
+                }
+            }
+            new_data
+        });
+        restored
+    }
+
+    /// The tombstones to report in a `/metrics` render
+    ///
+    /// A tombstone is reported for `TOMBSTONE_REPORT_INTERVALS` collection
+    /// `interval`s (at most `TOMBSTONE_MAX_AGE`), to every render: reading
+    /// does not use it up, so each scraper sees the removal whatever other
+    /// requests came first. It keeps guarding against late scrape results
+    /// until `TOMBSTONE_MAX_AGE`.
+    pub async fn tombstones(&self, interval: Duration) -> Vec<(String, Tombstone)> {
+        self.tombstones_at(Instant::now(), interval).await
+    }
+
+    /// The tombstones to report in a render at `now`
+    async fn tombstones_at(&self, now: Instant, interval: Duration) -> Vec<(String, Tombstone)> {
+        let window = interval
+            .saturating_mul(TOMBSTONE_REPORT_INTERVALS)
+            .min(TOMBSTONE_MAX_AGE);
+        let mut reported: Vec<_> = self
+            .tombstones
+            .lock()
+            .await
             .iter()
-            .filter(|(id, _)| *id != sandbox_id)
-            .map(|(id, cached)| (id.clone(), cached.clone()))
+            .filter(|(_, tombstone)| now.saturating_duration_since(tombstone.removed_at) < window)
+            .map(|(sandbox_id, tombstone)| (sandbox_id.clone(), tombstone.clone()))
             .collect();
+        reported.sort_by(|a, b| a.0.cmp(&b.0));
+        reported
+    }
+
+    /// Remove metrics for a sandbox (when sandbox is deleted)
+    ///
+    /// This updates the current cache immediately since we're removing stale data.
+    /// A tombstone is left behind so late results of the sandbox are not
+    /// published again, and the next renders can report it as removed.
+    pub async fn delete_metrics(&self, sandbox_id: &str, metadata: SandboxCRIMetadata) -> bool {
+        self.history.lock().await.remove(sandbox_id);
+        {
+            let mut tombstones = self.tombstones.lock().await;
+            tombstones.retain(|_, tombstone| tombstone.removed_at.elapsed() < TOMBSTONE_MAX_AGE);
+            tombstones.insert(
+                sandbox_id.to_string(),
+                Tombstone {
+                    metadata,
+                    removed_at: Instant::now(),
+                },
+            );
+        }
+
+        // We need to modify the current cache, so we rebuild it without the deleted entry
+        let previous = self.current_cache.rcu(|current| {
+            let mut new_data = (**current).clone();
+            new_data.remove(sandbox_id);
+            new_data
+        });
 
-        let was_present = new_data.len() < current.len();
-        *current = Arc::new(new_data);
+        let was_present = previous.contains_key(sandbox_id);
 
         if was_present {
             debug!(sandbox_id = %sandbox_id, "Deleted metrics for sandbox");
         }
         was_present
     }
+
+    /// Forget everything cached for a sandbox ID, without leaving a tombstone
+    ///
+    /// Used when a sandbox directory is (re)created under an ID that was seen
+    /// before, so the new instance neither inherits the old instance's metrics
+    /// and history nor is blocked by its tombstone.
+    pub async fn reset_sandbox(&self, sandbox_id: &str) {
+        self.tombstones.lock().await.remove(sandbox_id);
+        self.history.lock().await.remove(sandbox_id);
+        self.staging_cache.lock().await.remove(sandbox_id);
+
+        if self.current_cache.load().contains_key(sandbox_id) {
+            self.current_cache.rcu(|current| {
+                let mut new_data = (**current).clone();
+                new_data.remove(sandbox_id);
+                new_data
+            });
+            debug!(sandbox_id = %sandbox_id, "Reset cached metrics for sandbox");
+        }
+    }
 }
 
 impl Default for MetricsCache {
@@ -127,3 +472,237 @@ impl Default for MetricsCache {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_carry_over_preserves_metrics_across_swap() {
+        let cache = MetricsCache::new();
+
+        cache.start_collection().await;
+        cache
+            .add_metrics("sandbox-1".to_string(), PrometheusMetrics::new())
+            .await;
+        cache.finish_collection().await;
+        assert!(cache.get_metrics("sandbox-1").await.is_some());
+
+        // Next cycle does not scrape sandbox-1 but carries it over
+        cache.start_collection().await;
+        assert!(cache.carry_over("sandbox-1").await);
+        assert!(!cache.carry_over("sandbox-2").await);
+        cache.finish_collection().await;
+        assert!(cache.get_metrics("sandbox-1").await.is_some());
+
+        // A cycle without carry-over drops it
+        cache.start_collection().await;
+        cache.finish_collection().await;
+        assert!(cache.get_metrics("sandbox-1").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_buffer_swap_records_collection_time() {
+        let cache = MetricsCache::new();
+        assert!(cache.last_collection().is_none());
+        assert!(cache.render_metrics().is_empty());
+
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        cache.start_collection().await;
+        cache.finish_collection().await;
+        let last_collection = cache.last_collection().unwrap();
+        assert!(last_collection + Duration::from_millis(1) >= before);
+        assert!(cache
+            .render_metrics()
+            .contains("\nkata_pulse_last_collection_timestamp_seconds "));
+    }
+
+    #[tokio::test]
+    async fn test_ttl_hides_stale_metrics() {
+        let cache = MetricsCache::with_ttl(Duration::from_millis(20));
+
+        cache.start_collection().await;
+        cache
+            .add_metrics("sandbox-1".to_string(), PrometheusMetrics::new())
+            .await;
+        cache.finish_collection().await;
+        assert!(cache.get_metrics("sandbox-1").await.is_some());
+
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(cache.get_metrics("sandbox-1").await.is_none());
+
+        // Stale entries are evicted rather than carried over
+        cache.start_collection().await;
+        assert!(!cache.carry_over("sandbox-1").await);
+        cache.finish_collection().await;
+    }
+
+    #[tokio::test]
+    async fn test_history_is_bounded_and_windowed() {
+        let cache = MetricsCache::new().with_history(3);
+
+        for _ in 0..5 {
+            cache.start_collection().await;
+            cache
+                .add_metrics("sandbox-1".to_string(), PrometheusMetrics::new())
+                .await;
+            cache.finish_collection().await;
+        }
+        assert_eq!(cache.get_history("sandbox-1", None).await.unwrap().len(), 3);
+        assert!(cache.get_history("sandbox-2", None).await.is_none());
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        cache.start_collection().await;
+        cache
+            .add_metrics("sandbox-1".to_string(), PrometheusMetrics::new())
+            .await;
+        cache.finish_collection().await;
+        let recent = cache
+            .get_history("sandbox-1", Some(Duration::from_millis(20)))
+            .await
+            .unwrap();
+        assert_eq!(recent.len(), 1);
+
+        cache
+            .delete_metrics("sandbox-1", SandboxCRIMetadata::default())
+            .await;
+        assert!(cache.get_history("sandbox-1", None).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_removed_sandbox_is_not_republished() {
+        let cache = MetricsCache::new();
+
+        // Scrapes in flight while the sandbox is removed
+        cache.start_collection().await;
+        cache
+            .add_metrics("sandbox-1".to_string(), PrometheusMetrics::new())
+            .await;
+        cache
+            .delete_metrics("sandbox-1", SandboxCRIMetadata::default())
+            .await;
+        cache
+            .add_metrics("sandbox-1".to_string(), PrometheusMetrics::new())
+            .await;
+        cache.finish_collection().await;
+
+        assert!(cache.get_metrics("sandbox-1").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reset_sandbox_clears_tombstone() {
+        let cache = MetricsCache::new();
+        cache
+            .delete_metrics("sandbox-1", SandboxCRIMetadata::default())
+            .await;
+        cache.reset_sandbox("sandbox-1").await;
+
+        cache.start_collection().await;
+        cache
+            .add_metrics("sandbox-1".to_string(), PrometheusMetrics::new())
+            .await;
+        cache.finish_collection().await;
+
+        assert!(cache.get_metrics("sandbox-1").await.is_some());
+        assert!(cache.tombstones(Duration::from_secs(60)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_tombstones_are_reported_for_limited_intervals() {
+        let cache = MetricsCache::new();
+        cache
+            .delete_metrics("sandbox-1", SandboxCRIMetadata::default())
+            .await;
+        let interval = Duration::from_secs(60);
+        let now = Instant::now();
+
+        // Every render within the window reports it, however many there are
+        for _ in 0..5 {
+            let tombstones = cache.tombstones_at(now, interval).await;
+            assert_eq!(tombstones.len(), 1);
+            assert_eq!(tombstones[0].0, "sandbox-1");
+        }
+        let later = now + interval * TOMBSTONE_REPORT_INTERVALS - Duration::from_secs(1);
+        assert_eq!(cache.tombstones_at(later, interval).await.len(), 1);
+        let expired = now + interval * TOMBSTONE_REPORT_INTERVALS + Duration::from_secs(1);
+        assert!(cache.tombstones_at(expired, interval).await.is_empty());
+
+        // Long intervals are capped by the age of the tombstone
+        let hour = Duration::from_secs(3600);
+        let capped = now + TOMBSTONE_MAX_AGE + Duration::from_secs(1);
+        assert!(cache.tombstones_at(capped, hour).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_history_disabled_by_default() {
+        let cache = MetricsCache::new();
+        cache.start_collection().await;
+        cache
+            .add_metrics("sandbox-1".to_string(), PrometheusMetrics::new())
+            .await;
+        cache.finish_collection().await;
+        assert!(cache.get_history("sandbox-1", None).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_no_ttl_serves_metrics_indefinitely() {
+        let cache = MetricsCache::new();
+
+        cache.start_collection().await;
+        cache
+            .add_metrics("sandbox-1".to_string(), PrometheusMetrics::new())
+            .await;
+        cache.finish_collection().await;
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(cache.get_metrics("sandbox-1").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_converted_output_is_memoized() {
+        use crate::monitor::sandbox_cache::SandboxCache;
+        use crate::utils::metrics_converter::{CRILabelEnricher, LabelEnricher};
+
+        let sandbox_cache = Arc::new(SandboxCache::new());
+        let enricher: Arc<dyn LabelEnricher> =
+            Arc::new(CRILabelEnricher::new(sandbox_cache.clone()));
+        let config = Arc::new(ReloadableConfig {
+            metrics_interval_secs: 60,
+            sandbox_filter: Default::default(),
+            name_filter: Default::default(),
+            conversion: Default::default(),
+        });
+        let metrics =
+            PrometheusMetrics::parse("kata_guest_cpu_time{cpu=\"total\",item=\"user\"} 100\n")
+                .unwrap();
+        let cached = CachedMetrics::new(Arc::new(metrics), Instant::now());
+
+        let labels = enricher.enrich("sb-1").await;
+        let first = cached.converted(&config, labels.clone(), "sb-1").unwrap();
+        let again = cached
+            .clone()
+            .converted(&config, labels.clone(), "sb-1")
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+
+        // Reloaded settings convert again
+        let reloaded = Arc::new((*config).clone());
+        let after_reload = cached.converted(&reloaded, labels, "sb-1").unwrap();
+        assert!(!Arc::ptr_eq(&first, &after_reload));
+
+        // So does pod metadata arriving after the scrape
+        sandbox_cache
+            .set_cri_metadata(
+                "sb-1",
+                SandboxCRIMetadata {
+                    name: "my-pod".to_string(),
+                    namespace: "default".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await;
+        let labels = enricher.enrich("sb-1").await;
+        let enriched = cached.converted(&reloaded, labels, "sb-1").unwrap();
+        assert!(enriched.contains("pod=\"my-pod\""));
+    }
+}
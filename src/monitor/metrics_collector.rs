@@ -5,14 +5,38 @@
 //! - Parse Prometheus format metrics
 //! - Store metrics in double-buffered cache
 //! - Track collection statistics (success/failure counts, timing)
+//! - Honor per-sandbox collection intervals (scrape-interval annotation)
+//! - Hand the converted metrics to the push exporters after every cycle
+//! - Inject the faults of chaos mode into the scrapes, if enabled
+//! - Record the scraped payloads, or replay a recording instead of scraping
 
 use anyhow::Result;
+use std::borrow::Cow;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
-use tracing::{debug, info, warn};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, info_span, warn, Instrument};
 
+use super::cache_snapshot;
+use super::chaos::FaultInjector;
+use super::host_cgroup::HostCgroupReader;
+use super::hypervisor_proc::HypervisorProcReader;
 use super::metrics_cache::MetricsCache;
+use super::mock_sandboxes::MockSandboxes;
+use super::pipeline_health::{Component, PipelineHealth};
+use super::recording::{PayloadRecorder, PayloadReplay};
+use super::rendered_metrics::RenderedMetrics;
 use super::sandbox_cache::SandboxCache;
+use super::scrape_health::ScrapeHealth;
+use super::scrape_scheduler::ScrapeScheduler;
+use crate::config::reload::SharedConfig;
+use crate::exporter::ExportPipeline;
+use crate::utils::interner;
+use crate::utils::log_dedup::{error_class, LogDecision, LogDedup};
+use crate::utils::prometheus_parser::{NameFilter, ParseError, ParseMode, PrometheusMetrics};
+use crate::utils::shim_client::{PayloadTooLarge, ShimClient};
+use crate::utils::systemd::SystemdNotify;
 
 /// Collects metrics from sandboxes at regular intervals
 ///
@@ -24,7 +48,51 @@ use super::sandbox_cache::SandboxCache;
 pub struct MetricsCollector {
     sandbox_cache: Arc<SandboxCache>,
     metrics_cache: Arc<MetricsCache>,
-    metrics_interval_secs: u64,
+    scrape_health: Arc<ScrapeHealth>,
+    shim_client: Arc<ShimClient>,
+    pipeline_health: Arc<PipelineHealth>,
+    /// Interval, sandbox filter and name filter; re-read every cycle
+    config: Arc<SharedConfig>,
+    snapshot_path: Option<PathBuf>,
+    host_sources: HostSources,
+    parse_mode: ParseMode,
+    exports: Option<Arc<ExportPipeline>>,
+    /// Renders the aggregated `/metrics` output after every buffer swap
+    rendered: Option<Arc<RenderedMetrics>>,
+    /// Generates the metrics of fake sandboxes instead of scraping shims
+    mock: Option<Arc<MockSandboxes>>,
+    /// Reports readiness and watchdog heartbeats to systemd
+    systemd: Option<Arc<SystemdNotify>>,
+    /// Keeps a dead shim from logging a warning every cycle
+    failure_log: LogDedup,
+    /// Fails, delays and corrupts scrapes in chaos mode
+    chaos: Option<Arc<FaultInjector>>,
+    /// Serves recorded payloads instead of scraping shims
+    replay: Option<Arc<PayloadReplay>>,
+    /// Saves the payloads scraped from shims
+    recorder: Option<Arc<PayloadRecorder>>,
+}
+
+/// Interval of the summaries of repeated scrape failures of a sandbox
+const FAILURE_LOG_SUMMARY_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Host-side sources attached to the scraped metrics of each sandbox
+#[derive(Default)]
+struct HostSources {
+    cgroup: Option<Arc<HostCgroupReader>>,
+    hypervisor: Option<Arc<HypervisorProcReader>>,
+}
+
+impl HostSources {
+    /// Append the host-side usage of a sandbox to its scraped metrics
+    fn append(&self, sandbox_id: &str, metrics: &mut PrometheusMetrics) {
+        if let Some(reader) = &self.cgroup {
+            reader.append_overhead(sandbox_id, metrics);
+        }
+        if let Some(reader) = &self.hypervisor {
+            reader.append_vmm(sandbox_id, metrics);
+        }
+    }
 }
 
 impl MetricsCollector {
@@ -32,143 +100,538 @@ impl MetricsCollector {
     pub fn new(
         sandbox_cache: Arc<SandboxCache>,
         metrics_cache: Arc<MetricsCache>,
-        metrics_interval_secs: u64,
+        scrape_health: Arc<ScrapeHealth>,
+        shim_client: Arc<ShimClient>,
+        pipeline_health: Arc<PipelineHealth>,
+        config: Arc<SharedConfig>,
     ) -> Self {
         MetricsCollector {
             sandbox_cache,
             metrics_cache,
-            metrics_interval_secs,
+            scrape_health,
+            shim_client,
+            pipeline_health,
+            config,
+            snapshot_path: None,
+            host_sources: HostSources::default(),
+            parse_mode: ParseMode::default(),
+            exports: None,
+            rendered: None,
+            mock: None,
+            systemd: None,
+            failure_log: LogDedup::new(FAILURE_LOG_SUMMARY_INTERVAL),
+            chaos: None,
+            replay: None,
+            recorder: None,
         }
     }
 
-    /// Start the periodic metrics collection task
+    /// Persist the metrics cache to `path` after every collection cycle and
+    /// restore it from there on start
+    pub fn with_snapshot_path(mut self, path: Option<PathBuf>) -> Self {
+        self.snapshot_path = path;
+        self
+    }
+
+    /// Attach the host overhead cgroup usage of each sandbox to its scraped metrics
+    pub fn with_host_cgroup(mut self, reader: Option<Arc<HostCgroupReader>>) -> Self {
+        self.host_sources.cgroup = reader;
+        self
+    }
+
+    /// Attach the /proc usage of each sandbox's hypervisor process to its scraped metrics
+    pub fn with_hypervisor_proc(mut self, reader: Option<Arc<HypervisorProcReader>>) -> Self {
+        self.host_sources.hypervisor = reader;
+        self
+    }
+
+    /// Fail scrapes with malformed lines instead of skipping the lines in strict mode
+    pub fn with_parse_mode(mut self, parse_mode: ParseMode) -> Self {
+        self.parse_mode = parse_mode;
+        self
+    }
+
+    /// Push the converted metrics through `exports` after every collection cycle
+    pub fn with_exports(mut self, exports: Option<Arc<ExportPipeline>>) -> Self {
+        self.exports = exports;
+        self
+    }
+
+    /// Pre-render the aggregated `/metrics` output after every buffer swap
+    pub fn with_rendered_metrics(mut self, rendered: Option<Arc<RenderedMetrics>>) -> Self {
+        self.rendered = rendered;
+        self
+    }
+
+    /// Take the metrics of the fake sandboxes of `mock` instead of scraping shims
+    pub fn with_mock_sandboxes(mut self, mock: Option<Arc<MockSandboxes>>) -> Self {
+        self.mock = mock;
+        self
+    }
+
+    /// Notify systemd once the first cycle completes, and send watchdog
+    /// heartbeats from the collection loop
+    pub fn with_systemd(mut self, systemd: Option<Arc<SystemdNotify>>) -> Self {
+        self.systemd = systemd;
+        self
+    }
+
+    /// Inject the faults of chaos mode into the scrapes
+    pub fn with_chaos(mut self, chaos: Option<Arc<FaultInjector>>) -> Self {
+        self.chaos = chaos;
+        self
+    }
+
+    /// Take the payloads of the recording of `replay` instead of scraping shims
+    pub fn with_replay(mut self, replay: Option<Arc<PayloadReplay>>) -> Self {
+        self.replay = replay;
+        self
+    }
+
+    /// Save the payloads scraped from shims with `recorder`
+    pub fn with_recorder(mut self, recorder: Option<Arc<PayloadRecorder>>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    /// Run the periodic metrics collection loop until `cancel` fires
     ///
-    /// This spawns a background task that collects metrics at the specified interval.
-    /// The task will:
+    /// This is a long-running task that should be spawned as a background task.
+    /// Each iteration will:
     /// 1. Get list of active sandboxes
     /// 2. Fetch metrics from all sandboxes in parallel
     /// 3. Parse Prometheus format metrics
     /// 4. Store in double-buffered cache with atomic buffer swap
     /// 5. Report timing and success/failure statistics
-    pub async fn start(&self) -> Result<()> {
-        let sandbox_cache = self.sandbox_cache.clone();
-        let metrics_cache = self.metrics_cache.clone();
+    pub async fn start(&self, cancel: CancellationToken) -> Result<()> {
+        let sandbox_cache = &self.sandbox_cache;
+        let metrics_cache = &self.metrics_cache;
+        let scrape_health = &self.scrape_health;
+        let shim_client = &self.shim_client;
+        let snapshot_path = &self.snapshot_path;
+        let pipeline_health = &self.pipeline_health;
 
-        let interval_secs = self.metrics_interval_secs;
+        let interval_secs = self.config.load().metrics_interval_secs;
 
         info!(
             interval_secs = interval_secs,
             "Starting metrics collector task"
         );
 
-        tokio::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
-
-            loop {
-                interval.tick().await;
+        // Warm restart: serve the last saved metrics until sandboxes are scraped again
+        if let Some(path) = snapshot_path {
+            match cache_snapshot::load(metrics_cache, path).await {
+                Ok(restored) => {
+                    info!(path = %path.display(), restored = restored, "Restored metrics cache snapshot")
+                }
+                Err(e) => {
+                    warn!(path = %path.display(), error = %e, "Failed to restore metrics cache snapshot")
+                }
+            }
+        }
 
-                let cycle_start = std::time::Instant::now();
-                info!("Starting metrics collection cycle (double-buffered)");
+        let mut scheduler = ScrapeScheduler::new(Duration::from_secs(interval_secs));
 
-                // Get current list of sandboxes
-                let sandboxes = sandbox_cache.get_sandbox_list().await;
-                debug!(
-                    sandbox_count = sandboxes.len(),
-                    "Retrieved sandbox list for metrics collection"
-                );
+        loop {
+            // Interval and filter may have been reloaded since the last cycle
+            let config = self.config.load();
+            scheduler.set_default_interval(config.metrics_interval());
 
-                if sandboxes.is_empty() {
-                    debug!("No sandboxes running, skipping metrics collection");
-                    continue;
-                }
+            // Get current list of collected sandboxes with their interval overrides
+            let sandboxes: Vec<(String, Option<u64>)> = sandbox_cache
+                .get_sandboxes_with_metadata()
+                .await
+                .into_iter()
+                .filter(|(_, metadata)| config.sandbox_filter.allows(metadata))
+                .map(|(id, metadata)| (id, metadata.scrape_interval_secs))
+                .collect();
+            debug!(
+                sandbox_count = sandboxes.len(),
+                "Retrieved sandbox list for metrics collection"
+            );
 
-                let total_sandboxes = sandboxes.len();
-                info!(
-                    sandbox_count = total_sandboxes,
-                    "Collecting metrics from sandboxes (parallel, double-buffered)"
-                );
+            let now = tokio::time::Instant::now();
+            let due = scheduler.take_due(now, &sandboxes);
 
-                // Start collection - prepare staging cache
-                metrics_cache.start_collection().await;
+            // Forget scrape results of sandboxes that are gone
+            let sandbox_ids: Vec<String> = sandboxes.iter().map(|(id, _)| id.clone()).collect();
+            scrape_health.retain(&sandbox_ids).await;
+            shim_client.retain(&sandbox_ids);
+            self.failure_log.retain(&sandbox_ids);
 
-                // Collect metrics from all sandboxes in parallel
-                let futures: Vec<_> = sandboxes
+            if sandboxes.is_empty() {
+                debug!("No sandboxes running, skipping metrics collection");
+                pipeline_health.healthy(Component::MetricsCollector);
+            } else if !due.is_empty() {
+                let not_due: Vec<String> = sandboxes
                     .into_iter()
-                    .map(|sandbox_id| {
-                        async move {
-                            debug!(sandbox_id = %sandbox_id, "Attempting to fetch metrics from sandbox");
-                            let fetch_result = crate::utils::shim_client::do_get(&sandbox_id, crate::config::METRICS_URL).await;
-                            (sandbox_id, fetch_result)
-                        }
-                    })
+                    .map(|(id, _)| id)
+                    .filter(|id| !due.contains(id))
                     .collect();
+                let (success_count, failure_count) = self.collect_cycle(due, not_due).await;
+                if success_count == 0 && failure_count > 0 {
+                    pipeline_health.degraded(
+                        Component::MetricsCollector,
+                        format!("all {} scrapes failed", failure_count),
+                    );
+                } else {
+                    pipeline_health.healthy(Component::MetricsCollector);
+                }
 
-                let results = futures::future::join_all(futures).await;
-
-                // Process results and add to staging cache
-                let mut success_count = 0;
-                let mut failure_count = 0;
-
-                for (sandbox_id, result) in results {
-                    match result {
-                        Ok(data) => {
-                            debug!(sandbox_id = %sandbox_id, data_size = data.len(), "Received metrics data from shim");
-                            let metrics_text = String::from_utf8_lossy(&data);
-                            match crate::utils::prometheus_parser::PrometheusMetrics::parse(
-                                &metrics_text,
-                            ) {
-                                Ok(parsed_metrics) => {
-                                    // Add to staging cache (not yet visible to readers)
-                                    metrics_cache
-                                        .add_metrics(sandbox_id.clone(), parsed_metrics)
-                                        .await;
-                                    success_count += 1;
-                                    debug!(sandbox_id = %sandbox_id, "Metrics collected and added to staging");
-                                }
-                                Err(e) => {
-                                    failure_count += 1;
-                                    warn!(sandbox_id = %sandbox_id, error = %e, "Failed to parse metrics");
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            failure_count += 1;
-                            warn!(sandbox_id = %sandbox_id, error = %e, "Failed to collect metrics from sandbox");
-                        }
+                if let Some(path) = snapshot_path {
+                    if let Err(e) = cache_snapshot::save(metrics_cache, path).await {
+                        warn!(path = %path.display(), error = %e, "Failed to save metrics cache snapshot");
                     }
                 }
 
-                // Finish collection - atomic swap of buffers
-                let swap_start = std::time::Instant::now();
-                metrics_cache.finish_collection().await;
-                let swap_duration_us = swap_start.elapsed().as_micros();
-
-                let cycle_duration_ms = cycle_start.elapsed().as_millis();
-                info!(
-                    success = success_count,
-                    failure = failure_count,
-                    total = total_sandboxes,
-                    duration_ms = cycle_duration_ms,
-                    swap_duration_us = swap_duration_us,
-                    "Metrics collection cycle completed (buffers swapped atomically)"
-                );
+                if let Some(rendered) = &self.rendered {
+                    rendered.refresh().instrument(info_span!("render")).await;
+                }
+
+                if let Some(exports) = &self.exports {
+                    exports.export_cycle().await;
+                }
+            }
+
+            // The loop is alive as long as it gets here; a wedged cycle stops the heartbeats
+            let mut wakeup = scheduler.next_wakeup(now);
+            if let Some(systemd) = &self.systemd {
+                if let Err(e) = systemd.ready().and_then(|_| systemd.watchdog()) {
+                    warn!(error = %format!("{:#}", e), "Failed to notify systemd");
+                }
+                if let Some(interval) = systemd.watchdog_interval() {
+                    wakeup = wakeup.min(tokio::time::Instant::now() + interval);
+                }
+            }
+
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep_until(wakeup) => {}
             }
-        });
+        }
 
+        info!("Metrics collector stopped");
         Ok(())
     }
+
+    /// Run a single collection cycle
+    ///
+    /// Scrapes the `due` sandboxes in parallel, carries the last metrics of the
+    /// `not_due` sandboxes over, and atomically swaps the buffers.
+    /// Returns the number of successful and failed scrapes.
+    ///
+    /// Traced as a `collection_cycle` span with per-sandbox `scrape` and
+    /// `parse` spans and a `buffer_swap` span.
+    #[tracing::instrument(name = "collection_cycle", skip_all, fields(due = due.len(), not_due = not_due.len()))]
+    async fn collect_cycle(&self, due: Vec<String>, not_due: Vec<String>) -> (usize, usize) {
+        let sandbox_cache = &self.sandbox_cache;
+        let metrics_cache = &self.metrics_cache;
+        let scrape_health = &self.scrape_health;
+        let shim_client = &self.shim_client;
+        let host_sources = &self.host_sources;
+        let mock = self.mock.as_deref();
+        let replay = self.replay.as_deref();
+        let recorder = self.recorder.as_deref();
+        let chaos = self.chaos.as_deref();
+        let parse_mode = self.parse_mode;
+        let config = self.config.load();
+        let name_filter = &config.name_filter;
+
+        let cycle_start = std::time::Instant::now();
+        debug!("Starting metrics collection cycle (double-buffered)");
+
+        let total_sandboxes = due.len();
+        debug!(
+            sandbox_count = total_sandboxes,
+            skipped = not_due.len(),
+            "Collecting metrics from sandboxes (parallel, double-buffered)"
+        );
+
+        // Start collection - prepare staging cache
+        metrics_cache.start_collection().await;
+
+        // Sandboxes on a longer interval keep their last metrics
+        for sandbox_id in &not_due {
+            metrics_cache.carry_over(sandbox_id).await;
+        }
+
+        // Quarantined sandboxes sit out cycles until their next probe
+        let mut to_scrape = Vec::with_capacity(due.len());
+        for sandbox_id in due {
+            if scrape_health.should_scrape(&sandbox_id).await {
+                to_scrape.push(sandbox_id);
+            } else {
+                debug!(sandbox_id = %sandbox_id, "Sandbox quarantined, skipping scrape");
+                metrics_cache.carry_over(&sandbox_id).await;
+            }
+        }
+
+        // Collect metrics from all due sandboxes in parallel
+        let futures: Vec<_> = to_scrape
+            .into_iter()
+            .map(|sandbox_id| {
+                let span = info_span!("scrape", sandbox_id = %sandbox_id);
+                async move {
+                    debug!(sandbox_id = %sandbox_id, "Attempting to fetch metrics from sandbox");
+                    let scrape_start = std::time::Instant::now();
+                    let fetch_result = if let Some(replay) = replay {
+                        let payload = replay.next_payload(&sandbox_id);
+                        scrape_local(&sandbox_id, payload, chaos, name_filter).await
+                    } else if let Some(mock) = mock {
+                        let payload = async { mock.payload(&sandbox_id) };
+                        scrape_local(&sandbox_id, payload, chaos, name_filter).await
+                    } else {
+                        scrape_sandbox(
+                            shim_client,
+                            sandbox_cache,
+                            &sandbox_id,
+                            shim_client.config().timeout,
+                            name_filter,
+                            chaos,
+                            recorder,
+                        )
+                        .await
+                    };
+                    (sandbox_id, fetch_result, scrape_start.elapsed())
+                }
+                .instrument(span)
+            })
+            .collect();
+
+        let results = futures::future::join_all(futures).await;
+
+        // Process results and add to staging cache
+        let mut success_count = 0;
+        let mut failure_count = 0;
+
+        for (sandbox_id, result, duration) in results {
+            match check_parse_errors(scrape_health, parse_mode, &sandbox_id, result).await {
+                Ok(mut parsed_metrics) => {
+                    host_sources.append(&sandbox_id, &mut parsed_metrics);
+                    if scrape_health.is_quarantined(&sandbox_id).await {
+                        info!(sandbox_id = %sandbox_id, "Quarantined sandbox recovered");
+                    }
+                    self.failure_log.clear(&sandbox_id);
+                    scrape_health.record_success(&sandbox_id, duration).await;
+                    // Add to staging cache (not yet visible to readers)
+                    metrics_cache
+                        .add_metrics(sandbox_id.clone(), parsed_metrics)
+                        .await;
+                    success_count += 1;
+                    debug!(sandbox_id = %sandbox_id, duration_ms = duration.as_millis(), "Metrics collected and added to staging");
+                }
+                Err(e) => {
+                    failure_count += 1;
+                    if e.chain().any(|cause| cause.is::<PayloadTooLarge>()) {
+                        scrape_health.record_oversized_payload(&sandbox_id).await;
+                    }
+                    let was_quarantined = scrape_health.is_quarantined(&sandbox_id).await;
+                    let status = scrape_health
+                        .record_failure(&sandbox_id, duration, format!("{:#}", e))
+                        .await;
+                    if was_quarantined {
+                        debug!(sandbox_id = %sandbox_id, error = %e, skip_cycles = status.skip_cycles_remaining, "Probe of quarantined sandbox failed");
+                    } else if status.quarantined {
+                        warn!(sandbox_id = %sandbox_id, error = %e, consecutive_failures = status.consecutive_failures, "Sandbox keeps failing, quarantining it");
+                    } else {
+                        match self.failure_log.check(&sandbox_id, &error_class(&e)) {
+                            LogDecision::First => {
+                                warn!(sandbox_id = %sandbox_id, error = %e, duration_ms = duration.as_millis(), "Failed to collect metrics from sandbox")
+                            }
+                            LogDecision::Summary { suppressed } => {
+                                warn!(sandbox_id = %sandbox_id, error = %e, duration_ms = duration.as_millis(), repeated = suppressed, "Failed to collect metrics from sandbox (repeated since last logged)")
+                            }
+                            LogDecision::Suppress => {
+                                debug!(sandbox_id = %sandbox_id, error = %e, duration_ms = duration.as_millis(), "Failed to collect metrics from sandbox")
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // Finish collection - atomic swap of buffers
+        let swap_start = std::time::Instant::now();
+        metrics_cache
+            .finish_collection()
+            .instrument(info_span!("buffer_swap"))
+            .await;
+        let swap_duration_us = swap_start.elapsed().as_micros();
+
+        // Label strings of the replaced buffer are no longer referenced
+        let purged_strings = interner::labels().purge_unused();
+
+        let cycle_duration_ms = cycle_start.elapsed().as_millis();
+        info!(
+            success = success_count,
+            failure = failure_count,
+            total = total_sandboxes,
+            duration_ms = cycle_duration_ms,
+            swap_duration_us = swap_duration_us,
+            purged_strings = purged_strings,
+            "Metrics collection cycle completed (buffers swapped atomically)"
+        );
+
+        (success_count, failure_count)
+    }
+}
+
+/// Fetch and parse the metrics of a single sandbox from its shim
+///
+/// Families dropped by `name_filter` are skipped while parsing. Malformed lines
+/// are skipped and logged, and returned with the metrics so the caller can
+/// apply its `ParseMode`. In chaos mode, `chaos` fails, delays and corrupts
+/// the scrape; `recorder` saves the payload as received from the shim.
+/// Also used by the on-demand path in the HTTP server when the cache is cold.
+pub async fn scrape_sandbox(
+    shim_client: &ShimClient,
+    sandbox_cache: &SandboxCache,
+    sandbox_id: &str,
+    timeout: Duration,
+    name_filter: &NameFilter,
+    chaos: Option<&FaultInjector>,
+    recorder: Option<&PayloadRecorder>,
+) -> Result<(PrometheusMetrics, Vec<ParseError>)> {
+    if let Some(chaos) = chaos {
+        chaos.before_scrape(sandbox_id).await?;
+    }
+    let socket = sandbox_cache.shim_socket(sandbox_id).await?;
+    let data = match shim_client
+        .get(
+            sandbox_id,
+            &socket.path,
+            crate::config::METRICS_URL,
+            timeout,
+        )
+        .await
+    {
+        Ok(data) => data,
+        Err(e) => {
+            // The shim may have been restarted under the other runtime's path
+            sandbox_cache.invalidate_shim_socket(sandbox_id).await;
+            return Err(e);
+        }
+    };
+    debug!(sandbox_id = %sandbox_id, data_size = data.len(), "Received metrics data from shim");
+    if let Some(recorder) = recorder {
+        let metadata = sandbox_cache.get_metadata(sandbox_id).await;
+        if let Err(e) = recorder.record(sandbox_id, &data, metadata).await {
+            warn!(sandbox_id = %sandbox_id, error = %e, "Failed to record shim payload");
+        }
+    }
+    let mut metrics_text = String::from_utf8_lossy(&data);
+    if let Some(chaos) = chaos {
+        metrics_text = chaos.corrupt_payload(sandbox_id, metrics_text);
+    }
+    Ok(parse_payload(sandbox_id, &metrics_text, name_filter))
+}
+
+/// Parse a payload generated by a mock sandbox or read from a recording
+///
+/// The faults of chaos mode apply as to a shim scrape.
+async fn scrape_local(
+    sandbox_id: &str,
+    payload: impl std::future::Future<Output = Result<String>>,
+    chaos: Option<&FaultInjector>,
+    name_filter: &NameFilter,
+) -> Result<(PrometheusMetrics, Vec<ParseError>)> {
+    if let Some(chaos) = chaos {
+        chaos.before_scrape(sandbox_id).await?;
+    }
+    let mut payload = Cow::Owned(payload.await?);
+    if let Some(chaos) = chaos {
+        payload = chaos.corrupt_payload(sandbox_id, payload);
+    }
+    Ok(parse_payload(sandbox_id, &payload, name_filter))
+}
+
+/// Parse a scraped payload, logging its malformed lines
+fn parse_payload(
+    sandbox_id: &str,
+    metrics_text: &str,
+    name_filter: &NameFilter,
+) -> (PrometheusMetrics, Vec<ParseError>) {
+    let (metrics, errors) =
+        info_span!("parse", sandbox_id = %sandbox_id, bytes = metrics_text.len())
+            .in_scope(|| PrometheusMetrics::parse_filtered(metrics_text, name_filter));
+    for error in &errors {
+        debug!(sandbox_id = %sandbox_id, error = %error, "Malformed metrics line");
+    }
+    (metrics, errors)
+}
+
+/// Apply the parse mode to a scrape, counting its malformed lines
+///
+/// Malformed lines are counted in both modes. In lenient mode they are
+/// skipped with a warning; in strict mode the scrape fails instead, and the
+/// caller logs it as a failed scrape.
+/// Also used by the on-demand path in the HTTP server.
+pub async fn check_parse_errors(
+    scrape_health: &ScrapeHealth,
+    parse_mode: ParseMode,
+    sandbox_id: &str,
+    scraped: Result<(PrometheusMetrics, Vec<ParseError>)>,
+) -> Result<PrometheusMetrics> {
+    let (metrics, errors) = scraped?;
+    scrape_health
+        .record_parse_errors(sandbox_id, errors.len())
+        .await;
+    parse_mode.check(&errors)?;
+    if let Some(first) = errors.first() {
+        warn!(sandbox_id = %sandbox_id, parse_errors = errors.len(), first_error = %first, "Skipped malformed lines in sandbox metrics");
+    }
+    Ok(metrics)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::reload::ReloadableConfig;
 
     #[test]
     fn test_metrics_collector_creation() {
         let sandbox_cache = Arc::new(SandboxCache::new());
         let metrics_cache = Arc::new(MetricsCache::new());
-        let collector = MetricsCollector::new(sandbox_cache, metrics_cache, 30);
+        let scrape_health = Arc::new(ScrapeHealth::default());
+        let collector = MetricsCollector::new(
+            sandbox_cache,
+            metrics_cache,
+            scrape_health,
+            Arc::new(ShimClient::new(Default::default())),
+            Arc::new(PipelineHealth::new()),
+            Arc::new(SharedConfig::new(ReloadableConfig {
+                metrics_interval_secs: 30,
+                sandbox_filter: Default::default(),
+                name_filter: Default::default(),
+                conversion: Default::default(),
+            })),
+        );
         // Verify it's created successfully
         assert!(std::mem::size_of_val(&collector) > 0);
     }
+
+    #[tokio::test]
+    async fn test_check_parse_errors() {
+        let scrape_health = ScrapeHealth::default();
+        let scrape = || {
+            Ok(PrometheusMetrics::parse_with_diagnostics(
+                "kata_shim_fds 12\nkata_shim_threads{\n",
+            ))
+        };
+
+        let metrics = check_parse_errors(&scrape_health, ParseMode::Lenient, "sb-1", scrape())
+            .await
+            .unwrap();
+        assert!(metrics.metrics.contains_key("kata_shim_fds"));
+        assert!(
+            check_parse_errors(&scrape_health, ParseMode::Strict, "sb-1", scrape())
+                .await
+                .is_err()
+        );
+        // Counted in both modes
+        assert_eq!(
+            scrape_health.get("sb-1").await.unwrap().parse_errors_total,
+            2
+        );
+    }
 }
@@ -0,0 +1,282 @@
+//! Synthetic sandboxes for development and load testing
+//!
+//! Responsibilities:
+//! - Register N fake sandboxes with pod metadata in the sandbox cache, in
+//!   place of the directory monitoring and CRI sync
+//! - Generate a Kata shim metrics payload for each of them on every scrape
+//!   (guest CPU, memory, network, disk, tasks, load and shim families), with
+//!   counters growing over time, so the whole parse/convert/serve pipeline
+//!   runs without containerd or shims
+//!
+//! Enabled with `--mock-sandboxes N`; never use it on a real node.
+
+use anyhow::Result;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use super::pipeline_health::{Component, PipelineHealth};
+use super::sandbox_cache::{MetadataStatus, SandboxCRIMetadata, SandboxCache};
+use crate::utils::prometheus_parser::{NameFilter, ParseError, PrometheusMetrics};
+
+/// Prefix of the fake sandbox IDs
+const ID_PREFIX: &str = "mock-";
+
+/// Namespace of the fake pods
+const NAMESPACE: &str = "kata-pulse-mock";
+
+/// Number of guest CPUs of every fake sandbox
+const CPUS: usize = 2;
+
+/// Guest memory of every fake sandbox in bytes
+const MEMORY_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Fabricates sandboxes and their shim metrics
+pub struct MockSandboxes {
+    count: usize,
+    started: Instant,
+}
+
+impl MockSandboxes {
+    /// Create `count` fake sandboxes
+    pub fn new(count: usize) -> Self {
+        MockSandboxes {
+            count,
+            started: Instant::now(),
+        }
+    }
+
+    /// ID of the fake sandbox with the given index
+    fn sandbox_id(index: usize) -> String {
+        format!("{}{:06}", ID_PREFIX, index)
+    }
+
+    /// Index of a fake sandbox ID, None for other IDs
+    fn index(sandbox_id: &str) -> Option<usize> {
+        sandbox_id.strip_prefix(ID_PREFIX)?.parse().ok()
+    }
+
+    /// Register the fake sandboxes in `cache`
+    pub async fn populate(&self, cache: &SandboxCache) {
+        for index in 0..self.count {
+            let id = Self::sandbox_id(index);
+            cache
+                .set_cri_metadata(
+                    &id,
+                    SandboxCRIMetadata {
+                        uid: format!("00000000-0000-4000-8000-{:012}", index),
+                        name: format!("mock-pod-{}", index),
+                        namespace: NAMESPACE.to_string(),
+                        runtime_handler: "kata".to_string(),
+                        scrape_interval_secs: None,
+                        clk_tck: None,
+                        metadata_status: MetadataStatus::Synced,
+                        pod_labels: Default::default(),
+                        workload: None,
+                        cri_annotations: Default::default(),
+                    },
+                )
+                .await;
+        }
+    }
+
+    /// Register the fake sandboxes, then idle until `cancel` fires
+    ///
+    /// Runs in place of the sandbox cache manager.
+    pub async fn start(
+        &self,
+        cache: Arc<SandboxCache>,
+        pipeline_health: Arc<PipelineHealth>,
+        cancel: CancellationToken,
+    ) -> Result<()> {
+        self.populate(&cache).await;
+        info!(
+            count = self.count,
+            namespace = NAMESPACE,
+            "Registered mock sandboxes"
+        );
+        pipeline_health.healthy(Component::SandboxCacheManager);
+        cancel.cancelled().await;
+        Ok(())
+    }
+
+    /// Generated shim metrics of a fake sandbox, parsed like a real scrape
+    pub fn scrape(
+        &self,
+        sandbox_id: &str,
+        name_filter: &NameFilter,
+    ) -> Result<(PrometheusMetrics, Vec<ParseError>)> {
+        let payload = self.payload(sandbox_id)?;
+        Ok(PrometheusMetrics::parse_filtered(&payload, name_filter))
+    }
+
+    /// Generated shim metrics payload of a fake sandbox
+    pub fn payload(&self, sandbox_id: &str) -> Result<String> {
+        let index = Self::index(sandbox_id)
+            .filter(|index| *index < self.count)
+            .ok_or_else(|| anyhow::anyhow!("unknown mock sandbox {}", sandbox_id))?;
+        Ok(payload(index, self.started.elapsed().as_secs_f64()))
+    }
+}
+
+/// Kata shim metrics of the fake sandbox `index`, `elapsed` seconds after start
+///
+/// Sandboxes differ by a load factor derived from the index, so dashboards
+/// show distinct series.
+fn payload(index: usize, elapsed: f64) -> String {
+    let load = 0.1 + (index % 10) as f64 * 0.1;
+    let jiffies = elapsed * 100.0 * load;
+    let mut out = String::with_capacity(4096);
+
+    out.push_str("# HELP kata_guest_cpu_time Guest CPU time in jiffies\n");
+    out.push_str("# TYPE kata_guest_cpu_time gauge\n");
+    for cpu in (0..CPUS).map(|cpu| cpu.to_string()).chain(["total".into()]) {
+        let share = if cpu == "total" {
+            1.0
+        } else {
+            1.0 / CPUS as f64
+        };
+        for (item, part) in [("user", 0.7), ("system", 0.2), ("nice", 0.0), ("idle", 1.0)] {
+            let value = if item == "idle" {
+                elapsed * 100.0 * CPUS as f64 * share - jiffies * share
+            } else {
+                jiffies * part * share
+            };
+            let _ = writeln!(
+                out,
+                "kata_guest_cpu_time{{cpu=\"{}\",item=\"{}\"}} {}",
+                cpu,
+                item,
+                value.max(0.0).floor()
+            );
+        }
+    }
+
+    let used = (MEMORY_BYTES as f64 * (0.2 + load * 0.5)) as u64;
+    out.push_str("# HELP kata_guest_meminfo Guest memory statistics\n");
+    out.push_str("# TYPE kata_guest_meminfo gauge\n");
+    for (item, value) in [
+        ("memtotal", MEMORY_BYTES),
+        ("memfree", MEMORY_BYTES - used),
+        ("active", used / 2),
+        ("inactive_file", used / 4),
+        ("cached", used / 4),
+        ("buffers", used / 16),
+        ("anon_pages", used / 2),
+        ("mapped", used / 8),
+        ("swaptotal", 0),
+        ("swapfree", 0),
+    ] {
+        let _ = writeln!(out, "kata_guest_meminfo{{item=\"{}\"}} {}", item, value);
+    }
+
+    let bytes = elapsed * 10_000.0 * load;
+    out.push_str("# HELP kata_guest_netdev_stat Guest network device statistics\n");
+    out.push_str("# TYPE kata_guest_netdev_stat gauge\n");
+    for (item, value) in [
+        ("recv_bytes", bytes),
+        ("xmit_bytes", bytes / 2.0),
+        ("recv_packets", bytes / 1000.0),
+        ("xmit_packets", bytes / 2000.0),
+        ("recv_errs", 0.0),
+        ("xmit_errs", 0.0),
+        ("recv_drop", 0.0),
+        ("xmit_drop", 0.0),
+    ] {
+        let _ = writeln!(
+            out,
+            "kata_guest_netdev_stat{{interface=\"eth0\",item=\"{}\"}} {}",
+            item,
+            value.floor()
+        );
+    }
+
+    let ios = elapsed * 5.0 * load;
+    out.push_str("# HELP kata_guest_diskstat Guest disk statistics\n");
+    out.push_str("# TYPE kata_guest_diskstat gauge\n");
+    for (item, value) in [
+        ("reads", ios),
+        ("writes", ios / 2.0),
+        ("sectors_read", ios * 8.0),
+        ("sectors_written", ios * 4.0),
+        ("time_reading", ios),
+        ("time_writing", ios),
+        ("time_in_progress", ios * 2.0),
+        ("weighted_time_in_progress", ios * 3.0),
+    ] {
+        let _ = writeln!(
+            out,
+            "kata_guest_diskstat{{disk=\"vda\",item=\"{}\"}} {}",
+            item,
+            value.floor()
+        );
+    }
+
+    out.push_str("# HELP kata_guest_tasks Guest tasks\n");
+    out.push_str("# TYPE kata_guest_tasks gauge\n");
+    let _ = writeln!(out, "kata_guest_tasks{{item=\"cur\"}} {}", 20 + index % 50);
+    let _ = writeln!(out, "kata_guest_tasks{{item=\"max\"}} 4096");
+
+    out.push_str("# HELP kata_guest_load Guest load average\n");
+    out.push_str("# TYPE kata_guest_load gauge\n");
+    for item in ["load1", "load5", "load15"] {
+        let _ = writeln!(out, "kata_guest_load{{item=\"{}\"}} {}", item, load);
+    }
+
+    out.push_str("# HELP kata_shim_threads Shim threads\n");
+    out.push_str("# TYPE kata_shim_threads gauge\n");
+    out.push_str("kata_shim_threads 14\n");
+    out.push_str("# HELP kata_shim_fds Shim open file descriptors\n");
+    out.push_str("# TYPE kata_shim_fds gauge\n");
+    out.push_str("kata_shim_fds 42\n");
+    out.push_str("# HELP kata_shim_proc_status Shim process status\n");
+    out.push_str("# TYPE kata_shim_proc_status gauge\n");
+    out.push_str("kata_shim_proc_status{item=\"vmrss\"} 32768000\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_sandboxes() {
+        let mock = MockSandboxes::new(3);
+        let cache = SandboxCache::new();
+        mock.populate(&cache).await;
+        let mut ids = cache.get_sandbox_list().await;
+        ids.sort();
+        assert_eq!(ids, vec!["mock-000000", "mock-000001", "mock-000002"]);
+        assert_eq!(
+            cache.get_metadata("mock-000001").await.unwrap().name,
+            "mock-pod-1"
+        );
+
+        let (metrics, errors) = mock.scrape("mock-000002", &NameFilter::default()).unwrap();
+        assert!(errors.is_empty());
+        for family in ["kata_guest_cpu_time", "kata_guest_meminfo", "kata_shim_fds"] {
+            assert!(metrics.metrics.contains_key(family), "{}", family);
+        }
+        assert!(mock.scrape("mock-000003", &NameFilter::default()).is_err());
+        assert!(mock.scrape("other", &NameFilter::default()).is_err());
+    }
+
+    #[test]
+    fn test_payload_counters_grow() {
+        let user = |elapsed| {
+            let metrics = PrometheusMetrics::parse(&payload(4, elapsed)).unwrap();
+            metrics.metrics["kata_guest_cpu_time"]
+                .samples
+                .iter()
+                .find(|s| {
+                    s.labels.get("cpu").map(|v| v.as_ref()) == Some("total")
+                        && s.labels.get("item").map(|v| v.as_ref()) == Some("user")
+                })
+                .unwrap()
+                .value
+        };
+        assert!(user(120.0) > user(60.0));
+    }
+}
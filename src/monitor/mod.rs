@@ -1,6 +1,28 @@
+pub mod agent_stats;
+pub mod cache_snapshot;
+pub mod chaos;
+pub mod conversion_pool;
 pub mod cri;
 pub mod cri_client;
+pub mod entity_info;
+pub mod fleet;
+pub mod host_cgroup;
+pub mod hypervisor_proc;
+pub mod janitor;
+pub mod k8s_pods;
 pub mod metrics_cache;
 pub mod metrics_collector;
+pub mod mock_sandboxes;
+pub mod node_info;
+pub mod pipeline_health;
+pub mod recording;
+pub mod rendered_metrics;
+pub mod sandbox_boot;
 pub mod sandbox_cache;
 pub mod sandbox_cache_manager;
+pub mod sandbox_events;
+pub mod sandbox_filter;
+pub mod sandbox_health;
+pub mod scrape_health;
+pub mod scrape_scheduler;
+pub mod supervisor;
@@ -0,0 +1,79 @@
+//! Node info - identity and topology of the node kata-pulse runs on
+//!
+//! Responsibilities:
+//! - Hold the node name (downward API `NODE_NAME`) and optional zone/region
+//! - Render them as the `kata_pulse_node_info` metric, so series can be
+//!   aggregated across nodes without external relabeling
+
+use serde::Serialize;
+
+use crate::utils::metrics_converter::cadvisor::escape_label_value;
+
+/// Node identity and topology
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct NodeInfo {
+    /// Kubernetes node name
+    pub node_name: Option<String>,
+    /// Topology zone (`topology.kubernetes.io/zone`)
+    pub zone: Option<String>,
+    /// Topology region (`topology.kubernetes.io/region`)
+    pub region: Option<String>,
+}
+
+impl NodeInfo {
+    /// Create node info, treating empty values as unset
+    pub fn new(node_name: Option<String>, zone: Option<String>, region: Option<String>) -> Self {
+        let non_empty = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+        NodeInfo {
+            node_name: non_empty(node_name),
+            zone: non_empty(zone),
+            region: non_empty(region),
+        }
+    }
+
+    /// Render `kata_pulse_node_info` in Prometheus text format
+    ///
+    /// Nothing is rendered when the node name is unknown.
+    pub fn render_metrics(&self) -> String {
+        let Some(node_name) = &self.node_name else {
+            return String::new();
+        };
+
+        let mut output = String::new();
+        output.push_str(
+            "# HELP kata_pulse_node_info Node kata-pulse runs on, with its topology labels\n",
+        );
+        output.push_str("# TYPE kata_pulse_node_info gauge\n");
+        output.push_str(&format!(
+            "kata_pulse_node_info{{node=\"{}\",zone=\"{}\",region=\"{}\"}} 1\n",
+            escape_label_value(node_name),
+            escape_label_value(self.zone.as_deref().unwrap_or_default()),
+            escape_label_value(self.region.as_deref().unwrap_or_default())
+        ));
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_node_info() {
+        let info = NodeInfo::new(
+            Some("worker-1".to_string()),
+            Some("eu-west-1a".to_string()),
+            None,
+        );
+        let output = info.render_metrics();
+        assert!(output
+            .contains(r#"kata_pulse_node_info{node="worker-1",zone="eu-west-1a",region=""} 1"#));
+    }
+
+    #[test]
+    fn test_unknown_node_renders_nothing() {
+        let info = NodeInfo::new(Some(" ".to_string()), Some("zone".to_string()), None);
+        assert_eq!(info.node_name, None);
+        assert!(info.render_metrics().is_empty());
+    }
+}
@@ -0,0 +1,280 @@
+//! Pipeline health - explicit states of the background components
+//!
+//! Responsibilities:
+//! - Track the state of the sandbox cache manager and metrics collector, and
+//!   of the optional background tasks
+//! - Decide readiness of the agent (`/readyz`)
+//! - Render component states as internal metrics
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Background components of the metrics pipeline
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Component {
+    /// Directory monitoring + CRI metadata sync
+    SandboxCacheManager,
+    /// Periodic metrics collection
+    MetricsCollector,
+    /// Removal of leftover sandbox directories (optional, not needed for readiness)
+    Janitor,
+    /// Guest agent statistics collection (optional, not needed for readiness)
+    AgentStats,
+    /// Boot duration tracking of new sandboxes (optional, not needed for readiness)
+    BootTracker,
+    /// Kubernetes pod watch enriching the sandboxes (optional, not needed for readiness)
+    PodWatch,
+}
+
+impl Component {
+    /// Components every agent runs, which readiness waits for, in reporting order
+    pub const ALL: [Component; 2] = [Component::SandboxCacheManager, Component::MetricsCollector];
+
+    /// Name used in JSON and metric labels
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Component::SandboxCacheManager => "sandbox_cache_manager",
+            Component::MetricsCollector => "metrics_collector",
+            Component::Janitor => "janitor",
+            Component::AgentStats => "agent_stats",
+            Component::BootTracker => "boot_tracker",
+            Component::PodWatch => "pod_watch",
+        }
+    }
+}
+
+/// State of a pipeline component
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentState {
+    /// Not done with its first iteration yet
+    #[default]
+    Starting,
+    /// Working normally
+    Healthy,
+    /// Running, but with errors (e.g. CRI unreachable, all scrapes failing)
+    Degraded,
+    /// Stopped with an error
+    Failed,
+}
+
+impl ComponentState {
+    /// All states, in reporting order
+    pub const ALL: [ComponentState; 4] = [
+        ComponentState::Starting,
+        ComponentState::Healthy,
+        ComponentState::Degraded,
+        ComponentState::Failed,
+    ];
+
+    /// Name used in JSON and metric labels
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ComponentState::Starting => "starting",
+            ComponentState::Healthy => "healthy",
+            ComponentState::Degraded => "degraded",
+            ComponentState::Failed => "failed",
+        }
+    }
+}
+
+/// Current status of a pipeline component
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ComponentStatus {
+    /// Current state
+    pub state: ComponentState,
+    /// Reason for the current state (errors for degraded/failed)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// When the component entered the current state (ms since the Unix epoch)
+    pub since_unix_ms: u64,
+}
+
+/// Thread-safe registry of component states
+#[derive(Clone)]
+pub struct PipelineHealth {
+    components: Arc<RwLock<BTreeMap<Component, ComponentStatus>>>,
+}
+
+impl PipelineHealth {
+    /// Create a registry with all components starting
+    pub fn new() -> Self {
+        let now = unix_now_ms();
+        let components = Component::ALL
+            .iter()
+            .map(|component| {
+                (
+                    *component,
+                    ComponentStatus {
+                        since_unix_ms: now,
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        PipelineHealth {
+            components: Arc::new(RwLock::new(components)),
+        }
+    }
+
+    /// Update the state of a component
+    ///
+    /// State changes are logged; repeated reports of the same state only update the message.
+    pub fn set(&self, component: Component, state: ComponentState, message: Option<String>) {
+        let mut components = self.components.write().unwrap_or_else(|e| e.into_inner());
+        let status = components.entry(component).or_default();
+        if status.state != state {
+            match state {
+                ComponentState::Degraded | ComponentState::Failed => {
+                    warn!(component = component.as_str(), from = status.state.as_str(), to = state.as_str(), message = ?message, "Pipeline component state changed")
+                }
+                _ => {
+                    info!(
+                        component = component.as_str(),
+                        from = status.state.as_str(),
+                        to = state.as_str(),
+                        "Pipeline component state changed"
+                    )
+                }
+            }
+            status.state = state;
+            status.since_unix_ms = unix_now_ms();
+        }
+        status.message = message;
+    }
+
+    /// Mark a component healthy
+    pub fn healthy(&self, component: Component) {
+        self.set(component, ComponentState::Healthy, None);
+    }
+
+    /// Mark a component degraded with a reason
+    pub fn degraded(&self, component: Component, message: impl Into<String>) {
+        self.set(component, ComponentState::Degraded, Some(message.into()));
+    }
+
+    /// Mark a component failed with a reason
+    pub fn failed(&self, component: Component, message: impl Into<String>) {
+        self.set(component, ComponentState::Failed, Some(message.into()));
+    }
+
+    /// Snapshot of all component statuses
+    pub fn snapshot(&self) -> BTreeMap<Component, ComponentStatus> {
+        self.components
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Whether the agent is ready to serve metrics
+    ///
+    /// Degraded components still serve (possibly partial) data; starting or
+    /// failed ones do not. Optional components don't affect readiness.
+    pub fn is_ready(&self) -> bool {
+        self.snapshot()
+            .iter()
+            .filter(|(component, _)| Component::ALL.contains(component))
+            .all(|(_, status)| {
+                matches!(
+                    status.state,
+                    ComponentState::Healthy | ComponentState::Degraded
+                )
+            })
+    }
+
+    /// Render component states in Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        let mut output = String::new();
+        output.push_str(
+            "# HELP kata_pulse_component_state State of kata-pulse pipeline components (1 for the current state)\n",
+        );
+        output.push_str("# TYPE kata_pulse_component_state gauge\n");
+        for (component, status) in self.snapshot() {
+            for state in ComponentState::ALL {
+                output.push_str(&format!(
+                    "kata_pulse_component_state{{component=\"{}\",state=\"{}\"}} {}\n",
+                    component.as_str(),
+                    state.as_str(),
+                    if status.state == state { 1 } else { 0 }
+                ));
+            }
+        }
+        output
+    }
+}
+
+impl Default for PipelineHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Current wall-clock time in milliseconds since the Unix epoch
+pub(crate) fn unix_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starts_not_ready() {
+        let health = PipelineHealth::new();
+        assert!(!health.is_ready());
+        assert_eq!(
+            health.snapshot()[&Component::MetricsCollector].state,
+            ComponentState::Starting
+        );
+    }
+
+    #[test]
+    fn test_readiness_transitions() {
+        let health = PipelineHealth::new();
+        health.healthy(Component::SandboxCacheManager);
+        assert!(!health.is_ready());
+
+        health.healthy(Component::MetricsCollector);
+        assert!(health.is_ready());
+
+        health.degraded(Component::SandboxCacheManager, "CRI unreachable");
+        assert!(health.is_ready());
+        assert_eq!(
+            health.snapshot()[&Component::SandboxCacheManager]
+                .message
+                .as_deref(),
+            Some("CRI unreachable")
+        );
+
+        health.failed(Component::MetricsCollector, "task exited");
+        assert!(!health.is_ready());
+
+        health.healthy(Component::MetricsCollector);
+        health.failed(Component::Janitor, "task exited");
+        assert!(health.is_ready());
+    }
+
+    #[test]
+    fn test_render_metrics() {
+        let health = PipelineHealth::new();
+        health.degraded(Component::MetricsCollector, "all scrapes failed");
+
+        let output = health.render_metrics();
+        assert!(output.contains(
+            r#"kata_pulse_component_state{component="metrics_collector",state="degraded"} 1"#
+        ));
+        assert!(output.contains(
+            r#"kata_pulse_component_state{component="metrics_collector",state="healthy"} 0"#
+        ));
+        assert!(output.contains(
+            r#"kata_pulse_component_state{component="sandbox_cache_manager",state="starting"} 1"#
+        ));
+    }
+}
@@ -0,0 +1,320 @@
+//! Recording - save raw shim payloads and replay them through the pipeline
+//!
+//! Responsibilities:
+//! - Record every payload scraped from a shim, as received, under
+//!   `<dir>/<sandbox-id>/<unix-ms>.prom`, with the pod metadata of the
+//!   sandbox in `<dir>/<sandbox-id>/metadata.json`
+//! - Keep at most a configured number of payloads per sandbox
+//! - Replay a recording: register its sandboxes in the sandbox cache in
+//!   place of the directory monitoring and CRI sync, and serve their payloads
+//!   in recorded order (one per scrape, starting over after the last)
+//!
+//! A recording makes a conversion bug reproducible from a report and lets
+//! the pipeline be benchmarked on production-shaped data.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::pipeline_health::{Component, PipelineHealth};
+use super::sandbox_cache::{MetadataStatus, SandboxCRIMetadata, SandboxCache};
+
+/// Default number of payloads kept per sandbox by the recorder
+pub const DEFAULT_RECORD_MAX_FILES: usize = 360;
+
+/// Extension of the recorded payloads
+const PAYLOAD_EXTENSION: &str = "prom";
+
+/// File holding the pod metadata of a recorded sandbox
+const METADATA_FILE: &str = "metadata.json";
+
+/// Namespace of replayed sandboxes recorded without metadata
+const REPLAY_NAMESPACE: &str = "kata-pulse-replay";
+
+/// Saves the raw payloads of the scraped shims
+pub struct PayloadRecorder {
+    dir: PathBuf,
+    max_files: usize,
+    /// Metadata last written per sandbox, to only rewrite it when it changes
+    written_metadata: Mutex<HashMap<String, SandboxCRIMetadata>>,
+}
+
+impl PayloadRecorder {
+    /// Record under `dir`, keeping the last `max_files` payloads per sandbox (0 = all)
+    pub fn new(dir: PathBuf, max_files: usize) -> Self {
+        PayloadRecorder {
+            dir,
+            max_files,
+            written_metadata: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Directory of the recording
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Save a payload scraped from the shim of a sandbox
+    pub async fn record(
+        &self,
+        sandbox_id: &str,
+        payload: &[u8],
+        metadata: Option<SandboxCRIMetadata>,
+    ) -> Result<()> {
+        let dir = self.dir.join(sandbox_id);
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = dir.join(format!("{}.{}", timestamp_ms, PAYLOAD_EXTENSION));
+        tokio::fs::write(&path, payload)
+            .await
+            .with_context(|| format!("failed to write {}", path.display()))?;
+
+        if let Some(metadata) = metadata {
+            let changed = self
+                .written_metadata
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(sandbox_id)
+                != Some(&metadata);
+            if changed {
+                let path = dir.join(METADATA_FILE);
+                tokio::fs::write(&path, serde_json::to_vec_pretty(&metadata)?)
+                    .await
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+                self.written_metadata
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(sandbox_id.to_string(), metadata);
+            }
+        }
+
+        if self.max_files > 0 {
+            let payloads = payload_files(&dir)?;
+            let excess = payloads.len().saturating_sub(self.max_files);
+            for (_, old) in payloads.into_iter().take(excess) {
+                if let Err(e) = tokio::fs::remove_file(&old).await {
+                    debug!(path = %old.display(), error = %e, "Failed to remove old recorded payload");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Recorded payloads in a sandbox directory, oldest first
+fn payload_files(dir: &Path) -> Result<Vec<(u128, PathBuf)>> {
+    let mut payloads: Vec<(u128, PathBuf)> = std::fs::read_dir(dir)
+        .with_context(|| format!("failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == PAYLOAD_EXTENSION))
+        .filter_map(|path| {
+            let timestamp = path.file_stem()?.to_str()?.parse().ok()?;
+            Some((timestamp, path))
+        })
+        .collect();
+    payloads.sort();
+    Ok(payloads)
+}
+
+/// A recorded sandbox
+struct ReplayedSandbox {
+    metadata: SandboxCRIMetadata,
+    payloads: Vec<PathBuf>,
+    /// Index of the payload served by the next scrape
+    next: AtomicUsize,
+}
+
+/// Serves the payloads of a recording instead of scraping shims
+pub struct PayloadReplay {
+    dir: PathBuf,
+    sandboxes: BTreeMap<String, ReplayedSandbox>,
+}
+
+impl PayloadReplay {
+    /// Load the recording in `dir`; sandboxes without payloads are skipped
+    pub fn load(dir: PathBuf) -> Result<Self> {
+        let mut sandboxes = BTreeMap::new();
+        let entries =
+            std::fs::read_dir(&dir).with_context(|| format!("failed to read {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            let Some(sandbox_id) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if !path.is_dir() {
+                continue;
+            }
+            let payloads: Vec<PathBuf> = payload_files(&path)?
+                .into_iter()
+                .map(|(_, path)| path)
+                .collect();
+            if payloads.is_empty() {
+                warn!(path = %path.display(), "No recorded payloads, skipping sandbox");
+                continue;
+            }
+            let metadata =
+                read_metadata(&path.join(METADATA_FILE))?.unwrap_or_else(|| SandboxCRIMetadata {
+                    name: sandbox_id.to_string(),
+                    namespace: REPLAY_NAMESPACE.to_string(),
+                    ..Default::default()
+                });
+            sandboxes.insert(
+                sandbox_id.to_string(),
+                ReplayedSandbox {
+                    metadata: SandboxCRIMetadata {
+                        metadata_status: MetadataStatus::Synced,
+                        ..metadata
+                    },
+                    payloads,
+                    next: AtomicUsize::new(0),
+                },
+            );
+        }
+        if sandboxes.is_empty() {
+            anyhow::bail!("no recorded sandboxes in {}", dir.display());
+        }
+        Ok(PayloadReplay { dir, sandboxes })
+    }
+
+    /// IDs of the recorded sandboxes
+    pub fn sandbox_ids(&self) -> impl Iterator<Item = &str> {
+        self.sandboxes.keys().map(String::as_str)
+    }
+
+    /// Register the recorded sandboxes in `cache`
+    pub async fn populate(&self, cache: &SandboxCache) {
+        for (id, sandbox) in &self.sandboxes {
+            cache.set_cri_metadata(id, sandbox.metadata.clone()).await;
+        }
+    }
+
+    /// Register the recorded sandboxes, then idle until `cancel` fires
+    ///
+    /// Runs in place of the sandbox cache manager.
+    pub async fn start(
+        &self,
+        cache: Arc<SandboxCache>,
+        pipeline_health: Arc<PipelineHealth>,
+        cancel: CancellationToken,
+    ) -> Result<()> {
+        self.populate(&cache).await;
+        info!(
+            dir = %self.dir.display(),
+            sandboxes = self.sandboxes.len(),
+            payloads = self.sandboxes.values().map(|s| s.payloads.len()).sum::<usize>(),
+            "Replaying recorded shim payloads"
+        );
+        pipeline_health.healthy(Component::SandboxCacheManager);
+        cancel.cancelled().await;
+        Ok(())
+    }
+
+    /// The next recorded payload of a sandbox, starting over after the last one
+    pub async fn next_payload(&self, sandbox_id: &str) -> Result<String> {
+        let sandbox = self
+            .sandboxes
+            .get(sandbox_id)
+            .ok_or_else(|| anyhow::anyhow!("sandbox {} is not in the recording", sandbox_id))?;
+        let index = sandbox.next.fetch_add(1, Ordering::Relaxed) % sandbox.payloads.len();
+        let path = &sandbox.payloads[index];
+        let data = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+}
+
+/// Read the recorded metadata of a sandbox, if it was recorded
+fn read_metadata(path: &Path) -> Result<Option<SandboxCRIMetadata>> {
+    match std::fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data)
+            .map(Some)
+            .with_context(|| format!("invalid metadata in {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(name: &str) -> SandboxCRIMetadata {
+        SandboxCRIMetadata {
+            uid: format!("uid-{}", name),
+            name: name.to_string(),
+            namespace: "default".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = PayloadRecorder::new(dir.path().to_path_buf(), 0);
+        for payload in ["first 1\n", "second 2\n"] {
+            recorder
+                .record("sb-1", payload.as_bytes(), Some(metadata("pod-1")))
+                .await
+                .unwrap();
+            // Payloads are named by their millisecond timestamp
+            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        }
+        recorder.record("sb-2", b"other 3\n", None).await.unwrap();
+
+        let replay = PayloadReplay::load(dir.path().to_path_buf()).unwrap();
+        assert_eq!(
+            replay.sandbox_ids().collect::<Vec<_>>(),
+            vec!["sb-1", "sb-2"]
+        );
+        assert_eq!(replay.next_payload("sb-1").await.unwrap(), "first 1\n");
+        assert_eq!(replay.next_payload("sb-1").await.unwrap(), "second 2\n");
+        assert_eq!(replay.next_payload("sb-1").await.unwrap(), "first 1\n");
+        assert!(replay.next_payload("sb-3").await.is_err());
+
+        let cache = SandboxCache::new();
+        replay.populate(&cache).await;
+        let recorded = cache.get_metadata("sb-1").await.unwrap();
+        assert_eq!(recorded.name, "pod-1");
+        assert_eq!(recorded.metadata_status, MetadataStatus::Synced);
+        let unrecorded = cache.get_metadata("sb-2").await.unwrap();
+        assert_eq!(unrecorded.name, "sb-2");
+        assert_eq!(unrecorded.namespace, REPLAY_NAMESPACE);
+    }
+
+    #[tokio::test]
+    async fn test_recorder_keeps_the_last_payloads() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorder = PayloadRecorder::new(dir.path().to_path_buf(), 2);
+        for index in 0..4 {
+            recorder
+                .record("sb-1", format!("m {}\n", index).as_bytes(), None)
+                .await
+                .unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        }
+
+        let replay = PayloadReplay::load(dir.path().to_path_buf()).unwrap();
+        assert_eq!(replay.next_payload("sb-1").await.unwrap(), "m 2\n");
+        assert_eq!(replay.next_payload("sb-1").await.unwrap(), "m 3\n");
+    }
+
+    #[test]
+    fn test_empty_recording_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("sb-empty")).unwrap();
+        assert!(PayloadReplay::load(dir.path().to_path_buf()).is_err());
+        assert!(PayloadReplay::load(dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Pre-rendered aggregated metrics
+//!
+//! Responsibilities:
+//! - Render the converted metrics of all served sandboxes once, right after
+//!   each buffer swap, instead of on every `/metrics` request
+//! - Hand out the rendered text while the published buffer, the settings and
+//!   the sandbox metadata it was rendered from are current; render again on
+//!   demand otherwise (metadata synced, sandbox removed, settings reloaded,
+//!   entries past the TTL)
+//! - Convert the sandboxes on the blocking workers of the conversion pool
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::{debug, warn};
+
+use super::conversion_pool::{ConversionJob, ConversionPool};
+use super::metrics_cache::{CachedMetrics, MetricsCache};
+use super::sandbox_cache::SandboxCache;
+use crate::config::reload::{ReloadableConfig, SharedConfig};
+use crate::utils::interner;
+use crate::utils::metrics_converter::cadvisor::PrometheusFormat;
+use crate::utils::metrics_converter::LabelEnricher;
+use crate::utils::prometheus_parser::PrometheusMetrics;
+
+/// Aggregated sandbox metrics, rendered once per published buffer
+pub struct RenderedMetrics {
+    sandbox_cache: Arc<SandboxCache>,
+    metrics_cache: Arc<MetricsCache>,
+    config: Arc<SharedConfig>,
+    enricher: Arc<dyn LabelEnricher>,
+    pool: ConversionPool,
+    current: Mutex<Option<Rendered>>,
+}
+
+/// Rendered text with the inputs it was rendered from
+struct Rendered {
+    /// Published buffer (compared by identity)
+    buffer: Arc<HashMap<String, CachedMetrics>>,
+    /// Settings (compared by identity)
+    config: Arc<ReloadableConfig>,
+    /// Sandbox cache version
+    sandbox_version: u64,
+    /// When the oldest rendered entry goes past the TTL
+    expires_at: Option<Instant>,
+    text: Arc<str>,
+}
+
+impl RenderedMetrics {
+    /// Render the metrics of `metrics_cache` for the sandboxes of `sandbox_cache`
+    pub fn new(
+        sandbox_cache: Arc<SandboxCache>,
+        metrics_cache: Arc<MetricsCache>,
+        config: Arc<SharedConfig>,
+        enricher: Arc<dyn LabelEnricher>,
+    ) -> Self {
+        RenderedMetrics {
+            sandbox_cache,
+            metrics_cache,
+            config,
+            enricher,
+            pool: ConversionPool::default(),
+            current: Mutex::new(None),
+        }
+    }
+
+    /// Convert on `pool` instead of a pool with one worker per CPU
+    pub fn with_conversion_pool(mut self, pool: ConversionPool) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Render the current buffer ahead of the next request
+    ///
+    /// Called by the collector after every buffer swap.
+    pub async fn refresh(&self) {
+        self.get().await;
+    }
+
+    /// Aggregated converted metrics of all served sandboxes
+    ///
+    /// Reuses the last render while its inputs are current.
+    pub async fn get(&self) -> Arc<str> {
+        let buffer = self.metrics_cache.published().await;
+        let config = self.config.load();
+        let sandbox_version = self.sandbox_cache.version();
+        let previous_len = {
+            let current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(rendered) = current.as_ref().filter(|rendered| {
+                Arc::ptr_eq(&rendered.buffer, &buffer)
+                    && Arc::ptr_eq(&rendered.config, &config)
+                    && rendered.sandbox_version == sandbox_version
+                    && rendered
+                        .expires_at
+                        .is_none_or(|expires_at| Instant::now() < expires_at)
+            }) {
+                return rendered.text.clone();
+            }
+            current.as_ref().map_or(0, |rendered| rendered.text.len())
+        };
+
+        let (text, expires_at) = self.render(&buffer, &config, previous_len).await;
+        let text: Arc<str> = text.into();
+        debug!(output_size = text.len(), "Rendered aggregated metrics");
+        *self.current.lock().unwrap_or_else(|e| e.into_inner()) = Some(Rendered {
+            buffer,
+            config,
+            sandbox_version,
+            expires_at,
+            text: text.clone(),
+        });
+        text
+    }
+
+    /// Render the converted metrics of every served sandbox in `buffer`
+    ///
+    /// Returns the text and when its oldest entry goes past the TTL. The output
+    /// is preallocated with the length of the previous render.
+    async fn render(
+        &self,
+        buffer: &HashMap<String, CachedMetrics>,
+        config: &Arc<ReloadableConfig>,
+        previous_len: usize,
+    ) -> (String, Option<Instant>) {
+        let sandboxes = self.sandbox_cache.get_sandboxes_with_metadata().await;
+        let ttl = self.metrics_cache.ttl();
+
+        let mut expires_at: Option<Instant> = None;
+        let mut jobs = Vec::new();
+        for (sandbox_id, metadata) in sandboxes {
+            if !config.sandbox_filter.allows(&metadata) {
+                continue;
+            }
+            debug!(sandbox_id = %sandbox_id, "Processing metrics for sandbox");
+
+            let Some(cached_metrics) = buffer
+                .get(&sandbox_id)
+                .filter(|cached| !self.metrics_cache.is_stale(cached))
+            else {
+                warn!(sandbox_id = %sandbox_id, "No cached metrics available for sandbox");
+                continue;
+            };
+            if let Some(ttl) = ttl {
+                let expiry = cached_metrics.collected_at + ttl;
+                expires_at = Some(expires_at.map_or(expiry, |earliest| earliest.min(expiry)));
+            }
+
+            let labels = self.enricher.enrich(&sandbox_id).await;
+            jobs.push(ConversionJob {
+                metrics: cached_metrics.clone(),
+                sandbox_id,
+                labels,
+            });
+        }
+
+        // Convert on the blocking workers, then assemble in sandbox order
+        let sandboxes: Vec<_> = jobs
+            .iter()
+            .map(|job| (job.sandbox_id.clone(), job.metrics.metrics.clone()))
+            .collect();
+        let converted = self.pool.convert(config, jobs).await;
+
+        let mut output = String::with_capacity(previous_len);
+        let mut raw_fallback = PrometheusMetrics::new();
+        for ((sandbox_id, metrics), result) in sandboxes.iter().zip(converted) {
+            match result {
+                Ok(text) => {
+                    debug!(sandbox_id = %sandbox_id, "Successfully converted to cAdvisor format");
+                    output.push_str(&text);
+                }
+                Err(e) => {
+                    warn!(sandbox_id = %sandbox_id, error = %e, "Failed to convert metrics, falling back to raw format");
+                    merge_raw_fallback(&mut raw_fallback, sandbox_id, metrics);
+                }
+            }
+            output.push('\n');
+        }
+
+        // Raw families are declared once, however many sandboxes fell back
+        if !raw_fallback.metrics.is_empty() {
+            raw_fallback.write_prometheus(&mut output, None);
+            output.push('\n');
+        }
+        (output, expires_at)
+    }
+}
+
+/// Add the raw metrics of a sandbox whose conversion failed to the raw output
+///
+/// Samples are labelled with the sandbox ID so the series of different
+/// sandboxes do not collide.
+fn merge_raw_fallback(raw: &mut PrometheusMetrics, sandbox_id: &str, metrics: &PrometheusMetrics) {
+    let mut metrics = metrics.clone();
+    let mut labels = interner::labels().batch();
+    let (label, value) = (labels.intern("sandbox_id"), labels.intern(sandbox_id));
+    for sample in metrics
+        .metrics
+        .values_mut()
+        .flat_map(|metric| metric.samples.iter_mut())
+    {
+        sample.labels.insert(label.clone(), value.clone());
+    }
+
+    for conflict in raw.merge(metrics) {
+        warn!(sandbox_id = %sandbox_id, conflict = %conflict, "Conflicting raw metric family");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::sandbox_cache::SandboxCRIMetadata;
+    use crate::utils::metrics_converter::CRILabelEnricher;
+
+    #[tokio::test]
+    async fn test_render_is_reused_until_inputs_change() {
+        let sandbox_cache = Arc::new(SandboxCache::new());
+        let metrics_cache = Arc::new(MetricsCache::new());
+        let config = Arc::new(SharedConfig::new(ReloadableConfig {
+            metrics_interval_secs: 60,
+            sandbox_filter: Default::default(),
+            name_filter: Default::default(),
+            conversion: Default::default(),
+        }));
+        let enricher = Arc::new(CRILabelEnricher::new(sandbox_cache.clone()));
+        let rendered = RenderedMetrics::new(
+            sandbox_cache.clone(),
+            metrics_cache.clone(),
+            config,
+            enricher,
+        );
+
+        sandbox_cache
+            .set_cri_metadata("sb-1", SandboxCRIMetadata::default())
+            .await;
+        metrics_cache.start_collection().await;
+        metrics_cache
+            .add_metrics(
+                "sb-1".to_string(),
+                PrometheusMetrics::parse("kata_guest_cpu_time{cpu=\"total\",item=\"user\"} 100\n")
+                    .unwrap(),
+            )
+            .await;
+        metrics_cache.finish_collection().await;
+
+        rendered.refresh().await;
+        let first = rendered.get().await;
+        assert!(first.contains("container_cpu_usage_seconds_total"));
+        assert!(Arc::ptr_eq(&first, &rendered.get().await));
+
+        // Pod metadata synced after the swap shows up without a new cycle
+        sandbox_cache
+            .set_cri_metadata(
+                "sb-1",
+                SandboxCRIMetadata {
+                    name: "my-pod".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await;
+        let synced = rendered.get().await;
+        assert!(synced.contains("pod=\"my-pod\""));
+
+        // Removed sandboxes disappear at once
+        metrics_cache
+            .delete_metrics("sb-1", SandboxCRIMetadata::default())
+            .await;
+        assert!(!rendered
+            .get()
+            .await
+            .contains("container_cpu_usage_seconds_total"));
+    }
+}
@@ -0,0 +1,332 @@
+//! Sandbox boot - time from sandbox creation to a ready guest
+//!
+//! Responsibilities:
+//! - Notice sandboxes created while kata-pulse runs (sandboxes found at
+//!   startup have booted long before and are not timed)
+//! - Poll the shim of each booting sandbox until its metrics carry guest
+//!   (`kata_guest_*`) families, i.e. the kata-agent in the VM answers
+//! - Observe the time from the creation of the sandbox directory to that
+//!   point in `kata_sandbox_boot_duration_seconds`, by hypervisor
+//! - Count the sandboxes that never got ready within the boot timeout
+//!
+//! The periodic scrapes are spread over the collection interval, so the
+//! first of them says little about the boot time; the tracker polls booting
+//! sandboxes every second instead.
+
+use futures::future::join_all;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, warn};
+
+use super::entity_info::hypervisor_of;
+use super::sandbox_cache::SandboxCache;
+use crate::config::resolve_shim_socket;
+use crate::utils::metrics_converter::cadvisor::escape_label_value;
+use crate::utils::shim_client::ShimClient;
+
+/// Time between two polls of the booting sandboxes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Time a sandbox gets to become ready before it counts as a boot timeout
+const BOOT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Upper bounds (seconds) of the boot duration histogram buckets
+const BUCKETS: [f64; 12] = [
+    0.5, 1.0, 2.0, 3.0, 5.0, 7.5, 10.0, 15.0, 20.0, 30.0, 60.0, 120.0,
+];
+
+/// Prefix of the guest metric families, present once the agent answers
+const GUEST_METRICS_PREFIX: &str = "kata_guest_";
+
+/// A sandbox waiting for its guest to become ready
+#[derive(Clone, Debug)]
+struct Booting {
+    /// Creation of the sandbox directory (when first seen if unknown)
+    created: SystemTime,
+    /// When the tracker first saw the sandbox
+    seen: Instant,
+}
+
+/// Histogram of boot durations
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Histogram {
+    /// Observations per bucket of [`BUCKETS`] (not cumulative), then +Inf
+    counts: [u64; BUCKETS.len() + 1],
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        let bucket = BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += seconds;
+    }
+}
+
+/// Times the boot of the sandboxes created while running
+pub struct BootTracker {
+    sandbox_cache: Arc<SandboxCache>,
+    shim_client: Arc<ShimClient>,
+    /// Sandboxes seen so far; None until the first sweep
+    known: Mutex<Option<HashSet<String>>>,
+    booting: Mutex<HashMap<String, Booting>>,
+    /// Boot durations by hypervisor
+    durations: Mutex<BTreeMap<String, Histogram>>,
+    timeouts: AtomicU64,
+}
+
+impl BootTracker {
+    /// Create a tracker of the sandboxes of `sandbox_cache`
+    pub fn new(sandbox_cache: Arc<SandboxCache>, shim_client: Arc<ShimClient>) -> Self {
+        BootTracker {
+            sandbox_cache,
+            shim_client,
+            known: Mutex::new(None),
+            booting: Mutex::new(HashMap::new()),
+            durations: Mutex::new(BTreeMap::new()),
+            timeouts: AtomicU64::new(0),
+        }
+    }
+
+    /// Poll the booting sandboxes every second until `cancel` fires
+    pub async fn start(&self, cancel: CancellationToken) {
+        info!("Starting the sandbox boot tracker");
+        loop {
+            self.sweep(Instant::now()).await;
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = sleep(POLL_INTERVAL) => {}
+            }
+        }
+    }
+
+    /// Track new sandboxes and poll the booting ones
+    async fn sweep(&self, now: Instant) {
+        let ids: HashSet<String> = self
+            .sandbox_cache
+            .get_sandbox_list()
+            .await
+            .into_iter()
+            .collect();
+        let booting: Vec<(String, Booting)> = {
+            let mut known = self.known.lock().unwrap_or_else(|e| e.into_inner());
+            let mut booting = self.booting.lock().unwrap_or_else(|e| e.into_inner());
+            match known.as_mut() {
+                // Sandboxes found at startup booted before kata-pulse ran
+                None => *known = Some(ids.clone()),
+                Some(known) => {
+                    for id in ids.difference(known) {
+                        let created = resolve_shim_socket(id)
+                            .ok()
+                            .and_then(|(_, socket)| socket.parent().and_then(created_at))
+                            .unwrap_or_else(SystemTime::now);
+                        booting.insert(id.clone(), Booting { created, seen: now });
+                    }
+                    *known = ids.clone();
+                }
+            }
+            booting.retain(|id, _| ids.contains(id));
+            booting
+                .iter()
+                .map(|(id, booting)| (id.clone(), booting.clone()))
+                .collect()
+        };
+
+        let results = join_all(booting.into_iter().map(|(id, booting)| async move {
+            let ready = self.guest_ready(&id).await;
+            (id, booting, ready)
+        }))
+        .await;
+        for (id, booting, ready) in results {
+            if ready {
+                let duration = SystemTime::now()
+                    .duration_since(booting.created)
+                    .unwrap_or_default();
+                self.record(&id, duration).await;
+            } else if now.saturating_duration_since(booting.seen) >= BOOT_TIMEOUT {
+                warn!(sandbox_id = %id, timeout = ?BOOT_TIMEOUT, "Sandbox guest did not become ready");
+                self.timeouts.fetch_add(1, Ordering::Relaxed);
+            } else {
+                continue;
+            }
+            self.booting
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&id);
+        }
+    }
+
+    /// Whether the shim of a sandbox reports guest metrics (the agent answers)
+    async fn guest_ready(&self, id: &str) -> bool {
+        let Ok((_, socket)) = resolve_shim_socket(id) else {
+            return false;
+        };
+        match self
+            .shim_client
+            .get(id, &socket, "/metrics", POLL_INTERVAL)
+            .await
+        {
+            Ok(body) => String::from_utf8_lossy(&body).contains(GUEST_METRICS_PREFIX),
+            Err(e) => {
+                debug!(sandbox_id = %id, error = %format!("{:#}", e), "Booting sandbox not ready");
+                false
+            }
+        }
+    }
+
+    /// Observe the boot duration of a sandbox
+    async fn record(&self, id: &str, duration: Duration) {
+        let runtime_handler = self
+            .sandbox_cache
+            .get_metadata(id)
+            .await
+            .map(|metadata| metadata.runtime_handler)
+            .unwrap_or_default();
+        let hypervisor = hypervisor_of(&runtime_handler);
+        info!(sandbox_id = %id, hypervisor, duration_ms = duration.as_millis() as u64, "Sandbox guest ready");
+        self.durations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(hypervisor.to_string())
+            .or_default()
+            .observe(duration.as_secs_f64());
+    }
+
+    /// Render the boot durations in Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        let mut output = String::new();
+        output.push_str("# HELP kata_sandbox_boot_duration_seconds Time from sandbox creation until the guest agent answers\n");
+        output.push_str("# TYPE kata_sandbox_boot_duration_seconds histogram\n");
+        for (hypervisor, histogram) in self
+            .durations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            let hypervisor = escape_label_value(hypervisor);
+            let mut cumulative = 0;
+            for (bound, count) in BUCKETS.iter().zip(histogram.counts) {
+                cumulative += count;
+                let _ = writeln!(
+                    output,
+                    "kata_sandbox_boot_duration_seconds_bucket{{hypervisor=\"{}\",le=\"{}\"}} {}",
+                    hypervisor, bound, cumulative
+                );
+            }
+            cumulative += histogram.counts[BUCKETS.len()];
+            let _ = writeln!(
+                output,
+                "kata_sandbox_boot_duration_seconds_bucket{{hypervisor=\"{}\",le=\"+Inf\"}} {}",
+                hypervisor, cumulative
+            );
+            let _ = writeln!(
+                output,
+                "kata_sandbox_boot_duration_seconds_sum{{hypervisor=\"{}\"}} {}",
+                hypervisor, histogram.sum
+            );
+            let _ = writeln!(
+                output,
+                "kata_sandbox_boot_duration_seconds_count{{hypervisor=\"{}\"}} {}",
+                hypervisor, cumulative
+            );
+        }
+        output.push_str("# HELP kata_pulse_sandbox_boot_timeouts_total Sandboxes whose guest did not become ready within 5 minutes\n");
+        output.push_str("# TYPE kata_pulse_sandbox_boot_timeouts_total counter\n");
+        let _ = writeln!(
+            output,
+            "kata_pulse_sandbox_boot_timeouts_total {}",
+            self.timeouts.load(Ordering::Relaxed)
+        );
+        output
+    }
+}
+
+/// Creation time of a directory (its modification time if unsupported)
+fn created_at(dir: &Path) -> Option<SystemTime> {
+    let metadata = std::fs::metadata(dir).ok()?;
+    metadata.created().or_else(|_| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitor::sandbox_cache::SandboxCRIMetadata;
+    use crate::utils::shim_client::ShimClientConfig;
+
+    fn tracker() -> BootTracker {
+        BootTracker::new(
+            Arc::new(SandboxCache::new()),
+            Arc::new(ShimClient::new(ShimClientConfig::default())),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_render_boot_durations() {
+        let tracker = tracker();
+        tracker
+            .sandbox_cache
+            .put_if_not_exists(
+                "sb-1",
+                SandboxCRIMetadata {
+                    runtime_handler: "kata-clh".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await;
+        tracker.record("sb-1", Duration::from_millis(2500)).await;
+        tracker.record("sb-2", Duration::from_secs(600)).await;
+
+        let output = tracker.render_metrics();
+        assert!(output.contains("# TYPE kata_sandbox_boot_duration_seconds histogram\n"));
+        assert!(output.contains(
+            "kata_sandbox_boot_duration_seconds_bucket{hypervisor=\"cloud-hypervisor\",le=\"2\"} 0\n"
+        ));
+        assert!(output.contains(
+            "kata_sandbox_boot_duration_seconds_bucket{hypervisor=\"cloud-hypervisor\",le=\"3\"} 1\n"
+        ));
+        assert!(output.contains(
+            "kata_sandbox_boot_duration_seconds_sum{hypervisor=\"cloud-hypervisor\"} 2.5\n"
+        ));
+        // Unknown hypervisor, beyond the last bucket
+        assert!(output
+            .contains("kata_sandbox_boot_duration_seconds_bucket{hypervisor=\"\",le=\"120\"} 0\n"));
+        assert!(output.contains("kata_sandbox_boot_duration_seconds_count{hypervisor=\"\"} 1\n"));
+        assert!(output.contains("kata_pulse_sandbox_boot_timeouts_total 0\n"));
+    }
+
+    #[tokio::test]
+    async fn test_only_new_sandboxes_are_timed() {
+        let tracker = tracker();
+        let metadata = SandboxCRIMetadata::default();
+        tracker
+            .sandbox_cache
+            .put_if_not_exists("at-startup", metadata.clone())
+            .await;
+        let start = Instant::now();
+        tracker.sweep(start).await;
+        assert!(tracker.booting.lock().unwrap().is_empty());
+
+        tracker
+            .sandbox_cache
+            .put_if_not_exists("created", metadata)
+            .await;
+        tracker.sweep(start).await;
+        assert!(tracker.booting.lock().unwrap().contains_key("created"));
+
+        // No shim answers: a boot timeout after 5 minutes
+        tracker.sweep(start + BOOT_TIMEOUT).await;
+        assert!(tracker.booting.lock().unwrap().is_empty());
+        assert!(tracker
+            .render_metrics()
+            .contains("kata_pulse_sandbox_boot_timeouts_total 1\n"));
+    }
+}
@@ -1,18 +1,171 @@
-use serde::Serialize;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::Metadata;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 use tokio::sync::RwLock;
 
-#[derive(Clone, Debug, Serialize)]
+use super::sandbox_events::{SandboxEventKind, SandboxEvents};
+use crate::config::{self, RuntimeFlavor};
+
+/// Whether the CRI runtime reported the pod metadata of a sandbox
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataStatus {
+    /// Not reported yet
+    #[default]
+    Pending,
+    /// Reported by the last metadata sync
+    Synced,
+    /// Not reported within the retry budget; pod labels stay empty unless a
+    /// later sync reports the sandbox after all
+    MetadataUnavailable,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SandboxCRIMetadata {
     pub uid: String,
     pub name: String,
     pub namespace: String,
+    /// CRI runtime handler that created the sandbox (e.g. "kata")
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub runtime_handler: String,
+    /// Per-sandbox collection interval override (from the scrape-interval pod annotation)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrape_interval_secs: Option<u64>,
+    /// Guest CLK_TCK override (from the clk-tck pod annotation)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clk_tck: Option<f64>,
+    /// Whether the CRI runtime reported the metadata
+    pub metadata_status: MetadataStatus,
+    /// Selected pod labels from the Kubernetes API (when the pod watch is enabled)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub pod_labels: BTreeMap<String, String>,
+    /// Controller managing the pod, from the Kubernetes API (when the pod watch is enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workload: Option<Workload>,
+    /// Well-known `io.kubernetes.cri.*` annotations of the pod sandbox
+    #[serde(skip_serializing_if = "CriAnnotations::is_empty")]
+    pub cri_annotations: CriAnnotations,
+}
+
+/// Well-known `io.kubernetes.cri.*` annotations of a pod sandbox
+///
+/// containerd sets them on the sandbox from the pod spec; the sandbox
+/// resources are the sums of the limits of the pod's containers.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CriAnnotations {
+    /// CFS period of the sandbox in microseconds (`sandbox-cpu-period`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_period: Option<u64>,
+    /// CFS quota of the sandbox in microseconds (`sandbox-cpu-quota`, absent or -1 if unlimited)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_quota: Option<i64>,
+    /// CPU shares of the sandbox (`sandbox-cpu-shares`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_shares: Option<u64>,
+    /// Memory limit of the sandbox in bytes (`sandbox-memory`, absent if unlimited)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_limit_bytes: Option<u64>,
+    /// Image of the sandbox (`image-name`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_name: Option<String>,
+    /// Whether the annotated object is the `sandbox` or a `container` (`container-type`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container_type: Option<String>,
+}
+
+impl CriAnnotations {
+    /// Whether no annotation was found
+    pub fn is_empty(&self) -> bool {
+        *self == CriAnnotations::default()
+    }
+
+    /// CPU limit of the sandbox in cores, if its quota is limited
+    pub fn cpu_limit_cores(&self) -> Option<f64> {
+        match (self.cpu_quota, self.cpu_period) {
+            (Some(quota), Some(period)) if quota > 0 && period > 0 => {
+                Some(quota as f64 / period as f64)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Workload a pod belongs to: the top of its controller chain
+///
+/// A pod of a Deployment is owned by a ReplicaSet, itself owned by the
+/// Deployment; the workload is the Deployment.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Workload {
+    /// Kind of the controller (Deployment, StatefulSet, DaemonSet, CronJob, ...)
+    pub kind: String,
+    /// Name of the controller
+    pub name: String,
+}
+
+/// What the Kubernetes API reports about a pod
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct K8sPodInfo {
+    /// Selected pod labels
+    pub labels: BTreeMap<String, String>,
+    /// Workload of the pod (None for bare pods)
+    pub workload: Option<Workload>,
+}
+
+/// Resolved shim monitor socket of a sandbox
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ShimSocket {
+    pub path: PathBuf,
+    pub runtime: RuntimeFlavor,
+}
+
+/// Identity of a sandbox directory instance
+///
+/// A crash-looping pod can get a sandbox directory with the same ID again;
+/// the inode and creation time tell the instances apart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct SandboxGeneration {
+    pub inode: u64,
+    /// Creation time in nanoseconds since the epoch (if the filesystem reports it)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_unix_nanos: Option<u128>,
+}
+
+impl SandboxGeneration {
+    /// Generation of the sandbox directory with the given metadata
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        SandboxGeneration {
+            inode: metadata.ino(),
+            created_unix_nanos: metadata
+                .created()
+                .ok()
+                .and_then(|created| created.duration_since(UNIX_EPOCH).ok())
+                .map(|since_epoch| since_epoch.as_nanos()),
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct SandboxCache {
     sandboxes: Arc<RwLock<HashMap<String, SandboxCRIMetadata>>>,
+    /// Shim sockets resolved at discovery (or first scrape), so scrapes don't stat the filesystem
+    shim_sockets: Arc<RwLock<HashMap<String, ShimSocket>>>,
+    /// Generation of each sandbox directory, to detect ID reuse
+    generations: Arc<RwLock<HashMap<String, SandboxGeneration>>>,
+    /// Metadata syncs that did not report a sandbox, since its metadata was last set
+    metadata_misses: Arc<RwLock<HashMap<String, u32>>>,
+    /// Labels and workloads of the pods on the node by pod UID, from the Kubernetes API
+    pod_info: Arc<RwLock<HashMap<String, K8sPodInfo>>>,
+    /// Bumped whenever a sandbox or its metadata is added, changed or removed
+    version: Arc<AtomicU64>,
+    /// Lifecycle events of the sandboxes
+    events: Arc<SandboxEvents>,
 }
 
 impl SandboxCache {
@@ -20,9 +173,29 @@ impl SandboxCache {
     pub fn new() -> Self {
         SandboxCache {
             sandboxes: Arc::new(RwLock::new(HashMap::new())),
+            shim_sockets: Arc::new(RwLock::new(HashMap::new())),
+            generations: Arc::new(RwLock::new(HashMap::new())),
+            metadata_misses: Arc::new(RwLock::new(HashMap::new())),
+            pod_info: Arc::new(RwLock::new(HashMap::new())),
+            version: Arc::new(AtomicU64::new(0)),
+            events: Arc::new(SandboxEvents::default()),
         }
     }
 
+    /// Lifecycle events of the sandboxes of this cache
+    pub fn events(&self) -> &Arc<SandboxEvents> {
+        &self.events
+    }
+
+    /// Version of the sandbox list and metadata; changes on every modification
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    fn bump_version(&self) {
+        self.version.fetch_add(1, Ordering::AcqRel);
+    }
+
     /// Get list of all sandbox IDs
     pub async fn get_sandbox_list(&self) -> Vec<String> {
         let map = self.sandboxes.read().await;
@@ -30,10 +203,18 @@ impl SandboxCache {
     }
 
     /// Delete a sandbox if it exists
-    /// Returns true if the sandbox was deleted, false if it didn't exist
-    pub async fn delete_if_exists(&self, id: &str) -> bool {
+    /// Returns the metadata of the deleted sandbox, None if it didn't exist
+    pub async fn delete_if_exists(&self, id: &str) -> Option<SandboxCRIMetadata> {
+        self.shim_sockets.write().await.remove(id);
+        self.generations.write().await.remove(id);
+        self.metadata_misses.write().await.remove(id);
         let mut map = self.sandboxes.write().await;
-        map.remove(id).is_some()
+        let removed = map.remove(id);
+        if let Some(metadata) = &removed {
+            self.bump_version();
+            self.events.record(SandboxEventKind::Removed, id, metadata);
+        }
+        removed
     }
 
     /// Put a sandbox in the cache if it doesn't already exist
@@ -43,39 +224,170 @@ impl SandboxCache {
         if map.contains_key(id) {
             false
         } else {
+            self.events.record(SandboxEventKind::Added, id, &value);
             map.insert(id.to_string(), value);
+            self.bump_version();
             true
         }
     }
 
     /// Set CRI metadata for a sandbox (inserts or updates)
+    ///
+    /// Restarts the metadata retry budget of the sandbox.
     pub async fn set_cri_metadata(&self, id: &str, value: SandboxCRIMetadata) {
+        self.metadata_misses.write().await.remove(id);
         let mut map = self.sandboxes.write().await;
-        map.insert(id.to_string(), value);
+        if map.get(id) != Some(&value) {
+            if value.metadata_status == MetadataStatus::Synced {
+                self.events
+                    .record(SandboxEventKind::MetadataSynced, id, &value);
+            }
+            map.insert(id.to_string(), value);
+            self.bump_version();
+        }
+    }
+
+    /// Count a metadata sync that did not report a pending sandbox
+    ///
+    /// After `budget` misses (0 = never) the sandbox is marked
+    /// [`MetadataStatus::MetadataUnavailable`]. Returns true when this miss
+    /// marked it.
+    pub async fn record_metadata_miss(&self, id: &str, budget: u32) -> bool {
+        let misses = {
+            let mut misses = self.metadata_misses.write().await;
+            let count = misses.entry(id.to_string()).or_default();
+            *count = count.saturating_add(1);
+            *count
+        };
+        if budget == 0 || misses < budget {
+            return false;
+        }
+        let mut map = self.sandboxes.write().await;
+        match map.get_mut(id) {
+            Some(metadata) if metadata.metadata_status == MetadataStatus::Pending => {
+                metadata.metadata_status = MetadataStatus::MetadataUnavailable;
+                self.bump_version();
+                self.events
+                    .record(SandboxEventKind::MetadataUnavailable, id, metadata);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Metadata syncs that did not report the sandbox, since its metadata was last set
+    pub async fn metadata_misses(&self, id: &str) -> u32 {
+        self.metadata_misses
+            .read()
+            .await
+            .get(id)
+            .copied()
+            .unwrap_or_default()
     }
 
     /// Get all sandboxes with their CRI metadata
     pub async fn get_sandboxes_with_metadata(&self) -> Vec<(String, SandboxCRIMetadata)> {
         let map = self.sandboxes.read().await;
+        let pod_info = self.pod_info.read().await;
         map.iter()
-            .map(|(id, metadata)| (id.clone(), metadata.clone()))
+            .map(|(id, metadata)| (id.clone(), with_pod_info(metadata, &pod_info)))
             .collect()
     }
 
-    /// Get CRI metadata for a specific sandbox (blocking variant)
+    /// Get CRI metadata for a specific sandbox
+    pub async fn get_metadata(&self, id: &str) -> Option<SandboxCRIMetadata> {
+        let map = self.sandboxes.read().await;
+        let pod_info = self.pod_info.read().await;
+        map.get(id)
+            .map(|metadata| with_pod_info(metadata, &pod_info))
+    }
+
+    /// Set what the Kubernetes API reports about a pod
+    pub async fn set_pod_info(&self, pod_uid: &str, info: K8sPodInfo) {
+        let mut pod_info = self.pod_info.write().await;
+        if pod_info.get(pod_uid) != Some(&info) {
+            pod_info.insert(pod_uid.to_string(), info);
+            self.bump_version();
+        }
+    }
+
+    /// Forget what the Kubernetes API reported about a deleted pod
+    pub async fn remove_pod_info(&self, pod_uid: &str) {
+        if self.pod_info.write().await.remove(pod_uid).is_some() {
+            self.bump_version();
+        }
+    }
+
+    /// Forget the pods not in `pod_uids` (after a relist)
+    pub async fn retain_pod_info(&self, pod_uids: &HashSet<String>) {
+        let mut pod_info = self.pod_info.write().await;
+        let before = pod_info.len();
+        pod_info.retain(|uid, _| pod_uids.contains(uid));
+        if pod_info.len() != before {
+            self.bump_version();
+        }
+    }
+
+    /// Get the shim socket of a sandbox, resolving it on first use
     ///
-    /// This variant tries to get the metadata without blocking for long.
-    /// If the lock is already held, it returns None to avoid deadlocks.
-    /// This is safe to call from sync contexts like the LabelEnricher.
-    pub fn get_metadata_try(&self, id: &str) -> Option<SandboxCRIMetadata> {
-        // Try to acquire the read lock without blocking
-        // This is safe because we're just reading, and RwLock allows multiple readers
-        match self.sandboxes.try_read() {
-            Ok(map) => map.get(id).cloned(),
-            // If the lock is busy (write in progress), we gracefully return None
-            // rather than blocking or panicking
-            Err(_) => None,
+    /// The resolved path is remembered for known sandboxes until
+    /// `invalidate_shim_socket` is called (e.g. when scrapes start failing).
+    pub async fn shim_socket(&self, id: &str) -> anyhow::Result<ShimSocket> {
+        if let Some(socket) = self.shim_sockets.read().await.get(id) {
+            return Ok(socket.clone());
         }
+
+        let (runtime, path) = config::resolve_shim_socket(id)?;
+        let socket = ShimSocket { path, runtime };
+        if self.sandboxes.read().await.contains_key(id) {
+            self.shim_sockets
+                .write()
+                .await
+                .insert(id.to_string(), socket.clone());
+        }
+        Ok(socket)
+    }
+
+    /// Forget the resolved shim socket of a sandbox so the next scrape resolves it again
+    pub async fn invalidate_shim_socket(&self, id: &str) {
+        self.shim_sockets.write().await.remove(id);
+    }
+
+    /// Get all resolved shim sockets
+    pub async fn get_shim_sockets(&self) -> HashMap<String, ShimSocket> {
+        self.shim_sockets.read().await.clone()
+    }
+
+    /// Record the directory generation of a sandbox
+    ///
+    /// Returns true if a different generation was recorded before, i.e. the
+    /// sandbox directory was recreated under the same ID.
+    pub async fn update_generation(&self, id: &str, generation: SandboxGeneration) -> bool {
+        let mut generations = self.generations.write().await;
+        match generations.insert(id.to_string(), generation) {
+            Some(previous) => previous != generation,
+            None => false,
+        }
+    }
+
+    /// Get the recorded directory generations of all sandboxes
+    pub async fn get_generations(&self) -> HashMap<String, SandboxGeneration> {
+        self.generations.read().await.clone()
+    }
+}
+
+/// Metadata of a sandbox with the Kubernetes labels and workload of its pod, if known
+fn with_pod_info(
+    metadata: &SandboxCRIMetadata,
+    pod_info: &HashMap<String, K8sPodInfo>,
+) -> SandboxCRIMetadata {
+    match pod_info.get(&metadata.uid) {
+        Some(info) if !metadata.uid.is_empty() => SandboxCRIMetadata {
+            pod_labels: info.labels.clone(),
+            workload: info.workload.clone(),
+            ..metadata.clone()
+        },
+        _ => metadata.clone(),
     }
 }
 
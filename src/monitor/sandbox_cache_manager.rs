@@ -5,20 +5,75 @@
 //! - Synchronize CRI metadata (pod names, namespaces, UIDs)
 //! - Maintain sandbox cache state
 //! - Delete metrics when sandboxes are removed
+//! - Drop cached state when a sandbox directory is recreated under the same ID
+//! - Ignore entries that are not Kata sandboxes (leftover files, foreign
+//!   directories), giving just-created sandboxes a grace period
+//! - Inject the CRI timeouts of chaos mode, if enabled
 
 use crate::config;
 use anyhow::Result;
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::HashSet;
+use std::fs::Metadata;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::fs::ReadDir;
 use tokio::time::sleep;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
+use super::chaos::FaultInjector;
+use super::cri::CRIClientConfig;
 use super::metrics_cache::MetricsCache;
-use super::sandbox_cache::SandboxCache;
+use super::pipeline_health::{Component, PipelineHealth};
+use super::sandbox_cache::{SandboxCRIMetadata, SandboxCache, SandboxGeneration};
+use super::sandbox_events::SandboxEventKind;
 
 const FS_MONITOR_RETRY_DELAY_SECONDS: u64 = 60;
 const POD_CACHE_REFRESH_DELAY_SECONDS: u64 = 5;
 const FS_CHECK_INTERVAL_SECONDS: u64 = 5;
+const ENTRY_GRACE_PERIOD_SECONDS: u64 = 60;
+/// Metadata syncs a sandbox may miss before it is marked metadata_unavailable
+pub const DEFAULT_METADATA_RETRY_BUDGET: u32 = 12;
+
+/// What an entry of the sandbox directory holds
+#[derive(Debug, PartialEq)]
+enum EntryKind {
+    /// A Kata sandbox (has a monitor socket or persisted state)
+    Sandbox,
+    /// A directory that may still become a sandbox
+    Pending,
+    /// Not a sandbox, with the reason
+    Invalid(&'static str),
+}
+
+/// Classify an entry of the sandbox directory
+///
+/// The runtime creates the sandbox directory before the shim creates its
+/// monitor socket and `persist.json`, so a bare directory is given `grace`
+/// (from its creation) to be populated before it is ignored.
+fn classify_entry(id: &str, path: &Path, metadata: &Metadata, grace: Duration) -> EntryKind {
+    if !metadata.is_dir() {
+        return EntryKind::Invalid("not a directory");
+    }
+    if path.join("shim-monitor.sock").exists()
+        || path.join("persist.json").exists()
+        || config::socket_path_rust(id).exists()
+    {
+        return EntryKind::Sandbox;
+    }
+    let age = metadata
+        .created()
+        .or_else(|_| metadata.modified())
+        .ok()
+        .and_then(|created| SystemTime::now().duration_since(created).ok())
+        .unwrap_or_default();
+    if age < grace {
+        EntryKind::Pending
+    } else {
+        EntryKind::Invalid("no shim monitor socket or persist.json")
+    }
+}
 
 /// Manages sandbox cache and directory monitoring
 ///
@@ -30,7 +85,17 @@ const FS_CHECK_INTERVAL_SECONDS: u64 = 5;
 pub struct SandboxCacheManager {
     sandbox_cache: Arc<SandboxCache>,
     metrics_cache: Arc<MetricsCache>,
-    runtime_endpoint: String,
+    pipeline_health: Arc<PipelineHealth>,
+    runtime_endpoints: Vec<String>,
+    cri_client_config: CRIClientConfig,
+    /// Time a bare directory gets to become a sandbox before it is ignored
+    entry_grace_period: Duration,
+    /// Entries currently ignored as not being sandboxes (logged once)
+    ignored_entries: Mutex<HashSet<String>>,
+    /// Metadata syncs a sandbox may miss before it is marked unavailable (0 = never)
+    metadata_retry_budget: u32,
+    /// Times out CRI syncs in chaos mode
+    chaos: Option<Arc<FaultInjector>>,
 }
 
 impl SandboxCacheManager {
@@ -38,23 +103,54 @@ impl SandboxCacheManager {
     pub fn new(
         sandbox_cache: Arc<SandboxCache>,
         metrics_cache: Arc<MetricsCache>,
-        runtime_endpoint: String,
+        pipeline_health: Arc<PipelineHealth>,
+        runtime_endpoints: Vec<String>,
     ) -> Self {
         SandboxCacheManager {
             sandbox_cache,
             metrics_cache,
-            runtime_endpoint,
+            pipeline_health,
+            runtime_endpoints,
+            cri_client_config: CRIClientConfig::default(),
+            entry_grace_period: Duration::from_secs(ENTRY_GRACE_PERIOD_SECONDS),
+            ignored_entries: Mutex::new(HashSet::new()),
+            metadata_retry_budget: DEFAULT_METADATA_RETRY_BUDGET,
+            chaos: None,
         }
     }
 
-    /// Start monitoring sandbox directory and syncing CRI metadata
+    /// Use the given timeout and retry settings for CRI requests
+    pub fn with_cri_client_config(mut self, config: CRIClientConfig) -> Self {
+        self.cri_client_config = config;
+        self
+    }
+
+    /// Give bare directories `grace` to become sandboxes before ignoring them
+    pub fn with_entry_grace_period(mut self, grace: Duration) -> Self {
+        self.entry_grace_period = grace;
+        self
+    }
+
+    /// Mark sandboxes metadata_unavailable after `budget` missed syncs (0 = never)
+    pub fn with_metadata_retry_budget(mut self, budget: u32) -> Self {
+        self.metadata_retry_budget = budget;
+        self
+    }
+
+    /// Inject the CRI timeouts of chaos mode into the metadata syncs
+    pub fn with_chaos(mut self, chaos: Option<Arc<FaultInjector>>) -> Self {
+        self.chaos = chaos;
+        self
+    }
+
+    /// Monitor the sandbox directory and sync CRI metadata until `cancel` fires
     ///
     /// This is a long-running task that should be spawned as a background task.
     /// It will:
     /// 1. Read initial sandbox list from filesystem
     /// 2. Monitor filesystem for additions/deletions
     /// 3. Periodically sync CRI metadata
-    pub async fn start(&self) -> Result<()> {
+    pub async fn start(&self, cancel: CancellationToken) -> Result<()> {
         let sandbox_dir = config::get_sandboxes_storage_path();
         info!(path = ?sandbox_dir, "Starting sandbox cache manager");
 
@@ -66,21 +162,16 @@ impl SandboxCacheManager {
                     info!(path = ?sandbox_dir, "Successfully opened sandbox directory");
                     // Read initial sandbox list
                     let mut sandbox_list = Vec::new();
-                    while let Some(entry) = dir.next_entry().await? {
-                        if let Some(name) = entry.file_name().to_str() {
-                            debug!(sandbox = %name, "Adding sandbox to initial list");
-                            sandbox_list.push(name.to_string());
-                            self.sandbox_cache
-                                .put_if_not_exists(
-                                    name,
-                                    super::sandbox_cache::SandboxCRIMetadata {
-                                        uid: String::new(),
-                                        name: String::new(),
-                                        namespace: String::new(),
-                                    },
-                                )
-                                .await;
-                        }
+                    for (name, metadata) in self.sandbox_entries(&mut dir, &[]).await {
+                        debug!(sandbox_id = %name, "Adding sandbox to initial list");
+                        self.sandbox_cache
+                            .put_if_not_exists(&name, SandboxCRIMetadata::default())
+                            .await;
+                        self.sandbox_cache
+                            .update_generation(&name, SandboxGeneration::from_metadata(&metadata))
+                            .await;
+                        self.resolve_shim_socket(&name).await;
+                        sandbox_list.push(name);
                     }
                     info!(
                         count = sandbox_list.len(),
@@ -92,7 +183,7 @@ impl SandboxCacheManager {
                         count = sandbox_list.len(),
                         "Starting sandbox directory monitoring"
                     );
-                    self.monitor_directory(&sandbox_list).await?;
+                    self.monitor_directory(&sandbox_list, &cancel).await;
                     break;
                 }
                 Err(e) => {
@@ -102,16 +193,24 @@ impl SandboxCacheManager {
                         retry_delay_sec = FS_MONITOR_RETRY_DELAY_SECONDS,
                         "cannot monitor sandboxes, retrying"
                     );
-                    sleep(Duration::from_secs(FS_MONITOR_RETRY_DELAY_SECONDS)).await;
+                    self.pipeline_health.degraded(
+                        Component::SandboxCacheManager,
+                        format!("cannot read {}: {}", sandbox_dir.display(), e),
+                    );
+                    tokio::select! {
+                        _ = cancel.cancelled() => break,
+                        _ = sleep(Duration::from_secs(FS_MONITOR_RETRY_DELAY_SECONDS)) => {}
+                    }
                 }
             }
         }
 
+        info!("Sandbox cache manager stopped");
         Ok(())
     }
 
-    /// Monitor sandbox directory for changes
-    async fn monitor_directory(&self, initial_list: &[String]) -> Result<()> {
+    /// Monitor sandbox directory for changes until `cancel` fires
+    async fn monitor_directory(&self, initial_list: &[String], cancel: &CancellationToken) {
         let sandbox_dir = config::get_sandboxes_storage_path();
         let sandbox_dir_str = sandbox_dir.to_string_lossy().to_string();
         let mut sandbox_list = initial_list.to_vec();
@@ -137,7 +236,10 @@ impl SandboxCacheManager {
             }
 
             // Sleep for a short period before checking again
-            sleep(Duration::from_millis(100)).await;
+            tokio::select! {
+                _ = cancel.cancelled() => return,
+                _ = sleep(Duration::from_millis(100)) => {}
+            }
         }
     }
 
@@ -145,14 +247,24 @@ impl SandboxCacheManager {
     async fn sync_cri_metadata(&self, sandbox_list: &mut Vec<String>) {
         debug!(sandboxes = ?sandbox_list, "retrieve pods metadata from the container manager");
 
-        match super::cri::sync_sandboxes(
-            &self.runtime_endpoint,
-            &self.sandbox_cache,
-            sandbox_list.clone(),
-        )
-        .await
-        {
+        let result = async {
+            if let Some(chaos) = &self.chaos {
+                chaos
+                    .before_cri_sync(self.cri_client_config.timeout)
+                    .await?;
+            }
+            super::cri::sync_sandboxes(
+                &self.runtime_endpoints,
+                &self.cri_client_config,
+                &self.sandbox_cache,
+                sandbox_list.clone(),
+            )
+            .await
+        }
+        .await;
+        match result {
             Ok(remaining) => {
+                self.pipeline_health.healthy(Component::SandboxCacheManager);
                 // Note: remaining contains only sandboxes that failed to sync and should be retried
                 // We do NOT replace the entire sandbox_list with it
                 // The sandbox_list is managed by check_filesystem_changes(), not by CRI sync
@@ -162,23 +274,120 @@ impl SandboxCacheManager {
                         "sandboxes still missing metadata (will retry)"
                     );
                 }
+                // Matching continues past the budget, so a late pod still syncs
+                for sandbox in &remaining {
+                    if self
+                        .sandbox_cache
+                        .record_metadata_miss(sandbox, self.metadata_retry_budget)
+                        .await
+                    {
+                        warn!(
+                            sandbox_id = %sandbox,
+                            attempts = self.metadata_retry_budget,
+                            "no CRI metadata for sandbox, marking it metadata_unavailable"
+                        );
+                    }
+                }
             }
             Err(e) => {
-                error!(error = %e, "failed to sync sandboxes");
+                error!(error = format!("{:#}", e), "failed to sync sandboxes");
+                self.pipeline_health.degraded(
+                    Component::SandboxCacheManager,
+                    format!("CRI metadata sync failed: {:#}", e),
+                );
             }
         }
     }
 
+    /// Resolve the shim socket of a newly discovered sandbox
+    ///
+    /// The shim may not have created its socket yet; the first scrape retries then.
+    async fn resolve_shim_socket(&self, sandbox: &str) {
+        match self.sandbox_cache.shim_socket(sandbox).await {
+            Ok(socket) => {
+                debug!(sandbox_id = %sandbox, path = %socket.path.display(), runtime = ?socket.runtime, "Resolved shim socket")
+            }
+            Err(e) => debug!(sandbox_id = %sandbox, error = %e, "Shim socket not resolved yet"),
+        }
+    }
+
+    /// Drop the cached state of the previous instance of a recreated sandbox
+    ///
+    /// The directory may disappear and reappear between two filesystem checks
+    /// (e.g. a crash-looping pod), so the ID alone does not identify an instance.
+    async fn reset_recreated_sandbox(&self, sandbox: &str) {
+        info!(sandbox_id = %sandbox, "sandbox cache: sandbox directory was recreated, dropping cached state");
+        if let Some(metadata) = self.sandbox_cache.get_metadata(sandbox).await {
+            self.sandbox_cache
+                .events()
+                .record(SandboxEventKind::Recreated, sandbox, &metadata);
+        }
+        self.metrics_cache.reset_sandbox(sandbox).await;
+        self.sandbox_cache.invalidate_shim_socket(sandbox).await;
+        // The metadata of the new pod instance is synced from CRI again
+        self.sandbox_cache
+            .set_cri_metadata(sandbox, SandboxCRIMetadata::default())
+            .await;
+    }
+
+    /// Read the sandbox entries of the sandbox directory, skipping the rest
+    ///
+    /// Sandboxes in `tracked` are kept as long as their directory exists, even
+    /// once their shim is gone. Pending directories are left for a later check.
+    async fn sandbox_entries(
+        &self,
+        dir: &mut ReadDir,
+        tracked: &[String],
+    ) -> Vec<(String, Metadata)> {
+        let mut entries = Vec::new();
+        let mut seen = HashSet::new();
+        while let Ok(Some(entry)) = dir.next_entry().await {
+            let (Some(name), Ok(metadata)) = (
+                entry.file_name().to_str().map(str::to_string),
+                entry.metadata().await,
+            ) else {
+                continue;
+            };
+            let kind = if metadata.is_dir() && tracked.contains(&name) {
+                EntryKind::Sandbox
+            } else {
+                classify_entry(&name, &entry.path(), &metadata, self.entry_grace_period)
+            };
+            match kind {
+                EntryKind::Sandbox => entries.push((name.clone(), metadata)),
+                EntryKind::Pending => {
+                    debug!(entry = %name, "Sandbox directory not populated yet, checking again later")
+                }
+                EntryKind::Invalid(reason) => {
+                    let mut ignored = self
+                        .ignored_entries
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner());
+                    if ignored.insert(name.clone()) {
+                        info!(entry = %name, reason = reason, "Ignoring entry of the sandbox directory that is not a sandbox");
+                    }
+                }
+            }
+            seen.insert(name);
+        }
+        // Forget ignored entries that are gone, so a reappearing one is logged again
+        self.ignored_entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|name| seen.contains(name));
+        entries
+    }
+
     /// Check filesystem for sandbox additions/deletions
     async fn check_filesystem_changes(&self, sandbox_dir: &str, sandbox_list: &mut Vec<String>) {
         use tokio::fs;
 
         if let Ok(mut dir) = fs::read_dir(sandbox_dir).await {
             let mut current_list = Vec::new();
-            while let Ok(Some(entry)) = dir.next_entry().await {
-                if let Some(name) = entry.file_name().to_str() {
-                    current_list.push(name.to_string());
-                }
+            let mut generations = Vec::new();
+            for (name, metadata) in self.sandbox_entries(&mut dir, sandbox_list).await {
+                generations.push((name.clone(), SandboxGeneration::from_metadata(&metadata)));
+                current_list.push(name);
             }
 
             // Check for new sandboxes
@@ -191,30 +400,38 @@ impl SandboxCacheManager {
                         .contains(sandbox)
                     && self
                         .sandbox_cache
-                        .put_if_not_exists(
-                            sandbox,
-                            super::sandbox_cache::SandboxCRIMetadata {
-                                uid: String::new(),
-                                name: String::new(),
-                                namespace: String::new(),
-                            },
-                        )
+                        .put_if_not_exists(sandbox, SandboxCRIMetadata::default())
                         .await
                 {
-                    info!(sandbox = %sandbox, "sandbox cache: added pod");
+                    info!(sandbox_id = %sandbox, "sandbox cache: added pod");
+                    // A previous instance with this ID may have left a tombstone
+                    self.metrics_cache.reset_sandbox(sandbox).await;
+                    self.resolve_shim_socket(sandbox).await;
                     sandbox_list.push(sandbox.clone());
                 }
             }
 
+            // Check for sandboxes recreated since the last check
+            for (sandbox, generation) in generations {
+                if self
+                    .sandbox_cache
+                    .update_generation(&sandbox, generation)
+                    .await
+                {
+                    self.reset_recreated_sandbox(&sandbox).await;
+                }
+            }
+
             // Check for deleted sandboxes
             let mut to_remove = Vec::new();
             for sandbox in &*sandbox_list {
-                if !current_list.contains(sandbox)
-                    && self.sandbox_cache.delete_if_exists(sandbox).await
-                {
-                    // Also remove metrics cache for deleted sandbox
-                    self.metrics_cache.delete_metrics(sandbox).await;
-                    info!(sandbox = %sandbox, "sandbox cache: removed pod and cleared metrics");
+                if current_list.contains(sandbox) {
+                    continue;
+                }
+                if let Some(metadata) = self.sandbox_cache.delete_if_exists(sandbox).await {
+                    // Also remove metrics cache for deleted sandbox (leaves a tombstone)
+                    self.metrics_cache.delete_metrics(sandbox, metadata).await;
+                    info!(sandbox_id = %sandbox, "sandbox cache: removed pod and cleared metrics");
                     to_remove.push(sandbox.clone());
                 }
             }
@@ -236,9 +453,13 @@ mod tests {
         let manager = SandboxCacheManager::new(
             sandbox_cache,
             metrics_cache,
-            "/run/containerd/containerd.sock".to_string(),
+            Arc::new(PipelineHealth::new()),
+            vec!["/run/containerd/containerd.sock".to_string()],
+        );
+        assert_eq!(
+            manager.runtime_endpoints,
+            vec!["/run/containerd/containerd.sock".to_string()]
         );
-        assert_eq!(manager.runtime_endpoint, "/run/containerd/containerd.sock");
     }
 
     #[tokio::test]
@@ -252,7 +473,8 @@ mod tests {
         let manager = SandboxCacheManager::new(
             sandbox_cache.clone(),
             metrics_cache,
-            "/run/containerd/containerd.sock".to_string(),
+            Arc::new(PipelineHealth::new()),
+            vec!["/run/containerd/containerd.sock".to_string()],
         );
 
         // Set up initial sandboxes in the cache
@@ -264,14 +486,7 @@ mod tests {
 
         for sandbox in &initial_sandboxes {
             sandbox_cache
-                .put_if_not_exists(
-                    sandbox,
-                    crate::monitor::sandbox_cache::SandboxCRIMetadata {
-                        uid: String::new(),
-                        name: String::new(),
-                        namespace: String::new(),
-                    },
-                )
+                .put_if_not_exists(sandbox, SandboxCRIMetadata::default())
                 .await;
         }
 
@@ -315,6 +530,57 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_metadata_retry_budget() {
+        use crate::monitor::sandbox_cache::MetadataStatus;
+
+        let sandbox_cache = SandboxCache::new();
+        sandbox_cache
+            .put_if_not_exists("sandbox-1", SandboxCRIMetadata::default())
+            .await;
+        let status = |cache: SandboxCache| async move {
+            cache
+                .get_metadata("sandbox-1")
+                .await
+                .unwrap()
+                .metadata_status
+        };
+
+        assert!(!sandbox_cache.record_metadata_miss("sandbox-1", 2).await);
+        assert_eq!(status(sandbox_cache.clone()).await, MetadataStatus::Pending);
+        assert!(sandbox_cache.record_metadata_miss("sandbox-1", 2).await);
+        assert_eq!(
+            status(sandbox_cache.clone()).await,
+            MetadataStatus::MetadataUnavailable
+        );
+        // Marked once; further misses keep the status
+        assert!(!sandbox_cache.record_metadata_miss("sandbox-1", 2).await);
+        assert_eq!(sandbox_cache.metadata_misses("sandbox-1").await, 3);
+
+        // A late sync recovers the sandbox and restarts the budget
+        sandbox_cache
+            .set_cri_metadata(
+                "sandbox-1",
+                SandboxCRIMetadata {
+                    uid: "uid-1".to_string(),
+                    metadata_status: MetadataStatus::Synced,
+                    ..Default::default()
+                },
+            )
+            .await;
+        assert_eq!(sandbox_cache.metadata_misses("sandbox-1").await, 0);
+        assert!(!sandbox_cache.record_metadata_miss("sandbox-1", 1).await);
+        assert_eq!(status(sandbox_cache.clone()).await, MetadataStatus::Synced);
+
+        // A budget of 0 never gives up
+        sandbox_cache
+            .put_if_not_exists("sandbox-2", SandboxCRIMetadata::default())
+            .await;
+        for _ in 0..100 {
+            assert!(!sandbox_cache.record_metadata_miss("sandbox-2", 0).await);
+        }
+    }
+
     #[tokio::test]
     async fn test_sandbox_list_survives_filesystem_check() {
         // This test validates that the sandbox_list is managed by check_filesystem_changes
@@ -325,7 +591,8 @@ mod tests {
         let manager = SandboxCacheManager::new(
             sandbox_cache.clone(),
             metrics_cache,
-            "/run/containerd/containerd.sock".to_string(),
+            Arc::new(PipelineHealth::new()),
+            vec!["/run/containerd/containerd.sock".to_string()],
         );
 
         // Set up initial sandboxes
@@ -335,10 +602,11 @@ mod tests {
             sandbox_cache
                 .put_if_not_exists(
                     id,
-                    crate::monitor::sandbox_cache::SandboxCRIMetadata {
+                    SandboxCRIMetadata {
                         uid: format!("uid-{}", id),
                         name: format!("pod-{}", id),
                         namespace: "default".to_string(),
+                        ..Default::default()
                     },
                 )
                 .await;
@@ -374,4 +642,147 @@ mod tests {
             "After multiple CRI syncs, should still have all 3 sandboxes"
         );
     }
+
+    #[tokio::test]
+    async fn test_recreated_sandbox_drops_cached_state() {
+        let root = tempfile::tempdir().unwrap();
+        let sandbox_dir = root.path().to_string_lossy().to_string();
+        std::fs::create_dir(root.path().join("sb1")).unwrap();
+        std::fs::write(root.path().join("sb1/persist.json"), "{}").unwrap();
+
+        let sandbox_cache = Arc::new(SandboxCache::new());
+        let metrics_cache = Arc::new(MetricsCache::new());
+        let manager = SandboxCacheManager::new(
+            sandbox_cache.clone(),
+            metrics_cache.clone(),
+            Arc::new(PipelineHealth::new()),
+            vec![],
+        );
+        let mut sandbox_list = Vec::new();
+        manager
+            .check_filesystem_changes(&sandbox_dir, &mut sandbox_list)
+            .await;
+        assert_eq!(sandbox_list, vec!["sb1".to_string()]);
+
+        let metadata = SandboxCRIMetadata {
+            uid: "uid-old".to_string(),
+            ..Default::default()
+        };
+        sandbox_cache.set_cri_metadata("sb1", metadata).await;
+        metrics_cache.start_collection().await;
+        metrics_cache
+            .add_metrics(
+                "sb1".to_string(),
+                crate::utils::prometheus_parser::PrometheusMetrics::new(),
+            )
+            .await;
+        metrics_cache.finish_collection().await;
+
+        // An unchanged directory keeps its state
+        manager
+            .check_filesystem_changes(&sandbox_dir, &mut sandbox_list)
+            .await;
+        assert!(metrics_cache.get_metrics("sb1").await.is_some());
+
+        // Replace the directory between two checks (a different inode)
+        std::fs::create_dir(root.path().join("sb1-new")).unwrap();
+        std::fs::remove_dir_all(root.path().join("sb1")).unwrap();
+        std::fs::rename(root.path().join("sb1-new"), root.path().join("sb1")).unwrap();
+        manager
+            .check_filesystem_changes(&sandbox_dir, &mut sandbox_list)
+            .await;
+
+        assert_eq!(sandbox_list, vec!["sb1".to_string()]);
+        assert!(metrics_cache.get_metrics("sb1").await.is_none());
+        assert!(sandbox_cache
+            .get_metadata("sb1")
+            .await
+            .unwrap()
+            .uid
+            .is_empty());
+
+        let events: Vec<_> = sandbox_cache
+            .events()
+            .since(None)
+            .into_iter()
+            .map(|event| event.event)
+            .collect();
+        assert_eq!(
+            events,
+            vec![SandboxEventKind::Added, SandboxEventKind::Recreated]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_sandbox_entries_are_tracked() {
+        let root = tempfile::tempdir().unwrap();
+        let sandbox_dir = root.path().to_string_lossy().to_string();
+        std::fs::write(root.path().join("leftover.log"), "").unwrap();
+        std::fs::create_dir(root.path().join("starting")).unwrap();
+        std::fs::create_dir(root.path().join("sb1")).unwrap();
+        std::fs::write(root.path().join("sb1/shim-monitor.sock"), "").unwrap();
+
+        let sandbox_cache = Arc::new(SandboxCache::new());
+        let manager = SandboxCacheManager::new(
+            sandbox_cache.clone(),
+            Arc::new(MetricsCache::new()),
+            Arc::new(PipelineHealth::new()),
+            vec![],
+        );
+        let mut sandbox_list = Vec::new();
+        manager
+            .check_filesystem_changes(&sandbox_dir, &mut sandbox_list)
+            .await;
+        assert_eq!(sandbox_list, vec!["sb1".to_string()]);
+        assert!(manager
+            .ignored_entries
+            .lock()
+            .unwrap()
+            .contains("leftover.log"));
+
+        // The directory in its grace period is tracked once the shim starts
+        std::fs::write(root.path().join("starting/persist.json"), "{}").unwrap();
+        manager
+            .check_filesystem_changes(&sandbox_dir, &mut sandbox_list)
+            .await;
+        sandbox_list.sort();
+        assert_eq!(
+            sandbox_list,
+            vec!["sb1".to_string(), "starting".to_string()]
+        );
+        assert_eq!(sandbox_cache.get_sandbox_list().await.len(), 2);
+
+        // A tracked sandbox stays tracked after its shim is gone
+        std::fs::remove_file(root.path().join("sb1/shim-monitor.sock")).unwrap();
+        let manager = manager.with_entry_grace_period(Duration::ZERO);
+        manager
+            .check_filesystem_changes(&sandbox_dir, &mut sandbox_list)
+            .await;
+        assert_eq!(sandbox_list.len(), 2);
+    }
+
+    #[test]
+    fn test_classify_entry() {
+        let root = tempfile::tempdir().unwrap();
+        let grace = Duration::from_secs(60);
+        let file = root.path().join("file");
+        std::fs::write(&file, "").unwrap();
+        let dir = root.path().join("dir");
+        std::fs::create_dir(&dir).unwrap();
+        let classify = |path: &Path, grace| {
+            classify_entry("sb", path, &std::fs::metadata(path).unwrap(), grace)
+        };
+
+        assert_eq!(
+            classify(&file, grace),
+            EntryKind::Invalid("not a directory")
+        );
+        assert_eq!(classify(&dir, grace), EntryKind::Pending);
+        assert!(matches!(
+            classify(&dir, Duration::ZERO),
+            EntryKind::Invalid(_)
+        ));
+        std::fs::write(dir.join("persist.json"), "{}").unwrap();
+        assert_eq!(classify(&dir, Duration::ZERO), EntryKind::Sandbox);
+    }
 }
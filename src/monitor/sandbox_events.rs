@@ -0,0 +1,207 @@
+//! Sandbox events - bounded log of sandbox lifecycle changes
+//!
+//! Responsibilities:
+//! - Record when sandboxes are added, removed or recreated, and when their
+//!   CRI metadata is synced or given up on, with the time and pod
+//! - Keep the most recent events only, so memory stays bounded
+//! - Serve the events since a point in time (`/api/events?since=`)
+//! - Count the events by kind as internal metrics
+//!
+//! Answers "when did the sandbox of this pod go away" without debug logs.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use super::pipeline_health::unix_now_ms;
+use super::sandbox_cache::SandboxCRIMetadata;
+
+/// Number of events kept before the oldest are dropped
+pub const EVENT_LOG_CAPACITY: usize = 1024;
+
+/// Kind of a sandbox lifecycle event
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxEventKind {
+    /// The sandbox directory appeared
+    Added,
+    /// The sandbox directory disappeared
+    Removed,
+    /// The sandbox directory was recreated under the same ID
+    Recreated,
+    /// The CRI runtime reported (new) pod metadata of the sandbox
+    MetadataSynced,
+    /// The CRI runtime did not report the sandbox within the retry budget
+    MetadataUnavailable,
+}
+
+impl SandboxEventKind {
+    /// All kinds, in reporting order
+    pub const ALL: [SandboxEventKind; 5] = [
+        SandboxEventKind::Added,
+        SandboxEventKind::Removed,
+        SandboxEventKind::Recreated,
+        SandboxEventKind::MetadataSynced,
+        SandboxEventKind::MetadataUnavailable,
+    ];
+
+    /// Name used in JSON and metric labels
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SandboxEventKind::Added => "added",
+            SandboxEventKind::Removed => "removed",
+            SandboxEventKind::Recreated => "recreated",
+            SandboxEventKind::MetadataSynced => "metadata_synced",
+            SandboxEventKind::MetadataUnavailable => "metadata_unavailable",
+        }
+    }
+}
+
+/// A sandbox lifecycle event
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SandboxEvent {
+    /// When the event was recorded (ms since the Unix epoch)
+    pub timestamp_ms: u64,
+    /// What happened
+    pub event: SandboxEventKind,
+    pub sandbox_id: String,
+    /// Pod of the sandbox, as known when the event was recorded (empty if not synced)
+    pub pod: String,
+    pub namespace: String,
+}
+
+/// Bounded, thread-safe log of sandbox lifecycle events
+pub struct SandboxEvents {
+    capacity: usize,
+    events: Mutex<VecDeque<SandboxEvent>>,
+    /// Events recorded by kind, in the order of [`SandboxEventKind::ALL`]
+    counts: [AtomicU64; SandboxEventKind::ALL.len()],
+}
+
+impl SandboxEvents {
+    /// Create a log keeping the last `capacity` events
+    pub fn new(capacity: usize) -> Self {
+        SandboxEvents {
+            capacity,
+            events: Mutex::new(VecDeque::with_capacity(capacity.min(EVENT_LOG_CAPACITY))),
+            counts: Default::default(),
+        }
+    }
+
+    /// Record an event of a sandbox with its current metadata
+    pub fn record(&self, event: SandboxEventKind, sandbox_id: &str, metadata: &SandboxCRIMetadata) {
+        self.push(SandboxEvent {
+            timestamp_ms: unix_now_ms(),
+            event,
+            sandbox_id: sandbox_id.to_string(),
+            pod: metadata.name.clone(),
+            namespace: metadata.namespace.clone(),
+        });
+    }
+
+    fn push(&self, event: SandboxEvent) {
+        let index = SandboxEventKind::ALL
+            .iter()
+            .position(|kind| *kind == event.event)
+            .unwrap_or_default();
+        self.counts[index].fetch_add(1, Ordering::Relaxed);
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        if events.len() >= self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// The kept events recorded after `since_ms` (all of them if None), oldest first
+    pub fn since(&self, since_ms: Option<u64>) -> Vec<SandboxEvent> {
+        let events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        events
+            .iter()
+            .filter(|event| since_ms.is_none_or(|since| event.timestamp_ms > since))
+            .cloned()
+            .collect()
+    }
+
+    /// Render the event counts in Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        let mut output = String::new();
+        output.push_str(
+            "# HELP kata_pulse_sandbox_events_total Sandbox lifecycle events recorded, by kind\n",
+        );
+        output.push_str("# TYPE kata_pulse_sandbox_events_total counter\n");
+        for (kind, count) in SandboxEventKind::ALL.iter().zip(&self.counts) {
+            output.push_str(&format!(
+                "kata_pulse_sandbox_events_total{{event=\"{}\"}} {}\n",
+                kind.as_str(),
+                count.load(Ordering::Relaxed)
+            ));
+        }
+        output
+    }
+}
+
+impl Default for SandboxEvents {
+    fn default() -> Self {
+        Self::new(EVENT_LOG_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(timestamp_ms: u64, event: SandboxEventKind, sandbox_id: &str) -> SandboxEvent {
+        SandboxEvent {
+            timestamp_ms,
+            event,
+            sandbox_id: sandbox_id.to_string(),
+            pod: String::new(),
+            namespace: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_log_is_bounded() {
+        let events = SandboxEvents::new(2);
+        events.push(event(1, SandboxEventKind::Added, "sb-1"));
+        events.push(event(2, SandboxEventKind::MetadataSynced, "sb-1"));
+        events.push(event(3, SandboxEventKind::Removed, "sb-1"));
+
+        let kept = events.since(None);
+        assert_eq!(kept.len(), 2);
+        assert_eq!(kept[0].event, SandboxEventKind::MetadataSynced);
+        assert_eq!(
+            events.since(Some(2)),
+            vec![event(3, SandboxEventKind::Removed, "sb-1")]
+        );
+        assert!(events.since(Some(3)).is_empty());
+
+        // Dropped events are still counted
+        let output = events.render_metrics();
+        assert!(output.contains("kata_pulse_sandbox_events_total{event=\"added\"} 1\n"));
+        assert!(output.contains("kata_pulse_sandbox_events_total{event=\"recreated\"} 0\n"));
+    }
+
+    #[test]
+    fn test_record() {
+        let events = SandboxEvents::default();
+        let metadata = SandboxCRIMetadata {
+            name: "my-pod".to_string(),
+            namespace: "default".to_string(),
+            ..Default::default()
+        };
+        events.record(SandboxEventKind::Removed, "sb-1", &metadata);
+
+        let recorded = events.since(None);
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].pod, "my-pod");
+        assert_eq!(recorded[0].namespace, "default");
+        assert!(recorded[0].timestamp_ms > 0);
+        let json = serde_json::to_string(&recorded[0]).unwrap();
+        assert!(json.contains(r#""event":"removed""#));
+    }
+}
@@ -0,0 +1,140 @@
+//! Sandbox filter - decides which sandboxes are collected and served
+//!
+//! Responsibilities:
+//! - Namespace allowlist/denylist (e.g. drop `kube-system`, or only tenant namespaces)
+//! - CRI runtime handlers (only track sandboxes created by the kata handlers)
+//! - Sandboxes whose CRI metadata never arrived (`metadata_unavailable`)
+//!
+//! The collector applies the filter before scheduling, so excluded sandboxes
+//! are never scraped.
+
+use serde::Serialize;
+
+use super::sandbox_cache::{MetadataStatus, SandboxCRIMetadata};
+
+/// Filter applied to sandboxes before collection
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SandboxFilter {
+    /// Only sandboxes in these namespaces are collected (empty = all)
+    pub namespace_allow: Vec<String>,
+    /// Sandboxes in these namespaces are never collected
+    pub namespace_deny: Vec<String>,
+    /// Only sandboxes created by these CRI runtime handlers are collected (empty = all)
+    pub runtime_handlers: Vec<String>,
+    /// Sandboxes marked metadata_unavailable are not collected
+    pub exclude_metadata_unavailable: bool,
+}
+
+impl SandboxFilter {
+    /// Only collect sandboxes in the given namespaces
+    pub fn with_namespace_allow(mut self, namespaces: Vec<String>) -> Self {
+        self.namespace_allow = namespaces;
+        self
+    }
+
+    /// Never collect sandboxes in the given namespaces
+    pub fn with_namespace_deny(mut self, namespaces: Vec<String>) -> Self {
+        self.namespace_deny = namespaces;
+        self
+    }
+
+    /// Only collect sandboxes created by the given CRI runtime handlers
+    pub fn with_runtime_handlers(mut self, handlers: Vec<String>) -> Self {
+        self.runtime_handlers = handlers;
+        self
+    }
+
+    /// Do not collect sandboxes whose CRI metadata never arrived
+    pub fn with_exclude_metadata_unavailable(mut self, exclude: bool) -> Self {
+        self.exclude_metadata_unavailable = exclude;
+        self
+    }
+
+    /// Whether a sandbox with the given CRI metadata is collected
+    ///
+    /// A sandbox whose CRI metadata is not known yet passes the denylist but
+    /// not a non-empty allowlist or runtime handler list.
+    pub fn allows(&self, metadata: &SandboxCRIMetadata) -> bool {
+        if self.exclude_metadata_unavailable
+            && metadata.metadata_status == MetadataStatus::MetadataUnavailable
+        {
+            return false;
+        }
+        if !self.runtime_handlers.is_empty()
+            && !self.runtime_handlers.contains(&metadata.runtime_handler)
+        {
+            return false;
+        }
+
+        let namespace = metadata.namespace.as_str();
+        if self.namespace_deny.iter().any(|ns| ns == namespace) {
+            return false;
+        }
+        self.namespace_allow.is_empty() || self.namespace_allow.iter().any(|ns| ns == namespace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn in_namespace(namespace: &str) -> SandboxCRIMetadata {
+        SandboxCRIMetadata {
+            namespace: namespace.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_allows_everything() {
+        let filter = SandboxFilter::default();
+        assert!(filter.allows(&in_namespace("kube-system")));
+        assert!(filter.allows(&SandboxCRIMetadata::default()));
+    }
+
+    #[test]
+    fn test_namespace_deny() {
+        let filter = SandboxFilter::default().with_namespace_deny(vec!["kube-system".to_string()]);
+        assert!(!filter.allows(&in_namespace("kube-system")));
+        assert!(filter.allows(&in_namespace("tenant-a")));
+        assert!(filter.allows(&SandboxCRIMetadata::default()));
+    }
+
+    #[test]
+    fn test_namespace_allow() {
+        let filter = SandboxFilter::default()
+            .with_namespace_allow(vec!["tenant-a".to_string(), "tenant-b".to_string()])
+            .with_namespace_deny(vec!["tenant-b".to_string()]);
+        assert!(filter.allows(&in_namespace("tenant-a")));
+        assert!(!filter.allows(&in_namespace("tenant-b")));
+        assert!(!filter.allows(&in_namespace("default")));
+        assert!(!filter.allows(&SandboxCRIMetadata::default()));
+    }
+
+    #[test]
+    fn test_runtime_handlers() {
+        let filter = SandboxFilter::default()
+            .with_runtime_handlers(vec!["kata".to_string(), "kata-qemu".to_string()]);
+        let with_handler = |handler: &str| SandboxCRIMetadata {
+            runtime_handler: handler.to_string(),
+            ..Default::default()
+        };
+        assert!(filter.allows(&with_handler("kata-qemu")));
+        assert!(!filter.allows(&with_handler("runc")));
+        assert!(!filter.allows(&SandboxCRIMetadata::default()));
+    }
+
+    #[test]
+    fn test_exclude_metadata_unavailable() {
+        let unavailable = SandboxCRIMetadata {
+            metadata_status: MetadataStatus::MetadataUnavailable,
+            ..Default::default()
+        };
+        assert!(SandboxFilter::default().allows(&unavailable));
+
+        let filter = SandboxFilter::default().with_exclude_metadata_unavailable(true);
+        assert!(!filter.allows(&unavailable));
+        assert!(filter.allows(&SandboxCRIMetadata::default()));
+        assert!(filter.allows(&in_namespace("tenant-a")));
+    }
+}
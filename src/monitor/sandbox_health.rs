@@ -0,0 +1,208 @@
+//! Sandbox health - one classification per collected sandbox
+//!
+//! Responsibilities:
+//! - Classify each collected sandbox as healthy, degraded or failing from its
+//!   scrape results and CRI metadata status
+//! - Render the classification as the `kata_pulse_sandbox_health` gauge, so a
+//!   single expression (`kata_pulse_sandbox_health{state="failing"} == 1`)
+//!   alerts on Kata pods that are not being monitored
+//!
+//! Sandboxes skipped by the sandbox filter are not monitored on purpose and
+//! are not classified.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use super::sandbox_cache::{MetadataStatus, SandboxCRIMetadata};
+use super::sandbox_filter::SandboxFilter;
+use super::scrape_health::ScrapeStatus;
+use crate::utils::metrics_converter::cadvisor::escape_label_value;
+
+/// Monitoring health of a sandbox
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxHealth {
+    /// The last scrape succeeded and the pod metadata is (being) synced
+    Healthy,
+    /// Not scraped yet, the last scrape failed, or no CRI metadata within the retry budget
+    Degraded,
+    /// Scrapes keep failing and the sandbox is quarantined
+    Failing,
+}
+
+impl SandboxHealth {
+    /// All classifications, in the order they are rendered
+    pub const ALL: [SandboxHealth; 3] = [
+        SandboxHealth::Healthy,
+        SandboxHealth::Degraded,
+        SandboxHealth::Failing,
+    ];
+
+    /// Classify a sandbox from its CRI metadata and scrape status (None = not scraped yet)
+    pub fn classify(metadata: &SandboxCRIMetadata, status: Option<&ScrapeStatus>) -> Self {
+        match status {
+            Some(status) if status.quarantined => SandboxHealth::Failing,
+            Some(status)
+                if status.up && metadata.metadata_status != MetadataStatus::MetadataUnavailable =>
+            {
+                SandboxHealth::Healthy
+            }
+            _ => SandboxHealth::Degraded,
+        }
+    }
+
+    /// Value of the `state` label
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SandboxHealth::Healthy => "healthy",
+            SandboxHealth::Degraded => "degraded",
+            SandboxHealth::Failing => "failing",
+        }
+    }
+}
+
+/// Classify the sandboxes allowed by `filter`, keyed by sandbox ID
+pub fn classify_sandboxes(
+    sandboxes: &[(String, SandboxCRIMetadata)],
+    statuses: &[(String, ScrapeStatus)],
+    filter: &SandboxFilter,
+) -> HashMap<String, SandboxHealth> {
+    let statuses: HashMap<&str, &ScrapeStatus> = statuses
+        .iter()
+        .map(|(id, status)| (id.as_str(), status))
+        .collect();
+    sandboxes
+        .iter()
+        .filter(|(_, metadata)| filter.allows(metadata))
+        .map(|(id, metadata)| {
+            let health = SandboxHealth::classify(metadata, statuses.get(id.as_str()).copied());
+            (id.clone(), health)
+        })
+        .collect()
+}
+
+/// Render the classification of the given sandboxes in Prometheus text format
+///
+/// Every sandbox gets one series per state, set to 1 for its current state.
+pub fn render_sandbox_health(
+    sandboxes: &[(String, SandboxCRIMetadata)],
+    health: &HashMap<String, SandboxHealth>,
+) -> String {
+    let mut output = String::new();
+    let mut classified: Vec<_> = sandboxes
+        .iter()
+        .filter_map(|(id, metadata)| health.get(id).map(|health| (id, metadata, *health)))
+        .collect();
+    if classified.is_empty() {
+        return output;
+    }
+    classified.sort_by(|a, b| a.0.cmp(b.0));
+
+    output.push_str("# HELP kata_pulse_sandbox_health Monitoring health of the sandbox (1 for its current state)\n");
+    output.push_str("# TYPE kata_pulse_sandbox_health gauge\n");
+    for (id, metadata, current) in classified {
+        for state in SandboxHealth::ALL {
+            output.push_str(&format!(
+                "kata_pulse_sandbox_health{{sandbox_id=\"{}\",pod=\"{}\",namespace=\"{}\",state=\"{}\"}} {}\n",
+                escape_label_value(id),
+                escape_label_value(&metadata.name),
+                escape_label_value(&metadata.namespace),
+                state.as_str(),
+                if state == current { 1 } else { 0 }
+            ));
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(up: bool, quarantined: bool) -> ScrapeStatus {
+        ScrapeStatus {
+            up,
+            quarantined,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let synced = SandboxCRIMetadata {
+            metadata_status: MetadataStatus::Synced,
+            ..Default::default()
+        };
+        let unavailable = SandboxCRIMetadata {
+            metadata_status: MetadataStatus::MetadataUnavailable,
+            ..Default::default()
+        };
+
+        let classify = SandboxHealth::classify;
+        assert_eq!(
+            classify(&synced, Some(&status(true, false))),
+            SandboxHealth::Healthy
+        );
+        assert_eq!(
+            classify(&SandboxCRIMetadata::default(), Some(&status(true, false))),
+            SandboxHealth::Healthy
+        );
+        assert_eq!(classify(&synced, None), SandboxHealth::Degraded);
+        assert_eq!(
+            classify(&synced, Some(&status(false, false))),
+            SandboxHealth::Degraded
+        );
+        assert_eq!(
+            classify(&unavailable, Some(&status(true, false))),
+            SandboxHealth::Degraded
+        );
+        assert_eq!(
+            classify(&synced, Some(&status(false, true))),
+            SandboxHealth::Failing
+        );
+    }
+
+    #[test]
+    fn test_render_sandbox_health() {
+        let sandbox = |id: &str, namespace: &str| {
+            (
+                id.to_string(),
+                SandboxCRIMetadata {
+                    name: format!("pod-{}", id),
+                    namespace: namespace.to_string(),
+                    ..Default::default()
+                },
+            )
+        };
+        let sandboxes = vec![
+            sandbox("sb-2", "default"),
+            sandbox("sb-1", "default"),
+            sandbox("sb-3", "kube-system"),
+        ];
+        let statuses = vec![
+            ("sb-1".to_string(), status(true, false)),
+            ("sb-2".to_string(), status(false, true)),
+        ];
+        let filter = SandboxFilter::default().with_namespace_deny(vec!["kube-system".to_string()]);
+
+        let health = classify_sandboxes(&sandboxes, &statuses, &filter);
+        assert_eq!(health.len(), 2);
+        assert_eq!(health["sb-1"], SandboxHealth::Healthy);
+        assert_eq!(health["sb-2"], SandboxHealth::Failing);
+
+        let output = render_sandbox_health(&sandboxes, &health);
+        assert!(output.contains(
+            "kata_pulse_sandbox_health{sandbox_id=\"sb-1\",pod=\"pod-sb-1\",namespace=\"default\",state=\"healthy\"} 1\n"
+        ));
+        assert!(output.contains(
+            "kata_pulse_sandbox_health{sandbox_id=\"sb-2\",pod=\"pod-sb-2\",namespace=\"default\",state=\"failing\"} 1\n"
+        ));
+        assert!(output.contains(
+            "kata_pulse_sandbox_health{sandbox_id=\"sb-2\",pod=\"pod-sb-2\",namespace=\"default\",state=\"healthy\"} 0\n"
+        ));
+        assert!(!output.contains("sb-3"));
+        assert!(output.find("sb-1").unwrap() < output.find("sb-2").unwrap());
+
+        assert!(render_sandbox_health(&sandboxes, &HashMap::new()).is_empty());
+    }
+}
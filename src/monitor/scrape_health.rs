@@ -0,0 +1,441 @@
+//! Scrape health tracking - per-sandbox results of the last collection attempts
+//!
+//! Responsibilities:
+//! - Record success/failure and duration of each sandbox scrape
+//! - Track consecutive failures per sandbox
+//! - Quarantine persistently failing sandboxes (circuit breaker with backoff)
+//! - Count malformed lines skipped while parsing each sandbox's metrics
+//! - Count shim responses rejected for exceeding the payload limit
+//! - Render `kata_pulse_sandbox_up`, scrape duration, parse error and
+//!   oversized payload metrics
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use super::sandbox_cache::SandboxCRIMetadata;
+use crate::utils::metrics_converter::cadvisor::escape_label_value;
+
+/// Result of the most recent scrape(s) of a sandbox
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ScrapeStatus {
+    /// Whether the last scrape succeeded
+    pub up: bool,
+    /// Duration of the last scrape attempt
+    pub last_duration: Duration,
+    /// Number of consecutive failed scrapes (0 after a success)
+    pub consecutive_failures: u32,
+    /// Error message of the last failed scrape
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// Whether the circuit breaker is open for this sandbox
+    pub quarantined: bool,
+    /// Number of scrape cycles to skip before the next probe
+    pub skip_cycles_remaining: u32,
+    /// Malformed metric lines skipped over all scrapes
+    pub parse_errors_total: u64,
+    /// Shim responses rejected for exceeding the payload limit
+    pub oversized_payloads_total: u64,
+}
+
+/// Circuit breaker settings for persistently failing sandboxes
+#[derive(Clone, Copy, Debug)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures before a sandbox is quarantined
+    pub failure_threshold: u32,
+    /// Upper bound for the number of skipped cycles between probes
+    pub max_skip_cycles: u32,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        CircuitBreakerConfig {
+            failure_threshold: 3,
+            max_skip_cycles: 16,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    /// Cycles to skip after `consecutive_failures` failures (0 = not quarantined)
+    ///
+    /// Doubles with every failed probe: 1, 2, 4, ... up to `max_skip_cycles`.
+    fn skip_cycles(&self, consecutive_failures: u32) -> u32 {
+        if consecutive_failures < self.failure_threshold {
+            return 0;
+        }
+        let exponent = (consecutive_failures - self.failure_threshold).min(31);
+        (1u32 << exponent).min(self.max_skip_cycles)
+    }
+}
+
+/// Thread-safe registry of per-sandbox scrape results
+#[derive(Clone, Default)]
+pub struct ScrapeHealth {
+    statuses: Arc<RwLock<HashMap<String, ScrapeStatus>>>,
+    circuit_breaker: CircuitBreakerConfig,
+}
+
+impl ScrapeHealth {
+    /// Create an empty scrape health registry
+    pub fn new(circuit_breaker: CircuitBreakerConfig) -> Self {
+        ScrapeHealth {
+            statuses: Arc::default(),
+            circuit_breaker,
+        }
+    }
+
+    /// Check whether a due sandbox should be scraped in this cycle
+    ///
+    /// Quarantined sandboxes are skipped until their backoff expires; the next
+    /// scrape after that acts as a probe.
+    pub async fn should_scrape(&self, sandbox_id: &str) -> bool {
+        let mut statuses = self.statuses.write().await;
+        match statuses.get_mut(sandbox_id) {
+            Some(status) if status.skip_cycles_remaining > 0 => {
+                status.skip_cycles_remaining -= 1;
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Check whether a sandbox is currently quarantined
+    pub async fn is_quarantined(&self, sandbox_id: &str) -> bool {
+        self.statuses
+            .read()
+            .await
+            .get(sandbox_id)
+            .is_some_and(|status| status.quarantined)
+    }
+
+    /// IDs of all quarantined sandboxes, sorted
+    pub async fn quarantined(&self) -> Vec<String> {
+        let statuses = self.statuses.read().await;
+        let mut ids: Vec<String> = statuses
+            .iter()
+            .filter(|(_, status)| status.quarantined)
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Record a successful scrape
+    pub async fn record_success(&self, sandbox_id: &str, duration: Duration) {
+        let mut statuses = self.statuses.write().await;
+        let status = statuses.entry(sandbox_id.to_string()).or_default();
+        status.up = true;
+        status.last_duration = duration;
+        status.consecutive_failures = 0;
+        status.last_error = None;
+        status.quarantined = false;
+        status.skip_cycles_remaining = 0;
+    }
+
+    /// Record a failed scrape, returning the updated status
+    pub async fn record_failure(
+        &self,
+        sandbox_id: &str,
+        duration: Duration,
+        error: String,
+    ) -> ScrapeStatus {
+        let mut statuses = self.statuses.write().await;
+        let status = statuses.entry(sandbox_id.to_string()).or_default();
+        status.up = false;
+        status.last_duration = duration;
+        status.consecutive_failures = status.consecutive_failures.saturating_add(1);
+        status.last_error = Some(error);
+        status.skip_cycles_remaining = self
+            .circuit_breaker
+            .skip_cycles(status.consecutive_failures);
+        status.quarantined = status.skip_cycles_remaining > 0;
+        status.clone()
+    }
+
+    /// Count malformed lines found while parsing a scrape
+    pub async fn record_parse_errors(&self, sandbox_id: &str, count: usize) {
+        if count == 0 {
+            return;
+        }
+        let mut statuses = self.statuses.write().await;
+        let status = statuses.entry(sandbox_id.to_string()).or_default();
+        status.parse_errors_total = status.parse_errors_total.saturating_add(count as u64);
+    }
+
+    /// Count a shim response rejected for exceeding the payload limit
+    pub async fn record_oversized_payload(&self, sandbox_id: &str) {
+        let mut statuses = self.statuses.write().await;
+        let status = statuses.entry(sandbox_id.to_string()).or_default();
+        status.oversized_payloads_total = status.oversized_payloads_total.saturating_add(1);
+    }
+
+    /// Get the scrape status of a sandbox
+    pub async fn get(&self, sandbox_id: &str) -> Option<ScrapeStatus> {
+        self.statuses.read().await.get(sandbox_id).cloned()
+    }
+
+    /// Forget sandboxes that are no longer tracked
+    pub async fn retain(&self, sandbox_ids: &[String]) {
+        let mut statuses = self.statuses.write().await;
+        statuses.retain(|id, _| sandbox_ids.contains(id));
+    }
+
+    /// Snapshot of all scrape statuses
+    pub async fn snapshot(&self) -> Vec<(String, ScrapeStatus)> {
+        let statuses = self.statuses.read().await;
+        statuses
+            .iter()
+            .map(|(id, status)| (id.clone(), status.clone()))
+            .collect()
+    }
+}
+
+/// Label set identifying a sandbox in scrape health metrics
+fn sandbox_labels(sandbox_id: &str, metadata: Option<&SandboxCRIMetadata>) -> String {
+    let (pod, namespace) = metadata
+        .map(|m| (m.name.as_str(), m.namespace.as_str()))
+        .unwrap_or_default();
+    format!(
+        r#"{{sandbox_id="{}",pod="{}",namespace="{}"}}"#,
+        escape_label_value(sandbox_id),
+        escape_label_value(pod),
+        escape_label_value(namespace)
+    )
+}
+
+/// Render scrape health metrics in Prometheus text format
+///
+/// `sandboxes` pairs each sandbox's scrape status with its CRI metadata (if known),
+/// which provides the pod/namespace labels. `removed` lists recently removed
+/// sandboxes, which are reported as down for a few intervals so their series end
+/// with an explicit 0 instead of disappearing at their last value.
+pub fn render_scrape_health(
+    sandboxes: &[(String, ScrapeStatus, Option<SandboxCRIMetadata>)],
+    removed: &[(String, SandboxCRIMetadata)],
+) -> String {
+    let mut output = String::new();
+    if sandboxes.is_empty() && removed.is_empty() {
+        return output;
+    }
+
+    let labels: Vec<String> = sandboxes
+        .iter()
+        .map(|(sandbox_id, _, metadata)| sandbox_labels(sandbox_id, metadata.as_ref()))
+        .collect();
+
+    output.push_str(
+        "# HELP kata_pulse_sandbox_up Whether the last metrics scrape of the sandbox succeeded\n",
+    );
+    output.push_str("# TYPE kata_pulse_sandbox_up gauge\n");
+    for ((_, status, _), labels) in sandboxes.iter().zip(&labels) {
+        output.push_str(&format!(
+            "kata_pulse_sandbox_up{} {}\n",
+            labels,
+            if status.up { 1 } else { 0 }
+        ));
+    }
+    for (sandbox_id, metadata) in removed {
+        output.push_str(&format!(
+            "kata_pulse_sandbox_up{} 0\n",
+            sandbox_labels(sandbox_id, Some(metadata))
+        ));
+    }
+
+    if sandboxes.is_empty() {
+        return output;
+    }
+
+    output.push_str("# HELP kata_pulse_sandbox_scrape_duration_seconds Duration of the last metrics scrape of the sandbox\n");
+    output.push_str("# TYPE kata_pulse_sandbox_scrape_duration_seconds gauge\n");
+    for ((_, status, _), labels) in sandboxes.iter().zip(&labels) {
+        output.push_str(&format!(
+            "kata_pulse_sandbox_scrape_duration_seconds{} {}\n",
+            labels,
+            status.last_duration.as_secs_f64()
+        ));
+    }
+
+    output.push_str("# HELP kata_pulse_parse_errors_total Malformed metric lines skipped while parsing the sandbox's metrics\n");
+    output.push_str("# TYPE kata_pulse_parse_errors_total counter\n");
+    for ((_, status, _), labels) in sandboxes.iter().zip(&labels) {
+        output.push_str(&format!(
+            "kata_pulse_parse_errors_total{} {}\n",
+            labels, status.parse_errors_total
+        ));
+    }
+
+    output.push_str("# HELP kata_pulse_shim_payload_too_large_total Shim responses of the sandbox rejected for exceeding the payload size limit\n");
+    output.push_str("# TYPE kata_pulse_shim_payload_too_large_total counter\n");
+    for ((_, status, _), labels) in sandboxes.iter().zip(&labels) {
+        output.push_str(&format!(
+            "kata_pulse_shim_payload_too_large_total{} {}\n",
+            labels, status.oversized_payloads_total
+        ));
+    }
+
+    // cAdvisor-compatible flag: 1 if any scrape failed in the last cycle
+    let any_error = sandboxes.iter().any(|(_, status, _)| !status.up);
+    output.push_str(
+        "# HELP container_scrape_error 1 if there was an error while getting container metrics, 0 otherwise\n",
+    );
+    output.push_str("# TYPE container_scrape_error gauge\n");
+    output.push_str(&format!(
+        "container_scrape_error {}\n",
+        if any_error { 1 } else { 0 }
+    ));
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_success_and_failure() {
+        let health = ScrapeHealth::default();
+
+        health
+            .record_failure("sb-1", Duration::from_millis(10), "boom".to_string())
+            .await;
+        health
+            .record_failure("sb-1", Duration::from_millis(10), "boom".to_string())
+            .await;
+        let status = health.get("sb-1").await.unwrap();
+        assert!(!status.up);
+        assert_eq!(status.consecutive_failures, 2);
+        assert_eq!(status.last_error.as_deref(), Some("boom"));
+
+        health
+            .record_success("sb-1", Duration::from_millis(5))
+            .await;
+        let status = health.get("sb-1").await.unwrap();
+        assert!(status.up);
+        assert_eq!(status.consecutive_failures, 0);
+        assert!(status.last_error.is_none());
+
+        health.record_parse_errors("sb-1", 2).await;
+        health.record_parse_errors("sb-1", 0).await;
+        health.record_parse_errors("sb-1", 1).await;
+        assert_eq!(health.get("sb-1").await.unwrap().parse_errors_total, 3);
+
+        health.record_oversized_payload("sb-1").await;
+        assert_eq!(
+            health.get("sb-1").await.unwrap().oversized_payloads_total,
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_backs_off_and_probes() {
+        let health = ScrapeHealth::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            max_skip_cycles: 4,
+        });
+        let fail = || health.record_failure("sb-1", Duration::ZERO, "refused".to_string());
+
+        assert!(!fail().await.quarantined);
+        assert!(health.should_scrape("sb-1").await);
+
+        // Threshold reached: skip one cycle, then probe
+        let status = fail().await;
+        assert!(status.quarantined);
+        assert_eq!(status.skip_cycles_remaining, 1);
+        assert_eq!(health.quarantined().await, vec!["sb-1".to_string()]);
+        assert!(!health.should_scrape("sb-1").await);
+        assert!(health.should_scrape("sb-1").await);
+
+        // Failed probes double the backoff, capped at max_skip_cycles
+        assert_eq!(fail().await.skip_cycles_remaining, 2);
+        assert_eq!(fail().await.skip_cycles_remaining, 4);
+        assert_eq!(fail().await.skip_cycles_remaining, 4);
+
+        // A successful probe closes the breaker
+        health.record_success("sb-1", Duration::ZERO).await;
+        assert!(!health.is_quarantined("sb-1").await);
+        assert!(health.should_scrape("sb-1").await);
+        assert!(health.quarantined().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retain_forgets_removed_sandboxes() {
+        let health = ScrapeHealth::default();
+        health.record_success("sb-1", Duration::ZERO).await;
+        health.record_success("sb-2", Duration::ZERO).await;
+
+        health.retain(&["sb-2".to_string()]).await;
+        assert!(health.get("sb-1").await.is_none());
+        assert!(health.get("sb-2").await.is_some());
+    }
+
+    #[test]
+    fn test_render_scrape_health() {
+        let metadata = SandboxCRIMetadata {
+            uid: "uid-1".to_string(),
+            name: "my-pod".to_string(),
+            namespace: "default".to_string(),
+            ..Default::default()
+        };
+        let up = ScrapeStatus {
+            up: true,
+            last_duration: Duration::from_millis(250),
+            parse_errors_total: 3,
+            oversized_payloads_total: 2,
+            ..Default::default()
+        };
+        let down = ScrapeStatus {
+            up: false,
+            consecutive_failures: 1,
+            ..Default::default()
+        };
+
+        let output = render_scrape_health(
+            &[
+                ("sb-1".to_string(), up, Some(metadata)),
+                ("sb-2".to_string(), down, None),
+            ],
+            &[],
+        );
+
+        assert!(output.contains(
+            r#"kata_pulse_sandbox_up{sandbox_id="sb-1",pod="my-pod",namespace="default"} 1"#
+        ));
+        assert!(
+            output.contains(r#"kata_pulse_sandbox_up{sandbox_id="sb-2",pod="",namespace=""} 0"#)
+        );
+        assert!(output.contains(
+            r#"kata_pulse_sandbox_scrape_duration_seconds{sandbox_id="sb-1",pod="my-pod",namespace="default"} 0.25"#
+        ));
+        assert!(output.contains(
+            r#"kata_pulse_parse_errors_total{sandbox_id="sb-1",pod="my-pod",namespace="default"} 3"#
+        ));
+        assert!(output.contains(
+            r#"kata_pulse_shim_payload_too_large_total{sandbox_id="sb-1",pod="my-pod",namespace="default"} 2"#
+        ));
+        assert!(output.contains("container_scrape_error 1"));
+    }
+
+    #[test]
+    fn test_render_scrape_health_empty() {
+        assert!(render_scrape_health(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_render_removed_sandboxes_as_down() {
+        let metadata = SandboxCRIMetadata {
+            name: "gone-pod".to_string(),
+            namespace: "default".to_string(),
+            ..Default::default()
+        };
+        let output = render_scrape_health(&[], &[("sb-9".to_string(), metadata)]);
+
+        assert!(output.contains(
+            r#"kata_pulse_sandbox_up{sandbox_id="sb-9",pod="gone-pod",namespace="default"} 0"#
+        ));
+        assert_eq!(output.matches("# TYPE").count(), 1);
+        assert!(!output.contains("container_scrape_error"));
+    }
+}
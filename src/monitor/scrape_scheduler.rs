@@ -0,0 +1,219 @@
+//! Scrape scheduler - decides which sandboxes are due for metrics collection
+//!
+//! Responsibilities:
+//! - Track the next collection deadline per sandbox
+//! - Honor per-sandbox interval overrides (pod annotation)
+//! - Spread sandboxes across the interval using a hashed offset
+//! - Compute when the collector should wake up next
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Scheduling state for a single sandbox
+#[derive(Clone, Debug)]
+struct ScheduleEntry {
+    next_due: Instant,
+    interval: Duration,
+}
+
+/// Per-sandbox scrape scheduler
+///
+/// Sandboxes without an override use the default (global) interval.
+/// Newly seen sandboxes are first scheduled at a stable, hashed offset within
+/// their interval, so scrapes are spread out instead of all firing on the same tick.
+pub struct ScrapeScheduler {
+    default_interval: Duration,
+    entries: HashMap<String, ScheduleEntry>,
+}
+
+impl ScrapeScheduler {
+    /// Create a new scheduler with the global collection interval
+    pub fn new(default_interval: Duration) -> Self {
+        ScrapeScheduler {
+            default_interval,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Change the global collection interval; sandboxes without an override
+    /// move to it on their next `take_due`
+    pub fn set_default_interval(&mut self, interval: Duration) {
+        self.default_interval = interval;
+    }
+
+    /// Resolve the effective interval for a sandbox
+    fn interval_for(&self, override_secs: Option<u64>) -> Duration {
+        override_secs
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(self.default_interval)
+    }
+
+    /// Stable offset of a sandbox within its interval (whole seconds)
+    ///
+    /// Offsets are quantized to seconds so sandboxes share wakeups in small groups
+    /// rather than waking the collector once per sandbox.
+    fn offset_for(sandbox_id: &str, interval: Duration) -> Duration {
+        let slots = interval.as_secs().max(1);
+        let mut hasher = DefaultHasher::new();
+        sandbox_id.hash(&mut hasher);
+        Duration::from_secs(hasher.finish() % slots)
+    }
+
+    /// Return the sandboxes that are due at `now` and reschedule them
+    ///
+    /// `sandboxes` is the current list of (sandbox ID, interval override in seconds).
+    /// Sandboxes that are no longer present are forgotten.
+    pub fn take_due(&mut self, now: Instant, sandboxes: &[(String, Option<u64>)]) -> Vec<String> {
+        self.entries
+            .retain(|id, _| sandboxes.iter().any(|(sandbox_id, _)| sandbox_id == id));
+
+        let mut due = Vec::new();
+        for (sandbox_id, override_secs) in sandboxes {
+            let interval = self.interval_for(*override_secs);
+            let entry = self
+                .entries
+                .entry(sandbox_id.clone())
+                .or_insert_with(|| ScheduleEntry {
+                    next_due: now + Self::offset_for(sandbox_id, interval),
+                    interval,
+                });
+
+            // Interval changed (annotation updated): don't wait out the old interval
+            if entry.interval != interval {
+                entry.interval = interval;
+                entry.next_due = entry
+                    .next_due
+                    .min(now + Self::offset_for(sandbox_id, interval));
+            }
+
+            if entry.next_due <= now {
+                entry.next_due = now + entry.interval;
+                due.push(sandbox_id.clone());
+            }
+        }
+
+        due
+    }
+
+    /// Compute when the collector should wake up next
+    ///
+    /// Never later than one default interval from `now`, so newly discovered
+    /// sandboxes are picked up at least as often as before.
+    pub fn next_wakeup(&self, now: Instant) -> Instant {
+        self.entries
+            .values()
+            .map(|entry| entry.next_due)
+            .fold(now + self.default_interval, Instant::min)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandboxes(list: &[(&str, Option<u64>)]) -> Vec<(String, Option<u64>)> {
+        list.iter().map(|(id, i)| (id.to_string(), *i)).collect()
+    }
+
+    /// Advance through `window` one second at a time, collecting all due sandboxes
+    fn collect_due(
+        scheduler: &mut ScrapeScheduler,
+        start: Instant,
+        window: Duration,
+        list: &[(String, Option<u64>)],
+    ) -> Vec<String> {
+        let mut due = Vec::new();
+        for sec in 0..window.as_secs() {
+            due.extend(scheduler.take_due(start + Duration::from_secs(sec), list));
+        }
+        due.sort();
+        due
+    }
+
+    #[tokio::test]
+    async fn test_new_sandboxes_are_due_within_one_interval() {
+        let mut scheduler = ScrapeScheduler::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let list = sandboxes(&[("a", None), ("b", Some(10))]);
+
+        let due = collect_due(&mut scheduler, start, Duration::from_secs(10), &list);
+        assert!(due.contains(&"b".to_string()));
+
+        let mut scheduler = ScrapeScheduler::new(Duration::from_secs(60));
+        let due = collect_due(&mut scheduler, start, Duration::from_secs(60), &list);
+        assert_eq!(due.iter().filter(|id| *id == "a").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_offsets_are_stable_and_within_interval() {
+        let interval = Duration::from_secs(60);
+        for id in ["sandbox-1", "sandbox-2", "abcdef0123456789"] {
+            let offset = ScrapeScheduler::offset_for(id, interval);
+            assert!(offset < interval);
+            assert_eq!(offset, ScrapeScheduler::offset_for(id, interval));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scrapes_are_spread_across_interval() {
+        let mut scheduler = ScrapeScheduler::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let ids: Vec<String> = (0..100).map(|i| format!("sandbox-{}", i)).collect();
+        let list: Vec<(String, Option<u64>)> = ids.iter().map(|id| (id.clone(), None)).collect();
+
+        let due_at_start = scheduler.take_due(start, &list);
+        assert!(
+            due_at_start.len() < 20,
+            "scrapes should not all fire on the same tick"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_per_sandbox_interval_override() {
+        let mut scheduler = ScrapeScheduler::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let list = sandboxes(&[("fast", Some(10)), ("slow", None)]);
+
+        let due = collect_due(&mut scheduler, start, Duration::from_secs(120), &list);
+        assert_eq!(due.iter().filter(|id| *id == "fast").count(), 12);
+        assert_eq!(due.iter().filter(|id| *id == "slow").count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_next_wakeup_is_capped_by_default_interval() {
+        let mut scheduler = ScrapeScheduler::new(Duration::from_secs(60));
+        let now = Instant::now();
+        assert_eq!(scheduler.next_wakeup(now), now + Duration::from_secs(60));
+
+        scheduler.take_due(now, &sandboxes(&[("a", Some(5))]));
+        assert!(scheduler.next_wakeup(now) <= now + Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_removed_sandboxes_are_forgotten() {
+        let mut scheduler = ScrapeScheduler::new(Duration::from_secs(60));
+        let now = Instant::now();
+        scheduler.take_due(now, &sandboxes(&[("a", Some(5)), ("b", None)]));
+        scheduler.take_due(now, &sandboxes(&[]));
+
+        assert_eq!(scheduler.next_wakeup(now), now + Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_interval_shortened_takes_effect() {
+        let mut scheduler = ScrapeScheduler::new(Duration::from_secs(300));
+        let start = Instant::now();
+        let list = sandboxes(&[("a", None)]);
+        collect_due(&mut scheduler, start, Duration::from_secs(300), &list);
+
+        // Switch to a 10s interval: it must be scraped within the next 10 seconds
+        let later = start + Duration::from_secs(300);
+        let list = sandboxes(&[("a", Some(10))]);
+        let due = collect_due(&mut scheduler, later, Duration::from_secs(10), &list);
+        assert_eq!(due, vec!["a".to_string()]);
+    }
+}
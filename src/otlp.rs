@@ -0,0 +1,95 @@
+//! OTLP export of tracing spans
+//!
+//! Responsibilities:
+//! - Build the tracing layer that exports the collection pipeline spans
+//!   (cycle, per-sandbox scrape, parse, convert, buffer swap) via OTLP/gRPC
+//! - Flush pending spans on shutdown
+//!
+//! Requires the `otlp` feature; without it an endpoint is rejected.
+
+use anyhow::Result;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// Service name reported in the exported spans
+#[cfg(feature = "otlp")]
+const SERVICE_NAME: &str = "kata-pulse";
+
+/// Boxed tracing layer added to the subscriber
+pub type BoxedLayer<S> = Box<dyn Layer<S> + Send + Sync>;
+
+/// Flushes the exported spans when dropped
+#[derive(Default)]
+pub struct OtlpGuard {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::TracerProvider>,
+}
+
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to flush OTLP spans: {}", e);
+            }
+        }
+    }
+}
+
+/// Build the layer exporting spans to the OTLP/gRPC `endpoint`
+///
+/// Must be called from within the Tokio runtime (spans are exported by a
+/// background batch task).
+#[cfg(feature = "otlp")]
+pub fn layer<S>(endpoint: Option<&str>) -> Result<(Option<BoxedLayer<S>>, OtlpGuard)>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+{
+    use anyhow::Context;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::trace::TracerProvider;
+    use opentelemetry_sdk::{runtime, Resource};
+
+    let Some(endpoint) = endpoint else {
+        return Ok((None, OtlpGuard::default()));
+    };
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()
+        .with_context(|| format!("failed to create OTLP exporter for {}", endpoint))?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([
+            KeyValue::new("service.name", SERVICE_NAME),
+            KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+        ]))
+        .build();
+    let tracer = provider.tracer(SERVICE_NAME);
+    let layer = tracing_opentelemetry::layer().with_tracer(tracer).boxed();
+
+    Ok((
+        Some(layer),
+        OtlpGuard {
+            provider: Some(provider),
+        },
+    ))
+}
+
+/// Build the layer exporting spans to the OTLP/gRPC `endpoint`
+#[cfg(not(feature = "otlp"))]
+pub fn layer<S>(endpoint: Option<&str>) -> Result<(Option<BoxedLayer<S>>, OtlpGuard)>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+{
+    if let Some(endpoint) = endpoint {
+        anyhow::bail!(
+            "OTLP endpoint {} requires kata-pulse to be built with the `otlp` feature",
+            endpoint
+        );
+    }
+    Ok((None, OtlpGuard::default()))
+}
@@ -1,43 +1,236 @@
 use axum::{
-    extract::Query,
-    response::{Html, IntoResponse},
-    routing::get,
-    Router,
+    extract::{Path, Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{Html, IntoResponse, Response},
+    routing::{get, put},
+    Extension, Router,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, info, warn};
 
+use crate::aggregator::Aggregator;
+use crate::auth::{Review, TokenReviewer};
+use crate::build_info::BuildInfo;
 use crate::context::AppContext;
+use crate::monitor::conversion_pool::ConversionJob;
+use crate::monitor::entity_info;
+use crate::monitor::fleet::FleetStats;
+use crate::monitor::metrics_cache::CachedMetrics;
+use crate::monitor::metrics_collector::{check_parse_errors, scrape_sandbox};
+use crate::monitor::sandbox_cache::SandboxCRIMetadata;
+use crate::monitor::sandbox_health::{self, SandboxHealth};
+use crate::monitor::scrape_health::render_scrape_health;
+use crate::tenants::{constant_time_eq, Scope, Tenants};
 use crate::utils::metrics_converter::cadvisor::PrometheusFormat;
-use crate::utils::metrics_converter::ConversionConfig;
+use crate::utils::prometheus_parser::PrometheusMetrics;
+use crate::utils::{allocator, interner};
 
-/// Extract sandbox ID from query parameters
+/// Timeout for synchronous scrapes when a sandbox's metrics are not cached yet
+const ON_DEMAND_SCRAPE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Extract sandbox ID and namespace from query parameters
 #[derive(Deserialize)]
 pub struct SandboxQuery {
     sandbox: Option<String>,
+    namespace: Option<String>,
+}
+
+/// Query parameters of the history endpoint
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    sandbox: Option<String>,
+    window: Option<String>,
+    deltas: Option<bool>,
+}
+
+/// Query parameters of the events endpoint
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    since: Option<String>,
 }
 
 /// Create the HTTP server router
 pub fn create_router(app_context: Arc<AppContext>) -> Router {
     let app_context_clone1 = app_context.clone();
     let app_context_clone2 = app_context.clone();
+    let app_context_clone3 = app_context.clone();
+    let app_context_clone4 = app_context.clone();
+    let app_context_clone5 = app_context.clone();
+    let app_context_clone6 = app_context.clone();
+    let app_context_clone7 = app_context.clone();
+    let app_context_clone8 = app_context.clone();
+
+    // Endpoints of pod-level data, answered within the scope of the request's token
+    let scoped = Router::new()
+        .route(
+            "/metrics",
+            get(
+                move |Extension(scope): Extension<Scope>,
+                      Query(params): Query<SandboxQuery>| async move {
+                    let Some(scope) = scope.narrow(params.namespace.as_deref()) else {
+                        return namespace_forbidden().into_response();
+                    };
+                    metrics_handler(app_context_clone1.clone(), params, scope)
+                        .await
+                        .into_response()
+                },
+            ),
+        )
+        .route(
+            "/sandboxes",
+            get(move |Extension(scope): Extension<Scope>| async move {
+                sandboxes_handler(app_context_clone2.clone(), scope).await
+            }),
+        )
+        .route(
+            "/sandboxes/{id}",
+            get(
+                move |Extension(scope): Extension<Scope>, Path(id): Path<String>| async move {
+                    sandbox_handler(app_context_clone7.clone(), id, scope).await
+                },
+            ),
+        )
+        .route(
+            "/debug/state",
+            get(move |Extension(scope): Extension<Scope>| async move {
+                if scope != Scope::All {
+                    return namespace_forbidden().into_response();
+                }
+                debug_state_handler(app_context_clone3.clone())
+                    .await
+                    .into_response()
+            }),
+        )
+        .route(
+            "/api/history",
+            get(
+                move |Extension(scope): Extension<Scope>,
+                      Query(params): Query<HistoryQuery>| async move {
+                    history_handler(app_context_clone4.clone(), params, scope).await
+                },
+            ),
+        )
+        .route(
+            "/api/events",
+            get(
+                move |Extension(scope): Extension<Scope>,
+                      Query(params): Query<EventsQuery>| async move {
+                    events_handler(app_context_clone8.clone(), params, scope).await
+                },
+            ),
+        )
+        .route_layer(middleware::from_fn_with_state(app_context, require_scope));
 
     Router::new()
         .route("/", get(index_page))
+        .merge(scoped)
+        .route("/version", get(version_handler))
         .route(
-            "/metrics",
-            get(move |Query(params): Query<SandboxQuery>| async move {
-                let ctx = app_context_clone1.clone();
-                metrics_handler(ctx, params).await
+            "/readyz",
+            get(move || async move { readyz_handler(app_context_clone5.clone()).await }),
+        )
+        .route(
+            "/admin/loglevel",
+            put(move |headers: HeaderMap, body: String| async move {
+                loglevel_handler(app_context_clone6.clone(), headers, body).await
             }),
         )
+}
+
+/// Create the HTTP server router of the aggregator mode
+pub fn create_aggregator_router(aggregator: Arc<Aggregator>, auth: Authentication) -> Router {
+    let aggregator_clone1 = aggregator.clone();
+    let aggregator_clone2 = aggregator.clone();
+    let aggregator_clone3 = aggregator.clone();
+
+    // Endpoints of pod-level data, answered within the scope of the request's token
+    let scoped =
+        Router::new()
+            .route(
+                "/metrics",
+                get(
+                    move |Extension(scope): Extension<Scope>,
+                          Query(params): Query<SandboxQuery>| async move {
+                        info!("Aggregated metrics request received");
+                        let Some(scope) = scope.narrow(params.namespace.as_deref()) else {
+                            return namespace_forbidden().into_response();
+                        };
+                        let output = aggregator_clone1.output().metrics.clone();
+                        metrics_response(scope.filter_metrics(output), None)
+                    },
+                ),
+            )
+            .route(
+                "/sandboxes",
+                get(move |Extension(scope): Extension<Scope>| async move {
+                    info!("Aggregated sandboxes listing request received");
+                    let sandboxes = aggregator_clone2.output().sandboxes.clone();
+                    (
+                        StatusCode::OK,
+                        [("Content-Type", "application/json; charset=utf-8")],
+                        scope.filter_sandboxes(sandboxes),
+                    )
+                        .into_response()
+                }),
+            )
+            .route_layer(middleware::from_fn_with_state(
+                auth,
+                require_aggregator_scope,
+            ));
+
+    Router::new()
+        .route("/", get(aggregator_index_page))
+        .merge(scoped)
+        .route("/version", get(version_handler))
         .route(
-            "/sandboxes",
-            get(move || async move { sandboxes_handler(app_context_clone2.clone()).await }),
+            "/readyz",
+            get(move || async move {
+                // Ready once the first round covered the targets
+                let ready = aggregator_clone3.is_ready();
+                let body = serde_json::json!({
+                    "status": if ready { "ready" } else { "not_ready" },
+                    "targets": aggregator_clone3.config().targets.len(),
+                });
+                let status = if ready {
+                    StatusCode::OK
+                } else {
+                    StatusCode::SERVICE_UNAVAILABLE
+                };
+                (
+                    status,
+                    [("Content-Type", "application/json; charset=utf-8")],
+                    body.to_string(),
+                )
+                    .into_response()
+            }),
         )
 }
 
+/// Index page handler of the aggregator mode
+async fn aggregator_index_page() -> impl IntoResponse {
+    let html = r#"<html>
+    <head><title>Kata Pulse</title></head>
+    <body>
+    <h1>Available HTTP endpoints (aggregator mode):</h1>
+    <ul>
+    <li><b><a href='/metrics'>/metrics</a></b>: Merged metrics of all kata-pulse instances</li>
+    <li><b><a href='/sandboxes'>/sandboxes</a></b>: Sandboxes of all kata-pulse instances</li>
+    <li><b><a href='/readyz'>/readyz</a></b>: Readiness (after the first scrape round)</li>
+    <li><b><a href='/version'>/version</a></b>: Version, git SHA, build date and enabled features</li>
+    </ul>
+    </body>
+    </html>"#;
+    Html(html).into_response()
+}
+
 /// Index page handler
 async fn index_page() -> impl IntoResponse {
     info!("Index page request received");
@@ -46,8 +239,14 @@ async fn index_page() -> impl IntoResponse {
     <body>
     <h1>Available HTTP endpoints:</h1>
     <ul>
-    <li><b><a href='/metrics'>/metrics</a></b>: Get metrics from sandboxes</li>
+    <li><b><a href='/metrics'>/metrics</a></b>: Get metrics from sandboxes (<b>?sandbox=&lt;id&gt;</b> or <b>?namespace=&lt;ns&gt;</b> for a subset)</li>
     <li><b><a href='/sandboxes'>/sandboxes</a></b>: List all Kata Containers sandboxes</li>
+    <li><b>/sandboxes/&lt;id&gt;</b>: One sandbox with its metadata, CRI annotations and health</li>
+    <li><b><a href='/readyz'>/readyz</a></b>: Readiness with pipeline component states</li>
+    <li><b><a href='/version'>/version</a></b>: Version, git SHA, build date and enabled features</li>
+    <li><b><a href='/debug/state'>/debug/state</a></b>: Scrape health and quarantined sandboxes</li>
+    <li><b>/api/history?sandbox=&lt;id&gt;&amp;window=10m&amp;deltas=true</b>: Recent metric snapshots of a sandbox</li>
+    <li><b><a href='/api/events'>/api/events</a>?since=10m</b>: Sandbox lifecycle events (added, removed, metadata synced)</li>
     </ul>
     </body>
     </html>"#;
@@ -56,7 +255,11 @@ async fn index_page() -> impl IntoResponse {
 
 /// Text version of index page
 /// Metrics endpoint handler
-async fn metrics_handler(ctx: Arc<AppContext>, params: SandboxQuery) -> impl IntoResponse {
+async fn metrics_handler(
+    ctx: Arc<AppContext>,
+    params: SandboxQuery,
+    scope: Scope,
+) -> impl IntoResponse {
     info!("Metrics request received");
 
     debug!("Processing metrics request");
@@ -64,98 +267,119 @@ async fn metrics_handler(ctx: Arc<AppContext>, params: SandboxQuery) -> impl Int
     // Check if specific sandbox requested
     if let Some(sandbox_id) = params.sandbox {
         info!(sandbox_id = %sandbox_id, "Fetching metrics for specific sandbox");
+        // Like the aggregated output, only sandboxes of the cache that pass the filter
+        let metadata = ctx.sandbox_cache().get_metadata(&sandbox_id).await;
+        let Some(metadata) = metadata.filter(|metadata| scope.allows(&metadata.namespace)) else {
+            debug!(sandbox_id = %sandbox_id, "Sandbox unknown or outside the namespaces of the request");
+            return (
+                axum::http::StatusCode::NOT_FOUND,
+                [("Content-Type", "text/plain; charset=utf-8")],
+                "Sandbox not found".to_string(),
+            )
+                .into_response();
+        };
+        if !ctx.config().load().sandbox_filter.allows(&metadata) {
+            debug!(sandbox_id = %sandbox_id, namespace = %metadata.namespace, "Sandbox excluded by filter");
+            return (
+                axum::http::StatusCode::NOT_FOUND,
+                [("Content-Type", "text/plain; charset=utf-8")],
+                "Sandbox is excluded from collection".to_string(),
+            )
+                .into_response();
+        }
         let metrics_cache = ctx.metrics_cache();
         debug!("Acquired metrics_cache, calling get_metrics");
 
-        match metrics_cache.get_metrics(&sandbox_id).await {
+        let cached_metrics = match metrics_cache.get_metrics(&sandbox_id).await {
             Some(cached_metrics) => {
                 info!(sandbox_id = %sandbox_id, "Found cached metrics for sandbox");
-
-                // Convert to cAdvisor format with CRI enrichment
-                debug!(sandbox_id = %sandbox_id, "Converting to cAdvisor metrics format with CRI enrichment");
-                let config = ConversionConfig::default();
-                let cri_enricher = ctx.cri_enricher().clone();
-                let converter = crate::utils::metrics_converter::create_converter(
-                    config,
-                    cri_enricher,
-                    sandbox_id.clone(),
-                );
-
-                // Try to convert to cAdvisor format, fall back to raw format if conversion fails
-                match converter.convert_all(&cached_metrics.metrics) {
-                    Ok(cadvisor_metrics) => {
-                        debug!(sandbox_id = %sandbox_id, "Successfully converted to cAdvisor format");
-                        let output = cadvisor_metrics.to_prometheus_format(Some(&sandbox_id));
-                        info!(sandbox_id = %sandbox_id, output_size = output.len(), "Returning converted metrics");
-                        return (
-                            axum::http::StatusCode::OK,
-                            [("Content-Type", "text/plain; charset=utf-8")],
-                            output,
-                        )
-                            .into_response();
-                    }
-                    Err(e) => {
-                        warn!(sandbox_id = %sandbox_id, error = %e, "Failed to convert metrics, falling back to raw format");
-                        let output = cached_metrics.metrics.to_prometheus_format(None);
-                        return (
-                            axum::http::StatusCode::OK,
-                            [("Content-Type", "text/plain; charset=utf-8")],
-                            output,
-                        )
-                            .into_response();
-                    }
+                cached_metrics
+            }
+            None => match scrape_on_demand(&ctx, &sandbox_id).await {
+                Some(metrics) => CachedMetrics::new(Arc::new(metrics), Instant::now()),
+                None => {
+                    warn!(sandbox_id = %sandbox_id, "No cached metrics available for sandbox");
+                    return (
+                        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                        [("Content-Type", "text/plain; charset=utf-8")],
+                        "No cached metrics available for this sandbox".to_string(),
+                    )
+                        .into_response();
                 }
+            },
+        };
+
+        let scrape_health = scrape_health_output(&ctx, Some(&sandbox_id)).await;
+
+        // Convert to cAdvisor format with CRI enrichment (memoized per scrape),
+        // fall back to raw format if conversion fails
+        debug!(sandbox_id = %sandbox_id, "Converting to cAdvisor metrics format with CRI enrichment");
+        let job = ConversionJob {
+            sandbox_id: sandbox_id.clone(),
+            metrics: cached_metrics.clone(),
+            labels: ctx.cri_enricher().enrich(&sandbox_id).await,
+        };
+        let converted = ctx
+            .conversion_pool()
+            .convert_one(&ctx.config().load(), job)
+            .await;
+        let mut output = match converted {
+            Ok(text) => {
+                info!(sandbox_id = %sandbox_id, output_size = text.len(), "Returning converted metrics");
+                text.to_string()
             }
-            None => {
-                warn!(sandbox_id = %sandbox_id, "No cached metrics available for sandbox");
-                return (
-                    axum::http::StatusCode::INTERNAL_SERVER_ERROR,
-                    [("Content-Type", "text/plain; charset=utf-8")],
-                    "No cached metrics available for this sandbox".to_string(),
-                )
-                    .into_response();
+            Err(e) => {
+                warn!(sandbox_id = %sandbox_id, error = %e, "Failed to convert metrics, falling back to raw format");
+                cached_metrics.metrics.to_prometheus_format(None)
             }
-        }
+        };
+        output.push_str(&scrape_health);
+        return metrics_response(
+            scope.filter_metrics(output),
+            Some(cached_metrics.collected_at_unix()),
+        );
     }
 
-    // Aggregate metrics from all sandboxes
-    let cache = ctx.sandbox_cache();
-    let sandboxes = cache.get_sandboxes_with_metadata().await;
-    let metrics_cache = ctx.metrics_cache();
-
-    let mut output = String::new();
-    for (sandbox_id, _metadata) in &sandboxes {
-        debug!(sandbox_id = %sandbox_id, "Processing metrics for sandbox");
-
-        // Get metrics first (async operation)
-        let metrics_opt = metrics_cache.get_metrics(sandbox_id).await;
-
-        // Then process with converter (sync operation, no awaits)
-        if let Some(cached_metrics) = metrics_opt {
-            let config = ConversionConfig::default();
-            let cri_enricher = ctx.cri_enricher().clone();
-            let converter = crate::utils::metrics_converter::create_converter(
-                config,
-                cri_enricher,
-                sandbox_id.clone(),
-            );
-
-            match converter.convert_all(&cached_metrics.metrics) {
-                Ok(cadvisor_metrics) => {
-                    debug!(sandbox_id = %sandbox_id, "Successfully converted to cAdvisor format");
-                    output.push_str(&cadvisor_metrics.to_prometheus_format(Some(sandbox_id)));
-                }
-                Err(e) => {
-                    warn!(sandbox_id = %sandbox_id, error = %e, "Failed to convert metrics, falling back to raw format");
-                    let metrics = &cached_metrics.metrics;
-                    output.push_str(&metrics.to_prometheus_format(None));
-                }
-            }
-            output.push('\n');
-            debug!(sandbox_id = %sandbox_id, output_size = output.len(), "Added metrics to output");
-        } else {
-            warn!(sandbox_id = %sandbox_id, "No cached metrics available for sandbox");
-        }
+    // Aggregate metrics from all sandboxes (rendered once per buffer swap)
+    let sandboxes = ctx.sandbox_cache().get_sandboxes_with_metadata().await;
+    let rendered = ctx.rendered_metrics().get().await;
+
+    let mut output = String::with_capacity(rendered.len() + 16 * 1024);
+    output.push_str(&rendered);
+    output.push_str(&scrape_health_output(&ctx, None).await);
+    output.push_str(&fleet_stats(&ctx, &sandboxes).await.render_metrics());
+    output.push_str(&sandbox_health::render_sandbox_health(
+        &sandboxes,
+        &sandbox_health(&ctx, &sandboxes).await,
+    ));
+    output.push_str(&entity_info::render_sandbox_info(
+        &sandboxes,
+        &ctx.config().load().sandbox_filter,
+    ));
+    output.push_str(&ctx.pipeline_health().render_metrics());
+    output.push_str(&ctx.sandbox_cache().events().render_metrics());
+    output.push_str(&ctx.node_info().render_metrics());
+    let build_info = BuildInfo::current();
+    output.push_str(&entity_info::render_target_info(
+        ctx.node_info(),
+        &build_info,
+    ));
+    output.push_str(&build_info.render_metrics());
+    output.push_str(&interner::labels().stats().render_metrics());
+    if let Some(stats) = allocator::stats() {
+        output.push_str(&stats.render_metrics());
+    }
+    output.push_str(&ctx.exports().render_metrics());
+    output.push_str(&ctx.metrics_cache().render_metrics());
+    if let Some(chaos) = ctx.chaos() {
+        output.push_str(&chaos.render_metrics());
+    }
+    if let Some(janitor) = ctx.janitor() {
+        output.push_str(&janitor.render_metrics());
+    }
+    output.push_str(&ctx.boot_tracker().render_metrics());
+    if let Some(agent_stats) = ctx.agent_stats() {
+        output.push_str(&agent_stats.render_metrics());
     }
 
     if output.is_empty() {
@@ -166,24 +390,152 @@ async fn metrics_handler(ctx: Arc<AppContext>, params: SandboxQuery) -> impl Int
     } else {
         info!(output_size = output.len(), "Returning aggregated metrics");
     }
-    (
-        axum::http::StatusCode::OK,
-        [("Content-Type", "text/plain; charset=utf-8")],
-        output,
+    metrics_response(
+        scope.filter_metrics(output),
+        ctx.metrics_cache().last_collection(),
     )
-        .into_response()
+}
+
+/// Response header carrying the collection time of the served metrics
+const COLLECTED_AT_HEADER: &str = "X-Kata-Pulse-Collected-At";
+
+/// A `/metrics` response, stamped with the Unix time its metrics were collected at
+///
+/// The header is omitted before the first collection cycle finished.
+fn metrics_response(output: String, collected_at: Option<Duration>) -> axum::response::Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    if let Some(collected_at) = collected_at {
+        let value = format!("{:.3}", collected_at.as_secs_f64());
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(COLLECTED_AT_HEADER, value);
+        }
+    }
+    (StatusCode::OK, headers, output).into_response()
+}
+
+/// Render per-sandbox scrape health metrics (all sandboxes, or a single one)
+///
+/// The aggregated render also reports recently removed sandboxes as down.
+async fn scrape_health_output(ctx: &AppContext, sandbox_id: Option<&str>) -> String {
+    let statuses = match sandbox_id {
+        Some(id) => ctx
+            .scrape_health()
+            .get(id)
+            .await
+            .map(|status| vec![(id.to_string(), status)])
+            .unwrap_or_default(),
+        None => ctx.scrape_health().snapshot().await,
+    };
+
+    let mut sandboxes = Vec::with_capacity(statuses.len());
+    for (id, status) in statuses {
+        let metadata = ctx.sandbox_cache().get_metadata(&id).await;
+        sandboxes.push((id, status, metadata));
+    }
+    sandboxes.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let removed: Vec<_> = match sandbox_id {
+        Some(_) => Vec::new(),
+        None => ctx
+            .metrics_cache()
+            .tombstones(ctx.config().load().metrics_interval())
+            .await
+            .into_iter()
+            .map(|(id, tombstone)| (id, tombstone.metadata))
+            .collect(),
+    };
+
+    render_scrape_health(&sandboxes, &removed)
+}
+
+/// Node-level counts of the given sandboxes and their scrape results
+async fn fleet_stats(ctx: &AppContext, sandboxes: &[(String, SandboxCRIMetadata)]) -> FleetStats {
+    let statuses = ctx.scrape_health().snapshot().await;
+    FleetStats::compute(sandboxes, &statuses, &ctx.config().load().sandbox_filter)
+}
+
+/// Health classification of the collected sandboxes among the given ones
+async fn sandbox_health(
+    ctx: &AppContext,
+    sandboxes: &[(String, SandboxCRIMetadata)],
+) -> HashMap<String, SandboxHealth> {
+    let statuses = ctx.scrape_health().snapshot().await;
+    sandbox_health::classify_sandboxes(sandboxes, &statuses, &ctx.config().load().sandbox_filter)
+}
+
+/// Scrape a sandbox synchronously when its metrics are not cached yet
+///
+/// New pods would otherwise have no metrics until the collector's first pass.
+/// Only sandboxes known to the sandbox cache are scraped, with a short timeout.
+async fn scrape_on_demand(ctx: &AppContext, sandbox_id: &str) -> Option<PrometheusMetrics> {
+    let known = ctx
+        .sandbox_cache()
+        .get_sandbox_list()
+        .await
+        .iter()
+        .any(|id| id == sandbox_id);
+    if !known {
+        debug!(sandbox_id = %sandbox_id, "Unknown sandbox, skipping on-demand scrape");
+        return None;
+    }
+    if ctx.scrape_health().is_quarantined(sandbox_id).await {
+        debug!(sandbox_id = %sandbox_id, "Sandbox quarantined, skipping on-demand scrape");
+        return None;
+    }
+
+    info!(sandbox_id = %sandbox_id, "Cache miss, scraping sandbox on demand");
+    let scraped = scrape_sandbox(
+        ctx.shim_client(),
+        ctx.sandbox_cache(),
+        sandbox_id,
+        ON_DEMAND_SCRAPE_TIMEOUT,
+        &ctx.config().load().name_filter,
+        ctx.chaos().map(|chaos| chaos.as_ref()),
+        ctx.recorder().map(|recorder| recorder.as_ref()),
+    )
+    .await;
+    match check_parse_errors(ctx.scrape_health(), ctx.parse_mode(), sandbox_id, scraped).await {
+        Ok(metrics) => Some(metrics),
+        Err(e) => {
+            warn!(sandbox_id = %sandbox_id, error = %e, "On-demand scrape failed");
+            None
+        }
+    }
+}
+
+/// A sandbox as listed by `/sandboxes`
+#[derive(Serialize)]
+struct SandboxEntry {
+    #[serde(flatten)]
+    metadata: SandboxCRIMetadata,
+    /// Health classification (omitted for sandboxes skipped by the filter)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    health: Option<SandboxHealth>,
 }
 
 /// Sandboxes listing handler
-async fn sandboxes_handler(ctx: Arc<AppContext>) -> impl IntoResponse {
+async fn sandboxes_handler(ctx: Arc<AppContext>, scope: Scope) -> impl IntoResponse {
     info!("Sandboxes listing request received");
     let cache = ctx.sandbox_cache();
     debug!("Acquiring sandbox cache");
-    let sandboxes = cache.get_sandboxes_with_metadata().await;
+    let mut sandboxes = cache.get_sandboxes_with_metadata().await;
+    sandboxes.retain(|(_, metadata)| scope.allows(&metadata.namespace));
     info!(
         sandbox_count = sandboxes.len(),
         "Returning list of sandboxes"
     );
+    let mut health = sandbox_health(&ctx, &sandboxes).await;
+    let sandboxes: Vec<_> = sandboxes
+        .into_iter()
+        .map(|(id, metadata)| {
+            let health = health.remove(&id);
+            (id, SandboxEntry { metadata, health })
+        })
+        .collect();
 
     let json_output = serde_json::to_string(&sandboxes).unwrap_or_else(|e| {
         warn!("Failed to serialize sandboxes: {}", e);
@@ -198,15 +550,525 @@ async fn sandboxes_handler(ctx: Arc<AppContext>) -> impl IntoResponse {
         .into_response()
 }
 
+/// Sandbox handler - one sandbox as listed by `/sandboxes`, with its ID
+async fn sandbox_handler(
+    ctx: Arc<AppContext>,
+    sandbox_id: String,
+    scope: Scope,
+) -> impl IntoResponse {
+    info!(sandbox_id = %sandbox_id, "Sandbox request received");
+    let metadata = ctx.sandbox_cache().get_metadata(&sandbox_id).await;
+    // Sandboxes outside the namespaces of the request are not found either
+    let Some(metadata) = metadata.filter(|metadata| scope.allows(&metadata.namespace)) else {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("sandbox {} not found", sandbox_id),
+        )
+            .into_response();
+    };
+    let sandboxes = [(sandbox_id.clone(), metadata)];
+    let mut health = sandbox_health(&ctx, &sandboxes).await;
+    let [(_, metadata)] = sandboxes;
+    let mut body = serde_json::to_value(SandboxEntry {
+        metadata,
+        health: health.remove(&sandbox_id),
+    })
+    .unwrap_or_default();
+    if let Some(entry) = body.as_object_mut() {
+        entry.insert("sandbox_id".to_string(), sandbox_id.into());
+    }
+
+    (
+        StatusCode::OK,
+        [("Content-Type", "application/json; charset=utf-8")],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+/// Readiness handler - 200 when all pipeline components are running, 503 otherwise
+async fn readyz_handler(ctx: Arc<AppContext>) -> impl IntoResponse {
+    let pipeline_health = ctx.pipeline_health();
+    let ready = pipeline_health.is_ready();
+    let components: std::collections::BTreeMap<_, _> = pipeline_health
+        .snapshot()
+        .into_iter()
+        .map(|(component, status)| (component.as_str(), status))
+        .collect();
+
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "components": components,
+    });
+    let status = if ready {
+        axum::http::StatusCode::OK
+    } else {
+        axum::http::StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        [("Content-Type", "application/json; charset=utf-8")],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+/// Debug state handler - scrape health per sandbox and the quarantine list
+async fn debug_state_handler(ctx: Arc<AppContext>) -> impl IntoResponse {
+    info!("Debug state request received");
+    let scrape_health = ctx.scrape_health();
+    let scrape_status: std::collections::BTreeMap<_, _> =
+        scrape_health.snapshot().await.into_iter().collect();
+    let quarantined = scrape_health.quarantined().await;
+    let sandboxes = ctx.sandbox_cache().get_sandboxes_with_metadata().await;
+    let config = ctx.config().load();
+
+    let state = serde_json::json!({
+        "node": ctx.node_info().as_ref(),
+        "sandbox_filter": &config.sandbox_filter,
+        "metrics_interval_secs": config.metrics_interval_secs,
+        "sandbox_count": sandboxes.len(),
+        "fleet": fleet_stats(&ctx, &sandboxes).await,
+        "cri_endpoint": crate::monitor::cri::active_endpoint(),
+        "shim_sockets": ctx.sandbox_cache().get_shim_sockets().await,
+        "generations": ctx.sandbox_cache().get_generations().await,
+        "label_interner": interner::labels().stats(),
+        "scrape_buffers": ctx.shim_client().buffer_stats(),
+        "allocator": {
+            "name": allocator::name(),
+            "stats": allocator::stats(),
+        },
+        "exporters": ctx
+            .exports()
+            .stats()
+            .into_iter()
+            .collect::<std::collections::BTreeMap<_, _>>(),
+        "quarantined": quarantined,
+        "stale_sandboxes": ctx.janitor().map(|janitor| janitor.stale()),
+        "scrape_status": scrape_status,
+    });
+
+    (
+        axum::http::StatusCode::OK,
+        [("Content-Type", "application/json; charset=utf-8")],
+        state.to_string(),
+    )
+        .into_response()
+}
+
+/// Version handler - version and build of the running binary as JSON
+async fn version_handler() -> impl IntoResponse {
+    let info = serde_json::to_string(&BuildInfo::current()).unwrap_or_default();
+    (
+        StatusCode::OK,
+        [("Content-Type", "application/json; charset=utf-8")],
+        info,
+    )
+        .into_response()
+}
+
+/// History handler - recent metric snapshots of a sandbox as JSON
+async fn history_handler(
+    ctx: Arc<AppContext>,
+    params: HistoryQuery,
+    scope: Scope,
+) -> impl IntoResponse {
+    let Some(sandbox_id) = params.sandbox else {
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            "missing sandbox parameter".to_string(),
+        )
+            .into_response();
+    };
+    info!(sandbox_id = %sandbox_id, window = ?params.window, "History request received");
+
+    let window = match params.window.as_deref().map(crate::config::parse_duration) {
+        None => None,
+        Some(Ok(window)) => Some(window),
+        Some(Err(e)) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                format!("invalid window: {}", e),
+            )
+                .into_response();
+        }
+    };
+
+    let visible = scope == Scope::All
+        || ctx
+            .sandbox_cache()
+            .get_metadata(&sandbox_id)
+            .await
+            .is_some_and(|metadata| scope.allows(&metadata.namespace));
+    let history = if visible {
+        ctx.metrics_cache().get_history(&sandbox_id, window).await
+    } else {
+        None
+    };
+    let Some(history) = history else {
+        return (
+            axum::http::StatusCode::NOT_FOUND,
+            format!("no history for sandbox {}", sandbox_id),
+        )
+            .into_response();
+    };
+
+    let with_deltas = params.deltas.unwrap_or(false);
+    let snapshots: Vec<_> = history
+        .iter()
+        .enumerate()
+        .map(|(idx, cached)| {
+            let mut snapshot = serde_json::json!({
+                "collected_at_unix_ms": cached.collected_at_unix().as_millis() as u64,
+                "age_secs": cached.age().as_secs_f64(),
+                "metrics": cached.metrics.metrics,
+            });
+            // Change of each series since the previous snapshot
+            if let Some(previous) = idx.checked_sub(1).filter(|_| with_deltas) {
+                snapshot["deltas"] =
+                    serde_json::json!(cached.metrics.diff(&history[previous].metrics).metrics);
+            }
+            snapshot
+        })
+        .collect();
+    let body = serde_json::json!({
+        "sandbox_id": sandbox_id,
+        "snapshots": snapshots,
+    });
+
+    (
+        axum::http::StatusCode::OK,
+        [("Content-Type", "application/json; charset=utf-8")],
+        body.to_string(),
+    )
+        .into_response()
+}
+
+/// Events handler - sandbox lifecycle events as JSON, oldest first
+///
+/// `since` is a Unix time in milliseconds, or a duration (e.g. `10m`) back
+/// from now; all kept events without it.
+async fn events_handler(
+    ctx: Arc<AppContext>,
+    params: EventsQuery,
+    scope: Scope,
+) -> impl IntoResponse {
+    info!(since = ?params.since, "Events request received");
+    let since_ms = match params.since.as_deref().map(parse_since) {
+        None => None,
+        Some(Ok(since_ms)) => Some(since_ms),
+        Some(Err(e)) => {
+            return (StatusCode::BAD_REQUEST, format!("invalid since: {}", e)).into_response();
+        }
+    };
+
+    let mut events = ctx.sandbox_cache().events().since(since_ms);
+    events.retain(|event| scope.allows(&event.namespace));
+    let body = serde_json::to_string(&events).unwrap_or_else(|e| {
+        warn!("Failed to serialize events: {}", e);
+        "[]".to_string()
+    });
+    (
+        StatusCode::OK,
+        [("Content-Type", "application/json; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
+
+/// Parse the `since` of the events endpoint to a Unix time in milliseconds
+fn parse_since(since: &str) -> anyhow::Result<u64> {
+    if let Ok(unix_ms) = since.trim().parse::<u64>() {
+        return Ok(unix_ms);
+    }
+    let ago = crate::config::parse_duration(since)?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(now.saturating_sub(ago).as_millis() as u64)
+}
+
+/// Log level handler - switches the log level of the running process
+///
+/// The body is the new level name; the request needs the admin bearer token.
+async fn loglevel_handler(
+    ctx: Arc<AppContext>,
+    headers: HeaderMap,
+    body: String,
+) -> impl IntoResponse {
+    if let Err(rejection) = authorize_admin(&ctx, &headers) {
+        return rejection.into_response();
+    }
+    let Some(log_level) = ctx.log_level() else {
+        return (
+            StatusCode::NOT_FOUND,
+            "log level cannot be changed".to_string(),
+        )
+            .into_response();
+    };
+
+    let previous = log_level.current();
+    let level = body.trim();
+    match log_level.set(level) {
+        Ok(()) => {
+            warn!(previous = %previous, log_level = %level, "Log level changed via admin endpoint");
+            (StatusCode::OK, format!("{}\n", level)).into_response()
+        }
+        Err(e) if crate::log_level::LOG_LEVELS.contains(&level) => {
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)).into_response()
+        }
+        Err(e) => (StatusCode::BAD_REQUEST, format!("{:#}", e)).into_response(),
+    }
+}
+
+/// Check the admin bearer token of a request, returning the rejection otherwise
+///
+/// Admin endpoints are disabled (404) without a configured token file. The
+/// file is re-read on every request, so a rotated token applies right away.
+fn authorize_admin(
+    ctx: &AppContext,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, &'static str)> {
+    let Some(path) = ctx.admin_token_file() else {
+        return Err((StatusCode::NOT_FOUND, "admin endpoints are disabled"));
+    };
+    let token = match std::fs::read_to_string(path.as_path()) {
+        Ok(token) => token.trim().to_string(),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Failed to read admin token file");
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, "admin token unavailable"));
+        }
+    };
+
+    match bearer_token(headers) {
+        Some(presented)
+            if !token.is_empty() && constant_time_eq(presented.as_bytes(), token.as_bytes()) =>
+        {
+            Ok(())
+        }
+        _ => {
+            warn!("Rejected admin request with a missing or invalid token");
+            Err((StatusCode::UNAUTHORIZED, "invalid or missing bearer token"))
+        }
+    }
+}
+
+/// Middleware authenticating the requests of the scoped endpoints
+///
+/// Rejected requests are answered here; others carry their `Scope` as an
+/// extension to the handler.
+async fn require_scope(
+    State(ctx): State<Arc<AppContext>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let reviewer = ctx.token_reviewer().map(Arc::as_ref);
+    let tenants = ctx.tenants().map(Arc::as_ref);
+    match authenticate(reviewer, tenants, request.headers()).await {
+        Ok(scope) => {
+            request.extensions_mut().insert(scope);
+            next.run(request).await
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// Authentication of the aggregator mode, which has no `AppContext`
+#[derive(Clone, Default)]
+pub struct Authentication {
+    /// Tenants file, if configured
+    pub tenants: Option<Arc<Tenants>>,
+    /// TokenReview authentication, if enabled
+    pub token_reviewer: Option<Arc<TokenReviewer>>,
+}
+
+/// Middleware authenticating the requests of the scoped endpoints of the
+/// aggregator mode, like `require_scope`
+async fn require_aggregator_scope(
+    State(auth): State<Authentication>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let reviewer = auth.token_reviewer.as_deref();
+    match authenticate(reviewer, auth.tenants.as_deref(), request.headers()).await {
+        Ok(scope) => {
+            request.extensions_mut().insert(scope);
+            next.run(request).await
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// Authenticate the bearer token of a request, returning the rejection otherwise
+///
+/// Resolves to the namespaces the request may see: those of the tenant
+/// holding the token, else those of the user the TokenReview authenticates
+/// it as. Every request sees all namespaces with neither configured.
+async fn authenticate(
+    reviewer: Option<&TokenReviewer>,
+    tenants: Option<&Tenants>,
+    headers: &HeaderMap,
+) -> Result<Scope, (StatusCode, &'static str)> {
+    let Some(reviewer) = reviewer else {
+        return tenant_scope(tenants, headers);
+    };
+    let Some(token) = bearer_token(headers) else {
+        return Err((StatusCode::UNAUTHORIZED, "missing bearer token"));
+    };
+    if let Some((tenant, scope)) = tenants.and_then(|tenants| tenants.lookup(token)) {
+        debug!(tenant = %tenant, "Request authenticated as tenant");
+        return Ok(scope);
+    }
+    match reviewer.review(token).await {
+        Ok(Review::Authenticated(user)) => reviewed_scope(tenants, &user),
+        Ok(Review::Denied) => {
+            warn!("Rejected request with an invalid bearer token");
+            Err((StatusCode::UNAUTHORIZED, "invalid bearer token"))
+        }
+        Err(e) => {
+            warn!(error = %format!("{:#}", e), "Failed to review bearer token");
+            Err((StatusCode::SERVICE_UNAVAILABLE, "token review unavailable"))
+        }
+    }
+}
+
+/// Resolve the user the TokenReview authenticated a request as to its scope
+///
+/// With a tenants file the user must be one of a tenant's `users`, so a
+/// valid token of any other ServiceAccount sees nothing; without one every
+/// authenticated user sees all namespaces.
+fn reviewed_scope(
+    tenants: Option<&Tenants>,
+    user: &str,
+) -> Result<Scope, (StatusCode, &'static str)> {
+    let Some(tenants) = tenants else {
+        debug!(user = %user, "Request authenticated");
+        return Ok(Scope::All);
+    };
+    match tenants.lookup_user(user) {
+        Some((tenant, scope)) => {
+            debug!(user = %user, tenant = %tenant, "Request authenticated as tenant user");
+            Ok(scope)
+        }
+        None => {
+            warn!(user = %user, "Rejected request of a user of no tenant");
+            Err((StatusCode::FORBIDDEN, "user of no tenant"))
+        }
+    }
+}
+
+/// The bearer token of the Authorization header of a request
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+}
+
+/// Resolve the bearer token of a request to the namespaces of its tenant
+///
+/// Every request sees all namespaces without a tenants file.
+fn tenant_scope(
+    tenants: Option<&Tenants>,
+    headers: &HeaderMap,
+) -> Result<Scope, (StatusCode, &'static str)> {
+    let Some(tenants) = tenants else {
+        return Ok(Scope::All);
+    };
+    let Some(token) = bearer_token(headers) else {
+        return Err((StatusCode::UNAUTHORIZED, "missing bearer token"));
+    };
+    match tenants.lookup(token) {
+        Some((tenant, scope)) => {
+            debug!(tenant = %tenant, "Request authenticated as tenant");
+            Ok(scope)
+        }
+        None => {
+            warn!("Rejected request with a token of no tenant");
+            Err((StatusCode::UNAUTHORIZED, "invalid bearer token"))
+        }
+    }
+}
+
+/// Rejection of a request for namespaces its token may not see
+fn namespace_forbidden() -> (StatusCode, &'static str) {
+    (
+        StatusCode::FORBIDDEN,
+        "namespace not allowed for this token",
+    )
+}
+
 /// Start the HTTP server
 pub async fn start_server(listen_address: &str, app_context: AppContext) -> anyhow::Result<()> {
+    let shutdown = app_context.cancellation_token().clone();
     let app_context = Arc::new(app_context);
     let router = create_router(app_context);
 
     let listener = tokio::net::TcpListener::bind(listen_address).await?;
     info!("Server listening on {}", listen_address);
 
-    axum::serve(listener, router).await?;
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
 
     Ok(())
 }
+
+/// Start the HTTP server of the aggregator mode
+pub async fn start_aggregator_server(
+    listen_address: &str,
+    aggregator: Arc<Aggregator>,
+    auth: Authentication,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let router = create_aggregator_router(aggregator, auth);
+
+    let listener = tokio::net::TcpListener::bind(listen_address).await?;
+    info!("Aggregator listening on {}", listen_address);
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown.cancelled_owned())
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reviewed_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tenants.toml");
+        std::fs::write(
+            &path,
+            r#"
+[[tenants]]
+name = "team-a"
+token = "token-a"
+users = ["system:serviceaccount:team-a:prometheus"]
+namespaces = ["team-a"]
+"#,
+        )
+        .unwrap();
+        let tenants = Tenants::load(&path).unwrap();
+
+        assert_eq!(
+            reviewed_scope(Some(&tenants), "system:serviceaccount:team-a:prometheus"),
+            Ok(Scope::Namespaces(["team-a".to_string()].into()))
+        );
+        // A valid token of another ServiceAccount, e.g. of a tenant's own pod
+        assert_eq!(
+            reviewed_scope(Some(&tenants), "system:serviceaccount:team-a:default"),
+            Err((StatusCode::FORBIDDEN, "user of no tenant"))
+        );
+        assert_eq!(
+            reviewed_scope(None, "system:serviceaccount:team-a:default"),
+            Ok(Scope::All)
+        );
+    }
+}
@@ -0,0 +1,382 @@
+//! Tenants - namespace-scoped access for tenant-owned scrapers
+//!
+//! Responsibilities:
+//! - Load the tenants file (TOML, or YAML for `.yaml`/`.yml`) mapping bearer
+//!   tokens and Kubernetes users to the namespaces their holder may see, and
+//!   reload it when it changes, so tokens are rotated without a restart
+//! - Resolve the bearer token of a request, or the user the TokenReview
+//!   authenticated it as, to its scope
+//! - Filter a metrics exposition and the sandbox listing down to a scope
+//!
+//! A tenant with the namespace `*` sees every namespace, e.g. for the
+//! cluster's own Prometheus.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::utils::prometheus_parser::sample_label;
+
+/// Namespace granting access to all namespaces
+const ALL_NAMESPACES: &str = "*";
+
+/// A tenant of the tenants file
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Tenant {
+    /// Name of the tenant, for logs
+    pub name: String,
+    /// Bearer token presented by the tenant's scraper (none if empty)
+    #[serde(default)]
+    pub token: String,
+    /// Kubernetes users (e.g. `system:serviceaccount:<namespace>:<name>`)
+    /// whose tokens passing the TokenReview get the tenant's scope
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// Namespaces whose series and sandboxes the tenant sees
+    pub namespaces: Vec<String>,
+}
+
+impl Tenant {
+    /// The namespaces the tenant sees
+    fn scope(&self) -> Scope {
+        if self.namespaces.iter().any(|ns| ns == ALL_NAMESPACES) {
+            Scope::All
+        } else {
+            Scope::Namespaces(self.namespaces.iter().cloned().collect())
+        }
+    }
+}
+
+/// Contents of the tenants file
+#[derive(Debug, Default, Deserialize)]
+struct TenantsFile {
+    #[serde(default)]
+    tenants: Vec<Tenant>,
+}
+
+impl TenantsFile {
+    /// Parse and check the tenants file at `path`
+    fn load(path: &Path) -> Result<Vec<Tenant>> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("failed to read tenants file {}", path.display()))?;
+        let yaml = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("yaml" | "yml")
+        );
+        let file: TenantsFile = if yaml {
+            serde_yaml::from_str(&text).map_err(anyhow::Error::from)
+        } else {
+            toml::from_str(&text).map_err(anyhow::Error::from)
+        }
+        .with_context(|| format!("failed to parse tenants file {}", path.display()))?;
+
+        let mut tokens = BTreeSet::new();
+        let mut users = BTreeSet::new();
+        for tenant in &file.tenants {
+            if tenant.token.trim().is_empty() && tenant.users.is_empty() {
+                anyhow::bail!("tenant {} has neither a token nor users", tenant.name);
+            }
+            if tenant.namespaces.is_empty() {
+                anyhow::bail!("tenant {} has no namespaces", tenant.name);
+            }
+            if !tenant.token.trim().is_empty() && !tokens.insert(tenant.token.trim()) {
+                anyhow::bail!(
+                    "tenant {} shares its token with another tenant",
+                    tenant.name
+                );
+            }
+            if let Some(user) = tenant
+                .users
+                .iter()
+                .find(|user| !users.insert(user.as_str()))
+            {
+                anyhow::bail!(
+                    "tenant {} shares the user {} with another tenant",
+                    tenant.name,
+                    user
+                );
+            }
+        }
+        Ok(file.tenants)
+    }
+}
+
+/// Namespaces the caller of a request may see
+#[derive(Clone, Debug, PartialEq)]
+pub enum Scope {
+    /// Every namespace, and the node-level series
+    All,
+    /// Only series and sandboxes of these namespaces
+    Namespaces(BTreeSet<String>),
+}
+
+impl Scope {
+    /// Whether series and sandboxes of `namespace` are visible
+    pub fn allows(&self, namespace: &str) -> bool {
+        match self {
+            Scope::All => true,
+            Scope::Namespaces(namespaces) => namespaces.contains(namespace),
+        }
+    }
+
+    /// Narrow the scope to the requested namespace, None if it is not visible
+    pub fn narrow(self, namespace: Option<&str>) -> Option<Scope> {
+        match namespace {
+            None => Some(self),
+            Some(namespace) if self.allows(namespace) => Some(Scope::Namespaces(
+                [namespace.to_string()].into_iter().collect(),
+            )),
+            Some(_) => None,
+        }
+    }
+
+    /// Keep the series of a metrics exposition visible in this scope
+    ///
+    /// Only series with a visible `namespace` label are kept, so node-level
+    /// series (which count other tenants' sandboxes) are dropped. `# HELP`
+    /// and `# TYPE` lines are kept for the families with a kept series.
+    pub fn filter_metrics(&self, output: String) -> String {
+        if *self == Scope::All {
+            return output;
+        }
+        let mut filtered = String::with_capacity(output.len() / 4);
+        // Comments of the current family, until one of its series is kept
+        let mut comments = String::new();
+        let mut after_sample = false;
+        for line in output.lines() {
+            if line.starts_with('#') {
+                if after_sample {
+                    comments.clear();
+                    after_sample = false;
+                }
+                comments.push_str(line);
+                comments.push('\n');
+                continue;
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            after_sample = true;
+            let visible = sample_label(line, "namespace")
+                .is_some_and(|namespace| !namespace.is_empty() && self.allows(&namespace));
+            if visible {
+                filtered.push_str(&comments);
+                comments.clear();
+                filtered.push_str(line);
+                filtered.push('\n');
+            }
+        }
+        filtered
+    }
+
+    /// Keep the sandboxes of a `/sandboxes` listing visible in this scope
+    ///
+    /// The listing is the JSON array of `[id, metadata]` pairs; sandboxes
+    /// without a visible `namespace` are dropped.
+    pub fn filter_sandboxes(&self, listing: String) -> String {
+        if *self == Scope::All {
+            return listing;
+        }
+        let Ok(mut sandboxes) = serde_json::from_str::<Vec<(String, Value)>>(&listing) else {
+            return "[]".to_string();
+        };
+        sandboxes.retain(|(_, metadata)| {
+            metadata["namespace"]
+                .as_str()
+                .is_some_and(|namespace| self.allows(namespace))
+        });
+        serde_json::to_string(&sandboxes).unwrap_or_else(|_| "[]".to_string())
+    }
+}
+
+/// Tenants file, reloaded when its modification time changes
+pub struct Tenants {
+    path: PathBuf,
+    loaded: Mutex<(Option<SystemTime>, Arc<Vec<Tenant>>)>,
+}
+
+impl Tenants {
+    /// Load the tenants file at `path`
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let modified = modified(&path);
+        let tenants = TenantsFile::load(&path)?;
+        Ok(Tenants {
+            path,
+            loaded: Mutex::new((modified, Arc::new(tenants))),
+        })
+    }
+
+    /// Path of the tenants file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The current tenants, reloading the file if it changed
+    ///
+    /// A file that fails to load keeps the previous tenants (with a warning).
+    pub fn current(&self) -> Arc<Vec<Tenant>> {
+        let mut loaded = self.loaded.lock().unwrap_or_else(|e| e.into_inner());
+        let modified = modified(&self.path);
+        if modified != loaded.0 {
+            match TenantsFile::load(&self.path) {
+                Ok(tenants) => {
+                    tracing::info!(path = %self.path.display(), tenants = tenants.len(), "Reloaded tenants file");
+                    *loaded = (modified, Arc::new(tenants));
+                }
+                Err(e) => {
+                    tracing::warn!(error = %format!("{:#}", e), "Failed to reload tenants file, keeping the previous tenants");
+                    loaded.0 = modified;
+                }
+            }
+        }
+        loaded.1.clone()
+    }
+
+    /// The tenant holding `token` and its scope, if any
+    pub fn lookup(&self, token: &str) -> Option<(String, Scope)> {
+        let tenants = self.current();
+        // Compare with every tenant, so the time does not tell which one matched
+        let mut found = None;
+        for tenant in tenants.iter() {
+            let tenant_token = tenant.token.trim();
+            if constant_time_eq(tenant_token.as_bytes(), token.as_bytes())
+                && !tenant_token.is_empty()
+            {
+                found = Some(tenant);
+            }
+        }
+        let tenant = found?;
+        Some((tenant.name.clone(), tenant.scope()))
+    }
+
+    /// The tenant listing the Kubernetes `user` and its scope, if any
+    pub fn lookup_user(&self, user: &str) -> Option<(String, Scope)> {
+        self.current()
+            .iter()
+            .find(|tenant| tenant.users.iter().any(|listed| listed == user))
+            .map(|tenant| (tenant.name.clone(), tenant.scope()))
+    }
+}
+
+/// Modification time of a file, None if it cannot be read
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Compare two byte strings in time independent of where they differ
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TENANTS: &str = r#"
+[[tenants]]
+name = "team-a"
+token = "token-a"
+namespaces = ["team-a", "team-a-staging"]
+
+[[tenants]]
+name = "platform"
+token = "token-platform"
+namespaces = ["*"]
+
+[[tenants]]
+name = "team-b"
+users = ["system:serviceaccount:team-b:prometheus"]
+namespaces = ["team-b"]
+"#;
+
+    #[test]
+    fn test_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tenants.toml");
+        fs::write(&path, TENANTS).unwrap();
+        let tenants = Tenants::load(&path).unwrap();
+
+        let (name, scope) = tenants.lookup("token-a").unwrap();
+        assert_eq!(name, "team-a");
+        assert!(scope.allows("team-a-staging"));
+        assert!(!scope.allows("team-b"));
+        assert_eq!(tenants.lookup("token-platform").unwrap().1, Scope::All);
+        assert!(tenants.lookup("token-b").is_none());
+        assert!(tenants.lookup("").is_none());
+
+        let (user_tenant, user_scope) = tenants
+            .lookup_user("system:serviceaccount:team-b:prometheus")
+            .unwrap();
+        assert_eq!(user_tenant, "team-b");
+        assert_eq!(user_scope, Scope::Namespaces(["team-b".to_string()].into()));
+        assert!(tenants
+            .lookup_user("system:serviceaccount:team-a:default")
+            .is_none());
+
+        assert!(scope.clone().narrow(Some("team-b")).is_none());
+        assert_eq!(
+            scope.narrow(Some("team-a")),
+            Some(Scope::Namespaces(["team-a".to_string()].into()))
+        );
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_tenants() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tenants.yaml");
+        for invalid in [
+            "tenants:\n  - name: a\n    token: ''\n    namespaces: [a]\n",
+            "tenants:\n  - name: a\n    namespaces: [a]\n",
+            "tenants:\n  - name: a\n    users: [u]\n    namespaces: [a]\n  - name: b\n    users: [u]\n    namespaces: [b]\n",
+            "tenants:\n  - name: a\n    token: x\n    namespaces: []\n",
+            "tenants:\n  - name: a\n    token: x\n    namespaces: [a]\n  - name: b\n    token: x\n    namespaces: [b]\n",
+        ] {
+            fs::write(&path, invalid).unwrap();
+            assert!(Tenants::load(&path).is_err(), "{}", invalid);
+        }
+        assert!(Tenants::load(dir.path().join("missing.toml")).is_err());
+    }
+
+    #[test]
+    fn test_filter_metrics() {
+        let output = "\
+# HELP container_memory_usage_bytes Memory usage
+# TYPE container_memory_usage_bytes gauge
+container_memory_usage_bytes{pod=\"b\",namespace=\"team-b\"} 2
+container_memory_usage_bytes{pod=\"a\",namespace=\"team-a\"} 1
+# HELP kata_pulse_sandbox_up Whether the last scrape succeeded
+# TYPE kata_pulse_sandbox_up gauge
+kata_pulse_sandbox_up{sandbox_id=\"b\",pod=\"b\",namespace=\"team-b\"} 1
+# HELP kata_pulse_sandboxes_total Tracked sandboxes
+# TYPE kata_pulse_sandboxes_total gauge
+kata_pulse_sandboxes_total 2
+";
+        let scope = Scope::Namespaces(["team-a".to_string()].into());
+        assert_eq!(
+            scope.filter_metrics(output.to_string()),
+            "\
+# HELP container_memory_usage_bytes Memory usage
+# TYPE container_memory_usage_bytes gauge
+container_memory_usage_bytes{pod=\"a\",namespace=\"team-a\"} 1
+"
+        );
+        assert_eq!(Scope::All.filter_metrics(output.to_string()), output);
+    }
+
+    #[test]
+    fn test_filter_sandboxes() {
+        let listing = r#"[["sb-a",{"name":"a","namespace":"team-a"}],["sb-b",{"name":"b","namespace":"team-b"}],["sb-c",{"name":"c"}]]"#;
+        let scope = Scope::Namespaces(["team-a".to_string()].into());
+        assert_eq!(
+            scope.filter_sandboxes(listing.to_string()),
+            r#"[["sb-a",{"name":"a","namespace":"team-a"}]]"#
+        );
+        assert_eq!(Scope::All.filter_sandboxes(listing.to_string()), listing);
+    }
+}
@@ -0,0 +1,530 @@
+//! Agent client - container stats straight from the kata-agent in the guest
+//!
+//! Responsibilities:
+//! - Parse the agent URL reported by the shim (`/agent-url`): `vsock://<cid>:<port>`
+//!   or the hybrid vsock `hvsock://<unix socket>:<port>` of Cloud Hypervisor
+//!   and Firecracker
+//! - Connect to the agent: an AF_VSOCK socket, or the hybrid vsock Unix
+//!   socket with its `CONNECT <port>` handshake
+//! - Speak enough ttrpc (length-prefixed protobuf frames) to call
+//!   `grpc.AgentService/StatsContainer`, which reports the cgroup stats of a
+//!   container inside the guest
+//!
+//! The exchange uses blocking sockets with read/write timeouts; callers run
+//! it on the blocking thread pool.
+
+use anyhow::{Context, Result};
+use prost::Message;
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Service of the kata-agent API
+const AGENT_SERVICE: &str = "grpc.AgentService";
+
+/// Largest ttrpc message accepted, like the ttrpc default
+const MAX_MESSAGE_LEN: usize = 4 << 20;
+
+/// ttrpc message types
+const MESSAGE_TYPE_REQUEST: u8 = 0x1;
+const MESSAGE_TYPE_RESPONSE: u8 = 0x2;
+
+/// Where the kata-agent of a sandbox listens
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AgentUrl {
+    /// vsock of the guest (QEMU)
+    Vsock { cid: u32, port: u32 },
+    /// Unix socket of the VMM forwarding to a guest vsock port (Cloud Hypervisor, Firecracker)
+    HybridVsock { path: PathBuf, port: u32 },
+}
+
+impl AgentUrl {
+    /// Parse the agent URL reported by the shim
+    pub fn parse(url: &str) -> Result<Self> {
+        let url = url.trim();
+        let invalid = || anyhow::anyhow!("invalid agent URL {:?}", url);
+        if let Some(rest) = url.strip_prefix("vsock://") {
+            let (cid, port) = rest.split_once(':').ok_or_else(invalid)?;
+            return Ok(AgentUrl::Vsock {
+                cid: cid.parse().map_err(|_| invalid())?,
+                port: port.parse().map_err(|_| invalid())?,
+            });
+        }
+        if let Some(rest) = url.strip_prefix("hvsock://") {
+            let (path, port) = rest.rsplit_once(':').ok_or_else(invalid)?;
+            if path.is_empty() {
+                return Err(invalid());
+            }
+            return Ok(AgentUrl::HybridVsock {
+                path: PathBuf::from(path),
+                port: port.parse().map_err(|_| invalid())?,
+            });
+        }
+        anyhow::bail!(
+            "unsupported agent URL {:?} (expected vsock:// or hvsock://)",
+            url
+        )
+    }
+}
+
+/// Request of a ttrpc call
+#[derive(Clone, PartialEq, Message)]
+struct TtrpcRequest {
+    #[prost(string, tag = "1")]
+    service: String,
+    #[prost(string, tag = "2")]
+    method: String,
+    #[prost(bytes = "vec", tag = "3")]
+    payload: Vec<u8>,
+    #[prost(int64, tag = "4")]
+    timeout_nano: i64,
+}
+
+/// Response of a ttrpc call
+#[derive(Clone, PartialEq, Message)]
+struct TtrpcResponse {
+    #[prost(message, optional, tag = "1")]
+    status: Option<RpcStatus>,
+    #[prost(bytes = "vec", tag = "2")]
+    payload: Vec<u8>,
+}
+
+/// Status of a failed ttrpc call (`google.rpc.Status`)
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StatsContainerRequest {
+    #[prost(string, tag = "1")]
+    container_id: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StatsContainerResponse {
+    #[prost(message, optional, tag = "1")]
+    cgroup_stats: Option<CgroupStats>,
+}
+
+/// Cgroup stats of a container, as reported by the agent
+#[derive(Clone, PartialEq, Message)]
+pub struct CgroupStats {
+    #[prost(message, optional, tag = "1")]
+    pub cpu_stats: Option<CpuStats>,
+    #[prost(message, optional, tag = "2")]
+    pub memory_stats: Option<MemoryStats>,
+    #[prost(message, optional, tag = "3")]
+    pub pids_stats: Option<PidsStats>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct CpuStats {
+    #[prost(message, optional, tag = "1")]
+    pub cpu_usage: Option<CpuUsage>,
+    #[prost(message, optional, tag = "2")]
+    pub throttling_data: Option<ThrottlingData>,
+}
+
+/// CPU time in nanoseconds
+#[derive(Clone, PartialEq, Message)]
+pub struct CpuUsage {
+    #[prost(uint64, tag = "1")]
+    pub total_usage: u64,
+    #[prost(uint64, tag = "3")]
+    pub usage_in_kernelmode: u64,
+    #[prost(uint64, tag = "4")]
+    pub usage_in_usermode: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ThrottlingData {
+    #[prost(uint64, tag = "1")]
+    pub periods: u64,
+    #[prost(uint64, tag = "2")]
+    pub throttled_periods: u64,
+    /// Throttled time in nanoseconds
+    #[prost(uint64, tag = "3")]
+    pub throttled_time: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MemoryStats {
+    #[prost(uint64, tag = "1")]
+    pub cache: u64,
+    #[prost(message, optional, tag = "2")]
+    pub usage: Option<MemoryData>,
+    /// Raw `memory.stat` entries (cgroup v1 or v2 names)
+    #[prost(map = "string, uint64", tag = "6")]
+    pub stats: HashMap<String, u64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MemoryData {
+    #[prost(uint64, tag = "1")]
+    pub usage: u64,
+    #[prost(uint64, tag = "2")]
+    pub max_usage: u64,
+    #[prost(uint64, tag = "3")]
+    pub failcnt: u64,
+    #[prost(uint64, tag = "4")]
+    pub limit: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PidsStats {
+    #[prost(uint64, tag = "1")]
+    pub current: u64,
+    #[prost(uint64, tag = "2")]
+    pub limit: u64,
+}
+
+/// A connection to the kata-agent of one sandbox
+pub struct AgentConnection {
+    stream: Box<dyn Stream>,
+    timeout: Duration,
+    next_stream_id: u32,
+}
+
+/// A blocking byte stream to the agent
+trait Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Stream for T {}
+
+impl AgentConnection {
+    /// Connect to the agent at `url`, with `timeout` for every read and write
+    pub fn connect(url: &AgentUrl, timeout: Duration) -> Result<Self> {
+        let stream: Box<dyn Stream> = match url {
+            AgentUrl::Vsock { cid, port } => Box::new(
+                VsockStream::connect(*cid, *port, timeout)
+                    .with_context(|| format!("failed to connect to vsock {}:{}", cid, port))?,
+            ),
+            AgentUrl::HybridVsock { path, port } => {
+                Box::new(connect_hybrid_vsock(path, *port, timeout).with_context(|| {
+                    format!(
+                        "failed to connect to hybrid vsock {}:{}",
+                        path.display(),
+                        port
+                    )
+                })?)
+            }
+        };
+        Ok(AgentConnection {
+            stream,
+            timeout,
+            next_stream_id: 1,
+        })
+    }
+
+    /// Cgroup stats of a container inside the guest
+    pub fn stats_container(&mut self, container_id: &str) -> Result<CgroupStats> {
+        let request = StatsContainerRequest {
+            container_id: container_id.to_string(),
+        };
+        let response: StatsContainerResponse = self.call("StatsContainer", &request)?;
+        Ok(response.cgroup_stats.unwrap_or_default())
+    }
+
+    /// Call a method of the agent service
+    fn call<Req: Message, Resp: Message + Default>(
+        &mut self,
+        method: &str,
+        request: &Req,
+    ) -> Result<Resp> {
+        let stream_id = self.next_stream_id;
+        // Client streams are odd
+        self.next_stream_id = self.next_stream_id.wrapping_add(2);
+        let request = TtrpcRequest {
+            service: AGENT_SERVICE.to_string(),
+            method: method.to_string(),
+            payload: request.encode_to_vec(),
+            timeout_nano: self.timeout.as_nanos().min(i64::MAX as u128) as i64,
+        };
+        write_frame(
+            &mut self.stream,
+            stream_id,
+            MESSAGE_TYPE_REQUEST,
+            &request.encode_to_vec(),
+        )?;
+
+        loop {
+            let (id, message_type, payload) = read_frame(&mut self.stream)?;
+            if id != stream_id || message_type != MESSAGE_TYPE_RESPONSE {
+                continue;
+            }
+            let response =
+                TtrpcResponse::decode(payload.as_slice()).context("invalid ttrpc response")?;
+            if let Some(status) = response.status.filter(|status| status.code != 0) {
+                anyhow::bail!(
+                    "{} failed with code {}: {}",
+                    method,
+                    status.code,
+                    status.message
+                );
+            }
+            return Resp::decode(response.payload.as_slice())
+                .with_context(|| format!("invalid {} response", method));
+        }
+    }
+}
+
+/// Write a ttrpc frame: length, stream ID, type and flags, then the message
+fn write_frame(
+    stream: &mut impl Write,
+    stream_id: u32,
+    message_type: u8,
+    message: &[u8],
+) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(10 + message.len());
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(&stream_id.to_be_bytes());
+    frame.push(message_type);
+    frame.push(0);
+    frame.extend_from_slice(message);
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+/// Read a ttrpc frame, returning its stream ID, type and message
+fn read_frame(stream: &mut impl Read) -> Result<(u32, u8, Vec<u8>)> {
+    let mut header = [0u8; 10];
+    stream
+        .read_exact(&mut header)
+        .context("failed to read ttrpc frame header")?;
+    let len = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let stream_id = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+    if len > MAX_MESSAGE_LEN {
+        anyhow::bail!(
+            "ttrpc message of {} bytes exceeds the {} byte limit",
+            len,
+            MAX_MESSAGE_LEN
+        );
+    }
+    let mut message = vec![0u8; len];
+    stream
+        .read_exact(&mut message)
+        .context("failed to read ttrpc message")?;
+    Ok((stream_id, header[8], message))
+}
+
+/// Connect to a hybrid vsock: the VMM's Unix socket, then `CONNECT <port>`
+fn connect_hybrid_vsock(
+    path: &std::path::Path,
+    port: u32,
+    timeout: Duration,
+) -> Result<UnixStream> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+    stream.write_all(format!("CONNECT {}\n", port).as_bytes())?;
+
+    // Read the reply byte by byte, so no agent data is consumed
+    let mut reply = Vec::new();
+    let mut byte = [0u8; 1];
+    while byte[0] != b'\n' {
+        stream.read_exact(&mut byte)?;
+        reply.push(byte[0]);
+        if reply.len() > 64 {
+            anyhow::bail!("no hybrid vsock handshake reply");
+        }
+    }
+    if !reply.starts_with(b"OK ") {
+        anyhow::bail!(
+            "hybrid vsock handshake failed: {:?}",
+            String::from_utf8_lossy(&reply).trim()
+        );
+    }
+    Ok(stream)
+}
+
+/// A connected AF_VSOCK stream socket
+struct VsockStream(OwnedFd);
+
+impl VsockStream {
+    /// Connect to `port` of the guest with context ID `cid`
+    ///
+    /// The send timeout also bounds the connect.
+    fn connect(cid: u32, port: u32, timeout: Duration) -> io::Result<Self> {
+        // SAFETY: plain socket(2) call; the descriptor is owned right away
+        let fd = unsafe { libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `fd` is a fresh descriptor owned by nobody else
+        let socket = VsockStream(unsafe { OwnedFd::from_raw_fd(fd) });
+        socket.set_timeout(libc::SO_SNDTIMEO, timeout)?;
+        socket.set_timeout(libc::SO_RCVTIMEO, timeout)?;
+
+        // SAFETY: sockaddr_vm is plain old data, valid when zeroed
+        let mut addr: libc::sockaddr_vm = unsafe { std::mem::zeroed() };
+        addr.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+        addr.svm_cid = cid;
+        addr.svm_port = port;
+        // SAFETY: `addr` is a valid sockaddr_vm of the given length
+        let result = unsafe {
+            libc::connect(
+                socket.0.as_raw_fd(),
+                &addr as *const libc::sockaddr_vm as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_vm>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(socket)
+    }
+
+    fn set_timeout(&self, option: libc::c_int, timeout: Duration) -> io::Result<()> {
+        let timeval = libc::timeval {
+            tv_sec: timeout.as_secs() as libc::time_t,
+            tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+        };
+        // SAFETY: `timeval` is valid for the duration of the call
+        let result = unsafe {
+            libc::setsockopt(
+                self.0.as_raw_fd(),
+                libc::SOL_SOCKET,
+                option,
+                &timeval as *const libc::timeval as *const libc::c_void,
+                std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Read for VsockStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // SAFETY: `buf` is valid for writes of its length
+        let n = unsafe { libc::read(self.0.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+}
+
+impl Write for VsockStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // SAFETY: `buf` is valid for reads of its length
+        let n = unsafe { libc::write(self.0.as_raw_fd(), buf.as_ptr().cast(), buf.len()) };
+        if n < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(n as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixListener;
+
+    #[test]
+    fn test_parse_agent_url() {
+        assert_eq!(
+            AgentUrl::parse("vsock://3:1024").unwrap(),
+            AgentUrl::Vsock { cid: 3, port: 1024 }
+        );
+        assert_eq!(
+            AgentUrl::parse("hvsock:///run/vc/vm/abc/clh.sock:1024\n").unwrap(),
+            AgentUrl::HybridVsock {
+                path: PathBuf::from("/run/vc/vm/abc/clh.sock"),
+                port: 1024
+            }
+        );
+        assert!(AgentUrl::parse("vsock://3").is_err());
+        assert!(AgentUrl::parse("hvsock://:1024").is_err());
+        assert!(AgentUrl::parse("mock://").is_err());
+    }
+
+    /// Serve one StatsContainer call over a hybrid vsock socket
+    fn fake_agent(listener: UnixListener) -> std::thread::JoinHandle<String> {
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut handshake = String::new();
+            reader.read_line(&mut handshake).unwrap();
+            (&stream).write_all(b"OK 1073741824\n").unwrap();
+
+            let (stream_id, message_type, message) = read_frame(&mut reader).unwrap();
+            assert_eq!(message_type, MESSAGE_TYPE_REQUEST);
+            let request = TtrpcRequest::decode(message.as_slice()).unwrap();
+            assert_eq!(request.service, AGENT_SERVICE);
+            assert_eq!(request.method, "StatsContainer");
+            let container_id = StatsContainerRequest::decode(request.payload.as_slice())
+                .unwrap()
+                .container_id;
+
+            let stats = StatsContainerResponse {
+                cgroup_stats: Some(CgroupStats {
+                    cpu_stats: Some(CpuStats {
+                        cpu_usage: Some(CpuUsage {
+                            total_usage: 2_500_000_000,
+                            ..Default::default()
+                        }),
+                        throttling_data: None,
+                    }),
+                    ..Default::default()
+                }),
+            };
+            let response = TtrpcResponse {
+                status: None,
+                payload: stats.encode_to_vec(),
+            };
+            write_frame(
+                &mut &stream,
+                stream_id,
+                MESSAGE_TYPE_RESPONSE,
+                &response.encode_to_vec(),
+            )
+            .unwrap();
+            format!("{}{}", handshake, container_id)
+        })
+    }
+
+    #[test]
+    fn test_stats_container_over_hybrid_vsock() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clh.sock");
+        let agent = fake_agent(UnixListener::bind(&path).unwrap());
+
+        let url = AgentUrl::HybridVsock { path, port: 1024 };
+        let mut connection = AgentConnection::connect(&url, Duration::from_secs(5)).unwrap();
+        let stats = connection.stats_container("container-1").unwrap();
+        assert_eq!(
+            stats.cpu_stats.unwrap().cpu_usage.unwrap().total_usage,
+            2_500_000_000
+        );
+        assert_eq!(agent.join().unwrap(), "CONNECT 1024\ncontainer-1");
+    }
+
+    #[test]
+    fn test_failed_handshake() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clh.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let vmm = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"ERR no such port\n").unwrap();
+        });
+
+        let url = AgentUrl::HybridVsock { path, port: 1024 };
+        assert!(AgentConnection::connect(&url, Duration::from_secs(5)).is_err());
+        vmm.join().unwrap();
+    }
+}
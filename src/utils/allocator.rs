@@ -0,0 +1,147 @@
+//! Allocator statistics - heap usage as seen by the memory allocator
+//!
+//! Responsibilities:
+//! - Read the jemalloc counters when kata-pulse is built with the `jemalloc`
+//!   feature (the binary then installs jemalloc as its global allocator)
+//! - Render them as `kata_pulse_allocator_*` metrics, so RSS creep on
+//!   long-running agents can be told apart from live heap growth
+//!
+//! Without the feature the system allocator is used and no statistics are
+//! available.
+
+use serde::Serialize;
+
+/// Heap statistics of the allocator, in bytes
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct AllocatorStats {
+    /// Bytes allocated by the application
+    pub allocated: u64,
+    /// Bytes in pages holding allocations (allocated plus their fragmentation)
+    pub active: u64,
+    /// Bytes of allocator metadata
+    pub metadata: u64,
+    /// Bytes in physically resident pages mapped by the allocator
+    pub resident: u64,
+    /// Bytes in chunks mapped by the allocator
+    pub mapped: u64,
+    /// Bytes of virtual memory retained for reuse instead of being unmapped
+    pub retained: u64,
+}
+
+impl AllocatorStats {
+    /// Share of resident memory not holding live allocations
+    ///
+    /// Grows when freed memory stays resident, which shows up as RSS creep
+    /// without a matching growth of `allocated`.
+    pub fn fragmentation(&self) -> f64 {
+        if self.resident == 0 {
+            return 0.0;
+        }
+        1.0 - (self.allocated as f64 / self.resident as f64).min(1.0)
+    }
+
+    /// Render the stats in Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        let mut output = String::new();
+        for (name, help, value) in [
+            (
+                "kata_pulse_allocator_allocated_bytes",
+                "Bytes allocated by kata-pulse",
+                self.allocated,
+            ),
+            (
+                "kata_pulse_allocator_active_bytes",
+                "Bytes in allocator pages holding allocations",
+                self.active,
+            ),
+            (
+                "kata_pulse_allocator_metadata_bytes",
+                "Bytes of allocator metadata",
+                self.metadata,
+            ),
+            (
+                "kata_pulse_allocator_resident_bytes",
+                "Bytes in physically resident pages mapped by the allocator",
+                self.resident,
+            ),
+            (
+                "kata_pulse_allocator_mapped_bytes",
+                "Bytes in chunks mapped by the allocator",
+                self.mapped,
+            ),
+            (
+                "kata_pulse_allocator_retained_bytes",
+                "Bytes of virtual memory the allocator retained instead of unmapping",
+                self.retained,
+            ),
+        ] {
+            output.push_str(&format!("# HELP {} {}\n", name, help));
+            output.push_str(&format!("# TYPE {} gauge\n", name));
+            output.push_str(&format!("{} {}\n", name, value));
+        }
+        output.push_str("# HELP kata_pulse_allocator_fragmentation_ratio Share of allocator resident memory not holding live allocations\n");
+        output.push_str("# TYPE kata_pulse_allocator_fragmentation_ratio gauge\n");
+        output.push_str(&format!(
+            "kata_pulse_allocator_fragmentation_ratio {}\n",
+            self.fragmentation()
+        ));
+        output
+    }
+}
+
+/// Name of the global allocator kata-pulse was built with
+pub fn name() -> &'static str {
+    if cfg!(feature = "jemalloc") {
+        "jemalloc"
+    } else {
+        "system"
+    }
+}
+
+/// Current allocator statistics, if the allocator provides them
+#[cfg(feature = "jemalloc")]
+pub fn stats() -> Option<AllocatorStats> {
+    use tikv_jemalloc_ctl::{epoch, stats};
+
+    // jemalloc caches its statistics until the epoch is advanced
+    epoch::advance().ok()?;
+    Some(AllocatorStats {
+        allocated: stats::allocated::read().ok()? as u64,
+        active: stats::active::read().ok()? as u64,
+        metadata: stats::metadata::read().ok()? as u64,
+        resident: stats::resident::read().ok()? as u64,
+        mapped: stats::mapped::read().ok()? as u64,
+        retained: stats::retained::read().ok()? as u64,
+    })
+}
+
+/// Current allocator statistics, if the allocator provides them
+#[cfg(not(feature = "jemalloc"))]
+pub fn stats() -> Option<AllocatorStats> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_allocator_stats() {
+        let stats = AllocatorStats {
+            allocated: 30,
+            active: 35,
+            metadata: 5,
+            resident: 40,
+            mapped: 50,
+            retained: 10,
+        };
+        assert_eq!(stats.fragmentation(), 0.25);
+        let output = stats.render_metrics();
+        assert!(output.contains("kata_pulse_allocator_allocated_bytes 30\n"));
+        assert!(output.contains("kata_pulse_allocator_resident_bytes 40\n"));
+        assert!(output.contains("kata_pulse_allocator_fragmentation_ratio 0.25\n"));
+        assert_eq!(AllocatorStats::default().fragmentation(), 0.0);
+
+        assert_eq!(super::stats().is_some(), cfg!(feature = "jemalloc"));
+    }
+}
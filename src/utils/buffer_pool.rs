@@ -0,0 +1,183 @@
+//! Buffer pool - reuses scrape payload buffers across sandboxes and cycles
+//!
+//! Responsibilities:
+//! - Hand out byte buffers for shim responses, reusing the capacity of
+//!   buffers returned by earlier scrapes instead of allocating per scrape
+//! - Bound the memory kept idle, and drop oversized buffers instead of
+//!   pooling them
+//! - Report its size and reuse rate so the win can be verified
+
+use serde::Serialize;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Default bound of the idle buffer capacity (32 MiB)
+pub const DEFAULT_MAX_IDLE_BYTES: usize = 32 * 1024 * 1024;
+
+/// Default largest buffer that is returned to the pool (4 MiB)
+pub const DEFAULT_MAX_BUFFER_BYTES: usize = 4 * 1024 * 1024;
+
+/// Size and effectiveness of a buffer pool
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BufferPoolStats {
+    /// Idle buffers held
+    pub idle: usize,
+    /// Capacity of the idle buffers in bytes
+    pub idle_bytes: usize,
+    /// Buffers handed out from the pool
+    pub reused: u64,
+    /// Buffers handed out freshly allocated
+    pub allocated: u64,
+}
+
+/// Thread-safe pool of byte buffers
+#[derive(Debug)]
+pub struct BufferPool {
+    idle: Mutex<Vec<Vec<u8>>>,
+    max_idle_bytes: usize,
+    max_buffer_bytes: usize,
+    reused: AtomicU64,
+    allocated: AtomicU64,
+}
+
+impl BufferPool {
+    /// Create a pool keeping at most `max_idle_bytes` of idle buffers, each
+    /// at most `max_buffer_bytes` large
+    pub fn new(max_idle_bytes: usize, max_buffer_bytes: usize) -> Self {
+        BufferPool {
+            idle: Mutex::new(Vec::new()),
+            max_idle_bytes,
+            max_buffer_bytes,
+            reused: AtomicU64::new(0),
+            allocated: AtomicU64::new(0),
+        }
+    }
+
+    /// Take an empty buffer; it goes back to the pool when dropped
+    ///
+    /// Hands out the largest idle buffer, so big payloads keep landing in
+    /// buffers that already fit them.
+    pub fn take(self: &Arc<Self>) -> PooledBuffer {
+        let buffer = {
+            let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+            idle.pop()
+        };
+        let buffer = match buffer {
+            Some(buffer) => {
+                self.reused.fetch_add(1, Ordering::Relaxed);
+                buffer
+            }
+            None => {
+                self.allocated.fetch_add(1, Ordering::Relaxed);
+                Vec::new()
+            }
+        };
+        PooledBuffer {
+            buffer,
+            pool: self.clone(),
+        }
+    }
+
+    /// Current size and reuse rate
+    pub fn stats(&self) -> BufferPoolStats {
+        let idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        BufferPoolStats {
+            idle: idle.len(),
+            idle_bytes: idle.iter().map(Vec::capacity).sum(),
+            reused: self.reused.load(Ordering::Relaxed),
+            allocated: self.allocated.load(Ordering::Relaxed),
+        }
+    }
+
+    fn put(&self, mut buffer: Vec<u8>) {
+        let capacity = buffer.capacity();
+        if capacity == 0 || capacity > self.max_buffer_bytes {
+            return;
+        }
+        buffer.clear();
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        let idle_bytes: usize = idle.iter().map(Vec::capacity).sum();
+        if idle_bytes + capacity > self.max_idle_bytes {
+            return;
+        }
+        // Kept sorted by capacity, largest last
+        let index = idle.partition_point(|held| held.capacity() <= capacity);
+        idle.insert(index, buffer);
+    }
+}
+
+impl Default for BufferPool {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_IDLE_BYTES, DEFAULT_MAX_BUFFER_BYTES)
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`]
+///
+/// Dereferences to the underlying `Vec<u8>`; returned to the pool on drop.
+#[derive(Debug)]
+pub struct PooledBuffer {
+    buffer: Vec<u8>,
+    pool: Arc<BufferPool>,
+}
+
+impl Deref for PooledBuffer {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buffer
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buffer
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buffer));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffers_are_reused_within_bounds() {
+        let pool = Arc::new(BufferPool::new(3000, 2000));
+
+        let mut first = pool.take();
+        first.extend_from_slice(&[1; 1000]);
+        let capacity = first.capacity();
+        drop(first);
+        assert_eq!(pool.stats().idle, 1);
+
+        // The returned buffer comes back empty, with its capacity
+        let second = pool.take();
+        assert!(second.is_empty());
+        assert_eq!(second.capacity(), capacity);
+        drop(second);
+
+        // Oversized buffers are dropped instead of pooled
+        let mut large = pool.take();
+        large.reserve_exact(4000);
+        drop(large);
+        assert_eq!(pool.stats().idle, 0);
+
+        // So is anything past the idle bound
+        let (mut a, mut b) = (pool.take(), pool.take());
+        a.reserve_exact(1600);
+        b.reserve_exact(1600);
+        drop(a);
+        drop(b);
+        let stats = pool.stats();
+        assert_eq!(stats.idle, 1);
+        assert!(stats.idle_bytes <= 3000);
+        assert_eq!(stats.reused, 2);
+        assert_eq!(stats.allocated, 3);
+    }
+}
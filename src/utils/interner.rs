@@ -0,0 +1,201 @@
+//! String interner - shares repeated label names and values between samples
+//!
+//! Responsibilities:
+//! - Hand out one `Arc<str>` per distinct label string, across parses and
+//!   collection cycles (`item`, `cpu`, `interface`, ... repeat thousands of times)
+//! - Drop strings no sample references anymore, once per collection cycle
+//! - Report its size and hit rate so the win can be verified
+
+use serde::Serialize;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Interner shared by all parses of the process
+static LABEL_INTERNER: LazyLock<StringInterner> = LazyLock::new(StringInterner::new);
+
+/// The process-wide label interner
+pub fn labels() -> &'static StringInterner {
+    &LABEL_INTERNER
+}
+
+/// Size and effectiveness of a string interner
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct InternerStats {
+    /// Distinct strings held
+    pub strings: usize,
+    /// Bytes of string data held
+    pub bytes: usize,
+    /// Lookups served by an existing string
+    pub hits: u64,
+    /// Lookups that added a new string
+    pub misses: u64,
+}
+
+impl InternerStats {
+    /// Render the stats in Prometheus text format
+    pub fn render_metrics(&self) -> String {
+        let mut output = String::new();
+        output.push_str(
+            "# HELP kata_pulse_interned_strings Distinct label strings held by the interner\n",
+        );
+        output.push_str("# TYPE kata_pulse_interned_strings gauge\n");
+        output.push_str(&format!("kata_pulse_interned_strings {}\n", self.strings));
+        output.push_str(
+            "# HELP kata_pulse_interned_bytes Bytes of label strings held by the interner\n",
+        );
+        output.push_str("# TYPE kata_pulse_interned_bytes gauge\n");
+        output.push_str(&format!("kata_pulse_interned_bytes {}\n", self.bytes));
+        output
+            .push_str("# HELP kata_pulse_interner_lookups_total Label string lookups by result\n");
+        output.push_str("# TYPE kata_pulse_interner_lookups_total counter\n");
+        output.push_str(&format!(
+            "kata_pulse_interner_lookups_total{{result=\"hit\"}} {}\n",
+            self.hits
+        ));
+        output.push_str(&format!(
+            "kata_pulse_interner_lookups_total{{result=\"miss\"}} {}\n",
+            self.misses
+        ));
+        output
+    }
+}
+
+/// Thread-safe set of shared strings
+#[derive(Debug, Default)]
+pub struct StringInterner {
+    strings: Mutex<HashSet<Arc<str>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl StringInterner {
+    /// Create an empty interner
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a batch of lookups (e.g. one parse)
+    ///
+    /// The batch remembers the strings it has seen, so the shared set is
+    /// locked once per distinct string rather than once per lookup.
+    pub fn batch(&self) -> InternBatch<'_> {
+        InternBatch {
+            interner: self,
+            seen: HashSet::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Drop the strings that are no longer referenced outside the interner
+    ///
+    /// Returns the number of dropped strings.
+    pub fn purge_unused(&self) -> usize {
+        let mut strings = self.strings.lock().unwrap_or_else(|e| e.into_inner());
+        let before = strings.len();
+        strings.retain(|s| Arc::strong_count(s) > 1);
+        before - strings.len()
+    }
+
+    /// Current size and hit rate
+    pub fn stats(&self) -> InternerStats {
+        let strings = self.strings.lock().unwrap_or_else(|e| e.into_inner());
+        InternerStats {
+            strings: strings.len(),
+            bytes: strings.iter().map(|s| s.len()).sum(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    fn intern_shared(&self, value: &str) -> (Arc<str>, bool) {
+        let mut strings = self.strings.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = strings.get(value) {
+            return (existing.clone(), true);
+        }
+        let interned: Arc<str> = Arc::from(value);
+        strings.insert(interned.clone());
+        (interned, false)
+    }
+}
+
+/// A batch of lookups against a [`StringInterner`]
+///
+/// Hit and miss counts are added to the interner when the batch is dropped.
+pub struct InternBatch<'a> {
+    interner: &'a StringInterner,
+    seen: HashSet<Arc<str>>,
+    hits: u64,
+    misses: u64,
+}
+
+impl InternBatch<'_> {
+    /// Get the shared copy of `value`
+    pub fn intern(&mut self, value: &str) -> Arc<str> {
+        if let Some(existing) = self.seen.get(value) {
+            self.hits += 1;
+            return existing.clone();
+        }
+        let (interned, hit) = self.interner.intern_shared(value);
+        if hit {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+        }
+        self.seen.insert(interned.clone());
+        interned
+    }
+}
+
+impl Drop for InternBatch<'_> {
+    fn drop(&mut self) {
+        self.interner.hits.fetch_add(self.hits, Ordering::Relaxed);
+        self.interner
+            .misses
+            .fetch_add(self.misses, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intern_shares_strings_across_batches() {
+        let interner = StringInterner::new();
+        let first = {
+            let mut batch = interner.batch();
+            let a = batch.intern("item");
+            let b = batch.intern("item");
+            assert!(Arc::ptr_eq(&a, &b));
+            a
+        };
+        let second = interner.batch().intern("item");
+        assert!(Arc::ptr_eq(&first, &second));
+
+        assert_eq!(
+            interner.stats(),
+            InternerStats {
+                strings: 1,
+                bytes: 4,
+                hits: 2,
+                misses: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_purge_unused() {
+        let interner = StringInterner::new();
+        let kept = {
+            let mut batch = interner.batch();
+            batch.intern("dropped");
+            batch.intern("kept")
+        };
+
+        assert_eq!(interner.purge_unused(), 1);
+        assert_eq!(interner.stats().strings, 1);
+        assert!(Arc::ptr_eq(&kept, &interner.batch().intern("kept")));
+    }
+}
@@ -0,0 +1,161 @@
+//! Log deduplication - keeps a failing sandbox from flooding the log
+//!
+//! Responsibilities:
+//! - Let the first occurrence of a (sandbox, error class) pair through
+//! - Suppress repeats, letting a summary with the suppressed count through
+//!   once per interval
+//! - Forget a pair when the sandbox recovers or is gone, so the next failure
+//!   is logged right away
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// What to do with an occurrence
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogDecision {
+    /// First occurrence: log it
+    First,
+    /// The interval passed: log a summary of the occurrences suppressed since the last log
+    Summary { suppressed: u64 },
+    /// Repeat within the interval: do not log (or log at debug)
+    Suppress,
+}
+
+/// Per (sandbox, error class) state
+struct Entry {
+    last_logged: Instant,
+    suppressed: u64,
+}
+
+/// Deduplicates log lines keyed by sandbox and error class
+pub struct LogDedup {
+    interval: Duration,
+    entries: Mutex<HashMap<(String, String), Entry>>,
+}
+
+impl LogDedup {
+    /// Create a deduplicator logging a summary at most once per `interval`
+    pub fn new(interval: Duration) -> Self {
+        LogDedup {
+            interval,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record an occurrence of `class` for `sandbox_id`
+    pub fn check(&self, sandbox_id: &str, class: &str) -> LogDecision {
+        self.check_at(sandbox_id, class, Instant::now())
+    }
+
+    fn check_at(&self, sandbox_id: &str, class: &str, now: Instant) -> LogDecision {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let key = (sandbox_id.to_string(), class.to_string());
+        let Some(entry) = entries.get_mut(&key) else {
+            entries.insert(
+                key,
+                Entry {
+                    last_logged: now,
+                    suppressed: 0,
+                },
+            );
+            return LogDecision::First;
+        };
+
+        entry.suppressed += 1;
+        if now.duration_since(entry.last_logged) < self.interval {
+            return LogDecision::Suppress;
+        }
+        let suppressed = entry.suppressed;
+        entry.last_logged = now;
+        entry.suppressed = 0;
+        LogDecision::Summary { suppressed }
+    }
+
+    /// Forget all error classes of a sandbox (it recovered)
+    pub fn clear(&self, sandbox_id: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|(id, _), _| id != sandbox_id);
+    }
+
+    /// Forget sandboxes that are no longer tracked
+    pub fn retain(&self, sandbox_ids: &[String]) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|(id, _), _| sandbox_ids.contains(id));
+    }
+}
+
+/// Class of an error for deduplication: the kind of the underlying I/O error
+/// if there is one, otherwise the root cause without numbers (durations,
+/// sizes and addresses vary between otherwise identical errors)
+pub fn error_class(error: &anyhow::Error) -> String {
+    if let Some(io) = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<std::io::Error>())
+    {
+        return format!("{:?}", io.kind());
+    }
+    error
+        .root_cause()
+        .to_string()
+        .chars()
+        .filter(|c| !c.is_ascii_digit())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_then_summary() {
+        let dedup = LogDedup::new(Duration::from_secs(60));
+        let start = Instant::now();
+        assert_eq!(dedup.check_at("sb-1", "timeout", start), LogDecision::First);
+        assert_eq!(
+            dedup.check_at("sb-1", "timeout", start + Duration::from_secs(10)),
+            LogDecision::Suppress
+        );
+        assert_eq!(
+            dedup.check_at("sb-1", "refused", start + Duration::from_secs(10)),
+            LogDecision::First
+        );
+        assert_eq!(dedup.check_at("sb-2", "timeout", start), LogDecision::First);
+        assert_eq!(
+            dedup.check_at("sb-1", "timeout", start + Duration::from_secs(20)),
+            LogDecision::Suppress
+        );
+        assert_eq!(
+            dedup.check_at("sb-1", "timeout", start + Duration::from_secs(61)),
+            LogDecision::Summary { suppressed: 3 }
+        );
+
+        // A recovered sandbox logs its next failure again
+        dedup.clear("sb-1");
+        assert_eq!(
+            dedup.check_at("sb-1", "timeout", start + Duration::from_secs(62)),
+            LogDecision::First
+        );
+        dedup.retain(&["sb-1".to_string()]);
+        assert_eq!(
+            dedup.check_at("sb-2", "timeout", start + Duration::from_secs(62)),
+            LogDecision::First
+        );
+    }
+
+    #[test]
+    fn test_error_class() {
+        let refused =
+            anyhow::Error::from(std::io::Error::from(std::io::ErrorKind::ConnectionRefused))
+                .context("failed to connect to /run/vc/sbs/sb-1/shim-monitor.sock");
+        assert_eq!(error_class(&refused), "ConnectionRefused");
+        assert_eq!(
+            error_class(&anyhow::anyhow!(
+                "timed out after 3s reading from http://shim/metrics"
+            )),
+            error_class(&anyhow::anyhow!(
+                "timed out after 5s reading from http://shim/metrics"
+            ))
+        );
+    }
+}
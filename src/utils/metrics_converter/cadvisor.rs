@@ -3,34 +3,101 @@
 //! This module defines the output format for converted metrics,
 //! matching cAdvisor's metric structure and naming conventions.
 
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::{Display, Write};
+use std::sync::Arc;
 
 /// Trait for converting metrics to Prometheus text format
 ///
 /// Implementations handle the serialization of metrics into valid Prometheus exposition format,
 /// including HELP and TYPE annotations.
 pub trait PrometheusFormat {
-    /// Convert this metric to Prometheus text format
+    /// Append this metric in Prometheus text format to `out`
     /// Optional sandbox_id parameter can be used to add labels
-    fn to_prometheus_format(&self, _sandbox_id: Option<&str>) -> String;
+    fn write_prometheus(&self, out: &mut String, sandbox_id: Option<&str>);
+
+    /// Estimated length of the text, used to preallocate the output
+    fn estimated_len(&self) -> usize {
+        0
+    }
+
+    /// Convert this metric to Prometheus text format
+    fn to_prometheus_format(&self, sandbox_id: Option<&str>) -> String {
+        let mut output = String::with_capacity(self.estimated_len());
+        self.write_prometheus(&mut output, sandbox_id);
+        output
+    }
 }
 
-/// Helper function to escape label values for Prometheus format
-fn escape_label_value(value: &str) -> String {
-    let mut result = String::new();
+/// Append `value` to `out`, escaped for a Prometheus label value
+pub fn write_escaped(out: &mut String, value: &str) {
     for ch in value.chars() {
         match ch {
-            '\\' => result.push_str("\\\\"),
-            '"' => result.push_str("\\\""),
-            '\n' => result.push_str("\\n"),
-            _ => result.push(ch),
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            _ => out.push(ch),
         }
     }
+}
+
+/// Helper function to escape label values for Prometheus format
+pub fn escape_label_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    write_escaped(&mut result, value);
     result
 }
 
+/// Append the `# HELP` and `# TYPE` lines of a metric family to `out`
+fn write_header(out: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = write!(
+        out,
+        "# HELP {} {}\n# TYPE {} {}\n",
+        name, help, name, metric_type
+    );
+}
+
+/// Standard labels of a metric set, escaped once and reused for every sample
+struct LabelWriter {
+    /// `container="...",...,pod="..."`
+    escaped: String,
+}
+
+impl LabelWriter {
+    /// Append a sample with the standard labels plus `extras` to `out`
+    fn sample(&self, out: &mut String, name: &str, extras: &[(&str, &str)], value: impl Display) {
+        out.push_str(name);
+        out.push('{');
+        out.push_str(&self.escaped);
+        for (key, extra) in extras {
+            out.push(',');
+            out.push_str(key);
+            out.push_str("=\"");
+            write_escaped(out, extra);
+            out.push('"');
+        }
+        let _ = writeln!(out, "}} {}", value);
+    }
+
+    /// Append a family of a single sample with its header to `out`, if `value` is known
+    fn family(
+        &self,
+        out: &mut String,
+        name: &str,
+        metric_type: &str,
+        help: &str,
+        value: Option<impl Display>,
+    ) {
+        if let Some(value) = value {
+            write_header(out, name, metric_type, help);
+            self.sample(out, name, &[], value);
+        }
+    }
+}
+
 /// Standard cAdvisor labels present on all container metrics
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct StandardLabels {
     /// Container ID (empty for pod-level aggregates)
     pub container: String,
@@ -44,6 +111,9 @@ pub struct StandardLabels {
     pub namespace: String,
     /// Kubernetes pod name
     pub pod: String,
+    /// Labels from the Kubernetes API (workload, `label_<name>`), after the labels above
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pod_labels: Vec<(String, String)>,
 }
 
 impl StandardLabels {
@@ -69,53 +139,87 @@ impl StandardLabels {
             name: pod_name_str.clone(),   // Use pod name as container name
             namespace: pod_namespace_str,
             pod: pod_name_str,
+            pod_labels: Vec::new(),
+        }
+    }
+
+    /// Set the image of the sandbox, keeping `unknown` if None
+    pub fn with_image(mut self, image: Option<String>) -> Self {
+        if let Some(image) = image {
+            self.image = image;
         }
+        self
     }
 
-    /// Convert to label string for Prometheus format
-    fn to_label_string(&self) -> String {
-        let labels = [
-            format!(r#"container="{}""#, escape_label_value(&self.container)),
-            format!(r#"id="{}""#, escape_label_value(&self.id)),
-            format!(r#"image="{}""#, escape_label_value(&self.image)),
-            format!(r#"name="{}""#, escape_label_value(&self.name)),
-            format!(r#"namespace="{}""#, escape_label_value(&self.namespace)),
-            format!(r#"pod="{}""#, escape_label_value(&self.pod)),
-        ];
-        format!("{{{}}}", labels.join(","))
+    /// Add the labels from the Kubernetes API
+    pub fn with_pod_labels(mut self, pod_labels: Vec<(String, String)>) -> Self {
+        self.pod_labels = pod_labels;
+        self
     }
 
-    /// Convert to label string with additional labels
-    fn to_label_string_with_extras(&self, extras: &[(&str, &str)]) -> String {
-        let mut labels = vec![
-            format!(r#"container="{}""#, escape_label_value(&self.container)),
-            format!(r#"id="{}""#, escape_label_value(&self.id)),
-            format!(r#"image="{}""#, escape_label_value(&self.image)),
-            format!(r#"name="{}""#, escape_label_value(&self.name)),
-            format!(r#"namespace="{}""#, escape_label_value(&self.namespace)),
-            format!(r#"pod="{}""#, escape_label_value(&self.pod)),
-        ];
-
-        for (key, value) in extras {
-            labels.push(format!(r#"{}="{}""#, key, escape_label_value(value)));
+    /// Escape the labels once for writing the samples of a metric set
+    fn writer(&self) -> LabelWriter {
+        let mut escaped = String::with_capacity(self.estimated_len());
+        for (i, (key, value)) in [
+            ("container", &self.container),
+            ("id", &self.id),
+            ("image", &self.image),
+            ("name", &self.name),
+            ("namespace", &self.namespace),
+            ("pod", &self.pod),
+        ]
+        .into_iter()
+        .chain(
+            self.pod_labels
+                .iter()
+                .map(|(key, value)| (key.as_str(), value)),
+        )
+        .enumerate()
+        {
+            if i > 0 {
+                escaped.push(',');
+            }
+            escaped.push_str(key);
+            escaped.push_str("=\"");
+            write_escaped(&mut escaped, value);
+            escaped.push('"');
         }
+        LabelWriter { escaped }
+    }
 
-        format!("{{{}}}", labels.join(","))
+    /// Estimated length of the rendered labels
+    fn estimated_len(&self) -> usize {
+        self.container.len()
+            + self.id.len()
+            + self.image.len()
+            + self.name.len()
+            + self.namespace.len()
+            + self.pod.len()
+            + self
+                .pod_labels
+                .iter()
+                .map(|(key, value)| key.len() + value.len() + 4)
+                .sum::<usize>()
+            + 48
     }
 }
 
 /// Complete set of converted cAdvisor metrics
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CadvisorMetrics {
     pub cpu: CpuMetrics,
     pub memory: MemoryMetrics,
     pub network: NetworkMetrics,
     pub disk: DiskMetrics,
     pub process: ProcessMetrics,
+    pub shim: ShimMetrics,
+    pub virtiofsd: VirtiofsdMetrics,
+    pub overhead: OverheadMetrics,
+    pub vmm: VmmMetrics,
 }
 
 /// CPU metrics in cAdvisor format
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CpuMetrics {
     /// Total CPU usage in seconds (all CPUs combined)
     pub usage_seconds_total: f64,
@@ -134,11 +238,11 @@ pub struct CpuMetrics {
     pub per_cpu: HashMap<String, f64>,
 
     /// Standard cAdvisor labels (container, id, image, name, namespace, pod)
-    pub standard_labels: StandardLabels,
+    pub standard_labels: Arc<StandardLabels>,
 }
 
 /// Load average breakdown
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoadAverage {
     pub one_minute: f64,
     pub five_minute: f64,
@@ -146,7 +250,7 @@ pub struct LoadAverage {
 }
 
 /// Memory metrics in cAdvisor format
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MemoryMetrics {
     /// Total memory in use (in bytes)
     pub usage_bytes: u64,
@@ -166,17 +270,21 @@ pub struct MemoryMetrics {
     /// Memory-mapped file size (in bytes)
     pub mapped_file_bytes: Option<u64>,
 
+    /// Memory the guest currently sees (MemTotal), after hotplug and ballooning (in bytes)
+    #[serde(default)]
+    pub guest_total_bytes: Option<u64>,
+
     /// Memory failure counts - mapped by "failure_type:scope" key (e.g., "pgfault:container")
     /// failure_type: "pgfault" or "pgmajfault"
     /// scope: "container" or "hierarchy"
     pub failures: HashMap<String, u64>,
 
     /// Standard cAdvisor labels (container, id, image, name, namespace, pod)
-    pub standard_labels: StandardLabels,
+    pub standard_labels: Arc<StandardLabels>,
 }
 
 /// Network metrics in cAdvisor format
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct NetworkMetrics {
     /// Total bytes received
     pub receive_bytes_total: u64,
@@ -206,11 +314,11 @@ pub struct NetworkMetrics {
     pub per_interface: HashMap<String, InterfaceMetrics>,
 
     /// Standard cAdvisor labels (container, id, image, name, namespace, pod)
-    pub standard_labels: StandardLabels,
+    pub standard_labels: Arc<StandardLabels>,
 }
 
 /// Per-interface network metrics
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct InterfaceMetrics {
     /// Interface name (eth0, cilium_vxlan, etc.) - used for the interface label
     pub name: String,
@@ -225,7 +333,7 @@ pub struct InterfaceMetrics {
 }
 
 /// Disk I/O metrics in cAdvisor format
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DiskMetrics {
     /// Total disk read operations
     pub reads_total: u64,
@@ -255,11 +363,11 @@ pub struct DiskMetrics {
     pub per_device: HashMap<String, DeviceMetrics>,
 
     /// Standard cAdvisor labels (container, id, image, name, namespace, pod)
-    pub standard_labels: StandardLabels,
+    pub standard_labels: Arc<StandardLabels>,
 }
 
 /// Per-device disk metrics for block I/O
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DeviceMetrics {
     /// Device name/path for the device label (e.g., /dev/sda, /dev/sdb, or empty "")
     pub device: String,
@@ -276,7 +384,7 @@ pub struct DeviceMetrics {
 }
 
 /// Process metrics in cAdvisor format
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProcessMetrics {
     /// Number of running processes
     pub count: u64,
@@ -294,440 +402,634 @@ pub struct ProcessMetrics {
     pub tasks_by_state: HashMap<String, u64>,
 
     /// Standard cAdvisor labels (container, id, image, name, namespace, pod)
-    pub standard_labels: StandardLabels,
+    pub standard_labels: Arc<StandardLabels>,
+}
+
+/// Host-side metrics of the Kata shim process itself (runtime overhead per pod)
+///
+/// Rendered under the `kata_pod_shim_` prefix so they never mix with the
+/// guest-derived `container_*` families.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ShimMetrics {
+    /// Number of shim threads
+    pub threads: Option<u64>,
+
+    /// Open file descriptors of the shim
+    pub fds: Option<u64>,
+
+    /// Resident set size of the shim (in bytes)
+    pub rss_bytes: Option<u64>,
+
+    /// RPC latencies of the shim towards the agent - mapped by RPC action
+    pub rpc_durations: HashMap<String, RpcLatency>,
+
+    /// Standard cAdvisor labels (container, id, image, name, namespace, pod)
+    pub standard_labels: Arc<StandardLabels>,
+}
+
+/// Accumulated latency of a single shim RPC
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RpcLatency {
+    /// Number of calls
+    pub count: u64,
+    /// Total time spent in calls (in seconds)
+    pub sum_seconds: f64,
+    /// Latency quantiles as (quantile, seconds), sorted by quantile
+    pub quantiles: Vec<(f64, f64)>,
+}
+
+/// Host-side metrics of the sandbox's virtiofsd daemon
+///
+/// Rendered under the `kata_pod_virtiofsd_` prefix; empty when virtiofsd
+/// metrics are disabled or the sandbox does not use virtio-fs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VirtiofsdMetrics {
+    /// Number of virtiofsd threads
+    pub threads: Option<u64>,
+
+    /// Open file descriptors of virtiofsd
+    pub fds: Option<u64>,
+
+    /// Bytes read on behalf of the guest
+    pub read_bytes_total: Option<u64>,
+
+    /// Bytes written on behalf of the guest
+    pub write_bytes_total: Option<u64>,
+
+    /// Read syscalls issued on behalf of the guest
+    pub read_ops_total: Option<u64>,
+
+    /// Write syscalls issued on behalf of the guest
+    pub write_ops_total: Option<u64>,
+
+    /// Standard cAdvisor labels (container, id, image, name, namespace, pod)
+    pub standard_labels: Arc<StandardLabels>,
+}
+
+/// Host resource usage of the sandbox overhead (shim + hypervisor cgroup)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OverheadMetrics {
+    /// CPU time of the overhead cgroup in seconds
+    pub cpu_seconds_total: Option<f64>,
+
+    /// Memory usage of the overhead cgroup (in bytes)
+    pub memory_bytes: Option<u64>,
+
+    /// Standard cAdvisor labels (container, id, image, name, namespace, pod)
+    pub standard_labels: Arc<StandardLabels>,
+}
+
+/// Host resource usage of the sandbox's hypervisor (VMM) process
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VmmMetrics {
+    /// Resident set size of the VMM (in bytes)
+    pub rss_bytes: Option<u64>,
+
+    /// CPU time of the VMM in seconds
+    pub cpu_seconds_total: Option<f64>,
+
+    /// Open file descriptors of the VMM
+    pub fds: Option<u64>,
+
+    /// Standard cAdvisor labels (container, id, image, name, namespace, pod)
+    pub standard_labels: Arc<StandardLabels>,
 }
 
 // PrometheusFormat trait implementations for each metric type
 
-impl PrometheusFormat for CpuMetrics {
-    fn to_prometheus_format(&self, _sandbox_id: Option<&str>) -> String {
-        let mut output = String::new();
-        let labels_with_cpu = self
-            .standard_labels
-            .to_label_string_with_extras(&[("cpu", "total")]);
+/// Accessor of a per-interface network counter
+type InterfaceCounter = fn(&InterfaceMetrics) -> u64;
 
-        output
-            .push_str("# HELP container_cpu_usage_seconds_total Total CPU time used in seconds\n");
-        output.push_str("# TYPE container_cpu_usage_seconds_total counter\n");
-        output.push_str(&format!(
-            "container_cpu_usage_seconds_total{} {}\n",
-            labels_with_cpu, self.usage_seconds_total
-        ));
+impl PrometheusFormat for CpuMetrics {
+    fn write_prometheus(&self, out: &mut String, _sandbox_id: Option<&str>) {
+        let labels = self.standard_labels.writer();
+        let cpu = [("cpu", "total")];
+
+        write_header(
+            out,
+            "container_cpu_usage_seconds_total",
+            "counter",
+            "Total CPU time used in seconds",
+        );
+        labels.sample(
+            out,
+            "container_cpu_usage_seconds_total",
+            &cpu,
+            self.usage_seconds_total,
+        );
 
         if self.user_seconds_total > 0.0 {
-            output
-                .push_str("# HELP container_cpu_user_seconds_total CPU time spent in user mode\n");
-            output.push_str("# TYPE container_cpu_user_seconds_total counter\n");
-            output.push_str(&format!(
-                "container_cpu_user_seconds_total{} {}\n",
-                labels_with_cpu, self.user_seconds_total
-            ));
+            write_header(
+                out,
+                "container_cpu_user_seconds_total",
+                "counter",
+                "CPU time spent in user mode",
+            );
+            labels.sample(
+                out,
+                "container_cpu_user_seconds_total",
+                &cpu,
+                self.user_seconds_total,
+            );
         }
 
         if self.system_seconds_total > 0.0 {
-            output.push_str(
-                "# HELP container_cpu_system_seconds_total CPU time spent in system mode\n",
+            write_header(
+                out,
+                "container_cpu_system_seconds_total",
+                "counter",
+                "CPU time spent in system mode",
+            );
+            labels.sample(
+                out,
+                "container_cpu_system_seconds_total",
+                &cpu,
+                self.system_seconds_total,
             );
-            output.push_str("# TYPE container_cpu_system_seconds_total counter\n");
-            output.push_str(&format!(
-                "container_cpu_system_seconds_total{} {}\n",
-                labels_with_cpu, self.system_seconds_total
-            ));
         }
 
         if let Some(load) = &self.load_average {
-            output.push_str("# HELP container_load_average_1m 1-minute load average\n");
-            output.push_str("# TYPE container_load_average_1m gauge\n");
-            output.push_str(&format!(
-                "container_load_average_1m{} {}\n",
-                labels_with_cpu, load.one_minute
-            ));
-
-            output.push_str("# HELP container_load_average_5m 5-minute load average\n");
-            output.push_str("# TYPE container_load_average_5m gauge\n");
-            output.push_str(&format!(
-                "container_load_average_5m{} {}\n",
-                labels_with_cpu, load.five_minute
-            ));
-
-            output.push_str("# HELP container_load_average_15m 15-minute load average\n");
-            output.push_str("# TYPE container_load_average_15m gauge\n");
-            output.push_str(&format!(
-                "container_load_average_15m{} {}\n",
-                labels_with_cpu, load.fifteen_minute
-            ));
+            for (name, help, value) in [
+                (
+                    "container_load_average_1m",
+                    "1-minute load average",
+                    load.one_minute,
+                ),
+                (
+                    "container_load_average_5m",
+                    "5-minute load average",
+                    load.five_minute,
+                ),
+                (
+                    "container_load_average_15m",
+                    "15-minute load average",
+                    load.fifteen_minute,
+                ),
+            ] {
+                write_header(out, name, "gauge", help);
+                labels.sample(out, name, &cpu, value);
+            }
         }
-
-        output
     }
 }
 
 impl PrometheusFormat for MemoryMetrics {
-    fn to_prometheus_format(&self, _sandbox_id: Option<&str>) -> String {
-        let mut output = String::new();
-        let labels_suffix = self.standard_labels.to_label_string();
-
-        output.push_str("# HELP container_memory_usage_bytes Memory usage in bytes\n");
-        output.push_str("# TYPE container_memory_usage_bytes gauge\n");
-        output.push_str(&format!(
-            "container_memory_usage_bytes{} {}\n",
-            labels_suffix, self.usage_bytes
-        ));
-
-        if let Some(working_set) = self.working_set_bytes {
-            output
-                .push_str("# HELP container_memory_working_set_bytes Working set size in bytes\n");
-            output.push_str("# TYPE container_memory_working_set_bytes gauge\n");
-            output.push_str(&format!(
-                "container_memory_working_set_bytes{} {}\n",
-                labels_suffix, working_set
-            ));
-        }
-
-        if let Some(cache) = self.cache_bytes {
-            output.push_str("# HELP container_memory_cache_bytes Memory cache in bytes\n");
-            output.push_str("# TYPE container_memory_cache_bytes gauge\n");
-            output.push_str(&format!(
-                "container_memory_cache_bytes{} {}\n",
-                labels_suffix, cache
-            ));
-        }
-
-        if let Some(rss) = self.rss_bytes {
-            output.push_str("# HELP container_memory_rss_bytes Resident set size in bytes\n");
-            output.push_str("# TYPE container_memory_rss_bytes gauge\n");
-            output.push_str(&format!(
-                "container_memory_rss_bytes{} {}\n",
-                labels_suffix, rss
-            ));
-        }
-
-        if let Some(swap) = self.swap_bytes {
-            output.push_str("# HELP container_memory_swap_bytes Swap usage in bytes\n");
-            output.push_str("# TYPE container_memory_swap_bytes gauge\n");
-            output.push_str(&format!(
-                "container_memory_swap_bytes{} {}\n",
-                labels_suffix, swap
-            ));
+    fn write_prometheus(&self, out: &mut String, _sandbox_id: Option<&str>) {
+        let labels = self.standard_labels.writer();
+
+        write_header(
+            out,
+            "container_memory_usage_bytes",
+            "gauge",
+            "Memory usage in bytes",
+        );
+        labels.sample(out, "container_memory_usage_bytes", &[], self.usage_bytes);
+
+        for (name, help, value) in [
+            (
+                "container_memory_working_set_bytes",
+                "Working set size in bytes",
+                self.working_set_bytes,
+            ),
+            (
+                "container_memory_cache_bytes",
+                "Memory cache in bytes",
+                self.cache_bytes,
+            ),
+            (
+                "container_memory_rss_bytes",
+                "Resident set size in bytes",
+                self.rss_bytes,
+            ),
+            (
+                "container_memory_swap_bytes",
+                "Swap usage in bytes",
+                self.swap_bytes,
+            ),
+            (
+                "container_memory_guest_total_bytes",
+                "Memory the guest currently has, after hotplug and ballooning",
+                self.guest_total_bytes,
+            ),
+        ] {
+            if let Some(value) = value {
+                write_header(out, name, "gauge", help);
+                labels.sample(out, name, &[], value);
+            }
         }
 
         // Emit memory failure metrics if available
         if !self.failures.is_empty() {
-            output.push_str("# HELP container_memory_failures_total Memory failure count\n");
-            output.push_str("# TYPE container_memory_failures_total counter\n");
+            write_header(
+                out,
+                "container_memory_failures_total",
+                "counter",
+                "Memory failure count",
+            );
             for (key, count) in &self.failures {
                 // Key format is "failure_type:scope" e.g., "pgfault:container"
                 let parts: Vec<&str> = key.split(':').collect();
-                if parts.len() == 2 {
-                    let failure_type = parts[0];
-                    let scope = parts[1];
-                    let failure_labels = self.standard_labels.to_label_string_with_extras(&[
-                        ("failure_type", failure_type),
-                        ("scope", scope),
-                    ]);
-                    output.push_str(&format!(
-                        "container_memory_failures_total{} {}\n",
-                        failure_labels, count
-                    ));
+                if let [failure_type, scope] = parts[..] {
+                    labels.sample(
+                        out,
+                        "container_memory_failures_total",
+                        &[("failure_type", failure_type), ("scope", scope)],
+                        count,
+                    );
                 }
             }
         }
-
-        output
     }
 }
 
 impl PrometheusFormat for NetworkMetrics {
-    fn to_prometheus_format(&self, _sandbox_id: Option<&str>) -> String {
-        let mut output = String::new();
-        let labels_suffix = self.standard_labels.to_label_string();
+    fn write_prometheus(&self, out: &mut String, _sandbox_id: Option<&str>) {
+        let labels = self.standard_labels.writer();
 
         if self.receive_bytes_total > 0 || self.transmit_bytes_total > 0 {
-            output.push_str("# HELP container_network_receive_bytes_total Total bytes received\n");
-            output.push_str("# TYPE container_network_receive_bytes_total counter\n");
-            output.push_str(&format!(
-                "container_network_receive_bytes_total{} {}\n",
-                labels_suffix, self.receive_bytes_total
-            ));
-
-            output.push_str(
-                "# HELP container_network_transmit_bytes_total Total bytes transmitted\n",
-            );
-            output.push_str("# TYPE container_network_transmit_bytes_total counter\n");
-            output.push_str(&format!(
-                "container_network_transmit_bytes_total{} {}\n",
-                labels_suffix, self.transmit_bytes_total
-            ));
-
-            output.push_str(
-                "# HELP container_network_receive_packets_total Total packets received\n",
-            );
-            output.push_str("# TYPE container_network_receive_packets_total counter\n");
-            output.push_str(&format!(
-                "container_network_receive_packets_total{} {}\n",
-                labels_suffix, self.receive_packets_total
-            ));
-
-            output.push_str(
-                "# HELP container_network_transmit_packets_total Total packets transmitted\n",
-            );
-            output.push_str("# TYPE container_network_transmit_packets_total counter\n");
-            output.push_str(&format!(
-                "container_network_transmit_packets_total{} {}\n",
-                labels_suffix, self.transmit_packets_total
-            ));
-        }
-
-        if let Some(errors) = self.receive_errors_total {
-            output.push_str("# HELP container_network_receive_errors_total Receive errors\n");
-            output.push_str("# TYPE container_network_receive_errors_total counter\n");
-            output.push_str(&format!(
-                "container_network_receive_errors_total{} {}\n",
-                labels_suffix, errors
-            ));
+            for (name, help, value) in [
+                (
+                    "container_network_receive_bytes_total",
+                    "Total bytes received",
+                    self.receive_bytes_total,
+                ),
+                (
+                    "container_network_transmit_bytes_total",
+                    "Total bytes transmitted",
+                    self.transmit_bytes_total,
+                ),
+                (
+                    "container_network_receive_packets_total",
+                    "Total packets received",
+                    self.receive_packets_total,
+                ),
+                (
+                    "container_network_transmit_packets_total",
+                    "Total packets transmitted",
+                    self.transmit_packets_total,
+                ),
+            ] {
+                write_header(out, name, "counter", help);
+                labels.sample(out, name, &[], value);
+            }
         }
 
-        if let Some(errors) = self.transmit_errors_total {
-            output.push_str("# HELP container_network_transmit_errors_total Transmit errors\n");
-            output.push_str("# TYPE container_network_transmit_errors_total counter\n");
-            output.push_str(&format!(
-                "container_network_transmit_errors_total{} {}\n",
-                labels_suffix, errors
-            ));
+        for (name, help, value) in [
+            (
+                "container_network_receive_errors_total",
+                "Receive errors",
+                self.receive_errors_total,
+            ),
+            (
+                "container_network_transmit_errors_total",
+                "Transmit errors",
+                self.transmit_errors_total,
+            ),
+        ] {
+            if let Some(value) = value {
+                write_header(out, name, "counter", help);
+                labels.sample(out, name, &[], value);
+            }
         }
 
         // Emit per-interface metrics if available
         if !self.per_interface.is_empty() {
-            output.push_str(
-                "# HELP container_network_receive_bytes_total Total bytes received per interface\n",
-            );
-            output.push_str("# TYPE container_network_receive_bytes_total counter\n");
-            for iface in self.per_interface.values() {
-                if iface.receive_bytes > 0 {
-                    let iface_labels = self
-                        .standard_labels
-                        .to_label_string_with_extras(&[("interface", &iface.name)]);
-                    output.push_str(&format!(
-                        "container_network_receive_bytes_total{} {}\n",
-                        iface_labels, iface.receive_bytes
-                    ));
-                }
-            }
-
-            output.push_str("# HELP container_network_transmit_bytes_total Total bytes transmitted per interface\n");
-            output.push_str("# TYPE container_network_transmit_bytes_total counter\n");
-            for iface in self.per_interface.values() {
-                if iface.transmit_bytes > 0 {
-                    let iface_labels = self
-                        .standard_labels
-                        .to_label_string_with_extras(&[("interface", &iface.name)]);
-                    output.push_str(&format!(
-                        "container_network_transmit_bytes_total{} {}\n",
-                        iface_labels, iface.transmit_bytes
-                    ));
-                }
-            }
-
-            output.push_str("# HELP container_network_receive_packets_total Total packets received per interface\n");
-            output.push_str("# TYPE container_network_receive_packets_total counter\n");
-            for iface in self.per_interface.values() {
-                if iface.receive_packets > 0 {
-                    let iface_labels = self
-                        .standard_labels
-                        .to_label_string_with_extras(&[("interface", &iface.name)]);
-                    output.push_str(&format!(
-                        "container_network_receive_packets_total{} {}\n",
-                        iface_labels, iface.receive_packets
-                    ));
-                }
-            }
-
-            output.push_str("# HELP container_network_transmit_packets_total Total packets transmitted per interface\n");
-            output.push_str("# TYPE container_network_transmit_packets_total counter\n");
-            for iface in self.per_interface.values() {
-                if iface.transmit_packets > 0 {
-                    let iface_labels = self
-                        .standard_labels
-                        .to_label_string_with_extras(&[("interface", &iface.name)]);
-                    output.push_str(&format!(
-                        "container_network_transmit_packets_total{} {}\n",
-                        iface_labels, iface.transmit_packets
-                    ));
+            let families: [(&str, &str, InterfaceCounter); 4] = [
+                (
+                    "container_network_receive_bytes_total",
+                    "Total bytes received per interface",
+                    |iface| iface.receive_bytes,
+                ),
+                (
+                    "container_network_transmit_bytes_total",
+                    "Total bytes transmitted per interface",
+                    |iface| iface.transmit_bytes,
+                ),
+                (
+                    "container_network_receive_packets_total",
+                    "Total packets received per interface",
+                    |iface| iface.receive_packets,
+                ),
+                (
+                    "container_network_transmit_packets_total",
+                    "Total packets transmitted per interface",
+                    |iface| iface.transmit_packets,
+                ),
+            ];
+            for (name, help, value) in families {
+                write_header(out, name, "counter", help);
+                for iface in self.per_interface.values() {
+                    let value = value(iface);
+                    if value > 0 {
+                        labels.sample(out, name, &[("interface", &iface.name)], value);
+                    }
                 }
             }
         }
-
-        output
     }
 }
 
 impl PrometheusFormat for DiskMetrics {
-    fn to_prometheus_format(&self, _sandbox_id: Option<&str>) -> String {
-        let mut output = String::new();
-        let labels_suffix = self.standard_labels.to_label_string();
+    fn write_prometheus(&self, out: &mut String, _sandbox_id: Option<&str>) {
+        let labels = self.standard_labels.writer();
 
         if self.reads_total > 0 || self.writes_total > 0 {
-            output.push_str("# HELP container_disk_io_reads_total Total disk read operations\n");
-            output.push_str("# TYPE container_disk_io_reads_total counter\n");
-            output.push_str(&format!(
-                "container_disk_io_reads_total{} {}\n",
-                labels_suffix, self.reads_total
-            ));
-
-            output.push_str("# HELP container_disk_io_writes_total Total disk write operations\n");
-            output.push_str("# TYPE container_disk_io_writes_total counter\n");
-            output.push_str(&format!(
-                "container_disk_io_writes_total{} {}\n",
-                labels_suffix, self.writes_total
-            ));
-
-            output
-                .push_str("# HELP container_disk_io_read_bytes_total Total bytes read from disk\n");
-            output.push_str("# TYPE container_disk_io_read_bytes_total counter\n");
-            output.push_str(&format!(
-                "container_disk_io_read_bytes_total{} {}\n",
-                labels_suffix, self.reads_bytes_total
-            ));
-
-            output.push_str(
-                "# HELP container_disk_io_write_bytes_total Total bytes written to disk\n",
-            );
-            output.push_str("# TYPE container_disk_io_write_bytes_total counter\n");
-            output.push_str(&format!(
-                "container_disk_io_write_bytes_total{} {}\n",
-                labels_suffix, self.writes_bytes_total
-            ));
-
-            if self.read_seconds_total > 0.0 {
-                output.push_str(
-                    "# HELP container_disk_io_read_seconds_total Total time spent reading\n",
-                );
-                output.push_str("# TYPE container_disk_io_read_seconds_total counter\n");
-                output.push_str(&format!(
-                    "container_disk_io_read_seconds_total{} {}\n",
-                    labels_suffix, self.read_seconds_total
-                ));
+            for (name, help, value) in [
+                (
+                    "container_disk_io_reads_total",
+                    "Total disk read operations",
+                    self.reads_total,
+                ),
+                (
+                    "container_disk_io_writes_total",
+                    "Total disk write operations",
+                    self.writes_total,
+                ),
+                (
+                    "container_disk_io_read_bytes_total",
+                    "Total bytes read from disk",
+                    self.reads_bytes_total,
+                ),
+                (
+                    "container_disk_io_write_bytes_total",
+                    "Total bytes written to disk",
+                    self.writes_bytes_total,
+                ),
+            ] {
+                write_header(out, name, "counter", help);
+                labels.sample(out, name, &[], value);
             }
 
-            if self.write_seconds_total > 0.0 {
-                output.push_str(
-                    "# HELP container_disk_io_write_seconds_total Total time spent writing\n",
-                );
-                output.push_str("# TYPE container_disk_io_write_seconds_total counter\n");
-                output.push_str(&format!(
-                    "container_disk_io_write_seconds_total{} {}\n",
-                    labels_suffix, self.write_seconds_total
-                ));
+            for (name, help, value) in [
+                (
+                    "container_disk_io_read_seconds_total",
+                    "Total time spent reading",
+                    self.read_seconds_total,
+                ),
+                (
+                    "container_disk_io_write_seconds_total",
+                    "Total time spent writing",
+                    self.write_seconds_total,
+                ),
+            ] {
+                if value > 0.0 {
+                    write_header(out, name, "counter", help);
+                    labels.sample(out, name, &[], value);
+                }
             }
         }
 
         // Emit per-device block I/O metrics if available
-        if !self.per_device.is_empty() {
-            // cAdvisor emits block I/O as container_blkio_device_usage_total with device, major, minor, operation labels
-            for device in self.per_device.values() {
-                // Read operations
-                if device.reads > 0 {
-                    let dev_labels = self.standard_labels.to_label_string_with_extras(&[
-                        ("device", &device.device),
-                        ("major", &device.major),
-                        ("minor", &device.minor),
-                        ("operation", "Read"),
-                    ]);
-                    output.push_str(&format!(
-                        "container_blkio_device_usage_total{} {}\n",
-                        dev_labels, device.reads
-                    ));
-                }
-                // Write operations
-                if device.writes > 0 {
-                    let dev_labels = self.standard_labels.to_label_string_with_extras(&[
-                        ("device", &device.device),
-                        ("major", &device.major),
-                        ("minor", &device.minor),
-                        ("operation", "Write"),
-                    ]);
-                    output.push_str(&format!(
-                        "container_blkio_device_usage_total{} {}\n",
-                        dev_labels, device.writes
-                    ));
+        // cAdvisor emits block I/O as container_blkio_device_usage_total with device, major, minor, operation labels
+        for device in self.per_device.values() {
+            for (operation, value) in [("Read", device.reads), ("Write", device.writes)] {
+                if value > 0 {
+                    labels.sample(
+                        out,
+                        "container_blkio_device_usage_total",
+                        &[
+                            ("device", &device.device),
+                            ("major", &device.major),
+                            ("minor", &device.minor),
+                            ("operation", operation),
+                        ],
+                        value,
+                    );
                 }
             }
         }
-
-        output
     }
 }
 
 impl PrometheusFormat for ProcessMetrics {
-    fn to_prometheus_format(&self, _sandbox_id: Option<&str>) -> String {
-        let mut output = String::new();
-        let labels_suffix = self.standard_labels.to_label_string();
-
-        if self.count > 0 {
-            output.push_str("# HELP container_processes_count Number of running processes\n");
-            output.push_str("# TYPE container_processes_count gauge\n");
-            output.push_str(&format!(
-                "container_processes_count{} {}\n",
-                labels_suffix, self.count
-            ));
+    fn write_prometheus(&self, out: &mut String, _sandbox_id: Option<&str>) {
+        let labels = self.standard_labels.writer();
+
+        for (name, help, value) in [
+            (
+                "container_processes_count",
+                "Number of running processes",
+                Some(self.count).filter(|count| *count > 0),
+            ),
+            (
+                "container_threads_count",
+                "Number of threads",
+                Some(self.thread_count).filter(|count| *count > 0),
+            ),
+            (
+                "container_threads_max_count",
+                "Maximum number of threads allowed",
+                self.thread_count_max,
+            ),
+            (
+                "container_file_descriptors",
+                "Number of open file descriptors",
+                Some(self.file_descriptors).filter(|count| *count > 0),
+            ),
+        ] {
+            labels.family(out, name, "gauge", help, value);
         }
 
-        if self.thread_count > 0 {
-            output.push_str("# HELP container_threads_count Number of threads\n");
-            output.push_str("# TYPE container_threads_count gauge\n");
-            output.push_str(&format!(
-                "container_threads_count{} {}\n",
-                labels_suffix, self.thread_count
-            ));
+        // Emit task state metrics if available
+        if !self.tasks_by_state.is_empty() {
+            write_header(
+                out,
+                "container_tasks_state",
+                "gauge",
+                "Number of tasks in each state",
+            );
+            for (state, count) in &self.tasks_by_state {
+                labels.sample(out, "container_tasks_state", &[("state", state)], count);
+            }
         }
+    }
+}
 
-        if let Some(max) = self.thread_count_max {
-            output
-                .push_str("# HELP container_threads_max_count Maximum number of threads allowed\n");
-            output.push_str("# TYPE container_threads_max_count gauge\n");
-            output.push_str(&format!(
-                "container_threads_max_count{} {}\n",
-                labels_suffix, max
-            ));
+impl PrometheusFormat for ShimMetrics {
+    fn write_prometheus(&self, out: &mut String, _sandbox_id: Option<&str>) {
+        let labels = self.standard_labels.writer();
+
+        for (name, help, value) in [
+            (
+                "kata_pod_shim_threads",
+                "Number of threads of the Kata shim",
+                self.threads,
+            ),
+            (
+                "kata_pod_shim_fds",
+                "Number of open file descriptors of the Kata shim",
+                self.fds,
+            ),
+            (
+                "kata_pod_shim_rss_bytes",
+                "Resident set size of the Kata shim in bytes",
+                self.rss_bytes,
+            ),
+        ] {
+            labels.family(out, name, "gauge", help, value);
         }
 
-        if self.file_descriptors > 0 {
-            output.push_str("# HELP container_file_descriptors Number of open file descriptors\n");
-            output.push_str("# TYPE container_file_descriptors gauge\n");
-            output.push_str(&format!(
-                "container_file_descriptors{} {}\n",
-                labels_suffix, self.file_descriptors
-            ));
+        if !self.rpc_durations.is_empty() {
+            write_header(
+                out,
+                "kata_pod_shim_rpc_duration_seconds",
+                "summary",
+                "Latency of RPCs from the Kata shim to the agent",
+            );
+            let mut actions: Vec<_> = self.rpc_durations.iter().collect();
+            actions.sort_by(|a, b| a.0.cmp(b.0));
+            let mut quantile_label = String::new();
+            for (action, latency) in actions {
+                for (quantile, seconds) in &latency.quantiles {
+                    quantile_label.clear();
+                    let _ = write!(quantile_label, "{}", quantile);
+                    labels.sample(
+                        out,
+                        "kata_pod_shim_rpc_duration_seconds",
+                        &[("action", action), ("quantile", &quantile_label)],
+                        seconds,
+                    );
+                }
+                labels.sample(
+                    out,
+                    "kata_pod_shim_rpc_duration_seconds_sum",
+                    &[("action", action)],
+                    latency.sum_seconds,
+                );
+                labels.sample(
+                    out,
+                    "kata_pod_shim_rpc_duration_seconds_count",
+                    &[("action", action)],
+                    latency.count,
+                );
+            }
         }
+    }
+}
 
-        // Emit task state metrics if available
-        if !self.tasks_by_state.is_empty() {
-            output.push_str("# HELP container_tasks_state Number of tasks in each state\n");
-            output.push_str("# TYPE container_tasks_state gauge\n");
-            for (state, count) in &self.tasks_by_state {
-                let state_labels = self
-                    .standard_labels
-                    .to_label_string_with_extras(&[("state", state)]);
-                output.push_str(&format!(
-                    "container_tasks_state{} {}\n",
-                    state_labels, count
-                ));
-            }
+impl PrometheusFormat for VirtiofsdMetrics {
+    fn write_prometheus(&self, out: &mut String, _sandbox_id: Option<&str>) {
+        let labels = self.standard_labels.writer();
+
+        for (name, metric_type, help, value) in [
+            (
+                "kata_pod_virtiofsd_threads",
+                "gauge",
+                "Number of threads of virtiofsd",
+                self.threads,
+            ),
+            (
+                "kata_pod_virtiofsd_fds",
+                "gauge",
+                "Number of open file descriptors of virtiofsd",
+                self.fds,
+            ),
+            (
+                "kata_pod_virtiofsd_read_bytes_total",
+                "counter",
+                "Bytes read by virtiofsd on behalf of the guest",
+                self.read_bytes_total,
+            ),
+            (
+                "kata_pod_virtiofsd_write_bytes_total",
+                "counter",
+                "Bytes written by virtiofsd on behalf of the guest",
+                self.write_bytes_total,
+            ),
+            (
+                "kata_pod_virtiofsd_read_ops_total",
+                "counter",
+                "Read syscalls issued by virtiofsd on behalf of the guest",
+                self.read_ops_total,
+            ),
+            (
+                "kata_pod_virtiofsd_write_ops_total",
+                "counter",
+                "Write syscalls issued by virtiofsd on behalf of the guest",
+                self.write_ops_total,
+            ),
+        ] {
+            labels.family(out, name, metric_type, help, value);
         }
+    }
+}
 
-        output
+impl PrometheusFormat for OverheadMetrics {
+    fn write_prometheus(&self, out: &mut String, _sandbox_id: Option<&str>) {
+        let labels = self.standard_labels.writer();
+
+        labels.family(
+            out,
+            "kata_overhead_cpu_seconds_total",
+            "counter",
+            "CPU time of the sandbox's shim and hypervisor on the host",
+            self.cpu_seconds_total,
+        );
+        labels.family(
+            out,
+            "kata_overhead_memory_bytes",
+            "gauge",
+            "Memory of the sandbox's shim and hypervisor on the host",
+            self.memory_bytes,
+        );
+    }
+}
+
+impl PrometheusFormat for VmmMetrics {
+    fn write_prometheus(&self, out: &mut String, _sandbox_id: Option<&str>) {
+        let labels = self.standard_labels.writer();
+
+        labels.family(
+            out,
+            "kata_vmm_rss_bytes",
+            "gauge",
+            "Resident set size of the sandbox's hypervisor process",
+            self.rss_bytes,
+        );
+        labels.family(
+            out,
+            "kata_vmm_cpu_seconds_total",
+            "counter",
+            "CPU time of the sandbox's hypervisor process",
+            self.cpu_seconds_total,
+        );
+        labels.family(
+            out,
+            "kata_vmm_fds",
+            "gauge",
+            "Open file descriptors of the sandbox's hypervisor process",
+            self.fds,
+        );
     }
 }
 
 impl PrometheusFormat for CadvisorMetrics {
-    fn to_prometheus_format(&self, sandbox_id: Option<&str>) -> String {
-        let mut output = String::new();
-        output.push_str(&self.cpu.to_prometheus_format(sandbox_id));
-        output.push_str(&self.memory.to_prometheus_format(sandbox_id));
-        output.push_str(&self.network.to_prometheus_format(sandbox_id));
-        output.push_str(&self.disk.to_prometheus_format(sandbox_id));
-        output.push_str(&self.process.to_prometheus_format(sandbox_id));
-        output
+    fn write_prometheus(&self, out: &mut String, sandbox_id: Option<&str>) {
+        self.cpu.write_prometheus(out, sandbox_id);
+        self.memory.write_prometheus(out, sandbox_id);
+        self.network.write_prometheus(out, sandbox_id);
+        self.disk.write_prometheus(out, sandbox_id);
+        self.process.write_prometheus(out, sandbox_id);
+        self.shim.write_prometheus(out, sandbox_id);
+        self.virtiofsd.write_prometheus(out, sandbox_id);
+        self.overhead.write_prometheus(out, sandbox_id);
+        self.vmm.write_prometheus(out, sandbox_id);
+    }
+
+    fn estimated_len(&self) -> usize {
+        let samples = 40
+            + self.memory.failures.len()
+            + 4 * self.network.per_interface.len()
+            + 2 * self.disk.per_device.len()
+            + self.process.tasks_by_state.len()
+            + self
+                .shim
+                .rpc_durations
+                .values()
+                .map(|latency| latency.quantiles.len() + 2)
+                .sum::<usize>();
+        // Every sample repeats the standard labels; headers are ~100 bytes per family
+        samples * (self.cpu.standard_labels.estimated_len() + 96) + 40 * 100
     }
 }
 
@@ -748,7 +1050,7 @@ mod tests {
                     fifteen_minute: 1.0,
                 }),
                 per_cpu: Default::default(),
-                standard_labels: StandardLabels::default(),
+                standard_labels: Default::default(),
             },
             memory: MemoryMetrics {
                 usage_bytes: 1024 * 1024 * 512, // 512 MB
@@ -758,7 +1060,7 @@ mod tests {
                 swap_bytes: Some(0),
                 mapped_file_bytes: None,
                 failures: HashMap::new(),
-                standard_labels: StandardLabels::default(),
+                ..Default::default()
             },
             network: Default::default(),
             disk: Default::default(),
@@ -768,13 +1070,22 @@ mod tests {
                 thread_count_max: Some(256),
                 file_descriptors: 256,
                 tasks_by_state: HashMap::new(),
-                standard_labels: StandardLabels::default(),
+                standard_labels: Default::default(),
             },
+            ..Default::default()
         };
 
         assert_eq!(metrics.cpu.usage_seconds_total, 100.0);
         assert_eq!(metrics.memory.usage_bytes, 1024 * 1024 * 512);
         assert_eq!(metrics.process.count, 42);
+
+        // Converted metrics survive a JSON roundtrip (snapshots, golden files)
+        let json = serde_json::to_string(&metrics).unwrap();
+        let restored: CadvisorMetrics = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.to_prometheus_format(None),
+            metrics.to_prometheus_format(None)
+        );
     }
 
     #[test]
@@ -801,14 +1112,15 @@ mod tests {
                 fifteen_minute: 1.0,
             }),
             per_cpu: Default::default(),
-            standard_labels: StandardLabels {
+            standard_labels: Arc::new(StandardLabels {
                 container: "".to_string(),
                 id: "test-pod".to_string(),
                 image: "".to_string(),
                 name: "test-pod".to_string(),
                 namespace: "default".to_string(),
                 pod: "test-pod".to_string(),
-            },
+                pod_labels: Vec::new(),
+            }),
         };
 
         let output = cpu.to_prometheus_format(Some("test-pod"));
@@ -832,7 +1144,7 @@ mod tests {
             swap_bytes: Some(0),
             mapped_file_bytes: None,
             failures: HashMap::new(),
-            standard_labels: StandardLabels::default(),
+            ..Default::default()
         };
 
         let output = memory.to_prometheus_format(None);
@@ -854,7 +1166,7 @@ mod tests {
             receive_packets_dropped_total: None,
             transmit_packets_dropped_total: None,
             per_interface: Default::default(),
-            standard_labels: StandardLabels::default(),
+            standard_labels: Default::default(),
         };
 
         let output = network.to_prometheus_format(Some("sandbox-1"));
@@ -876,7 +1188,7 @@ mod tests {
             io_time_seconds_total: None,
             io_time_weighted_seconds_total: None,
             per_device: Default::default(),
-            standard_labels: StandardLabels::default(),
+            standard_labels: Default::default(),
         };
 
         let output = disk.to_prometheus_format(None);
@@ -894,18 +1206,20 @@ mod tests {
             thread_count_max: Some(256),
             file_descriptors: 512,
             tasks_by_state: HashMap::new(),
-            standard_labels: StandardLabels {
+            standard_labels: Arc::new(StandardLabels {
                 container: "".to_string(),
                 id: "app-pod".to_string(),
                 image: "".to_string(),
                 name: "app-pod".to_string(),
                 namespace: "default".to_string(),
                 pod: "app-pod".to_string(),
-            },
+                pod_labels: vec![("label_team".to_string(), "payments".to_string())],
+            }),
         };
 
         let output = process.to_prometheus_format(Some("app-pod"));
         assert!(output.contains("container_processes_count"));
+        assert!(output.contains(r#"pod="app-pod",label_team="payments"}"#));
         assert!(output.contains("42"));
         assert!(output.contains("container_threads_count"));
         assert!(output.contains("128"));
@@ -925,7 +1239,7 @@ mod tests {
                 system_seconds_total: 20.0,
                 load_average: None,
                 per_cpu: Default::default(),
-                standard_labels: StandardLabels::default(),
+                standard_labels: Default::default(),
             },
             memory: MemoryMetrics {
                 usage_bytes: 1073741824,
@@ -935,7 +1249,7 @@ mod tests {
                 swap_bytes: None,
                 mapped_file_bytes: None,
                 failures: HashMap::new(),
-                standard_labels: StandardLabels::default(),
+                ..Default::default()
             },
             network: NetworkMetrics {
                 receive_bytes_total: 5000000,
@@ -947,7 +1261,7 @@ mod tests {
                 receive_packets_dropped_total: None,
                 transmit_packets_dropped_total: None,
                 per_interface: Default::default(),
-                standard_labels: StandardLabels::default(),
+                standard_labels: Default::default(),
             },
             disk: DiskMetrics {
                 reads_total: 5000,
@@ -959,7 +1273,7 @@ mod tests {
                 io_time_seconds_total: None,
                 io_time_weighted_seconds_total: None,
                 per_device: Default::default(),
-                standard_labels: StandardLabels::default(),
+                standard_labels: Default::default(),
             },
             process: ProcessMetrics {
                 count: 25,
@@ -967,8 +1281,9 @@ mod tests {
                 thread_count_max: Some(512),
                 file_descriptors: 256,
                 tasks_by_state: HashMap::new(),
-                standard_labels: StandardLabels::default(),
+                standard_labels: Default::default(),
             },
+            ..Default::default()
         };
 
         let output = metrics.to_prometheus_format(Some("test-sandbox"));
@@ -987,4 +1302,50 @@ mod tests {
         assert!(output.contains("5000")); // Disk reads
         assert!(output.contains("25")); // Process count
     }
+
+    #[test]
+    fn test_shim_metrics_prometheus_format() {
+        let mut shim = ShimMetrics {
+            threads: Some(12),
+            fds: Some(34),
+            rss_bytes: None,
+            rpc_durations: HashMap::new(),
+            standard_labels: Arc::new(StandardLabels::new("uid-1", "app-pod", "default")),
+        };
+        shim.rpc_durations.insert(
+            "CreateContainer".to_string(),
+            RpcLatency {
+                count: 2,
+                sum_seconds: 0.5,
+                quantiles: vec![(0.99, 0.4)],
+            },
+        );
+
+        let output = shim.to_prometheus_format(Some("app-pod"));
+        assert!(output.contains(r#"kata_pod_shim_threads{container="kata",id="uid-1",image="unknown",name="app-pod",namespace="default",pod="app-pod"} 12"#));
+        assert!(output.contains("kata_pod_shim_fds{"));
+        assert!(!output.contains("kata_pod_shim_rss_bytes"));
+        assert!(output.contains(r#"action="CreateContainer"} 0.5"#));
+        assert!(output.contains(r#"action="CreateContainer",quantile="0.99"} 0.4"#));
+        assert!(output.contains("kata_pod_shim_rpc_duration_seconds_count{"));
+        assert!(!output.contains("container_"));
+    }
+
+    #[test]
+    fn test_virtiofsd_metrics_prometheus_format() {
+        let virtiofsd = VirtiofsdMetrics {
+            threads: Some(8),
+            read_bytes_total: Some(4096),
+            standard_labels: Arc::new(StandardLabels::new("uid-1", "app-pod", "default")),
+            ..Default::default()
+        };
+
+        let output = virtiofsd.to_prometheus_format(Some("app-pod"));
+        assert!(output.contains("# TYPE kata_pod_virtiofsd_read_bytes_total counter"));
+        assert!(output.contains(r#"kata_pod_virtiofsd_threads{container="kata",id="uid-1",image="unknown",name="app-pod",namespace="default",pod="app-pod"} 8"#));
+        assert!(!output.contains("kata_pod_virtiofsd_fds"));
+        assert!(VirtiofsdMetrics::default()
+            .to_prometheus_format(None)
+            .is_empty());
+    }
 }
@@ -3,12 +3,15 @@
 //! Converts Kata/Cloud Hypervisor Prometheus metrics to cAdvisor format.
 //! Implements the metric mappings documented in KATA_TO_CADVISOR_MAPPING.md
 
+use crate::monitor::host_cgroup::{OVERHEAD_CPU_METRIC, OVERHEAD_MEMORY_METRIC};
+use crate::monitor::hypervisor_proc::{VMM_CPU_METRIC, VMM_FDS_METRIC, VMM_RSS_METRIC};
 use crate::utils::metrics_converter::cadvisor::{
-    DeviceMetrics, InterfaceMetrics, LoadAverage, StandardLabels,
+    DeviceMetrics, InterfaceMetrics, LoadAverage, RpcLatency, StandardLabels,
 };
-use crate::utils::metrics_converter::config::{ConversionConfig, LabelEnricher};
+use crate::utils::metrics_converter::config::{ConversionConfig, EnrichedLabels};
 use crate::utils::metrics_converter::{
-    CpuMetrics, DiskMetrics, MemoryMetrics, MetricsConverter, NetworkMetrics, ProcessMetrics,
+    CpuMetrics, DiskMetrics, MemoryMetrics, MetricsConverter, NetworkMetrics, OverheadMetrics,
+    ProcessMetrics, ShimMetrics, VirtiofsdMetrics, VmmMetrics,
 };
 use crate::utils::prometheus_parser::PrometheusMetrics;
 use anyhow::Result;
@@ -16,41 +19,35 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tracing::debug;
 
+/// Quantiles estimated from the shim RPC duration histogram buckets
+const RPC_LATENCY_QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
 /// Cloud Hypervisor metrics converter
 ///
 /// Converts Kata metrics (from Cloud Hypervisor) to cAdvisor-compatible format.
 pub struct CloudHypervisorConverter {
     config: ConversionConfig,
-    label_enricher: Option<Arc<dyn LabelEnricher>>,
-    sandbox_id: Option<String>,
+    /// cAdvisor labels of the sandbox, shared by every converted category
+    standard_labels: Arc<StandardLabels>,
 }
 
 impl CloudHypervisorConverter {
-    /// Create a new converter with label enricher and sandbox ID
-    pub fn with_enricher(
-        config: ConversionConfig,
-        label_enricher: Arc<dyn LabelEnricher>,
-        sandbox_id: String,
-    ) -> Self {
+    /// Create a new converter for a sandbox with its resolved pod labels
+    ///
+    /// A per-sandbox guest CLK_TCK in the labels replaces the global
+    /// `cpu_jiffy_conversion_factor`.
+    pub fn with_labels(mut config: ConversionConfig, labels: EnrichedLabels) -> Self {
+        if let Some(clk_tck) = labels.clk_tck {
+            config.cpu_jiffy_conversion_factor = clk_tck;
+        }
+        let standard_labels = Arc::new(
+            StandardLabels::new(labels.pod_uid, labels.pod_name, labels.pod_namespace)
+                .with_image(labels.cri_annotations.image_name)
+                .with_pod_labels(labels.pod_labels),
+        );
         Self {
             config,
-            label_enricher: Some(label_enricher),
-            sandbox_id: Some(sandbox_id),
-        }
-    }
-
-    /// Create standard cAdvisor labels from CRI enricher metadata
-    fn create_standard_labels(&self) -> StandardLabels {
-        // Get enriched labels from CRI enricher if available
-        if let (Some(enricher), Some(ref sandbox_id)) = (&self.label_enricher, &self.sandbox_id) {
-            let enriched = enricher.enrich(sandbox_id);
-            StandardLabels::new(
-                &enriched.pod_uid,
-                &enriched.pod_name,
-                &enriched.pod_namespace,
-            )
-        } else {
-            StandardLabels::new("", "", "")
+            standard_labels,
         }
     }
 }
@@ -66,9 +63,9 @@ impl MetricsConverter for CloudHypervisorConverter {
                 continue;
             }
 
-            for sample in &metric.samples {
-                let cpu = sample.labels.get("cpu").map(|s| s.as_str());
-                let item = sample.labels.get("item").map(|s| s.as_str());
+            for sample in metric.finite_samples() {
+                let cpu = sample.labels.get("cpu").map(|s| s.as_ref());
+                let item = sample.labels.get("item").map(|s| s.as_ref());
                 let value = sample.value;
 
                 // Only use the pre-aggregated cpu="total" values
@@ -102,7 +99,7 @@ impl MetricsConverter for CloudHypervisorConverter {
         }
 
         // Populate standard labels with CRI metadata during conversion
-        cpu_metrics.standard_labels = self.create_standard_labels();
+        cpu_metrics.standard_labels = self.standard_labels.clone();
 
         Ok(cpu_metrics)
     }
@@ -119,9 +116,9 @@ impl MetricsConverter for CloudHypervisorConverter {
                 continue;
             }
 
-            for sample in &metric.samples {
+            for sample in metric.finite_samples() {
                 if let Some(item) = sample.labels.get("item") {
-                    meminfo.insert(item.clone(), sample.value as u64);
+                    meminfo.insert(item.to_string(), sample.value as u64);
                 }
             }
         }
@@ -160,8 +157,11 @@ impl MetricsConverter for CloudHypervisorConverter {
             memory_metrics.mapped_file_bytes = Some(mapped);
         }
 
+        // Memory the guest has now: changes with hotplug and ballooning
+        memory_metrics.guest_total_bytes = meminfo.get("memtotal").copied();
+
         // Populate standard labels with CRI metadata during conversion
-        memory_metrics.standard_labels = self.create_standard_labels();
+        memory_metrics.standard_labels = self.standard_labels.clone();
 
         Ok(memory_metrics)
     }
@@ -178,9 +178,9 @@ impl MetricsConverter for CloudHypervisorConverter {
                 continue;
             }
 
-            for sample in &metric.samples {
+            for sample in metric.finite_samples() {
                 let interface = match sample.labels.get("interface") {
-                    Some(iface) => iface.clone(),
+                    Some(iface) => iface.to_string(),
                     None => continue,
                 };
 
@@ -189,7 +189,7 @@ impl MetricsConverter for CloudHypervisorConverter {
                     continue;
                 }
 
-                let item = sample.labels.get("item").map(|s| s.as_str());
+                let item = sample.labels.get("item").map(|s| s.as_ref());
                 let value = sample.value as u64;
 
                 let iface_metrics = interfaces.entry(interface.clone()).or_default();
@@ -244,7 +244,7 @@ impl MetricsConverter for CloudHypervisorConverter {
         }
 
         // Populate standard labels with CRI metadata during conversion
-        network_metrics.standard_labels = self.create_standard_labels();
+        network_metrics.standard_labels = self.standard_labels.clone();
 
         Ok(network_metrics)
     }
@@ -261,17 +261,29 @@ impl MetricsConverter for CloudHypervisorConverter {
                 continue;
             }
 
-            for sample in &metric.samples {
+            for sample in metric.finite_samples() {
                 let disk = match sample.labels.get("disk") {
-                    Some(d) => d.clone(),
+                    Some(d) => d.to_string(),
                     None => continue,
                 };
 
-                let item = sample.labels.get("item").map(|s| s.as_str());
+                // Filter devices: loop and pmem devices are not the pod's disks
+                if !self.config.matches_disk_device(&disk) {
+                    continue;
+                }
+
+                let item = sample.labels.get("item").map(|s| s.as_ref());
                 let value = sample.value;
 
                 let device_metrics = devices.entry(disk.clone()).or_default();
                 device_metrics.device = disk;
+                // Agents that export the device numbers label the samples with them
+                if let (Some(major), Some(minor)) =
+                    (sample.labels.get("major"), sample.labels.get("minor"))
+                {
+                    device_metrics.major = major.to_string();
+                    device_metrics.minor = minor.to_string();
+                }
 
                 match item {
                     Some("reads") => {
@@ -325,11 +337,20 @@ impl MetricsConverter for CloudHypervisorConverter {
         }
 
         if self.config.include_per_device {
+            // Otherwise the numbers come from the guest block device map
+            for device in devices.values_mut() {
+                if device.major.is_empty() {
+                    if let Some((major, minor)) = self.config.block_device_numbers(&device.device) {
+                        device.major = major.to_string();
+                        device.minor = minor.to_string();
+                    }
+                }
+            }
             disk_metrics.per_device = devices;
         }
 
         // Populate standard labels with CRI metadata during conversion
-        disk_metrics.standard_labels = self.create_standard_labels();
+        disk_metrics.standard_labels = self.standard_labels.clone();
 
         Ok(disk_metrics)
     }
@@ -345,8 +366,8 @@ impl MetricsConverter for CloudHypervisorConverter {
                 continue;
             }
 
-            for sample in &metric.samples {
-                let item = sample.labels.get("item").map(|s| s.as_str());
+            for sample in metric.finite_samples() {
+                let item = sample.labels.get("item").map(|s| s.as_ref());
                 let value = sample.value as u64;
 
                 match item {
@@ -366,7 +387,7 @@ impl MetricsConverter for CloudHypervisorConverter {
                     || metric.name.contains("virtiofsd"));
 
             if should_count {
-                for sample in &metric.samples {
+                for sample in metric.finite_samples() {
                     process_metrics.thread_count += sample.value as u64;
                 }
             }
@@ -381,17 +402,168 @@ impl MetricsConverter for CloudHypervisorConverter {
                     || metric.name.contains("virtiofsd"));
 
             if should_count {
-                for sample in &metric.samples {
+                for sample in metric.finite_samples() {
                     process_metrics.file_descriptors += sample.value as u64;
                 }
             }
         }
 
         // Populate standard labels with CRI metadata during conversion
-        process_metrics.standard_labels = self.create_standard_labels();
+        process_metrics.standard_labels = self.standard_labels.clone();
 
         Ok(process_metrics)
     }
+
+    fn convert_shim(&self, metrics: &PrometheusMetrics) -> Result<ShimMetrics> {
+        debug!("Converting shim metrics");
+
+        let mut shim_metrics = ShimMetrics {
+            threads: sample_value(metrics, "kata_shim_threads").map(|v| v as u64),
+            fds: sample_value(metrics, "kata_shim_fds").map(|v| v as u64),
+            ..Default::default()
+        };
+
+        for metric in metrics.metrics.values() {
+            match metric.name.as_str() {
+                "kata_shim_proc_status" => {
+                    // VmRSS is reported in bytes
+                    shim_metrics.rss_bytes = metric
+                        .finite_samples()
+                        .find(|s| s.labels.get("item").map(|i| i.as_ref()) == Some("vmrss"))
+                        .map(|s| s.value as u64);
+                }
+                "kata_shim_rpc_durations_histogram_milliseconds" => {
+                    // Older runtimes expose the RPC durations as a summary
+                    let series = if metric.metric_type.as_deref() == Some("summary") {
+                        metric
+                            .summaries()
+                            .into_iter()
+                            .map(|summary| {
+                                (
+                                    summary.labels,
+                                    summary.sum,
+                                    summary.count,
+                                    summary.quantiles,
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                    } else {
+                        metric
+                            .histograms()
+                            .into_iter()
+                            .map(|histogram| {
+                                let quantiles = RPC_LATENCY_QUANTILES
+                                    .iter()
+                                    .filter_map(|&q| histogram.quantile(q).map(|v| (q, v)))
+                                    .collect();
+                                (histogram.labels, histogram.sum, histogram.count, quantiles)
+                            })
+                            .collect()
+                    };
+
+                    for (labels, sum, count, quantiles) in series {
+                        let (Some(action), Some(sum), Some(count)) =
+                            (labels.get("action"), sum, count)
+                        else {
+                            continue;
+                        };
+                        if !sum.is_finite() || !count.is_finite() {
+                            continue;
+                        }
+                        // Convert milliseconds to seconds
+                        shim_metrics.rpc_durations.insert(
+                            action.to_string(),
+                            RpcLatency {
+                                count: count as u64,
+                                sum_seconds: sum / 1000.0,
+                                quantiles: quantiles
+                                    .into_iter()
+                                    .map(|(q, ms)| (q, ms / 1000.0))
+                                    .collect(),
+                            },
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Populate standard labels with CRI metadata during conversion
+        shim_metrics.standard_labels = self.standard_labels.clone();
+
+        Ok(shim_metrics)
+    }
+
+    fn convert_virtiofsd(&self, metrics: &PrometheusMetrics) -> Result<VirtiofsdMetrics> {
+        if !self.config.include_virtiofsd {
+            return Ok(VirtiofsdMetrics::default());
+        }
+
+        debug!("Converting virtiofsd metrics");
+
+        let mut virtiofsd_metrics = VirtiofsdMetrics {
+            threads: sample_value(metrics, "kata_virtiofsd_threads").map(|v| v as u64),
+            fds: sample_value(metrics, "kata_virtiofsd_fds").map(|v| v as u64),
+            ..Default::default()
+        };
+
+        if let Some(metric) = metrics.metrics.get("kata_virtiofsd_io_stat") {
+            for sample in metric.finite_samples() {
+                let value = Some(sample.value as u64);
+                match sample.labels.get("item").map(|s| s.as_ref()) {
+                    Some("rchar") => virtiofsd_metrics.read_bytes_total = value,
+                    Some("wchar") => virtiofsd_metrics.write_bytes_total = value,
+                    Some("syscr") => virtiofsd_metrics.read_ops_total = value,
+                    Some("syscw") => virtiofsd_metrics.write_ops_total = value,
+                    _ => {}
+                }
+            }
+        }
+
+        // Populate standard labels with CRI metadata during conversion
+        virtiofsd_metrics.standard_labels = self.standard_labels.clone();
+
+        Ok(virtiofsd_metrics)
+    }
+
+    fn convert_overhead(&self, metrics: &PrometheusMetrics) -> Result<OverheadMetrics> {
+        debug!("Converting overhead metrics");
+
+        // Attached by the collector from the host cgroup, not reported by the shim
+        Ok(OverheadMetrics {
+            cpu_seconds_total: sample_value(metrics, OVERHEAD_CPU_METRIC),
+            memory_bytes: sample_value(metrics, OVERHEAD_MEMORY_METRIC).map(|v| v as u64),
+            // Populate standard labels with CRI metadata during conversion
+            standard_labels: self.standard_labels.clone(),
+        })
+    }
+
+    fn convert_vmm(&self, metrics: &PrometheusMetrics) -> Result<VmmMetrics> {
+        debug!("Converting VMM metrics");
+
+        // Attached by the collector from /proc, not reported by the shim
+        Ok(VmmMetrics {
+            rss_bytes: sample_value(metrics, VMM_RSS_METRIC).map(|v| v as u64),
+            cpu_seconds_total: sample_value(metrics, VMM_CPU_METRIC),
+            fds: sample_value(metrics, VMM_FDS_METRIC).map(|v| v as u64),
+            // Populate standard labels with CRI metadata during conversion
+            standard_labels: self.standard_labels.clone(),
+        })
+    }
+}
+
+/// Value of the first finite sample named `name`
+///
+/// Only families whose name prefixes `name` are searched, so the `_total`
+/// suffix of counters may or may not be part of the family name.
+fn sample_value(metrics: &PrometheusMetrics, name: &str) -> Option<f64> {
+    metrics
+        .metrics
+        .values()
+        .filter(|metric| name.starts_with(metric.name.as_str()))
+        .flat_map(|metric| metric.finite_samples())
+        .find(|sample| sample.name == name)
+        .map(|sample| sample.value)
 }
 
 impl CloudHypervisorConverter {
@@ -404,7 +576,7 @@ impl CloudHypervisorConverter {
                 continue;
             }
 
-            for sample in &metric.samples {
+            for sample in metric.finite_samples() {
                 if let Some(item) = sample.labels.get("item") {
                     loads.insert(item.clone(), sample.value);
                 }
@@ -428,7 +600,7 @@ mod tests {
     use super::*;
     use crate::utils::metrics_converter::cadvisor::PrometheusFormat;
     use crate::utils::metrics_converter::config::EnrichedLabels;
-    use crate::utils::metrics_converter::CRILabelEnricher;
+    use crate::utils::metrics_converter::{CRILabelEnricher, LabelEnricher};
     use crate::utils::prometheus_parser::{MetricSample, PrometheusMetrics};
 
     #[test]
@@ -439,8 +611,10 @@ mod tests {
             .entry("kata_guest_cpu_time".to_string())
             .or_insert_with(|| crate::utils::prometheus_parser::PrometheusMetric {
                 name: "kata_guest_cpu_time".to_string(),
+                family: None,
                 metric_type: Some("gauge".to_string()),
                 help: None,
+                unit: None,
                 samples: vec![],
             });
 
@@ -450,8 +624,8 @@ mod tests {
             name: "kata_guest_cpu_time".to_string(),
             labels: {
                 let mut map = HashMap::new();
-                map.insert("cpu".to_string(), "total".to_string());
-                map.insert("item".to_string(), "user".to_string());
+                map.insert("cpu".into(), "total".into());
+                map.insert("item".into(), "user".into());
                 map
             },
             value: 56160.0,
@@ -462,21 +636,16 @@ mod tests {
             name: "kata_guest_cpu_time".to_string(),
             labels: {
                 let mut map = HashMap::new();
-                map.insert("cpu".to_string(), "total".to_string());
-                map.insert("item".to_string(), "system".to_string());
+                map.insert("cpu".into(), "total".into());
+                map.insert("item".into(), "system".into());
                 map
             },
             value: 82060.0,
             timestamp: None,
         });
 
-        let cache = Arc::new(crate::monitor::sandbox_cache::SandboxCache::new());
-        let enricher = Arc::new(CRILabelEnricher::new(cache));
-        let converter = CloudHypervisorConverter::with_enricher(
-            ConversionConfig::default(),
-            enricher,
-            "test-sandbox".to_string(),
-        );
+        let labels = EnrichedLabels::default();
+        let converter = CloudHypervisorConverter::with_labels(ConversionConfig::default(), labels);
         let cpu_metrics = converter.convert_cpu(&metrics).unwrap();
 
         // (56160 + 82060) / 100 = 1382.2 seconds (jiffies from /proc/stat with USER_HZ=100)
@@ -485,6 +654,42 @@ mod tests {
         assert_eq!(cpu_metrics.system_seconds_total, 820.6);
     }
 
+    #[tokio::test]
+    async fn test_cpu_conversion_with_sandbox_clk_tck() {
+        let metrics = PrometheusMetrics::parse(
+            "kata_guest_cpu_time{cpu=\"total\",item=\"user\"} 5000\n\
+             kata_guest_cpu_time{cpu=\"total\",item=\"system\"} 2500\n",
+        )
+        .unwrap();
+        let cache = Arc::new(crate::monitor::sandbox_cache::SandboxCache::new());
+        cache
+            .set_cri_metadata(
+                "sandbox-250hz",
+                crate::monitor::sandbox_cache::SandboxCRIMetadata {
+                    clk_tck: Some(250.0),
+                    ..Default::default()
+                },
+            )
+            .await;
+        let enricher: Arc<dyn LabelEnricher> = Arc::new(CRILabelEnricher::new(cache));
+        let config = ConversionConfig {
+            cpu_jiffy_conversion_factor: 100.0,
+            ..Default::default()
+        };
+
+        let labels = enricher.enrich("sandbox-250hz").await;
+        let converter = CloudHypervisorConverter::with_labels(config.clone(), labels);
+        let cpu_metrics = converter.convert_cpu(&metrics).unwrap();
+        assert_eq!(cpu_metrics.usage_seconds_total, 30.0);
+        assert_eq!(cpu_metrics.user_seconds_total, 20.0);
+
+        // Sandboxes without the annotation keep the global factor
+        let labels = enricher.enrich("other").await;
+        let converter = CloudHypervisorConverter::with_labels(config, labels);
+        let cpu_metrics = converter.convert_cpu(&metrics).unwrap();
+        assert_eq!(cpu_metrics.usage_seconds_total, 75.0);
+    }
+
     #[test]
     fn test_memory_conversion() {
         let mut metrics = PrometheusMetrics::new();
@@ -493,8 +698,10 @@ mod tests {
             .entry("kata_guest_meminfo".to_string())
             .or_insert_with(|| crate::utils::prometheus_parser::PrometheusMetric {
                 name: "kata_guest_meminfo".to_string(),
+                family: None,
                 metric_type: Some("gauge".to_string()),
                 help: None,
+                unit: None,
                 samples: vec![],
             });
 
@@ -503,7 +710,7 @@ mod tests {
             name: "kata_guest_meminfo".to_string(),
             labels: {
                 let mut map = HashMap::new();
-                map.insert("item".to_string(), "memtotal".to_string());
+                map.insert("item".into(), "memtotal".into());
                 map
             },
             value: 1000.0,
@@ -514,20 +721,15 @@ mod tests {
             name: "kata_guest_meminfo".to_string(),
             labels: {
                 let mut map = HashMap::new();
-                map.insert("item".to_string(), "memfree".to_string());
+                map.insert("item".into(), "memfree".into());
                 map
             },
             value: 400.0,
             timestamp: None,
         });
 
-        let cache = Arc::new(crate::monitor::sandbox_cache::SandboxCache::new());
-        let enricher = Arc::new(CRILabelEnricher::new(cache));
-        let converter = CloudHypervisorConverter::with_enricher(
-            ConversionConfig::default(),
-            enricher,
-            "test-sandbox".to_string(),
-        );
+        let labels = EnrichedLabels::default();
+        let converter = CloudHypervisorConverter::with_labels(ConversionConfig::default(), labels);
         let mem_metrics = converter.convert_memory(&metrics).unwrap();
 
         // 1000 - 400 = 600
@@ -535,29 +737,118 @@ mod tests {
     }
 
     #[test]
-    fn test_interface_filtering() {
-        let config = ConversionConfig::default();
-        assert!(config.matches_network_interface("eth0"));
-        assert!(!config.matches_network_interface("lo"));
+    fn test_guest_total_conversion() {
+        let content = r#"kata_guest_meminfo{item="memtotal"} 3221225472
+kata_guest_meminfo{item="memfree"} 1073741824
+"#;
+        let metrics = PrometheusMetrics::parse(content).unwrap();
+        let converter = CloudHypervisorConverter::with_labels(
+            ConversionConfig::default(),
+            pod_labels("my-pod", "default", "12345-67890"),
+        );
+        let memory = converter.convert_memory(&metrics).unwrap();
+        assert_eq!(memory.guest_total_bytes, Some(3221225472));
+
+        let output = memory.to_prometheus_format(None);
+        assert!(output.contains("# TYPE container_memory_guest_total_bytes gauge"));
+        assert!(output.contains(r#"container_memory_guest_total_bytes{container="kata",id="12345-67890",image="unknown",name="my-pod",namespace="default",pod="my-pod"} 3221225472"#));
+
+        // Without meminfo the guest total is unknown
+        let metrics = PrometheusMetrics::parse("").unwrap();
+        let memory = converter.convert_memory(&metrics).unwrap();
+        assert_eq!(memory.guest_total_bytes, None);
+        assert!(!memory
+            .to_prometheus_format(None)
+            .contains("container_memory_guest_total_bytes"));
     }
 
-    // Mock label enricher for testing
-    struct MockLabelEnricher {
-        enriched_labels: EnrichedLabels,
+    #[test]
+    fn test_disk_device_numbers() {
+        let config = ConversionConfig {
+            include_per_device: true,
+            ..Default::default()
+        };
+        // Real agent payload: only the disk name, whose dynamic major is unknown by default
+        let metrics =
+            PrometheusMetrics::parse(include_str!("../../../tests/fixtures/shim/qemu.prom"))
+                .unwrap();
+        let converter = CloudHypervisorConverter::with_labels(
+            config.clone(),
+            pod_labels("my-pod", "default", "12345-67890"),
+        );
+        let disk = converter.convert_disk(&metrics).unwrap();
+        let vda = &disk.per_device["vda"];
+        assert_eq!((vda.major.as_str(), vda.minor.as_str()), ("", ""));
+
+        // Resolved from the device map with the configured major
+        let config = ConversionConfig {
+            guest_block_majors: vec!["vd=254".to_string()],
+            ..config
+        };
+        let converter = CloudHypervisorConverter::with_labels(
+            config,
+            pod_labels("my-pod", "default", "12345-67890"),
+        );
+        let disk = converter.convert_disk(&metrics).unwrap();
+        let vda = &disk.per_device["vda"];
+        assert_eq!((vda.major.as_str(), vda.minor.as_str()), ("254", "0"));
+        let vdb = &disk.per_device["vdb"];
+        assert_eq!((vdb.major.as_str(), vdb.minor.as_str()), ("254", "16"));
+        assert!(disk
+            .to_prometheus_format(None)
+            .contains(r#"device="vdb",major="254",minor="16",operation="Read"} 1200289"#));
+
+        // Device numbers labelled by the agent win over the device map
+        let content = r#"kata_guest_diskstat{disk="vda",major="253",minor="0",item="reads"} 10
+kata_guest_diskstat{disk="nvme0n1",item="reads"} 5
+"#;
+        let metrics = PrometheusMetrics::parse(content).unwrap();
+        let disk = converter.convert_disk(&metrics).unwrap();
+        assert_eq!(disk.per_device["vda"].major, "253");
+        // Dynamic major and no label: left empty
+        assert_eq!(disk.per_device["nvme0n1"].major, "");
     }
 
-    impl MockLabelEnricher {
-        fn new(pod_name: &str, namespace: &str, uid: &str) -> Self {
-            Self {
-                enriched_labels: EnrichedLabels::new(uid, pod_name, namespace),
-            }
-        }
+    #[test]
+    fn test_disk_device_filtering() {
+        let content = r#"kata_guest_diskstat{disk="vda",item="reads"} 100
+kata_guest_diskstat{disk="vda",item="sectors_read"} 8
+kata_guest_diskstat{disk="loop0",item="reads"} 5000
+kata_guest_diskstat{disk="pmem0",item="reads"} 700
+"#;
+        let metrics = PrometheusMetrics::parse(content).unwrap();
+        let converter = CloudHypervisorConverter::with_labels(
+            ConversionConfig {
+                include_per_device: true,
+                ..Default::default()
+            },
+            pod_labels("my-pod", "default", "12345-67890"),
+        );
+        let disk = converter.convert_disk(&metrics).unwrap();
+        assert_eq!(disk.reads_total, 100);
+        assert_eq!(disk.reads_bytes_total, 4096);
+        assert_eq!(disk.per_device.len(), 1);
+
+        let converter = CloudHypervisorConverter::with_labels(
+            ConversionConfig {
+                disk_device_patterns: vec!["vd.*".to_string(), "pmem0".to_string()],
+                ..Default::default()
+            },
+            pod_labels("my-pod", "default", "12345-67890"),
+        );
+        assert_eq!(converter.convert_disk(&metrics).unwrap().reads_total, 800);
     }
 
-    impl crate::utils::metrics_converter::config::LabelEnricher for MockLabelEnricher {
-        fn enrich(&self, _sandbox_id: &str) -> EnrichedLabels {
-            self.enriched_labels.clone()
-        }
+    #[test]
+    fn test_interface_filtering() {
+        let config = ConversionConfig::default();
+        assert!(config.matches_network_interface("eth0"));
+        assert!(!config.matches_network_interface("lo"));
+    }
+
+    // Pod labels for testing
+    fn pod_labels(pod_name: &str, namespace: &str, uid: &str) -> EnrichedLabels {
+        EnrichedLabels::new(uid, pod_name, namespace)
     }
 
     #[test]
@@ -568,8 +859,10 @@ mod tests {
             .entry("kata_guest_cpu_time".to_string())
             .or_insert_with(|| crate::utils::prometheus_parser::PrometheusMetric {
                 name: "kata_guest_cpu_time".to_string(),
+                family: None,
                 metric_type: Some("gauge".to_string()),
                 help: None,
+                unit: None,
                 samples: vec![],
             });
 
@@ -578,8 +871,8 @@ mod tests {
             name: "kata_guest_cpu_time".to_string(),
             labels: {
                 let mut map = HashMap::new();
-                map.insert("cpu".to_string(), "total".to_string());
-                map.insert("item".to_string(), "user".to_string());
+                map.insert("cpu".into(), "total".into());
+                map.insert("item".into(), "user".into());
                 map
             },
             value: 56160.0,
@@ -590,8 +883,8 @@ mod tests {
             name: "kata_guest_cpu_time".to_string(),
             labels: {
                 let mut map = HashMap::new();
-                map.insert("cpu".to_string(), "total".to_string());
-                map.insert("item".to_string(), "system".to_string());
+                map.insert("cpu".into(), "total".into());
+                map.insert("item".into(), "system".into());
                 map
             },
             value: 82060.0,
@@ -599,9 +892,8 @@ mod tests {
         });
 
         let config = ConversionConfig::default();
-        let enricher = Arc::new(MockLabelEnricher::new("my-pod", "default", "12345-67890"));
-        let converter =
-            CloudHypervisorConverter::with_enricher(config, enricher, "sandbox-123".to_string());
+        let labels = pod_labels("my-pod", "default", "12345-67890");
+        let converter = CloudHypervisorConverter::with_labels(config, labels);
 
         let cpu_metrics = converter.convert_cpu(&metrics).unwrap();
 
@@ -614,6 +906,13 @@ mod tests {
         assert_eq!(cpu_metrics.standard_labels.namespace, "default");
         assert_eq!(cpu_metrics.standard_labels.pod, "my-pod");
         assert_eq!(cpu_metrics.standard_labels.id, "12345-67890"); // pod_uid from enricher
+
+        // The labels are built once per converter and shared by every category
+        let memory_metrics = converter.convert_memory(&metrics).unwrap();
+        assert!(Arc::ptr_eq(
+            &cpu_metrics.standard_labels,
+            &memory_metrics.standard_labels
+        ));
     }
 
     #[test]
@@ -624,8 +923,10 @@ mod tests {
             .entry("kata_guest_meminfo".to_string())
             .or_insert_with(|| crate::utils::prometheus_parser::PrometheusMetric {
                 name: "kata_guest_meminfo".to_string(),
+                family: None,
                 metric_type: Some("gauge".to_string()),
                 help: None,
+                unit: None,
                 samples: vec![],
             });
 
@@ -634,7 +935,7 @@ mod tests {
             name: "kata_guest_meminfo".to_string(),
             labels: {
                 let mut map = HashMap::new();
-                map.insert("item".to_string(), "memtotal".to_string());
+                map.insert("item".into(), "memtotal".into());
                 map
             },
             value: 1000.0,
@@ -645,7 +946,7 @@ mod tests {
             name: "kata_guest_meminfo".to_string(),
             labels: {
                 let mut map = HashMap::new();
-                map.insert("item".to_string(), "memfree".to_string());
+                map.insert("item".into(), "memfree".into());
                 map
             },
             value: 400.0,
@@ -653,13 +954,8 @@ mod tests {
         });
 
         let config = ConversionConfig::default();
-        let enricher = Arc::new(MockLabelEnricher::new(
-            "test-app",
-            "production",
-            "abc-123-def",
-        ));
-        let converter =
-            CloudHypervisorConverter::with_enricher(config, enricher, "sandbox-xyz".to_string());
+        let labels = pod_labels("test-app", "production", "abc-123-def");
+        let converter = CloudHypervisorConverter::with_labels(config, labels);
 
         let mem_metrics = converter.convert_memory(&metrics).unwrap();
 
@@ -680,8 +976,10 @@ mod tests {
             .entry("kata_guest_cpu_time".to_string())
             .or_insert_with(|| crate::utils::prometheus_parser::PrometheusMetric {
                 name: "kata_guest_cpu_time".to_string(),
+                family: None,
                 metric_type: Some("gauge".to_string()),
                 help: None,
+                unit: None,
                 samples: vec![],
             });
 
@@ -689,8 +987,8 @@ mod tests {
             name: "kata_guest_cpu_time".to_string(),
             labels: {
                 let mut map = HashMap::new();
-                map.insert("cpu".to_string(), "total".to_string());
-                map.insert("item".to_string(), "user".to_string());
+                map.insert("cpu".into(), "total".into());
+                map.insert("item".into(), "user".into());
                 map
             },
             value: 100.0,
@@ -698,9 +996,8 @@ mod tests {
         });
 
         let config = ConversionConfig::default();
-        let enricher = Arc::new(MockLabelEnricher::new("nginx-app", "web", "xyz-789"));
-        let converter =
-            CloudHypervisorConverter::with_enricher(config, enricher, "sandbox-abc".to_string());
+        let labels = pod_labels("nginx-app", "web", "xyz-789");
+        let converter = CloudHypervisorConverter::with_labels(config, labels);
 
         let cpu_metrics = converter.convert_cpu(&metrics).unwrap();
 
@@ -716,4 +1013,123 @@ mod tests {
         // Note: enriched_labels like pod_uid are deprecated and no longer emitted in Prometheus format
         // Only standard_labels (container, id, image, name, namespace, pod) are now emitted
     }
+
+    #[test]
+    fn test_shim_conversion() {
+        let content = r#"# TYPE kata_shim_threads gauge
+kata_shim_threads 14
+kata_shim_fds 42
+kata_shim_proc_status{item="vmrss"} 3.2768e+07
+kata_shim_proc_status{item="vmpeak"} 9.9e+07
+kata_shim_rpc_durations_histogram_milliseconds_bucket{action="CreateContainer",le="10"} 1
+kata_shim_rpc_durations_histogram_milliseconds_sum{action="CreateContainer"} 250
+kata_shim_rpc_durations_histogram_milliseconds_count{action="CreateContainer"} 2
+kata_guest_tasks{item="cur"} 7
+"#;
+        let metrics = PrometheusMetrics::parse(content).unwrap();
+
+        let labels = pod_labels("my-pod", "default", "12345-67890");
+        let converter = CloudHypervisorConverter::with_labels(ConversionConfig::default(), labels);
+        let shim = converter.convert_shim(&metrics).unwrap();
+
+        assert_eq!(shim.threads, Some(14));
+        assert_eq!(shim.fds, Some(42));
+        assert_eq!(shim.rss_bytes, Some(32_768_000));
+        assert_eq!(
+            shim.rpc_durations["CreateContainer"],
+            RpcLatency {
+                count: 2,
+                sum_seconds: 0.25,
+                // Estimated from the single (0, 10ms] bucket
+                quantiles: vec![(0.5, 0.005), (0.9, 0.009), (0.99, 0.0099)],
+            }
+        );
+        assert_eq!(shim.standard_labels.pod, "my-pod");
+
+        // Special values are skipped rather than cast to 0 or u64::MAX
+        let special = PrometheusMetrics::parse(
+            "kata_shim_threads NaN\nkata_shim_fds +Inf\nkata_shim_proc_status{item=\"vmrss\"} -Inf\n",
+        )
+        .unwrap();
+        let shim = converter.convert_shim(&special).unwrap();
+        assert_eq!(shim.threads, None);
+        assert_eq!(shim.fds, None);
+        assert_eq!(shim.rss_bytes, None);
+
+        // Guest-only input yields no shim families
+        let guest_only = PrometheusMetrics::parse("kata_guest_tasks{item=\"cur\"} 7\n").unwrap();
+        let shim = converter.convert_shim(&guest_only).unwrap();
+        assert!(shim.to_prometheus_format(None).is_empty());
+    }
+
+    #[test]
+    fn test_virtiofsd_conversion() {
+        let content = r#"kata_virtiofsd_threads 9
+kata_virtiofsd_fds 120
+kata_virtiofsd_io_stat{item="rchar"} 1048576
+kata_virtiofsd_io_stat{item="wchar"} 2048
+kata_virtiofsd_io_stat{item="syscr"} 300
+kata_virtiofsd_io_stat{item="syscw"} 4
+kata_shim_threads 14
+"#;
+        let metrics = PrometheusMetrics::parse(content).unwrap();
+        let labels = pod_labels("my-pod", "default", "12345-67890");
+
+        let converter =
+            CloudHypervisorConverter::with_labels(ConversionConfig::default(), labels.clone());
+        let virtiofsd = converter.convert_virtiofsd(&metrics).unwrap();
+        assert_eq!(virtiofsd.threads, Some(9));
+        assert_eq!(virtiofsd.fds, Some(120));
+        assert_eq!(virtiofsd.read_bytes_total, Some(1048576));
+        assert_eq!(virtiofsd.write_ops_total, Some(4));
+        assert_eq!(virtiofsd.standard_labels.pod, "my-pod");
+
+        // Disabled: no separate family, but still counted in the process totals
+        let converter = CloudHypervisorConverter::with_labels(
+            ConversionConfig {
+                include_virtiofsd: false,
+                ..Default::default()
+            },
+            labels,
+        );
+        let all = converter.convert_all(&metrics).unwrap();
+        assert!(all.virtiofsd.to_prometheus_format(None).is_empty());
+        assert_eq!(all.process.thread_count, 23);
+    }
+
+    #[test]
+    fn test_overhead_conversion() {
+        let metrics = PrometheusMetrics::parse(
+            "kata_overhead_cpu_seconds_total 2.5\nkata_overhead_memory_bytes 73400320\n",
+        )
+        .unwrap();
+        let labels = pod_labels("my-pod", "default", "12345-67890");
+        let converter = CloudHypervisorConverter::with_labels(ConversionConfig::default(), labels);
+
+        let output = converter
+            .convert_overhead(&metrics)
+            .unwrap()
+            .to_prometheus_format(None);
+        assert!(output.contains(r#"kata_overhead_cpu_seconds_total{container="kata",id="12345-67890",image="unknown",name="my-pod",namespace="default",pod="my-pod"} 2.5"#));
+        assert!(output.contains("kata_overhead_memory_bytes{"));
+        assert!(output.contains("} 73400320"));
+    }
+
+    #[test]
+    fn test_vmm_conversion() {
+        let metrics = PrometheusMetrics::parse(
+            "kata_vmm_rss_bytes 209715200\nkata_vmm_cpu_seconds_total 4\nkata_vmm_fds 3\n",
+        )
+        .unwrap();
+        let labels = pod_labels("my-pod", "default", "12345-67890");
+        let converter = CloudHypervisorConverter::with_labels(ConversionConfig::default(), labels);
+
+        let vmm = converter.convert_vmm(&metrics).unwrap();
+        assert_eq!(vmm.rss_bytes, Some(209715200));
+        assert_eq!(vmm.cpu_seconds_total, Some(4.0));
+        assert_eq!(vmm.fds, Some(3));
+        assert!(vmm
+            .to_prometheus_format(None)
+            .contains("# TYPE kata_vmm_cpu_seconds_total counter"));
+    }
 }
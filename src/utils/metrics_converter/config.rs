@@ -1,42 +1,47 @@
 //! Configuration and label enrichment for metrics conversion
 
+use futures::future::BoxFuture;
 use std::sync::Arc;
 
-/// Get the CLK_TCK value from the system (equivalent to `getconf CLK_TCK`)
+use crate::monitor::sandbox_cache::{CriAnnotations, SandboxCRIMetadata};
+
+/// Where the CLK_TCK value was obtained
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClkTckSource {
+    /// `cpu_jiffy_conversion_factor` setting
+    Configured,
+    /// `sysconf(_SC_CLK_TCK)`
+    Sysconf,
+    /// Hardcoded 100 Hz, detection failed
+    Fallback,
+}
+
+impl ClkTckSource {
+    /// Human-readable description of the source
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ClkTckSource::Configured => "cpu_jiffy_conversion_factor setting",
+            ClkTckSource::Sysconf => "sysconf(_SC_CLK_TCK)",
+            ClkTckSource::Fallback => "hardcoded default",
+        }
+    }
+}
+
+/// Detect the CLK_TCK value of the system (equivalent to `getconf CLK_TCK`)
 ///
 /// This is used to convert jiffies from /proc/stat to seconds.
 /// The value represents the number of clock ticks per second.
 ///
 /// Priority:
-/// 1. `KATA_PULSE_CLK_TCK` environment variable (if set and valid)
+/// 1. `configured` (the `cpu_jiffy_conversion_factor` setting), if set
 /// 2. `sysconf(_SC_CLK_TCK)` on Unix systems
 /// 3. Fallback to 100 Hz (standard on most Linux systems)
 ///
 /// # Returns
-/// The CLK_TCK value as a f64
-fn get_clk_tck() -> f64 {
-    // First, try environment variable override
-    if let Ok(env_value) = std::env::var("KATA_PULSE_CLK_TCK") {
-        if let Ok(clk_tck) = env_value.parse::<f64>() {
-            if clk_tck > 0.0 {
-                tracing::info!(
-                    clk_tck = clk_tck,
-                    source = "KATA_PULSE_CLK_TCK environment variable",
-                    "CPU jiffy conversion factor obtained from environment variable"
-                );
-                return clk_tck;
-            } else {
-                tracing::warn!(
-                    value = env_value,
-                    "KATA_PULSE_CLK_TCK must be positive, falling back to system detection"
-                );
-            }
-        } else {
-            tracing::warn!(
-                value = env_value,
-                "KATA_PULSE_CLK_TCK is not a valid number, falling back to system detection"
-            );
-        }
+/// The CLK_TCK value as a f64 and where it was obtained
+pub fn detect_clk_tck(configured: Option<f64>) -> (f64, ClkTckSource) {
+    if let Some(clk_tck) = configured {
+        return (clk_tck, ClkTckSource::Configured);
     }
 
     // Try using sysconf if available
@@ -46,31 +51,38 @@ fn get_clk_tck() -> f64 {
         let clk_tck = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
 
         if clk_tck > 0 {
-            let clk_tck_f64 = clk_tck as f64;
-            tracing::info!(
-                clk_tck = clk_tck_f64,
-                source = "sysconf(_SC_CLK_TCK)",
-                "CPU jiffy conversion factor obtained from system"
-            );
-            return clk_tck_f64;
+            return (clk_tck as f64, ClkTckSource::Sysconf);
         }
     }
 
     // Fallback to 100 Hz (standard on most Linux systems)
     // This is defined by Linux kernel as USER_HZ
-    let default_clk_tck = 100.0;
-    tracing::info!(
-        clk_tck = default_clk_tck,
-        source = "hardcoded default",
-        "CPU jiffy conversion factor using fallback default (sysconf unavailable or returned invalid value)"
-    );
-    default_clk_tck
+    (100.0, ClkTckSource::Fallback)
+}
+
+/// Get the CLK_TCK value of the system, logging where it was obtained
+fn get_clk_tck() -> f64 {
+    let (clk_tck, source) = detect_clk_tck(None);
+    match source {
+        ClkTckSource::Fallback => tracing::info!(
+            clk_tck = clk_tck,
+            source = source.as_str(),
+            "CPU jiffy conversion factor using fallback default (sysconf unavailable or returned invalid value)"
+        ),
+        _ => tracing::info!(
+            clk_tck = clk_tck,
+            source = source.as_str(),
+            "CPU jiffy conversion factor obtained from system"
+        ),
+    }
+    clk_tck
 }
 
 /// Enriched labels from CRI metadata
 ///
 /// Contains typed fields for Kubernetes pod metadata obtained from CRI.
-#[derive(Debug, Clone, Default)]
+/// Resolved before conversion, so a converter works on a consistent snapshot.
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct EnrichedLabels {
     /// Kubernetes pod UID
     pub pod_uid: String,
@@ -78,6 +90,13 @@ pub struct EnrichedLabels {
     pub pod_name: String,
     /// Kubernetes namespace
     pub pod_namespace: String,
+    /// Guest CLK_TCK of the sandbox, if it overrides `cpu_jiffy_conversion_factor`
+    pub clk_tck: Option<f64>,
+    /// Labels from the Kubernetes API: `workload` and `workload_kind`, then
+    /// the selected pod labels as (`label_<name>`, value), sorted by name
+    pub pod_labels: Vec<(String, String)>,
+    /// Well-known CRI annotations of the sandbox (its image becomes the `image` label)
+    pub cri_annotations: CriAnnotations,
 }
 
 impl EnrichedLabels {
@@ -91,6 +110,52 @@ impl EnrichedLabels {
             pod_uid: pod_uid.into(),
             pod_name: pod_name.into(),
             pod_namespace: pod_namespace.into(),
+            clk_tck: None,
+            pod_labels: Vec::new(),
+            cri_annotations: CriAnnotations::default(),
+        }
+    }
+}
+
+/// Name of the label of an emitted series carrying the Kubernetes pod label `key`
+///
+/// As in kube-state-metrics, `app.kubernetes.io/name` becomes
+/// `label_app_kubernetes_io_name`.
+pub fn pod_label_name(key: &str) -> String {
+    let mut name = String::with_capacity(key.len() + 6);
+    name.push_str("label_");
+    name.extend(key.chars().map(|c| {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            c
+        } else {
+            '_'
+        }
+    }));
+    name
+}
+
+impl From<&SandboxCRIMetadata> for EnrichedLabels {
+    fn from(metadata: &SandboxCRIMetadata) -> Self {
+        EnrichedLabels {
+            clk_tck: metadata.clk_tck,
+            pod_labels: metadata
+                .workload
+                .iter()
+                .flat_map(|workload| {
+                    [
+                        ("workload".to_string(), workload.name.clone()),
+                        ("workload_kind".to_string(), workload.kind.clone()),
+                    ]
+                })
+                .chain(
+                    metadata
+                        .pod_labels
+                        .iter()
+                        .map(|(key, value)| (pod_label_name(key), value.clone())),
+                )
+                .collect(),
+            cri_annotations: metadata.cri_annotations.clone(),
+            ..EnrichedLabels::new(&metadata.uid, &metadata.name, &metadata.namespace)
         }
     }
 }
@@ -127,9 +192,24 @@ pub struct ConversionConfig {
     /// Default: ["eth0", "veth.*", "tap.*", "tun.*"]
     pub network_interface_patterns: Vec<String>,
 
+    /// Guest block device filter: only include these patterns (loop and
+    /// pmem devices would otherwise inflate the disk totals)
+    /// Default: ["vd.*", "sd.*", "xvd.*", "nvme.*"]
+    pub disk_device_patterns: Vec<String>,
+
     /// CPU time conversion factor: jiffies to seconds
     /// jiffies from /proc/stat use USER_HZ (typically 100 Hz on Linux)
     pub cpu_jiffy_conversion_factor: f64,
+
+    /// Whether to emit the separate `kata_pod_virtiofsd_*` families
+    /// (virtiofsd threads/fds are still counted in the process totals)
+    pub include_virtiofsd: bool,
+
+    /// Block majors of guest disks named `<prefix><letters>[partition]`, as
+    /// `prefix=major`, for drivers with a dynamic major (virtio-blk), whose
+    /// major depends on the drivers the guest registered first
+    /// Default: [] (their major/minor labels stay empty)
+    pub guest_block_majors: Vec<String>,
 }
 
 impl Default for ConversionConfig {
@@ -146,7 +226,15 @@ impl Default for ConversionConfig {
                 "tap.*".to_string(),
                 "tun.*".to_string(),
             ],
+            disk_device_patterns: vec![
+                "vd.*".to_string(),
+                "sd.*".to_string(),
+                "xvd.*".to_string(),
+                "nvme.*".to_string(),
+            ],
             cpu_jiffy_conversion_factor: get_clk_tck(), // jiffies to seconds (obtained from system via sysconf)
+            include_virtiofsd: true,
+            guest_block_majors: Vec::new(),
         }
     }
 }
@@ -163,40 +251,176 @@ impl std::fmt::Debug for ConversionConfig {
                 "network_interface_patterns",
                 &self.network_interface_patterns,
             )
+            .field("disk_device_patterns", &self.disk_device_patterns)
             .field(
                 "cpu_jiffy_conversion_factor",
                 &self.cpu_jiffy_conversion_factor,
             )
+            .field("include_virtiofsd", &self.include_virtiofsd)
+            .field("guest_block_majors", &self.guest_block_majors)
             .finish()
     }
 }
 
 impl ConversionConfig {
+    /// Check the settings that come from configuration
+    ///
+    /// Interface and disk device patterns are exact names or `prefix.*`; other
+    /// regex syntax would silently never match, so it is rejected.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(self.cpu_jiffy_conversion_factor.is_finite() && self.cpu_jiffy_conversion_factor > 0.0)
+        {
+            anyhow::bail!(
+                "cpu_jiffy_conversion_factor must be > 0, got {}",
+                self.cpu_jiffy_conversion_factor
+            );
+        }
+        for pattern in &self.network_interface_patterns {
+            if !is_supported_pattern(pattern) {
+                anyhow::bail!(
+                    "unsupported network interface pattern {:?}: use an exact name or prefix.*",
+                    pattern
+                );
+            }
+        }
+        for pattern in &self.disk_device_patterns {
+            if !is_supported_pattern(pattern) {
+                anyhow::bail!(
+                    "unsupported disk device pattern {:?}: use an exact name or prefix.*",
+                    pattern
+                );
+            }
+        }
+        for entry in &self.guest_block_majors {
+            if parse_block_major(entry).is_none() {
+                anyhow::bail!(
+                    "invalid guest block major {:?}: use prefix=major (e.g. vd=254)",
+                    entry
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve the major and minor number of a guest block device by name
+    ///
+    /// Configured majors come first, then the fixed majors of the Linux
+    /// device list (`sd*`, `xvd*`, `loop*`, `ram*`, `sr*`, `md*`). Devices
+    /// with a dynamic major that is not configured (nvme, pmem, dm) are None.
+    pub fn block_device_numbers(&self, device: &str) -> Option<(u32, u32)> {
+        for (prefix, major) in self
+            .guest_block_majors
+            .iter()
+            .filter_map(|e| parse_block_major(e))
+        {
+            if let Some((index, partition)) = device.strip_prefix(prefix).and_then(disk_index) {
+                return Some((major, index.checked_mul(16)? + partition));
+            }
+        }
+        if let Some((index, partition)) = device.strip_prefix("sd").and_then(disk_index) {
+            // 16 disks per major: 8, 65-71, then 128-135
+            let major = match index / 16 {
+                0 => 8,
+                group @ 1..=7 => 64 + group,
+                group @ 8..=15 => 120 + group,
+                _ => return None,
+            };
+            return Some((major, (index % 16) * 16 + partition));
+        }
+        if let Some((index, partition)) = device.strip_prefix("xvd").and_then(disk_index) {
+            return Some((202, index.checked_mul(16)? + partition));
+        }
+        [("loop", 7), ("ram", 1), ("sr", 11), ("md", 9)]
+            .into_iter()
+            .find_map(|(prefix, major)| {
+                let number = device.strip_prefix(prefix)?;
+                if number.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                Some((major, number.parse().ok()?))
+            })
+    }
+
     /// Check if an interface name matches the configured patterns
     pub fn matches_network_interface(&self, interface: &str) -> bool {
-        self.network_interface_patterns.iter().any(|pattern| {
-            if pattern.ends_with(".*") {
-                // Simple glob-style matching: pattern.* matches interface starting with pattern
-                let prefix = &pattern[..pattern.len() - 2];
-                interface.starts_with(prefix)
-            } else {
-                // Exact match
-                interface == pattern
-            }
-        })
+        matches_any(&self.network_interface_patterns, interface)
+    }
+
+    /// Check if a guest block device name matches the configured patterns
+    pub fn matches_disk_device(&self, device: &str) -> bool {
+        matches_any(&self.disk_device_patterns, device)
+    }
+}
+
+/// Whether a pattern is an exact name or `prefix.*`
+fn is_supported_pattern(pattern: &str) -> bool {
+    let name = pattern.strip_suffix(".*").unwrap_or(pattern);
+    !pattern.is_empty()
+        && !name.contains([
+            '*', '+', '?', '(', ')', '[', ']', '{', '}', '|', '^', '$', '\\',
+        ])
+}
+
+/// Whether a name matches any of the exact names or `prefix.*` patterns
+fn matches_any(patterns: &[String], name: &str) -> bool {
+    patterns.iter().any(|pattern| {
+        if pattern.ends_with(".*") {
+            // Simple glob-style matching: pattern.* matches names starting with pattern
+            let prefix = &pattern[..pattern.len() - 2];
+            name.starts_with(prefix)
+        } else {
+            // Exact match
+            name == pattern
+        }
+    })
+}
+
+/// Split a `prefix=major` guest block major entry
+fn parse_block_major(entry: &str) -> Option<(&str, u32)> {
+    let (prefix, major) = entry.split_once('=')?;
+    let prefix = prefix.trim();
+    if prefix.is_empty() || !prefix.bytes().all(|b| b.is_ascii_lowercase()) {
+        return None;
     }
+    Some((prefix, major.trim().parse().ok()?))
+}
+
+/// Disk index and partition of the `<letters>[partition]` part of a disk name
+///
+/// Letters count like spreadsheet columns (`a` = 0, `z` = 25, `aa` = 26);
+/// partitions 1-15 share the 16 minors of their disk.
+fn disk_index(suffix: &str) -> Option<(u32, u32)> {
+    let digits = suffix
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(suffix.len());
+    let (letters, partition) = suffix.split_at(digits);
+    if letters.is_empty() || !letters.bytes().all(|b| b.is_ascii_lowercase()) {
+        return None;
+    }
+    let mut index: u32 = 0;
+    for letter in letters.bytes() {
+        index = index
+            .checked_mul(26)?
+            .checked_add(u32::from(letter - b'a') + 1)?;
+    }
+    let partition = match partition {
+        "" => 0,
+        digits => digits.parse().ok().filter(|p| (1..16).contains(p))?,
+    };
+    Some((index - 1, partition))
 }
 
 /// Trait for enriching metrics labels with Kubernetes metadata
 ///
 /// This allows injecting pod name, namespace, and other K8s info
-/// into the converted metrics by looking up CRI metadata.
+/// into the converted metrics by looking up CRI metadata. Labels are
+/// resolved asynchronously by the caller and handed to the converter.
 pub trait LabelEnricher: Send + Sync {
     /// Enrich labels for a sandbox
     ///
     /// Given a sandbox ID, return enriched labels from CRI metadata.
     /// Returns empty EnrichedLabels if enrichment not available.
-    fn enrich(&self, sandbox_id: &str) -> EnrichedLabels;
+    fn enrich<'a>(&'a self, sandbox_id: &'a str) -> BoxFuture<'a, EnrichedLabels>;
 }
 
 /// CRI-based label enricher that uses sandbox metadata
@@ -216,13 +440,14 @@ impl CRILabelEnricher {
 }
 
 impl LabelEnricher for CRILabelEnricher {
-    fn enrich(&self, sandbox_id: &str) -> EnrichedLabels {
-        // Try to get metadata from the sandbox cache (non-blocking)
-        if let Some(metadata) = self.sandbox_cache.get_metadata_try(sandbox_id) {
-            EnrichedLabels::new(metadata.uid, metadata.name, metadata.namespace)
-        } else {
-            EnrichedLabels::default()
-        }
+    fn enrich<'a>(&'a self, sandbox_id: &'a str) -> BoxFuture<'a, EnrichedLabels> {
+        Box::pin(async move {
+            self.sandbox_cache
+                .get_metadata(sandbox_id)
+                .await
+                .map(|metadata| EnrichedLabels::from(&metadata))
+                .unwrap_or_default()
+        })
     }
 }
 
@@ -253,6 +478,23 @@ mod tests {
         assert!(!config.matches_network_interface("br-abcdef"));
     }
 
+    #[test]
+    fn test_disk_device_matching() {
+        let mut config = ConversionConfig::default();
+        assert!(config.matches_disk_device("vda"));
+        assert!(config.matches_disk_device("sdb1"));
+        assert!(config.matches_disk_device("nvme0n1"));
+        assert!(!config.matches_disk_device("loop0"));
+        assert!(!config.matches_disk_device("pmem0"));
+
+        config.disk_device_patterns = vec!["vda".to_string()];
+        assert!(config.matches_disk_device("vda"));
+        assert!(!config.matches_disk_device("vdb"));
+
+        config.disk_device_patterns = vec!["vd*".to_string()];
+        assert!(config.validate().is_err());
+    }
+
     #[test]
     fn test_cri_label_enricher_with_metadata() {
         // Create a sandbox cache with test data
@@ -268,13 +510,14 @@ mod tests {
                         uid: "uid-12345".to_string(),
                         name: "my-pod".to_string(),
                         namespace: "default".to_string(),
+                        ..Default::default()
                     },
                 )
                 .await;
         });
 
         let enricher = CRILabelEnricher::new(cache);
-        let labels = enricher.enrich("sandbox-123");
+        let labels = runtime.block_on(enricher.enrich("sandbox-123"));
 
         // Verify all labels were enriched
         assert_eq!(labels.pod_name, "my-pod");
@@ -288,7 +531,8 @@ mod tests {
         let enricher = CRILabelEnricher::new(cache);
 
         // Enrich for non-existent sandbox
-        let labels = enricher.enrich("non-existent-sandbox");
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let labels = runtime.block_on(enricher.enrich("non-existent-sandbox"));
 
         // Should return empty labels
         assert_eq!(labels.pod_uid, "");
@@ -310,6 +554,7 @@ mod tests {
                         uid: "uid-1".to_string(),
                         name: "pod-1".to_string(),
                         namespace: "ns-1".to_string(),
+                        ..Default::default()
                     },
                 )
                 .await;
@@ -321,6 +566,7 @@ mod tests {
                         uid: "uid-2".to_string(),
                         name: "pod-2".to_string(),
                         namespace: "ns-2".to_string(),
+                        ..Default::default()
                     },
                 )
                 .await;
@@ -329,47 +575,129 @@ mod tests {
         let enricher = CRILabelEnricher::new(cache);
 
         // Check first sandbox
-        let labels1 = enricher.enrich("sandbox-1");
+        let labels1 = runtime.block_on(enricher.enrich("sandbox-1"));
         assert_eq!(labels1.pod_name, "pod-1");
         assert_eq!(labels1.pod_namespace, "ns-1");
         assert_eq!(labels1.pod_uid, "uid-1");
 
         // Check second sandbox
-        let labels2 = enricher.enrich("sandbox-2");
+        let labels2 = runtime.block_on(enricher.enrich("sandbox-2"));
         assert_eq!(labels2.pod_name, "pod-2");
         assert_eq!(labels2.pod_namespace, "ns-2");
         assert_eq!(labels2.pod_uid, "uid-2");
     }
 
     #[test]
-    fn test_get_clk_tck_with_valid_env_override() {
-        // Test that environment variable override works
-        std::env::set_var("KATA_PULSE_CLK_TCK", "250");
-        // Note: We can't directly test get_clk_tck() since it's private and called at config creation
-        // But we can verify config creation respects environment
-        let config = ConversionConfig::default();
-        // The conversion factor should be 250 if the env var was picked up
-        assert_eq!(config.cpu_jiffy_conversion_factor, 250.0);
-        std::env::remove_var("KATA_PULSE_CLK_TCK");
+    fn test_cri_label_enricher_with_pod_info() {
+        let cache = Arc::new(crate::monitor::sandbox_cache::SandboxCache::new());
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            cache
+                .set_cri_metadata(
+                    "sandbox-1",
+                    crate::monitor::sandbox_cache::SandboxCRIMetadata {
+                        uid: "uid-1".to_string(),
+                        name: "pod-1".to_string(),
+                        ..Default::default()
+                    },
+                )
+                .await;
+            cache
+                .set_pod_info(
+                    "uid-1",
+                    crate::monitor::sandbox_cache::K8sPodInfo {
+                        labels: [
+                            ("team".to_string(), "payments".to_string()),
+                            ("app.kubernetes.io/name".to_string(), "checkout".to_string()),
+                        ]
+                        .into(),
+                        workload: Some(crate::monitor::sandbox_cache::Workload {
+                            kind: "Deployment".to_string(),
+                            name: "checkout".to_string(),
+                        }),
+                    },
+                )
+                .await;
+        });
+
+        let enricher = CRILabelEnricher::new(cache.clone());
+        let labels = runtime.block_on(enricher.enrich("sandbox-1"));
+        assert_eq!(
+            labels.pod_labels,
+            vec![
+                ("workload".to_string(), "checkout".to_string()),
+                ("workload_kind".to_string(), "Deployment".to_string()),
+                (
+                    "label_app_kubernetes_io_name".to_string(),
+                    "checkout".to_string()
+                ),
+                ("label_team".to_string(), "payments".to_string()),
+            ]
+        );
+
+        runtime.block_on(cache.remove_pod_info("uid-1"));
+        assert!(runtime
+            .block_on(enricher.enrich("sandbox-1"))
+            .pod_labels
+            .is_empty());
     }
 
     #[test]
-    fn test_get_clk_tck_with_invalid_env_override() {
-        // Test that invalid env values fall back to system/default
-        std::env::set_var("KATA_PULSE_CLK_TCK", "not_a_number");
-        let config = ConversionConfig::default();
-        // Should fall back to sysconf or default (100)
-        assert!(config.cpu_jiffy_conversion_factor > 0.0);
-        std::env::remove_var("KATA_PULSE_CLK_TCK");
+    fn test_validate() {
+        let mut config = ConversionConfig {
+            network_interface_patterns: vec!["eth0.100".to_string(), ".*".to_string()],
+            ..Default::default()
+        };
+        assert!(config.validate().is_ok());
+
+        for pattern in ["eth[0-9]", "", "veth*"] {
+            config.network_interface_patterns = vec![pattern.to_string()];
+            assert!(config.validate().is_err(), "{}", pattern);
+        }
+
+        config.network_interface_patterns = vec!["eth0".to_string()];
+        config.cpu_jiffy_conversion_factor = 0.0;
+        assert!(config.validate().is_err());
+
+        config.cpu_jiffy_conversion_factor = 100.0;
+        for entry in ["vd", "vd=", "=254", "vd=major", "v1=254"] {
+            config.guest_block_majors = vec![entry.to_string()];
+            assert!(config.validate().is_err(), "{}", entry);
+        }
     }
 
     #[test]
-    fn test_get_clk_tck_with_negative_env_override() {
-        // Test that negative env values are rejected
-        std::env::set_var("KATA_PULSE_CLK_TCK", "-50");
-        let config = ConversionConfig::default();
-        // Should fall back to sysconf or default (100)
-        assert!(config.cpu_jiffy_conversion_factor > 0.0);
-        std::env::remove_var("KATA_PULSE_CLK_TCK");
+    fn test_block_device_numbers() {
+        let mut config = ConversionConfig::default();
+        // Dynamic majors are unknown unless configured
+        assert_eq!(config.block_device_numbers("vda"), None);
+        assert_eq!(config.block_device_numbers("sda"), Some((8, 0)));
+        assert_eq!(config.block_device_numbers("sdc2"), Some((8, 34)));
+        assert_eq!(config.block_device_numbers("sdq"), Some((65, 0)));
+        assert_eq!(config.block_device_numbers("sdaa"), Some((65, 160)));
+        assert_eq!(config.block_device_numbers("xvdb"), Some((202, 16)));
+        assert_eq!(config.block_device_numbers("loop3"), Some((7, 3)));
+        assert_eq!(config.block_device_numbers("sr0"), Some((11, 0)));
+        assert_eq!(config.block_device_numbers("pmem0"), None);
+        assert_eq!(config.block_device_numbers("nvme0n1"), None);
+
+        config.guest_block_majors = vec!["vd=252".to_string(), "pmem=259".to_string()];
+        assert_eq!(config.block_device_numbers("vda"), Some((252, 0)));
+        assert_eq!(config.block_device_numbers("vdb1"), Some((252, 17)));
+        assert_eq!(config.block_device_numbers("vdc"), Some((252, 32)));
+        assert_eq!(config.block_device_numbers("vda16"), None);
+        // Only letter-indexed names are resolved from configured majors
+        assert_eq!(config.block_device_numbers("pmem0"), None);
+    }
+
+    #[test]
+    fn test_detect_clk_tck() {
+        assert_eq!(
+            detect_clk_tck(Some(250.0)),
+            (250.0, ClkTckSource::Configured)
+        );
+        let (clk_tck, source) = detect_clk_tck(None);
+        assert!(clk_tck > 0.0);
+        assert_ne!(source, ClkTckSource::Configured);
     }
 }
@@ -9,7 +9,7 @@
 //! 1. **MetricsConverter** trait - Main conversion interface (hypervisor-agnostic)
 //! 2. **CloudHypervisorConverter** - Cloud hypervisor specific implementation
 //! 3. **CadvisorMetrics** - Output model (cAdvisor-compatible format)
-//! 4. **LabelEnricher** - Enriches labels with Kubernetes metadata
+//! 4. **LabelEnricher** - Resolves the Kubernetes labels handed to the converter
 //!
 //! ## Extensibility
 //!
@@ -34,14 +34,16 @@ pub mod cloud_hypervisor;
 pub mod config;
 
 pub use cadvisor::{
-    CadvisorMetrics, CpuMetrics, DiskMetrics, MemoryMetrics, NetworkMetrics, ProcessMetrics,
+    CadvisorMetrics, CpuMetrics, DiskMetrics, MemoryMetrics, NetworkMetrics, OverheadMetrics,
+    ProcessMetrics, ShimMetrics, VirtiofsdMetrics, VmmMetrics,
 };
 pub use cloud_hypervisor::CloudHypervisorConverter;
-pub use config::{CRILabelEnricher, ConversionConfig, LabelEnricher};
+pub use config::{
+    detect_clk_tck, CRILabelEnricher, ClkTckSource, ConversionConfig, EnrichedLabels, LabelEnricher,
+};
 
 use crate::utils::prometheus_parser::PrometheusMetrics;
 use anyhow::Result;
-use std::sync::Arc;
 
 /// Main trait for metrics conversion
 ///
@@ -63,13 +65,30 @@ pub trait MetricsConverter {
     /// Convert process metrics
     fn convert_process(&self, metrics: &PrometheusMetrics) -> Result<ProcessMetrics>;
 
-    /// Complete conversion: CPU + Memory + Network + Disk + Process
+    /// Convert host-side metrics of the shim process itself
+    fn convert_shim(&self, metrics: &PrometheusMetrics) -> Result<ShimMetrics>;
+
+    /// Convert host-side metrics of the virtiofsd daemon
+    fn convert_virtiofsd(&self, metrics: &PrometheusMetrics) -> Result<VirtiofsdMetrics>;
+
+    /// Convert host cgroup usage of the sandbox overhead
+    fn convert_overhead(&self, metrics: &PrometheusMetrics) -> Result<OverheadMetrics>;
+
+    /// Convert /proc usage of the hypervisor process
+    fn convert_vmm(&self, metrics: &PrometheusMetrics) -> Result<VmmMetrics>;
+
+    /// Complete conversion: guest families (CPU, memory, network, disk, process)
+    /// and host-side families (shim, virtiofsd, overhead, VMM)
     fn convert_all(&self, metrics: &PrometheusMetrics) -> Result<CadvisorMetrics> {
         let cpu = self.convert_cpu(metrics)?;
         let memory = self.convert_memory(metrics)?;
         let network = self.convert_network(metrics)?;
         let disk = self.convert_disk(metrics)?;
         let process = self.convert_process(metrics)?;
+        let shim = self.convert_shim(metrics)?;
+        let virtiofsd = self.convert_virtiofsd(metrics)?;
+        let overhead = self.convert_overhead(metrics)?;
+        let vmm = self.convert_vmm(metrics)?;
 
         Ok(CadvisorMetrics {
             cpu,
@@ -77,22 +96,27 @@ pub trait MetricsConverter {
             network,
             disk,
             process,
+            shim,
+            virtiofsd,
+            overhead,
+            vmm,
         })
     }
 }
 
-/// Factory function to create a converter with CRI label enricher
+/// Factory function to create a converter for a sandbox with its resolved pod labels
+///
+/// Resolve the labels with a [`LabelEnricher`] first.
 pub fn create_converter(
     config: ConversionConfig,
-    label_enricher: Arc<dyn LabelEnricher>,
-    sandbox_id: String,
+    labels: EnrichedLabels,
 ) -> Box<dyn MetricsConverter> {
     match config.hypervisor_type {
-        config::HypervisorType::CloudHypervisor => Box::new(
-            CloudHypervisorConverter::with_enricher(config, label_enricher, sandbox_id),
-        ), // Future: Add more hypervisor types
-           // config::HypervisorType::Qemu => Box::new(QemuConverter::with_enricher(config, label_enricher, sandbox_id)),
-           // config::HypervisorType::Firecracker => Box::new(FirecrackerConverter::with_enricher(config, label_enricher, sandbox_id)),
+        config::HypervisorType::CloudHypervisor => {
+            Box::new(CloudHypervisorConverter::with_labels(config, labels))
+        } // Future: Add more hypervisor types
+          // config::HypervisorType::Qemu => Box::new(QemuConverter::with_labels(config, labels)),
+          // config::HypervisorType::Firecracker => Box::new(FirecrackerConverter::with_labels(config, labels)),
     }
 }
 
@@ -103,9 +127,7 @@ mod tests {
     #[test]
     fn test_factory_creates_cloud_hypervisor_converter() {
         let config = ConversionConfig::default();
-        let cache = Arc::new(crate::monitor::sandbox_cache::SandboxCache::new());
-        let enricher = Arc::new(CRILabelEnricher::new(cache));
-        let converter = create_converter(config, enricher, "test".parse().unwrap());
+        let converter = create_converter(config, EnrichedLabels::default());
         // Just verify it doesn't crash - actual conversion tested in cloud_hypervisor tests
         assert!(std::mem::size_of_val(&*converter) > 0);
     }
@@ -1,3 +1,9 @@
+pub mod agent_client;
+pub mod allocator;
+pub mod buffer_pool;
+pub mod interner;
+pub mod log_dedup;
 pub mod metrics_converter;
 pub mod prometheus_parser;
 pub mod shim_client;
+pub mod systemd;
@@ -1,17 +1,30 @@
-use crate::utils::metrics_converter::cadvisor::PrometheusFormat;
+use crate::utils::interner::{self, InternBatch};
+use crate::utils::metrics_converter::cadvisor::{write_escaped, PrometheusFormat};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Label pairs of a sample; names and values are interned across samples
+pub type Labels = HashMap<Arc<str>, Arc<str>>;
 
 /// Represents a single Prometheus metric
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PrometheusMetric {
     /// Metric name
     pub name: String,
+    /// Name of the family in its HELP, TYPE and UNIT lines, if it differs
+    /// from `name` (e.g. `requests_total` of a counter)
+    #[serde(default)]
+    pub family: Option<String>,
     /// Metric type (counter, gauge, histogram, summary, etc.)
     pub metric_type: Option<String>,
     /// Metric help/description
     pub help: Option<String>,
+    /// Metric unit (from an OpenMetrics `# UNIT` line)
+    pub unit: Option<String>,
     /// Samples for this metric (value, labels)
     pub samples: Vec<MetricSample>,
 }
@@ -22,15 +35,388 @@ pub struct MetricSample {
     /// Full metric name with suffix (e.g., "requests_total", "duration_seconds_bucket")
     pub name: String,
     /// Label key-value pairs (e.g., {"method": "GET", "status": "200"})
-    pub labels: HashMap<String, String>,
+    #[serde(deserialize_with = "deserialize_labels")]
+    pub labels: Labels,
     /// Metric value
+    #[serde(with = "sample_value")]
     pub value: f64,
-    /// Timestamp (optional)
+    /// Timestamp in milliseconds (optional)
     pub timestamp: Option<i64>,
 }
 
+/// Deserialize labels into interned strings, like parsed ones
+fn deserialize_labels<'de, D>(deserializer: D) -> std::result::Result<Labels, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw = HashMap::<Cow<'de, str>, Cow<'de, str>>::deserialize(deserializer)?;
+    let mut interner = interner::labels().batch();
+    Ok(raw
+        .into_iter()
+        .map(|(key, value)| (interner.intern(&key), interner.intern(&value)))
+        .collect())
+}
+
+/// Sample values are serialized as numbers, and `NaN`/`+Inf`/`-Inf` as
+/// strings since JSON has no representation for them
+mod sample_value {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_finite() {
+            serializer.serialize_f64(*value)
+        } else {
+            serializer.serialize_str(&super::format_value(*value))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Number(f64),
+            Text(String),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Number(value) => Ok(value),
+            Repr::Text(text) => text
+                .parse()
+                .map_err(|_| D::Error::custom(format!("invalid sample value '{}'", text))),
+        }
+    }
+}
+
+impl MetricSample {
+    /// Create an unlabelled sample
+    pub fn new(name: impl Into<String>, value: f64) -> Self {
+        MetricSample {
+            name: name.into(),
+            labels: Labels::new(),
+            value,
+            timestamp: None,
+        }
+    }
+}
+
+/// A histogram series, grouped from its `_bucket`, `_sum` and `_count` samples
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Histogram {
+    /// Labels of the series (without `le`)
+    pub labels: Labels,
+    /// Cumulative bucket counts by upper bound, sorted by upper bound
+    pub buckets: Vec<(f64, f64)>,
+    /// Sum of the observed values
+    pub sum: Option<f64>,
+    /// Number of observations
+    pub count: Option<f64>,
+}
+
+impl Histogram {
+    /// Estimate the `q` quantile (0 <= q <= 1) from the buckets
+    ///
+    /// Interpolates linearly within the bucket like PromQL's `histogram_quantile`;
+    /// a quantile in the `+Inf` bucket is reported as the highest finite bound.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let total = self.buckets.last()?.1;
+        if !(0.0..=1.0).contains(&q) || total <= 0.0 {
+            return None;
+        }
+
+        let rank = q * total;
+        let mut lower_bound = 0.0;
+        let mut lower_count = 0.0;
+        for &(upper_bound, count) in &self.buckets {
+            if count >= rank {
+                if upper_bound.is_infinite() {
+                    return Some(lower_bound);
+                }
+                if count == lower_count {
+                    return Some(upper_bound);
+                }
+                return Some(
+                    lower_bound
+                        + (upper_bound - lower_bound) * (rank - lower_count)
+                            / (count - lower_count),
+                );
+            }
+            lower_bound = upper_bound;
+            lower_count = count;
+        }
+        Some(lower_bound)
+    }
+}
+
+/// A summary series, grouped from its quantile, `_sum` and `_count` samples
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    /// Labels of the series (without `quantile`)
+    pub labels: Labels,
+    /// Precomputed quantiles (quantile, value), sorted by quantile
+    pub quantiles: Vec<(f64, f64)>,
+    /// Sum of the observed values
+    pub sum: Option<f64>,
+    /// Number of observations
+    pub count: Option<f64>,
+}
+
+impl PrometheusMetric {
+    /// The family without its samples
+    fn clone_metadata(&self) -> Self {
+        PrometheusMetric {
+            name: self.name.clone(),
+            family: self.family.clone(),
+            metric_type: self.metric_type.clone(),
+            help: self.help.clone(),
+            unit: self.unit.clone(),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Samples with a finite value
+    ///
+    /// `NaN` and `±Inf` cannot be converted to integer or cumulative cAdvisor
+    /// values (`as u64` would turn them into 0 or `u64::MAX`), so converters skip them.
+    pub fn finite_samples(&self) -> impl Iterator<Item = &MetricSample> {
+        self.samples
+            .iter()
+            .filter(|sample| sample.value.is_finite())
+    }
+
+    /// Group the samples of a histogram family into series
+    ///
+    /// Series are returned in a stable order (sorted by labels).
+    pub fn histograms(&self) -> Vec<Histogram> {
+        let mut series: BTreeMap<SeriesKey, Histogram> = BTreeMap::new();
+        for sample in &self.samples {
+            let entry = series_entry(&mut series, sample, "le");
+            if sample.name.ends_with("_bucket") {
+                if let Some(le) = sample.labels.get("le").and_then(|le| parse_bound(le)) {
+                    entry.buckets.push((le, sample.value));
+                }
+            } else if sample.name.ends_with("_sum") {
+                entry.sum = Some(sample.value);
+            } else if sample.name.ends_with("_count") {
+                entry.count = Some(sample.value);
+            }
+        }
+
+        series
+            .into_values()
+            .map(|mut histogram| {
+                histogram.buckets.sort_by(|a, b| a.0.total_cmp(&b.0));
+                histogram
+            })
+            .collect()
+    }
+
+    /// Group the samples of a summary family into series
+    ///
+    /// Series are returned in a stable order (sorted by labels).
+    pub fn summaries(&self) -> Vec<Summary> {
+        let mut series: BTreeMap<SeriesKey, Summary> = BTreeMap::new();
+        for sample in &self.samples {
+            let entry = series_entry(&mut series, sample, "quantile");
+            if sample.name.ends_with("_sum") {
+                entry.sum = Some(sample.value);
+            } else if sample.name.ends_with("_count") {
+                entry.count = Some(sample.value);
+            } else if let Some(q) = sample.labels.get("quantile").and_then(|q| parse_bound(q)) {
+                // Quantiles of a summary without observations are NaN
+                if !sample.value.is_nan() {
+                    entry.quantiles.push((q, sample.value));
+                }
+            }
+        }
+
+        series
+            .into_values()
+            .map(|mut summary| {
+                summary.quantiles.sort_by(|a, b| a.0.total_cmp(&b.0));
+                summary
+            })
+            .collect()
+    }
+}
+
+/// Sorted labels identifying a histogram or summary series
+type SeriesKey = Vec<(Arc<str>, Arc<str>)>;
+
+/// Series of a histogram or summary grouped by labels
+trait GroupedSeries: Default {
+    fn set_labels(&mut self, labels: Labels);
+}
+
+impl GroupedSeries for Histogram {
+    fn set_labels(&mut self, labels: Labels) {
+        self.labels = labels;
+    }
+}
+
+impl GroupedSeries for Summary {
+    fn set_labels(&mut self, labels: Labels) {
+        self.labels = labels;
+    }
+}
+
+/// Get the series a sample belongs to, ignoring the bucket/quantile label
+fn series_entry<'a, T: GroupedSeries>(
+    series: &'a mut BTreeMap<SeriesKey, T>,
+    sample: &MetricSample,
+    bucket_label: &str,
+) -> &'a mut T {
+    let mut key: SeriesKey = sample
+        .labels
+        .iter()
+        .filter(|(name, _)| &***name != bucket_label)
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    key.sort();
+    series.entry(key).or_insert_with_key(|key| {
+        let mut entry = T::default();
+        entry.set_labels(key.iter().cloned().collect());
+        entry
+    })
+}
+
+/// Sample name and sorted labels identifying a single series
+fn sample_key(sample: &MetricSample) -> (&str, SeriesKey) {
+    let mut labels: SeriesKey = sample
+        .labels
+        .iter()
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    labels.sort();
+    (&sample.name, labels)
+}
+
+/// Whether samples of a family only go up between resets
+fn is_cumulative(metric: &PrometheusMetric) -> bool {
+    matches!(
+        metric.metric_type.as_deref(),
+        Some("counter" | "histogram" | "summary")
+    )
+}
+
+/// Parse an `le` or `quantile` label value (`+Inf` included)
+fn parse_bound(value: &str) -> Option<f64> {
+    match value {
+        "+Inf" | "Inf" => Some(f64::INFINITY),
+        _ => value.parse().ok(),
+    }
+}
+
+/// A line that could not be parsed
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    /// 1-based line number in the scraped text
+    pub line: usize,
+    /// What was wrong with the line
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// A family or series that could not be merged as is
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeConflict {
+    /// Base name of the metric family
+    pub metric: String,
+    /// What conflicted and how it was resolved
+    pub message: String,
+}
+
+impl std::fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.metric, self.message)
+    }
+}
+
+/// Metric families skipped while parsing, by name prefix
+///
+/// Families no converter reads (e.g. Go runtime or Firecracker seccomp
+/// counters) are dropped before they are allocated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NameFilter {
+    drop_prefixes: Vec<String>,
+}
+
+impl NameFilter {
+    /// Drop the families whose names start with any of `prefixes` (empty ones are ignored)
+    pub fn new<S: Into<String>>(prefixes: impl IntoIterator<Item = S>) -> Self {
+        NameFilter {
+            drop_prefixes: prefixes
+                .into_iter()
+                .map(Into::into)
+                .filter(|prefix| !prefix.is_empty())
+                .collect(),
+        }
+    }
+
+    /// Whether the family of `name` is dropped
+    pub fn drops(&self, name: &str) -> bool {
+        self.drop_prefixes
+            .iter()
+            .any(|prefix| name.starts_with(prefix.as_str()))
+    }
+}
+
+/// How malformed lines in scraped metrics are treated
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Skip malformed lines and keep the rest of the scrape
+    #[default]
+    Lenient,
+    /// Fail the whole scrape on the first malformed line
+    Strict,
+}
+
+impl ParseMode {
+    /// Check the errors of a parse against the mode
+    ///
+    /// Fails in strict mode if any line was malformed.
+    pub fn check(self, errors: &[ParseError]) -> Result<()> {
+        match (self, errors.first()) {
+            (ParseMode::Strict, Some(first)) => Err(anyhow::anyhow!(
+                "{} malformed metric line(s), first at {}",
+                errors.len(),
+                first
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::str::FromStr for ParseMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "lenient" => Ok(ParseMode::Lenient),
+            "strict" => Ok(ParseMode::Strict),
+            _ => Err(anyhow::anyhow!(
+                "unknown parse mode '{}' (expected lenient or strict)",
+                s
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ParseMode::Lenient => "lenient",
+            ParseMode::Strict => "strict",
+        })
+    }
+}
+
 /// Parsed Prometheus metrics text format
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PrometheusMetrics {
     /// Metrics grouped by base name (mutable to support aggregation)
     pub metrics: std::collections::HashMap<String, PrometheusMetric>,
@@ -45,84 +431,396 @@ impl PrometheusMetrics {
     }
 
     /// Get or create a metric entry
-    fn get_or_create_metric(&mut self, base_name: String) -> &mut PrometheusMetric {
+    ///
+    /// Only allocates the name when the metric is new.
+    fn get_or_create_metric(&mut self, base_name: &str) -> &mut PrometheusMetric {
+        if !self.metrics.contains_key(base_name) {
+            self.metrics.insert(
+                base_name.to_string(),
+                PrometheusMetric {
+                    name: base_name.to_string(),
+                    ..Default::default()
+                },
+            );
+        }
         self.metrics
-            .entry(base_name.clone())
-            .or_insert_with(|| PrometheusMetric {
-                name: base_name,
-                ..Default::default()
-            })
+            .get_mut(base_name)
+            .expect("metric was inserted above")
+    }
+
+    /// Add a sample, creating its metric family with the given type and help if needed
+    ///
+    /// The family of a counter or gauge is named after its sample.
+    pub fn push_sample(&mut self, metric_type: &str, help: &str, sample: MetricSample) {
+        let base_name = extract_base_metric_name(&sample.name);
+        let family = (base_name != sample.name && !matches!(metric_type, "histogram" | "summary"))
+            .then(|| sample.name.clone());
+        let metric = self.get_or_create_metric(base_name);
+        if metric.family.is_none() {
+            metric.family = family;
+        }
+        metric
+            .metric_type
+            .get_or_insert_with(|| metric_type.to_string());
+        metric.help.get_or_insert_with(|| help.to_string());
+        metric.samples.push(sample);
+    }
+
+    /// Merge the families of `other` into these metrics
+    ///
+    /// Families are combined by base name; missing HELP, TYPE and UNIT are
+    /// taken from `other`. Conflicts are resolved and returned:
+    /// - a family whose type differs keeps its own samples, `other`'s are dropped
+    /// - a series present in both takes the sample from `other`
+    pub fn merge(&mut self, other: PrometheusMetrics) -> Vec<MergeConflict> {
+        let mut conflicts = Vec::new();
+        for (name, family) in other.metrics {
+            let Some(existing) = self.metrics.get_mut(&name) else {
+                self.metrics.insert(name, family);
+                continue;
+            };
+
+            if let (Some(ours), Some(theirs)) = (&existing.metric_type, &family.metric_type) {
+                if ours != theirs {
+                    conflicts.push(MergeConflict {
+                        metric: name,
+                        message: format!(
+                            "type {} conflicts with {}, dropped {} sample(s)",
+                            theirs,
+                            ours,
+                            family.samples.len()
+                        ),
+                    });
+                    continue;
+                }
+            }
+            if existing.metric_type.is_none() {
+                existing.metric_type = family.metric_type;
+            }
+            if existing.help.is_none() {
+                existing.help = family.help;
+            }
+            if existing.unit.is_none() {
+                existing.unit = family.unit;
+            }
+            if existing.family.is_none() {
+                existing.family = family.family;
+            }
+
+            let mut index: HashMap<(String, SeriesKey), usize> = existing
+                .samples
+                .iter()
+                .enumerate()
+                .map(|(idx, sample)| {
+                    let (name, labels) = sample_key(sample);
+                    ((name.to_string(), labels), idx)
+                })
+                .collect();
+            for sample in family.samples {
+                let (sample_name, labels) = sample_key(&sample);
+                let key = (sample_name.to_string(), labels);
+                match index.get(&key) {
+                    Some(&idx) => {
+                        conflicts.push(MergeConflict {
+                            metric: name.clone(),
+                            message: format!("duplicate series of {}, replaced", key.0),
+                        });
+                        existing.samples[idx] = sample;
+                    }
+                    None => {
+                        index.insert(key, existing.samples.len());
+                        existing.samples.push(sample);
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Per-series change since `previous`
+    ///
+    /// Returns the families of these metrics with each sample's value replaced
+    /// by its delta against the same series in `previous`; series missing from
+    /// `previous` are left out. Cumulative families (counters, histograms,
+    /// summaries) that went down were reset, so their delta is the current
+    /// value. Summary quantiles are not cumulative and are left out.
+    pub fn diff(&self, previous: &PrometheusMetrics) -> PrometheusMetrics {
+        let mut deltas = PrometheusMetrics::new();
+        for (name, metric) in &self.metrics {
+            let Some(before) = previous.metrics.get(name) else {
+                continue;
+            };
+            let before: HashMap<(&str, SeriesKey), f64> = before
+                .samples
+                .iter()
+                .map(|sample| (sample_key(sample), sample.value))
+                .collect();
+
+            let cumulative = is_cumulative(metric);
+            let summary = metric.metric_type.as_deref() == Some("summary");
+            let samples: Vec<MetricSample> = metric
+                .samples
+                .iter()
+                .filter(|sample| !(summary && sample.labels.contains_key("quantile")))
+                .filter_map(|sample| {
+                    let previous = before.get(&sample_key(sample))?;
+                    let delta = if cumulative && sample.value < *previous {
+                        sample.value
+                    } else {
+                        sample.value - previous
+                    };
+                    Some(MetricSample {
+                        value: delta,
+                        timestamp: None,
+                        ..sample.clone()
+                    })
+                })
+                .collect();
+
+            if !samples.is_empty() {
+                deltas.metrics.insert(
+                    name.clone(),
+                    PrometheusMetric {
+                        samples,
+                        ..metric.clone_metadata()
+                    },
+                );
+            }
+        }
+        deltas
+    }
+
+    /// Split the samples into groups by `key`
+    ///
+    /// Each group keeps the metadata of the families it has samples of.
+    pub fn partition_by<K: Ord>(
+        &self,
+        key: impl Fn(&MetricSample) -> K,
+    ) -> BTreeMap<K, PrometheusMetrics> {
+        let mut groups: BTreeMap<K, PrometheusMetrics> = BTreeMap::new();
+        for (name, metric) in &self.metrics {
+            for sample in &metric.samples {
+                groups
+                    .entry(key(sample))
+                    .or_default()
+                    .metrics
+                    .entry(name.clone())
+                    .or_insert_with(|| metric.clone_metadata())
+                    .samples
+                    .push(sample.clone());
+            }
+        }
+        groups
     }
 
     /// Parse Prometheus text format metrics
+    ///
+    /// Lines are parsed as slices of `content`; strings are only allocated for
+    /// new metric families and for the samples that are kept.
+    ///
+    /// OpenMetrics input (terminated by `# EOF`) is accepted as well: `# UNIT`
+    /// lines are kept, exemplars are dropped, timestamps are converted from
+    /// seconds to milliseconds and the `_created` samples of counters,
+    /// histograms and summaries are skipped.
     pub fn parse(content: &str) -> Result<Self> {
+        Ok(Self::parse_with_diagnostics(content).0)
+    }
+
+    /// Parse Prometheus text format metrics, reporting the skipped lines
+    ///
+    /// Malformed lines do not fail the parse; they are returned with their
+    /// line numbers instead.
+    pub fn parse_with_diagnostics(content: &str) -> (Self, Vec<ParseError>) {
+        Self::parse_filtered(content, &NameFilter::default())
+    }
+
+    /// Parse Prometheus text format metrics, skipping the families dropped by `filter`
+    ///
+    /// Dropped lines are recognized by their leading metric name and skipped
+    /// before their labels are tokenized, so they cost no allocations.
+    pub fn parse_filtered(content: &str, filter: &NameFilter) -> (Self, Vec<ParseError>) {
         let mut metrics = PrometheusMetrics::new();
+        let mut errors = Vec::new();
+        let openmetrics = is_openmetrics(content);
+        let mut labels = interner::labels().batch();
 
-        for line in content.lines() {
+        for (idx, line) in content.lines().enumerate() {
             let trimmed = line.trim();
 
+            if trimmed == "# EOF" {
+                break;
+            }
+
             // Skip empty lines and other comments
             if trimmed.is_empty()
                 || trimmed.starts_with('#')
                     && !trimmed.starts_with("# HELP ")
                     && !trimmed.starts_with("# TYPE ")
+                    && !trimmed.starts_with("# UNIT ")
             {
                 continue;
             }
 
+            // Sample lines start with the metric name (unless it is quoted)
+            if filter.drops(trimmed) {
+                continue;
+            }
+
             // Handle HELP lines
             if let Some((metric_name, help)) = parse_metadata_line(trimmed, "# HELP ") {
-                let base_name = extract_base_metric_name(&metric_name);
-                metrics.get_or_create_metric(base_name).help = Some(help);
+                if filter.drops(&metric_name) {
+                    continue;
+                }
+                let metric = metrics.get_or_create_metric(extract_base_metric_name(&metric_name));
+                metric.help = Some(help.to_string());
+                set_family(metric, &metric_name);
                 continue;
             }
 
             // Handle TYPE lines
             if let Some((metric_name, metric_type)) = parse_metadata_line(trimmed, "# TYPE ") {
-                let base_name = extract_base_metric_name(&metric_name);
-                metrics.get_or_create_metric(base_name).metric_type = Some(metric_type);
+                if filter.drops(&metric_name) {
+                    continue;
+                }
+                let metric = metrics.get_or_create_metric(extract_base_metric_name(&metric_name));
+                metric.metric_type = Some(metric_type.to_string());
+                set_family(metric, &metric_name);
+                continue;
+            }
+
+            // Handle UNIT lines
+            if let Some((metric_name, unit)) = parse_metadata_line(trimmed, "# UNIT ") {
+                if filter.drops(&metric_name) {
+                    continue;
+                }
+                let metric = metrics.get_or_create_metric(extract_base_metric_name(&metric_name));
+                metric.unit = Some(unit.to_string());
+                set_family(metric, &metric_name);
                 continue;
             }
 
             // Parse sample line
-            if let Ok(sample) = parse_metric_sample(trimmed) {
-                let base_name = extract_base_metric_name(&sample.name);
-                metrics.get_or_create_metric(base_name).samples.push(sample);
+            let sample = match parse_raw_sample(trimmed, openmetrics) {
+                Ok(sample) => sample,
+                Err(e) => {
+                    errors.push(ParseError {
+                        line: idx + 1,
+                        message: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            if filter.drops(&sample.name) {
+                continue;
             }
+            let metric = metrics.get_or_create_metric(extract_base_metric_name(&sample.name));
+            // `_created` holds the creation time of the series, not a value
+            if openmetrics && sample.name.ends_with("_created") && has_created_series(metric) {
+                continue;
+            }
+            metric.samples.push(sample.into_owned(&mut labels));
         }
 
-        Ok(metrics)
+        (metrics, errors)
     }
 }
 
-/// Parse metadata line (HELP or TYPE)
-/// Returns (metric_name, value) if successful
-fn parse_metadata_line(line: &str, prefix: &str) -> Option<(String, String)> {
-    line.strip_prefix(prefix).and_then(|rest| {
-        rest.find(' ')
-            .map(|idx| (rest[..idx].to_string(), rest[idx + 1..].to_string()))
-    })
+/// Keep the name of a metadata line as the family name, if it is not the base name
+fn set_family(metric: &mut PrometheusMetric, name: &str) {
+    if name != metric.name && metric.family.as_deref() != Some(name) {
+        metric.family = Some(name.to_string());
+    }
 }
 
-/// Parse a single metric sample line
-/// Format: metric_name{label1="value1",label2="value2"} value [timestamp]
-fn parse_metric_sample(line: &str) -> Result<MetricSample> {
-    let (name, labels_str, rest) = if let Some(brace_start) = line.find('{') {
-        // Has labels: extract up to }
-        let brace_end = line
-            .find('}')
-            .ok_or_else(|| anyhow::anyhow!("Missing closing brace in metric line: {}", line))?;
-        let metric_name = line[..brace_start].to_string();
-        let labels_str = &line[brace_start + 1..brace_end];
-        let rest = line[brace_end + 1..].trim();
-        (metric_name, Some(labels_str), rest)
-    } else {
-        // No labels: split on first space
-        let (metric_name, rest) = line
+/// Whether the content is in OpenMetrics format (which is terminated by `# EOF`)
+fn is_openmetrics(content: &str) -> bool {
+    // Searching backwards finds the terminator right away in OpenMetrics input
+    content.starts_with("# EOF") || content.rfind("\n# EOF").is_some()
+}
+
+/// Whether the OpenMetrics family has `_created` samples
+fn has_created_series(metric: &PrometheusMetric) -> bool {
+    matches!(
+        metric.metric_type.as_deref(),
+        Some("counter" | "histogram" | "summary")
+    )
+}
+
+/// Parse metadata line (HELP, TYPE or UNIT)
+/// Returns (metric_name, value) if successful; the name may be quoted
+fn parse_metadata_line<'a>(line: &'a str, prefix: &str) -> Option<(Cow<'a, str>, &'a str)> {
+    let rest = line.strip_prefix(prefix)?;
+    match rest.strip_prefix('"') {
+        Some(quoted) => {
+            let (name, after) = parse_quoted(quoted)?;
+            Some((name, after.strip_prefix(' ')?))
+        }
+        None => rest
             .split_once(' ')
-            .ok_or_else(|| anyhow::anyhow!("Invalid metric format: {}", line))?;
-        (metric_name.to_string(), None, rest.trim())
+            .map(|(name, value)| (Cow::Borrowed(name), value)),
+    }
+}
+
+/// Label pairs borrowed from the scraped text; names and values only own
+/// a string when they contained escapes
+type RawLabels<'a> = Vec<(Cow<'a, str>, Cow<'a, str>)>;
+
+/// Quoted metric name, labels and rest of the line after the closing brace
+type ParsedLabels<'a> = (Option<Cow<'a, str>>, RawLabels<'a>, &'a str);
+
+/// A sample line borrowed from the scraped text
+struct RawSample<'a> {
+    name: Cow<'a, str>,
+    labels: RawLabels<'a>,
+    value: f64,
+    timestamp: Option<i64>,
+}
+
+impl RawSample<'_> {
+    /// Copy the sample out of the scraped text, interning its labels
+    fn into_owned(self, interner: &mut InternBatch) -> MetricSample {
+        let mut labels = Labels::with_capacity(self.labels.len());
+        for (key, value) in self.labels {
+            labels.insert(interner.intern(&key), interner.intern(&value));
+        }
+        MetricSample {
+            name: self.name.into_owned(),
+            labels,
+            value: self.value,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// Parse a single metric sample line
+/// Format: metric_name{label1="value1",label2="value2"} value [timestamp] [# exemplar]
+/// or, with UTF-8 names: {"metric.name","label.1"="value1"} value [timestamp]
+///
+/// With `openmetrics`, the timestamp is in (fractional) seconds.
+#[cfg(test)]
+fn parse_metric_sample(line: &str, openmetrics: bool) -> Result<MetricSample> {
+    let mut labels = interner::labels().batch();
+    parse_raw_sample(line, openmetrics).map(|sample| sample.into_owned(&mut labels))
+}
+
+/// Parse a single metric sample line without copying it
+fn parse_raw_sample(line: &str, openmetrics: bool) -> Result<RawSample<'_>> {
+    let name_end = line
+        .find(|c: char| c == '{' || c.is_whitespace())
+        .ok_or_else(|| anyhow::anyhow!("Invalid metric format: {}", line))?;
+
+    let (quoted_name, labels, rest) = match line[name_end..].strip_prefix('{') {
+        Some(labels_str) => parse_labels(labels_str)?,
+        None => (None, Vec::new(), &line[name_end..]),
     };
+    let name = match (&line[..name_end], quoted_name) {
+        ("", Some(name)) => name,
+        (name, None) if !name.is_empty() => Cow::Borrowed(name),
+        _ => anyhow::bail!("Invalid metric name in line: {}", line),
+    };
+
+    // Drop an OpenMetrics exemplar (`# {trace_id="..."} value [timestamp]`)
+    let rest = rest.split_once('#').map_or(rest, |(sample, _)| sample);
 
     // Parse value and optional timestamp
     let mut parts = rest.split_whitespace();
@@ -131,16 +829,17 @@ fn parse_metric_sample(line: &str) -> Result<MetricSample> {
         .ok_or_else(|| anyhow::anyhow!("Missing value in metric line: {}", line))?
         .parse::<f64>()?;
 
-    let timestamp = parts.next().and_then(|ts| ts.parse::<i64>().ok());
-
-    // Parse labels
-    let labels = if let Some(labels_str) = labels_str {
-        parse_labels(labels_str)?
-    } else {
-        HashMap::new()
-    };
+    let timestamp = parts.next().and_then(|ts| {
+        if openmetrics {
+            ts.parse::<f64>()
+                .ok()
+                .map(|secs| (secs * 1000.0).round() as i64)
+        } else {
+            ts.parse::<i64>().ok()
+        }
+    });
 
-    Ok(MetricSample {
+    Ok(RawSample {
         name,
         labels,
         value,
@@ -148,109 +847,261 @@ fn parse_metric_sample(line: &str) -> Result<MetricSample> {
     })
 }
 
-/// Parse label pairs from a label string
-/// Format: label1="value1",label2="value2"
-fn parse_labels(labels_str: &str) -> Result<HashMap<String, String>> {
-    labels_str
-        .split(',')
-        .filter(|pair| !pair.is_empty())
-        .try_fold(HashMap::new(), |mut acc, pair| {
-            let pair = pair.trim();
-            let (key, val) = pair
-                .split_once('=')
-                .ok_or_else(|| anyhow::anyhow!("Invalid label pair: {}", pair))?;
-
-            let key = key.trim().to_string();
-            let val = val
-                .trim_matches('"')
-                .replace("\\\"", "\"")
-                .replace("\\n", "\n")
-                .replace("\\t", "\t")
-                .replace("\\\\", "\\");
-
-            acc.insert(key, val);
-            Ok(acc)
-        })
+/// Parse label pairs, starting after the opening brace
+/// Format: label1="value1",label2="value2"}
+///
+/// Quoted values are unescaped (`\\`, `\"`, `\n`, `\t`) and may contain commas,
+/// braces and `#`. Label names may be quoted UTF-8 strings (`"label.1"="value1"`),
+/// and a quoted string without a value is the metric name (`{"metric.name",...}`).
+/// Returns the quoted metric name (if any), the labels and the rest of the line
+/// after the closing brace.
+fn parse_labels(input: &str) -> Result<ParsedLabels<'_>> {
+    let mut metric_name = None;
+    let mut labels = Vec::new();
+    let mut rest = input;
+
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix('}') {
+            return Ok((metric_name, labels, after));
+        }
+
+        let (key, after_key, quoted_key) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let (key, after) = parse_quoted(quoted)
+                    .ok_or_else(|| anyhow::anyhow!("Unterminated quoted name: {}", rest))?;
+                (key, after.trim_start(), true)
+            }
+            None => {
+                let end = rest
+                    .find(|c: char| c == '=' || c == ',' || c == '}' || c.is_whitespace())
+                    .unwrap_or(rest.len());
+                (Cow::Borrowed(&rest[..end]), rest[end..].trim_start(), false)
+            }
+        };
+
+        let Some(after_key) = after_key.strip_prefix('=') else {
+            // A quoted string without a value names the metric
+            if quoted_key && metric_name.is_none() && labels.is_empty() {
+                metric_name = Some(key);
+                rest = after_key;
+                if let Some(after) = rest.strip_prefix(',') {
+                    rest = after;
+                }
+                continue;
+            }
+            anyhow::bail!("Invalid label pair: {}", rest);
+        };
+        if key.is_empty() || !quoted_key && key.contains('"') {
+            anyhow::bail!("Invalid label name: {}", key);
+        }
+
+        let quoted = after_key
+            .trim_start()
+            .strip_prefix('"')
+            .ok_or_else(|| anyhow::anyhow!("Unquoted value of label {}", key))?;
+        let (value, after_value) = parse_quoted(quoted)
+            .ok_or_else(|| anyhow::anyhow!("Unterminated value of label {}", key))?;
+        rest = after_value.trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after;
+        } else if !rest.starts_with('}') {
+            anyhow::bail!("Expected ',' or '}}' after value of label {}", key);
+        }
+        labels.push((key, value));
+    }
+}
+
+/// Value of the label `name` of a sample line in the text format
+///
+/// None for samples without the label and for lines whose labels do not
+/// parse; comment lines are the caller's to skip.
+pub fn sample_label<'a>(line: &'a str, name: &str) -> Option<Cow<'a, str>> {
+    let start = line.find('{')?;
+    let (_, labels, _) = parse_labels(&line[start + 1..]).ok()?;
+    labels
+        .into_iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+}
+
+/// Parse the labels of a sample line, for the `parse_labels` fuzz target
+///
+/// `input` starts after the opening brace. A quoted metric name is returned
+/// as the `__name__` label; the rest of the line is dropped.
+#[cfg(feature = "fuzzing")]
+#[doc(hidden)]
+pub fn fuzz_parse_labels(input: &str) -> Result<Vec<(String, String)>> {
+    let (metric_name, labels, _) = parse_labels(input)?;
+    Ok(metric_name
+        .map(|name| (Cow::Borrowed("__name__"), name))
+        .into_iter()
+        .chain(labels)
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect())
+}
+
+/// Unescape a quoted string, starting after the opening quote
+///
+/// Returns the value and the rest after the closing quote, or None if unterminated.
+/// The value borrows from the input unless it contains escapes.
+fn parse_quoted(input: &str) -> Option<(Cow<'_, str>, &str)> {
+    let end = input.find(['"', '\\'])?;
+    if input[end..].starts_with('"') {
+        return Some((Cow::Borrowed(&input[..end]), &input[end + 1..]));
+    }
+
+    let mut value = String::from(&input[..end]);
+    let mut chars = input[end..].char_indices().map(|(idx, ch)| (end + idx, ch));
+    while let Some((idx, ch)) = chars.next() {
+        match ch {
+            '"' => return Some((Cow::Owned(value), &input[idx + 1..])),
+            '\\' => match chars.next()?.1 {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                escaped @ ('\\' | '"') => value.push(escaped),
+                other => {
+                    value.push('\\');
+                    value.push(other);
+                }
+            },
+            _ => value.push(ch),
+        }
+    }
+    None
 }
 
 /// Extract the base metric name from a full metric name (removing suffixes like _total, _count, _bucket, etc.)
-fn extract_base_metric_name(full_name: &str) -> String {
+fn extract_base_metric_name(full_name: &str) -> &str {
     // Common Prometheus suffixes
-    for suffix in &["_total", "_count", "_sum", "_bucket", "_info", "_created"] {
+    for suffix in &[
+        "_total", "_count", "_sum", "_bucket", "_info", "_created", "_gcount", "_gsum",
+    ] {
         if let Some(base) = full_name.strip_suffix(suffix) {
-            return base.to_string();
+            return base;
         }
     }
-    full_name.to_string()
+    full_name
 }
 
-/// Escape label values for Prometheus format (internal helper)
-fn escape_label_value(value: &str) -> String {
-    let mut result = String::new();
-    for ch in value.chars() {
-        match ch {
-            '\\' => result.push_str("\\\\"),
-            '"' => result.push_str("\\\""),
-            '\n' => result.push_str("\\n"),
-            _ => result.push(ch),
-        }
+/// Format a sample value for the Prometheus text format
+///
+/// Special values are written as `NaN`, `+Inf` and `-Inf`.
+pub fn format_value(value: f64) -> String {
+    let mut output = String::new();
+    write_value(&mut output, value);
+    output
+}
+
+/// Append a sample value in the Prometheus text format to `out`
+fn write_value(out: &mut String, value: f64) {
+    if value.is_nan() {
+        out.push_str("NaN");
+    } else if value.is_infinite() {
+        out.push_str(if value > 0.0 { "+Inf" } else { "-Inf" });
+    } else {
+        let _ = write!(out, "{}", value);
     }
-    result
 }
 
-impl PrometheusFormat for PrometheusMetrics {
-    /// Convert parsed Prometheus metrics back to text format
-    fn to_prometheus_format(&self, _sandbox_id: Option<&str>) -> String {
-        let mut output = String::new();
+/// Whether a name can be written without quotes (`[a-zA-Z_:][a-zA-Z0-9_:]*`)
+fn is_legacy_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
 
-        for metric in self.metrics.values() {
-            // Write HELP line if available
-            if let Some(help) = &metric.help {
-                output.push_str(&format!("# HELP {} {}\n", metric.name, help));
-            }
+/// Append a metric or label name to `out`, quoting it if it is not a legacy name
+fn write_name(out: &mut String, name: &str) {
+    if is_legacy_name(name) {
+        out.push_str(name);
+    } else {
+        out.push('"');
+        write_escaped(out, name);
+        out.push('"');
+    }
+}
 
-            // Write TYPE line if available
-            if let Some(metric_type) = &metric.metric_type {
-                output.push_str(&format!("# TYPE {} {}\n", metric.name, metric_type));
+impl PrometheusFormat for PrometheusMetrics {
+    /// Write parsed Prometheus metrics back in text format
+    fn write_prometheus(&self, out: &mut String, _sandbox_id: Option<&str>) {
+        for metric in self.metrics.values() {
+            // Write HELP, TYPE and UNIT lines if available (UNIT is a comment for text format parsers)
+            for (keyword, value) in [
+                ("HELP", &metric.help),
+                ("TYPE", &metric.metric_type),
+                ("UNIT", &metric.unit),
+            ] {
+                if let Some(value) = value {
+                    out.push_str("# ");
+                    out.push_str(keyword);
+                    out.push(' ');
+                    write_name(out, metric.family.as_deref().unwrap_or(&metric.name));
+                    out.push(' ');
+                    out.push_str(value);
+                    out.push('\n');
+                }
             }
 
             // Write samples
             for sample in &metric.samples {
-                output.push_str(&sample.name);
+                // UTF-8 metric names are written as the first element inside the braces
+                let legacy_name = is_legacy_name(&sample.name);
+                let mut first = true;
+                if legacy_name {
+                    out.push_str(&sample.name);
+                    if !sample.labels.is_empty() {
+                        out.push('{');
+                    }
+                } else {
+                    out.push('{');
+                    write_name(out, &sample.name);
+                    first = false;
+                }
 
                 // Write labels if present
-                if !sample.labels.is_empty() {
-                    output.push('{');
-                    let mut first = true;
-                    for (label_name, label_value) in &sample.labels {
-                        if !first {
-                            output.push(',');
-                        }
-                        first = false;
-                        output.push_str(&format!(
-                            "{}=\"{}\"",
-                            label_name,
-                            escape_label_value(label_value)
-                        ));
+                for (label_name, label_value) in &sample.labels {
+                    if !first {
+                        out.push(',');
                     }
-                    output.push('}');
+                    first = false;
+                    write_name(out, label_name);
+                    out.push_str("=\"");
+                    write_escaped(out, label_value);
+                    out.push('"');
+                }
+                if !legacy_name || !sample.labels.is_empty() {
+                    out.push('}');
                 }
 
-                // Write value
-                output.push(' ');
-                output.push_str(&sample.value.to_string());
-
-                // Write timestamp if present
+                // Write value and timestamp if present
+                out.push(' ');
+                write_value(out, sample.value);
                 if let Some(timestamp) = sample.timestamp {
-                    output.push(' ');
-                    output.push_str(&timestamp.to_string());
+                    let _ = write!(out, " {}", timestamp);
                 }
-
-                output.push('\n');
+                out.push('\n');
             }
         }
+    }
 
-        output
+    fn estimated_len(&self) -> usize {
+        self.metrics
+            .values()
+            .map(|metric| {
+                let line = metric.name.len() + 32;
+                let labels = metric.samples.first().map_or(0, |sample| {
+                    sample
+                        .labels
+                        .iter()
+                        .map(|(name, value)| name.len() + value.len() + 4)
+                        .sum()
+                });
+                3 * (line + 64) + metric.samples.len() * (line + labels)
+            })
+            .sum()
     }
 }
 
@@ -283,8 +1134,8 @@ http_requests_total{method="POST",status="201"} 10
         // Metrics are stored by base name (without _total suffix)
         let metric = metrics.metrics.get("http_requests").unwrap();
         assert_eq!(metric.samples.len(), 2);
-        assert_eq!(metric.samples[0].labels.get("method").unwrap(), "GET");
-        assert_eq!(metric.samples[1].labels.get("status").unwrap(), "201");
+        assert_eq!(&*metric.samples[0].labels["method"], "GET");
+        assert_eq!(&*metric.samples[1].labels["status"], "201");
     }
 
     #[test]
@@ -305,10 +1156,10 @@ request_duration_seconds_count 60
     #[test]
     fn test_parse_with_timestamp() {
         let content = "request_total{path=\"/api\"} 42 1234567890";
-        let sample = parse_metric_sample(content).unwrap();
+        let sample = parse_metric_sample(content, false).unwrap();
         assert_eq!(sample.value, 42.0);
         assert_eq!(sample.timestamp, Some(1234567890));
-        assert_eq!(sample.labels.get("path").unwrap(), "/api");
+        assert_eq!(&*sample.labels["path"], "/api");
     }
 
     #[test]
@@ -321,9 +1172,17 @@ requests_total 42
         let output = metrics.to_prometheus_format(None);
 
         // Verify the output contains expected elements
-        assert!(output.contains("# HELP requests Total requests"));
-        assert!(output.contains("# TYPE requests counter"));
+        assert!(output.contains("# HELP requests_total Total requests"));
+        assert!(output.contains("# TYPE requests_total counter"));
         assert!(output.contains("requests_total 42"));
+
+        // OpenMetrics names the family without the suffix
+        let metrics = PrometheusMetrics::parse(
+            "# TYPE requests counter\n# HELP requests Total requests\nrequests_total 42\n# EOF\n",
+        )
+        .unwrap();
+        let output = metrics.to_prometheus_format(None);
+        assert!(output.contains("# TYPE requests counter"));
     }
 
     #[test]
@@ -363,4 +1222,339 @@ test_metric{label1="value3"} 456.78
         assert!(output.contains("1234567890"));
         assert!(output.contains("456.78"));
     }
+
+    #[test]
+    fn test_parse_openmetrics() {
+        let content = r#"# HELP kata_shim_rpc_requests Shim RPC requests
+# TYPE kata_shim_rpc_requests counter
+# UNIT kata_shim_rpc_requests requests
+kata_shim_rpc_requests_total{action="create"} 17 1520879607.789 # {trace_id="KOO5S4vxi0o"} 1 1520879607.123
+kata_shim_rpc_requests_created{action="create"} 1520430000.123
+# TYPE kata_shim_uptime gauge
+kata_shim_uptime 3600 # {span_id="1"} 1
+# EOF
+ignored_after_eof 1
+"#;
+        let metrics = PrometheusMetrics::parse(content).unwrap();
+        assert!(!metrics.metrics.contains_key("ignored_after_eof"));
+
+        let requests = &metrics.metrics["kata_shim_rpc_requests"];
+        assert_eq!(requests.unit.as_deref(), Some("requests"));
+        assert_eq!(requests.samples.len(), 1);
+        assert_eq!(requests.samples[0].value, 17.0);
+        assert_eq!(requests.samples[0].timestamp, Some(1520879607789));
+        assert_eq!(&*requests.samples[0].labels["action"], "create");
+
+        let uptime = &metrics.metrics["kata_shim_uptime"];
+        assert_eq!(uptime.samples[0].value, 3600.0);
+        assert!(uptime.samples[0].labels.is_empty());
+
+        let output = metrics.to_prometheus_format(None);
+        assert!(output.contains("# UNIT kata_shim_rpc_requests requests\n"));
+    }
+
+    #[test]
+    fn test_parse_labels_with_special_characters() {
+        let sample = parse_metric_sample(
+            r#"kata_guest_diskstat{disk="/dev/vda,1",cmd="sh -c \"echo {a,b}\" # x",path="C:\\tmp\n"} 5"#,
+            false,
+        )
+        .unwrap();
+        assert_eq!(sample.value, 5.0);
+        assert_eq!(&*sample.labels["disk"], "/dev/vda,1");
+        assert_eq!(&*sample.labels["cmd"], r#"sh -c "echo {a,b}" # x"#);
+        assert_eq!(&*sample.labels["path"], "C:\\tmp\n");
+
+        // Whitespace and a trailing comma are allowed
+        let sample = parse_metric_sample(r#"m{ a = "1" , b="2", } 1"#, false).unwrap();
+        assert_eq!(sample.labels.len(), 2);
+        assert_eq!(&*sample.labels["a"], "1");
+    }
+
+    #[test]
+    fn test_parse_labels_rejects_malformed_input() {
+        for line in [
+            r#"m{a="1" 1"#,
+            r#"m{a="1} 1"#,
+            r#"m{a=1} 1"#,
+            r#"m{a="1"b="2"} 1"#,
+            r#"m{="1"} 1"#,
+            r#"m{a="1\"} 1"#,
+            r#"m{a="1",b} 1"#,
+        ] {
+            assert!(parse_metric_sample(line, false).is_err(), "{}", line);
+        }
+
+        // A malformed line does not affect the others
+        let metrics = PrometheusMetrics::parse("m{a=\"1} 1\nm{a=\"2\"} 2\n").unwrap();
+        assert_eq!(metrics.metrics["m"].samples.len(), 1);
+    }
+
+    #[test]
+    fn test_histograms() {
+        let content = r#"# TYPE kata_shim_rpc_durations_histogram_milliseconds histogram
+kata_shim_rpc_durations_histogram_milliseconds_bucket{action="create",le="1"} 2
+kata_shim_rpc_durations_histogram_milliseconds_bucket{action="create",le="10"} 6
+kata_shim_rpc_durations_histogram_milliseconds_bucket{action="create",le="+Inf"} 8
+kata_shim_rpc_durations_histogram_milliseconds_sum{action="create"} 120
+kata_shim_rpc_durations_histogram_milliseconds_count{action="create"} 8
+kata_shim_rpc_durations_histogram_milliseconds_bucket{action="wait",le="1"} 0
+kata_shim_rpc_durations_histogram_milliseconds_bucket{action="wait",le="+Inf"} 0
+"#;
+        let metrics = PrometheusMetrics::parse(content).unwrap();
+        let histograms =
+            metrics.metrics["kata_shim_rpc_durations_histogram_milliseconds"].histograms();
+        assert_eq!(histograms.len(), 2);
+
+        let create = &histograms[0];
+        assert_eq!(&*create.labels["action"], "create");
+        assert!(!create.labels.contains_key("le"));
+        assert_eq!(
+            create.buckets,
+            vec![(1.0, 2.0), (10.0, 6.0), (f64::INFINITY, 8.0)]
+        );
+        assert_eq!(create.sum, Some(120.0));
+        // Rank 4 is halfway through the (1, 10] bucket
+        assert_eq!(create.quantile(0.5), Some(5.5));
+        // Ranks in the +Inf bucket report the highest finite bound
+        assert_eq!(create.quantile(0.99), Some(10.0));
+
+        let wait = &histograms[1];
+        assert_eq!(wait.quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_summaries() {
+        let content = r#"# TYPE go_gc_duration_seconds summary
+go_gc_duration_seconds{quantile="0.5"} 0.002
+go_gc_duration_seconds{quantile="0"} 0.001
+go_gc_duration_seconds{quantile="1"} 0.01
+go_gc_duration_seconds_sum 0.5
+go_gc_duration_seconds_count 100
+"#;
+        let metrics = PrometheusMetrics::parse(content).unwrap();
+        let summaries = metrics.metrics["go_gc_duration_seconds"].summaries();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(
+            summaries[0].quantiles,
+            vec![(0.0, 0.001), (0.5, 0.002), (1.0, 0.01)]
+        );
+        assert_eq!(summaries[0].sum, Some(0.5));
+        assert_eq!(summaries[0].count, Some(100.0));
+    }
+
+    #[test]
+    fn test_labels_are_interned_across_parses() {
+        let content = "kata_guest_meminfo{item=\"memfree\"} 1\nkata_guest_load{item=\"load1\"} 2\n";
+        let first = PrometheusMetrics::parse(content).unwrap();
+        let second = PrometheusMetrics::parse(content).unwrap();
+
+        let key = |metrics: &PrometheusMetrics, name: &str| {
+            let (key, _) = metrics.metrics[name].samples[0]
+                .labels
+                .get_key_value("item")
+                .unwrap();
+            key.clone()
+        };
+        assert!(Arc::ptr_eq(
+            &key(&first, "kata_guest_meminfo"),
+            &key(&first, "kata_guest_load")
+        ));
+        assert!(Arc::ptr_eq(
+            &key(&first, "kata_guest_meminfo"),
+            &key(&second, "kata_guest_meminfo")
+        ));
+    }
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let content = "# TYPE m gauge\nm{a=\"1\"} 1\nm{a=\"2\" 2\nm{a=\"3\"} three\nm 4\n";
+        let (metrics, errors) = PrometheusMetrics::parse_with_diagnostics(content);
+        assert_eq!(metrics.metrics["m"].samples.len(), 2);
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 3);
+        assert_eq!(errors[1].line, 4);
+        assert!(errors[1].to_string().starts_with("line 4: "));
+    }
+
+    #[test]
+    fn test_special_values() {
+        let content = "m{a=\"nan\"} NaN\nm{a=\"pos\"} +Inf\nm{a=\"neg\"} -Inf\nm{a=\"one\"} 1\n";
+        let (metrics, errors) = PrometheusMetrics::parse_with_diagnostics(content);
+        assert!(errors.is_empty());
+
+        let metric = &metrics.metrics["m"];
+        assert_eq!(metric.samples.len(), 4);
+        assert_eq!(metric.finite_samples().count(), 1);
+
+        let output = metrics.to_prometheus_format(None);
+        assert!(output.contains("m{a=\"nan\"} NaN\n"));
+        assert!(output.contains("m{a=\"pos\"} +Inf\n"));
+        assert!(output.contains("m{a=\"neg\"} -Inf\n"));
+    }
+
+    #[test]
+    fn test_utf8_names() {
+        let content = r#"# HELP "kata.shim.rpc" RPCs by name
+# TYPE "kata.shim.rpc" gauge
+{"kata.shim.rpc","rpc.name"="Create",plain="x"} 3
+{"kata.shim.rpc"} 4
+kata_plain{"with space"="1"} 5
+"#;
+        let (metrics, errors) = PrometheusMetrics::parse_with_diagnostics(content);
+        assert!(errors.is_empty(), "{:?}", errors);
+
+        let rpc = &metrics.metrics["kata.shim.rpc"];
+        assert_eq!(rpc.metric_type.as_deref(), Some("gauge"));
+        assert_eq!(rpc.samples.len(), 2);
+        assert_eq!(&*rpc.samples[0].labels["rpc.name"], "Create");
+        assert_eq!(&*rpc.samples[0].labels["plain"], "x");
+        assert_eq!(
+            &*metrics.metrics["kata_plain"].samples[0].labels["with space"],
+            "1"
+        );
+
+        let output = metrics.to_prometheus_format(None);
+        assert!(output.contains("# TYPE \"kata.shim.rpc\" gauge\n"));
+        assert!(output.contains("{\"kata.shim.rpc\"} 4\n"));
+        assert!(output.contains("kata_plain{\"with space\"=\"1\"} 5\n"));
+
+        // The output parses back to the same samples
+        let reparsed = PrometheusMetrics::parse(&output).unwrap();
+        assert_eq!(reparsed.metrics["kata.shim.rpc"].samples.len(), 2);
+
+        // A second quoted name or a name on both sides is rejected
+        assert!(parse_metric_sample(r#"{"a.b","c.d"} 1"#, false).is_err());
+        assert!(parse_metric_sample(r#"m{"a.b"} 1"#, false).is_err());
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        let (_, errors) = PrometheusMetrics::parse_with_diagnostics("ok 1\nbroken{ 2\n");
+        assert_eq!(errors.len(), 1);
+        assert!(ParseMode::Lenient.check(&errors).is_ok());
+        let err = ParseMode::Strict.check(&errors).unwrap_err().to_string();
+        assert!(err.starts_with("1 malformed metric line(s), first at line 2:"));
+        assert!(ParseMode::Strict.check(&[]).is_ok());
+
+        assert_eq!("strict".parse::<ParseMode>().unwrap(), ParseMode::Strict);
+        assert!("pedantic".parse::<ParseMode>().is_err());
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let content = "# TYPE m gauge\nm{a=\"x\"} 1.5\nm{a=\"nan\"} NaN\nm{a=\"inf\"} +Inf\n";
+        let metrics = PrometheusMetrics::parse(content).unwrap();
+
+        let json = serde_json::to_string(&metrics).unwrap();
+        assert!(json.contains(r#""value":"NaN""#));
+        assert!(json.contains(r#""value":"+Inf""#));
+
+        let restored: PrometheusMetrics = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            restored.to_prometheus_format(None),
+            metrics.to_prometheus_format(None)
+        );
+        // Restored labels share the interned strings of parsed ones
+        let label = |m: &PrometheusMetrics| m.metrics["m"].samples[0].labels.keys().next().cloned();
+        assert!(Arc::ptr_eq(
+            &label(&metrics).unwrap(),
+            &label(&restored).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut metrics = PrometheusMetrics::parse(
+            "# TYPE reqs_total counter\nreqs_total{a=\"1\"} 1\n# TYPE mem gauge\nmem 10\n",
+        )
+        .unwrap();
+        let other = PrometheusMetrics::parse(
+            "# HELP reqs_total Requests\nreqs_total{a=\"1\"} 5\nreqs_total{a=\"2\"} 2\n# TYPE mem counter\nmem 20\nnew_metric 3\n",
+        )
+        .unwrap();
+
+        let conflicts = metrics.merge(other);
+        assert_eq!(conflicts.len(), 2);
+        assert!(conflicts.iter().any(
+            |c| c.to_string() == "mem: type counter conflicts with gauge, dropped 1 sample(s)"
+        ));
+
+        let reqs = &metrics.metrics["reqs"];
+        assert_eq!(reqs.metric_type.as_deref(), Some("counter"));
+        assert_eq!(reqs.help.as_deref(), Some("Requests"));
+        assert_eq!(reqs.samples.len(), 2);
+        assert_eq!(reqs.samples[0].value, 5.0);
+        assert_eq!(metrics.metrics["mem"].samples[0].value, 10.0);
+        assert!(metrics.metrics.contains_key("new_metric"));
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = PrometheusMetrics::parse(
+            "# TYPE reqs_total counter\nreqs_total{a=\"1\"} 10\nreqs_total{a=\"2\"} 50\n# TYPE mem gauge\nmem 100\n# TYPE lat summary\nlat{quantile=\"0.5\"} 1\nlat_count 4\n",
+        )
+        .unwrap();
+        let current = PrometheusMetrics::parse(
+            "# TYPE reqs_total counter\nreqs_total{a=\"1\"} 15\nreqs_total{a=\"2\"} 3\nreqs_total{a=\"3\"} 7\n# TYPE mem gauge\nmem 80\n# TYPE lat summary\nlat{quantile=\"0.5\"} 2\nlat_count 6\n",
+        )
+        .unwrap();
+
+        let deltas = current.diff(&previous);
+        let value = |name: &str, label: Option<&str>| {
+            deltas.metrics[name]
+                .samples
+                .iter()
+                .find(|s| label.is_none_or(|l| &*s.labels["a"] == l))
+                .map(|s| s.value)
+        };
+        assert_eq!(value("reqs", Some("1")), Some(5.0));
+        // Counter reset: the delta is the value since the reset
+        assert_eq!(value("reqs", Some("2")), Some(3.0));
+        // New series have no delta yet
+        assert_eq!(deltas.metrics["reqs"].samples.len(), 2);
+        assert_eq!(value("mem", None), Some(-20.0));
+        // Only the summary count is diffed
+        assert_eq!(deltas.metrics["lat"].samples.len(), 1);
+        assert_eq!(deltas.metrics["lat"].samples[0].value, 2.0);
+    }
+
+    #[test]
+    fn test_partition_by() {
+        let metrics = PrometheusMetrics::parse(
+            "# TYPE mem gauge\nmem{pod=\"a\"} 1\nmem{pod=\"b\"} 2\n# TYPE cpu_total counter\ncpu_total{pod=\"a\"} 3\n",
+        )
+        .unwrap();
+        let groups =
+            metrics.partition_by(|sample| sample.labels.get("pod").map(|pod| pod.to_string()));
+        assert_eq!(groups.len(), 2);
+        let a = &groups[&Some("a".to_string())];
+        assert_eq!(a.metrics.len(), 2);
+        assert_eq!(a.metrics["cpu"].metric_type.as_deref(), Some("counter"));
+        assert_eq!(
+            groups[&Some("b".to_string())].metrics["mem"].samples[0].value,
+            2.0
+        );
+    }
+
+    #[test]
+    fn test_parse_filtered() {
+        let content = r#"# HELP kata_firecracker_seccomp Seccomp counters
+# TYPE kata_firecracker_seccomp gauge
+kata_firecracker_seccomp{item="num_faults"} 0
+go_goroutines 12
+{"go_threads"} 9
+kata_guest_load{item="load1"} 0.5
+"#;
+        let filter = NameFilter::new(["kata_firecracker_", "go_", ""]);
+        let (metrics, errors) = PrometheusMetrics::parse_filtered(content, &filter);
+        assert!(errors.is_empty());
+        assert_eq!(
+            metrics.metrics.keys().collect::<Vec<_>>(),
+            vec!["kata_guest_load"]
+        );
+
+        // The empty prefix does not drop everything
+        assert!(!NameFilter::new([""]).drops("kata_guest_load"));
+    }
 }
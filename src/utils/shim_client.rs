@@ -1,93 +1,695 @@
-use crate::config;
-use anyhow::Result;
+use anyhow::{Context, Result};
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::client::conn::http1::{self, SendRequest};
+use hyper::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, HOST, LOCATION};
+use hyper::{Request, StatusCode};
+use hyper_util::rt::TokioIo;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tracing::debug;
 
-const DEFAULT_TIMEOUT: Duration = Duration::from_secs(3);
+use super::buffer_pool::{BufferPool, BufferPoolStats, PooledBuffer};
 
-/// Performs an HTTP GET request to the shim monitor socket
-pub async fn do_get(sandbox_id: &str, path: &str) -> Result<Vec<u8>> {
-    do_get_with_timeout(sandbox_id, DEFAULT_TIMEOUT, path).await
+/// Configuration for requests to the shim monitor socket
+#[derive(Clone, Debug)]
+pub struct ShimClientConfig {
+    /// Timeout for the whole exchange, including retries
+    pub timeout: Duration,
+
+    /// Maximum number of retries for connection failures
+    pub max_retries: u32,
+
+    /// Backoff before the first retry, doubled for each further retry
+    pub retry_backoff: Duration,
+
+    /// Upper bound for the backoff between retries
+    pub max_backoff: Duration,
+
+    /// Randomize each backoff to 50-100% of its value
+    pub jitter: bool,
+
+    /// Largest response body accepted, in bytes (after gzip decoding)
+    pub max_payload_bytes: usize,
+}
+
+impl Default for ShimClientConfig {
+    fn default() -> Self {
+        ShimClientConfig {
+            timeout: Duration::from_secs(3),
+            max_retries: 2,
+            retry_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            jitter: true,
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+        }
+    }
+}
+
+impl ShimClientConfig {
+    /// Set the maximum retries
+    pub fn with_max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Set the initial retry backoff
+    pub fn with_retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    /// Set the largest accepted response body
+    pub fn with_max_payload_bytes(mut self, bytes: usize) -> Self {
+        self.max_payload_bytes = bytes;
+        self
+    }
+
+    /// Backoff before retry number `attempt` (1-based), without jitter
+    fn backoff_for(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(16);
+        self.retry_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Backoff before retry number `attempt`, with jitter applied if enabled
+    fn jittered_backoff_for(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff_for(attempt);
+        if self.jitter {
+            backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+        } else {
+            backoff
+        }
+    }
+}
+
+/// Maximum number of redirects followed for a single request
+const MAX_REDIRECTS: usize = 3;
+
+/// Default largest accepted response body (16 MiB)
+pub const DEFAULT_MAX_PAYLOAD_BYTES: usize = 16 * 1024 * 1024;
+
+/// A shim response body larger than [`ShimClientConfig::max_payload_bytes`]
+///
+/// The body is discarded as soon as the limit is crossed, and the connection
+/// is closed instead of pooled.
+#[derive(Debug)]
+pub struct PayloadTooLarge {
+    /// The configured limit in bytes
+    pub limit: usize,
+}
+
+impl fmt::Display for PayloadTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "shim response exceeds the {} byte payload limit",
+            self.limit
+        )
+    }
+}
+
+impl std::error::Error for PayloadTooLarge {}
+
+/// Request handle of an HTTP/1 connection to a shim monitor socket
+type ShimConnection = SendRequest<Empty<Bytes>>;
+
+/// HTTP client for shim monitor sockets
+///
+/// Keeps at most one idle keep-alive connection per sandbox, so periodic
+/// scrapes reuse the socket instead of reconnecting every cycle. A pooled
+/// connection that was closed by the shim is replaced transparently.
+/// Response bodies are read into buffers from a pool shared by all scrapes.
+pub struct ShimClient {
+    config: ShimClientConfig,
+    idle: Mutex<HashMap<String, ShimConnection>>,
+    buffers: Arc<BufferPool>,
 }
 
-/// Performs an HTTP GET request with custom timeout
-pub async fn do_get_with_timeout(
-    sandbox_id: &str,
-    timeout: Duration,
-    path: &str,
-) -> Result<Vec<u8>> {
-    let socket_address = config::client_socket_address(sandbox_id)?;
+impl ShimClient {
+    /// Create a new shim client with the given configuration
+    pub fn new(config: ShimClientConfig) -> Self {
+        ShimClient {
+            config,
+            idle: Mutex::new(HashMap::new()),
+            buffers: Arc::new(BufferPool::default()),
+        }
+    }
+
+    /// Get the client configuration
+    pub fn config(&self) -> &ShimClientConfig {
+        &self.config
+    }
+
+    /// Size and reuse rate of the response buffer pool
+    pub fn buffer_stats(&self) -> BufferPoolStats {
+        self.buffers.stats()
+    }
+
+    /// Performs an HTTP GET request to the shim monitor socket at `socket_path`
+    ///
+    /// The default timeout is `config().timeout`; callers on a latency budget
+    /// (the on-demand scrape path) pass a shorter one. The body is returned in
+    /// a pooled buffer; drop it once parsed so the next scrape can reuse it.
+    pub async fn get(
+        &self,
+        sandbox_id: &str,
+        socket_path: &Path,
+        path: &str,
+        timeout: Duration,
+    ) -> Result<PooledBuffer> {
+        let socket_path = socket_path.to_string_lossy();
+
+        // The timeout covers the whole exchange (retries included) so a hung
+        // shim cannot stall the caller indefinitely
+        tokio::time::timeout(timeout, self.request(sandbox_id, &socket_path, path))
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "timed out after {:?} reading from http://shim{}",
+                    timeout,
+                    path
+                )
+            })?
+    }
+
+    /// Drop idle connections of sandboxes that are no longer tracked
+    pub fn retain(&self, sandbox_ids: &[String]) {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        idle.retain(|id, _| sandbox_ids.contains(id));
+    }
+
+    fn take_idle(&self, sandbox_id: &str) -> Option<ShimConnection> {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        idle.remove(sandbox_id)
+    }
+
+    fn put_idle(&self, sandbox_id: &str, conn: ShimConnection) {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        idle.insert(sandbox_id.to_string(), conn);
+    }
 
-    // Parse the socket address to extract the path
-    let socket_path = if let Some(path) = socket_address.strip_prefix("unix://") {
-        path
-    } else {
-        &socket_address
-    };
+    /// Send a GET request, following redirects within the shim socket
+    async fn request(
+        &self,
+        sandbox_id: &str,
+        socket_path: &str,
+        path: &str,
+    ) -> Result<PooledBuffer> {
+        let mut path = path.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self.send(sandbox_id, socket_path, &path).await?;
 
-    // Create a URI for the HTTP request
-    let uri = format!("http://shim{}", path);
+            if response.status.is_redirection() {
+                let location = response.location.ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "HTTP status {} from http://shim{} without Location",
+                        response.status,
+                        path
+                    )
+                })?;
+                debug!(sandbox_id = %sandbox_id, from = %path, to = %location, "Following shim redirect");
+                path = redirect_path(&location)?;
+                continue;
+            }
 
-    // Use Unix socket connector
-    let response = do_http_get_unix_socket(socket_path, &uri, timeout).await?;
+            // Only accept 200 OK
+            if response.status != StatusCode::OK {
+                return Err(anyhow::anyhow!(
+                    "unexpected HTTP status {} from http://shim{}",
+                    response.status,
+                    path
+                ));
+            }
+
+            return Ok(response.body);
+        }
+
+        Err(anyhow::anyhow!(
+            "too many redirects (>{}) from http://shim{}",
+            MAX_REDIRECTS,
+            path
+        ))
+    }
+
+    /// Send a single GET request, reusing the idle connection of the sandbox if any
+    async fn send(&self, sandbox_id: &str, socket_path: &str, path: &str) -> Result<ShimResponse> {
+        if let Some(conn) = self.take_idle(sandbox_id) {
+            match self.exchange(sandbox_id, conn, path).await {
+                Ok(response) => return Ok(response),
+                // The shim answered; asking again would only refetch the same body
+                Err(e) if e.is::<PayloadTooLarge>() => return Err(e),
+                // The shim closed the idle connection; fall through to a fresh one
+                Err(e) => {
+                    debug!(sandbox_id = %sandbox_id, error = %e, "Pooled shim connection failed, reconnecting")
+                }
+            }
+        }
+
+        let conn = connect(socket_path, &self.config).await?;
+        self.exchange(sandbox_id, conn, path).await
+    }
+
+    /// Run one request/response exchange and return the connection to the pool
+    async fn exchange(
+        &self,
+        sandbox_id: &str,
+        mut conn: ShimConnection,
+        path: &str,
+    ) -> Result<ShimResponse> {
+        conn.ready().await?;
+        let request = Request::get(path)
+            .header(HOST, "shim")
+            .header(ACCEPT_ENCODING, "gzip")
+            .body(Empty::<Bytes>::new())?;
+        let response = conn.send_request(request).await?;
+
+        let status = response.status();
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let gzipped = response
+            .headers()
+            .get(CONTENT_ENCODING)
+            .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"gzip"));
+
+        // Reject a declared oversized body before reading any of it; chunked
+        // bodies are checked as they arrive. Either way `conn` is dropped
+        // rather than pooled, since the rest of the body is left unread.
+        let limit = self.config.max_payload_bytes;
+        let content_length = response
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if content_length.is_some_and(|length| length > limit as u64) {
+            return Err(PayloadTooLarge { limit }.into());
+        }
+        let mut body = self.buffers.take();
+        let mut incoming = response.into_body();
+        while let Some(frame) = incoming.frame().await {
+            if let Some(data) = frame?.data_ref() {
+                if body.len() + data.len() > limit {
+                    return Err(PayloadTooLarge { limit }.into());
+                }
+                body.extend_from_slice(data);
+            }
+        }
+
+        if !conn.is_closed() {
+            self.put_idle(sandbox_id, conn);
+        }
+
+        let body = if gzipped {
+            let mut decoded = self.buffers.take();
+            decode_gzip(&body, &mut decoded, limit)
+                .context("failed to decompress gzip response")?;
+            decoded
+        } else {
+            body
+        };
+
+        Ok(ShimResponse {
+            status,
+            location,
+            body,
+        })
+    }
+}
 
-    Ok(response)
+/// A response from a shim monitor socket
+struct ShimResponse {
+    status: StatusCode,
+    location: Option<String>,
+    body: PooledBuffer,
 }
 
-/// Perform HTTP GET over Unix socket
-async fn do_http_get_unix_socket(
-    socket_path: &str,
-    uri: &str,
-    timeout: Duration,
-) -> Result<Vec<u8>> {
-    use tokio::net::UnixStream;
-
-    let request = format!(
-        "GET {} HTTP/1.1\r\nHost: shim\r\nConnection: close\r\n\r\n",
-        uri
-    );
-
-    // Connect to Unix socket with timeout
-    let mut stream = tokio::time::timeout(timeout, UnixStream::connect(socket_path)).await??;
-
-    // Send request
-    stream.write_all(request.as_bytes()).await?;
-
-    // Read response
-    let mut buffer = Vec::new();
-    stream.read_to_end(&mut buffer).await?;
-
-    // Parse HTTP response to extract body
-    let response_str = String::from_utf8_lossy(&buffer);
-
-    // Validate HTTP status code
-    // Parse the status line: "HTTP/1.1 200 OK" or similar
-    let status_line = response_str.lines().next().unwrap_or("");
-    let status_code = if let Some(code_str) = status_line.split_whitespace().nth(1) {
-        code_str
-    } else {
-        return Err(anyhow::anyhow!(
-            "malformed HTTP response from {}: missing status code in line: {}",
-            uri,
-            status_line
-        ));
-    };
-
-    // Only accept 200 OK (exact match, not substring)
-    if status_code != "200" {
-        return Err(anyhow::anyhow!(
-            "unexpected HTTP status {} from {}: {}",
-            status_code,
-            uri,
-            status_line
-        ));
-    }
-
-    // Find the body (after empty line)
-    if let Some(body_start) = response_str.find("\r\n\r\n") {
-        Ok(response_str[body_start + 4..].as_bytes().to_vec())
-    } else {
-        // Return empty body for responses with no body
-        Ok(vec![])
+/// Decompress a gzip-encoded response body into `decoded`, up to `limit` bytes
+///
+/// Stops decoding as soon as the limit is crossed, so a small compressed body
+/// cannot expand into an unbounded buffer.
+fn decode_gzip(body: &[u8], decoded: &mut Vec<u8>, limit: usize) -> Result<()> {
+    decoded.reserve((body.len() * 4).min(limit));
+    flate2::read::GzDecoder::new(body)
+        .take(limit as u64 + 1)
+        .read_to_end(decoded)?;
+    if decoded.len() > limit {
+        return Err(PayloadTooLarge { limit }.into());
+    }
+    Ok(())
+}
+
+/// Resolve a redirect target to a path on the same shim socket
+fn redirect_path(location: &str) -> Result<String> {
+    if location.starts_with('/') {
+        return Ok(location.to_string());
+    }
+    match location.strip_prefix("http://shim") {
+        Some(path) if path.starts_with('/') => Ok(path.to_string()),
+        _ => Err(anyhow::anyhow!(
+            "refusing to follow redirect outside the shim socket: {}",
+            location
+        )),
+    }
+}
+
+/// Connect to the shim socket and perform the HTTP/1 handshake
+async fn connect(socket_path: &str, config: &ShimClientConfig) -> Result<ShimConnection> {
+    // Connect to Unix socket (retrying while the shim is not ready yet)
+    let stream = connect_with_retry(socket_path, config).await?;
+    let (conn, connection) = http1::handshake(TokioIo::new(stream)).await?;
+
+    // Drive the connection until it is closed or the request handle is dropped
+    let socket_path = socket_path.to_string();
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            debug!(socket_path = %socket_path, error = %e, "Shim connection closed with error");
+        }
+    });
+
+    Ok(conn)
+}
+
+/// Whether a connection error is worth retrying
+///
+/// The shim socket may not exist yet or refuse connections while the VM is starting.
+fn is_transient_connect_error(err: &std::io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::NotFound
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::WouldBlock
+            | ErrorKind::Interrupted
+    )
+}
+
+/// Connect to the shim socket, retrying transient failures with exponential backoff
+async fn connect_with_retry(socket_path: &str, config: &ShimClientConfig) -> Result<UnixStream> {
+    let mut attempt = 0;
+    loop {
+        match UnixStream::connect(socket_path).await {
+            Ok(stream) => return Ok(stream),
+            Err(e) if attempt < config.max_retries && is_transient_connect_error(&e) => {
+                attempt += 1;
+                let backoff = config.jittered_backoff_for(attempt);
+                debug!(
+                    socket_path = %socket_path,
+                    error = %e,
+                    attempt = attempt,
+                    max_retries = config.max_retries,
+                    backoff_ms = backoff.as_millis(),
+                    "Failed to connect to shim socket, retrying..."
+                );
+                tokio::time::sleep(backoff).await;
+            }
+            Err(e) => {
+                return Err(anyhow::anyhow!(
+                    "failed to connect to {} after {} retries: {}",
+                    socket_path,
+                    attempt,
+                    e
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    #[test]
+    fn test_backoff_is_exponential_and_capped() {
+        let config = ShimClientConfig {
+            retry_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(500),
+            ..Default::default()
+        };
+        assert_eq!(config.backoff_for(1), Duration::from_millis(100));
+        assert_eq!(config.backoff_for(2), Duration::from_millis(200));
+        assert_eq!(config.backoff_for(3), Duration::from_millis(400));
+        assert_eq!(config.backoff_for(4), Duration::from_millis(500));
+        assert_eq!(config.backoff_for(100), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_jitter_stays_within_bounds() {
+        let config = ShimClientConfig::default();
+        for attempt in 1..=4 {
+            let backoff = config.jittered_backoff_for(attempt);
+            assert!(backoff <= config.backoff_for(attempt));
+            assert!(backoff >= config.backoff_for(attempt) / 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_retries_until_socket_appears() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("shim.sock");
+        let config = ShimClientConfig::default()
+            .with_max_retries(5)
+            .with_retry_backoff(Duration::from_millis(20));
+
+        let path = socket_path.clone();
+        let listener = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            let listener = tokio::net::UnixListener::bind(&path).unwrap();
+            listener.accept().await.unwrap();
+        });
+
+        let stream = connect_with_retry(socket_path.to_str().unwrap(), &config).await;
+        assert!(stream.is_ok());
+        listener.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connect_gives_up_after_max_retries() {
+        let config = ShimClientConfig::default()
+            .with_max_retries(2)
+            .with_retry_backoff(Duration::from_millis(1));
+
+        let err = connect_with_retry("/nonexistent/shim.sock", &config)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("after 2 retries"));
+    }
+
+    /// Serve canned responses on a Unix socket, counting accepted connections
+    fn spawn_shim(
+        socket_path: &std::path::Path,
+        responses: Vec<Vec<u8>>,
+        close_after_each: bool,
+    ) -> Arc<AtomicUsize> {
+        let listener = tokio::net::UnixListener::bind(socket_path).unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let counter = connections.clone();
+        tokio::spawn(async move {
+            let mut responses = responses.into_iter();
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut conn = BufReader::new(stream);
+                loop {
+                    // Read the request headers
+                    let mut got_request = false;
+                    let mut line = String::new();
+                    while conn.read_line(&mut line).await.unwrap_or(0) > 0 {
+                        got_request = true;
+                        if line == "\r\n" {
+                            break;
+                        }
+                        line.clear();
+                    }
+                    let Some(response) = responses.next().filter(|_| got_request) else {
+                        break;
+                    };
+                    conn.get_mut().write_all(&response).await.unwrap();
+                    if close_after_each {
+                        break;
+                    }
+                }
+            }
+        });
+        connections
+    }
+
+    const CHUNKED: &str = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n";
+    const SIZED: &str = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+
+    #[tokio::test]
+    async fn test_connection_is_reused() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("shim.sock");
+        let connections = spawn_shim(&socket_path, vec![SIZED.into(), CHUNKED.into()], false);
+        let client = ShimClient::new(ShimClientConfig::default());
+        let socket = socket_path.to_str().unwrap();
+
+        let body = client.request("sb-1", socket, "/metrics").await.unwrap();
+        assert_eq!(&body[..], b"hello");
+        let body = client.request("sb-1", socket, "/metrics").await.unwrap();
+        assert_eq!(&body[..], b"hello world");
+        assert_eq!(connections.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reconnects_when_pooled_connection_closed() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("shim.sock");
+        let connections = spawn_shim(&socket_path, vec![SIZED.into(), SIZED.into()], true);
+        let client = ShimClient::new(ShimClientConfig::default());
+        let socket = socket_path.to_str().unwrap();
+
+        client.request("sb-1", socket, "/metrics").await.unwrap();
+        let body = client.request("sb-1", socket, "/metrics").await.unwrap();
+        assert_eq!(&body[..], b"hello");
+        assert_eq!(connections.load(Ordering::SeqCst), 2);
+        // The first body was dropped, so its buffer was reused for the second
+        assert_eq!(client.buffer_stats().reused, 1);
+    }
+
+    #[tokio::test]
+    async fn test_non_200_status_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("shim.sock");
+        spawn_shim(
+            &socket_path,
+            vec!["HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".into()],
+            false,
+        );
+        let client = ShimClient::new(ShimClientConfig::default());
+
+        let err = client
+            .request("sb-1", socket_path.to_str().unwrap(), "/metrics")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("unexpected HTTP status 404"));
+    }
+
+    #[tokio::test]
+    async fn test_follows_redirect_on_same_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("shim.sock");
+        spawn_shim(
+            &socket_path,
+            vec![
+                "HTTP/1.1 307 Temporary Redirect\r\nLocation: /metrics/v2\r\nContent-Length: 0\r\n\r\n".into(),
+                SIZED.into(),
+            ],
+            false,
+        );
+        let client = ShimClient::new(ShimClientConfig::default());
+
+        let body = client
+            .request("sb-1", socket_path.to_str().unwrap(), "/metrics")
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_gzip_response_is_decompressed() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        let payload = "kata_hypervisor_fds 42\n".repeat(100);
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(payload.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            compressed.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(&compressed);
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("shim.sock");
+        spawn_shim(&socket_path, vec![response], false);
+        let client = ShimClient::new(ShimClientConfig::default());
+
+        let body = client
+            .request("sb-1", socket_path.to_str().unwrap(), "/metrics")
+            .await
+            .unwrap();
+        assert_eq!(&body[..], payload.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_payload_is_rejected() {
+        use flate2::write::GzEncoder;
+        use std::io::Write;
+
+        // 10 bytes compress well below the limit but expand past it
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&[b'a'; 10]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut gzipped = format!(
+            "HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+            compressed.len()
+        )
+        .into_bytes();
+        gzipped.extend_from_slice(&compressed);
+
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("shim.sock");
+        let connections = spawn_shim(
+            &socket_path,
+            vec![SIZED.into(), CHUNKED.into(), gzipped],
+            true,
+        );
+        let config = ShimClientConfig::default().with_max_payload_bytes(4);
+        let client = ShimClient::new(config);
+        let socket = socket_path.to_str().unwrap();
+
+        // Declared by Content-Length, chunked, and expanded by gzip
+        for _ in 0..3 {
+            let err = client
+                .request("sb-1", socket, "/metrics")
+                .await
+                .unwrap_err();
+            assert!(
+                err.chain().any(|cause| cause.is::<PayloadTooLarge>()),
+                "{:#}",
+                err
+            );
+            assert!(client.take_idle("sb-1").is_none());
+        }
+        assert_eq!(connections.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_redirect_path() {
+        assert_eq!(redirect_path("/metrics").unwrap(), "/metrics");
+        assert_eq!(redirect_path("http://shim/metrics").unwrap(), "/metrics");
+        assert!(redirect_path("http://example.com/metrics").is_err());
+        assert!(redirect_path("http://shim.evil/metrics").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_retain_drops_idle_connections() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket_path = dir.path().join("shim.sock");
+        spawn_shim(&socket_path, vec![SIZED.into()], false);
+        let client = ShimClient::new(ShimClientConfig::default());
+
+        client
+            .request("sb-1", socket_path.to_str().unwrap(), "/metrics")
+            .await
+            .unwrap();
+        client.retain(&["sb-2".to_string()]);
+        assert!(client.take_idle("sb-1").is_none());
     }
 }
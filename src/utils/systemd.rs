@@ -0,0 +1,99 @@
+//! systemd service notifications (sd_notify protocol)
+//!
+//! Responsibilities:
+//! - Tell systemd the service is ready (`READY=1`) once, after the first
+//!   collection cycle
+//! - Send watchdog heartbeats (`WATCHDOG=1`) so systemd restarts a wedged
+//!   pipeline when `WatchdogSec=` is set
+//!
+//! Messages are datagrams to `NOTIFY_SOCKET` (a path, or an abstract socket
+//! name starting with `@`).
+
+use anyhow::{Context, Result};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Sends state notifications to the systemd service manager
+#[derive(Debug)]
+pub struct SystemdNotify {
+    socket: String,
+    watchdog_interval: Option<Duration>,
+    ready_sent: AtomicBool,
+}
+
+impl SystemdNotify {
+    /// Notify through `socket`; `watchdog_usec` is the `WatchdogSec=` of the
+    /// service in microseconds, heartbeats are sent at half of it
+    pub fn new(socket: impl Into<String>, watchdog_usec: Option<u64>) -> Self {
+        SystemdNotify {
+            socket: socket.into(),
+            watchdog_interval: watchdog_usec
+                .filter(|usec| *usec > 0)
+                .map(|usec| Duration::from_micros(usec / 2)),
+            ready_sent: AtomicBool::new(false),
+        }
+    }
+
+    /// Interval of the watchdog heartbeats, None without a watchdog
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog_interval
+    }
+
+    /// Report the service ready; only the first call sends
+    pub fn ready(&self) -> Result<()> {
+        if self.ready_sent.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.send("READY=1")
+    }
+
+    /// Send a watchdog heartbeat (no-op without a watchdog)
+    pub fn watchdog(&self) -> Result<()> {
+        if self.watchdog_interval.is_none() {
+            return Ok(());
+        }
+        self.send("WATCHDOG=1")
+    }
+
+    fn send(&self, state: &str) -> Result<()> {
+        let socket = UnixDatagram::unbound().context("failed to create notify socket")?;
+        let sent = match self.socket.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name)
+                .and_then(|addr| socket.send_to_addr(state.as_bytes(), &addr)),
+            None => socket.send_to(state.as_bytes(), &self.socket),
+        };
+        sent.with_context(|| format!("failed to notify systemd via {}", self.socket))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ready_once_and_watchdog() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+        server.set_nonblocking(true).unwrap();
+        let notify = SystemdNotify::new(path.to_str().unwrap(), Some(20_000_000));
+        assert_eq!(notify.watchdog_interval(), Some(Duration::from_secs(10)));
+
+        notify.ready().unwrap();
+        notify.ready().unwrap();
+        notify.watchdog().unwrap();
+        let mut buf = [0u8; 64];
+        let mut received = Vec::new();
+        while let Ok(len) = server.recv(&mut buf) {
+            received.push(String::from_utf8_lossy(&buf[..len]).to_string());
+        }
+        assert_eq!(received, vec!["READY=1", "WATCHDOG=1"]);
+
+        let notify = SystemdNotify::new(path.to_str().unwrap(), None);
+        notify.watchdog().unwrap();
+        assert!(server.recv(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Golden tests of the cAdvisor conversion on shim payloads
+//!
+//! Every payload in `tests/fixtures/shim/` is parsed, converted and rendered,
+//! and the output is compared with `tests/fixtures/cadvisor/<name>.prom`:
+//!
+//! - `clh.prom` - Go runtime with Cloud Hypervisor
+//! - `qemu.prom` - Go runtime with QEMU (two disks, two guest interfaces)
+//! - `runtime-rs.prom` - Rust runtime with Dragonball (no Go, hypervisor or
+//!   virtiofsd process metrics)
+//!
+//! After an intended mapping change, regenerate the golden files and review
+//! their diff like any other change:
+//!
+//! ```sh
+//! UPDATE_GOLDEN=1 cargo test --test conversion_golden
+//! ```
+//!
+//! To cover a payload captured on a node, save it as a new fixture and
+//! generate its golden file the same way:
+//!
+//! ```sh
+//! kata-pulse scrape <sandbox-id> --raw > tests/fixtures/shim/<name>.prom
+//! ```
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use kata_pulse::utils::metrics_converter::cadvisor::PrometheusFormat;
+use kata_pulse::utils::metrics_converter::config::{ConversionConfig, EnrichedLabels};
+use kata_pulse::utils::metrics_converter::create_converter;
+use kata_pulse::utils::prometheus_parser::PrometheusMetrics;
+
+const SANDBOX_ID: &str = "0b5d8e3c7f1a4b6e9d2c5f8a1e4b7d0c3f6a9e2d5b8c1f4a7e0d3b6c9f2a5e8d";
+
+/// Number of differing lines shown per fixture
+const MAX_DIFF_LINES: usize = 20;
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// Names of the files with the `.prom` extension in `dir`, sorted
+fn prom_files(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("{}: {}", dir.display(), e))
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "prom"))
+        .map(|path| path.file_stem().unwrap().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// The conversion settings of the golden files
+///
+/// Every per-CPU/interface/device breakdown is enabled so that the golden
+/// files cover them, and CLK_TCK is pinned so they do not depend on the host.
+fn golden_config() -> ConversionConfig {
+    ConversionConfig {
+        include_per_cpu: true,
+        include_per_interface: true,
+        include_per_device: true,
+        cpu_jiffy_conversion_factor: 100.0,
+        ..ConversionConfig::default()
+    }
+}
+
+/// Convert a shim payload and render it in cAdvisor format
+fn convert(name: &str, payload: &str) -> String {
+    let (metrics, errors) = PrometheusMetrics::parse_with_diagnostics(payload);
+    assert!(errors.is_empty(), "{}: {:?}", name, errors);
+    let converter = create_converter(
+        golden_config(),
+        EnrichedLabels::new(
+            "0f6c9a6e-3b1d-4c59-9d7e-2a8f1e4b7c30",
+            "checkout-service-7d9f8b6c5d-x2x9k",
+            "production-payments",
+        ),
+    );
+    let converted = converter
+        .convert_all(&metrics)
+        .unwrap_or_else(|e| panic!("{}: {:#}", name, e));
+    canonical(&converted.to_prometheus_format(Some(SANDBOX_ID)))
+}
+
+/// Sort the samples of each metric family
+///
+/// Labelled samples are rendered in hash map order, which changes between
+/// runs; the `# HELP` and `# TYPE` lines and the family order are kept.
+fn canonical(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut samples: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        if line.starts_with('#') {
+            if !samples.is_empty() {
+                samples.sort_unstable();
+                output.extend(samples.drain(..).map(|s| format!("{}\n", s)));
+            }
+            output.push_str(line);
+            output.push('\n');
+        } else if !line.is_empty() {
+            samples.push(line);
+        }
+    }
+    samples.sort_unstable();
+    output.extend(samples.drain(..).map(|s| format!("{}\n", s)));
+    output
+}
+
+/// The lines only in `expected` (`-`) and only in `actual` (`+`)
+fn diff(expected: &str, actual: &str) -> Vec<String> {
+    let expected_lines: BTreeSet<&str> = expected.lines().collect();
+    let actual_lines: BTreeSet<&str> = actual.lines().collect();
+    let missing = expected
+        .lines()
+        .filter(|line| !actual_lines.contains(line))
+        .map(|line| format!("- {}", line));
+    let unexpected = actual
+        .lines()
+        .filter(|line| !expected_lines.contains(line))
+        .map(|line| format!("+ {}", line));
+    missing.chain(unexpected).collect()
+}
+
+#[test]
+fn test_conversions_match_golden_files() {
+    let dir = fixtures_dir();
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let fixtures = prom_files(&dir.join("shim"));
+    assert!(!fixtures.is_empty(), "no fixtures in tests/fixtures/shim");
+
+    let mut failures = Vec::new();
+    for name in &fixtures {
+        let payload =
+            std::fs::read_to_string(dir.join("shim").join(format!("{}.prom", name))).unwrap();
+        let actual = convert(name, &payload);
+        let golden_path = dir.join("cadvisor").join(format!("{}.prom", name));
+        if update {
+            std::fs::write(&golden_path, &actual).unwrap();
+            continue;
+        }
+
+        let expected = std::fs::read_to_string(&golden_path).unwrap_or_default();
+        if expected != actual {
+            let lines = diff(&expected, &actual);
+            let shown = lines.len().min(MAX_DIFF_LINES);
+            failures.push(format!(
+                "{} ({} differing lines):\n{}",
+                golden_path.display(),
+                lines.len(),
+                lines[..shown].join("\n")
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "conversions differ from the golden files (rerun with UPDATE_GOLDEN=1 if intended)\n\n{}",
+        failures.join("\n\n")
+    );
+}
+
+#[test]
+fn test_every_golden_file_has_a_fixture() {
+    let dir = fixtures_dir();
+    let fixtures = prom_files(&dir.join("shim"));
+    let orphans: Vec<String> = prom_files(&dir.join("cadvisor"))
+        .into_iter()
+        .filter(|name| !fixtures.contains(name))
+        .collect();
+    assert!(
+        orphans.is_empty(),
+        "golden files without a fixture: {:?}",
+        orphans
+    );
+}
+
+#[test]
+fn test_canonical_sorts_samples_within_families() {
+    let text = "# HELP a A\n# TYPE a gauge\na{x=\"2\"} 2\na{x=\"1\"} 1\n\
+                # HELP b B\n# TYPE b gauge\nb 1\n";
+    assert_eq!(
+        canonical(text),
+        "# HELP a A\n# TYPE a gauge\na{x=\"1\"} 1\na{x=\"2\"} 2\n\
+         # HELP b B\n# TYPE b gauge\nb 1\n"
+    );
+    assert_eq!(canonical(text), canonical(&canonical(text)));
+}
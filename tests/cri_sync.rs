@@ -0,0 +1,260 @@
+//! End-to-end tests of the CRI metadata sync against a fake CRI runtime
+
+mod support;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use kata_pulse::monitor::cri::{self, CRIClientConfig};
+use kata_pulse::monitor::metrics_cache::MetricsCache;
+use kata_pulse::monitor::pipeline_health::PipelineHealth;
+use kata_pulse::monitor::sandbox_cache::{MetadataStatus, SandboxCRIMetadata, SandboxCache};
+use kata_pulse::monitor::sandbox_cache_manager::SandboxCacheManager;
+use support::fake_cri::{kata_pod, FakeCri};
+use support::sandboxes_dir;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio_util::sync::CancellationToken;
+
+/// The CRI client of the last answering endpoint is process-wide, so the
+/// tests of this binary run one at a time, each starting without one
+async fn exclusive() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::const_new(());
+    let guard = LOCK.lock().await;
+    cri::set_cri_client(None);
+    guard
+}
+
+fn client_config() -> CRIClientConfig {
+    CRIClientConfig::default()
+        .with_timeout(Duration::from_secs(2))
+        .with_max_retries(0)
+}
+
+fn endpoints(dir: &Path) -> Vec<String> {
+    vec![dir.join("cri.sock").display().to_string()]
+}
+
+async fn status(cache: &SandboxCache, id: &str) -> MetadataStatus {
+    cache.get_metadata(id).await.unwrap().metadata_status
+}
+
+#[tokio::test]
+async fn test_sync_matches_known_sandboxes() {
+    let _guard = exclusive().await;
+    let dir = tempfile::tempdir().unwrap();
+    let fake = FakeCri::spawn(&dir.path().join("cri.sock"));
+    fake.add_pod(kata_pod("sb-a", "pod-a", "tenant-a"));
+    fake.add_pod(kata_pod("sb-unknown", "pod-unknown", "tenant-a"));
+
+    let cache = SandboxCache::new();
+    for id in ["sb-a", "sb-b"] {
+        cache
+            .put_if_not_exists(id, SandboxCRIMetadata::default())
+            .await;
+    }
+    let remaining = cri::sync_sandboxes(
+        &endpoints(dir.path()),
+        &client_config(),
+        &cache,
+        vec!["sb-a".to_string(), "sb-b".to_string()],
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(remaining, vec!["sb-b".to_string()]);
+    let metadata = cache.get_metadata("sb-a").await.unwrap();
+    assert_eq!(metadata.name, "pod-a");
+    assert_eq!(metadata.namespace, "tenant-a");
+    assert_eq!(metadata.uid, "uid-sb-a");
+    assert_eq!(metadata.runtime_handler, "kata");
+    assert_eq!(metadata.metadata_status, MetadataStatus::Synced);
+    assert_eq!(status(&cache, "sb-b").await, MetadataStatus::Pending);
+    assert!(cache.get_metadata("sb-unknown").await.is_none());
+    assert_eq!(fake.list_pod_sandbox_calls(), 1);
+}
+
+#[tokio::test]
+async fn test_metadata_arriving_late() {
+    let _guard = exclusive().await;
+    let dir = tempfile::tempdir().unwrap();
+    let fake = FakeCri::spawn(&dir.path().join("cri.sock"));
+    let cache = SandboxCache::new();
+    cache
+        .put_if_not_exists("sb-late", SandboxCRIMetadata::default())
+        .await;
+    let sandboxes = vec!["sb-late".to_string()];
+
+    // The runtime does not know the pod yet; the retry budget runs out
+    let remaining = cri::sync_sandboxes(
+        &endpoints(dir.path()),
+        &client_config(),
+        &cache,
+        sandboxes.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(remaining, sandboxes);
+    assert!(cache.record_metadata_miss("sb-late", 1).await);
+    assert_eq!(
+        status(&cache, "sb-late").await,
+        MetadataStatus::MetadataUnavailable
+    );
+
+    // A later sync still picks the pod up
+    fake.add_pod(kata_pod("sb-late", "pod-late", "default"));
+    let remaining =
+        cri::sync_sandboxes(&endpoints(dir.path()), &client_config(), &cache, sandboxes)
+            .await
+            .unwrap();
+    assert!(remaining.is_empty());
+    assert_eq!(status(&cache, "sb-late").await, MetadataStatus::Synced);
+    assert_eq!(cache.metadata_misses("sb-late").await, 0);
+}
+
+#[tokio::test]
+async fn test_disappearing_pod_keeps_metadata() {
+    let _guard = exclusive().await;
+    let dir = tempfile::tempdir().unwrap();
+    let fake = FakeCri::spawn(&dir.path().join("cri.sock"));
+    fake.add_pod(kata_pod("sb-gone", "pod-gone", "default"));
+    let cache = SandboxCache::new();
+    cache
+        .put_if_not_exists("sb-gone", SandboxCRIMetadata::default())
+        .await;
+    let sandboxes = vec!["sb-gone".to_string()];
+
+    cri::sync_sandboxes(
+        &endpoints(dir.path()),
+        &client_config(),
+        &cache,
+        sandboxes.clone(),
+    )
+    .await
+    .unwrap();
+
+    // The pod is deleted before its sandbox directory: the directory stays
+    // the source of truth and the last known metadata is kept
+    fake.remove_pod("sb-gone");
+    let remaining =
+        cri::sync_sandboxes(&endpoints(dir.path()), &client_config(), &cache, sandboxes)
+            .await
+            .unwrap();
+    assert_eq!(remaining, vec!["sb-gone".to_string()]);
+    assert!(!cache.record_metadata_miss("sb-gone", 1).await);
+    let metadata = cache.get_metadata("sb-gone").await.unwrap();
+    assert_eq!(metadata.name, "pod-gone");
+    assert_eq!(metadata.metadata_status, MetadataStatus::Synced);
+}
+
+#[tokio::test]
+async fn test_restarting_runtime() {
+    let _guard = exclusive().await;
+    let dir = tempfile::tempdir().unwrap();
+    let fake = FakeCri::spawn(&dir.path().join("cri.sock"));
+    fake.add_pod(kata_pod("sb-r", "pod-r", "default"));
+    let cache = SandboxCache::new();
+    cache
+        .put_if_not_exists("sb-r", SandboxCRIMetadata::default())
+        .await;
+
+    fake.set_unavailable(true);
+    let result = cri::sync_sandboxes(
+        &endpoints(dir.path()),
+        &client_config(),
+        &cache,
+        vec!["sb-r".to_string()],
+    )
+    .await;
+    assert!(result.is_err());
+    assert_eq!(status(&cache, "sb-r").await, MetadataStatus::Pending);
+
+    fake.set_unavailable(false);
+    let remaining = cri::sync_sandboxes(
+        &endpoints(dir.path()),
+        &client_config(),
+        &cache,
+        vec!["sb-r".to_string()],
+    )
+    .await
+    .unwrap();
+    assert!(remaining.is_empty());
+    assert_eq!(
+        cri::active_endpoint(),
+        Some(endpoints(dir.path())[0].clone())
+    );
+}
+
+/// Create a Kata sandbox directory under the shared sandbox directory
+fn create_sandbox_dir(id: &str) {
+    let path = sandboxes_dir().join(id);
+    std::fs::create_dir_all(&path).unwrap();
+    std::fs::write(path.join("persist.json"), "{}").unwrap();
+}
+
+/// Wait until `condition` holds for the cache, checking every 100ms
+async fn wait_until<F, Fut>(what: &str, condition: F)
+where
+    F: Fn() -> Fut,
+    Fut: std::future::Future<Output = bool>,
+{
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(30);
+    while !condition().await {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "timed out waiting until {}",
+            what
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_cache_manager_follows_runtime_and_directory() {
+    let _guard = exclusive().await;
+    let dir = tempfile::tempdir().unwrap();
+    let fake = FakeCri::spawn(&dir.path().join("cri.sock"));
+    fake.add_pod(kata_pod("mgr-ready", "pod-ready", "default"));
+    create_sandbox_dir("mgr-ready");
+    create_sandbox_dir("mgr-late");
+
+    let cache = Arc::new(SandboxCache::new());
+    let manager = SandboxCacheManager::new(
+        cache.clone(),
+        Arc::new(MetricsCache::new()),
+        Arc::new(PipelineHealth::new()),
+        endpoints(dir.path()),
+    )
+    .with_cri_client_config(client_config())
+    .with_metadata_retry_budget(1);
+    let cancel = CancellationToken::new();
+    let token = cancel.clone();
+    let task = tokio::spawn(async move { manager.start(token).await });
+
+    // First sync: one pod is known, the other one runs out of retries
+    wait_until("mgr-late is marked metadata_unavailable", || async {
+        cache
+            .get_metadata("mgr-late")
+            .await
+            .is_some_and(|m| m.metadata_status == MetadataStatus::MetadataUnavailable)
+    })
+    .await;
+    assert_eq!(status(&cache, "mgr-ready").await, MetadataStatus::Synced);
+
+    // The late pod shows up in the runtime
+    fake.add_pod(kata_pod("mgr-late", "pod-late", "default"));
+    wait_until("mgr-late is synced", || async {
+        status(&cache, "mgr-late").await == MetadataStatus::Synced
+    })
+    .await;
+
+    // The sandbox directory goes away
+    std::fs::remove_dir_all(sandboxes_dir().join("mgr-ready")).unwrap();
+    wait_until("mgr-ready is dropped", || async {
+        cache.get_metadata("mgr-ready").await.is_none()
+    })
+    .await;
+
+    cancel.cancel();
+    task.await.unwrap().unwrap();
+}
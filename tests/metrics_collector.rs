@@ -0,0 +1,155 @@
+//! End-to-end tests of the metrics collector scraping mock shim monitor sockets
+
+mod support;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use kata_pulse::config::{self, reload::ReloadableConfig, reload::SharedConfig};
+use kata_pulse::monitor::metrics_cache::MetricsCache;
+use kata_pulse::monitor::metrics_collector::MetricsCollector;
+use kata_pulse::monitor::pipeline_health::PipelineHealth;
+use kata_pulse::monitor::sandbox_cache::{SandboxCRIMetadata, SandboxCache};
+use kata_pulse::monitor::scrape_health::{CircuitBreakerConfig, ScrapeHealth, ScrapeStatus};
+use kata_pulse::utils::shim_client::{ShimClient, ShimClientConfig};
+use support::mock_shim::{MockShim, Reply};
+use support::sandboxes_dir;
+use tokio_util::sync::CancellationToken;
+
+/// A mock shim serving the sandbox `id` at its Go runtime socket path
+fn spawn_sandbox_shim(id: &str, default: Reply) -> MockShim {
+    let socket = config::socket_path(id, sandboxes_dir());
+    MockShim::spawn(&socket, default)
+}
+
+/// A collector running every second over a single tracked sandbox
+struct Harness {
+    metrics_cache: Arc<MetricsCache>,
+    scrape_health: Arc<ScrapeHealth>,
+    cancel: CancellationToken,
+}
+
+impl Harness {
+    async fn start(id: &str, shim_client: ShimClientConfig, breaker: CircuitBreakerConfig) -> Self {
+        sandboxes_dir();
+        let sandbox_cache = Arc::new(SandboxCache::new());
+        sandbox_cache
+            .put_if_not_exists(id, SandboxCRIMetadata::default())
+            .await;
+        let metrics_cache = Arc::new(MetricsCache::new());
+        let scrape_health = Arc::new(ScrapeHealth::new(breaker));
+        let collector = MetricsCollector::new(
+            sandbox_cache,
+            metrics_cache.clone(),
+            scrape_health.clone(),
+            Arc::new(ShimClient::new(shim_client)),
+            Arc::new(PipelineHealth::new()),
+            Arc::new(SharedConfig::new(ReloadableConfig {
+                metrics_interval_secs: 1,
+                sandbox_filter: Default::default(),
+                name_filter: Default::default(),
+                conversion: Default::default(),
+            })),
+        );
+
+        let cancel = CancellationToken::new();
+        let token = cancel.clone();
+        tokio::spawn(async move { collector.start(token).await });
+
+        Harness {
+            metrics_cache,
+            scrape_health,
+            cancel,
+        }
+    }
+
+    /// Wait until the scrape status of `id` satisfies `condition`
+    async fn wait_for(&self, id: &str, condition: impl Fn(&ScrapeStatus) -> bool) -> ScrapeStatus {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(15);
+        loop {
+            if let Some(status) = self.scrape_health.get(id).await {
+                if condition(&status) {
+                    return status;
+                }
+            }
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "timed out waiting for the scrape status of {}",
+                id
+            );
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        self.cancel.cancel();
+    }
+}
+
+#[tokio::test]
+async fn test_collects_metrics_from_shim() {
+    let id = "it-collect";
+    let shim = spawn_sandbox_shim(id, Reply::kata_metrics());
+    let harness = Harness::start(id, Default::default(), Default::default()).await;
+
+    harness.wait_for(id, |status| status.up).await;
+    let cached = harness.metrics_cache.get_metrics(id).await.unwrap();
+    assert!(cached.metrics.metrics.contains_key("kata_agent_io_stat"));
+    assert!(shim.requests() >= 1);
+}
+
+#[tokio::test]
+async fn test_gzip_metrics_are_collected() {
+    let id = "it-gzip";
+    let _shim = spawn_sandbox_shim(id, Reply::Gzip(support::mock_shim::KATA_METRICS.into()));
+    let harness = Harness::start(id, Default::default(), Default::default()).await;
+
+    harness.wait_for(id, |status| status.up).await;
+    assert!(harness.metrics_cache.get_metrics(id).await.is_some());
+}
+
+#[tokio::test]
+async fn test_slow_shim_fails_the_scrape() {
+    let id = "it-slow";
+    let _shim = spawn_sandbox_shim(
+        id,
+        Reply::Slow(Duration::from_secs(10), Box::new(Reply::kata_metrics())),
+    );
+    let shim_client = ShimClientConfig {
+        timeout: Duration::from_millis(200),
+        ..Default::default()
+    };
+    let harness = Harness::start(id, shim_client, Default::default()).await;
+
+    let status = harness
+        .wait_for(id, |status| status.consecutive_failures > 0)
+        .await;
+    assert!(!status.up);
+    assert!(status.last_error.unwrap().contains("timed out"));
+    assert!(harness.metrics_cache.get_metrics(id).await.is_none());
+}
+
+#[tokio::test]
+async fn test_failing_shim_is_quarantined_and_recovers() {
+    let id = "it-quarantine";
+    let shim = spawn_sandbox_shim(id, Reply::kata_metrics());
+    shim.push(Reply::Status(500));
+    shim.push(Reply::Status(503));
+    let breaker = CircuitBreakerConfig {
+        failure_threshold: 2,
+        max_skip_cycles: 1,
+    };
+    let harness = Harness::start(id, Default::default(), breaker).await;
+
+    let status = harness.wait_for(id, |status| status.quarantined).await;
+    assert_eq!(status.consecutive_failures, 2);
+
+    // The probe after the backoff gets the default reply again
+    let status = harness
+        .wait_for(id, |status| status.up && !status.quarantined)
+        .await;
+    assert_eq!(status.consecutive_failures, 0);
+    assert!(harness.metrics_cache.get_metrics(id).await.is_some());
+}
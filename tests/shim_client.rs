@@ -0,0 +1,111 @@
+//! End-to-end tests of the shim client against a mock shim monitor socket
+
+mod support;
+
+use std::time::Duration;
+
+use kata_pulse::utils::shim_client::{PayloadTooLarge, ShimClient, ShimClientConfig};
+use support::mock_shim::{MockShim, Reply, KATA_METRICS};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn test_fetches_kata_metrics() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("shim-monitor.sock");
+    let shim = MockShim::spawn(&socket, Reply::kata_metrics());
+    let client = ShimClient::new(ShimClientConfig::default());
+
+    for _ in 0..3 {
+        let body = client
+            .get("sb-1", &socket, "/metrics", TIMEOUT)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], KATA_METRICS.as_bytes());
+    }
+    assert_eq!(shim.requests(), 3);
+}
+
+#[tokio::test]
+async fn test_decodes_gzip_metrics() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("shim-monitor.sock");
+    let _shim = MockShim::spawn(&socket, Reply::Gzip(KATA_METRICS.to_string()));
+    let client = ShimClient::new(ShimClientConfig::default());
+
+    let body = client
+        .get("sb-1", &socket, "/metrics", TIMEOUT)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], KATA_METRICS.as_bytes());
+}
+
+#[tokio::test]
+async fn test_slow_shim_times_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("shim-monitor.sock");
+    let _shim = MockShim::spawn(
+        &socket,
+        Reply::Slow(Duration::from_secs(10), Box::new(Reply::kata_metrics())),
+    );
+    let client = ShimClient::new(ShimClientConfig::default());
+
+    let err = client
+        .get("sb-1", &socket, "/metrics", Duration::from_millis(200))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("timed out"), "{:#}", err);
+}
+
+#[tokio::test]
+async fn test_truncated_response_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("shim-monitor.sock");
+    let shim = MockShim::spawn(&socket, Reply::kata_metrics());
+    shim.push(Reply::Truncated(KATA_METRICS.to_string()));
+    let client = ShimClient::new(ShimClientConfig::default());
+
+    assert!(client
+        .get("sb-1", &socket, "/metrics", TIMEOUT)
+        .await
+        .is_err());
+    // The broken connection is not reused
+    let body = client
+        .get("sb-1", &socket, "/metrics", TIMEOUT)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], KATA_METRICS.as_bytes());
+}
+
+#[tokio::test]
+async fn test_server_error_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("shim-monitor.sock");
+    let shim = MockShim::spawn(&socket, Reply::kata_metrics());
+    shim.push(Reply::Status(500));
+    let client = ShimClient::new(ShimClientConfig::default());
+
+    let err = client
+        .get("sb-1", &socket, "/metrics", TIMEOUT)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("500"), "{:#}", err);
+    assert!(client
+        .get("sb-1", &socket, "/metrics", TIMEOUT)
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn test_oversized_gzip_payload_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let socket = dir.path().join("shim-monitor.sock");
+    let _shim = MockShim::spawn(&socket, Reply::Gzip(KATA_METRICS.to_string()));
+    let client = ShimClient::new(ShimClientConfig::default().with_max_payload_bytes(1024));
+
+    let err = client
+        .get("sb-1", &socket, "/metrics", TIMEOUT)
+        .await
+        .unwrap_err();
+    assert!(err.chain().any(|cause| cause.is::<PayloadTooLarge>()));
+}
@@ -0,0 +1,276 @@
+//! Fake CRI - an in-process CRI v1 runtime service for integration tests
+//!
+//! Responsibilities:
+//! - Serve `RuntimeService` on a Unix socket like containerd or CRI-O
+//! - Answer Version, ListPodSandbox, PodSandboxStatus and ListContainers from
+//!   pods and containers the test adds and removes while it runs
+//! - Count ListPodSandbox calls and fail them on demand
+//!
+//! Every other RPC answers `Unimplemented`.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use k8s_cri::v1::runtime_service_server::{RuntimeService, RuntimeServiceServer};
+use k8s_cri::v1::*;
+use tokio::net::UnixListener;
+use tokio::task::JoinHandle;
+use tonic::codegen::tokio_stream::wrappers::UnixListenerStream;
+use tonic::{Request, Response, Status};
+
+/// Pods and containers served by the fake runtime
+#[derive(Default)]
+struct State {
+    pods: Mutex<Vec<PodSandbox>>,
+    containers: Mutex<Vec<Container>>,
+    list_pod_sandbox_calls: AtomicUsize,
+    unavailable: AtomicBool,
+}
+
+/// A fake CRI runtime listening on a Unix socket until dropped
+pub struct FakeCri {
+    state: Arc<State>,
+    task: JoinHandle<()>,
+}
+
+impl FakeCri {
+    /// Serve the runtime service on `socket_path`
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn spawn(socket_path: &Path) -> Self {
+        let listener = UnixListener::bind(socket_path).unwrap();
+        let state = Arc::new(State::default());
+        let service = RuntimeServiceServer::new(FakeRuntime {
+            state: state.clone(),
+        });
+        let task = tokio::spawn(async move {
+            let _ = tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming(UnixListenerStream::new(listener))
+                .await;
+        });
+        FakeCri { state, task }
+    }
+
+    /// Report a pod sandbox from now on (replacing one with the same ID)
+    pub fn add_pod(&self, pod: PodSandbox) {
+        let mut pods = self.state.pods.lock().unwrap();
+        pods.retain(|p| p.id != pod.id);
+        pods.push(pod);
+    }
+
+    /// Stop reporting a pod sandbox and its containers
+    pub fn remove_pod(&self, id: &str) {
+        self.state.pods.lock().unwrap().retain(|p| p.id != id);
+        self.state
+            .containers
+            .lock()
+            .unwrap()
+            .retain(|c| c.pod_sandbox_id != id);
+    }
+
+    /// Report a container from now on
+    pub fn add_container(&self, container: Container) {
+        self.state.containers.lock().unwrap().push(container);
+    }
+
+    /// Fail ListPodSandbox with `Unavailable` (a runtime that is restarting)
+    pub fn set_unavailable(&self, unavailable: bool) {
+        self.state.unavailable.store(unavailable, Ordering::SeqCst);
+    }
+
+    /// Number of ListPodSandbox calls received so far
+    pub fn list_pod_sandbox_calls(&self) -> usize {
+        self.state.list_pod_sandbox_calls.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for FakeCri {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// A ready pod sandbox created by the `kata` runtime handler
+pub fn kata_pod(id: &str, name: &str, namespace: &str) -> PodSandbox {
+    PodSandbox {
+        id: id.to_string(),
+        metadata: Some(PodSandboxMetadata {
+            name: name.to_string(),
+            uid: format!("uid-{}", id),
+            namespace: namespace.to_string(),
+            attempt: 0,
+        }),
+        state: PodSandboxState::SandboxReady as i32,
+        runtime_handler: "kata".to_string(),
+        ..Default::default()
+    }
+}
+
+/// A running container of a pod sandbox
+pub fn container(id: &str, pod_sandbox_id: &str, name: &str) -> Container {
+    Container {
+        id: id.to_string(),
+        pod_sandbox_id: pod_sandbox_id.to_string(),
+        metadata: Some(ContainerMetadata {
+            name: name.to_string(),
+            attempt: 0,
+        }),
+        state: ContainerState::ContainerRunning as i32,
+        ..Default::default()
+    }
+}
+
+/// Whether `labels` contains every pair of `selector`
+fn matches_labels(labels: &HashMap<String, String>, selector: &HashMap<String, String>) -> bool {
+    selector
+        .iter()
+        .all(|(key, value)| labels.get(key) == Some(value))
+}
+
+struct FakeRuntime {
+    state: Arc<State>,
+}
+
+/// Implement `RuntimeService` with the given RPCs, answering `Unimplemented` to the rest
+///
+/// The impl is generated as a whole so that `async_trait` sees every method.
+macro_rules! runtime_service {
+    (
+        implemented { $($implemented:tt)* }
+        unimplemented { $($name:ident($request:ty) -> $response:ty;)* }
+    ) => {
+        #[tonic::async_trait]
+        impl RuntimeService for FakeRuntime {
+            $($implemented)*
+
+            $(
+                async fn $name(&self, _: Request<$request>) -> Result<Response<$response>, Status> {
+                    Err(Status::unimplemented(stringify!($name)))
+                }
+            )*
+        }
+    };
+}
+
+runtime_service! {
+    implemented {
+        type GetContainerEventsStream = tonic::codegen::tokio_stream::Empty<Result<ContainerEventResponse, Status>>;
+
+        async fn version(&self, _: Request<VersionRequest>) -> Result<Response<VersionResponse>, Status> {
+            Ok(Response::new(VersionResponse {
+                version: "0.1.0".to_string(),
+                runtime_name: "fake-cri".to_string(),
+                runtime_version: "1.0.0".to_string(),
+                runtime_api_version: "v1".to_string(),
+            }))
+        }
+
+        async fn list_pod_sandbox(
+            &self,
+            request: Request<ListPodSandboxRequest>,
+        ) -> Result<Response<ListPodSandboxResponse>, Status> {
+            self.state
+                .list_pod_sandbox_calls
+                .fetch_add(1, Ordering::SeqCst);
+            if self.state.unavailable.load(Ordering::SeqCst) {
+                return Err(Status::unavailable("runtime is restarting"));
+            }
+            let filter = request.into_inner().filter.unwrap_or_default();
+            let items = self
+                .state
+                .pods
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|pod| filter.id.is_empty() || pod.id == filter.id)
+                .filter(|pod| filter.state.as_ref().is_none_or(|s| s.state == pod.state))
+                .filter(|pod| matches_labels(&pod.labels, &filter.label_selector))
+                .cloned()
+                .collect();
+            Ok(Response::new(ListPodSandboxResponse { items }))
+        }
+
+        async fn pod_sandbox_status(
+            &self,
+            request: Request<PodSandboxStatusRequest>,
+        ) -> Result<Response<PodSandboxStatusResponse>, Status> {
+            let id = request.into_inner().pod_sandbox_id;
+            let pods = self.state.pods.lock().unwrap();
+            let pod = pods
+                .iter()
+                .find(|pod| pod.id == id)
+                .ok_or_else(|| Status::not_found(format!("pod sandbox {} not found", id)))?;
+            Ok(Response::new(PodSandboxStatusResponse {
+                status: Some(PodSandboxStatus {
+                    id: pod.id.clone(),
+                    metadata: pod.metadata.clone(),
+                    state: pod.state,
+                    created_at: pod.created_at,
+                    labels: pod.labels.clone(),
+                    annotations: pod.annotations.clone(),
+                    runtime_handler: pod.runtime_handler.clone(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }))
+        }
+
+        async fn list_containers(
+            &self,
+            request: Request<ListContainersRequest>,
+        ) -> Result<Response<ListContainersResponse>, Status> {
+            let filter = request.into_inner().filter.unwrap_or_default();
+            let containers = self
+                .state
+                .containers
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|c| filter.id.is_empty() || c.id == filter.id)
+                .filter(|c| filter.pod_sandbox_id.is_empty() || c.pod_sandbox_id == filter.pod_sandbox_id)
+                .filter(|c| filter.state.as_ref().is_none_or(|s| s.state == c.state))
+                .filter(|c| matches_labels(&c.labels, &filter.label_selector))
+                .cloned()
+                .collect();
+            Ok(Response::new(ListContainersResponse { containers }))
+        }
+
+        async fn get_container_events(
+            &self,
+            _: Request<GetEventsRequest>,
+        ) -> Result<Response<Self::GetContainerEventsStream>, Status> {
+            Err(Status::unimplemented("get_container_events"))
+        }
+
+    }
+
+    unimplemented {
+        run_pod_sandbox(RunPodSandboxRequest) -> RunPodSandboxResponse;
+        stop_pod_sandbox(StopPodSandboxRequest) -> StopPodSandboxResponse;
+        remove_pod_sandbox(RemovePodSandboxRequest) -> RemovePodSandboxResponse;
+        create_container(CreateContainerRequest) -> CreateContainerResponse;
+        start_container(StartContainerRequest) -> StartContainerResponse;
+        stop_container(StopContainerRequest) -> StopContainerResponse;
+        remove_container(RemoveContainerRequest) -> RemoveContainerResponse;
+        container_status(ContainerStatusRequest) -> ContainerStatusResponse;
+        update_container_resources(UpdateContainerResourcesRequest) -> UpdateContainerResourcesResponse;
+        reopen_container_log(ReopenContainerLogRequest) -> ReopenContainerLogResponse;
+        exec_sync(ExecSyncRequest) -> ExecSyncResponse;
+        exec(ExecRequest) -> ExecResponse;
+        attach(AttachRequest) -> AttachResponse;
+        port_forward(PortForwardRequest) -> PortForwardResponse;
+        container_stats(ContainerStatsRequest) -> ContainerStatsResponse;
+        list_container_stats(ListContainerStatsRequest) -> ListContainerStatsResponse;
+        pod_sandbox_stats(PodSandboxStatsRequest) -> PodSandboxStatsResponse;
+        list_pod_sandbox_stats(ListPodSandboxStatsRequest) -> ListPodSandboxStatsResponse;
+        update_runtime_config(UpdateRuntimeConfigRequest) -> UpdateRuntimeConfigResponse;
+        status(StatusRequest) -> StatusResponse;
+        checkpoint_container(CheckpointContainerRequest) -> CheckpointContainerResponse;
+        list_metric_descriptors(ListMetricDescriptorsRequest) -> ListMetricDescriptorsResponse;
+        list_pod_sandbox_metrics(ListPodSandboxMetricsRequest) -> ListPodSandboxMetricsResponse;
+        runtime_config(RuntimeConfigRequest) -> RuntimeConfigResponse;
+    }
+}
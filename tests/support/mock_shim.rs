@@ -0,0 +1,188 @@
+//! Mock shim - a fake shim monitor socket for integration tests
+//!
+//! Responsibilities:
+//! - Listen on a Unix socket and answer the HTTP/1.1 requests of the shim
+//!   client like the `shim-monitor.sock` of a Kata shim
+//! - Serve canned kata metric payloads, plain or gzip-encoded
+//! - Misbehave on demand: slow, truncated and error responses
+//!
+//! Replies queued with `push` are served first, one per request; the default
+//! reply answers every request after that.
+
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::task::JoinHandle;
+
+/// Metrics of a small Kata sandbox, as served by a shim
+pub const KATA_METRICS: &str = include_str!("../fixtures/shim/clh.prom");
+
+/// How the mock shim answers a request
+#[derive(Clone, Debug)]
+pub enum Reply {
+    /// 200 OK with the body
+    Metrics(String),
+    /// 200 OK with the gzip-encoded body
+    Gzip(String),
+    /// The reply, sent after a delay
+    Slow(Duration, Box<Reply>),
+    /// Announces the full body but sends only its first half, then closes the connection
+    Truncated(String),
+    /// The status code with an empty body
+    Status(u16),
+}
+
+impl Reply {
+    /// 200 OK with the canned Kata metrics
+    pub fn kata_metrics() -> Self {
+        Reply::Metrics(KATA_METRICS.to_string())
+    }
+}
+
+/// A fake shim monitor listening on a Unix socket until dropped
+pub struct MockShim {
+    queued: Arc<Mutex<VecDeque<Reply>>>,
+    requests: Arc<AtomicUsize>,
+    task: JoinHandle<()>,
+}
+
+impl MockShim {
+    /// Listen on `socket_path`, answering with `default` unless a reply is queued
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn spawn(socket_path: &Path, default: Reply) -> Self {
+        if let Some(parent) = socket_path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        let listener = UnixListener::bind(socket_path).unwrap();
+        let queued = Arc::new(Mutex::new(VecDeque::new()));
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let shim_queued = queued.clone();
+        let shim_requests = requests.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let queued = shim_queued.clone();
+                let requests = shim_requests.clone();
+                let default = default.clone();
+                tokio::spawn(async move {
+                    serve_connection(stream, &queued, &requests, &default).await;
+                });
+            }
+        });
+
+        MockShim {
+            queued,
+            requests,
+            task,
+        }
+    }
+
+    /// Answer the next request with `reply` (queued replies are served in order)
+    pub fn push(&self, reply: Reply) {
+        self.queued.lock().unwrap().push_back(reply);
+    }
+
+    /// Number of requests received so far
+    pub fn requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockShim {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Answer the requests of one connection until it is closed
+async fn serve_connection(
+    stream: UnixStream,
+    queued: &Mutex<VecDeque<Reply>>,
+    requests: &AtomicUsize,
+    default: &Reply,
+) {
+    let mut conn = BufReader::new(stream);
+    loop {
+        // Read the request line and headers; the client only sends GETs without a body
+        let mut got_request = false;
+        let mut line = String::new();
+        while conn.read_line(&mut line).await.unwrap_or(0) > 0 {
+            got_request = true;
+            if line == "\r\n" {
+                break;
+            }
+            line.clear();
+        }
+        if !got_request {
+            return;
+        }
+        requests.fetch_add(1, Ordering::SeqCst);
+
+        let reply = queued
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or_else(|| default.clone());
+        if !write_reply(conn.get_mut(), reply).await {
+            return;
+        }
+    }
+}
+
+/// Write a reply, returning whether the connection can be reused
+async fn write_reply(stream: &mut UnixStream, mut reply: Reply) -> bool {
+    while let Reply::Slow(delay, inner) = reply {
+        tokio::time::sleep(delay).await;
+        reply = *inner;
+    }
+
+    let (head, body, keep_alive) = match reply {
+        Reply::Metrics(body) => (ok_head(body.len(), None), body.into_bytes(), true),
+        Reply::Gzip(body) => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(body.as_bytes()).unwrap();
+            let body = encoder.finish().unwrap();
+            (ok_head(body.len(), Some("gzip")), body, true)
+        }
+        Reply::Truncated(body) => {
+            let mut body = body.into_bytes();
+            let head = ok_head(body.len(), None);
+            body.truncate(body.len() / 2);
+            (head, body, false)
+        }
+        Reply::Status(status) => (
+            format!("HTTP/1.1 {} Mock\r\nContent-Length: 0\r\n\r\n", status),
+            Vec::new(),
+            true,
+        ),
+        Reply::Slow(..) => unreachable!("unwrapped above"),
+    };
+
+    if stream.write_all(head.as_bytes()).await.is_err() || stream.write_all(&body).await.is_err() {
+        return false;
+    }
+    if !keep_alive {
+        let _ = stream.shutdown().await;
+    }
+    keep_alive
+}
+
+/// Headers of a 200 OK response with a body of `length` bytes
+fn ok_head(length: usize, encoding: Option<&str>) -> String {
+    let mut head = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n",
+        length
+    );
+    if let Some(encoding) = encoding {
+        head.push_str(&format!("Content-Encoding: {}\r\n", encoding));
+    }
+    head.push_str("\r\n");
+    head
+}
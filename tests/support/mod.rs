@@ -0,0 +1,30 @@
+//! Support code shared by the integration tests
+#![allow(dead_code)]
+
+pub mod fake_cri;
+pub mod mock_shim;
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use kata_pulse::config::{self, StoragePaths};
+
+/// Sandbox directory of the Go runtime, shared by the tests of a binary
+///
+/// The storage paths are process-wide, so every test uses its own sandbox IDs.
+pub fn sandboxes_dir() -> &'static PathBuf {
+    static DIR: OnceLock<(tempfile::TempDir, PathBuf)> = OnceLock::new();
+    &DIR.get_or_init(|| {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = StoragePaths {
+            sandboxes: dir.path().join("sbs"),
+            sandboxes_rust: dir.path().join("kata"),
+            vm: dir.path().join("vm"),
+        };
+        let sandboxes = paths.sandboxes.clone();
+        std::fs::create_dir_all(&sandboxes).unwrap();
+        config::set_storage_paths(paths).unwrap();
+        (dir, sandboxes)
+    })
+    .1
+}